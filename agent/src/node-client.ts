@@ -15,6 +15,8 @@ export class NodeClient {
   private connectReject: ((error: Error) => void) | null = null;
   public nodeId: string = "";
   public version: string = "";
+  /** Deprecation notices from the daemon, e.g. for the socket protocol version. */
+  public warnings: string[] = [];
 
   async connect(socketPath: string): Promise<void> {
     return new Promise((resolve, reject) => {
@@ -45,6 +47,7 @@ export class NodeClient {
       if (msg.type === "hello") {
         this.nodeId = msg.node_id;
         this.version = msg.version;
+        this.warnings = msg.warnings ?? [];
         if (this.connectResolve) {
           this.connectResolve();
           this.connectResolve = null;
@@ -93,6 +96,24 @@ export class NodeClient {
     return [];
   }
 
+  async setAlias(target: string, alias: string | null): Promise<NodeResponse> {
+    return this.request({ type: "set_alias", target, alias });
+  }
+
+  async addToGroup(group: string, target: string): Promise<NodeResponse> {
+    return this.request({ type: "group_add", group, target });
+  }
+
+  async removeFromGroup(group: string, target: string): Promise<NodeResponse> {
+    return this.request({ type: "group_remove", group, target });
+  }
+
+  async getGroups(): Promise<GroupInfo[]> {
+    const resp = await this.request({ type: "groups" });
+    if (resp.type === "ok" && resp.data) return resp.data as GroupInfo[];
+    return [];
+  }
+
   async getFollowing(): Promise<FollowInfo[]> {
     const resp = await this.request({ type: "following" });
     if (resp.type === "ok" && resp.data) return resp.data as FollowInfo[];
@@ -105,8 +126,45 @@ export class NodeClient {
     return [];
   }
 
-  async sendDm(to: string, body: string): Promise<NodeResponse> {
-    return this.request({ type: "send_dm", to, body });
+  async sendDm(
+    to: string,
+    body: string,
+    attachments?: Attachment[],
+    inReplyTo?: string,
+    replyWithinSecs?: number,
+  ): Promise<NodeResponse> {
+    return this.request({
+      type: "send_dm",
+      to,
+      body,
+      attachments,
+      in_reply_to: inReplyTo,
+      reply_within_secs: replyWithinSecs,
+    });
+  }
+
+  async broadcastDm(body: string, mutualOnly = false, group?: string): Promise<NodeResponse> {
+    return this.request({ type: "broadcast_dm", body, mutual_only: mutualOnly, group });
+  }
+
+  async sendTemplate(
+    template: string,
+    vars: Record<string, string> = {},
+    to?: string,
+  ): Promise<NodeResponse> {
+    return this.request({ type: "send_template", template, to, vars });
+  }
+
+  async getPendingReplies(): Promise<PendingReply[]> {
+    const resp = await this.request({ type: "pending_replies" });
+    if (resp.type === "ok" && resp.data) return resp.data as PendingReply[];
+    return [];
+  }
+
+  async getThread(threadId: string): Promise<InboxEntry[]> {
+    const resp = await this.request({ type: "message_thread", thread_id: threadId });
+    if (resp.type === "ok" && resp.data) return resp.data as InboxEntry[];
+    return [];
   }
 
   async postFeed(body: string): Promise<NodeResponse> {
@@ -129,6 +187,27 @@ export class NodeClient {
     return null;
   }
 
+  async ping(to: string, timeoutMs?: number): Promise<NodeResponse> {
+    return this.request({ type: "ping", to, timeout_ms: timeoutMs });
+  }
+
+  async getRelayUsage(sinceMs?: number): Promise<RelayUsage[]> {
+    const resp = await this.request({ type: "relay_usage", since_ms: sinceMs });
+    if (resp.type === "ok" && resp.data) return resp.data as RelayUsage[];
+    return [];
+  }
+
+  async getDaemonLog(afterSeq?: number, limit?: number): Promise<DaemonLogEntry[]> {
+    const resp = await this.request({ type: "daemon_log", after_seq: afterSeq, limit });
+    if (resp.type === "ok" && resp.data) return resp.data as DaemonLogEntry[];
+    return [];
+  }
+
+  /** Take the node's inbox: accepted messages arrive as `message_received` events. */
+  async claimInbox(): Promise<NodeResponse> {
+    return this.request({ type: "claim_inbox" });
+  }
+
   async getWalletBalance(walletType: WalletType): Promise<WalletInfo | null> {
     const resp = await this.request({ type: "wallet_balance", wallet: walletType });
     if (resp.type === "ok" && resp.data) return resp.data as WalletInfo;
@@ -191,17 +270,36 @@ export class NodeClient {
 export type NodeRequest =
   | { type: "identity" }
   | { type: "health" }
+  | { type: "relay_usage"; since_ms?: number }
+  | { type: "ping"; to: string; timeout_ms?: number }
+  | { type: "daemon_log"; after_seq?: number; limit?: number }
   | { type: "follow"; target: string }
   | { type: "unfollow"; target: string }
+  | { type: "set_alias"; target: string; alias?: string | null }
+  | { type: "group_add"; group: string; target: string }
+  | { type: "group_remove"; group: string; target: string }
+  | { type: "groups" }
   | { type: "block"; target: string }
   | { type: "following" }
   | { type: "followers" }
   | { type: "register_username"; username: string }
   | { type: "lookup_username"; username: string }
-  | { type: "send_dm"; to: string; body: string }
+  | {
+      type: "send_dm";
+      to: string;
+      body: string;
+      attachments?: Attachment[];
+      in_reply_to?: string;
+      reply_within_secs?: number;
+    }
+  | { type: "broadcast_dm"; body: string; mutual_only?: boolean; group?: string }
   | { type: "post_feed"; body: string }
+  | { type: "send_template"; template: string; to?: string; vars?: Record<string, string> }
   | { type: "inbox"; unread_only?: boolean; limit?: number }
   | { type: "inbox_ack"; message_id: string }
+  | { type: "claim_inbox" }
+  | { type: "message_thread"; thread_id: string }
+  | { type: "pending_replies" }
   | { type: "shutdown" }
   // -- Wallet --
   | { type: "wallet_balance"; wallet: string }
@@ -226,6 +324,9 @@ interface HelloResponse {
   type: "hello";
   node_id: string;
   version: string;
+  protocol_min?: number;
+  protocol_max?: number;
+  warnings?: string[];
   request_id?: number;
 }
 
@@ -261,6 +362,8 @@ export interface NodeEvent {
   message_type?: MessageType;
   preview?: string;
   node_id?: string;
+  /** Set on `message_received`, pushed to the connection holding the inbox. */
+  message?: InboxEntry;
 }
 
 export interface IdentityInfo {
@@ -269,10 +372,41 @@ export interface IdentityInfo {
   username: string | null;
 }
 
+export interface RelayUsage {
+  host: string;
+  window_ms: number;
+  total_frames: number;
+  total_bytes: number;
+  windows: { start_ms: number; frames: number; bytes: number }[];
+}
+
+export interface DaemonLogEntry {
+  seq: number;
+  at_ms: number;
+  kind:
+    | "ingress_accepted"
+    | "ingress_rejected"
+    | "relay_reconnect"
+    | "delivery_sent"
+    | "delivery_queued"
+    | "delivery_failed";
+  node_id?: string;
+  host?: string;
+  message_id?: string;
+  reason?: string;
+}
+
 export interface FollowInfo {
   node_id: string;
   username: string | null;
   followed_at_ms: number;
+  alias?: string;
+  groups?: string[];
+}
+
+export interface GroupInfo {
+  name: string;
+  members: string[];
 }
 
 export interface InboxEntry {
@@ -283,6 +417,25 @@ export interface InboxEntry {
   body: string;
   timestamp_ms: number;
   acked: boolean;
+  attachments?: Attachment[];
+  in_reply_to?: string;
+  /** ID of the message that started the thread; absent on that message. */
+  thread_id?: string;
+}
+
+/** A sent DM waiting for the reply it asked for. */
+export interface PendingReply {
+  message_id: string;
+  to: string;
+  deadline_ms: number;
+  overdue: boolean;
+}
+
+/** A file attached to a DM (at most 8, 40 KiB decoded in total). */
+export interface Attachment {
+  name: string;
+  mime: string;
+  data_b64: string;
 }
 
 export interface UsernameLookup {
@@ -296,6 +449,14 @@ export interface HealthStatus {
   relay_connected: boolean;
   following_count: number;
   unread_count: number;
+  clients: number;
+  frozen: boolean;
+  uptime_secs: number;
+  rss_bytes: number | null;
+  open_fds: number | null;
+  outbox_depth: number;
+  event_backlog: number;
+  events_lagged: number;
 }
 
 export interface WalletInfo {
@@ -0,0 +1,176 @@
+use crate::setup::{
+    connect_default_relay, print_mnemonic_words, prompt_new_passphrase,
+    register_username_interactive, run_totp_setup,
+};
+use agentbook_crypto::recovery::{key_to_mnemonic, mnemonic_to_key};
+use agentbook_mesh::identity::NodeIdentity;
+use agentbook_mesh::recovery;
+use agentbook_mesh::state_dir::{default_state_dir, ensure_state_dir};
+use agentbook_proto::host::v1 as host_pb;
+use anyhow::{Context, Result, bail};
+use k256::SecretKey;
+use std::path::PathBuf;
+
+/// Show the recovery phrases needed to restore this node on another machine.
+pub fn cmd_backup(state_dir: Option<PathBuf>) -> Result<()> {
+    let state_dir =
+        state_dir.unwrap_or_else(|| default_state_dir().expect("failed to determine state dir"));
+    let recovery_key_path = state_dir.join("recovery.key");
+    if !recovery::has_recovery_key(&recovery_key_path) {
+        bail!("no recovery key found — run `agentbook setup` first");
+    }
+
+    let passphrase = crate::read_passphrase(&state_dir)?;
+    let kek =
+        recovery::load_recovery_key(&recovery_key_path, &passphrase).context("wrong passphrase")?;
+    let identity =
+        NodeIdentity::load_or_create(&state_dir, &kek).context("failed to load identity")?;
+
+    eprintln!();
+    eprintln!("  \x1b[1;36m=== Backup ===\x1b[0m");
+    eprintln!("  Node ID: {}", identity.node_id);
+    eprintln!();
+    print_mnemonic_words(&key_to_mnemonic(&kek)?, "Your recovery phrase");
+
+    if !identity.is_derived_from(&kek) {
+        // Identities created before phrase-derived keys, or rotated since,
+        // use a random key that the recovery phrase cannot reproduce.
+        print_mnemonic_words(
+            &key_to_mnemonic(&identity.secret_key_bytes())?,
+            "Node key phrase (pass to `agentbook restore --node-key`)",
+        );
+    }
+
+    if agentbook_wallet::yolo::has_yolo_key(&state_dir) {
+        let yolo_key = agentbook_wallet::yolo::load_yolo_key(&state_dir)?;
+        print_mnemonic_words(
+            &key_to_mnemonic(&yolo_key)?,
+            "Agent wallet phrase (pass to `agentbook restore --yolo-key`)",
+        );
+    }
+
+    let retired = NodeIdentity::load_retired(&state_dir, &kek).unwrap_or_default();
+    if !retired.is_empty() {
+        eprintln!(
+            "  \x1b[1;33m{} retired key(s) are not covered by these phrases. Keep a copy of {} if their addresses hold funds.\x1b[0m",
+            retired.len(),
+            state_dir.join("retired").display()
+        );
+        eprintln!();
+    }
+
+    eprintln!("  \x1b[1;31mNever share these words with anyone — including AI agents.\x1b[0m");
+    eprintln!();
+    Ok(())
+}
+
+/// Recreate a node's identity and wallet keys from its recovery phrases.
+pub async fn cmd_restore(
+    phrase: Vec<String>,
+    node_key: Option<String>,
+    yolo_key: Option<String>,
+    state_dir: Option<PathBuf>,
+) -> Result<()> {
+    let state_dir =
+        state_dir.unwrap_or_else(|| default_state_dir().expect("failed to determine state dir"));
+    ensure_state_dir(&state_dir)?;
+    let recovery_key_path = state_dir.join("recovery.key");
+    if recovery::has_recovery_key(&recovery_key_path) || state_dir.join("node.key").exists() {
+        bail!(
+            "{} already holds a node identity; restore into an empty state directory",
+            state_dir.display()
+        );
+    }
+
+    let phrase = if phrase.is_empty() {
+        rpassword::prompt_password("  Enter your 24-word recovery phrase: ")
+            .context("failed to read recovery phrase")?
+    } else {
+        phrase.join(" ")
+    };
+    let kek = recovery::Zeroizing::new(mnemonic_to_key(&phrase)?);
+    let node_key = node_key
+        .map(|phrase| parse_secret_key(&phrase).context("invalid node key phrase"))
+        .transpose()?;
+    let yolo_key = yolo_key
+        .map(|phrase| mnemonic_to_key(&phrase).context("invalid agent wallet phrase"))
+        .transpose()?;
+
+    eprintln!();
+    eprintln!("  \x1b[1;36m=== Restore ===\x1b[0m");
+    eprintln!("  Choose a passphrase to protect the restored recovery key on this machine.");
+    eprintln!();
+    let passphrase = prompt_new_passphrase()?;
+
+    let identity =
+        NodeIdentity::restore(&state_dir, &kek, node_key).context("failed to restore identity")?;
+    recovery::save_recovery_key(&recovery_key_path, &passphrase, &kek)
+        .context("failed to save recovery key")?;
+    eprintln!("  \x1b[1;36mNode ID:\x1b[0m {}", identity.node_id);
+    eprintln!();
+
+    if let Some(yolo_key) = yolo_key {
+        let address = agentbook_wallet::yolo::restore_yolo_key(&state_dir, &yolo_key)
+            .context("failed to restore agent wallet")?;
+        eprintln!("  \x1b[1;36mAgent wallet:\x1b[0m {address}");
+        eprintln!();
+    }
+
+    // TOTP secrets are per-machine; enroll this one again.
+    run_totp_setup(&state_dir, &kek, &identity.node_id)?;
+
+    reregister_with_relay(&identity).await?;
+
+    eprintln!("  \x1b[1;32mRestore complete. Run `agentbook up` to start the node,\x1b[0m");
+    eprintln!("  \x1b[1;32mthen `agentbook sync-pull --confirm` to recover your follows.\x1b[0m");
+    eprintln!();
+    Ok(())
+}
+
+/// Confirm the relay still maps this node to its username, registering one if not.
+async fn reregister_with_relay(identity: &NodeIdentity) -> Result<()> {
+    let lookup = match connect_default_relay().await {
+        Ok(mut client) => client
+            .lookup_node_id(host_pb::LookupNodeIdRequest {
+                node_id: identity.node_id.clone(),
+            })
+            .await
+            .map(|resp| resp.into_inner())
+            .ok(),
+        Err(_) => None,
+    };
+
+    match lookup {
+        Some(found) if found.found && found.public_key_b64 == identity.public_key_b64 => {
+            eprintln!(
+                "  \x1b[1;32mRelay recognizes this node as @{}\x1b[0m",
+                found.username
+            );
+            eprintln!();
+            Ok(())
+        }
+        _ => register_username_interactive(identity).await,
+    }
+}
+
+/// Parse a node key exported by `agentbook backup`.
+fn parse_secret_key(phrase: &str) -> Result<SecretKey> {
+    let bytes = recovery::Zeroizing::new(mnemonic_to_key(phrase)?);
+    SecretKey::from_slice(bytes.as_slice()).context("phrase is not a valid secp256k1 key")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_key_phrase_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let kek = agentbook_crypto::crypto::random_key_material();
+        let identity = NodeIdentity::load_or_create(dir.path(), &kek).unwrap();
+
+        let phrase = key_to_mnemonic(&identity.secret_key_bytes()).unwrap();
+        assert_eq!(&parse_secret_key(&phrase).unwrap(), identity.secret_key());
+        assert!(parse_secret_key("not a phrase").is_err());
+    }
+}
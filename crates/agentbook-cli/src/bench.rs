@@ -0,0 +1,133 @@
+//! `agentbook bench` (hidden): load a running node with concurrent clients
+//! and report request throughput and latency, for comparing releases.
+//!
+//! Every client sends `health` requests, which touch no state, so it is safe
+//! to run against a live node.
+
+use agentbook::client::NodeClient;
+use agentbook::protocol::{Request, Response};
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Serialize)]
+struct Report {
+    clients: usize,
+    requests: usize,
+    errors: usize,
+    /// Events that arrived while waiting for responses.
+    events: usize,
+    elapsed_ms: u64,
+    requests_per_sec: f64,
+    p50_us: u64,
+    p99_us: u64,
+    max_us: u64,
+}
+
+struct ClientRun {
+    latencies: Vec<Duration>,
+    errors: usize,
+    events: usize,
+}
+
+/// Send `requests` health requests over one connection, at most `rate` per
+/// second if given.
+async fn run_client(socket_path: &Path, requests: usize, rate: Option<u32>) -> Result<ClientRun> {
+    let mut client = NodeClient::connect(socket_path).await?;
+    let mut run = ClientRun {
+        latencies: Vec::with_capacity(requests),
+        errors: 0,
+        events: 0,
+    };
+    let mut pace = rate.map(|r| tokio::time::interval(Duration::from_secs(1) / r.max(1)));
+    for _ in 0..requests {
+        if let Some(pace) = &mut pace {
+            pace.tick().await;
+        }
+        let started = Instant::now();
+        let request_id = client.send(Request::Health).await?;
+        loop {
+            let resp = client.next_response_envelope().await?;
+            match resp.response {
+                Response::Event { .. } => run.events += 1,
+                Response::Ok { .. } | Response::Error { .. }
+                    if resp.request_id == Some(request_id) =>
+                {
+                    if matches!(resp.response, Response::Error { .. }) {
+                        run.errors += 1;
+                    }
+                    break;
+                }
+                _ => {}
+            }
+        }
+        run.latencies.push(started.elapsed());
+    }
+    Ok(run)
+}
+
+/// The `pct`th percentile of sorted `latencies`.
+fn percentile(latencies: &[Duration], pct: usize) -> Duration {
+    if latencies.is_empty() {
+        return Duration::ZERO;
+    }
+    let idx = (latencies.len() * pct).div_ceil(100).saturating_sub(1);
+    latencies[idx.min(latencies.len() - 1)]
+}
+
+pub async fn cmd_bench(
+    socket_path: &Path,
+    clients: usize,
+    requests: usize,
+    rate: Option<u32>,
+) -> Result<()> {
+    if clients == 0 || requests == 0 {
+        bail!("--clients and --requests must be at least 1");
+    }
+    let started = Instant::now();
+    let mut tasks = tokio::task::JoinSet::new();
+    for _ in 0..clients {
+        let socket_path = socket_path.to_path_buf();
+        tasks.spawn(async move { run_client(&socket_path, requests, rate).await });
+    }
+
+    let mut latencies = Vec::with_capacity(clients * requests);
+    let (mut errors, mut events) = (0, 0);
+    while let Some(run) = tasks.join_next().await {
+        let run = run??;
+        latencies.extend(run.latencies);
+        errors += run.errors;
+        events += run.events;
+    }
+    let elapsed = started.elapsed();
+    latencies.sort_unstable();
+
+    let report = Report {
+        clients,
+        requests: latencies.len(),
+        errors,
+        events,
+        elapsed_ms: elapsed.as_millis() as u64,
+        requests_per_sec: latencies.len() as f64 / elapsed.as_secs_f64(),
+        p50_us: percentile(&latencies, 50).as_micros() as u64,
+        p99_us: percentile(&latencies, 99).as_micros() as u64,
+        max_us: latencies.last().copied().unwrap_or_default().as_micros() as u64,
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_pick_the_covering_sample() {
+        let latencies: Vec<_> = (1..=200).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&latencies, 50), Duration::from_millis(100));
+        assert_eq!(percentile(&latencies, 99), Duration::from_millis(198));
+        assert_eq!(percentile(&latencies[..1], 99), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 99), Duration::ZERO);
+    }
+}
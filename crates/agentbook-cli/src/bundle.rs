@@ -0,0 +1,335 @@
+//! `agentbook state export` / `state import`: move a whole node to another
+//! machine as one `.tar.zst` bundle.
+//!
+//! The bundle holds the state files as they are on disk, so the identity,
+//! follow store and inbox stay encrypted under the recovery key and the
+//! recovery key under the passphrase; importing needs the same passphrase
+//! when the node next starts. The plaintext agent wallet key is left out: move
+//! it with `agentbook backup` / `restore --yolo-key`. Sockets, logs, backups
+//! and per-machine rate limit state are not exported either.
+
+use agentbook_mesh::state_dir::{default_state_dir, ensure_state_dir};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+
+const MANIFEST: &str = "manifest.json";
+const FORMAT_VERSION: u32 = 1;
+const RETIRED_DIR: &str = "retired";
+
+/// State files that make up a node, relative to the state directory.
+const STATE_FILES: &[&str] = &[
+    // Identity, encrypted under the recovery key.
+    "recovery.key",
+    "node.key",
+    "node.pub",
+    "node.json",
+    "totp.key",
+    // Friends.
+    "following.json",
+    "blocked.json",
+    "key_history.json",
+    "username_cache.json",
+    "invites.json",
+    // Messages.
+    "inbox.jsonl",
+    "inbox_acked.jsonl",
+    "inbox.db",
+    "outbox.json",
+    "rooms.json",
+    // Settings.
+    "node_config.json",
+    "access.json",
+];
+
+/// Describes a bundle; written first so `import` can check it up front.
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    format: u32,
+    #[serde(default)]
+    node_id: Option<String>,
+    exported_at_ms: u64,
+    files: Vec<String>,
+}
+
+/// The state files present in `state_dir`, including retired keys.
+fn exported_files(state_dir: &Path) -> Result<Vec<String>> {
+    let mut files: Vec<String> = STATE_FILES
+        .iter()
+        .filter(|name| state_dir.join(name).is_file())
+        .map(|name| name.to_string())
+        .collect();
+    let retired = state_dir.join(RETIRED_DIR);
+    if retired.is_dir() {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&retired)
+            .with_context(|| format!("failed to read {}", retired.display()))?
+        {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.push(format!(
+                    "{RETIRED_DIR}/{}",
+                    entry.file_name().to_string_lossy()
+                ));
+            }
+        }
+        names.sort();
+        files.extend(names);
+    }
+    Ok(files)
+}
+
+/// Whether `name` is a path `import` may write: a known state file or a file
+/// directly under `retired/`.
+fn importable(name: &str) -> bool {
+    if STATE_FILES.contains(&name) {
+        return true;
+    }
+    let path = Path::new(name);
+    let mut components = path.components();
+    matches!(
+        (components.next(), components.next(), components.next()),
+        (Some(Component::Normal(dir)), Some(Component::Normal(_)), None) if dir == RETIRED_DIR
+    )
+}
+
+/// Write every state file of `state_dir` to `output`.
+fn export_bundle(state_dir: &Path, output: &Path) -> Result<Manifest> {
+    if !state_dir.join("recovery.key").exists() {
+        bail!(
+            "{} holds no node (no recovery.key); nothing to export",
+            state_dir.display()
+        );
+    }
+    let node_id = std::fs::read_to_string(state_dir.join("node.json"))
+        .ok()
+        .and_then(|data| serde_json::from_str::<serde_json::Value>(&data).ok())
+        .and_then(|meta| meta["node_id"].as_str().map(str::to_string));
+    let manifest = Manifest {
+        format: FORMAT_VERSION,
+        node_id,
+        exported_at_ms: agentbook_crypto::time::now_ms(),
+        files: exported_files(state_dir)?,
+    };
+
+    let file =
+        File::create(output).with_context(|| format!("failed to create {}", output.display()))?;
+    let encoder = zstd::Encoder::new(file, 0)?;
+    let mut tar = tar::Builder::new(encoder);
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(manifest.exported_at_ms / 1000);
+    header.set_cksum();
+    tar.append_data(&mut header, MANIFEST, manifest_json.as_slice())?;
+    for name in &manifest.files {
+        tar.append_path_with_name(state_dir.join(name), name)
+            .with_context(|| format!("failed to add {name}"))?;
+    }
+    tar.into_inner()?.finish()?.sync_all()?;
+    Ok(manifest)
+}
+
+/// Unpack `bundle` into `state_dir`, which must not hold a node yet.
+fn import_bundle(bundle: &Path, state_dir: &Path) -> Result<Manifest> {
+    if state_dir.join("recovery.key").exists() || state_dir.join("node.key").exists() {
+        bail!(
+            "{} already holds a node identity; import into an empty state directory",
+            state_dir.display()
+        );
+    }
+    ensure_state_dir(state_dir)?;
+
+    let file =
+        File::open(bundle).with_context(|| format!("failed to open {}", bundle.display()))?;
+    let mut archive = tar::Archive::new(zstd::Decoder::new(file)?);
+    let mut entries = archive.entries()?;
+
+    let manifest: Manifest = match entries.next() {
+        Some(entry) => {
+            let entry = entry?;
+            if entry.path()?.as_ref() != Path::new(MANIFEST) {
+                bail!("not an agentbook state bundle (no manifest)");
+            }
+            serde_json::from_reader(entry).context("invalid bundle manifest")?
+        }
+        None => bail!("bundle is empty"),
+    };
+    if manifest.format != FORMAT_VERSION {
+        bail!(
+            "bundle format {} is not supported (expected {FORMAT_VERSION}); upgrade agentbook",
+            manifest.format
+        );
+    }
+
+    let mut written: Vec<PathBuf> = Vec::new();
+    let result = (|| -> Result<()> {
+        for entry in entries {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            if !importable(&name) || !manifest.files.contains(&name) {
+                bail!("bundle contains unexpected entry {name:?}");
+            }
+            // Links and devices could point the write outside `state_dir`.
+            if entry.header().entry_type() != tar::EntryType::Regular {
+                bail!("bundle entry {name:?} is not a regular file");
+            }
+            let dest = state_dir.join(&name);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            if dest.exists() {
+                bail!("{} already exists", dest.display());
+            }
+            entry
+                .unpack(&dest)
+                .with_context(|| format!("failed to unpack {name}"))?;
+            written.push(dest);
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        // Leave the directory as empty as we found it.
+        for path in written {
+            let _ = std::fs::remove_file(path);
+        }
+        return Err(e);
+    }
+    Ok(manifest)
+}
+
+pub fn cmd_export(state_dir: Option<PathBuf>, output: PathBuf, socket_path: &Path) -> Result<()> {
+    let state_dir =
+        state_dir.unwrap_or_else(|| default_state_dir().expect("failed to determine state dir"));
+    // A running node keeps appending to the inbox and rewriting stores.
+    if std::os::unix::net::UnixStream::connect(socket_path).is_ok() {
+        bail!("the node is running; stop it with `agentbook down` before exporting");
+    }
+    let manifest = export_bundle(&state_dir, &output)?;
+    eprintln!(
+        "Exported {} file(s) for node {} to {}",
+        manifest.files.len(),
+        manifest.node_id.as_deref().unwrap_or("(unknown)"),
+        output.display()
+    );
+    if agentbook_wallet::yolo::has_yolo_key(&state_dir) {
+        eprintln!(
+            "The agent wallet key is not in the bundle; move it with `agentbook backup` and `agentbook restore --yolo-key`."
+        );
+    }
+    Ok(())
+}
+
+pub fn cmd_import(bundle: PathBuf, state_dir: Option<PathBuf>) -> Result<()> {
+    let state_dir =
+        state_dir.unwrap_or_else(|| default_state_dir().expect("failed to determine state dir"));
+    let manifest = import_bundle(&bundle, &state_dir)?;
+    eprintln!(
+        "Imported node {} ({} file(s)) into {}",
+        manifest.node_id.as_deref().unwrap_or("(unknown)"),
+        manifest.files.len(),
+        state_dir.display()
+    );
+    eprintln!(
+        "Run `agentbook up` with the same passphrase. Stop the node on the old machine first: both would answer for the same node id."
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundle_round_trips_state_files() {
+        let src = tempfile::tempdir().unwrap();
+        for (name, data) in [
+            ("recovery.key", "sealed"),
+            ("node.json", r#"{"node_id":"0xabc"}"#),
+            ("following.json", "[]"),
+            ("inbox.jsonl", "{}\n"),
+            ("yolo.key", "plaintext"),
+            ("following.json.bak", "[]"),
+        ] {
+            std::fs::write(src.path().join(name), data).unwrap();
+        }
+        std::fs::create_dir(src.path().join(RETIRED_DIR)).unwrap();
+        std::fs::write(src.path().join("retired/0xold.json"), "{}").unwrap();
+
+        let out = tempfile::tempdir().unwrap();
+        let bundle = out.path().join("node.tar.zst");
+        let manifest = export_bundle(src.path(), &bundle).unwrap();
+        assert_eq!(manifest.node_id.as_deref(), Some("0xabc"));
+        assert_eq!(
+            manifest.files,
+            [
+                "recovery.key",
+                "node.json",
+                "following.json",
+                "inbox.jsonl",
+                "retired/0xold.json"
+            ]
+        );
+
+        let dest = out.path().join("state");
+        import_bundle(&bundle, &dest).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dest.join("inbox.jsonl")).unwrap(),
+            "{}\n"
+        );
+        assert!(dest.join("retired/0xold.json").exists());
+        assert!(!dest.join("yolo.key").exists());
+
+        // A second import would overwrite a node.
+        assert!(import_bundle(&bundle, &dest).is_err());
+    }
+
+    #[test]
+    fn links_in_a_bundle_are_refused() {
+        let out = tempfile::tempdir().unwrap();
+        let bundle = out.path().join("evil.tar.zst");
+        let manifest = Manifest {
+            format: FORMAT_VERSION,
+            node_id: None,
+            exported_at_ms: 0,
+            files: vec!["following.json".into(), "node.json".into()],
+        };
+        let manifest_json = serde_json::to_vec(&manifest).unwrap();
+        let encoder = zstd::Encoder::new(File::create(&bundle).unwrap(), 0).unwrap();
+        let mut tar = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest_json.len() as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, MANIFEST, manifest_json.as_slice())
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(2);
+        header.set_cksum();
+        tar.append_data(&mut header, "following.json", b"[]".as_slice())
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        tar.append_link(&mut header, "node.json", "/etc/passwd")
+            .unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let dest = out.path().join("state");
+        let err = import_bundle(&bundle, &dest).unwrap_err();
+        assert!(err.to_string().contains("not a regular file"), "{err:#}");
+        assert!(!dest.join("following.json").exists());
+        assert!(dest.join("node.json").symlink_metadata().is_err());
+    }
+
+    #[test]
+    fn only_state_paths_are_importable() {
+        assert!(importable("inbox.db"));
+        assert!(importable("retired/0xold.json"));
+        assert!(!importable("../.bashrc"));
+        assert!(!importable("retired/../../x"));
+        assert!(!importable("/etc/passwd"));
+        assert!(!importable("yolo.key"));
+    }
+}
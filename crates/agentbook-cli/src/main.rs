@@ -1,10 +1,15 @@
+mod backup;
+mod bench;
+mod bundle;
 mod login;
 mod service;
 mod setup;
 mod update;
 
 use agentbook::client::{NodeClient, default_socket_path};
-use agentbook::protocol::{Request, WalletType};
+use agentbook::protocol::{
+    Attachment, DaemonLogEntry, IngressBudget, Request, ShareInfo, WalletType,
+};
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
@@ -35,6 +40,26 @@ enum Command {
         #[arg(long)]
         state_dir: Option<PathBuf>,
     },
+    /// Show the recovery phrases needed to restore this node on another machine.
+    Backup {
+        /// State directory.
+        #[arg(long)]
+        state_dir: Option<PathBuf>,
+    },
+    /// Restore node identity and wallet keys from recovery phrases into a new state directory.
+    Restore {
+        /// 24-word recovery phrase (prompted for if omitted).
+        phrase: Vec<String>,
+        /// Node key phrase, for nodes whose `backup` printed one.
+        #[arg(long)]
+        node_key: Option<String>,
+        /// Agent (yolo) wallet phrase.
+        #[arg(long)]
+        yolo_key: Option<String>,
+        /// State directory.
+        #[arg(long)]
+        state_dir: Option<PathBuf>,
+    },
     /// Start the node daemon.
     Up {
         /// Run in the foreground (default: background).
@@ -49,6 +74,20 @@ enum Command {
         /// Disable connecting to any relay host.
         #[arg(long)]
         no_relay: bool,
+        /// Also connect to the N fastest relays from the signed relay directory.
+        #[arg(
+            long,
+            value_name = "N",
+            conflicts_with = "no_relay",
+            requires = "relay_directory_signer"
+        )]
+        auto_relays: Option<usize>,
+        /// Address that must have signed the relay directory.
+        #[arg(long)]
+        relay_directory_signer: Option<String>,
+        /// Pad and seal DMs/feed posts so the relay only sees routing node IDs.
+        #[arg(long)]
+        privacy_mode: bool,
         /// Base chain RPC URL.
         #[arg(long)]
         rpc_url: Option<String>,
@@ -57,9 +96,55 @@ enum Command {
         yolo: bool,
     },
     /// Stop the node daemon.
-    Down,
+    Down {
+        /// Let in-flight requests and queued messages finish before exiting.
+        #[arg(long)]
+        drain: bool,
+        /// Seconds to wait for the drain (default: 5 minutes).
+        #[arg(long, requires = "drain")]
+        timeout_secs: Option<u64>,
+    },
     /// Show node identity.
     Identity,
+    /// Rotate the node key and announce the new one to follows and followers.
+    /// Takes effect when the node is restarted.
+    RotateKey {
+        /// Hours to keep accepting messages sent to the old key (default: 7 days).
+        #[arg(long)]
+        grace_hours: Option<u64>,
+    },
+    /// Show inbound rate limit budgets and per-sender usage.
+    IngressStats {
+        /// Only show this sender.
+        #[arg(long)]
+        node_id: Option<String>,
+    },
+    /// Show the inbox retention policy and how much has been pruned.
+    Retention,
+    /// Load the running node with concurrent clients and report request
+    /// throughput and latency.
+    #[command(hide = true)]
+    Bench {
+        /// Concurrent connections.
+        #[arg(long, default_value_t = 8)]
+        clients: usize,
+        /// Requests per connection.
+        #[arg(long, default_value_t = 1000)]
+        requests: usize,
+        /// Requests per second per connection (default: as fast as possible).
+        #[arg(long)]
+        rate: Option<u32>,
+    },
+    /// Show or change runtime settings of the running node.
+    Config {
+        /// Log filter, e.g. `debug` or `agentbook_node=trace`.
+        #[arg(long)]
+        log_level: Option<String>,
+        /// Ingress budget as CLASS=CAPACITY/PER_SECOND, e.g. `dm=40/4`
+        /// (repeatable; classes: dm, feed, key_notice, ping, other).
+        #[arg(long = "ingress-budget", value_parser = parse_ingress_budget)]
+        ingress_budgets: Vec<IngressBudget>,
+    },
     /// Register a username on the relay host.
     Register {
         /// Username to register.
@@ -80,6 +165,29 @@ enum Command {
         /// Node ID or @username.
         target: String,
     },
+    /// Give a node you follow a local alias to use instead of its node ID.
+    Alias {
+        /// Node ID, @username or current alias.
+        target: String,
+        /// New alias (a-z, 0-9, '-', '_'). Omit to clear it.
+        alias: Option<String>,
+    },
+    /// Put a node you follow in a group, e.g. `team-infra`.
+    GroupAdd {
+        /// Group name (a-z, 0-9, '-', '_').
+        group: String,
+        /// Node ID, @username or alias.
+        target: String,
+    },
+    /// Take a node out of a group.
+    GroupRemove {
+        /// Group name.
+        group: String,
+        /// Node ID, @username or alias.
+        target: String,
+    },
+    /// List groups and their members.
+    Groups,
     /// Block a node.
     Block {
         /// Node ID or @username.
@@ -89,6 +197,34 @@ enum Command {
     Following,
     /// List your followers.
     Followers,
+    /// Create an invite token. Whoever accepts it is followed back automatically.
+    Invite {
+        /// Hours until the invite expires (default: 7 days, at most a year).
+        #[arg(long)]
+        ttl_hours: Option<u64>,
+        /// Maximum number of nodes that may redeem the invite.
+        #[arg(long, conflicts_with = "single_use")]
+        max_uses: Option<u32>,
+        /// Allow only one node to redeem the invite.
+        #[arg(long)]
+        single_use: bool,
+        /// Limit what redeemers may send you: dm, feed. Repeatable; the
+        /// default allows everything.
+        #[arg(long = "scope")]
+        scopes: Vec<String>,
+    },
+    /// List invites you have issued.
+    Invites,
+    /// Revoke an issued invite.
+    InviteRevoke {
+        /// Token ID of the invite.
+        token_id: String,
+    },
+    /// Accept an invite token: follow the inviter and let them follow you back.
+    InviteAccept {
+        /// Invite token.
+        token: String,
+    },
     /// Push local follow data to relay (reconciliation).
     SyncPush {
         /// Confirm the push operation.
@@ -107,12 +243,44 @@ enum Command {
         to: String,
         /// Message body.
         message: String,
+        /// Attach a file (repeatable; 40 KiB in total).
+        #[arg(long = "attach", value_name = "PATH")]
+        attach: Vec<PathBuf>,
+        /// Reply to this message ID, continuing its thread.
+        #[arg(long, value_name = "MESSAGE_ID")]
+        reply_to: Option<String>,
+        /// Expect a reply within this many seconds; `pending-replies` and a
+        /// `reply_overdue` event report it if none comes.
+        #[arg(long, value_name = "SECS")]
+        reply_within: Option<u64>,
+    },
+    /// DM the same message to everyone you follow.
+    Broadcast {
+        /// Message body.
+        message: String,
+        /// Only DM nodes that follow you back.
+        #[arg(long)]
+        mutual_only: bool,
+        /// Only DM members of this group.
+        #[arg(long)]
+        group: Option<String>,
     },
     /// Post to your feed.
     Post {
         /// Message body.
         message: String,
     },
+    /// Send a message from a template in node_config.json.
+    SendTemplate {
+        /// Template name.
+        template: String,
+        /// Recipient node ID or @username, for DM templates.
+        #[arg(long)]
+        to: Option<String>,
+        /// Fill a template variable (repeatable).
+        #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+    },
     /// List inbox messages.
     Inbox {
         /// Show only unread messages.
@@ -122,13 +290,70 @@ enum Command {
         #[arg(long)]
         limit: Option<usize>,
     },
+    /// Show a DM thread in order.
+    Thread {
+        /// ID of the message that started the thread.
+        thread_id: String,
+    },
+    /// List sent DMs still waiting for a reply.
+    PendingReplies,
     /// Acknowledge a message.
     Ack {
         /// Message ID to acknowledge.
         message_id: String,
     },
-    /// Health check.
+    /// List DMs waiting to be retried.
+    Outbox,
+    /// Cancel a queued DM.
+    OutboxCancel {
+        /// Message ID to cancel.
+        message_id: String,
+    },
+    /// Health check, uptime and resource usage.
     Health,
+    /// Check that a node is reachable and measure the round trip.
+    Ping {
+        /// Node ID, @username or alias.
+        target: String,
+        /// Seconds to wait for an answer.
+        #[arg(long, default_value_t = 10)]
+        timeout_secs: u64,
+    },
+    /// Show how much traffic each relay has counted for this node.
+    RelayUsage {
+        /// Only the last N hours (default: everything the relay keeps).
+        #[arg(long)]
+        hours: Option<u64>,
+    },
+    /// Show recent daemon decisions: ingress accepts and rejects, relay
+    /// reconnects and delivery attempts, one JSON object per line.
+    Events {
+        /// Keep printing new entries as they happen.
+        #[arg(long)]
+        follow: bool,
+        /// How many recent entries to show first.
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// List clients connected to the daemon.
+    Clients,
+    /// Close a client's connection.
+    Disconnect {
+        /// Client ID from `agentbook clients`.
+        client_id: u64,
+    },
+    /// Pause sends, writes and payments, keeping all state.
+    Freeze,
+    /// Resume after `freeze`.
+    Unfreeze,
+    /// Let another local user read this node, via a token that expires.
+    Share {
+        /// How long the token lasts (default one hour).
+        #[arg(long)]
+        ttl_secs: Option<u64>,
+    },
+    /// Revoke every share token and disconnect their viewers.
+    Unshare,
 
     // -- Wallet commands --
     /// Show wallet address and balances.
@@ -220,6 +445,12 @@ enum Command {
         action: ServiceAction,
     },
 
+    /// Maintain the node's state directory.
+    State {
+        #[command(subcommand)]
+        action: StateAction,
+    },
+
     /// Control the in-memory credential agent (agentbook-agent).
     Agent {
         #[command(subcommand)]
@@ -285,6 +516,30 @@ enum ServiceAction {
     Status,
 }
 
+#[derive(Subcommand)]
+enum StateAction {
+    /// Rewrite the encrypted follow store and inbox with fresh encryption.
+    Reencrypt,
+    /// Package this node (identity, friends, inbox, rooms, settings) into a
+    /// bundle for moving it to another machine. The node must be stopped.
+    Export {
+        /// Bundle to write, e.g. `node.tar.zst`.
+        #[arg(long, short)]
+        output: PathBuf,
+        /// State directory.
+        #[arg(long)]
+        state_dir: Option<PathBuf>,
+    },
+    /// Unpack a bundle from `state export` into an empty state directory.
+    Import {
+        /// Bundle written by `state export`.
+        bundle: PathBuf,
+        /// State directory.
+        #[arg(long)]
+        state_dir: Option<PathBuf>,
+    },
+}
+
 #[derive(Subcommand)]
 enum AgentAction {
     /// Start the agent daemon (prompts for passphrase once via 1Password or interactively).
@@ -325,11 +580,21 @@ async fn main() -> Result<()> {
 
     match command {
         Command::Setup { yolo, state_dir } => setup::cmd_setup(yolo, state_dir).await,
+        Command::Backup { state_dir } => backup::cmd_backup(state_dir),
+        Command::Restore {
+            phrase,
+            node_key,
+            yolo_key,
+            state_dir,
+        } => backup::cmd_restore(phrase, node_key, yolo_key, state_dir).await,
         Command::Up {
             foreground,
             state_dir,
             relay_host,
             no_relay,
+            auto_relays,
+            relay_directory_signer,
+            privacy_mode,
             rpc_url,
             yolo,
         } => {
@@ -339,14 +604,29 @@ async fn main() -> Result<()> {
                 state_dir,
                 relay_host,
                 no_relay,
+                auto_relays.zip(relay_directory_signer),
+                privacy_mode,
                 rpc_url,
                 yolo,
             )
             .await
         }
-        Command::Down => {
+        Command::Down {
+            drain,
+            timeout_secs,
+        } => {
             let mut client = connect(&socket_path).await?;
-            client.request(Request::Shutdown).await?;
+            if drain {
+                println!("Draining node…");
+                let data = client
+                    .request(Request::Drain {
+                        timeout_ms: timeout_secs.map(|s| s * 1000),
+                    })
+                    .await?;
+                print_json(&data);
+            } else {
+                client.request(Request::Shutdown).await?;
+            }
             println!("Node shutting down.");
             Ok(())
         }
@@ -356,6 +636,58 @@ async fn main() -> Result<()> {
             print_json(&data);
             Ok(())
         }
+        Command::RotateKey { grace_hours } => {
+            let mut client = connect(&socket_path).await?;
+            let data = client
+                .request(Request::RotateKey {
+                    grace_ms: grace_hours.map(|h| h * 60 * 60 * 1000),
+                })
+                .await?;
+            print_json(&data);
+            println!("Restart the node to start using the new key: agentbook down && agentbook up");
+            println!(
+                "Your human wallet address changes with the node key; move any funds from the old address."
+            );
+            Ok(())
+        }
+        Command::IngressStats { node_id } => {
+            let mut client = connect(&socket_path).await?;
+            let data = client.request(Request::IngressStats { node_id }).await?;
+            print_json(&data);
+            Ok(())
+        }
+        Command::Retention => {
+            let mut client = connect(&socket_path).await?;
+            let data = client.request(Request::RetentionStats).await?;
+            print_json(&data);
+            Ok(())
+        }
+        Command::Bench {
+            clients,
+            requests,
+            rate,
+        } => {
+            // Fail with the usual hint if the node is not up.
+            connect(&socket_path).await?;
+            bench::cmd_bench(&socket_path, clients, requests, rate).await
+        }
+        Command::Config {
+            log_level,
+            ingress_budgets,
+        } => {
+            let mut client = connect(&socket_path).await?;
+            let request = if log_level.is_none() && ingress_budgets.is_empty() {
+                Request::Config
+            } else {
+                Request::ConfigSet {
+                    log_level,
+                    ingress_budgets,
+                }
+            };
+            let data = client.request(request).await?;
+            print_json(&data);
+            Ok(())
+        }
         Command::Register { username } => {
             let mut client = connect(&socket_path).await?;
             let data = client
@@ -385,6 +717,40 @@ async fn main() -> Result<()> {
             println!("Unfollowed.");
             Ok(())
         }
+        Command::Alias { target, alias } => {
+            let mut client = connect(&socket_path).await?;
+            let cleared = alias.is_none();
+            client.request(Request::SetAlias { target, alias }).await?;
+            println!(
+                "{}",
+                if cleared {
+                    "Alias cleared."
+                } else {
+                    "Alias set."
+                }
+            );
+            Ok(())
+        }
+        Command::GroupAdd { group, target } => {
+            let mut client = connect(&socket_path).await?;
+            client.request(Request::GroupAdd { group, target }).await?;
+            println!("Added to group.");
+            Ok(())
+        }
+        Command::GroupRemove { group, target } => {
+            let mut client = connect(&socket_path).await?;
+            client
+                .request(Request::GroupRemove { group, target })
+                .await?;
+            println!("Removed from group.");
+            Ok(())
+        }
+        Command::Groups => {
+            let mut client = connect(&socket_path).await?;
+            let data = client.request(Request::Groups).await?;
+            print_json(&data);
+            Ok(())
+        }
         Command::Block { target } => {
             let mut client = connect(&socket_path).await?;
             client.request(Request::Block { target }).await?;
@@ -403,6 +769,41 @@ async fn main() -> Result<()> {
             print_json(&data);
             Ok(())
         }
+        Command::Invite {
+            ttl_hours,
+            max_uses,
+            single_use,
+            scopes,
+        } => {
+            let mut client = connect(&socket_path).await?;
+            let data = client
+                .request(Request::InviteCreate {
+                    ttl_ms: ttl_hours.map(|h| h.saturating_mul(60 * 60 * 1000)),
+                    max_uses: if single_use { Some(1) } else { max_uses },
+                    scopes,
+                })
+                .await?;
+            print_json(&data);
+            Ok(())
+        }
+        Command::Invites => {
+            let mut client = connect(&socket_path).await?;
+            let data = client.request(Request::InviteList).await?;
+            print_json(&data);
+            Ok(())
+        }
+        Command::InviteRevoke { token_id } => {
+            let mut client = connect(&socket_path).await?;
+            client.request(Request::InviteRevoke { token_id }).await?;
+            println!("Revoked.");
+            Ok(())
+        }
+        Command::InviteAccept { token } => {
+            let mut client = connect(&socket_path).await?;
+            let data = client.request(Request::InviteAccept { token }).await?;
+            print_json(&data);
+            Ok(())
+        }
         Command::SyncPush { confirm } => {
             let mut client = connect(&socket_path).await?;
             let data = client.request(Request::SyncPush { confirm }).await?;
@@ -415,10 +816,42 @@ async fn main() -> Result<()> {
             print_json(&data);
             Ok(())
         }
-        Command::Send { to, message } => {
+        Command::Send {
+            to,
+            message,
+            attach,
+            reply_to,
+            reply_within,
+        } => {
+            let attachments = attach
+                .iter()
+                .map(|path| read_attachment(path))
+                .collect::<Result<Vec<_>>>()?;
+            let mut client = connect(&socket_path).await?;
+            let data = client
+                .request(Request::SendDm {
+                    to,
+                    body: message,
+                    attachments,
+                    in_reply_to: reply_to,
+                    reply_within_secs: reply_within,
+                })
+                .await?;
+            print_json(&data);
+            Ok(())
+        }
+        Command::Broadcast {
+            message,
+            mutual_only,
+            group,
+        } => {
             let mut client = connect(&socket_path).await?;
             let data = client
-                .request(Request::SendDm { to, body: message })
+                .request(Request::BroadcastDm {
+                    body: message,
+                    mutual_only,
+                    group,
+                })
                 .await?;
             print_json(&data);
             Ok(())
@@ -429,6 +862,18 @@ async fn main() -> Result<()> {
             print_json(&data);
             Ok(())
         }
+        Command::SendTemplate { template, to, vars } => {
+            let mut client = connect(&socket_path).await?;
+            let data = client
+                .request(Request::SendTemplate {
+                    template,
+                    to,
+                    vars: vars.into_iter().collect(),
+                })
+                .await?;
+            print_json(&data);
+            Ok(())
+        }
         Command::Inbox { unread, limit } => {
             let mut client = connect(&socket_path).await?;
             let data = client
@@ -440,18 +885,114 @@ async fn main() -> Result<()> {
             print_json(&data);
             Ok(())
         }
+        Command::Thread { thread_id } => {
+            let mut client = connect(&socket_path).await?;
+            let data = client.request(Request::MessageThread { thread_id }).await?;
+            print_json(&data);
+            Ok(())
+        }
+        Command::PendingReplies => {
+            let mut client = connect(&socket_path).await?;
+            let data = client.request(Request::PendingReplies).await?;
+            print_json(&data);
+            Ok(())
+        }
         Command::Ack { message_id } => {
             let mut client = connect(&socket_path).await?;
             client.request(Request::InboxAck { message_id }).await?;
             println!("Acknowledged.");
             Ok(())
         }
+        Command::Outbox => {
+            let mut client = connect(&socket_path).await?;
+            let data = client.request(Request::OutboxList).await?;
+            print_json(&data);
+            Ok(())
+        }
+        Command::OutboxCancel { message_id } => {
+            let mut client = connect(&socket_path).await?;
+            client.request(Request::OutboxCancel { message_id }).await?;
+            println!("Cancelled.");
+            Ok(())
+        }
         Command::Health => {
             let mut client = connect(&socket_path).await?;
             let data = client.request(Request::Health).await?;
             print_json(&data);
             Ok(())
         }
+        Command::Ping {
+            target,
+            timeout_secs,
+        } => {
+            let mut client = connect(&socket_path).await?;
+            let data = client
+                .request(Request::Ping {
+                    to: target,
+                    timeout_ms: Some(timeout_secs.saturating_mul(1000)),
+                })
+                .await?;
+            print_json(&data);
+            Ok(())
+        }
+        Command::RelayUsage { hours } => {
+            let mut client = connect(&socket_path).await?;
+            let since_ms =
+                hours.map(|h| agentbook_crypto::time::now_ms().saturating_sub(h * 60 * 60 * 1000));
+            let data = client.request(Request::RelayUsage { since_ms }).await?;
+            print_json(&data);
+            Ok(())
+        }
+        Command::Events { follow, limit } => {
+            let mut client = connect(&socket_path).await?;
+            cmd_events(&mut client, follow, limit).await
+        }
+        Command::Clients => {
+            let mut client = connect(&socket_path).await?;
+            let data = client.request(Request::Clients).await?;
+            print_json(&data);
+            Ok(())
+        }
+        Command::Freeze => {
+            let mut client = connect(&socket_path).await?;
+            client.request(Request::Freeze).await?;
+            println!("Frozen. Only reads are served until `agentbook unfreeze`.");
+            Ok(())
+        }
+        Command::Unfreeze => {
+            let mut client = connect(&socket_path).await?;
+            client.request(Request::Unfreeze).await?;
+            println!("Unfrozen.");
+            Ok(())
+        }
+        Command::Share { ttl_secs } => {
+            let mut client = connect(&socket_path).await?;
+            let data = client.request(Request::Share { ttl_secs }).await?;
+            let share: ShareInfo = serde_json::from_value(data.unwrap_or_default())?;
+            let now_ms = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_millis() as u64;
+            let minutes = share.expires_at_ms.saturating_sub(now_ms).div_ceil(60_000);
+            println!("Read-only access for the next {minutes} min.");
+            println!("The other user runs, for example:");
+            println!(
+                "  AGENTBOOK_SHARE={}:{} agentbook inbox",
+                share.node_id, share.token
+            );
+            Ok(())
+        }
+        Command::Unshare => {
+            let mut client = connect(&socket_path).await?;
+            let data = client.request(Request::Unshare).await?;
+            print_json(&data);
+            Ok(())
+        }
+        Command::Disconnect { client_id } => {
+            let mut client = connect(&socket_path).await?;
+            client.request(Request::Disconnect { client_id }).await?;
+            println!("Disconnected client {client_id}.");
+            Ok(())
+        }
 
         // -- Wallet commands --
         Command::Wallet { yolo } => {
@@ -626,6 +1167,19 @@ async fn main() -> Result<()> {
             ServiceAction::Status => service::cmd_service_status(),
         },
 
+        Command::State { action } => match action {
+            StateAction::Reencrypt => {
+                let mut client = connect(&socket_path).await?;
+                let data = client.request(Request::ReencryptState).await?;
+                print_json(&data);
+                Ok(())
+            }
+            StateAction::Export { output, state_dir } => {
+                bundle::cmd_export(state_dir, output, &socket_path)
+            }
+            StateAction::Import { bundle, state_dir } => bundle::cmd_import(bundle, state_dir),
+        },
+
         Command::Agent { action } => match action {
             AgentAction::Start {
                 state_dir,
@@ -640,7 +1194,41 @@ async fn main() -> Result<()> {
     }
 }
 
+/// Read a file to attach to a DM, guessing its MIME type from the extension.
+fn read_attachment(path: &std::path::Path) -> Result<Attachment> {
+    use base64::Engine;
+    let data = std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let name = path
+        .file_name()
+        .with_context(|| format!("{} has no file name", path.display()))?
+        .to_string_lossy()
+        .into_owned();
+    let mime = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => "application/json",
+        Some("txt" | "log") => "text/plain",
+        Some("md") => "text/markdown",
+        Some("patch" | "diff") => "text/x-diff",
+        Some("csv") => "text/csv",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("pdf") => "application/pdf",
+        _ => "application/octet-stream",
+    };
+    Ok(Attachment {
+        name,
+        mime: mime.to_string(),
+        data_b64: base64::engine::general_purpose::STANDARD.encode(data),
+    })
+}
+
 async fn connect(socket_path: &std::path::Path) -> Result<NodeClient> {
+    #[cfg(target_os = "linux")]
+    if let Ok(share) = std::env::var("AGENTBOOK_SHARE") {
+        let (node_id, token) = share
+            .split_once(':')
+            .context("AGENTBOOK_SHARE must be <node_id>:<token>")?;
+        return NodeClient::connect_view(node_id, token).await;
+    }
     NodeClient::connect(socket_path).await.with_context(|| {
         format!(
             "failed to connect to node at {}. Is the daemon running? Try: agentbook up",
@@ -649,6 +1237,33 @@ async fn connect(socket_path: &std::path::Path) -> Result<NodeClient> {
     })
 }
 
+/// How often `events --follow` asks for new entries.
+const EVENTS_POLL: Duration = Duration::from_secs(1);
+
+async fn cmd_events(client: &mut NodeClient, follow: bool, limit: usize) -> Result<()> {
+    let mut after_seq = None;
+    let mut limit = Some(limit);
+    loop {
+        let data = client
+            .request(Request::DaemonLog { after_seq, limit })
+            .await?;
+        let entries: Vec<DaemonLogEntry> = serde_json::from_value(data.unwrap_or_default())?;
+        for entry in &entries {
+            println!("{}", serde_json::to_string(entry)?);
+        }
+        if let Some(last) = entries.last() {
+            after_seq = Some(last.seq);
+        }
+        if !follow {
+            return Ok(());
+        }
+        // After the initial backlog, take everything new.
+        limit = Some(usize::MAX);
+        after_seq.get_or_insert(0);
+        tokio::time::sleep(EVENTS_POLL).await;
+    }
+}
+
 pub(crate) async fn wait_for_node_socket_ready(
     socket_path: &std::path::Path,
     child: &mut std::process::Child,
@@ -682,12 +1297,15 @@ pub(crate) async fn wait_for_node_socket_ready(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn cmd_up(
     socket_path: &std::path::Path,
     foreground: bool,
     state_dir: Option<PathBuf>,
     relay_host: Vec<String>,
     no_relay: bool,
+    auto_relays: Option<(usize, String)>,
+    privacy_mode: bool,
     rpc_url: Option<String>,
     yolo: bool,
 ) -> Result<()> {
@@ -736,6 +1354,13 @@ async fn cmd_up(
             cmd.arg("--relay-host").arg(host);
         }
     }
+    if let Some((count, signer)) = &auto_relays {
+        cmd.arg("--auto-relays").arg(count.to_string());
+        cmd.arg("--relay-directory-signer").arg(signer);
+    }
+    if privacy_mode {
+        cmd.arg("--privacy-mode");
+    }
     if let Some(ref url) = rpc_url {
         cmd.arg("--rpc-url").arg(url);
     }
@@ -993,29 +1618,7 @@ async fn cmd_agent_unlock(state_dir: Option<PathBuf>) -> Result<()> {
         agentbook_mesh::state_dir::default_state_dir().expect("failed to determine state dir")
     });
     let recovery_key_path = resolved_state_dir.join("recovery.key");
-
-    // Try 1Password first.
-    let op_title = agentbook_wallet::onepassword::item_title_from_state_dir(&resolved_state_dir);
-    let passphrase = if let Some(ref title) = op_title
-        && agentbook_wallet::onepassword::has_op_cli()
-        && agentbook_wallet::onepassword::has_agentbook_item(title)
-    {
-        eprintln!("  \x1b[1;36m1Password detected — reading passphrase...\x1b[0m");
-        match agentbook_wallet::onepassword::read_passphrase(title) {
-            Ok(p) => {
-                eprintln!("  \x1b[1;32mGot passphrase from 1Password.\x1b[0m");
-                p
-            }
-            Err(_) => {
-                eprintln!(
-                    "  \x1b[1;33m1Password read failed. Falling back to manual entry.\x1b[0m"
-                );
-                rpassword::prompt_password("  Enter passphrase: ")?
-            }
-        }
-    } else {
-        rpassword::prompt_password("  Enter passphrase: ")?
-    };
+    let passphrase = read_passphrase(&resolved_state_dir)?;
 
     // Verify passphrase locally before sending to agent.
     agentbook_mesh::recovery::load_recovery_key(&recovery_key_path, &passphrase)
@@ -1042,6 +1645,33 @@ async fn cmd_agent_unlock(state_dir: Option<PathBuf>) -> Result<()> {
     Ok(())
 }
 
+/// Read the recovery key passphrase from 1Password when available, else prompt.
+pub(crate) fn read_passphrase(state_dir: &std::path::Path) -> Result<String> {
+    // Try 1Password first.
+    let op_title = agentbook_wallet::onepassword::item_title_from_state_dir(state_dir);
+    let passphrase = if let Some(ref title) = op_title
+        && agentbook_wallet::onepassword::has_op_cli()
+        && agentbook_wallet::onepassword::has_agentbook_item(title)
+    {
+        eprintln!("  \x1b[1;36m1Password detected — reading passphrase...\x1b[0m");
+        match agentbook_wallet::onepassword::read_passphrase(title) {
+            Ok(p) => {
+                eprintln!("  \x1b[1;32mGot passphrase from 1Password.\x1b[0m");
+                p
+            }
+            Err(_) => {
+                eprintln!(
+                    "  \x1b[1;33m1Password read failed. Falling back to manual entry.\x1b[0m"
+                );
+                rpassword::prompt_password("  Enter passphrase: ")?
+            }
+        }
+    } else {
+        rpassword::prompt_password("  Enter passphrase: ")?
+    };
+    Ok(passphrase)
+}
+
 /// Exec the TUI binary, replacing the current process (zero overhead, correct TTY).
 fn exec_tui(socket: Option<PathBuf>) -> Result<()> {
     use std::os::unix::process::CommandExt;
@@ -1131,6 +1761,31 @@ fn read_otp_auto_or_prompt() -> Result<String> {
     Ok(otp.trim().to_string())
 }
 
+/// Parse `CLASS=CAPACITY/PER_SECOND`; the node validates the class name.
+fn parse_ingress_budget(s: &str) -> Result<IngressBudget, String> {
+    let (class, budget) = s
+        .split_once('=')
+        .ok_or("expected CLASS=CAPACITY/PER_SECOND")?;
+    let (capacity, per_second) = budget
+        .split_once('/')
+        .ok_or("expected CLASS=CAPACITY/PER_SECOND")?;
+    Ok(IngressBudget {
+        message_class: class.to_string(),
+        capacity: capacity
+            .parse()
+            .map_err(|_| format!("invalid capacity {capacity:?}"))?,
+        per_second: per_second
+            .parse()
+            .map_err(|_| format!("invalid rate {per_second:?}"))?,
+    })
+}
+
+/// Parse `NAME=VALUE`; the value may itself contain `=`.
+fn parse_var(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s.split_once('=').ok_or("expected NAME=VALUE")?;
+    Ok((name.to_string(), value.to_string()))
+}
+
 fn print_json(data: &Option<serde_json::Value>) {
     if let Some(v) = data {
         println!("{}", serde_json::to_string_pretty(v).unwrap());
@@ -1,11 +1,15 @@
 use agentbook_crypto::recovery::key_to_mnemonic;
 use agentbook_mesh::identity::NodeIdentity;
+use agentbook_mesh::proxy::Proxy;
 use agentbook_mesh::recovery;
 use agentbook_mesh::state_dir::{default_state_dir, ensure_state_dir};
+use agentbook_mesh::transport::{RelaySecurity, connect_relay, relay_endpoint};
 use agentbook_proto::host::v1 as host_pb;
 use agentbook_proto::host::v1::host_service_client::HostServiceClient;
 use anyhow::{Context, Result};
 use std::path::PathBuf;
+use std::sync::Arc;
+use tonic::transport::Channel;
 
 /// Run interactive first-time setup.
 pub async fn cmd_setup(yolo: bool, state_dir: Option<PathBuf>) -> Result<()> {
@@ -64,7 +68,7 @@ pub async fn cmd_setup(yolo: bool, state_dir: Option<PathBuf>) -> Result<()> {
 
     // Step 3: Node identity
     let identity =
-        NodeIdentity::load_or_create(&state_dir, &kek).context("failed to create identity")?;
+        NodeIdentity::load_or_derive(&state_dir, &kek).context("failed to create identity")?;
     eprintln!("  \x1b[1;36mNode ID:\x1b[0m {}", identity.node_id);
     eprintln!();
 
@@ -104,7 +108,7 @@ fn generate_passphrase() -> String {
 }
 
 /// Prompt for a new passphrase with confirmation and 8+ char minimum.
-fn prompt_new_passphrase() -> Result<String> {
+pub(crate) fn prompt_new_passphrase() -> Result<String> {
     loop {
         let pass1 = rpassword::prompt_password("  Enter passphrase: ")
             .context("failed to read passphrase")?;
@@ -162,7 +166,7 @@ fn display_and_prompt_mnemonic(key: &[u8; 32], label: &str) -> Option<String> {
 }
 
 /// Print formatted mnemonic words to stderr.
-fn print_mnemonic_words(mnemonic: &str, label: &str) {
+pub(crate) fn print_mnemonic_words(mnemonic: &str, label: &str) {
     eprintln!("  {label} (24 words):");
     eprintln!();
     let words: Vec<&str> = mnemonic.split_whitespace().collect();
@@ -233,7 +237,11 @@ fn run_totp_setup_op(
 }
 
 /// Interactive TOTP setup: show QR code, verify a code from the authenticator.
-fn run_totp_setup(state_dir: &std::path::Path, kek: &[u8; 32], node_id: &str) -> Result<()> {
+pub(crate) fn run_totp_setup(
+    state_dir: &std::path::Path,
+    kek: &[u8; 32],
+    node_id: &str,
+) -> Result<()> {
     eprintln!("  \x1b[1;36m=== TOTP Authenticator Setup ===\x1b[0m");
     eprintln!("  Setting up two-factor authentication for wallet transactions.");
     eprintln!();
@@ -279,16 +287,25 @@ fn run_totp_setup(state_dir: &std::path::Path, kek: &[u8; 32], node_id: &str) ->
     }
 }
 
+/// Connect to the default relay, through the proxy from the environment
+/// (`AGENTBOOK_PROXY`, `HTTPS_PROXY`, `ALL_PROXY`) if one is set.
+pub(crate) async fn connect_default_relay() -> Result<HostServiceClient<Channel>> {
+    let security = RelaySecurity {
+        proxy: Proxy::configured(None)?.map(Arc::new),
+        ..RelaySecurity::default()
+    };
+    let endpoint = relay_endpoint(agentbook::DEFAULT_RELAY_HOST);
+    connect_relay(&endpoint, &security).await
+}
+
 /// Prompt for a username and register it on the relay host.
 /// Keeps prompting until a valid, available username is chosen.
-async fn register_username_interactive(identity: &NodeIdentity) -> Result<()> {
+pub(crate) async fn register_username_interactive(identity: &NodeIdentity) -> Result<()> {
     eprintln!("  \x1b[1;36m=== Username Registration ===\x1b[0m");
     eprintln!("  Choose a username for the agentbook network.");
     eprintln!();
 
-    let relay_endpoint = agentbook_mesh::transport::relay_endpoint(agentbook::DEFAULT_RELAY_HOST);
-
-    let mut client = match HostServiceClient::connect(relay_endpoint.clone()).await {
+    let mut client = match connect_default_relay().await {
         Ok(c) => c,
         Err(e) => {
             eprintln!(
@@ -119,6 +119,37 @@ pub fn evm_address_from_public_key(public_key: &PublicKey) -> String {
     address
 }
 
+/// Whether a base64 SEC1 public key derives to the given EVM-style node ID.
+pub fn public_key_matches_node_id(public_key_b64: &str, node_id: &str) -> bool {
+    base64::engine::general_purpose::STANDARD
+        .decode(public_key_b64)
+        .ok()
+        .and_then(|bytes| PublicKey::from_sec1_bytes(&bytes).ok())
+        .is_some_and(|pk| evm_address_from_public_key(&pk).eq_ignore_ascii_case(node_id))
+}
+
+/// Payload a node signs when registering with a relay. Binding the timestamp
+/// lets the relay reject replays of an old registration.
+pub fn relay_registration_payload(node_id: &str, timestamp_ms: u64) -> Vec<u8> {
+    format!("agentbook-relay-register-v1:{node_id}:{timestamp_ms}").into_bytes()
+}
+
+/// Payload a node signs to fetch its usage report from a relay, so only the
+/// node itself can read it.
+pub fn relay_usage_payload(node_id: &str, timestamp_ms: u64) -> Vec<u8> {
+    format!("agentbook-relay-usage-v1:{node_id}:{timestamp_ms}").into_bytes()
+}
+
+/// Payload an operator signs to publish a relay directory. Hosts are joined
+/// in order, so reordering the list breaks the signature too.
+pub fn relay_directory_payload(hosts: &[String], issued_at_ms: u64) -> Vec<u8> {
+    format!(
+        "agentbook-relay-directory-v1:{issued_at_ms}:{}",
+        hosts.join(",")
+    )
+    .into_bytes()
+}
+
 /// Generate cryptographically random key material.
 pub fn random_key_material() -> [u8; ENVELOPE_KEY_BYTES] {
     let mut out = [0u8; ENVELOPE_KEY_BYTES];
@@ -126,6 +157,16 @@ pub fn random_key_material() -> [u8; ENVELOPE_KEY_BYTES] {
     out
 }
 
+/// Compare secret tokens without exiting early on the first differing byte.
+pub fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,6 +191,13 @@ mod tests {
         assert!(!verify_signature(&pub_b64, b"wrong", &sig));
     }
 
+    #[test]
+    fn token_comparison() {
+        assert!(token_matches("abc123", "abc123"));
+        assert!(!token_matches("abc124", "abc123"));
+        assert!(!token_matches("abc", "abc123"));
+    }
+
     #[test]
     fn evm_address_format() {
         let secret = SecretKey::random(&mut OsRng);
@@ -157,4 +205,17 @@ mod tests {
         assert!(addr.starts_with("0x"));
         assert_eq!(addr.len(), 42);
     }
+
+    #[test]
+    fn public_key_matches_own_node_id_only() {
+        let secret = SecretKey::random(&mut OsRng);
+        let public = secret.public_key();
+        let pub_b64 = base64::engine::general_purpose::STANDARD.encode(public.to_sec1_bytes());
+        let node_id = evm_address_from_public_key(&public);
+        assert!(public_key_matches_node_id(&pub_b64, &node_id));
+
+        let other = evm_address_from_public_key(&SecretKey::random(&mut OsRng).public_key());
+        assert!(!public_key_matches_node_id(&pub_b64, &other));
+        assert!(!public_key_matches_node_id("not-a-key", &node_id));
+    }
 }
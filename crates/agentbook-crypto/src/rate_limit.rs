@@ -1,4 +1,7 @@
+use crate::time::{Clock, SystemClock};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 /// Ban escalation schedule: 1min -> 10min -> 1hr -> 1day -> 1week -> 1month -> 1year.
@@ -23,6 +26,7 @@ pub struct RateLimiter {
     capacity: u32,
     refill_rate: f64,
     ban_threshold: u32,
+    clock: Arc<dyn Clock>,
 }
 
 struct Bucket {
@@ -40,6 +44,44 @@ struct BanEntry {
     times_banned: u32,
 }
 
+/// Serializable state of a [`RateLimiter`], with times as Unix milliseconds so
+/// it can be restored after a restart.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RateLimiterSnapshot {
+    #[serde(default)]
+    buckets: HashMap<String, BucketSnapshot>,
+    #[serde(default)]
+    bans: HashMap<String, BanSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BucketSnapshot {
+    tokens: f64,
+    last_refill_ms: u64,
+    violations: u32,
+    times_banned: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BanSnapshot {
+    banned_at_ms: u64,
+    duration_ms: u64,
+    times_banned: u32,
+}
+
+/// Current budget of one key, for diagnostics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyUsage {
+    pub key: String,
+    /// Tokens available right now (refill applied).
+    pub tokens: f64,
+    /// Consecutive violations since the last allowed check.
+    pub violations: u32,
+    pub times_banned: u32,
+    /// Time left on an active ban.
+    pub banned_for: Option<Duration>,
+}
+
 /// Look up ban duration from the escalation schedule.
 fn ban_duration_for(times_banned: u32) -> Duration {
     let idx = (times_banned as usize).min(BAN_DURATIONS.len() - 1);
@@ -61,9 +103,16 @@ impl RateLimiter {
             capacity,
             refill_rate: per_second,
             ban_threshold: 10,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Use `clock` for refills and ban expiry instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Create with a custom violation threshold (for testing).
     #[cfg(test)]
     fn with_threshold(capacity: u32, per_second: f64, threshold: u32) -> Self {
@@ -73,12 +122,13 @@ impl RateLimiter {
             capacity,
             refill_rate: per_second,
             ban_threshold: threshold,
+            clock: Arc::new(SystemClock),
         }
     }
 
     /// Check if the key is allowed to proceed.
     pub fn check(&mut self, key: &str) -> CheckResult {
-        let now = Instant::now();
+        let now = self.clock.now();
 
         // Fast path: check if banned
         if let Some(ban) = self.bans.get(key) {
@@ -155,7 +205,7 @@ impl RateLimiter {
 
     /// Remove stale entries that have been idle for a long time.
     pub fn cleanup(&mut self, max_idle_secs: f64) {
-        let now = Instant::now();
+        let now = self.clock.now();
         self.buckets.retain(|_, bucket| {
             now.duration_since(bucket.last_refill).as_secs_f64() < max_idle_secs
         });
@@ -163,6 +213,151 @@ impl RateLimiter {
             .retain(|_, ban| now.duration_since(ban.banned_at) < ban.duration);
     }
 
+    /// Burst size of each key's bucket.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Sustained rate in tokens per second.
+    pub fn refill_rate(&self) -> f64 {
+        self.refill_rate
+    }
+
+    /// Change the bucket parameters in place. Tracked keys keep their
+    /// counters and bans; balances above the new capacity are cut down to it.
+    pub fn set_budget(&mut self, capacity: u32, per_second: f64) {
+        self.capacity = capacity;
+        self.refill_rate = per_second;
+        for bucket in self.buckets.values_mut() {
+            bucket.tokens = bucket.tokens.min(capacity as f64);
+        }
+    }
+
+    /// Budget of every tracked key, sorted by key. Read-only: refill is
+    /// computed but not applied.
+    pub fn usage(&self) -> Vec<KeyUsage> {
+        let now = self.clock.now();
+        let cap = self.capacity as f64;
+        let mut keys: Vec<&String> = self.buckets.keys().chain(self.bans.keys()).collect();
+        keys.sort();
+        keys.dedup();
+
+        keys.into_iter()
+            .map(|key| {
+                let bucket = self.buckets.get(key);
+                let ban = self.bans.get(key);
+                let tokens = bucket.map_or(cap, |b| {
+                    let elapsed = now.duration_since(b.last_refill).as_secs_f64();
+                    (b.tokens + elapsed * self.refill_rate).min(cap)
+                });
+                let banned_for = ban.and_then(|ban| {
+                    ban.duration
+                        .checked_sub(now.duration_since(ban.banned_at))
+                        .filter(|left| !left.is_zero())
+                });
+                KeyUsage {
+                    key: key.clone(),
+                    tokens,
+                    violations: bucket.map_or(0, |b| b.violations),
+                    times_banned: ban
+                        .map(|ban| ban.times_banned)
+                        .or(bucket.map(|b| b.times_banned))
+                        .unwrap_or(0),
+                    banned_for,
+                }
+            })
+            .collect()
+    }
+
+    /// Capture buckets and bans for persistence.
+    pub fn snapshot(&self) -> RateLimiterSnapshot {
+        let now = self.clock.now();
+        let now_ms = self.clock.now_ms();
+        let ms_ago =
+            |then: Instant| now_ms.saturating_sub(now.duration_since(then).as_millis() as u64);
+
+        RateLimiterSnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .map(|(key, b)| {
+                    (
+                        key.clone(),
+                        BucketSnapshot {
+                            tokens: b.tokens,
+                            last_refill_ms: ms_ago(b.last_refill),
+                            violations: b.violations,
+                            times_banned: b.times_banned,
+                        },
+                    )
+                })
+                .collect(),
+            bans: self
+                .bans
+                .iter()
+                .map(|(key, ban)| {
+                    (
+                        key.clone(),
+                        BanSnapshot {
+                            banned_at_ms: ms_ago(ban.banned_at),
+                            duration_ms: ban.duration.as_millis() as u64,
+                            times_banned: ban.times_banned,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Replace all state with a snapshot taken by [`RateLimiter::snapshot`].
+    ///
+    /// Time that passed while the snapshot was on disk counts towards refills
+    /// and ban expiry. Bans that ran out are dropped, but their escalation
+    /// count is kept.
+    pub fn restore(&mut self, snapshot: RateLimiterSnapshot) {
+        let now = self.clock.now();
+        let now_ms = self.clock.now_ms();
+        let cap = self.capacity as f64;
+
+        self.buckets = snapshot
+            .buckets
+            .into_iter()
+            .map(|(key, b)| {
+                let idle_secs = now_ms.saturating_sub(b.last_refill_ms) as f64 / 1000.0;
+                let bucket = Bucket {
+                    tokens: (b.tokens + idle_secs * self.refill_rate).min(cap),
+                    last_refill: now,
+                    violations: b.violations,
+                    times_banned: b.times_banned,
+                };
+                (key, bucket)
+            })
+            .collect();
+
+        self.bans.clear();
+        for (key, ban) in snapshot.bans {
+            let elapsed_ms = now_ms.saturating_sub(ban.banned_at_ms);
+            if elapsed_ms < ban.duration_ms {
+                self.bans.insert(
+                    key,
+                    BanEntry {
+                        banned_at: now,
+                        duration: Duration::from_millis(ban.duration_ms - elapsed_ms),
+                        times_banned: ban.times_banned,
+                    },
+                );
+            } else {
+                let bucket = self.buckets.entry(key).or_insert(Bucket {
+                    tokens: cap,
+                    last_refill: now,
+                    violations: 0,
+                    times_banned: 0,
+                });
+                bucket.times_banned = bucket.times_banned.max(ban.times_banned);
+            }
+        }
+    }
+
     #[allow(dead_code)]
     pub fn len(&self) -> usize {
         self.buckets.len()
@@ -306,6 +501,23 @@ mod tests {
         assert_eq!(rl.banned_count(), 0);
     }
 
+    #[test]
+    fn ban_expires_with_manual_clock() {
+        let clock = Arc::new(crate::time::ManualClock::new());
+        let mut rl = RateLimiter::with_threshold(1, 0.001, 2).with_clock(clock.clone());
+
+        assert_eq!(rl.check("a"), CheckResult::Allowed);
+        assert_eq!(rl.check("a"), CheckResult::RateLimited);
+        assert!(matches!(rl.check("a"), CheckResult::Banned { .. }));
+
+        clock.advance(Duration::from_secs(59));
+        assert!(matches!(rl.check("a"), CheckResult::Banned { .. }));
+
+        // Past the one-minute ban, and enough refill time for a fresh token.
+        clock.advance(Duration::from_secs(1_000));
+        assert_eq!(rl.check("a"), CheckResult::Allowed);
+    }
+
     #[test]
     fn other_keys_unaffected_by_ban() {
         let mut rl = RateLimiter::with_threshold(1, 0.001, 2);
@@ -316,4 +528,37 @@ mod tests {
 
         assert_eq!(rl.check("b"), CheckResult::Allowed);
     }
+
+    #[test]
+    fn snapshot_survives_restart() {
+        let clock = Arc::new(crate::time::ManualClock::new());
+        let mut rl = RateLimiter::with_threshold(2, 0.001, 2).with_clock(clock.clone());
+        assert_eq!(rl.check("a"), CheckResult::Allowed);
+        assert_eq!(rl.check("b"), CheckResult::Allowed);
+        assert_eq!(rl.check("b"), CheckResult::Allowed);
+        assert_eq!(rl.check("b"), CheckResult::RateLimited);
+        assert!(matches!(rl.check("b"), CheckResult::Banned { .. }));
+
+        let json = serde_json::to_string(&rl.snapshot()).unwrap();
+        clock.advance(Duration::from_secs(30));
+        let mut restored = RateLimiter::with_threshold(2, 0.001, 2).with_clock(clock.clone());
+        restored.restore(serde_json::from_str(&json).unwrap());
+
+        // "a" keeps its spent token; "b" is still banned for the rest of the minute.
+        let usage = restored.usage();
+        assert_eq!(usage[0].key, "a");
+        assert!(usage[0].tokens < 1.1);
+        match restored.check("b") {
+            CheckResult::Banned { remaining } => assert_eq!(remaining.as_secs(), 30),
+            other => panic!("expected Banned, got {other:?}"),
+        }
+
+        // Once the ban has run out on disk, the escalation count carries over.
+        clock.advance(Duration::from_secs(60));
+        let mut later = RateLimiter::with_threshold(2, 0.001, 2).with_clock(clock.clone());
+        later.restore(serde_json::from_str(&json).unwrap());
+        assert_eq!(later.banned_count(), 0);
+        let b = later.usage().into_iter().find(|u| u.key == "b").unwrap();
+        assert_eq!((b.times_banned, b.banned_for), (1, None));
+    }
 }
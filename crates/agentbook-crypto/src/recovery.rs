@@ -43,6 +43,14 @@ pub fn derive_key_from_passphrase(passphrase: &str, salt: &[u8]) -> Result<[u8;
 ///
 /// Returns the raw 32-byte KEK wrapped in `Zeroizing` so it is wiped on drop.
 pub fn create_recovery_key(path: &Path, passphrase: &str) -> Result<Zeroizing<[u8; 32]>> {
+    let kek = Zeroizing::new(random_key_material());
+    save_recovery_key(path, passphrase, &kek)?;
+    Ok(kek)
+}
+
+/// Encrypt an existing recovery key (e.g. one restored from its mnemonic)
+/// with the passphrase and save it to disk.
+pub fn save_recovery_key(path: &Path, passphrase: &str, kek: &[u8; 32]) -> Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).with_context(|| {
             format!(
@@ -52,9 +60,7 @@ pub fn create_recovery_key(path: &Path, passphrase: &str) -> Result<Zeroizing<[u
         })?;
     }
 
-    let kek = Zeroizing::new(random_key_material());
-    save_encrypted_recovery_key(path, passphrase, &kek)?;
-    Ok(kek)
+    save_encrypted_recovery_key(path, passphrase, kek)
 }
 
 /// Load and decrypt a recovery key from disk using the passphrase.
@@ -307,4 +313,19 @@ mod tests {
         let loaded_key = load_recovery_key(&path, passphrase).unwrap();
         assert_eq!(restored_key, *loaded_key);
     }
+
+    #[test]
+    fn restored_key_saves_under_new_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let original_key = create_recovery_key(&dir.path().join("old.key"), "old-pass").unwrap();
+        let restored_key = mnemonic_to_key(&key_to_mnemonic(&original_key).unwrap()).unwrap();
+
+        let path = dir.path().join("new").join("recovery.key");
+        save_recovery_key(&path, "new-passphrase", &restored_key).unwrap();
+        assert_eq!(
+            *load_recovery_key(&path, "new-passphrase").unwrap(),
+            *original_key
+        );
+        assert!(load_recovery_key(&path, "old-pass").is_err());
+    }
 }
@@ -1,3 +1,6 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
 /// Returns the current time as milliseconds since the Unix epoch.
 pub fn now_ms() -> u64 {
     std::time::SystemTime::now()
@@ -6,6 +9,107 @@ pub fn now_ms() -> u64 {
         .as_millis() as u64
 }
 
+/// Source of wall-clock and monotonic time.
+///
+/// Code that enforces TTLs, backoff or rate limits takes a `Clock` so tests
+/// can move time forward instantly instead of sleeping.
+pub trait Clock: Send + Sync {
+    /// Milliseconds since the Unix epoch.
+    fn now_ms(&self) -> u64;
+    /// Monotonic instant for measuring elapsed time.
+    fn now(&self) -> Instant;
+}
+
+/// The real system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        now_ms()
+    }
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock driven by tokio's timer, so `tokio::time::pause`/`advance` (or
+/// `#[tokio::test(start_paused = true)]`) also move `now_ms`.
+#[derive(Debug, Clone, Copy)]
+pub struct TokioClock {
+    start_ms: u64,
+    start: tokio::time::Instant,
+}
+
+impl TokioClock {
+    pub fn new() -> Self {
+        Self {
+            start_ms: now_ms(),
+            start: tokio::time::Instant::now(),
+        }
+    }
+}
+
+impl Default for TokioClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for TokioClock {
+    fn now_ms(&self) -> u64 {
+        self.start_ms + self.start.elapsed().as_millis() as u64
+    }
+
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+}
+
+/// A clock that only moves when told to. Starts at the current wall time.
+#[derive(Debug)]
+pub struct ManualClock {
+    start_ms: u64,
+    start: Instant,
+    offset: Mutex<Duration>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            start_ms: now_ms(),
+            start: Instant::now(),
+            offset: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Move the clock forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        *self.offset.lock().unwrap_or_else(|e| e.into_inner()) += by;
+    }
+
+    fn offset(&self) -> Duration {
+        *self.offset.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_ms(&self) -> u64 {
+        self.start_ms + self.offset().as_millis() as u64
+    }
+
+    fn now(&self) -> Instant {
+        self.start + self.offset()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -17,4 +121,25 @@ mod tests {
         assert!(ms > 1_704_067_200_000);
         assert!(ms < 4_102_444_800_000);
     }
+
+    #[test]
+    fn manual_clock_only_moves_on_advance() {
+        let clock = ManualClock::new();
+        let (ms, instant) = (clock.now_ms(), clock.now());
+        assert_eq!(clock.now_ms(), ms);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now_ms(), ms + 90_000);
+        assert_eq!(clock.now() - instant, Duration::from_secs(90));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tokio_clock_follows_paused_time() {
+        let clock = TokioClock::new();
+        let (ms, instant) = (clock.now_ms(), clock.now());
+
+        tokio::time::advance(Duration::from_secs(3_600)).await;
+        assert_eq!(clock.now_ms(), ms + 3_600_000);
+        assert_eq!(clock.now() - instant, Duration::from_secs(3_600));
+    }
 }
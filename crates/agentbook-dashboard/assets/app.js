@@ -0,0 +1,77 @@
+// Polls /api/overview and redraws the tables. No framework, no build step.
+const POLL_MS = 5000;
+// A dashboard started with --token is opened as /#token=…; the fragment
+// never reaches the server or its logs.
+const TOKEN = new URLSearchParams(location.hash.slice(1)).get("token");
+
+const $ = (id) => document.getElementById(id);
+
+function text(value) {
+  const span = document.createElement("span");
+  span.textContent = value ?? "";
+  return span.innerHTML;
+}
+
+function who(nodeId, username) {
+  const short = nodeId.length > 14 ? `${nodeId.slice(0, 8)}…${nodeId.slice(-4)}` : nodeId;
+  return username
+    ? `@${text(username)} <span class="mono dim">${text(short)}</span>`
+    : `<span class="mono">${text(short)}</span>`;
+}
+
+function table(el, headers, rows) {
+  if (rows.length === 0) {
+    el.innerHTML = '<tr><td class="dim">none</td></tr>';
+    return;
+  }
+  const head = `<tr>${headers.map((h) => `<th>${h}</th>`).join("")}</tr>`;
+  el.innerHTML = head + rows.map((r) => `<tr>${r.map((c) => `<td>${c}</td>`).join("")}</tr>`).join("");
+}
+
+function time(ms) {
+  return new Date(ms).toLocaleString();
+}
+
+function render(o) {
+  $("whoami").innerHTML = who(o.identity.node_id, o.identity.username);
+  $("status").innerHTML = o.health.relay_connected
+    ? '<span class="ok">relay connected</span>'
+    : '<span class="bad">relay disconnected</span>';
+
+  table($("friends"), ["Node", "Following", "Follower"], o.friends.map((f) => [
+    who(f.node_id, f.username),
+    f.following ? "yes" : "",
+    f.follower ? "yes" : "",
+  ]));
+  table($("flows"), ["Peer", "Received", "Unread", "Queued"], o.flows.map((f) => [
+    who(f.node_id, f.username), f.received, f.unread, f.queued,
+  ]));
+  table($("rooms"), ["Room", "Secure"], o.rooms.map((r) => [
+    `#${text(r.room)}`, r.secure ? "yes" : "",
+  ]));
+  table($("outbox"), ["To", "Attempts", "Last error"], o.outbox.map((m) => [
+    who(m.to_node_id), m.attempts, text(m.last_error),
+  ]));
+  table($("recent"), ["Time", "From", "Kind", "Message"], o.recent.slice(0, 50).map((m) => [
+    time(m.timestamp_ms),
+    who(m.from_node_id, m.from_username),
+    m.room ? `#${text(m.room)}` : text(m.message_type),
+    (m.acked ? "" : "● ") + text(m.body),
+  ]));
+}
+
+async function refresh() {
+  try {
+    const headers = TOKEN ? { Authorization: `Bearer ${TOKEN}` } : {};
+    const resp = await fetch("api/overview", { headers });
+    const body = await resp.json();
+    if (!resp.ok) throw new Error(body.error || resp.statusText);
+    render(body);
+    $("error").textContent = "";
+  } catch (e) {
+    $("error").textContent = e.message;
+  }
+}
+
+refresh();
+setInterval(refresh, POLL_MS);
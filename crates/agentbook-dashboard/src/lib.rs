@@ -0,0 +1,433 @@
+//! Local web dashboard for the node daemon.
+//!
+//! Serves a single static page plus `GET /api/overview`, which queries the
+//! daemon's Unix socket and returns one JSON document: identity and health,
+//! friends (follows, with mutual status), joined rooms, per-peer message
+//! counts from the inbox and the outbound queue. The page polls that endpoint,
+//! so operators can watch the node from a browser instead of the TUI. The
+//! dashboard only reads, but it sees everything the socket owner sees,
+//! decrypted DMs included. Every request's `Host` must name the dashboard
+//! itself (a loopback name or its bind address), so a web page can't read it
+//! through DNS rebinding. Off loopback it also needs a bearer token, which
+//! the page takes from its URL fragment (`/#token=…`).
+
+use agentbook::client::NodeClient;
+use agentbook::protocol::{
+    FollowInfo, HealthStatus, IdentityInfo, InboxEntry, OutboxInfo, Request, RoomInfo,
+};
+use agentbook_crypto::crypto::token_matches;
+use axum::extract::{Request as HttpRequest, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response as HttpResponse};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashSet};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const INDEX_HTML: &str = include_str!("../assets/index.html");
+const APP_JS: &str = include_str!("../assets/app.js");
+
+/// How many recent inbox messages the overview counts and lists.
+const RECENT_MESSAGES: usize = 200;
+
+/// Everything the page shows, in one response.
+#[derive(Debug, Serialize)]
+pub struct Overview {
+    pub identity: IdentityInfo,
+    pub health: HealthStatus,
+    pub friends: Vec<Friend>,
+    pub rooms: Vec<RoomInfo>,
+    /// Message counts per peer, busiest first.
+    pub flows: Vec<Flow>,
+    pub outbox: Vec<OutboxInfo>,
+    /// The newest inbox messages, newest first.
+    pub recent: Vec<InboxEntry>,
+}
+
+/// A node we follow or that follows us.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Friend {
+    pub node_id: String,
+    pub username: Option<String>,
+    pub following: bool,
+    pub follower: bool,
+}
+
+/// Messages exchanged with one peer.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Flow {
+    pub node_id: String,
+    pub username: Option<String>,
+    pub received: usize,
+    pub unread: usize,
+    /// Messages to this peer still waiting in the outbox.
+    pub queued: usize,
+}
+
+impl Overview {
+    /// Combine the raw answers into the page's view of the node.
+    pub fn build(
+        identity: IdentityInfo,
+        health: HealthStatus,
+        following: Vec<FollowInfo>,
+        followers: Vec<FollowInfo>,
+        rooms: Vec<RoomInfo>,
+        recent: Vec<InboxEntry>,
+        outbox: Vec<OutboxInfo>,
+    ) -> Self {
+        let follower_ids: HashSet<&str> = followers.iter().map(|f| f.node_id.as_str()).collect();
+        let following_ids: HashSet<&str> = following.iter().map(|f| f.node_id.as_str()).collect();
+        let mut friends: Vec<Friend> = following
+            .iter()
+            .map(|f| Friend {
+                node_id: f.node_id.clone(),
+                username: f.username.clone(),
+                following: true,
+                follower: follower_ids.contains(f.node_id.as_str()),
+            })
+            .collect();
+        friends.extend(
+            followers
+                .iter()
+                .filter(|f| !following_ids.contains(f.node_id.as_str()))
+                .map(|f| Friend {
+                    node_id: f.node_id.clone(),
+                    username: f.username.clone(),
+                    following: false,
+                    follower: true,
+                }),
+        );
+
+        let mut flows: BTreeMap<String, Flow> = BTreeMap::new();
+        for msg in recent.iter().filter(|m| m.room.is_none()) {
+            let entry = flows
+                .entry(msg.from_node_id.clone())
+                .or_insert_with(|| new_flow(&msg.from_node_id));
+            entry.received += 1;
+            if !msg.acked {
+                entry.unread += 1;
+            }
+            if entry.username.is_none() {
+                entry.username = msg.from_username.clone();
+            }
+        }
+        for queued in &outbox {
+            flows
+                .entry(queued.to_node_id.clone())
+                .or_insert_with(|| new_flow(&queued.to_node_id))
+                .queued += 1;
+        }
+        let mut flows: Vec<Flow> = flows.into_values().collect();
+        for flow in flows.iter_mut().filter(|f| f.username.is_none()) {
+            flow.username = friends
+                .iter()
+                .find(|f| f.node_id == flow.node_id)
+                .and_then(|f| f.username.clone());
+        }
+        flows.sort_by_key(|f| std::cmp::Reverse(f.received + f.queued));
+
+        Self {
+            identity,
+            health,
+            friends,
+            rooms,
+            flows,
+            outbox,
+            recent,
+        }
+    }
+}
+
+fn new_flow(node_id: &str) -> Flow {
+    Flow {
+        node_id: node_id.to_string(),
+        username: None,
+        received: 0,
+        unread: 0,
+        queued: 0,
+    }
+}
+
+/// Query the daemon at `socket_path` for everything in an [`Overview`].
+pub async fn overview(socket_path: &std::path::Path) -> anyhow::Result<Overview> {
+    let mut client = NodeClient::connect(socket_path).await?;
+    Ok(Overview::build(
+        fetch(&mut client, Request::Identity).await?,
+        fetch(&mut client, Request::Health).await?,
+        fetch(&mut client, Request::Following).await?,
+        fetch(&mut client, Request::Followers).await?,
+        fetch(&mut client, Request::ListRooms).await?,
+        fetch(
+            &mut client,
+            Request::Inbox {
+                unread_only: false,
+                limit: Some(RECENT_MESSAGES),
+            },
+        )
+        .await?,
+        fetch(&mut client, Request::OutboxList).await?,
+    ))
+}
+
+async fn fetch<T: DeserializeOwned>(
+    client: &mut NodeClient,
+    request: Request,
+) -> anyhow::Result<T> {
+    let data = client.request(request).await?.unwrap_or_default();
+    Ok(serde_json::from_value(data)?)
+}
+
+/// Where to reach the daemon and who may ask.
+#[derive(Clone)]
+pub struct DashboardState {
+    socket_path: Arc<PathBuf>,
+    token: Option<Arc<str>>,
+    bind: Option<IpAddr>,
+}
+
+impl DashboardState {
+    /// Read the daemon at `socket_path`, answering only to loopback names.
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self {
+            socket_path: Arc::new(socket_path),
+            token: None,
+            bind: None,
+        }
+    }
+
+    /// Require `Authorization: Bearer <token>` on API calls.
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token.map(Into::into);
+        self
+    }
+
+    /// The address the dashboard listens on, also accepted as a `Host`.
+    pub fn with_bind_addr(mut self, bind: IpAddr) -> Self {
+        self.bind = Some(bind);
+        self
+    }
+}
+
+/// Routes for the page and its API.
+pub fn router(state: DashboardState) -> Router {
+    let api = Router::new()
+        .route("/api/overview", get(api_overview))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token));
+    Router::new()
+        .route("/", get(|| async { Html(INDEX_HTML) }))
+        .route(
+            "/app.js",
+            get(|| async { ([(header::CONTENT_TYPE, "text/javascript")], APP_JS) }),
+        )
+        .merge(api)
+        .layer(middleware::from_fn_with_state(state.clone(), require_host))
+        .with_state(state)
+}
+
+/// Whether a `Host` header names this dashboard: `localhost`, a loopback
+/// address, or the address it is bound to (any address when it is bound to
+/// all of them). DNS rebinding shows up as some other name.
+fn host_allowed(host: &str, bind: Option<IpAddr>) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    if name.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    name.parse::<IpAddr>().is_ok_and(|ip| {
+        ip.is_loopback() || bind.is_some_and(|bind| bind.is_unspecified() || bind == ip)
+    })
+}
+
+async fn require_host(
+    State(state): State<DashboardState>,
+    headers: HeaderMap,
+    req: HttpRequest,
+    next: Next,
+) -> HttpResponse {
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    if !host.is_some_and(|host| host_allowed(host, state.bind)) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": "Host does not name this dashboard" })),
+        )
+            .into_response();
+    }
+    next.run(req).await
+}
+
+async fn require_token(
+    State(state): State<DashboardState>,
+    headers: HeaderMap,
+    req: HttpRequest,
+    next: Next,
+) -> HttpResponse {
+    if let Some(expected) = &state.token {
+        let given = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if !given.is_some_and(|given| token_matches(given, expected)) {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "missing or invalid bearer token" })),
+            )
+                .into_response();
+        }
+    }
+    next.run(req).await
+}
+
+async fn api_overview(State(state): State<DashboardState>) -> HttpResponse {
+    match overview(&state.socket_path).await {
+        Ok(overview) => Json(overview).into_response(),
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            Json(serde_json::json!({ "error": format!("{e:#}") })),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentbook::protocol::MessageType;
+
+    fn follow(node_id: &str, username: Option<&str>) -> FollowInfo {
+        FollowInfo {
+            node_id: node_id.to_string(),
+            username: username.map(str::to_string),
+            followed_at_ms: 0,
+            alias: None,
+            groups: vec![],
+        }
+    }
+
+    fn dm(from: &str, acked: bool) -> InboxEntry {
+        InboxEntry {
+            message_id: format!("m-{from}-{acked}"),
+            from_node_id: from.to_string(),
+            from_username: None,
+            to_node_id: None,
+            message_type: MessageType::DmText,
+            body: "hi".to_string(),
+            timestamp_ms: 0,
+            acked,
+            room: None,
+            attachments: Vec::new(),
+            in_reply_to: None,
+            thread_id: None,
+        }
+    }
+
+    #[test]
+    fn overview_merges_friends_and_counts_flows() {
+        let overview = Overview::build(
+            IdentityInfo {
+                node_id: "0xme".to_string(),
+                public_key_b64: String::new(),
+                username: None,
+            },
+            HealthStatus {
+                healthy: true,
+                relay_connected: true,
+                following_count: 2,
+                unread_count: 1,
+                clients: 1,
+                frozen: false,
+                uptime_secs: 60,
+                rss_bytes: None,
+                open_fds: None,
+                outbox_depth: 1,
+                event_backlog: 0,
+                events_lagged: 0,
+            },
+            vec![follow("0xa", Some("alice")), follow("0xb", None)],
+            vec![follow("0xa", Some("alice")), follow("0xc", None)],
+            vec![],
+            vec![dm("0xa", true), dm("0xa", false), dm("0xc", true)],
+            vec![OutboxInfo {
+                message_id: "out".to_string(),
+                to_node_id: "0xb".to_string(),
+                attempts: 1,
+                created_at_ms: 0,
+                next_attempt_ms: 0,
+                last_error: None,
+            }],
+        );
+
+        let status: Vec<(&str, bool, bool)> = overview
+            .friends
+            .iter()
+            .map(|f| (f.node_id.as_str(), f.following, f.follower))
+            .collect();
+        assert_eq!(
+            status,
+            [
+                ("0xa", true, true),
+                ("0xb", true, false),
+                ("0xc", false, true)
+            ]
+        );
+
+        assert_eq!(overview.flows[0].node_id, "0xa");
+        assert_eq!(overview.flows[0].username.as_deref(), Some("alice"));
+        assert_eq!(
+            (overview.flows[0].received, overview.flows[0].unread),
+            (2, 1)
+        );
+        let b = overview.flows.iter().find(|f| f.node_id == "0xb").unwrap();
+        assert_eq!((b.received, b.queued), (0, 1));
+    }
+
+    #[tokio::test]
+    async fn serves_page_and_reports_missing_daemon() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(DashboardState::new(PathBuf::from(
+            "/nonexistent/agentbook.sock",
+        )));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let page = reqwest::get(format!("http://{addr}/")).await.unwrap();
+        assert_eq!(page.status(), 200);
+        assert!(page.text().await.unwrap().contains("app.js"));
+
+        let api = reqwest::get(format!("http://{addr}/api/overview"))
+            .await
+            .unwrap();
+        assert_eq!(api.status(), 502);
+    }
+
+    #[tokio::test]
+    async fn api_checks_host_and_token() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = DashboardState::new(PathBuf::from("/nonexistent/agentbook.sock"))
+            .with_token(Some("s3cret".to_string()));
+        tokio::spawn(async move { axum::serve(listener, router(state)).await });
+        let http = reqwest::Client::new();
+        let url = format!("http://{addr}/api/overview");
+
+        let rebound = http
+            .get(&url)
+            .header("Host", "attacker.example")
+            .bearer_auth("s3cret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(rebound.status(), 403);
+        let anonymous = http.get(&url).send().await.unwrap();
+        assert_eq!(anonymous.status(), 401);
+        // Past both checks, the missing daemon is what fails.
+        let authorized = http.get(&url).bearer_auth("s3cret").send().await.unwrap();
+        assert_eq!(authorized.status(), 502);
+    }
+}
@@ -0,0 +1,68 @@
+use agentbook::client::default_socket_path;
+use agentbook_dashboard::{DashboardState, router};
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about = "Local web dashboard for the agentbook node daemon"
+)]
+struct Args {
+    /// Address to serve the dashboard on.
+    #[arg(long, default_value = "127.0.0.1:8421")]
+    listen: SocketAddr,
+
+    /// Path to the node daemon's Unix socket.
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Require `Authorization: Bearer <token>` on API calls; open the page as
+    /// `/#token=<token>`. Needed to listen beyond loopback.
+    #[arg(long)]
+    token: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "agentbook_dashboard=info".into()),
+        )
+        .init();
+
+    let args = Args::parse();
+    if !args.listen.ip().is_loopback() {
+        if args.token.is_none() {
+            anyhow::bail!(
+                "refusing to serve {} without --token: the dashboard shows decrypted messages",
+                args.listen
+            );
+        }
+        tracing::warn!(addr = %args.listen, "dashboard is exposed beyond loopback");
+    }
+
+    let socket_path = args.socket.unwrap_or_else(default_socket_path);
+    let listener = tokio::net::TcpListener::bind(args.listen)
+        .await
+        .with_context(|| format!("failed to bind {}", args.listen))?;
+    tracing::info!(
+        url = %format!("http://{}", listener.local_addr()?),
+        socket = %socket_path.display(),
+        "dashboard listening"
+    );
+
+    let state = DashboardState::new(socket_path)
+        .with_token(args.token)
+        .with_bind_addr(args.listen.ip());
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .context("dashboard server failed")
+}
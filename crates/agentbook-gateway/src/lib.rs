@@ -0,0 +1,719 @@
+//! HTTP/REST + Server-Sent Events gateway in front of the node daemon.
+//!
+//! Each REST call opens a connection to the daemon's Unix socket, sends one
+//! [`Request`] and maps the answer to JSON. `GET /v1/events` holds a socket
+//! connection open and forwards its events as SSE, so webhooks and scripts
+//! with nothing but `curl` can drive and watch the node. `GET /v1/ws` carries
+//! the socket's JSON-lines protocol itself over a WebSocket, for browser
+//! clients (see `agentbook-wasm`); it only carries the reading and messaging
+//! requests the REST routes offer (see [`bridged`]).
+//!
+//! The gateway acts with the socket owner's rights, so it should stay on
+//! loopback or be given a bearer token (`--token`); the binary refuses a
+//! non-loopback address without one unless told `--insecure-no-token`. Browsers send an
+//! `Origin` header with cross-site calls, and any call that has one is
+//! refused unless the origin is allowed (`--allow-origin`), so a web page the
+//! user happens to have open can't drive the node. Without a token the
+//! `Host` header must also name the gateway itself (a loopback name or the
+//! address it is bound to), so a page can't reach it through DNS rebinding,
+//! where its calls are same-origin and carry no `Origin` at all.
+
+use agentbook::client::NodeClient;
+use agentbook::protocol::{Attachment, Request, RequestEnvelope, Response, ResponseEnvelope};
+use agentbook_crypto::crypto::token_matches;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, Request as HttpRequest, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response as HttpResponse};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::Stream;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Where to reach the daemon and what callers must present.
+#[derive(Clone)]
+pub struct GatewayState {
+    socket_path: PathBuf,
+    token: Option<Arc<str>>,
+    allowed_origins: Arc<[String]>,
+    bind: Option<IpAddr>,
+}
+
+impl GatewayState {
+    /// Proxy to the daemon at `socket_path`. When `token` is set, every call
+    /// must carry `Authorization: Bearer <token>`.
+    pub fn new(socket_path: PathBuf, token: Option<String>) -> Self {
+        Self {
+            socket_path,
+            token: token.map(Into::into),
+            allowed_origins: Arc::new([]),
+            bind: None,
+        }
+    }
+
+    /// Accept browser calls from these origins, e.g. `http://localhost:3000`.
+    /// Calls with any other `Origin` header are refused.
+    pub fn with_allowed_origins(mut self, origins: Vec<String>) -> Self {
+        self.allowed_origins = origins.into();
+        self
+    }
+
+    /// The address the gateway listens on. Without a token, calls must
+    /// name it or a loopback name in their `Host` header.
+    pub fn with_bind_addr(mut self, bind: IpAddr) -> Self {
+        self.bind = Some(bind);
+        self
+    }
+}
+
+/// Whether a `Host` header names this gateway: `localhost`, a loopback
+/// address, or the address it is bound to (any address when it is bound to
+/// all of them). DNS rebinding shows up as some other name.
+fn host_allowed(host: &str, bind: Option<IpAddr>) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    if name.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    name.parse::<IpAddr>().is_ok_and(|ip| {
+        ip.is_loopback() || bind.is_some_and(|bind| bind.is_unspecified() || bind == ip)
+    })
+}
+
+/// Routes under `/v1`.
+pub fn router(state: GatewayState) -> Router {
+    Router::new()
+        .route("/v1/health", get(health))
+        .route("/v1/identity", get(identity))
+        .route("/v1/following", get(following))
+        .route("/v1/follow", post(follow))
+        .route("/v1/messages", get(inbox))
+        .route("/v1/messages/{message_id}/ack", post(inbox_ack))
+        .route("/v1/threads/{thread_id}", get(message_thread))
+        .route("/v1/dm", post(send_dm))
+        .route("/v1/feed", post(post_feed))
+        .route("/v1/rooms", get(list_rooms))
+        .route("/v1/rooms/{room}/messages", get(room_inbox).post(send_room))
+        .route("/v1/events", get(events))
+        .route("/v1/ws", get(ws))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+/// A daemon or connection error, rendered as `{"code", "message"}`.
+pub struct ApiError {
+    status: StatusCode,
+    code: String,
+    message: String,
+}
+
+impl ApiError {
+    fn daemon(code: String, message: String) -> Self {
+        let status = match code.as_str() {
+            "not_found" | "not_joined" => StatusCode::NOT_FOUND,
+            "forbidden" => StatusCode::FORBIDDEN,
+            "draining" | "no_relay" | "relay_unavailable" => StatusCode::SERVICE_UNAVAILABLE,
+            "cooldown" => StatusCode::TOO_MANY_REQUESTS,
+            c if c.starts_with("invalid_") || c == "empty_message" => StatusCode::BAD_REQUEST,
+            _ => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        Self {
+            status,
+            code,
+            message,
+        }
+    }
+
+    fn unavailable(err: anyhow::Error) -> Self {
+        Self {
+            status: StatusCode::BAD_GATEWAY,
+            code: "daemon_unavailable".to_string(),
+            message: format!("{err:#}"),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> HttpResponse {
+        let body = serde_json::json!({ "code": self.code, "message": self.message });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// Check the caller's origin and bearer token, or without a token its
+/// `Host`. Browsers cannot set headers on WebSocket or EventSource requests,
+/// so an `access_token` query parameter also counts.
+async fn require_token(
+    State(state): State<GatewayState>,
+    headers: HeaderMap,
+    req: HttpRequest,
+    next: Next,
+) -> HttpResponse {
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        let allowed = origin
+            .to_str()
+            .is_ok_and(|origin| state.allowed_origins.iter().any(|o| o == origin));
+        if !allowed {
+            return ApiError {
+                status: StatusCode::FORBIDDEN,
+                code: "forbidden_origin".to_string(),
+                message: "origin is not allowed; start the gateway with --allow-origin".to_string(),
+            }
+            .into_response();
+        }
+    }
+    match &state.token {
+        Some(expected) => {
+            let given = headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .or_else(|| {
+                    req.uri()
+                        .query()?
+                        .split('&')
+                        .find_map(|pair| pair.strip_prefix("access_token="))
+                });
+            if !given.is_some_and(|given| token_matches(given, expected)) {
+                return ApiError {
+                    status: StatusCode::UNAUTHORIZED,
+                    code: "unauthorized".to_string(),
+                    message: "missing or invalid bearer token".to_string(),
+                }
+                .into_response();
+            }
+        }
+        None => {
+            let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+            if !host.is_some_and(|host| host_allowed(host, state.bind)) {
+                return ApiError {
+                    status: StatusCode::FORBIDDEN,
+                    code: "forbidden_host".to_string(),
+                    message:
+                        "Host must name the gateway's own address; use --token for other names"
+                            .to_string(),
+                }
+                .into_response();
+            }
+        }
+    }
+    next.run(req).await
+}
+
+/// Send `request` on a fresh daemon connection and return its data.
+async fn call(state: &GatewayState, request: Request) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut client = NodeClient::connect(&state.socket_path)
+        .await
+        .map_err(ApiError::unavailable)?;
+    let request_id = client.send(request).await.map_err(ApiError::unavailable)?;
+    loop {
+        let resp = client
+            .next_response_envelope()
+            .await
+            .map_err(ApiError::unavailable)?;
+        if resp.request_id != Some(request_id) {
+            continue;
+        }
+        match resp.response {
+            Response::Ok { data } => return Ok(Json(data.unwrap_or_default())),
+            Response::Error { code, message } => return Err(ApiError::daemon(code, message)),
+            Response::Hello { .. } | Response::Event { .. } => continue,
+        }
+    }
+}
+
+type ApiResult = Result<Json<serde_json::Value>, ApiError>;
+
+async fn health(State(state): State<GatewayState>) -> ApiResult {
+    call(&state, Request::Health).await
+}
+
+async fn identity(State(state): State<GatewayState>) -> ApiResult {
+    call(&state, Request::Identity).await
+}
+
+async fn following(State(state): State<GatewayState>) -> ApiResult {
+    call(&state, Request::Following).await
+}
+
+#[derive(Deserialize)]
+struct FollowBody {
+    target: String,
+}
+
+async fn follow(State(state): State<GatewayState>, Json(body): Json<FollowBody>) -> ApiResult {
+    call(
+        &state,
+        Request::Follow {
+            target: body.target,
+        },
+    )
+    .await
+}
+
+#[derive(Deserialize)]
+struct InboxQuery {
+    #[serde(default)]
+    unread_only: bool,
+    limit: Option<usize>,
+}
+
+async fn inbox(State(state): State<GatewayState>, Query(q): Query<InboxQuery>) -> ApiResult {
+    call(
+        &state,
+        Request::Inbox {
+            unread_only: q.unread_only,
+            limit: q.limit,
+        },
+    )
+    .await
+}
+
+async fn inbox_ack(State(state): State<GatewayState>, Path(message_id): Path<String>) -> ApiResult {
+    call(&state, Request::InboxAck { message_id }).await
+}
+
+async fn message_thread(
+    State(state): State<GatewayState>,
+    Path(thread_id): Path<String>,
+) -> ApiResult {
+    call(&state, Request::MessageThread { thread_id }).await
+}
+
+#[derive(Deserialize)]
+struct DmBody {
+    to: String,
+    body: String,
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+    #[serde(default)]
+    in_reply_to: Option<String>,
+    #[serde(default)]
+    reply_within_secs: Option<u64>,
+}
+
+async fn send_dm(State(state): State<GatewayState>, Json(dm): Json<DmBody>) -> ApiResult {
+    call(
+        &state,
+        Request::SendDm {
+            to: dm.to,
+            body: dm.body,
+            attachments: dm.attachments,
+            in_reply_to: dm.in_reply_to,
+            reply_within_secs: dm.reply_within_secs,
+        },
+    )
+    .await
+}
+
+#[derive(Deserialize)]
+struct PostBody {
+    body: String,
+}
+
+async fn post_feed(State(state): State<GatewayState>, Json(post): Json<PostBody>) -> ApiResult {
+    call(&state, Request::PostFeed { body: post.body }).await
+}
+
+async fn list_rooms(State(state): State<GatewayState>) -> ApiResult {
+    call(&state, Request::ListRooms).await
+}
+
+#[derive(Deserialize)]
+struct LimitQuery {
+    limit: Option<usize>,
+}
+
+async fn room_inbox(
+    State(state): State<GatewayState>,
+    Path(room): Path<String>,
+    Query(q): Query<LimitQuery>,
+) -> ApiResult {
+    call(
+        &state,
+        Request::RoomInbox {
+            room,
+            limit: q.limit,
+        },
+    )
+    .await
+}
+
+async fn send_room(
+    State(state): State<GatewayState>,
+    Path(room): Path<String>,
+    Json(post): Json<PostBody>,
+) -> ApiResult {
+    call(
+        &state,
+        Request::SendRoom {
+            room,
+            body: post.body,
+        },
+    )
+    .await
+}
+
+/// Stream daemon events as SSE. The SSE `event` field is the event's `kind`
+/// and `data` is its JSON.
+async fn events(
+    State(state): State<GatewayState>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, ApiError> {
+    let client = NodeClient::connect(&state.socket_path)
+        .await
+        .map_err(ApiError::unavailable)?;
+    // The client stays in the stream state: dropping its write half would
+    // close the daemon connection.
+    let stream = futures_util::stream::unfold(client, |mut client| async move {
+        loop {
+            match client.next_response_envelope().await {
+                Ok(envelope) => {
+                    let Response::Event { event } = envelope.response else {
+                        continue;
+                    };
+                    let data = serde_json::to_value(&event).unwrap_or_default();
+                    let kind = data["kind"].as_str().unwrap_or("event").to_string();
+                    let sse = SseEvent::default().event(kind).data(data.to_string());
+                    return Some((Ok(sse), client));
+                }
+                Err(e) => {
+                    tracing::debug!(err = %e, "event stream ended");
+                    return None;
+                }
+            }
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Bridge a WebSocket to a daemon connection. Text messages are
+/// [`RequestEnvelope`]s and replies are [`ResponseEnvelope`]s, exactly as on
+/// the Unix socket, starting with a `hello`.
+async fn ws(
+    ws: WebSocketUpgrade,
+    State(state): State<GatewayState>,
+) -> Result<HttpResponse, ApiError> {
+    let client = NodeClient::connect(&state.socket_path)
+        .await
+        .map_err(ApiError::unavailable)?;
+    Ok(ws.on_upgrade(move |socket| bridge(socket, client)))
+}
+
+/// Requests the WebSocket bridge passes on: what the REST routes can do,
+/// plus the other read-only lookups. Wallet, key, config and lifecycle
+/// requests stay on the Unix socket.
+fn bridged(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::Identity
+            | Request::Health
+            | Request::Following
+            | Request::Followers
+            | Request::Follow { .. }
+            | Request::LookupUsername { .. }
+            | Request::LookupNodeId { .. }
+            | Request::Inbox { .. }
+            | Request::InboxAck { .. }
+            | Request::MessageThread { .. }
+            | Request::PendingReplies
+            | Request::SendDm { .. }
+            | Request::PostFeed { .. }
+            | Request::ListRooms
+            | Request::RoomInbox { .. }
+            | Request::SendRoom { .. }
+    )
+}
+
+async fn bridge(mut socket: WebSocket, client: NodeClient) {
+    let (mut writer, mut reader) = client.into_split();
+    let hello = ResponseEnvelope {
+        request_id: None,
+        // The daemon connection is already negotiated; the browser gets
+        // whatever version it runs at.
+        response: Response::Hello {
+            node_id: writer.node_id().to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_min: writer.protocol(),
+            protocol_max: writer.protocol(),
+            warnings: Vec::new(),
+        },
+    };
+    if !send_envelope(&mut socket, &hello).await {
+        return;
+    }
+
+    // The daemon connection numbers requests itself; map its ids back to the
+    // ones the browser chose.
+    let mut request_ids: HashMap<u64, Option<u64>> = HashMap::new();
+    loop {
+        tokio::select! {
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                let envelope = match serde_json::from_str::<RequestEnvelope>(&text) {
+                    Ok(envelope) => envelope,
+                    Err(e) => {
+                        let resp = ResponseEnvelope {
+                            request_id: None,
+                            response: Response::Error {
+                                code: "invalid_request".to_string(),
+                                message: e.to_string(),
+                            },
+                        };
+                        send_envelope(&mut socket, &resp).await;
+                        continue;
+                    }
+                };
+                if !bridged(&envelope.request) {
+                    let resp = ResponseEnvelope {
+                        request_id: envelope.request_id,
+                        response: Response::Error {
+                            code: "forbidden".to_string(),
+                            message: "request is not available over the gateway".to_string(),
+                        },
+                    };
+                    send_envelope(&mut socket, &resp).await;
+                    continue;
+                }
+                match writer.send_with_id(envelope.request).await {
+                    Ok(id) => {
+                        request_ids.insert(id, envelope.request_id);
+                    }
+                    Err(_) => break,
+                }
+            }
+            resp = reader.next() => {
+                let Some(Ok(mut resp)) = resp else { break };
+                if let Some(id) = resp.request_id {
+                    resp.request_id = request_ids.remove(&id).flatten();
+                }
+                if !send_envelope(&mut socket, &resp).await {
+                    break;
+                }
+            }
+        }
+    }
+    tracing::debug!("websocket client disconnected");
+}
+
+async fn send_envelope(socket: &mut WebSocket, resp: &ResponseEnvelope) -> bool {
+    match serde_json::to_string(resp) {
+        Ok(json) => socket.send(Message::Text(json.into())).await.is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentbook::protocol::{RequestEnvelope, ResponseEnvelope};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    /// Answer every connection with Hello, then reply to each request with
+    /// `reply`.
+    fn fake_daemon(socket_path: &std::path::Path, reply: Response) {
+        let listener = UnixListener::bind(socket_path).unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let reply = reply.clone();
+                tokio::spawn(async move {
+                    let (r, mut w) = stream.into_split();
+                    let hello = Response::Hello {
+                        node_id: "0xnode".to_string(),
+                        version: "test".to_string(),
+                        protocol_min: 1,
+                        protocol_max: 1,
+                        warnings: Vec::new(),
+                    };
+                    let line = serde_json::to_string(&hello).unwrap();
+                    w.write_all(format!("{line}\n").as_bytes()).await.unwrap();
+                    let mut lines = BufReader::new(r).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let req: RequestEnvelope = serde_json::from_str(&line).unwrap();
+                        let resp = ResponseEnvelope {
+                            request_id: req.request_id,
+                            response: reply.clone(),
+                        };
+                        let line = serde_json::to_string(&resp).unwrap();
+                        w.write_all(format!("{line}\n").as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+    }
+
+    async fn serve(state: GatewayState) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router(state)).await });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn rest_calls_proxy_to_the_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("node.sock");
+        fake_daemon(
+            &socket,
+            Response::Ok {
+                data: Some(serde_json::json!({ "healthy": true })),
+            },
+        );
+        let base = serve(GatewayState::new(socket, Some("t0ken".to_string()))).await;
+        let http = reqwest::Client::new();
+
+        let resp = http.get(format!("{base}/v1/health")).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let resp = http
+            .get(format!("{base}/v1/health"))
+            .bearer_auth("t0ken")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["healthy"], true);
+    }
+
+    #[tokio::test]
+    async fn daemon_errors_become_http_statuses() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("node.sock");
+        fake_daemon(
+            &socket,
+            Response::Error {
+                code: "not_found".to_string(),
+                message: "message m1 not found".to_string(),
+            },
+        );
+        let base = serve(GatewayState::new(socket, None)).await;
+
+        let resp = reqwest::Client::new()
+            .post(format!("{base}/v1/messages/m1/ack"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["code"], "not_found");
+    }
+
+    #[tokio::test]
+    async fn missing_daemon_is_bad_gateway() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = serve(GatewayState::new(dir.path().join("none.sock"), None)).await;
+        let resp = reqwest::get(format!("{base}/v1/identity")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn websocket_keeps_client_request_ids() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("node.sock");
+        fake_daemon(&socket, Response::Ok { data: None });
+        let base = serve(GatewayState::new(socket, Some("t0ken".to_string()))).await;
+        let url = format!("{}/v1/ws?access_token=t0ken", base.replace("http", "ws"));
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        let next = |msg: Option<Result<WsMessage, _>>| -> ResponseEnvelope {
+            serde_json::from_str(msg.unwrap().unwrap().to_text().unwrap()).unwrap()
+        };
+        let hello = next(ws.next().await);
+        assert!(matches!(hello.response, Response::Hello { node_id, .. } if node_id == "0xnode"));
+
+        ws.send(WsMessage::Text(
+            r#"{"request_id":42,"type":"health"}"#.into(),
+        ))
+        .await
+        .unwrap();
+        let resp = next(ws.next().await);
+        assert_eq!(resp.request_id, Some(42));
+        assert!(matches!(resp.response, Response::Ok { .. }));
+
+        // Admin requests never reach the daemon.
+        ws.send(WsMessage::Text(
+            r#"{"request_id":43,"type":"shutdown"}"#.into(),
+        ))
+        .await
+        .unwrap();
+        let resp = next(ws.next().await);
+        assert_eq!(resp.request_id, Some(43));
+        assert!(matches!(resp.response, Response::Error { code, .. } if code == "forbidden"));
+    }
+
+    #[tokio::test]
+    async fn cross_site_calls_need_an_allowed_origin() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("node.sock");
+        fake_daemon(&socket, Response::Ok { data: None });
+        let state = GatewayState::new(socket, None)
+            .with_allowed_origins(vec!["http://localhost:3000".to_string()]);
+        let base = serve(state).await;
+        let http = reqwest::Client::new();
+        let health = |origin: &'static str| {
+            http.get(format!("{base}/v1/health"))
+                .header("Origin", origin)
+                .send()
+        };
+
+        let resp = health("https://evil.example").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = health("http://localhost:3000").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn rebound_hostnames_are_refused_without_a_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("node.sock");
+        fake_daemon(&socket, Response::Ok { data: None });
+        let base = serve(GatewayState::new(socket, None)).await;
+        let http = reqwest::Client::new();
+        let health = |host: &'static str| {
+            http.get(format!("{base}/v1/health"))
+                .header("Host", host)
+                .send()
+        };
+
+        let resp = health("attacker.example:8420").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        for host in ["localhost:8420", "127.0.0.1:8420", "[::1]:8420"] {
+            assert_eq!(
+                health(host).await.unwrap().status(),
+                StatusCode::OK,
+                "{host}"
+            );
+        }
+    }
+
+    #[test]
+    fn host_allowed_matches_the_bind_address() {
+        let lan: IpAddr = "192.168.1.5".parse().unwrap();
+        assert!(host_allowed("192.168.1.5:8420", Some(lan)));
+        assert!(!host_allowed("192.168.1.6:8420", Some(lan)));
+        assert!(!host_allowed("192.168.1.5:8420", None));
+        assert!(host_allowed("10.0.0.1", Some("0.0.0.0".parse().unwrap())));
+        assert!(!host_allowed(
+            "rebind.example",
+            Some("0.0.0.0".parse().unwrap())
+        ));
+    }
+}
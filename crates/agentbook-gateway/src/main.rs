@@ -0,0 +1,77 @@
+use agentbook::client::default_socket_path;
+use agentbook_gateway::{GatewayState, router};
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about = "HTTP/REST + SSE gateway for the agentbook node daemon"
+)]
+struct Args {
+    /// Address to serve HTTP on.
+    #[arg(long, default_value = "127.0.0.1:8420")]
+    listen: SocketAddr,
+
+    /// Path to the node daemon's Unix socket.
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Require `Authorization: Bearer <token>` on every call.
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Accept browser calls from this origin, e.g. `http://localhost:3000`
+    /// (repeatable). Calls with any other `Origin` header are refused.
+    #[arg(long = "allow-origin", value_name = "ORIGIN")]
+    allowed_origins: Vec<String>,
+
+    /// Serve a non-loopback --listen address without --token, letting
+    /// anyone who can reach it act as the node's owner.
+    #[arg(long)]
+    insecure_no_token: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "agentbook_gateway=info".into()),
+        )
+        .init();
+
+    let args = Args::parse();
+    if args.token.is_none() && !args.listen.ip().is_loopback() {
+        if !args.insecure_no_token {
+            anyhow::bail!(
+                "refusing to serve {} without --token; pass --insecure-no-token to do it anyway",
+                args.listen
+            );
+        }
+        tracing::warn!(addr = %args.listen, "gateway is exposed without --token");
+    }
+
+    let socket_path = args.socket.unwrap_or_else(default_socket_path);
+    let listener = tokio::net::TcpListener::bind(args.listen)
+        .await
+        .with_context(|| format!("failed to bind {}", args.listen))?;
+    tracing::info!(
+        addr = %listener.local_addr()?,
+        socket = %socket_path.display(),
+        "gateway listening"
+    );
+
+    let state = GatewayState::new(socket_path, args.token)
+        .with_allowed_origins(args.allowed_origins)
+        .with_bind_addr(args.listen.ip());
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .context("gateway server failed")
+}
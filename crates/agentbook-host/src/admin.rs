@@ -0,0 +1,268 @@
+use crate::router::Router;
+use crate::service::usage_report;
+use agentbook_crypto::crypto::token_matches;
+use agentbook_proto::host::v1 as host_pb;
+use agentbook_proto::host::v1::host_admin_service_server::HostAdminService;
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+
+/// Metadata key carrying the operator token on admin RPCs.
+pub const ADMIN_TOKEN_HEADER: &str = "x-agentbook-admin-token";
+
+/// Operator API for inspecting and managing a running relay.
+///
+/// Served on its own listener (see `--admin-listen`) so it can stay bound to
+/// loopback while the relay itself is public.
+#[derive(Clone)]
+pub struct HostAdminImpl {
+    pub router: Arc<Router>,
+    /// Token required on every admin RPC, if set.
+    pub token: Option<String>,
+}
+
+impl HostAdminImpl {
+    fn authorize<T>(&self, req: &Request<T>) -> Result<(), Status> {
+        let Some(expected) = &self.token else {
+            return Ok(());
+        };
+        match req.metadata().get(ADMIN_TOKEN_HEADER) {
+            Some(v) if v.to_str().is_ok_and(|v| token_matches(v, expected)) => Ok(()),
+            _ => Err(Status::unauthenticated("invalid admin token")),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl HostAdminService for HostAdminImpl {
+    async fn get_stats(
+        &self,
+        req: Request<host_pb::GetStatsRequest>,
+    ) -> Result<Response<host_pb::GetStatsResponse>, Status> {
+        self.authorize(&req)?;
+        let stats = self.router.stats();
+        Ok(Response::new(host_pb::GetStatsResponse {
+            uptime_ms: stats.uptime_ms(),
+            connected_nodes: self.router.connected_count() as u32,
+            max_connections: self.router.max_connections() as u32,
+            relayed_frames: stats.relayed_frames(),
+            relayed_bytes: stats.relayed_bytes(),
+            rejected_frames: stats.rejected_frames(),
+            lookups: stats.lookups(),
+            username_registrations: stats.username_registrations(),
+            rooms: self.router.room_count() as u32,
+            draining: self.router.is_draining(),
+        }))
+    }
+
+    async fn list_nodes(
+        &self,
+        req: Request<host_pb::ListNodesRequest>,
+    ) -> Result<Response<host_pb::ListNodesResponse>, Status> {
+        self.authorize(&req)?;
+        let nodes = self
+            .router
+            .connected_nodes()
+            .into_iter()
+            .map(|(node_id, sender)| {
+                let traffic = sender.traffic();
+                host_pb::NodeStats {
+                    observed_endpoints: self.router.lookup_endpoints(&node_id),
+                    node_id,
+                    connected_at_ms: traffic.connected_at_ms,
+                    frames_in: traffic.frames_in(),
+                    bytes_in: traffic.bytes_in(),
+                    frames_out: traffic.frames_out(),
+                    bytes_out: traffic.bytes_out(),
+                    queued_bytes: sender.queued_bytes() as u64,
+                }
+            })
+            .collect();
+        Ok(Response::new(host_pb::ListNodesResponse { nodes }))
+    }
+
+    async fn evict_node(
+        &self,
+        req: Request<host_pb::EvictNodeRequest>,
+    ) -> Result<Response<host_pb::EvictNodeResponse>, Status> {
+        self.authorize(&req)?;
+        let req = req.into_inner();
+        let evicted = self.router.evict(&req.node_id);
+        tracing::info!(node_id = %req.node_id, evicted, "admin evict");
+        Ok(Response::new(host_pb::EvictNodeResponse { evicted }))
+    }
+
+    async fn drain(
+        &self,
+        req: Request<host_pb::DrainRequest>,
+    ) -> Result<Response<host_pb::DrainResponse>, Status> {
+        self.authorize(&req)?;
+        let req = req.into_inner();
+        self.router.set_draining(req.draining);
+        let evicted = if req.evict_all {
+            self.router.evict_all()
+        } else {
+            0
+        };
+        tracing::info!(draining = req.draining, evicted, "admin drain");
+        Ok(Response::new(host_pb::DrainResponse {
+            evicted: evicted as u32,
+        }))
+    }
+
+    async fn ban(
+        &self,
+        req: Request<host_pb::BanRequest>,
+    ) -> Result<Response<host_pb::BanResponse>, Status> {
+        self.authorize(&req)?;
+        let req = req.into_inner();
+        self.router
+            .ban(&req.target, req.reason)
+            .await
+            .map_err(Status::invalid_argument)?;
+        let evicted = self.router.evict(&req.target.trim().to_lowercase());
+        tracing::info!(target = %req.target, evicted, "admin ban");
+        Ok(Response::new(host_pb::BanResponse { evicted }))
+    }
+
+    async fn unban(
+        &self,
+        req: Request<host_pb::UnbanRequest>,
+    ) -> Result<Response<host_pb::UnbanResponse>, Status> {
+        self.authorize(&req)?;
+        let req = req.into_inner();
+        let removed = self
+            .router
+            .unban(&req.target)
+            .await
+            .map_err(Status::internal)?;
+        Ok(Response::new(host_pb::UnbanResponse { removed }))
+    }
+
+    async fn list_bans(
+        &self,
+        req: Request<host_pb::ListBansRequest>,
+    ) -> Result<Response<host_pb::ListBansResponse>, Status> {
+        self.authorize(&req)?;
+        let bans = self
+            .router
+            .bans()
+            .into_iter()
+            .map(|ban| host_pb::BanEntry {
+                target: ban.target,
+                reason: ban.reason,
+                banned_at_ms: ban.banned_at_ms,
+            })
+            .collect();
+        Ok(Response::new(host_pb::ListBansResponse { bans }))
+    }
+
+    async fn get_node_usage(
+        &self,
+        req: Request<host_pb::GetNodeUsageRequest>,
+    ) -> Result<Response<host_pb::UsageReport>, Status> {
+        self.authorize(&req)?;
+        let req = req.into_inner();
+        Ok(Response::new(usage_report(
+            &self.router,
+            &req.node_id,
+            req.since_ms,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::NodeSender;
+
+    fn admin(token: Option<&str>) -> HostAdminImpl {
+        HostAdminImpl {
+            router: Arc::new(Router::new(10, None)),
+            token: token.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_token() {
+        let admin = admin(Some("secret"));
+        let err = admin
+            .get_stats(Request::new(host_pb::GetStatsRequest {}))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+
+        let mut req = Request::new(host_pb::GetStatsRequest {});
+        req.metadata_mut()
+            .insert(ADMIN_TOKEN_HEADER, "secret".parse().unwrap());
+        assert!(admin.get_stats(req).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn lists_and_evicts_connected_nodes() {
+        let admin = admin(None);
+        let (tx, _rx) = NodeSender::channel(4, usize::MAX);
+        admin
+            .router
+            .register("0xaaa".to_string(), String::new(), tx.clone(), None);
+
+        let nodes = admin
+            .list_nodes(Request::new(host_pb::ListNodesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .nodes;
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].node_id, "0xaaa");
+
+        let resp = admin
+            .evict_node(Request::new(host_pb::EvictNodeRequest {
+                node_id: "0xaaa".to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(resp.evicted);
+        // The session task sees the eviction even though it was not yet waiting.
+        tokio::time::timeout(std::time::Duration::from_secs(1), tx.evicted())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_usage_per_node() {
+        let admin = admin(None);
+        admin.router.usage().record("0xaaa", 120, 1_000);
+        admin.router.usage().record("0xaaa", 80, 2_000);
+
+        let report = admin
+            .get_node_usage(Request::new(host_pb::GetNodeUsageRequest {
+                node_id: "0xaaa".to_string(),
+                since_ms: 0,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(report.windows.len(), 1);
+        assert_eq!(report.windows[0].frames, 2);
+        assert_eq!(report.windows[0].bytes, 200);
+    }
+
+    #[tokio::test]
+    async fn drain_is_reported_in_stats() {
+        let admin = admin(None);
+        admin
+            .drain(Request::new(host_pb::DrainRequest {
+                draining: true,
+                evict_all: false,
+            }))
+            .await
+            .unwrap();
+        let stats = admin
+            .get_stats(Request::new(host_pb::GetStatsRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(stats.draining);
+        assert_eq!(stats.connected_nodes, 0);
+    }
+}
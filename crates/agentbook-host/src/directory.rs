@@ -0,0 +1,84 @@
+use agentbook_crypto::crypto::{relay_directory_payload, sign_payload};
+use agentbook_proto::host::v1 as host_pb;
+use anyhow::{Context, Result};
+use base64::Engine;
+use k256::SecretKey;
+use std::path::Path;
+
+/// The relay list this host publishes through `GetRelayDirectory`.
+///
+/// Each response is signed afresh with the operator's directory key, so
+/// nodes can reject stale copies as well as forged ones.
+pub struct RelayDirectory {
+    hosts: Vec<String>,
+    signing_key: SecretKey,
+    public_key_b64: String,
+}
+
+impl RelayDirectory {
+    pub fn new(hosts: Vec<String>, signing_key: SecretKey) -> Self {
+        let public_key_b64 = base64::engine::general_purpose::STANDARD
+            .encode(signing_key.public_key().to_sec1_bytes());
+        Self {
+            hosts,
+            signing_key,
+            public_key_b64,
+        }
+    }
+
+    /// Load the directory key: a file holding the raw 32-byte secp256k1
+    /// secret, e.g. from `head -c 32 /dev/urandom`.
+    pub fn load(hosts: Vec<String>, key_path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(key_path)
+            .with_context(|| format!("failed to read {}", key_path.display()))?;
+        let signing_key = SecretKey::from_slice(&bytes)
+            .with_context(|| format!("{} is not a 32-byte secret key", key_path.display()))?;
+        Ok(Self::new(hosts, signing_key))
+    }
+
+    /// The signer nodes pin with `--relay-directory-signer`.
+    pub fn signer(&self) -> String {
+        agentbook_crypto::crypto::evm_address_from_public_key(&self.signing_key.public_key())
+    }
+
+    pub fn signed(&self, now_ms: u64) -> Result<host_pb::RelayDirectory> {
+        let payload = relay_directory_payload(&self.hosts, now_ms);
+        Ok(host_pb::RelayDirectory {
+            hosts: self.hosts.clone(),
+            issued_at_ms: now_ms,
+            public_key_b64: self.public_key_b64.clone(),
+            signature_b64: sign_payload(&self.signing_key, &payload)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentbook_crypto::crypto::{public_key_matches_node_id, verify_signature};
+
+    #[test]
+    fn signs_each_copy_with_its_issue_time() {
+        let signing_key = SecretKey::random(&mut rand::rngs::OsRng);
+        let hosts = vec!["a.example:50100".to_string(), "b.example:50100".to_string()];
+        let directory = RelayDirectory::new(hosts.clone(), signing_key);
+
+        let signed = directory.signed(1_000).unwrap();
+        assert_eq!(signed.hosts, hosts);
+        assert!(public_key_matches_node_id(
+            &signed.public_key_b64,
+            &directory.signer()
+        ));
+        assert!(verify_signature(
+            &signed.public_key_b64,
+            &relay_directory_payload(&hosts, 1_000),
+            &signed.signature_b64
+        ));
+        // A replayed copy can't claim a newer issue time.
+        assert!(!verify_signature(
+            &signed.public_key_b64,
+            &relay_directory_payload(&hosts, 2_000),
+            &signed.signature_b64
+        ));
+    }
+}
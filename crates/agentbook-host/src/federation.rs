@@ -0,0 +1,191 @@
+use agentbook_crypto::crypto::token_matches;
+use agentbook_proto::host::v1 as host_pb;
+use agentbook_proto::host::v1::host_service_client::HostServiceClient;
+use agentbook_proto::mesh::v1 as mesh_pb;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tonic::metadata::MetadataValue;
+use tonic::transport::Channel;
+use tonic::{Request, Status};
+
+/// Metadata key carrying the shared federation token on peer RPCs.
+pub const FEDERATION_TOKEN_HEADER: &str = "x-agentbook-federation-token";
+
+/// Upper bound on a single peer RPC, so a dead peer cannot stall relaying.
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Links this host to peer hosts so nodes homed on different relays can
+/// reach each other.
+///
+/// Envelopes for nodes that are not connected locally, and username/node_id
+/// lookups that miss the local directory, are tried against each peer in
+/// order. Peers answer from their own state only. Every peer RPC carries a
+/// shared token, which the receiving host checks.
+pub struct Federation {
+    peers: Vec<String>,
+    token: String,
+    clients: Mutex<HashMap<String, HostServiceClient<Channel>>>,
+}
+
+impl Federation {
+    pub fn new(peers: Vec<String>, token: String) -> Self {
+        Self {
+            peers,
+            token,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn peers(&self) -> &[String] {
+        &self.peers
+    }
+
+    /// Reject peer RPCs that do not carry our federation token.
+    pub fn authorize<T>(&self, req: &Request<T>) -> Result<(), Status> {
+        match req.metadata().get(FEDERATION_TOKEN_HEADER) {
+            Some(v) if v.to_str().is_ok_and(|v| token_matches(v, &self.token)) => Ok(()),
+            _ => Err(Status::unauthenticated("invalid federation token")),
+        }
+    }
+
+    fn request<T>(&self, msg: T) -> Request<T> {
+        let mut req = Request::new(msg);
+        req.set_timeout(PEER_TIMEOUT);
+        if let Ok(value) = MetadataValue::try_from(self.token.as_str()) {
+            req.metadata_mut().insert(FEDERATION_TOKEN_HEADER, value);
+        }
+        req
+    }
+
+    async fn client(&self, peer: &str) -> Option<HostServiceClient<Channel>> {
+        let mut clients = self.clients.lock().await;
+        if let Some(client) = clients.get(peer) {
+            return Some(client.clone());
+        }
+        let connect = HostServiceClient::connect(peer_endpoint(peer));
+        match tokio::time::timeout(PEER_TIMEOUT, connect).await {
+            Ok(Ok(client)) => {
+                clients.insert(peer.to_string(), client.clone());
+                Some(client)
+            }
+            Ok(Err(e)) => {
+                tracing::warn!(peer = %peer, err = %e, "failed to connect to federation peer");
+                None
+            }
+            Err(_) => {
+                tracing::warn!(peer = %peer, "timed out connecting to federation peer");
+                None
+            }
+        }
+    }
+
+    /// Drop a cached client after an RPC error so the next call reconnects.
+    async fn evict(&self, peer: &str) {
+        self.clients.lock().await.remove(peer);
+    }
+
+    /// Forward an envelope to whichever peer has the target node connected.
+    /// Returns true once a peer reports delivery.
+    pub async fn forward(&self, envelope: mesh_pb::Envelope) -> bool {
+        for peer in &self.peers {
+            let Some(mut client) = self.client(peer).await else {
+                continue;
+            };
+            let req = self.request(host_pb::FederationForwardRequest {
+                envelope: Some(envelope.clone()),
+            });
+            match client.federation_forward(req).await {
+                Ok(resp) if resp.get_ref().delivered => {
+                    tracing::debug!(peer = %peer, to = %envelope.to_node_id, "envelope forwarded to peer");
+                    return true;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!(peer = %peer, err = %e, "FederationForward RPC failed");
+                    self.evict(peer).await;
+                }
+            }
+        }
+        false
+    }
+
+    /// Look a username up on peer hosts.
+    pub async fn lookup_username(&self, username: &str) -> Option<host_pb::LookupUsernameResponse> {
+        for peer in &self.peers {
+            let Some(mut client) = self.client(peer).await else {
+                continue;
+            };
+            let req = self.request(host_pb::LookupUsernameRequest {
+                username: username.to_string(),
+            });
+            match client.federation_lookup_username(req).await {
+                Ok(resp) if resp.get_ref().found => return Some(resp.into_inner()),
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!(peer = %peer, err = %e, "FederationLookupUsername RPC failed");
+                    self.evict(peer).await;
+                }
+            }
+        }
+        None
+    }
+
+    /// Reverse-look a node_id up on peer hosts.
+    pub async fn lookup_node_id(&self, node_id: &str) -> Option<host_pb::LookupNodeIdResponse> {
+        for peer in &self.peers {
+            let Some(mut client) = self.client(peer).await else {
+                continue;
+            };
+            let req = self.request(host_pb::LookupNodeIdRequest {
+                node_id: node_id.to_string(),
+            });
+            match client.federation_lookup_node_id(req).await {
+                Ok(resp) if resp.get_ref().found => return Some(resp.into_inner()),
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!(peer = %peer, err = %e, "FederationLookupNodeId RPC failed");
+                    self.evict(peer).await;
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Peer addresses without a scheme are assumed to be TLS relays.
+fn peer_endpoint(peer: &str) -> String {
+    if peer.starts_with("http://") || peer.starts_with("https://") {
+        peer.to_string()
+    } else {
+        format!("https://{peer}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authorize_checks_token() {
+        let fed = Federation::new(vec![], "secret".to_string());
+        assert!(fed.authorize(&Request::new(())).is_err());
+
+        let mut req = Request::new(());
+        req.metadata_mut()
+            .insert(FEDERATION_TOKEN_HEADER, "wrong".parse().unwrap());
+        assert!(fed.authorize(&req).is_err());
+
+        let req = fed.request(());
+        assert!(fed.authorize(&req).is_ok());
+    }
+
+    #[test]
+    fn peer_endpoint_defaults_to_https() {
+        assert_eq!(
+            peer_endpoint("relay.example:443"),
+            "https://relay.example:443"
+        );
+        assert_eq!(peer_endpoint("http://127.0.0.1:1"), "http://127.0.0.1:1");
+    }
+}
@@ -0,0 +1,459 @@
+use crate::service::HostServiceImpl;
+use agentbook_proto::host::v1 as host_pb;
+use agentbook_proto::mesh::v1 as mesh_pb;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, State};
+use axum::response::Response;
+use axum::routing::get;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Status;
+
+/// JSON form of a mesh envelope. Field names match the protobuf message;
+/// `message_type` carries the `MessageType` enum value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct JsonEnvelope {
+    pub message_id: String,
+    pub from_node_id: String,
+    #[serde(default)]
+    pub to_node_id: String,
+    pub timestamp_ms: u64,
+    #[serde(default)]
+    pub nonce_b64: String,
+    pub ciphertext_b64: String,
+    #[serde(default)]
+    pub signature_b64: String,
+    #[serde(default)]
+    pub from_public_key_b64: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    #[serde(default)]
+    pub message_type: i32,
+    #[serde(default)]
+    pub sealed: bool,
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+impl From<JsonEnvelope> for mesh_pb::Envelope {
+    fn from(e: JsonEnvelope) -> Self {
+        Self {
+            message_id: e.message_id,
+            from_node_id: e.from_node_id,
+            to_node_id: e.to_node_id,
+            timestamp_ms: e.timestamp_ms,
+            nonce_b64: e.nonce_b64,
+            ciphertext_b64: e.ciphertext_b64,
+            signature_b64: e.signature_b64,
+            from_public_key_b64: e.from_public_key_b64,
+            topic: e.topic,
+            message_type: e.message_type,
+            sealed: e.sealed,
+            schema_version: e.schema_version,
+        }
+    }
+}
+
+impl From<mesh_pb::Envelope> for JsonEnvelope {
+    fn from(e: mesh_pb::Envelope) -> Self {
+        Self {
+            message_id: e.message_id,
+            from_node_id: e.from_node_id,
+            to_node_id: e.to_node_id,
+            timestamp_ms: e.timestamp_ms,
+            nonce_b64: e.nonce_b64,
+            ciphertext_b64: e.ciphertext_b64,
+            signature_b64: e.signature_b64,
+            from_public_key_b64: e.from_public_key_b64,
+            topic: e.topic,
+            message_type: e.message_type,
+            sealed: e.sealed,
+            schema_version: e.schema_version,
+        }
+    }
+}
+
+/// Messages a WebSocket client sends, mirroring `NodeFrame`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Must be the first message; same fields and signature as `RegisterFrame`.
+    Register {
+        node_id: String,
+        public_key_b64: String,
+        signature_b64: String,
+        timestamp_ms: u64,
+    },
+    /// Relay an envelope. Leave `to_node_id` empty and set the envelope's
+    /// topic to broadcast to a room.
+    Send {
+        #[serde(default)]
+        to_node_id: String,
+        envelope: JsonEnvelope,
+    },
+    Ping {
+        timestamp_ms: u64,
+    },
+    RoomSubscribe {
+        room_id: String,
+    },
+    RoomUnsubscribe {
+        room_id: String,
+    },
+}
+
+/// Messages the gateway sends back, mirroring `HostFrame`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    RegisterAck {
+        success: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        #[serde(default)]
+        relay_acks: bool,
+    },
+    Delivery {
+        envelope: JsonEnvelope,
+    },
+    Pong {
+        timestamp_ms: u64,
+    },
+    RelayAck {
+        message_id: String,
+    },
+    Error {
+        code: String,
+        message: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        message_id: String,
+    },
+}
+
+impl From<ClientMessage> for host_pb::NodeFrame {
+    fn from(msg: ClientMessage) -> Self {
+        use host_pb::node_frame::Frame;
+        let frame = match msg {
+            ClientMessage::Register {
+                node_id,
+                public_key_b64,
+                signature_b64,
+                timestamp_ms,
+            } => Frame::Register(host_pb::RegisterFrame {
+                node_id,
+                public_key_b64,
+                signature_b64,
+                timestamp_ms,
+            }),
+            ClientMessage::Send {
+                to_node_id,
+                envelope,
+            } => Frame::RelaySend(host_pb::RelaySendFrame {
+                to_node_id,
+                envelope: Some(envelope.into()),
+            }),
+            ClientMessage::Ping { timestamp_ms } => {
+                Frame::Ping(host_pb::PingFrame { timestamp_ms })
+            }
+            ClientMessage::RoomSubscribe { room_id } => {
+                Frame::RoomSubscribe(host_pb::RoomSubscribeFrame { room_id })
+            }
+            ClientMessage::RoomUnsubscribe { room_id } => {
+                Frame::RoomUnsubscribe(host_pb::RoomUnsubscribeFrame { room_id })
+            }
+        };
+        Self { frame: Some(frame) }
+    }
+}
+
+impl ServerMessage {
+    fn from_frame(frame: host_pb::HostFrame) -> Option<Self> {
+        use host_pb::host_frame::Frame;
+        Some(match frame.frame? {
+            Frame::RegisterAck(ack) => Self::RegisterAck {
+                success: ack.success,
+                error: ack.error,
+                relay_acks: ack.relay_acks,
+            },
+            Frame::Delivery(delivery) => Self::Delivery {
+                envelope: delivery.envelope?.into(),
+            },
+            Frame::Pong(pong) => Self::Pong {
+                timestamp_ms: pong.timestamp_ms,
+            },
+            Frame::RelayAck(ack) => Self::RelayAck {
+                message_id: ack.message_id,
+            },
+            Frame::Error(err) => Self::Error {
+                code: err.code,
+                message: err.message,
+                message_id: err.message_id,
+            },
+        })
+    }
+
+    fn from_status(status: &Status) -> Self {
+        Self::Error {
+            code: format!("{:?}", status.code()).to_uppercase(),
+            message: status.message().to_string(),
+            message_id: String::new(),
+        }
+    }
+}
+
+/// HTTP router exposing the gateway at `/ws`.
+///
+/// Browser clients speak JSON over a WebSocket instead of gRPC; each
+/// connection runs the same relay session as a gRPC node, so registration
+/// checks, bans and rate limits apply unchanged.
+pub fn router(svc: HostServiceImpl) -> axum::Router {
+    axum::Router::new()
+        .route("/ws", get(ws_handler))
+        .with_state(svc)
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(svc): State<HostServiceImpl>,
+) -> Response {
+    ws.on_upgrade(move |socket| bridge(socket, addr, svc))
+}
+
+async fn bridge(mut socket: WebSocket, addr: SocketAddr, svc: HostServiceImpl) {
+    let (frame_tx, frame_rx) = mpsc::channel::<Result<host_pb::NodeFrame, Status>>(64);
+
+    let session = svc.relay_session(Some(addr), ReceiverStream::new(frame_rx));
+
+    // The session waits for the Register frame, so keep reading from the
+    // socket until it has registered (or failed to).
+    let outbound = {
+        let reader = async {
+            while let Some(Ok(msg)) = socket.recv().await {
+                let text = match msg {
+                    Message::Text(text) => text,
+                    Message::Close(_) => break,
+                    _ => continue,
+                };
+                let frame = serde_json::from_str::<ClientMessage>(&text)
+                    .map(host_pb::NodeFrame::from)
+                    .map_err(|e| Status::invalid_argument(format!("invalid message: {e}")));
+                if frame_tx.send(frame).await.is_err() {
+                    break;
+                }
+            }
+        };
+        tokio::select! {
+            result = session => result,
+            _ = reader => return,
+        }
+    };
+    let mut outbound = match outbound {
+        Ok(stream) => stream,
+        Err(status) => {
+            send_json(&mut socket, &ServerMessage::from_status(&status)).await;
+            return;
+        }
+    };
+
+    loop {
+        tokio::select! {
+            frame = outbound.next() => {
+                let Some(Ok(frame)) = frame else { break };
+                if let Some(msg) = ServerMessage::from_frame(frame)
+                    && !send_json(&mut socket, &msg).await
+                {
+                    break;
+                }
+            }
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(msg) => {
+                        if frame_tx.send(Ok(msg.into())).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let err = ServerMessage::Error {
+                            code: "INVALID_MESSAGE".to_string(),
+                            message: e.to_string(),
+                            message_id: String::new(),
+                        };
+                        send_json(&mut socket, &err).await;
+                    }
+                }
+            }
+        }
+    }
+    tracing::debug!(addr = %addr, "websocket client disconnected");
+}
+
+async fn send_json(socket: &mut WebSocket, msg: &ServerMessage) -> bool {
+    match serde_json::to_string(msg) {
+        Ok(json) => socket.send(Message::Text(json.into())).await.is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::Router;
+    use crate::service::DEFAULT_MAX_QUEUED_BYTES;
+    use agentbook_crypto::crypto::{
+        evm_address_from_public_key, relay_registration_payload, sign_payload,
+    };
+    use agentbook_crypto::rate_limit::RateLimiter;
+    use agentbook_crypto::time::now_ms;
+    use base64::Engine;
+    use futures_util::SinkExt;
+    use k256::SecretKey;
+    use rand::rngs::OsRng;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use tokio::sync::Mutex;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    type Client = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    async fn spawn_gateway() -> SocketAddr {
+        let svc = HostServiceImpl {
+            router: Arc::new(Router::new(10, None)),
+            relay_burst: 100,
+            relay_rate: 100.0,
+            relay_ip_limiter: Arc::new(Mutex::new(RateLimiter::new(100, 100.0))),
+            max_queued_bytes: DEFAULT_MAX_QUEUED_BYTES,
+            register_limiter: Arc::new(Mutex::new(RateLimiter::new(10, 10.0))),
+            lookup_limiter: Arc::new(Mutex::new(RateLimiter::new(10, 10.0))),
+            federation: None,
+            require_signed_registration: true,
+            directory: None,
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(svc).into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await.ok() });
+        addr
+    }
+
+    async fn send(client: &mut Client, msg: &ClientMessage) {
+        let json = serde_json::to_string(msg).unwrap();
+        client.send(WsMessage::Text(json.into())).await.unwrap();
+    }
+
+    async fn recv(client: &mut Client) -> ServerMessage {
+        loop {
+            let msg = tokio::time::timeout(std::time::Duration::from_secs(5), client.next())
+                .await
+                .expect("timed out waiting for gateway")
+                .unwrap()
+                .unwrap();
+            if let WsMessage::Text(text) = msg {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    /// Connect and register a fresh identity. Returns the client and its node_id.
+    async fn connect(addr: SocketAddr) -> (Client, String) {
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/ws"))
+            .await
+            .unwrap();
+        let secret = SecretKey::random(&mut OsRng);
+        let public = secret.public_key();
+        let node_id = evm_address_from_public_key(&public);
+        let timestamp_ms = now_ms();
+        let signature_b64 =
+            sign_payload(&secret, &relay_registration_payload(&node_id, timestamp_ms)).unwrap();
+        send(
+            &mut client,
+            &ClientMessage::Register {
+                node_id: node_id.clone(),
+                public_key_b64: base64::engine::general_purpose::STANDARD
+                    .encode(public.to_sec1_bytes()),
+                signature_b64,
+                timestamp_ms,
+            },
+        )
+        .await;
+        assert_eq!(
+            recv(&mut client).await,
+            ServerMessage::RegisterAck {
+                success: true,
+                error: None,
+                relay_acks: true,
+            }
+        );
+        (client, node_id)
+    }
+
+    #[tokio::test]
+    async fn websocket_clients_exchange_envelopes() {
+        let addr = spawn_gateway().await;
+        let (mut alice, alice_id) = connect(addr).await;
+        let (mut bob, bob_id) = connect(addr).await;
+
+        let envelope = JsonEnvelope {
+            message_id: "m1".to_string(),
+            from_node_id: alice_id,
+            to_node_id: bob_id.clone(),
+            timestamp_ms: now_ms(),
+            ciphertext_b64: "aGVsbG8=".to_string(),
+            message_type: mesh_pb::MessageType::DmText as i32,
+            ..JsonEnvelope::default()
+        };
+        send(
+            &mut alice,
+            &ClientMessage::Send {
+                to_node_id: bob_id,
+                envelope: envelope.clone(),
+            },
+        )
+        .await;
+        assert_eq!(
+            recv(&mut alice).await,
+            ServerMessage::RelayAck {
+                message_id: envelope.message_id.clone()
+            }
+        );
+        assert_eq!(recv(&mut bob).await, ServerMessage::Delivery { envelope });
+
+        send(&mut alice, &ClientMessage::Ping { timestamp_ms: 7 }).await;
+        assert_eq!(
+            recv(&mut alice).await,
+            ServerMessage::Pong { timestamp_ms: 7 }
+        );
+    }
+
+    #[tokio::test]
+    async fn websocket_registration_is_verified() {
+        let addr = spawn_gateway().await;
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/ws"))
+            .await
+            .unwrap();
+        send(
+            &mut client,
+            &ClientMessage::Register {
+                node_id: "0x0000000000000000000000000000000000000000".to_string(),
+                public_key_b64: "bogus".to_string(),
+                signature_b64: "bogus".to_string(),
+                timestamp_ms: now_ms(),
+            },
+        )
+        .await;
+        match recv(&mut client).await {
+            ServerMessage::Error { code, .. } => assert_eq!(code, "UNAUTHENTICATED"),
+            other => panic!("expected error, got {other:?}"),
+        }
+    }
+}
@@ -1,2 +1,9 @@
+pub mod admin;
+pub mod directory;
+pub mod federation;
+pub mod gateway;
+pub mod net;
 pub mod router;
 pub mod service;
+pub mod stats;
+pub mod store;
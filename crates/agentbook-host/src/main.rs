@@ -1,21 +1,29 @@
 use agentbook_crypto::rate_limit::RateLimiter;
+use agentbook_host::admin::HostAdminImpl;
+use agentbook_host::directory::RelayDirectory;
+use agentbook_host::federation::Federation;
+use agentbook_host::gateway;
+use agentbook_host::net;
 use agentbook_host::router::Router;
 use agentbook_host::service::HostServiceImpl;
+use agentbook_proto::host::v1::host_admin_service_server::HostAdminServiceServer;
 use agentbook_proto::host::v1::host_service_server::HostServiceServer;
 use anyhow::{Context, Result};
 use clap::Parser;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::net::TcpListener;
+use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio_stream::wrappers::TcpListenerStream;
-use tonic::transport::{Identity, Server, ServerTlsConfig};
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "agentbook relay/rendezvous host")]
 struct Args {
-    #[arg(long, default_value = "0.0.0.0:50100")]
+    /// Address to serve relay traffic on. The default `[::]` accepts IPv6
+    /// and IPv4 connections.
+    #[arg(long, default_value = "[::]:50100")]
     listen: String,
     #[arg(long, default_value = "1000")]
     max_connections: usize,
@@ -27,6 +35,20 @@ struct Args {
     /// Max relay messages per node per second.
     #[arg(long, default_value = "100")]
     relay_rate_limit: u32,
+    /// Max relay messages per source IP per second, across all its nodes.
+    #[arg(long, default_value = "500")]
+    relay_ip_rate_limit: u32,
+    /// Max bytes queued for delivery to a single node; further deliveries to
+    /// it are refused until it catches up.
+    #[arg(long, default_value = "8388608")]
+    max_queued_bytes: usize,
+    /// Ban a node_id or IP address (repeatable). Bans are persisted in the
+    /// data directory and apply to new and existing connections.
+    #[arg(long = "ban", value_name = "NODE_ID_OR_IP")]
+    bans: Vec<String>,
+    /// Lift a persisted ban (repeatable).
+    #[arg(long = "unban", value_name = "NODE_ID_OR_IP")]
+    unbans: Vec<String>,
     /// Max username registrations per IP per minute.
     #[arg(long, default_value = "2")]
     register_rate_limit: u32,
@@ -39,6 +61,55 @@ struct Args {
     /// Path to TLS private key file (PEM). Enables TLS when both --tls-cert and --tls-key are set.
     #[arg(long)]
     tls_key: Option<PathBuf>,
+    /// CA certificate (PEM) for client certificates. When set alongside TLS,
+    /// nodes must present a certificate signed by this CA (mutual TLS).
+    #[arg(long)]
+    tls_client_ca: Option<PathBuf>,
+    /// Only accept relay registrations signed over a fresh timestamp,
+    /// rejecting older clients that sign the bare node_id.
+    #[arg(long)]
+    require_signed_registration: bool,
+    /// Seconds a node registration survives without a ping. Registrations are
+    /// persisted, so nodes keep their rooms across relay restarts within this window.
+    #[arg(long, default_value = "600")]
+    registration_ttl_secs: u64,
+    /// Run as one of several replicas behind a load balancer that share
+    /// --data-dir. Lookups and deliveries then reach nodes connected to any replica.
+    #[arg(long)]
+    replicated: bool,
+    /// How often a replica checks the shared mailbox for frames addressed to
+    /// its nodes, in milliseconds.
+    #[arg(long, default_value = "250")]
+    mailbox_poll_ms: u64,
+    /// Peer relay host to federate with (repeatable). Envelopes and lookups for
+    /// nodes not registered here are forwarded to peers. Needs
+    /// --federation-token.
+    #[arg(long = "peer", requires = "federation_token")]
+    peers: Vec<String>,
+    /// Address for the WebSocket/JSON gateway used by browser clients
+    /// (served at /ws). Disabled when unset; terminate TLS in front of it.
+    #[arg(long)]
+    ws_listen: Option<String>,
+    /// Address for the operator admin API (stats, evict, drain, bans). Disabled
+    /// when unset; keep it on loopback or set --admin-token.
+    #[arg(long)]
+    admin_listen: Option<String>,
+    /// Token operators must send in the x-agentbook-admin-token header.
+    #[arg(long)]
+    admin_token: Option<String>,
+    /// Shared secret that peer hosts must present on federation RPCs; federation
+    /// is off without it. Setting it without --peer accepts forwards from peers
+    /// without forwarding out.
+    #[arg(long)]
+    federation_token: Option<String>,
+    /// Relay to list in the published relay directory (repeatable). Nodes
+    /// started with --auto-relays pick the fastest healthy ones.
+    #[arg(long = "directory-relay", requires = "directory_key")]
+    directory_relays: Vec<String>,
+    /// File with the raw 32-byte secp256k1 key that signs the relay
+    /// directory. Nodes pin its address with --relay-directory-signer.
+    #[arg(long, requires = "directory_relays")]
+    directory_key: Option<PathBuf>,
 }
 
 #[tokio::main]
@@ -56,11 +127,44 @@ async fn main() -> Result<()> {
         .parse()
         .with_context(|| format!("invalid --listen {}", args.listen))?;
 
-    let router = Arc::new(Router::new(args.max_connections, Some(&args.data_dir)));
+    let router = Arc::new(
+        Router::new(args.max_connections, Some(&args.data_dir))
+            .with_registration_ttl(Duration::from_secs(args.registration_ttl_secs))
+            .with_replication(args.replicated),
+    );
 
-    let listener = TcpListener::bind(addr)
-        .await
-        .with_context(|| format!("failed to bind {addr}"))?;
+    if args.replicated {
+        let mailbox_router = router.clone();
+        let poll = Duration::from_millis(args.mailbox_poll_ms.max(10));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(poll);
+            loop {
+                interval.tick().await;
+                mailbox_router.drain_mailboxes().await;
+            }
+        });
+        tracing::info!(data_dir = %args.data_dir.display(), "replicated mode: sharing registration store");
+    }
+
+    for target in &args.unbans {
+        match router.unban(target).await {
+            Ok(true) => tracing::info!(target = %target, "ban lifted"),
+            Ok(false) => tracing::warn!(target = %target, "--unban target was not banned"),
+            Err(e) => anyhow::bail!("failed to unban {target}: {e}"),
+        }
+    }
+    for target in &args.bans {
+        router
+            .ban(target, Some("operator --ban".to_string()))
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to ban {target}: {e}"))?;
+    }
+    let bans = router.bans();
+    if !bans.is_empty() {
+        tracing::info!(count = bans.len(), "operator ban list loaded");
+    }
+
+    let listener = net::bind(addr).with_context(|| format!("failed to bind {addr}"))?;
     let local_addr = listener.local_addr()?;
     tracing::info!(
         "agentbook-host relay listening addr={local_addr} max_connections={} relay_rate={}/s register_rate={}/min lookup_rate={}/s",
@@ -74,6 +178,11 @@ async fn main() -> Result<()> {
         router,
         relay_burst: args.relay_rate_limit,
         relay_rate: args.relay_rate_limit as f64,
+        relay_ip_limiter: Arc::new(Mutex::new(RateLimiter::new(
+            args.relay_ip_rate_limit,
+            args.relay_ip_rate_limit as f64,
+        ))),
+        max_queued_bytes: args.max_queued_bytes,
         register_limiter: Arc::new(Mutex::new(RateLimiter::new(
             args.register_rate_limit,
             args.register_rate_limit as f64 / 60.0,
@@ -82,20 +191,84 @@ async fn main() -> Result<()> {
             args.lookup_rate_limit,
             args.lookup_rate_limit as f64,
         ))),
+        federation: args.federation_token.clone().map(|token| {
+            tracing::info!(peers = ?args.peers, "federation enabled");
+            Arc::new(Federation::new(args.peers.clone(), token))
+        }),
+        require_signed_registration: args.require_signed_registration,
+        directory: match &args.directory_key {
+            Some(key_path) => {
+                let directory = RelayDirectory::load(args.directory_relays.clone(), key_path)?;
+                tracing::info!(
+                    relays = args.directory_relays.len(),
+                    signer = %directory.signer(),
+                    "publishing relay directory"
+                );
+                Some(Arc::new(directory))
+            }
+            None => None,
+        },
     };
 
-    // Spawn periodic cleanup of stale rate limit buckets
+    // Spawn periodic cleanup of stale rate limit buckets and expired registrations
     let register_limiter = svc.register_limiter.clone();
     let lookup_limiter = svc.lookup_limiter.clone();
+    let relay_ip_limiter = svc.relay_ip_limiter.clone();
+    let cleanup_router = svc.router.clone();
     tokio::spawn(async move {
-        let mut interval = tokio::time::interval(std::time::Duration::from_secs(300));
+        let mut interval = tokio::time::interval(Duration::from_secs(300));
         loop {
             interval.tick().await;
             register_limiter.lock().await.cleanup(600.0);
             lookup_limiter.lock().await.cleanup(600.0);
+            relay_ip_limiter.lock().await.cleanup(600.0);
+            let purged = cleanup_router.purge_expired_registrations().await;
+            if purged > 0 {
+                tracing::info!(purged, "purged expired registrations");
+            }
         }
     });
 
+    if let Some(admin_listen) = &args.admin_listen {
+        let admin_addr: SocketAddr = admin_listen
+            .parse()
+            .with_context(|| format!("invalid --admin-listen {admin_listen}"))?;
+        let admin_listener = net::bind(admin_addr)
+            .with_context(|| format!("failed to bind admin API on {admin_addr}"))?;
+        if args.admin_token.is_none() && !admin_addr.ip().is_loopback() {
+            tracing::warn!(addr = %admin_addr, "admin API is exposed without --admin-token");
+        }
+        let admin = HostAdminImpl {
+            router: svc.router.clone(),
+            token: args.admin_token.clone(),
+        };
+        tracing::info!(addr = %admin_listener.local_addr()?, "admin API listening");
+        tokio::spawn(async move {
+            if let Err(e) = Server::builder()
+                .add_service(HostAdminServiceServer::new(admin))
+                .serve_with_incoming(TcpListenerStream::new(admin_listener))
+                .await
+            {
+                tracing::error!(err = %e, "admin API server failed");
+            }
+        });
+    }
+
+    if let Some(ws_listen) = &args.ws_listen {
+        let ws_addr: SocketAddr = ws_listen
+            .parse()
+            .with_context(|| format!("invalid --ws-listen {ws_listen}"))?;
+        let ws_listener = net::bind(ws_addr)
+            .with_context(|| format!("failed to bind WebSocket gateway on {ws_addr}"))?;
+        tracing::info!(addr = %ws_listener.local_addr()?, "WebSocket gateway listening");
+        let app = gateway::router(svc.clone()).into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(ws_listener, app).await {
+                tracing::error!(err = %e, "WebSocket gateway failed");
+            }
+        });
+    }
+
     let mut builder = Server::builder();
 
     // Configure TLS if both cert and key are provided
@@ -106,16 +279,26 @@ async fn main() -> Result<()> {
             let key_pem = std::fs::read(key_path)
                 .with_context(|| format!("failed to read TLS key: {}", key_path.display()))?;
             let identity = Identity::from_pem(cert_pem, key_pem);
-            let tls_config = ServerTlsConfig::new().identity(identity);
+            let mut tls_config = ServerTlsConfig::new().identity(identity);
+            if let Some(ca_path) = &args.tls_client_ca {
+                let ca_pem = std::fs::read(ca_path).with_context(|| {
+                    format!("failed to read TLS client CA: {}", ca_path.display())
+                })?;
+                tls_config = tls_config.client_ca_root(Certificate::from_pem(ca_pem));
+            }
             builder = builder
                 .tls_config(tls_config)
                 .context("failed to configure TLS")?;
-            tracing::info!("TLS enabled");
+            tracing::info!(mutual = args.tls_client_ca.is_some(), "TLS enabled");
         }
         (Some(_), None) | (None, Some(_)) => {
             anyhow::bail!("both --tls-cert and --tls-key must be provided together");
         }
-        (None, None) => {}
+        (None, None) => {
+            if args.tls_client_ca.is_some() {
+                anyhow::bail!("--tls-client-ca requires --tls-cert and --tls-key");
+            }
+        }
     }
 
     builder
@@ -131,10 +314,13 @@ async fn main() -> Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use agentbook_crypto::crypto::{sign_payload, verify_signature};
+    use agentbook_crypto::crypto::{relay_registration_payload, sign_payload, verify_signature};
     use agentbook_crypto::rate_limit::RateLimiter;
+    use agentbook_crypto::time::now_ms;
     use agentbook_host::router::Router;
-    use agentbook_host::service::HostServiceImpl;
+    use agentbook_host::service::{
+        DEFAULT_MAX_QUEUED_BYTES, HostServiceImpl, REGISTRATION_MAX_SKEW_MS, verify_register_frame,
+    };
     use agentbook_proto::host::v1 as host_pb;
     use agentbook_proto::host::v1::host_service_server::HostService;
     use base64::Engine;
@@ -198,14 +384,77 @@ mod tests {
         assert!(!verify_signature("bad-key", node_id.as_bytes(), &sig));
     }
 
+    fn timestamped_register_frame(
+        secret: &SecretKey,
+        node_id: &str,
+        pub_b64: &str,
+        timestamp_ms: u64,
+    ) -> host_pb::RegisterFrame {
+        let payload = relay_registration_payload(node_id, timestamp_ms);
+        host_pb::RegisterFrame {
+            node_id: node_id.to_string(),
+            public_key_b64: pub_b64.to_string(),
+            signature_b64: sign_payload(secret, &payload).unwrap(),
+            timestamp_ms,
+        }
+    }
+
+    #[test]
+    fn timestamped_register_frame_accepted_when_fresh() {
+        let (secret, node_id, pub_b64, _sig) = test_keypair();
+        let now = now_ms();
+        let frame = timestamped_register_frame(&secret, &node_id, &pub_b64, now);
+        assert_eq!(verify_register_frame(&frame, now, true).ok(), Some(true));
+
+        let stale = timestamped_register_frame(
+            &secret,
+            &node_id,
+            &pub_b64,
+            now - REGISTRATION_MAX_SKEW_MS - 1,
+        );
+        assert!(verify_register_frame(&stale, now, false).is_err());
+    }
+
+    #[test]
+    fn legacy_register_frame_rejected_when_signed_registration_required() {
+        let (_secret, node_id, pub_b64, sig) = test_keypair();
+        let frame = host_pb::RegisterFrame {
+            node_id,
+            public_key_b64: pub_b64,
+            signature_b64: sig,
+            timestamp_ms: now_ms(),
+        };
+        assert_eq!(
+            verify_register_frame(&frame, now_ms(), false).ok(),
+            Some(false)
+        );
+        assert!(verify_register_frame(&frame, now_ms(), true).is_err());
+    }
+
+    #[test]
+    fn register_frame_for_foreign_node_id_rejected() {
+        let (secret, _node_id, pub_b64, _sig) = test_keypair();
+        let (_, victim_node_id, _, _) = test_keypair();
+        let now = now_ms();
+        // Validly signed by our key, but claiming someone else's node_id.
+        let frame = timestamped_register_frame(&secret, &victim_node_id, &pub_b64, now);
+        let err = verify_register_frame(&frame, now, false).unwrap_err();
+        assert!(err.message().contains("does not match"));
+    }
+
     #[tokio::test]
     async fn register_username_rejects_invalid_signature() {
         let svc = HostServiceImpl {
             router: Arc::new(Router::new(10, None)),
             relay_burst: 100,
             relay_rate: 100.0,
+            relay_ip_limiter: Arc::new(Mutex::new(RateLimiter::new(100, 100.0))),
+            max_queued_bytes: DEFAULT_MAX_QUEUED_BYTES,
             register_limiter: Arc::new(Mutex::new(RateLimiter::new(10, 10.0))),
             lookup_limiter: Arc::new(Mutex::new(RateLimiter::new(10, 10.0))),
+            federation: None,
+            require_signed_registration: false,
+            directory: None,
         };
 
         let (_secret, node_id, pub_b64, _sig) = test_keypair();
@@ -222,14 +471,50 @@ mod tests {
         assert!(resp.error.unwrap().contains("invalid signature"));
     }
 
+    #[tokio::test]
+    async fn register_username_rejected_for_banned_node() {
+        let svc = HostServiceImpl {
+            router: Arc::new(Router::new(10, None)),
+            relay_burst: 100,
+            relay_rate: 100.0,
+            relay_ip_limiter: Arc::new(Mutex::new(RateLimiter::new(100, 100.0))),
+            max_queued_bytes: DEFAULT_MAX_QUEUED_BYTES,
+            register_limiter: Arc::new(Mutex::new(RateLimiter::new(10, 10.0))),
+            lookup_limiter: Arc::new(Mutex::new(RateLimiter::new(10, 10.0))),
+            federation: None,
+            require_signed_registration: false,
+            directory: None,
+        };
+
+        let (_secret, node_id, pub_b64, sig) = test_keypair();
+        svc.router.ban(&node_id, None).await.unwrap();
+
+        let req = Request::new(host_pb::RegisterUsernameRequest {
+            username: "testuser".to_string(),
+            node_id,
+            public_key_b64: pub_b64,
+            signature_b64: sig,
+        });
+
+        let resp = svc.register_username(req).await.unwrap().into_inner();
+        assert!(!resp.success);
+        assert!(resp.error.unwrap().contains("banned"));
+        assert!(svc.router.lookup_username("testuser").await.is_none());
+    }
+
     #[tokio::test]
     async fn register_username_accepts_valid_signature() {
         let svc = HostServiceImpl {
             router: Arc::new(Router::new(10, None)),
             relay_burst: 100,
             relay_rate: 100.0,
+            relay_ip_limiter: Arc::new(Mutex::new(RateLimiter::new(100, 100.0))),
+            max_queued_bytes: DEFAULT_MAX_QUEUED_BYTES,
             register_limiter: Arc::new(Mutex::new(RateLimiter::new(10, 10.0))),
             lookup_limiter: Arc::new(Mutex::new(RateLimiter::new(10, 10.0))),
+            federation: None,
+            require_signed_registration: false,
+            directory: None,
         };
 
         let (_secret, node_id, pub_b64, sig) = test_keypair();
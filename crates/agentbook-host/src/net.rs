@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, SocketAddr};
+use tokio::net::TcpListener;
+
+/// Bind a listener. An unspecified IPv6 address (`[::]`) also accepts IPv4
+/// connections, whatever the OS default for `IPV6_V6ONLY`, so one listener
+/// serves both address families.
+pub fn bind(addr: SocketAddr) -> Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() && addr.ip().is_unspecified() {
+        socket
+            .set_only_v6(false)
+            .context("failed to enable dual-stack listening")?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    Ok(TcpListener::from_std(socket.into())?)
+}
+
+/// A peer address with IPv4-mapped IPv6 (`[::ffff:1.2.3.4]:5000`, as seen
+/// on a dual-stack listener) turned back into plain IPv4, so bans, rate
+/// limits and observed endpoints see the same address either way.
+pub fn canonical(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(addr.ip().to_canonical(), addr.port())
+}
+
+/// Key for per-IP rate limits. IPv6 clients usually hold a whole /64, so
+/// they are limited per /64 rather than per address.
+pub fn rate_limit_key(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V6(v6) => {
+            let s = v6.segments();
+            format!("{:x}:{:x}:{:x}:{:x}::/64", s[0], s[1], s[2], s[3])
+        }
+        v4 => v4.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped_ipv4_is_reported_as_ipv4() {
+        let mapped: SocketAddr = "[::ffff:1.2.3.4]:5000".parse().unwrap();
+        assert_eq!(canonical(mapped).to_string(), "1.2.3.4:5000");
+        let v6: SocketAddr = "[2001:db8::1]:5000".parse().unwrap();
+        assert_eq!(canonical(v6), v6);
+    }
+
+    #[test]
+    fn ipv6_is_rate_limited_per_64() {
+        let a: IpAddr = "2001:db8:1:2::1".parse().unwrap();
+        let b: IpAddr = "2001:db8:1:2:ffff::9".parse().unwrap();
+        let c: IpAddr = "2001:db8:1:3::1".parse().unwrap();
+        assert_eq!(rate_limit_key(a), rate_limit_key(b));
+        assert_ne!(rate_limit_key(a), rate_limit_key(c));
+        assert_eq!(rate_limit_key("::ffff:1.2.3.4".parse().unwrap()), "1.2.3.4");
+    }
+
+    #[tokio::test]
+    async fn unspecified_ipv6_accepts_ipv4_too() {
+        let Ok(listener) = bind("[::]:0".parse().unwrap()) else {
+            return; // No IPv6 on this host.
+        };
+        let port = listener.local_addr().unwrap().port();
+        let dial = tokio::net::TcpStream::connect(("127.0.0.1", port));
+        let (dialed, accepted) = tokio::join!(dial, listener.accept());
+        dialed.unwrap();
+        let (_, peer) = accepted.unwrap();
+        assert_eq!(canonical(peer).ip().to_string(), "127.0.0.1");
+    }
+}
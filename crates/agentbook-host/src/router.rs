@@ -1,12 +1,19 @@
+use crate::stats::{HostStats, NodeTraffic, UsageLedger};
+pub use crate::store::RegistrationRow;
+use crate::store::RegistrationStore;
+use agentbook_crypto::time::now_ms;
 use agentbook_crypto::username::validate_username;
 use agentbook_proto::host::v1 as host_pb;
 use agentbook_proto::mesh::v1 as mesh_pb;
 use dashmap::DashMap;
+use prost::Message;
 use rusqlite::Connection;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex as StdMutex};
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::{Notify, mpsc};
 
 /// A registered username entry.
 #[derive(Clone)]
@@ -44,6 +51,8 @@ impl UsernameDirectory {
         // WAL mode for better concurrent read performance
         conn.pragma_update(None, "journal_mode", "WAL").ok();
         conn.pragma_update(None, "synchronous", "NORMAL").ok();
+        // Replicas may share this database; wait out their write locks.
+        conn.busy_timeout(Duration::from_secs(5)).ok();
 
         conn.execute_batch(
             "CREATE TABLE IF NOT EXISTS usernames (
@@ -60,7 +69,30 @@ impl UsernameDirectory {
                 created_at        TEXT NOT NULL DEFAULT (datetime('now')),
                 PRIMARY KEY (follower_node_id, followed_node_id)
             );
-            CREATE INDEX IF NOT EXISTS idx_follows_followed ON follows(followed_node_id);",
+            CREATE INDEX IF NOT EXISTS idx_follows_followed ON follows(followed_node_id);
+            CREATE TABLE IF NOT EXISTS registrations (
+                node_id        TEXT PRIMARY KEY NOT NULL,
+                public_key     TEXT NOT NULL,
+                endpoints      TEXT NOT NULL DEFAULT '[]',
+                expires_at_ms  INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS room_subscriptions (
+                node_id  TEXT NOT NULL,
+                room_id  TEXT NOT NULL,
+                PRIMARY KEY (node_id, room_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_room_subscriptions_room ON room_subscriptions(room_id);
+            CREATE TABLE IF NOT EXISTS mailbox (
+                id             INTEGER PRIMARY KEY AUTOINCREMENT,
+                node_id        TEXT NOT NULL,
+                frame          BLOB NOT NULL,
+                expires_at_ms  INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS bans (
+                target        TEXT PRIMARY KEY NOT NULL,
+                reason        TEXT,
+                banned_at_ms  INTEGER NOT NULL
+            );",
         )
         .expect("failed to create tables");
 
@@ -240,6 +272,376 @@ impl UsernameDirectory {
     }
 }
 
+impl RegistrationStore for UsernameDirectory {
+    fn upsert_registration(
+        &self,
+        node_id: &str,
+        public_key_b64: &str,
+        endpoints: &[String],
+        expires_at_ms: u64,
+    ) -> Result<(), String> {
+        let endpoints = serde_json::to_string(endpoints).map_err(|e| e.to_string())?;
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("lock poisoned: {e}"))?;
+        conn.execute(
+            "INSERT INTO registrations (node_id, public_key, endpoints, expires_at_ms)
+                 VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(node_id) DO UPDATE SET
+                 public_key = excluded.public_key,
+                 endpoints = excluded.endpoints,
+                 expires_at_ms = excluded.expires_at_ms",
+            rusqlite::params![node_id, public_key_b64, endpoints, expires_at_ms as i64],
+        )
+        .map_err(|e| format!("database error: {e}"))?;
+        Ok(())
+    }
+
+    fn refresh_registration(&self, node_id: &str, expires_at_ms: u64) -> Result<(), String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("lock poisoned: {e}"))?;
+        conn.execute(
+            "UPDATE registrations SET expires_at_ms = ?2 WHERE node_id = ?1",
+            rusqlite::params![node_id, expires_at_ms as i64],
+        )
+        .map_err(|e| format!("database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Registrations that have not expired yet.
+    fn live_registrations(&self, now_ms: u64) -> Vec<RegistrationRow> {
+        let conn = match self.conn.lock() {
+            Ok(c) => c,
+            Err(_) => return vec![],
+        };
+        let mut stmt = match conn.prepare(
+            "SELECT node_id, public_key, endpoints, expires_at_ms
+             FROM registrations WHERE expires_at_ms > ?1",
+        ) {
+            Ok(s) => s,
+            Err(_) => return vec![],
+        };
+        stmt.query_map([now_ms as i64], |row| {
+            let endpoints: String = row.get(2)?;
+            Ok(RegistrationRow {
+                node_id: row.get(0)?,
+                public_key_b64: row.get(1)?,
+                endpoints: serde_json::from_str(&endpoints).unwrap_or_default(),
+                expires_at_ms: row.get::<_, i64>(3)? as u64,
+            })
+        })
+        .map(|rows| rows.filter_map(|r| r.ok()).collect())
+        .unwrap_or_default()
+    }
+
+    /// Delete expired registrations and their room subscriptions.
+    /// Returns the node_ids removed.
+    fn purge_expired_registrations(&self, now_ms: u64) -> Vec<String> {
+        let Ok(conn) = self.conn.lock() else {
+            return vec![];
+        };
+        let expired: Vec<String> = conn
+            .prepare("SELECT node_id FROM registrations WHERE expires_at_ms <= ?1")
+            .and_then(|mut stmt| {
+                stmt.query_map([now_ms as i64], |row| row.get(0))
+                    .map(|rows| rows.filter_map(|r| r.ok()).collect())
+            })
+            .unwrap_or_default();
+        conn.execute(
+            "DELETE FROM mailbox WHERE expires_at_ms <= ?1",
+            [now_ms as i64],
+        )
+        .ok();
+        for node_id in &expired {
+            conn.execute("DELETE FROM registrations WHERE node_id = ?1", [node_id])
+                .ok();
+            conn.execute(
+                "DELETE FROM room_subscriptions WHERE node_id = ?1",
+                [node_id],
+            )
+            .ok();
+        }
+        expired
+    }
+
+    fn save_room_subscription(&self, room_id: &str, node_id: &str) -> Result<(), String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("lock poisoned: {e}"))?;
+        conn.execute(
+            "INSERT OR IGNORE INTO room_subscriptions (node_id, room_id) VALUES (?1, ?2)",
+            rusqlite::params![node_id, room_id],
+        )
+        .map_err(|e| format!("database error: {e}"))?;
+        Ok(())
+    }
+
+    fn delete_room_subscription(&self, room_id: &str, node_id: &str) -> Result<(), String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("lock poisoned: {e}"))?;
+        conn.execute(
+            "DELETE FROM room_subscriptions WHERE node_id = ?1 AND room_id = ?2",
+            rusqlite::params![node_id, room_id],
+        )
+        .map_err(|e| format!("database error: {e}"))?;
+        Ok(())
+    }
+
+    fn room_subscriptions(&self, node_id: &str) -> Vec<String> {
+        let conn = match self.conn.lock() {
+            Ok(c) => c,
+            Err(_) => return vec![],
+        };
+        conn.prepare("SELECT room_id FROM room_subscriptions WHERE node_id = ?1")
+            .and_then(|mut stmt| {
+                stmt.query_map([node_id], |row| row.get(0))
+                    .map(|rows| rows.filter_map(|r| r.ok()).collect())
+            })
+            .unwrap_or_default()
+    }
+
+    fn registration(&self, node_id: &str, now_ms: u64) -> Option<RegistrationRow> {
+        let conn = self.conn.lock().ok()?;
+        conn.query_row(
+            "SELECT node_id, public_key, endpoints, expires_at_ms
+             FROM registrations WHERE node_id = ?1 AND expires_at_ms > ?2",
+            rusqlite::params![node_id, now_ms as i64],
+            |row| {
+                let endpoints: String = row.get(2)?;
+                Ok(RegistrationRow {
+                    node_id: row.get(0)?,
+                    public_key_b64: row.get(1)?,
+                    endpoints: serde_json::from_str(&endpoints).unwrap_or_default(),
+                    expires_at_ms: row.get::<_, i64>(3)? as u64,
+                })
+            },
+        )
+        .ok()
+    }
+
+    fn room_members(&self, room_id: &str) -> Vec<String> {
+        let Ok(conn) = self.conn.lock() else {
+            return vec![];
+        };
+        conn.prepare("SELECT node_id FROM room_subscriptions WHERE room_id = ?1")
+            .and_then(|mut stmt| {
+                stmt.query_map([room_id], |row| row.get(0))
+                    .map(|rows| rows.filter_map(|r| r.ok()).collect())
+            })
+            .unwrap_or_default()
+    }
+
+    fn push_mailbox(&self, node_id: &str, frame: &[u8], expires_at_ms: u64) -> Result<(), String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("lock poisoned: {e}"))?;
+        conn.execute(
+            "INSERT INTO mailbox (node_id, frame, expires_at_ms) VALUES (?1, ?2, ?3)",
+            rusqlite::params![node_id, frame, expires_at_ms as i64],
+        )
+        .map_err(|e| format!("database error: {e}"))?;
+        Ok(())
+    }
+
+    fn take_mailbox(&self, node_ids: &[String], now_ms: u64) -> Vec<(String, Vec<u8>)> {
+        if node_ids.is_empty() {
+            return vec![];
+        }
+        let Ok(mut conn) = self.conn.lock() else {
+            return vec![];
+        };
+        let Ok(tx) = conn.transaction() else {
+            return vec![];
+        };
+        let wanted: HashSet<&str> = node_ids.iter().map(String::as_str).collect();
+        let rows: Vec<(i64, String, Vec<u8>)> = tx
+            .prepare("SELECT id, node_id, frame FROM mailbox WHERE expires_at_ms > ?1 ORDER BY id")
+            .and_then(|mut stmt| {
+                stmt.query_map([now_ms as i64], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })
+                .map(|rows| rows.filter_map(|r| r.ok()).collect())
+            })
+            .unwrap_or_default();
+        let mut taken = Vec::new();
+        for (id, node_id, frame) in rows {
+            if wanted.contains(node_id.as_str()) {
+                tx.execute("DELETE FROM mailbox WHERE id = ?1", [id]).ok();
+                taken.push((node_id, frame));
+            }
+        }
+        if tx.commit().is_err() {
+            return vec![];
+        }
+        taken
+    }
+}
+
+impl UsernameDirectory {
+    fn save_ban(&self, ban: &BanEntry) -> Result<(), String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("lock poisoned: {e}"))?;
+        conn.execute(
+            "INSERT INTO bans (target, reason, banned_at_ms) VALUES (?1, ?2, ?3)
+             ON CONFLICT(target) DO UPDATE SET
+                 reason = excluded.reason,
+                 banned_at_ms = excluded.banned_at_ms",
+            rusqlite::params![ban.target, ban.reason, ban.banned_at_ms as i64],
+        )
+        .map_err(|e| format!("database error: {e}"))?;
+        Ok(())
+    }
+
+    fn delete_ban(&self, target: &str) -> Result<bool, String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("lock poisoned: {e}"))?;
+        conn.execute("DELETE FROM bans WHERE target = ?1", [target])
+            .map(|n| n > 0)
+            .map_err(|e| format!("database error: {e}"))
+    }
+
+    fn bans(&self) -> Vec<BanEntry> {
+        let Ok(conn) = self.conn.lock() else {
+            return vec![];
+        };
+        conn.prepare("SELECT target, reason, banned_at_ms FROM bans")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| {
+                    Ok(BanEntry {
+                        target: row.get(0)?,
+                        reason: row.get(1)?,
+                        banned_at_ms: row.get::<_, i64>(2)? as u64,
+                    })
+                })
+                .map(|rows| rows.filter_map(|r| r.ok()).collect())
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// An operator ban on a node_id or a client IP address.
+#[derive(Clone, Debug)]
+pub struct BanEntry {
+    /// Lowercased node_id, or an IP address as printed by `IpAddr`.
+    pub target: String,
+    pub reason: Option<String>,
+    pub banned_at_ms: u64,
+}
+
+/// Why a frame could not be queued for a node.
+#[derive(Debug, PartialEq, Eq)]
+pub enum QueueError {
+    /// The node's backlog is over its byte budget (slow or flooded reader).
+    Full,
+    /// The node has disconnected.
+    Closed,
+}
+
+/// Outbound frame queue for one connected node. Tracks the bytes waiting to
+/// be written so one slow or flooded node cannot pin unbounded relay memory.
+#[derive(Clone)]
+pub struct NodeSender {
+    tx: mpsc::Sender<host_pb::HostFrame>,
+    queued_bytes: Arc<AtomicUsize>,
+    max_queued_bytes: usize,
+    traffic: Arc<NodeTraffic>,
+    evict: Arc<Notify>,
+}
+
+impl NodeSender {
+    pub fn new(tx: mpsc::Sender<host_pb::HostFrame>, max_queued_bytes: usize) -> Self {
+        Self {
+            tx,
+            queued_bytes: Arc::new(AtomicUsize::new(0)),
+            max_queued_bytes,
+            traffic: Arc::new(NodeTraffic::default()),
+            evict: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Create a bounded queue for a node: `capacity` frames and at most
+    /// `max_queued_bytes` of encoded frames waiting to be written.
+    pub fn channel(capacity: usize, max_queued_bytes: usize) -> (Self, NodeReceiver) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let sender = Self::new(tx, max_queued_bytes);
+        let receiver = NodeReceiver {
+            rx,
+            queued_bytes: sender.queued_bytes.clone(),
+        };
+        (sender, receiver)
+    }
+
+    /// Queue a frame, refusing it if the backlog would exceed the byte budget.
+    pub async fn send(&self, frame: host_pb::HostFrame) -> Result<(), QueueError> {
+        let size = frame.encoded_len();
+        let queued = self.queued_bytes.fetch_add(size, Ordering::AcqRel) + size;
+        if queued > self.max_queued_bytes {
+            self.queued_bytes.fetch_sub(size, Ordering::AcqRel);
+            return Err(QueueError::Full);
+        }
+        if self.tx.send(frame).await.is_err() {
+            self.queued_bytes.fetch_sub(size, Ordering::AcqRel);
+            return Err(QueueError::Closed);
+        }
+        self.traffic.record_out(size);
+        Ok(())
+    }
+
+    /// Traffic counters for this connection.
+    pub fn traffic(&self) -> &NodeTraffic {
+        &self.traffic
+    }
+
+    /// Ask the connection's session task to disconnect the node.
+    pub fn evict(&self) {
+        self.evict.notify_one();
+    }
+
+    /// Resolves once [`NodeSender::evict`] has been called.
+    pub async fn evicted(&self) {
+        self.evict.notified().await;
+    }
+
+    /// Bytes currently queued for this node.
+    pub fn queued_bytes(&self) -> usize {
+        self.queued_bytes.load(Ordering::Acquire)
+    }
+}
+
+/// Receiving half of [`NodeSender::channel`]; releases each frame's bytes
+/// from the budget as it is taken off the queue.
+pub struct NodeReceiver {
+    rx: mpsc::Receiver<host_pb::HostFrame>,
+    queued_bytes: Arc<AtomicUsize>,
+}
+
+impl NodeReceiver {
+    pub async fn recv(&mut self) -> Option<host_pb::HostFrame> {
+        let frame = self.rx.recv().await?;
+        self.queued_bytes
+            .fetch_sub(frame.encoded_len(), Ordering::AcqRel);
+        Some(frame)
+    }
+}
+
+impl From<mpsc::Sender<host_pb::HostFrame>> for NodeSender {
+    /// A queue bounded only by its channel capacity.
+    fn from(tx: mpsc::Sender<host_pb::HostFrame>) -> Self {
+        Self::new(tx, usize::MAX)
+    }
+}
+
 /// A row from the followers query (joined with usernames).
 #[derive(Clone)]
 pub struct FollowEntryRow {
@@ -253,7 +655,7 @@ pub struct FollowEntryRow {
 /// Uses `DashMap` for lock-free concurrent access to the senders and endpoints maps.
 /// The username directory is behind its own lock and uses `spawn_blocking` for DB ops.
 pub struct Router {
-    senders: DashMap<String, mpsc::Sender<host_pb::HostFrame>>,
+    senders: DashMap<String, NodeSender>,
     /// Public keys per connected node (for join event envelope construction).
     public_keys: DashMap<String, String>,
     /// Observed remote addresses per node (for rendezvous lookup).
@@ -261,33 +663,111 @@ pub struct Router {
     /// Room subscribers: room_id → set of node_ids.
     room_subscribers: DashMap<String, HashSet<String>>,
     directory: Arc<UsernameDirectory>,
+    /// Registrations, room subscriptions and mailboxes (the directory by default).
+    store: Arc<dyn RegistrationStore>,
     max_connections: usize,
+    /// How long a registration survives without a refresh (register or ping).
+    registration_ttl_ms: u64,
+    /// Newest signed registration timestamp seen per node (replay guard).
+    registration_timestamps: DashMap<String, u64>,
+    /// Operator bans by node_id or IP, mirrored from the directory.
+    bans: DashMap<String, BanEntry>,
+    /// When set, new node registrations are refused (see the admin Drain RPC).
+    draining: AtomicBool,
+    stats: HostStats,
+    /// Bytes relayed per node over time, for usage reports.
+    usage: UsageLedger,
+    /// Whether other replicas share the store (see [`Router::with_replication`]).
+    replicated: bool,
 }
 
+/// Default registration lifetime; nodes refresh it on every ping.
+pub const DEFAULT_REGISTRATION_TTL: Duration = Duration::from_secs(10 * 60);
+
 impl Router {
     pub fn new(max_connections: usize, data_dir: Option<&Path>) -> Self {
-        Self {
+        let directory = Arc::new(UsernameDirectory::open(data_dir));
+
+        let bans = directory
+            .bans()
+            .into_iter()
+            .map(|ban| (ban.target.clone(), ban))
+            .collect();
+
+        let router = Self {
             senders: DashMap::new(),
             public_keys: DashMap::new(),
             observed_endpoints: DashMap::new(),
             room_subscribers: DashMap::new(),
-            directory: Arc::new(UsernameDirectory::open(data_dir)),
+            store: directory.clone(),
+            directory,
             max_connections,
+            registration_ttl_ms: DEFAULT_REGISTRATION_TTL.as_millis() as u64,
+            registration_timestamps: DashMap::new(),
+            bans,
+            draining: AtomicBool::new(false),
+            stats: HostStats::default(),
+            usage: UsageLedger::default(),
+            replicated: false,
+        };
+        router.restore_registrations();
+        router
+    }
+
+    /// Restore registrations that outlived the previous process so lookups
+    /// keep working until their nodes reconnect.
+    fn restore_registrations(&self) {
+        let restored = self.store.live_registrations(now_ms());
+        if !restored.is_empty() {
+            tracing::info!(
+                count = restored.len(),
+                "restored node registrations from disk"
+            );
+        }
+        for reg in restored {
+            self.public_keys
+                .insert(reg.node_id.clone(), reg.public_key_b64);
+            if !reg.endpoints.is_empty() {
+                self.observed_endpoints.insert(reg.node_id, reg.endpoints);
+            }
         }
     }
 
+    /// Keep registrations, room subscriptions and mailboxes in another store
+    /// instead of the local SQLite database.
+    pub fn with_store(mut self, store: Arc<dyn RegistrationStore>) -> Self {
+        self.store = store;
+        self.public_keys.clear();
+        self.observed_endpoints.clear();
+        self.restore_registrations();
+        self
+    }
+
+    /// Run as one of several replicas sharing the registration store: frames
+    /// for nodes connected to another replica go through the store's mailbox.
+    pub fn with_replication(mut self, enabled: bool) -> Self {
+        self.replicated = enabled;
+        self
+    }
+
+    /// Override how long registrations persist without a refresh.
+    pub fn with_registration_ttl(mut self, ttl: Duration) -> Self {
+        self.registration_ttl_ms = ttl.as_millis() as u64;
+        self
+    }
+
     /// Register a node. Returns false if at capacity.
     pub fn register(
         &self,
         node_id: String,
         public_key_b64: String,
-        sender: mpsc::Sender<host_pb::HostFrame>,
+        sender: impl Into<NodeSender>,
         observed_addr: Option<String>,
     ) -> bool {
         if self.senders.len() >= self.max_connections && !self.senders.contains_key(&node_id) {
             return false;
         }
-        self.senders.insert(node_id.clone(), sender);
+        self.senders.insert(node_id.clone(), sender.into());
         self.public_keys.insert(node_id.clone(), public_key_b64);
         if let Some(addr) = observed_addr {
             let mut endpoints = self.observed_endpoints.entry(node_id).or_default();
@@ -298,6 +778,62 @@ impl Router {
         true
     }
 
+    /// Record a signed registration timestamp. Returns false if it is not newer
+    /// than the last one accepted for this node, i.e. the frame is a replay.
+    pub fn accept_registration_timestamp(&self, node_id: &str, timestamp_ms: u64) -> bool {
+        let mut last = self
+            .registration_timestamps
+            .entry(node_id.to_string())
+            .or_insert(0);
+        if timestamp_ms <= *last {
+            return false;
+        }
+        *last = timestamp_ms;
+        true
+    }
+
+    /// Whether a node_id or client IP is on the operator ban list.
+    pub fn is_banned(&self, node_id: &str, ip: Option<&str>) -> bool {
+        (!node_id.is_empty() && self.bans.contains_key(&node_id.to_lowercase()))
+            || ip.is_some_and(|ip| self.bans.contains_key(ip))
+    }
+
+    /// Current operator bans.
+    pub fn bans(&self) -> Vec<BanEntry> {
+        self.bans.iter().map(|r| r.value().clone()).collect()
+    }
+
+    /// Ban a node_id or IP address and persist it. A connected node that is
+    /// banned is dropped the next time it sends a frame.
+    pub async fn ban(&self, target: &str, reason: Option<String>) -> Result<(), String> {
+        let ban = BanEntry {
+            target: target.trim().to_lowercase(),
+            reason,
+            banned_at_ms: now_ms(),
+        };
+        if ban.target.is_empty() {
+            return Err("ban target must not be empty".to_string());
+        }
+        let dir = self.directory.clone();
+        let row = ban.clone();
+        tokio::task::spawn_blocking(move || dir.save_ban(&row))
+            .await
+            .map_err(|e| format!("spawn_blocking failed: {e}"))??;
+        self.bans.insert(ban.target.clone(), ban);
+        Ok(())
+    }
+
+    /// Lift a ban. Returns false if the target was not banned.
+    pub async fn unban(&self, target: &str) -> Result<bool, String> {
+        let target = target.trim().to_lowercase();
+        let dir = self.directory.clone();
+        let key = target.clone();
+        let removed = tokio::task::spawn_blocking(move || dir.delete_ban(&key))
+            .await
+            .map_err(|e| format!("spawn_blocking failed: {e}"))??;
+        Ok(self.bans.remove(&target).is_some() || removed)
+    }
+
     /// Unregister a node (on disconnect).
     pub fn unregister(&self, node_id: &str) {
         self.senders.remove(node_id);
@@ -307,7 +843,7 @@ impl Router {
     }
 
     /// Get the sender for a target node, cloned so the caller doesn't hold the map entry.
-    pub fn get_sender(&self, to_node_id: &str) -> Option<mpsc::Sender<host_pb::HostFrame>> {
+    pub fn get_sender(&self, to_node_id: &str) -> Option<NodeSender> {
         self.senders.get(to_node_id).map(|r| r.value().clone())
     }
 
@@ -319,11 +855,68 @@ impl Router {
             .unwrap_or_default()
     }
 
-    #[allow(dead_code)]
     pub fn connected_count(&self) -> usize {
         self.senders.len()
     }
 
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    pub fn room_count(&self) -> usize {
+        self.room_subscribers.len()
+    }
+
+    /// Relay-wide counters.
+    pub fn stats(&self) -> &HostStats {
+        &self.stats
+    }
+
+    /// Per-node relayed bytes.
+    pub fn usage(&self) -> &UsageLedger {
+        &self.usage
+    }
+
+    /// Connected nodes and their outbound queues, sorted by node_id.
+    pub fn connected_nodes(&self) -> Vec<(String, NodeSender)> {
+        let mut nodes: Vec<_> = self
+            .senders
+            .iter()
+            .map(|r| (r.key().clone(), r.value().clone()))
+            .collect();
+        nodes.sort_by(|a, b| a.0.cmp(&b.0));
+        nodes
+    }
+
+    /// Disconnect a node. Returns false if it is not connected.
+    pub fn evict(&self, node_id: &str) -> bool {
+        match self.get_sender(node_id) {
+            Some(sender) => {
+                sender.evict();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Disconnect every connected node. Returns how many were evicted.
+    pub fn evict_all(&self) -> usize {
+        let senders: Vec<_> = self.senders.iter().map(|r| r.value().clone()).collect();
+        for sender in &senders {
+            sender.evict();
+        }
+        senders.len()
+    }
+
+    pub fn set_draining(&self, draining: bool) {
+        self.draining.store(draining, Ordering::Relaxed);
+    }
+
+    /// Whether the relay is refusing new registrations.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
     async fn broadcast_room_system_event(
         &self,
         room_id: &str,
@@ -347,6 +940,8 @@ impl Router {
             from_public_key_b64: String::new(),
             topic: Some(room_id.to_string()),
             message_type: message_type as i32,
+            sealed: false,
+            schema_version: 0,
         };
 
         let delivery = host_pb::HostFrame {
@@ -361,6 +956,8 @@ impl Router {
         for tx in subscribers {
             let _ = tx.send(delivery.clone()).await;
         }
+        self.deliver_room_to_replicas(room_id, node_id, &delivery)
+            .await;
     }
 
     /// Broadcast a RoomJoin system event to all current subscribers of a room.
@@ -430,11 +1027,7 @@ impl Router {
     }
 
     /// Get senders for all room subscribers except the given node.
-    pub fn get_room_subscribers(
-        &self,
-        room_id: &str,
-        exclude_node_id: &str,
-    ) -> Vec<mpsc::Sender<host_pb::HostFrame>> {
+    pub fn get_room_subscribers(&self, room_id: &str, exclude_node_id: &str) -> Vec<NodeSender> {
         let Some(subscribers) = self.room_subscribers.get(room_id) else {
             return vec![];
         };
@@ -525,6 +1118,181 @@ impl Router {
             .ok()?
     }
 
+    /// Persist a node's registration so it survives a relay restart, and
+    /// restore the room subscriptions it had before. Returns the restored rooms.
+    pub async fn persist_registration(&self, node_id: &str) -> Vec<String> {
+        let public_key_b64 = self
+            .public_keys
+            .get(node_id)
+            .map(|r| r.value().clone())
+            .unwrap_or_default();
+        let endpoints = self.lookup_endpoints(node_id);
+        let expires_at_ms = now_ms() + self.registration_ttl_ms;
+
+        let dir = self.store.clone();
+        let id = node_id.to_string();
+        let rooms = tokio::task::spawn_blocking(move || {
+            if let Err(e) = dir.upsert_registration(&id, &public_key_b64, &endpoints, expires_at_ms)
+            {
+                tracing::warn!(node_id = %id, err = %e, "failed to persist registration");
+            }
+            dir.room_subscriptions(&id)
+        })
+        .await
+        .unwrap_or_default();
+
+        for room_id in &rooms {
+            self.subscribe_room(room_id, node_id);
+        }
+        rooms
+    }
+
+    /// Extend a node's registration TTL (called on every ping).
+    pub async fn refresh_registration(&self, node_id: &str) {
+        let dir = self.store.clone();
+        let id = node_id.to_string();
+        let expires_at_ms = now_ms() + self.registration_ttl_ms;
+        let result =
+            tokio::task::spawn_blocking(move || dir.refresh_registration(&id, expires_at_ms)).await;
+        if let Ok(Err(e)) = result {
+            tracing::warn!(node_id = %node_id, err = %e, "failed to refresh registration");
+        }
+    }
+
+    /// Drop expired registrations from disk, and from memory for nodes that
+    /// are not currently connected. Returns how many were purged.
+    pub async fn purge_expired_registrations(&self) -> usize {
+        let dir = self.store.clone();
+        let expired =
+            tokio::task::spawn_blocking(move || dir.purge_expired_registrations(now_ms()))
+                .await
+                .unwrap_or_default();
+        for node_id in &expired {
+            if !self.senders.contains_key(node_id) {
+                self.public_keys.remove(node_id);
+                self.observed_endpoints.remove(node_id);
+            }
+        }
+        expired.len()
+    }
+
+    /// Subscribe a node to a room and persist the subscription.
+    pub async fn subscribe_room_persistent(&self, room_id: &str, node_id: &str) {
+        self.subscribe_room(room_id, node_id);
+        let dir = self.store.clone();
+        let (room, id) = (room_id.to_string(), node_id.to_string());
+        if let Ok(Err(e)) =
+            tokio::task::spawn_blocking(move || dir.save_room_subscription(&room, &id)).await
+        {
+            tracing::warn!(node_id = %node_id, room = %room_id, err = %e, "failed to persist room subscription");
+        }
+    }
+
+    /// Unsubscribe a node from a room and forget the persisted subscription.
+    pub async fn unsubscribe_room_persistent(&self, room_id: &str, node_id: &str) {
+        self.unsubscribe_room(room_id, node_id);
+        let dir = self.store.clone();
+        let (room, id) = (room_id.to_string(), node_id.to_string());
+        if let Ok(Err(e)) =
+            tokio::task::spawn_blocking(move || dir.delete_room_subscription(&room, &id)).await
+        {
+            tracing::warn!(node_id = %node_id, room = %room_id, err = %e, "failed to delete room subscription");
+        }
+    }
+
+    pub fn is_replicated(&self) -> bool {
+        self.replicated
+    }
+
+    /// Look a node's registration up in the shared store.
+    pub async fn lookup_registration(&self, node_id: &str) -> Option<RegistrationRow> {
+        let store = self.store.clone();
+        let id = node_id.to_string();
+        tokio::task::spawn_blocking(move || store.registration(&id, now_ms()))
+            .await
+            .ok()?
+    }
+
+    /// Hand a frame to whichever replica the node is connected to, via the
+    /// store's mailbox. Returns false when not replicated or the node has no
+    /// live registration.
+    pub async fn deliver_to_replica(&self, node_id: &str, frame: &host_pb::HostFrame) -> bool {
+        if !self.replicated || self.lookup_registration(node_id).await.is_none() {
+            return false;
+        }
+        self.push_mailbox(vec![node_id.to_string()], frame).await > 0
+    }
+
+    /// Queue a room frame for subscribers connected to other replicas.
+    pub async fn deliver_room_to_replicas(
+        &self,
+        room_id: &str,
+        exclude_node_id: &str,
+        frame: &host_pb::HostFrame,
+    ) {
+        if !self.replicated {
+            return;
+        }
+        let store = self.store.clone();
+        let room = room_id.to_string();
+        let members = tokio::task::spawn_blocking(move || store.room_members(&room))
+            .await
+            .unwrap_or_default();
+        let remote: Vec<String> = members
+            .into_iter()
+            .filter(|id| id != exclude_node_id && !self.senders.contains_key(id))
+            .collect();
+        if !remote.is_empty() {
+            self.push_mailbox(remote, frame).await;
+        }
+    }
+
+    async fn push_mailbox(&self, node_ids: Vec<String>, frame: &host_pb::HostFrame) -> usize {
+        let store = self.store.clone();
+        let bytes = frame.encode_to_vec();
+        let expires_at_ms = now_ms() + self.registration_ttl_ms;
+        tokio::task::spawn_blocking(move || {
+            node_ids
+                .iter()
+                .filter(|id| match store.push_mailbox(id, &bytes, expires_at_ms) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::warn!(node_id = %id, err = %e, "failed to queue mailbox frame");
+                        false
+                    }
+                })
+                .count()
+        })
+        .await
+        .unwrap_or(0)
+    }
+
+    /// Deliver mailbox frames queued by other replicas for nodes connected
+    /// here. Returns how many frames were delivered.
+    pub async fn drain_mailboxes(&self) -> usize {
+        let local: Vec<String> = self.senders.iter().map(|r| r.key().clone()).collect();
+        if !self.replicated || local.is_empty() {
+            return 0;
+        }
+        let store = self.store.clone();
+        let frames = tokio::task::spawn_blocking(move || store.take_mailbox(&local, now_ms()))
+            .await
+            .unwrap_or_default();
+        let mut delivered = 0;
+        for (node_id, bytes) in frames {
+            let Ok(frame) = host_pb::HostFrame::decode(bytes.as_slice()) else {
+                tracing::warn!(node_id = %node_id, "dropping undecodable mailbox frame");
+                continue;
+            };
+            if let Some(sender) = self.get_sender(&node_id)
+                && sender.send(frame).await.is_ok()
+            {
+                delivered += 1;
+            }
+        }
+        delivered
+    }
+
     /// Check if endpoints map contains a key (for tests).
     #[allow(dead_code)]
     pub fn has_observed_endpoints(&self, node_id: &str) -> bool {
@@ -597,6 +1365,112 @@ mod tests {
         assert!(!router.has_observed_endpoints("a"));
     }
 
+    #[tokio::test]
+    async fn node_sender_refuses_frames_over_budget() {
+        let pong = host_pb::HostFrame {
+            frame: Some(host_pb::host_frame::Frame::Pong(host_pb::PongFrame {
+                timestamp_ms: 1,
+            })),
+        };
+        let size = pong.encoded_len();
+        let (tx, mut rx) = NodeSender::channel(16, size * 2);
+
+        assert!(tx.send(pong.clone()).await.is_ok());
+        assert!(tx.send(pong.clone()).await.is_ok());
+        assert_eq!(tx.send(pong.clone()).await, Err(QueueError::Full));
+        assert_eq!(tx.queued_bytes(), size * 2);
+
+        // Draining a frame frees its share of the budget.
+        rx.recv().await.unwrap();
+        assert_eq!(tx.queued_bytes(), size);
+        assert!(tx.send(pong.clone()).await.is_ok());
+
+        drop(rx);
+        let (tx, rx) = NodeSender::channel(16, usize::MAX);
+        drop(rx);
+        assert_eq!(tx.send(pong).await, Err(QueueError::Closed));
+        assert_eq!(tx.queued_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn bans_match_node_id_or_ip_and_persist() {
+        let tmp = TempDir::new().unwrap();
+        {
+            let router = Router::new(10, Some(tmp.path()));
+            router.ban("0xABC", None).await.unwrap();
+            router
+                .ban("10.0.0.1", Some("spam".to_string()))
+                .await
+                .unwrap();
+            assert!(router.is_banned("0xabc", None));
+            assert!(router.is_banned("0xother", Some("10.0.0.1")));
+            assert!(!router.is_banned("0xother", Some("10.0.0.2")));
+        }
+
+        let router = Router::new(10, Some(tmp.path()));
+        assert_eq!(router.bans().len(), 2);
+        assert!(router.is_banned("0xAbC", None));
+        assert!(router.unban("0xabc").await.unwrap());
+        assert!(!router.unban("0xabc").await.unwrap());
+        assert!(!router.is_banned("0xabc", None));
+    }
+
+    #[tokio::test]
+    async fn replicas_reach_each_others_nodes_via_shared_store() {
+        let tmp = TempDir::new().unwrap();
+        let replica_a = Router::new(10, Some(tmp.path())).with_replication(true);
+        let replica_b = Router::new(10, Some(tmp.path())).with_replication(true);
+
+        let (tx, mut rx) = NodeSender::channel(16, usize::MAX);
+        replica_a.register(
+            "0xaaa".to_string(),
+            "pk-a".to_string(),
+            tx,
+            Some("1.2.3.4:5000".to_string()),
+        );
+        replica_a.persist_registration("0xaaa").await;
+
+        // Replica B sees the registration made on A.
+        let reg = replica_b.lookup_registration("0xaaa").await.unwrap();
+        assert_eq!(reg.endpoints, vec!["1.2.3.4:5000"]);
+
+        let frame = host_pb::HostFrame {
+            frame: Some(host_pb::host_frame::Frame::Pong(host_pb::PongFrame {
+                timestamp_ms: 42,
+            })),
+        };
+        assert!(replica_b.deliver_to_replica("0xaaa", &frame).await);
+        assert!(!replica_b.deliver_to_replica("0xunknown", &frame).await);
+
+        assert_eq!(replica_a.drain_mailboxes().await, 1);
+        assert_eq!(rx.recv().await.unwrap(), frame);
+        // Frames are taken exactly once.
+        assert_eq!(replica_a.drain_mailboxes().await, 0);
+    }
+
+    #[tokio::test]
+    async fn unreplicated_router_does_not_use_mailbox() {
+        let tmp = TempDir::new().unwrap();
+        let router = Router::new(10, Some(tmp.path()));
+        let (tx, _rx) = NodeSender::channel(16, usize::MAX);
+        router.register("0xaaa".to_string(), String::new(), tx, None);
+        router.persist_registration("0xaaa").await;
+
+        let other = Router::new(10, Some(tmp.path()));
+        let frame = host_pb::HostFrame { frame: None };
+        assert!(!other.deliver_to_replica("0xaaa", &frame).await);
+    }
+
+    #[test]
+    fn registration_timestamps_must_increase() {
+        let router = Router::new(10, None);
+        assert!(router.accept_registration_timestamp("a", 1000));
+        assert!(!router.accept_registration_timestamp("a", 1000));
+        assert!(!router.accept_registration_timestamp("a", 999));
+        assert!(router.accept_registration_timestamp("a", 1001));
+        assert!(router.accept_registration_timestamp("b", 1000));
+    }
+
     #[tokio::test]
     async fn username_persistence() {
         let tmp = TempDir::new().unwrap();
@@ -898,4 +1772,90 @@ mod tests {
         assert!(subs1.is_empty());
         assert!(subs2.is_empty());
     }
+
+    #[tokio::test]
+    async fn registration_survives_restart() {
+        let tmp = TempDir::new().unwrap();
+        let data_dir = tmp.path();
+
+        {
+            let router = Router::new(10, Some(data_dir));
+            let (tx, _rx) = mpsc::channel(1);
+            router.register(
+                "a".to_string(),
+                "pubkey-a".to_string(),
+                tx,
+                Some("1.2.3.4:5000".to_string()),
+            );
+            router.persist_registration("a").await;
+            router.subscribe_room_persistent("room1", "a").await;
+            router.subscribe_room_persistent("room2", "a").await;
+            router.unsubscribe_room_persistent("room2", "a").await;
+        }
+
+        let router = Router::new(10, Some(data_dir));
+        assert!(router.get_sender("a").is_none());
+        assert_eq!(router.lookup_endpoints("a"), vec!["1.2.3.4:5000"]);
+
+        // Rooms come back when the node reconnects.
+        let (tx, _rx) = mpsc::channel(1);
+        router.register("a".to_string(), "pubkey-a".to_string(), tx, None);
+        let rooms = router.persist_registration("a").await;
+        assert_eq!(rooms, vec!["room1"]);
+        assert_eq!(router.get_room_subscribers("room1", "").len(), 1);
+        assert!(router.get_room_subscribers("room2", "").is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_registrations_are_not_restored() {
+        let tmp = TempDir::new().unwrap();
+        let data_dir = tmp.path();
+
+        {
+            let router = Router::new(10, Some(data_dir)).with_registration_ttl(Duration::ZERO);
+            let (tx, _rx) = mpsc::channel(1);
+            router.register(
+                "a".to_string(),
+                "pubkey-a".to_string(),
+                tx,
+                Some("1.2.3.4:5000".to_string()),
+            );
+            router.persist_registration("a").await;
+            router.subscribe_room_persistent("room1", "a").await;
+        }
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let router = Router::new(10, Some(data_dir));
+        assert!(router.lookup_endpoints("a").is_empty());
+        assert_eq!(router.purge_expired_registrations().await, 1);
+
+        let (tx, _rx) = mpsc::channel(1);
+        router.register("a".to_string(), "pubkey-a".to_string(), tx, None);
+        assert!(router.persist_registration("a").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refresh_extends_registration() {
+        let tmp = TempDir::new().unwrap();
+        let data_dir = tmp.path();
+
+        {
+            let router = Router::new(10, Some(data_dir)).with_registration_ttl(Duration::ZERO);
+            let (tx, _rx) = mpsc::channel(1);
+            router.register(
+                "a".to_string(),
+                "pubkey-a".to_string(),
+                tx,
+                Some("1.2.3.4:5000".to_string()),
+            );
+            router.persist_registration("a").await;
+        }
+        {
+            let router = Router::new(10, Some(data_dir));
+            router.refresh_registration("a").await;
+        }
+
+        let router = Router::new(10, Some(data_dir));
+        assert_eq!(router.lookup_endpoints("a"), vec!["1.2.3.4:5000"]);
+    }
 }
@@ -1,15 +1,23 @@
-use crate::router::Router;
-use agentbook_crypto::crypto::verify_signature;
+use crate::directory::RelayDirectory;
+use crate::federation::Federation;
+use crate::net;
+use crate::router::{NodeSender, QueueError, Router};
+use crate::stats::USAGE_WINDOW_MS;
+use agentbook_crypto::crypto::{
+    public_key_matches_node_id, relay_registration_payload, relay_usage_payload, verify_signature,
+};
 use agentbook_crypto::rate_limit::{CheckResult, RateLimiter};
+use agentbook_crypto::time::now_ms;
 use agentbook_proto::host::v1 as host_pb;
 use agentbook_proto::host::v1::host_service_server::{HostService, HostServiceServer};
 use anyhow::{Context, Result};
+use prost::Message;
 use std::net::SocketAddr;
 use std::path::Path;
 use std::pin::Pin;
 use std::sync::Arc;
 use tokio::net::TcpListener;
-use tokio::sync::{Mutex, mpsc, oneshot};
+use tokio::sync::{Mutex, oneshot};
 use tokio_stream::wrappers::TcpListenerStream;
 use tokio_stream::{Stream, StreamExt};
 use tonic::transport::Server;
@@ -21,18 +29,124 @@ pub struct HostServiceImpl {
     /// Per-node relay rate limit config.
     pub relay_burst: u32,
     pub relay_rate: f64,
+    /// Per-IP relay rate limiter, shared by every node connecting from an IP.
+    pub relay_ip_limiter: Arc<Mutex<RateLimiter>>,
+    /// Max bytes queued for delivery to one node before frames are refused.
+    pub max_queued_bytes: usize,
     /// Per-IP username registration rate limiter.
     pub register_limiter: Arc<Mutex<RateLimiter>>,
     /// Per-IP username lookup rate limiter.
     pub lookup_limiter: Arc<Mutex<RateLimiter>>,
+    /// Peer hosts to forward to for nodes not connected here (if federated).
+    pub federation: Option<Arc<Federation>>,
+    /// Reject legacy registrations signed over the bare node_id, which can be
+    /// replayed by anyone who observed one.
+    pub require_signed_registration: bool,
+    /// Relay list served by `GetRelayDirectory`, if this host publishes one.
+    pub directory: Option<Arc<RelayDirectory>>,
+}
+
+/// Default per-node delivery backlog budget.
+pub const DEFAULT_MAX_QUEUED_BYTES: usize = 8 * 1024 * 1024;
+
+/// How far a signed registration timestamp may drift from the relay's clock.
+pub const REGISTRATION_MAX_SKEW_MS: u64 = 5 * 60 * 1000;
+
+/// Check a RegisterFrame against its claimed identity.
+///
+/// The public key must derive to `node_id`, and the signature must cover
+/// either a fresh [`relay_registration_payload`] or, unless
+/// `require_timestamped` is set, the bare node_id sent by older clients.
+/// Returns true if the timestamped form was used.
+pub fn verify_register_frame(
+    register: &host_pb::RegisterFrame,
+    now_ms: u64,
+    require_timestamped: bool,
+) -> Result<bool, Status> {
+    if !public_key_matches_node_id(&register.public_key_b64, &register.node_id) {
+        return Err(Status::unauthenticated(
+            "public key does not match node_id on RegisterFrame",
+        ));
+    }
+
+    let payload = relay_registration_payload(&register.node_id, register.timestamp_ms);
+    if verify_signature(&register.public_key_b64, &payload, &register.signature_b64) {
+        if now_ms.abs_diff(register.timestamp_ms) > REGISTRATION_MAX_SKEW_MS {
+            return Err(Status::unauthenticated(
+                "RegisterFrame timestamp out of range",
+            ));
+        }
+        return Ok(true);
+    }
+
+    if !require_timestamped
+        && verify_signature(
+            &register.public_key_b64,
+            register.node_id.as_bytes(),
+            &register.signature_b64,
+        )
+    {
+        return Ok(false);
+    }
+
+    Err(Status::unauthenticated(
+        "invalid signature on RegisterFrame",
+    ))
+}
+
+/// `node_id`'s relay usage since `since_ms`.
+pub fn usage_report(router: &Router, node_id: &str, since_ms: u64) -> host_pb::UsageReport {
+    host_pb::UsageReport {
+        node_id: node_id.to_string(),
+        window_ms: USAGE_WINDOW_MS,
+        windows: router
+            .usage()
+            .windows(node_id, since_ms)
+            .into_iter()
+            .map(|w| host_pb::UsageWindow {
+                start_ms: w.start_ms,
+                frames: w.frames,
+                bytes: w.bytes,
+            })
+            .collect(),
+    }
 }
 
 pub fn peer_ip(req_remote: Option<SocketAddr>) -> String {
     req_remote
-        .map(|a| a.ip().to_string())
+        .map(|a| a.ip().to_canonical().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Key for the per-IP rate limiters (see [`net::rate_limit_key`]).
+pub fn peer_rate_limit_key(req_remote: Option<SocketAddr>) -> String {
+    req_remote
+        .map(|a| net::rate_limit_key(a.ip()))
         .unwrap_or_else(|| "unknown".to_string())
 }
 
+/// Error frame about a relayed envelope, naming it so the sender can tell
+/// which message failed.
+fn relay_error(code: &str, message: String, message_id: &str) -> host_pb::HostFrame {
+    host_pb::HostFrame {
+        frame: Some(host_pb::host_frame::Frame::Error(host_pb::ErrorFrame {
+            code: code.to_string(),
+            message,
+            message_id: message_id.to_string(),
+        })),
+    }
+}
+
+fn relay_ack(message_id: &str) -> host_pb::HostFrame {
+    host_pb::HostFrame {
+        frame: Some(host_pb::host_frame::Frame::RelayAck(
+            host_pb::RelayAckFrame {
+                message_id: message_id.to_string(),
+            },
+        )),
+    }
+}
+
 pub type HostStream = Pin<Box<dyn Stream<Item = Result<host_pb::HostFrame, Status>> + Send>>;
 
 #[tonic::async_trait]
@@ -43,215 +157,10 @@ impl HostService for HostServiceImpl {
         &self,
         req: Request<Streaming<host_pb::NodeFrame>>,
     ) -> Result<Response<Self::RelayStream>, Status> {
-        let observed_addr = req.remote_addr().map(|a| a.to_string());
-        let mut inbound = req.into_inner();
-
-        // Wait for the first frame to be a Register
-        let first = inbound
-            .next()
+        let remote_addr = req.remote_addr();
+        self.relay_session(remote_addr, req.into_inner())
             .await
-            .ok_or_else(|| Status::invalid_argument("empty stream"))?
-            .map_err(|e| Status::internal(e.to_string()))?;
-
-        let register = match first.frame {
-            Some(host_pb::node_frame::Frame::Register(r)) => r,
-            _ => {
-                return Err(Status::invalid_argument("first frame must be Register"));
-            }
-        };
-
-        let node_id = register.node_id.clone();
-
-        // Verify the registration signature
-        if !verify_signature(
-            &register.public_key_b64,
-            node_id.as_bytes(),
-            &register.signature_b64,
-        ) {
-            return Err(Status::unauthenticated(
-                "invalid signature on RegisterFrame",
-            ));
-        }
-
-        // Create outbound channel
-        let (tx, mut rx) = mpsc::channel::<host_pb::HostFrame>(256);
-
-        // Register in router (no global lock -- DashMap handles concurrency)
-        if !self.router.register(
-            node_id.clone(),
-            register.public_key_b64.clone(),
-            tx.clone(),
-            observed_addr,
-        ) {
-            return Err(Status::resource_exhausted("relay at capacity"));
-        }
-
-        // Send RegisterAck
-        let _ = tx
-            .send(host_pb::HostFrame {
-                frame: Some(host_pb::host_frame::Frame::RegisterAck(
-                    host_pb::RegisterAckFrame {
-                        success: true,
-                        error: None,
-                    },
-                )),
-            })
-            .await;
-
-        let router = self.router.clone();
-        let node_id_clone = node_id.clone();
-
-        // Per-node relay rate limiter
-        let relay_limiter = Arc::new(Mutex::new(RateLimiter::new(
-            self.relay_burst,
-            self.relay_rate,
-        )));
-
-        // Spawn inbound processor
-        tokio::spawn(async move {
-            while let Some(Ok(frame)) = inbound.next().await {
-                match frame.frame {
-                    Some(host_pb::node_frame::Frame::RelaySend(relay)) => {
-                        // Rate limit relay messages per node
-                        {
-                            let mut limiter = relay_limiter.lock().await;
-                            match limiter.check(&node_id_clone) {
-                                CheckResult::Allowed => {}
-                                CheckResult::RateLimited | CheckResult::Banned { .. } => {
-                                    let _ = tx
-                                        .send(host_pb::HostFrame {
-                                            frame: Some(host_pb::host_frame::Frame::Error(
-                                                host_pb::ErrorFrame {
-                                                    code: "RATE_LIMITED".to_string(),
-                                                    message: "relay rate limit exceeded"
-                                                        .to_string(),
-                                                },
-                                            )),
-                                        })
-                                        .await;
-                                    continue;
-                                }
-                            }
-                        }
-
-                        // Room broadcast: when to_node_id is empty and topic is set,
-                        // broadcast to all room subscribers.
-                        if relay.to_node_id.is_empty() {
-                            if let Some(ref envelope) = relay.envelope
-                                && let Some(ref topic) = envelope.topic
-                            {
-                                let subscribers =
-                                    router.get_room_subscribers(topic, &node_id_clone);
-                                let delivery = host_pb::HostFrame {
-                                    frame: Some(host_pb::host_frame::Frame::Delivery(
-                                        host_pb::DeliveryFrame {
-                                            envelope: relay.envelope.clone(),
-                                        },
-                                    )),
-                                };
-                                for sub_tx in subscribers {
-                                    let _ = sub_tx.send(delivery.clone()).await;
-                                }
-                            }
-                        } else if let Some(target_tx) = router.get_sender(&relay.to_node_id) {
-                            if let Some(envelope) = relay.envelope {
-                                let delivery = host_pb::HostFrame {
-                                    frame: Some(host_pb::host_frame::Frame::Delivery(
-                                        host_pb::DeliveryFrame {
-                                            envelope: Some(envelope),
-                                        },
-                                    )),
-                                };
-                                let _ = target_tx.send(delivery).await;
-                            }
-                        } else {
-                            let _ = tx
-                                .send(host_pb::HostFrame {
-                                    frame: Some(host_pb::host_frame::Frame::Error(
-                                        host_pb::ErrorFrame {
-                                            code: "NOT_FOUND".to_string(),
-                                            message: format!(
-                                                "node {} not connected",
-                                                relay.to_node_id
-                                            ),
-                                        },
-                                    )),
-                                })
-                                .await;
-                        }
-                    }
-                    Some(host_pb::node_frame::Frame::Ping(ping)) => {
-                        let _ = tx
-                            .send(host_pb::HostFrame {
-                                frame: Some(host_pb::host_frame::Frame::Pong(host_pb::PongFrame {
-                                    timestamp_ms: ping.timestamp_ms,
-                                })),
-                            })
-                            .await;
-                    }
-                    Some(host_pb::node_frame::Frame::RoomSubscribe(sub)) => {
-                        router.subscribe_room(&sub.room_id, &node_id_clone);
-                        tracing::debug!(node_id = %node_id_clone, room = %sub.room_id, "room subscribed");
-
-                        // Build display label: @username or truncated node_id
-                        let display_label = {
-                            let label = match router.lookup_node_id(&node_id_clone).await {
-                                Some((username, _)) => format!("@{username}"),
-                                None => {
-                                    let id = &node_id_clone;
-                                    if id.len() > 12 {
-                                        format!("{}...", &id[..9])
-                                    } else {
-                                        id.clone()
-                                    }
-                                }
-                            };
-                            format!("{label} has joined #{}", sub.room_id)
-                        };
-
-                        router
-                            .broadcast_join_to_room(&sub.room_id, &node_id_clone, display_label)
-                            .await;
-                    }
-                    Some(host_pb::node_frame::Frame::RoomUnsubscribe(unsub)) => {
-                        let display_label = {
-                            let label = match router.lookup_node_id(&node_id_clone).await {
-                                Some((username, _)) => format!("@{username}"),
-                                None => {
-                                    let id = &node_id_clone;
-                                    if id.len() > 12 {
-                                        format!("{}...", &id[..9])
-                                    } else {
-                                        id.clone()
-                                    }
-                                }
-                            };
-                            format!("{label} has left #{}", unsub.room_id)
-                        };
-
-                        router.unsubscribe_room(&unsub.room_id, &node_id_clone);
-                        tracing::debug!(node_id = %node_id_clone, room = %unsub.room_id, "room unsubscribed");
-                        router
-                            .broadcast_leave_to_room(&unsub.room_id, &node_id_clone, display_label)
-                            .await;
-                    }
-                    _ => {}
-                }
-            }
-
-            // Client disconnected -- unregister (no global lock needed)
-            router.unregister(&node_id_clone);
-            tracing::info!(node_id = %node_id_clone, "node disconnected");
-        });
-
-        // Return outbound stream
-        let stream = async_stream::stream! {
-            while let Some(frame) = rx.recv().await {
-                yield Ok(frame);
-            }
-        };
-
-        Ok(Response::new(Box::pin(stream)))
+            .map(Response::new)
     }
 
     async fn lookup(
@@ -259,8 +168,16 @@ impl HostService for HostServiceImpl {
         req: Request<host_pb::LookupRequest>,
     ) -> Result<Response<host_pb::LookupResponse>, Status> {
         let req = req.into_inner();
+        self.router.stats().record_lookup();
         // No lock needed -- DashMap lookup is concurrent
-        let endpoints = self.router.lookup_endpoints(&req.node_id);
+        let mut endpoints = self.router.lookup_endpoints(&req.node_id);
+        if endpoints.is_empty()
+            && self.router.is_replicated()
+            && let Some(reg) = self.router.lookup_registration(&req.node_id).await
+        {
+            // Connected to another replica sharing our registration store.
+            endpoints = reg.endpoints;
+        }
         Ok(Response::new(host_pb::LookupResponse {
             observed_endpoints: endpoints,
         }))
@@ -271,12 +188,20 @@ impl HostService for HostServiceImpl {
         req: Request<host_pb::RegisterUsernameRequest>,
     ) -> Result<Response<host_pb::RegisterUsernameResponse>, Status> {
         let ip = peer_ip(req.remote_addr());
+        let limit_key = peer_rate_limit_key(req.remote_addr());
         let req = req.into_inner();
 
+        if self.router.is_banned(&req.node_id, Some(&ip)) {
+            return Ok(Response::new(host_pb::RegisterUsernameResponse {
+                success: false,
+                error: Some("banned by relay operator".to_string()),
+            }));
+        }
+
         // Rate limit username registrations per IP (with auto-ban)
         {
             let mut limiter = self.register_limiter.lock().await;
-            match limiter.check(&ip) {
+            match limiter.check(&limit_key) {
                 CheckResult::Allowed => {}
                 CheckResult::RateLimited => {
                     return Ok(Response::new(host_pb::RegisterUsernameResponse {
@@ -312,6 +237,7 @@ impl HostService for HostServiceImpl {
             .await
         {
             Ok(()) => {
+                self.router.stats().record_username_registration();
                 tracing::info!(
                     username = %req.username,
                     node_id = %req.node_id,
@@ -334,18 +260,15 @@ impl HostService for HostServiceImpl {
         req: Request<host_pb::LookupNodeIdRequest>,
     ) -> Result<Response<host_pb::LookupNodeIdResponse>, Status> {
         let req = req.into_inner();
-        match self.router.lookup_node_id(&req.node_id).await {
-            Some((username, public_key_b64)) => Ok(Response::new(host_pb::LookupNodeIdResponse {
-                found: true,
-                username,
-                public_key_b64,
-            })),
-            None => Ok(Response::new(host_pb::LookupNodeIdResponse {
-                found: false,
-                username: String::new(),
-                public_key_b64: String::new(),
-            })),
+        self.router.stats().record_lookup();
+        let local = self.local_lookup_node_id(&req.node_id).await;
+        if !local.found
+            && let Some(federation) = &self.federation
+            && let Some(remote) = federation.lookup_node_id(&req.node_id).await
+        {
+            return Ok(Response::new(remote));
         }
+        Ok(Response::new(local))
     }
 
     async fn notify_follow(
@@ -451,17 +374,58 @@ impl HostService for HostServiceImpl {
         Ok(Response::new(host_pb::GetFollowingResponse { following }))
     }
 
+    async fn get_usage(
+        &self,
+        req: Request<host_pb::GetUsageRequest>,
+    ) -> Result<Response<host_pb::UsageReport>, Status> {
+        let req = req.into_inner();
+        if !public_key_matches_node_id(&req.public_key_b64, &req.node_id) {
+            return Err(Status::unauthenticated(
+                "public key does not match node_id on GetUsageRequest",
+            ));
+        }
+        let payload = relay_usage_payload(&req.node_id, req.timestamp_ms);
+        if !verify_signature(&req.public_key_b64, &payload, &req.signature_b64) {
+            return Err(Status::unauthenticated(
+                "invalid signature on GetUsageRequest",
+            ));
+        }
+        if now_ms().abs_diff(req.timestamp_ms) > REGISTRATION_MAX_SKEW_MS {
+            return Err(Status::unauthenticated(
+                "GetUsageRequest timestamp out of range",
+            ));
+        }
+        Ok(Response::new(usage_report(
+            &self.router,
+            &req.node_id,
+            req.since_ms,
+        )))
+    }
+
+    async fn get_relay_directory(
+        &self,
+        _req: Request<host_pb::GetRelayDirectoryRequest>,
+    ) -> Result<Response<host_pb::RelayDirectory>, Status> {
+        let Some(directory) = &self.directory else {
+            return Err(Status::not_found("this relay does not publish a directory"));
+        };
+        directory
+            .signed(now_ms())
+            .map(Response::new)
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+
     async fn lookup_username(
         &self,
         req: Request<host_pb::LookupUsernameRequest>,
     ) -> Result<Response<host_pb::LookupUsernameResponse>, Status> {
-        let ip = peer_ip(req.remote_addr());
+        let limit_key = peer_rate_limit_key(req.remote_addr());
         let req = req.into_inner();
 
         // Rate limit username lookups per IP (with auto-ban)
         {
             let mut limiter = self.lookup_limiter.lock().await;
-            match limiter.check(&ip) {
+            match limiter.check(&limit_key) {
                 CheckResult::Allowed => {}
                 CheckResult::RateLimited => {
                     return Err(Status::resource_exhausted("rate limited — try again later"));
@@ -475,18 +439,459 @@ impl HostService for HostServiceImpl {
             }
         }
 
+        self.router.stats().record_lookup();
+        let local = self.local_lookup_username(&req.username).await;
+        if !local.found
+            && let Some(federation) = &self.federation
+            && let Some(remote) = federation.lookup_username(&req.username).await
+        {
+            return Ok(Response::new(remote));
+        }
+        Ok(Response::new(local))
+    }
+
+    async fn federation_forward(
+        &self,
+        req: Request<host_pb::FederationForwardRequest>,
+    ) -> Result<Response<host_pb::FederationForwardResponse>, Status> {
+        self.authorize_peer(&req)?;
+        let ip = peer_ip(req.remote_addr());
+        let limit_key = peer_rate_limit_key(req.remote_addr());
+        let Some(envelope) = req.into_inner().envelope else {
+            return Err(Status::invalid_argument("missing envelope"));
+        };
+        let not_delivered = Ok(Response::new(host_pb::FederationForwardResponse {
+            delivered: false,
+        }));
+        // Only direct deliveries are federated; room broadcasts stay local.
+        if envelope.to_node_id.is_empty() {
+            return not_delivered;
+        }
+        let Some(target_tx) = self.router.get_sender(&envelope.to_node_id) else {
+            return not_delivered;
+        };
+        // The same checks a node's own RelaySend gets: operator bans on the
+        // sender and the peer host's address, and the per-IP relay rate, so a
+        // peer can't relay past them.
+        if self.router.is_banned(&envelope.from_node_id, Some(&ip)) {
+            tracing::info!(from = %envelope.from_node_id, ip = %ip, "rejected federated envelope from a banned sender");
+            self.router.stats().record_rejected();
+            return not_delivered;
+        }
+        if self.relay_ip_limiter.lock().await.check(&limit_key) != CheckResult::Allowed {
+            self.router.stats().record_rejected();
+            return not_delivered;
+        }
+        let len = envelope.encoded_len();
+        self.router.stats().record_relay(len);
+        self.router
+            .usage()
+            .record(&envelope.from_node_id, len, now_ms());
+        // The recipient's queued-byte budget applies as for local senders.
+        let sent = target_tx
+            .send(host_pb::HostFrame {
+                frame: Some(host_pb::host_frame::Frame::Delivery(
+                    host_pb::DeliveryFrame {
+                        envelope: Some(envelope),
+                    },
+                )),
+            })
+            .await;
+        if sent == Err(QueueError::Full) {
+            self.router.stats().record_rejected();
+        }
+        Ok(Response::new(host_pb::FederationForwardResponse {
+            delivered: sent.is_ok(),
+        }))
+    }
+
+    async fn federation_lookup_username(
+        &self,
+        req: Request<host_pb::LookupUsernameRequest>,
+    ) -> Result<Response<host_pb::LookupUsernameResponse>, Status> {
+        self.authorize_peer(&req)?;
+        let req = req.into_inner();
+        Ok(Response::new(
+            self.local_lookup_username(&req.username).await,
+        ))
+    }
+
+    async fn federation_lookup_node_id(
+        &self,
+        req: Request<host_pb::LookupNodeIdRequest>,
+    ) -> Result<Response<host_pb::LookupNodeIdResponse>, Status> {
+        self.authorize_peer(&req)?;
+        let req = req.into_inner();
+        Ok(Response::new(self.local_lookup_node_id(&req.node_id).await))
+    }
+}
+
+impl HostServiceImpl {
+    /// Run one node's relay session: wait for its Register frame, then route
+    /// its frames until the inbound stream ends. Returns the frames to send
+    /// back to the node. Shared by the gRPC `Relay` RPC and the WebSocket gateway.
+    pub async fn relay_session<S>(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        mut inbound: S,
+    ) -> Result<HostStream, Status>
+    where
+        S: Stream<Item = Result<host_pb::NodeFrame, Status>> + Send + Unpin + 'static,
+    {
+        let observed_addr = remote_addr.map(|a| net::canonical(a).to_string());
+        let ip = peer_ip(remote_addr);
+        let limit_key = peer_rate_limit_key(remote_addr);
+
+        // Wait for the first frame to be a Register
+        let first = inbound
+            .next()
+            .await
+            .ok_or_else(|| Status::invalid_argument("empty stream"))?
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let register = match first.frame {
+            Some(host_pb::node_frame::Frame::Register(r)) => r,
+            _ => {
+                return Err(Status::invalid_argument("first frame must be Register"));
+            }
+        };
+
+        let node_id = register.node_id.clone();
+
+        if self.router.is_banned(&node_id, Some(&ip)) {
+            tracing::info!(node_id = %node_id, ip = %ip, "rejected banned node");
+            return Err(Status::permission_denied("banned by relay operator"));
+        }
+        if self.router.is_draining() {
+            return Err(Status::unavailable("relay is draining"));
+        }
+
+        // Verify the registration signature and reject replayed frames
+        let timestamped =
+            verify_register_frame(&register, now_ms(), self.require_signed_registration)?;
+        if timestamped
+            && !self
+                .router
+                .accept_registration_timestamp(&node_id, register.timestamp_ms)
+        {
+            return Err(Status::unauthenticated("replayed RegisterFrame"));
+        }
+
+        // Create outbound channel
+        let (tx, mut rx) = NodeSender::channel(256, self.max_queued_bytes);
+
+        // Register in router (no global lock -- DashMap handles concurrency)
+        if !self.router.register(
+            node_id.clone(),
+            register.public_key_b64.clone(),
+            tx.clone(),
+            observed_addr,
+        ) {
+            return Err(Status::resource_exhausted("relay at capacity"));
+        }
+
+        // Send RegisterAck
+        let _ = tx
+            .send(host_pb::HostFrame {
+                frame: Some(host_pb::host_frame::Frame::RegisterAck(
+                    host_pb::RegisterAckFrame {
+                        success: true,
+                        error: None,
+                        relay_acks: true,
+                    },
+                )),
+            })
+            .await;
+
+        // Persist the registration and restore any rooms the node was in
+        // before a relay restart.
+        let restored_rooms = self.router.persist_registration(&node_id).await;
+        if !restored_rooms.is_empty() {
+            tracing::info!(node_id = %node_id, rooms = ?restored_rooms, "restored room subscriptions");
+        }
+
+        let router = self.router.clone();
+        let federation = self.federation.clone();
+        let relay_ip_limiter = self.relay_ip_limiter.clone();
+        let node_id_clone = node_id.clone();
+
+        // Per-node relay rate limiter
+        let relay_limiter = Arc::new(Mutex::new(RateLimiter::new(
+            self.relay_burst,
+            self.relay_rate,
+        )));
+
+        // Spawn inbound processor
+        tokio::spawn(async move {
+            loop {
+                let frame = tokio::select! {
+                    _ = tx.evicted() => {
+                        tracing::info!(node_id = %node_id_clone, "node evicted by operator");
+                        break;
+                    }
+                    frame = inbound.next() => match frame {
+                        Some(Ok(frame)) => frame,
+                        _ => break,
+                    },
+                };
+                tx.traffic().record_in(frame.encoded_len());
+
+                // Drop nodes the operator banned while they were connected.
+                if router.is_banned(&node_id_clone, Some(&ip)) {
+                    tracing::info!(node_id = %node_id_clone, "disconnecting banned node");
+                    break;
+                }
+                match frame.frame {
+                    Some(host_pb::node_frame::Frame::RelaySend(relay)) => {
+                        let message_id = relay
+                            .envelope
+                            .as_ref()
+                            .map(|e| e.message_id.clone())
+                            .unwrap_or_default();
+                        // Rate limit relay messages per node and per source IP
+                        {
+                            let node_check = relay_limiter.lock().await.check(&node_id_clone);
+                            let check = match node_check {
+                                CheckResult::Allowed => {
+                                    relay_ip_limiter.lock().await.check(&limit_key)
+                                }
+                                limited => limited,
+                            };
+                            match check {
+                                CheckResult::Allowed => {}
+                                CheckResult::RateLimited | CheckResult::Banned { .. } => {
+                                    router.stats().record_rejected();
+                                    let _ = tx
+                                        .send(relay_error(
+                                            "RATE_LIMITED",
+                                            "relay rate limit exceeded".to_string(),
+                                            &message_id,
+                                        ))
+                                        .await;
+                                    continue;
+                                }
+                            }
+                        }
+
+                        if let Some(envelope) = &relay.envelope {
+                            router.stats().record_relay(envelope.encoded_len());
+                            router
+                                .usage()
+                                .record(&node_id_clone, envelope.encoded_len(), now_ms());
+                        }
+
+                        // Room broadcast: when to_node_id is empty and topic is set,
+                        // broadcast to all room subscribers.
+                        if relay.to_node_id.is_empty() {
+                            if let Some(ref envelope) = relay.envelope
+                                && let Some(ref topic) = envelope.topic
+                            {
+                                let subscribers =
+                                    router.get_room_subscribers(topic, &node_id_clone);
+                                let delivery = host_pb::HostFrame {
+                                    frame: Some(host_pb::host_frame::Frame::Delivery(
+                                        host_pb::DeliveryFrame {
+                                            envelope: relay.envelope.clone(),
+                                        },
+                                    )),
+                                };
+                                for sub_tx in subscribers {
+                                    let _ = sub_tx.send(delivery.clone()).await;
+                                }
+                                router
+                                    .deliver_room_to_replicas(topic, &node_id_clone, &delivery)
+                                    .await;
+                            }
+                            let _ = tx.send(relay_ack(&message_id)).await;
+                        } else if let Some(target_tx) = router.get_sender(&relay.to_node_id) {
+                            if let Some(envelope) = relay.envelope {
+                                let delivery = host_pb::HostFrame {
+                                    frame: Some(host_pb::host_frame::Frame::Delivery(
+                                        host_pb::DeliveryFrame {
+                                            envelope: Some(envelope),
+                                        },
+                                    )),
+                                };
+                                let reply = match target_tx.send(delivery).await {
+                                    Err(QueueError::Full) => {
+                                        router.stats().record_rejected();
+                                        relay_error(
+                                            "QUEUE_FULL",
+                                            format!(
+                                                "delivery queue for {} is full",
+                                                relay.to_node_id
+                                            ),
+                                            &message_id,
+                                        )
+                                    }
+                                    Err(QueueError::Closed) => relay_error(
+                                        "NOT_FOUND",
+                                        format!("node {} not connected", relay.to_node_id),
+                                        &message_id,
+                                    ),
+                                    Ok(()) => relay_ack(&message_id),
+                                };
+                                let _ = tx.send(reply).await;
+                            }
+                        } else {
+                            // Not connected here: try a replica sharing our store,
+                            // then the node's home relay via federation. Peers can
+                            // take seconds each, so this runs beside the session
+                            // rather than holding up its next frame.
+                            let router = router.clone();
+                            let federation = federation.clone();
+                            let tx = tx.clone();
+                            tokio::spawn(async move {
+                                let delivered = match relay.envelope {
+                                    Some(envelope) => {
+                                        let delivery = host_pb::HostFrame {
+                                            frame: Some(host_pb::host_frame::Frame::Delivery(
+                                                host_pb::DeliveryFrame {
+                                                    envelope: Some(envelope.clone()),
+                                                },
+                                            )),
+                                        };
+                                        router
+                                            .deliver_to_replica(&relay.to_node_id, &delivery)
+                                            .await
+                                            || match &federation {
+                                                Some(federation) => {
+                                                    federation.forward(envelope).await
+                                                }
+                                                None => false,
+                                            }
+                                    }
+                                    None => false,
+                                };
+                                let reply = if delivered {
+                                    relay_ack(&message_id)
+                                } else {
+                                    router.stats().record_rejected();
+                                    relay_error(
+                                        "NOT_FOUND",
+                                        format!("node {} not connected", relay.to_node_id),
+                                        &message_id,
+                                    )
+                                };
+                                let _ = tx.send(reply).await;
+                            });
+                        }
+                    }
+                    Some(host_pb::node_frame::Frame::Ping(ping)) => {
+                        router.refresh_registration(&node_id_clone).await;
+                        let _ = tx
+                            .send(host_pb::HostFrame {
+                                frame: Some(host_pb::host_frame::Frame::Pong(host_pb::PongFrame {
+                                    timestamp_ms: ping.timestamp_ms,
+                                })),
+                            })
+                            .await;
+                    }
+                    Some(host_pb::node_frame::Frame::RoomSubscribe(sub)) => {
+                        router
+                            .subscribe_room_persistent(&sub.room_id, &node_id_clone)
+                            .await;
+                        tracing::debug!(node_id = %node_id_clone, room = %sub.room_id, "room subscribed");
+
+                        // Build display label: @username or truncated node_id
+                        let display_label = {
+                            let label = match router.lookup_node_id(&node_id_clone).await {
+                                Some((username, _)) => format!("@{username}"),
+                                None => {
+                                    let id = &node_id_clone;
+                                    if id.len() > 12 {
+                                        format!("{}...", &id[..9])
+                                    } else {
+                                        id.clone()
+                                    }
+                                }
+                            };
+                            format!("{label} has joined #{}", sub.room_id)
+                        };
+
+                        router
+                            .broadcast_join_to_room(&sub.room_id, &node_id_clone, display_label)
+                            .await;
+                    }
+                    Some(host_pb::node_frame::Frame::RoomUnsubscribe(unsub)) => {
+                        let display_label = {
+                            let label = match router.lookup_node_id(&node_id_clone).await {
+                                Some((username, _)) => format!("@{username}"),
+                                None => {
+                                    let id = &node_id_clone;
+                                    if id.len() > 12 {
+                                        format!("{}...", &id[..9])
+                                    } else {
+                                        id.clone()
+                                    }
+                                }
+                            };
+                            format!("{label} has left #{}", unsub.room_id)
+                        };
+
+                        router
+                            .unsubscribe_room_persistent(&unsub.room_id, &node_id_clone)
+                            .await;
+                        tracing::debug!(node_id = %node_id_clone, room = %unsub.room_id, "room unsubscribed");
+                        router
+                            .broadcast_leave_to_room(&unsub.room_id, &node_id_clone, display_label)
+                            .await;
+                    }
+                    _ => {}
+                }
+            }
+
+            // Client disconnected -- unregister (no global lock needed)
+            router.unregister(&node_id_clone);
+            tracing::info!(node_id = %node_id_clone, "node disconnected");
+        });
+
+        // Return outbound stream
+        let stream = async_stream::stream! {
+            while let Some(frame) = rx.recv().await {
+                yield Ok(frame);
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+
+    /// Federation RPCs are only served when this host is federated.
+    fn authorize_peer<T>(&self, req: &Request<T>) -> Result<(), Status> {
+        match &self.federation {
+            Some(federation) => federation.authorize(req),
+            None => Err(Status::unimplemented("federation is not enabled")),
+        }
+    }
+
+    async fn local_lookup_username(&self, username: &str) -> host_pb::LookupUsernameResponse {
         // SQLite op runs on spawn_blocking inside Router
-        match self.router.lookup_username(&req.username).await {
-            Some(entry) => Ok(Response::new(host_pb::LookupUsernameResponse {
+        match self.router.lookup_username(username).await {
+            Some(entry) => host_pb::LookupUsernameResponse {
                 found: true,
                 node_id: entry.node_id,
                 public_key_b64: entry.public_key_b64,
-            })),
-            None => Ok(Response::new(host_pb::LookupUsernameResponse {
+            },
+            None => host_pb::LookupUsernameResponse {
                 found: false,
                 node_id: String::new(),
                 public_key_b64: String::new(),
-            })),
+            },
+        }
+    }
+
+    async fn local_lookup_node_id(&self, node_id: &str) -> host_pb::LookupNodeIdResponse {
+        match self.router.lookup_node_id(node_id).await {
+            Some((username, public_key_b64)) => host_pb::LookupNodeIdResponse {
+                found: true,
+                username,
+                public_key_b64,
+            },
+            None => host_pb::LookupNodeIdResponse {
+                found: false,
+                username: String::new(),
+                public_key_b64: String::new(),
+            },
         }
     }
 }
@@ -494,6 +899,14 @@ impl HostService for HostServiceImpl {
 /// Spawn a relay host on a random port with a temp data directory.
 /// Returns the bound address and a shutdown handle.
 pub async fn spawn_relay(data_dir: Option<&Path>) -> Result<(SocketAddr, oneshot::Sender<()>)> {
+    spawn_federated_relay(data_dir, None).await
+}
+
+/// Like [`spawn_relay`], but federated with the given peer hosts.
+pub async fn spawn_federated_relay(
+    data_dir: Option<&Path>,
+    federation: Option<Arc<Federation>>,
+) -> Result<(SocketAddr, oneshot::Sender<()>)> {
     let router = Arc::new(Router::new(1000, data_dir));
 
     let listener = TcpListener::bind("127.0.0.1:0")
@@ -505,8 +918,13 @@ pub async fn spawn_relay(data_dir: Option<&Path>) -> Result<(SocketAddr, oneshot
         router,
         relay_burst: 100,
         relay_rate: 100.0,
+        relay_ip_limiter: Arc::new(Mutex::new(RateLimiter::new(1000, 1000.0))),
+        max_queued_bytes: DEFAULT_MAX_QUEUED_BYTES,
         register_limiter: Arc::new(Mutex::new(RateLimiter::new(100, 100.0))),
         lookup_limiter: Arc::new(Mutex::new(RateLimiter::new(100, 100.0))),
+        federation,
+        require_signed_registration: false,
+        directory: None,
     };
 
     let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
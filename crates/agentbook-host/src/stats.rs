@@ -0,0 +1,222 @@
+use agentbook_crypto::time::now_ms;
+use dashmap::DashMap;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Length of one usage window (an hour).
+pub const USAGE_WINDOW_MS: u64 = 60 * 60 * 1000;
+/// Windows kept per node: a week of hours.
+const USAGE_WINDOWS_KEPT: usize = 7 * 24;
+
+/// Relay-wide counters reported by the admin API.
+pub struct HostStats {
+    started_at_ms: u64,
+    relayed_frames: AtomicU64,
+    relayed_bytes: AtomicU64,
+    rejected_frames: AtomicU64,
+    lookups: AtomicU64,
+    username_registrations: AtomicU64,
+}
+
+impl Default for HostStats {
+    fn default() -> Self {
+        Self {
+            started_at_ms: now_ms(),
+            relayed_frames: AtomicU64::new(0),
+            relayed_bytes: AtomicU64::new(0),
+            rejected_frames: AtomicU64::new(0),
+            lookups: AtomicU64::new(0),
+            username_registrations: AtomicU64::new(0),
+        }
+    }
+}
+
+impl HostStats {
+    pub fn record_relay(&self, bytes: usize) {
+        self.relayed_frames.fetch_add(1, Ordering::Relaxed);
+        self.relayed_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// A relay frame refused for rate limiting, a full queue, or an unknown target.
+    pub fn record_rejected(&self) {
+        self.rejected_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_lookup(&self) {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_username_registration(&self) {
+        self.username_registrations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn uptime_ms(&self) -> u64 {
+        now_ms().saturating_sub(self.started_at_ms)
+    }
+
+    pub fn relayed_frames(&self) -> u64 {
+        self.relayed_frames.load(Ordering::Relaxed)
+    }
+
+    pub fn relayed_bytes(&self) -> u64 {
+        self.relayed_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn rejected_frames(&self) -> u64 {
+        self.rejected_frames.load(Ordering::Relaxed)
+    }
+
+    pub fn lookups(&self) -> u64 {
+        self.lookups.load(Ordering::Relaxed)
+    }
+
+    pub fn username_registrations(&self) -> u64 {
+        self.username_registrations.load(Ordering::Relaxed)
+    }
+}
+
+/// Traffic counters for one node connection.
+pub struct NodeTraffic {
+    pub connected_at_ms: u64,
+    frames_in: AtomicU64,
+    bytes_in: AtomicU64,
+    frames_out: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl Default for NodeTraffic {
+    fn default() -> Self {
+        Self {
+            connected_at_ms: now_ms(),
+            frames_in: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            frames_out: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+        }
+    }
+}
+
+impl NodeTraffic {
+    /// A frame received from the node.
+    pub fn record_in(&self, bytes: usize) {
+        self.frames_in.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// A frame queued for delivery to the node.
+    pub fn record_out(&self, bytes: usize) {
+        self.frames_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn frames_in(&self) -> u64 {
+        self.frames_in.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    pub fn frames_out(&self) -> u64 {
+        self.frames_out.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+}
+
+/// Traffic one node relayed during one window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsageWindow {
+    pub start_ms: u64,
+    pub frames: u64,
+    pub bytes: u64,
+}
+
+/// Bytes each node sent through the relay, in [`USAGE_WINDOW_MS`] windows,
+/// kept across reconnects so operators can attribute usage. Held in
+/// memory; a restart starts over.
+#[derive(Default)]
+pub struct UsageLedger {
+    nodes: DashMap<String, VecDeque<UsageWindow>>,
+}
+
+impl UsageLedger {
+    /// Count a relayed frame of `bytes` from `node_id`.
+    pub fn record(&self, node_id: &str, bytes: usize, now_ms: u64) {
+        let start_ms = now_ms - now_ms % USAGE_WINDOW_MS;
+        let mut windows = self.nodes.entry(node_id.to_string()).or_default();
+        match windows.back_mut() {
+            Some(window) if window.start_ms == start_ms => {
+                window.frames += 1;
+                window.bytes += bytes as u64;
+            }
+            _ => {
+                windows.push_back(UsageWindow {
+                    start_ms,
+                    frames: 1,
+                    bytes: bytes as u64,
+                });
+                if windows.len() > USAGE_WINDOWS_KEPT {
+                    windows.pop_front();
+                }
+            }
+        }
+    }
+
+    /// `node_id`'s windows ending after `since_ms`, oldest first.
+    pub fn windows(&self, node_id: &str, since_ms: u64) -> Vec<UsageWindow> {
+        self.nodes
+            .get(node_id)
+            .map(|windows| {
+                windows
+                    .iter()
+                    .filter(|w| w.start_ms + USAGE_WINDOW_MS > since_ms)
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_is_bucketed_per_node_and_window() {
+        let ledger = UsageLedger::default();
+        let hour = USAGE_WINDOW_MS;
+        ledger.record("0xa", 100, 10);
+        ledger.record("0xa", 50, hour - 1);
+        ledger.record("0xa", 70, hour);
+        ledger.record("0xb", 5, hour);
+
+        assert_eq!(
+            ledger.windows("0xa", 0),
+            [
+                UsageWindow {
+                    start_ms: 0,
+                    frames: 2,
+                    bytes: 150
+                },
+                UsageWindow {
+                    start_ms: hour,
+                    frames: 1,
+                    bytes: 70
+                },
+            ]
+        );
+        assert_eq!(ledger.windows("0xa", hour).len(), 1);
+        assert!(ledger.windows("0xc", 0).is_empty());
+
+        for i in 0..USAGE_WINDOWS_KEPT as u64 {
+            ledger.record("0xb", 1, (i + 2) * hour);
+        }
+        let kept = ledger.windows("0xb", 0);
+        assert_eq!(kept.len(), USAGE_WINDOWS_KEPT);
+        assert_eq!(kept[0].start_ms, 2 * hour);
+    }
+}
@@ -0,0 +1,55 @@
+/// A persisted node registration.
+#[derive(Clone, Debug)]
+pub struct RegistrationRow {
+    pub node_id: String,
+    pub public_key_b64: String,
+    pub endpoints: Vec<String>,
+    pub expires_at_ms: u64,
+}
+
+/// Backing store for node registrations, room subscriptions, and mailboxes
+/// of frames waiting for nodes connected to another replica.
+///
+/// The default implementation is the relay's SQLite database. Relay replicas
+/// behind a load balancer share one store (a common `--data-dir`, or another
+/// implementation such as Redis or Postgres) so that any replica can answer
+/// rendezvous lookups and reach any node.
+///
+/// Methods are synchronous; the router calls them from `spawn_blocking`.
+pub trait RegistrationStore: Send + Sync {
+    fn upsert_registration(
+        &self,
+        node_id: &str,
+        public_key_b64: &str,
+        endpoints: &[String],
+        expires_at_ms: u64,
+    ) -> Result<(), String>;
+
+    fn refresh_registration(&self, node_id: &str, expires_at_ms: u64) -> Result<(), String>;
+
+    /// A node's registration, if it has not expired.
+    fn registration(&self, node_id: &str, now_ms: u64) -> Option<RegistrationRow>;
+
+    /// Registrations that have not expired yet.
+    fn live_registrations(&self, now_ms: u64) -> Vec<RegistrationRow>;
+
+    /// Delete expired registrations, their room subscriptions, and expired
+    /// mailbox frames. Returns the node_ids removed.
+    fn purge_expired_registrations(&self, now_ms: u64) -> Vec<String>;
+
+    fn save_room_subscription(&self, room_id: &str, node_id: &str) -> Result<(), String>;
+
+    fn delete_room_subscription(&self, room_id: &str, node_id: &str) -> Result<(), String>;
+
+    /// Rooms a node is subscribed to.
+    fn room_subscriptions(&self, node_id: &str) -> Vec<String>;
+
+    /// Nodes subscribed to a room, on any replica.
+    fn room_members(&self, room_id: &str) -> Vec<String>;
+
+    /// Queue an encoded frame for a node connected to another replica.
+    fn push_mailbox(&self, node_id: &str, frame: &[u8], expires_at_ms: u64) -> Result<(), String>;
+
+    /// Remove and return unexpired frames queued for any of `node_ids`.
+    fn take_mailbox(&self, node_ids: &[String], now_ms: u64) -> Vec<(String, Vec<u8>)>;
+}
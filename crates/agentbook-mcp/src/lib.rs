@@ -0,0 +1,328 @@
+//! Model Context Protocol server exposing the node's messaging API as tools.
+//!
+//! Speaks JSON-RPC 2.0 over stdio (one message per line), so MCP-capable
+//! agents can read their inbox, message friends and chat in rooms without
+//! shelling out to the CLI. Each tool call becomes one socket [`Request`];
+//! the tool's arguments are the request's fields. Wallet and admin requests
+//! are deliberately not exposed.
+
+use agentbook::client::NodeClient;
+use agentbook::protocol::Request;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+
+/// MCP revision this server implements.
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// One tool argument: name, JSON schema type, description, required.
+type Param = (&'static str, &'static str, &'static str, bool);
+
+struct Tool {
+    name: &'static str,
+    /// Wire tag of the socket request the tool sends.
+    request: &'static str,
+    description: &'static str,
+    params: &'static [Param],
+}
+
+const TOOLS: &[Tool] = &[
+    Tool {
+        name: "whoami",
+        request: "identity",
+        description: "Show this node's id, public key and username.",
+        params: &[],
+    },
+    Tool {
+        name: "inbox",
+        request: "inbox",
+        description: "List DMs and feed posts, newest first.",
+        params: &[
+            ("unread_only", "boolean", "Only unread messages.", false),
+            ("limit", "integer", "Maximum number of messages.", false),
+        ],
+    },
+    Tool {
+        name: "ack_message",
+        request: "inbox_ack",
+        description: "Mark an inbox message as read.",
+        params: &[("message_id", "string", "Message to mark read.", true)],
+    },
+    Tool {
+        name: "send_dm",
+        request: "send_dm",
+        description: "Send an encrypted DM to a mutual follow.",
+        params: &[
+            (
+                "to",
+                "string",
+                "Node id, wallet address or @username.",
+                true,
+            ),
+            ("body", "string", "Message text.", true),
+        ],
+    },
+    Tool {
+        name: "post_feed",
+        request: "post_feed",
+        description: "Post an encrypted message to all followers.",
+        params: &[("body", "string", "Post text.", true)],
+    },
+    Tool {
+        name: "follow",
+        request: "follow",
+        description: "Follow a node.",
+        params: &[(
+            "target",
+            "string",
+            "Node id, wallet address or @username.",
+            true,
+        )],
+    },
+    Tool {
+        name: "following",
+        request: "following",
+        description: "List nodes this node follows.",
+        params: &[],
+    },
+    Tool {
+        name: "lookup_username",
+        request: "lookup_username",
+        description: "Resolve a username to a node id on the relay.",
+        params: &[("username", "string", "Username without @.", true)],
+    },
+    Tool {
+        name: "list_rooms",
+        request: "list_rooms",
+        description: "List joined rooms.",
+        params: &[],
+    },
+    Tool {
+        name: "join_room",
+        request: "join_room",
+        description: "Join a room; a passphrase makes it an encrypted room.",
+        params: &[
+            ("room", "string", "Room name.", true),
+            (
+                "passphrase",
+                "string",
+                "Shared secret for secure rooms.",
+                false,
+            ),
+        ],
+    },
+    Tool {
+        name: "room_inbox",
+        request: "room_inbox",
+        description: "Read recent messages in a room.",
+        params: &[
+            ("room", "string", "Room name.", true),
+            ("limit", "integer", "Maximum number of messages.", false),
+        ],
+    },
+    Tool {
+        name: "send_room",
+        request: "send_room",
+        description: "Send a message to a room (140 characters, 3 second cooldown).",
+        params: &[
+            ("room", "string", "Room name.", true),
+            ("body", "string", "Message text.", true),
+        ],
+    },
+];
+
+impl Tool {
+    fn describe(&self) -> Value {
+        let properties: serde_json::Map<String, Value> = self
+            .params
+            .iter()
+            .map(|(name, ty, description, _)| {
+                (
+                    name.to_string(),
+                    json!({ "type": ty, "description": description }),
+                )
+            })
+            .collect();
+        let required: Vec<&str> = self.params.iter().filter(|p| p.3).map(|p| p.0).collect();
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "inputSchema": {
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            },
+        })
+    }
+
+    /// Build the socket request from the call's arguments.
+    fn request(&self, arguments: Option<Value>) -> Result<Request, String> {
+        let mut fields = match arguments {
+            None | Some(Value::Null) => serde_json::Map::new(),
+            Some(Value::Object(fields)) => fields,
+            Some(_) => return Err("arguments must be an object".to_string()),
+        };
+        fields.insert("type".to_string(), json!(self.request));
+        serde_json::from_value(Value::Object(fields)).map_err(|e| format!("invalid arguments: {e}"))
+    }
+}
+
+/// Answers MCP requests by calling the daemon at `socket_path`.
+pub struct McpServer {
+    socket_path: PathBuf,
+}
+
+impl McpServer {
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self { socket_path }
+    }
+
+    /// Handle one line from the client. Returns the reply to write, or
+    /// `None` for notifications.
+    pub async fn handle_line(&self, line: &str) -> Option<Value> {
+        match serde_json::from_str::<Value>(line) {
+            Ok(msg) => self.handle(msg).await,
+            Err(e) => Some(error(Value::Null, PARSE_ERROR, &e.to_string())),
+        }
+    }
+
+    async fn handle(&self, msg: Value) -> Option<Value> {
+        let id = msg.get("id").cloned()?;
+        let method = msg
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let params = msg.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": {
+                    "name": "agentbook",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            }),
+            "ping" => json!({}),
+            "tools/list" => {
+                json!({ "tools": TOOLS.iter().map(Tool::describe).collect::<Vec<_>>() })
+            }
+            "tools/call" => {
+                let name = params
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                let Some(tool) = TOOLS.iter().find(|t| t.name == name) else {
+                    return Some(error(id, INVALID_PARAMS, &format!("unknown tool: {name}")));
+                };
+                self.call_tool(tool, params.get("arguments").cloned()).await
+            }
+            _ => {
+                return Some(error(
+                    id,
+                    METHOD_NOT_FOUND,
+                    &format!("method not found: {method}"),
+                ));
+            }
+        };
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    async fn call_tool(&self, tool: &Tool, arguments: Option<Value>) -> Value {
+        let outcome = match tool.request(arguments) {
+            Ok(request) => self.request(request).await.map_err(|e| format!("{e:#}")),
+            Err(e) => Err(e),
+        };
+        match outcome {
+            Ok(data) => {
+                let text = match data {
+                    Some(data) => serde_json::to_string_pretty(&data).unwrap_or_default(),
+                    None => "ok".to_string(),
+                };
+                json!({ "content": [{ "type": "text", "text": text }], "isError": false })
+            }
+            Err(e) => json!({ "content": [{ "type": "text", "text": e }], "isError": true }),
+        }
+    }
+
+    async fn request(&self, request: Request) -> anyhow::Result<Option<Value>> {
+        let mut client = NodeClient::connect(&self.socket_path).await?;
+        client.request(request).await
+    }
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> McpServer {
+        McpServer::new(PathBuf::from("/nonexistent/agentbook.sock"))
+    }
+
+    #[test]
+    fn every_tool_builds_its_request() {
+        for tool in TOOLS {
+            let args: serde_json::Map<String, Value> = tool
+                .params
+                .iter()
+                .filter(|p| p.3)
+                .map(|p| (p.0.to_string(), json!("x")))
+                .collect();
+            let req = tool.request(Some(Value::Object(args)));
+            assert!(req.is_ok(), "{}: {:?}", tool.name, req.err());
+        }
+    }
+
+    #[test]
+    fn missing_arguments_are_reported() {
+        let send_dm = TOOLS.iter().find(|t| t.name == "send_dm").unwrap();
+        let err = send_dm
+            .request(Some(json!({ "to": "@alice" })))
+            .unwrap_err();
+        assert!(err.contains("body"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn initialize_and_list_tools() {
+        let server = server();
+        let init = server
+            .handle_line(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#)
+            .await
+            .unwrap();
+        assert_eq!(init["result"]["protocolVersion"], PROTOCOL_VERSION);
+
+        let notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+        assert!(server.handle_line(notification).await.is_none());
+
+        let list = server
+            .handle_line(r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#)
+            .await
+            .unwrap();
+        let tools = list["result"]["tools"].as_array().unwrap();
+        assert_eq!(tools.len(), TOOLS.len());
+        let send_dm = tools.iter().find(|t| t["name"] == "send_dm").unwrap();
+        assert_eq!(send_dm["inputSchema"]["required"], json!(["to", "body"]));
+    }
+
+    #[tokio::test]
+    async fn tool_errors_are_results_and_bad_calls_are_errors() {
+        let server = server();
+        let call = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call",
+                       "params":{"name":"whoami","arguments":{}}}"#;
+        let resp = server.handle_line(call).await.unwrap();
+        assert_eq!(resp["result"]["isError"], true);
+
+        let unknown = r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"nope"}}"#;
+        let resp = server.handle_line(unknown).await.unwrap();
+        assert_eq!(resp["error"]["code"], INVALID_PARAMS);
+
+        let resp = server.handle_line("{not json").await.unwrap();
+        assert_eq!(resp["error"]["code"], PARSE_ERROR);
+    }
+}
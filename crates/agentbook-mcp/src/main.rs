@@ -0,0 +1,40 @@
+use agentbook::client::default_socket_path;
+use agentbook_mcp::McpServer;
+use anyhow::Result;
+use clap::Parser;
+use std::path::PathBuf;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about = "MCP server (stdio) for the agentbook node daemon"
+)]
+struct Args {
+    /// Path to the node daemon's Unix socket.
+    #[arg(long)]
+    socket: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let server = McpServer::new(args.socket.unwrap_or_else(default_socket_path));
+
+    // stdout carries the protocol, so nothing else may be printed there.
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(reply) = server.handle_line(&line).await {
+            let mut out = serde_json::to_vec(&reply)?;
+            out.push(b'\n');
+            stdout.write_all(&out).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Encryption of node state files at rest.
+//!
+//! Each encrypted record is a single line, `enc1:<nonce_b64>:<ciphertext_b64>`,
+//! so line-oriented files like the inbox journal can still be appended to one
+//! record at a time. Records without the prefix are legacy plaintext; stores
+//! read them as-is and rewrite them encrypted, which migrates existing state
+//! directories on first load.
+
+use crate::crypto::{ENVELOPE_KEY_BYTES, decrypt_with_key, derive_symmetric_key, encrypt_with_key};
+use anyhow::{Context, Result, bail};
+use zeroize::Zeroizing;
+
+const RECORD_PREFIX: &str = "enc1:";
+const STATE_KEY_LABEL: &[u8] = b"agentbook-state-at-rest-v1";
+
+/// Seals and opens state file records with a key derived from the recovery key.
+#[derive(Clone)]
+pub struct StateCipher {
+    key: Zeroizing<[u8; ENVELOPE_KEY_BYTES]>,
+}
+
+impl StateCipher {
+    pub fn new(kek: &[u8; ENVELOPE_KEY_BYTES]) -> Self {
+        Self {
+            key: Zeroizing::new(derive_symmetric_key(STATE_KEY_LABEL, kek)),
+        }
+    }
+
+    /// Encrypt one record. The result never contains a newline.
+    pub fn seal(&self, plaintext: &str) -> Result<String> {
+        let (ciphertext_b64, nonce_b64) = encrypt_with_key(&self.key, plaintext.as_bytes())?;
+        Ok(format!("{RECORD_PREFIX}{nonce_b64}:{ciphertext_b64}"))
+    }
+
+    /// Decrypt a record produced by [`StateCipher::seal`].
+    pub fn open(&self, record: &str) -> Result<String> {
+        let Some((nonce_b64, ciphertext_b64)) = record
+            .strip_prefix(RECORD_PREFIX)
+            .and_then(|rest| rest.split_once(':'))
+        else {
+            bail!("not an encrypted state record");
+        };
+        let plaintext = decrypt_with_key(&self.key, ciphertext_b64, nonce_b64)
+            .context("failed to decrypt state record (wrong recovery key?)")?;
+        String::from_utf8(plaintext).context("decrypted state record is not UTF-8")
+    }
+}
+
+/// Whether `record` is encrypted rather than legacy plaintext.
+pub fn is_sealed(record: &str) -> bool {
+    record.starts_with(RECORD_PREFIX)
+}
+
+/// Encode a record for disk: sealed when a cipher is configured.
+pub(crate) fn encode(cipher: Option<&StateCipher>, plaintext: &str) -> Result<String> {
+    match cipher {
+        Some(cipher) => cipher.seal(plaintext),
+        None => Ok(plaintext.to_string()),
+    }
+}
+
+/// Decode a record read from disk, accepting legacy plaintext.
+pub(crate) fn decode(cipher: Option<&StateCipher>, record: &str) -> Result<String> {
+    if !is_sealed(record) {
+        return Ok(record.to_string());
+    }
+    match cipher {
+        Some(cipher) => cipher.open(record),
+        None => bail!("state file is encrypted but no recovery key was provided"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::random_key_material;
+
+    #[test]
+    fn seal_open_round_trip() {
+        let cipher = StateCipher::new(&random_key_material());
+        let record = cipher.seal("{\"a\":\n1}").unwrap();
+        assert!(is_sealed(&record));
+        assert!(!record.contains('\n'));
+        assert_eq!(cipher.open(&record).unwrap(), "{\"a\":\n1}");
+    }
+
+    #[test]
+    fn wrong_key_and_missing_key_fail() {
+        let cipher = StateCipher::new(&random_key_material());
+        let other = StateCipher::new(&random_key_material());
+        let record = cipher.seal("secret").unwrap();
+        assert!(other.open(&record).is_err());
+        assert!(decode(None, &record).is_err());
+    }
+
+    #[test]
+    fn plaintext_passes_through_decode() {
+        let cipher = StateCipher::new(&random_key_material());
+        assert_eq!(decode(Some(&cipher), "[]").unwrap(), "[]");
+    }
+}
@@ -0,0 +1,88 @@
+//! Small files carried inside an encrypted DM.
+//!
+//! Attachments travel in the DM plaintext (see [`crate::dm_payload`]), so
+//! they get the same end-to-end encryption and, in privacy mode, padding as
+//! the body.
+
+use anyhow::{Result, bail};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+/// Most attachments one DM may carry.
+pub const MAX_ATTACHMENTS: usize = 8;
+/// Most decoded bytes all attachments of one DM may add up to. Requests are
+/// limited to 64 KiB lines, which this leaves room for after base64.
+pub const MAX_ATTACHMENT_BYTES: usize = 40 * 1024;
+/// Longest attachment name.
+const MAX_NAME_LEN: usize = 255;
+
+/// A file attached to a DM.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// File name, without any directory part.
+    pub name: String,
+    /// MIME type, e.g. `application/json`.
+    pub mime: String,
+    /// Contents, standard base64.
+    pub data_b64: String,
+}
+
+/// Check names, count and total size.
+pub fn validate(attachments: &[Attachment]) -> Result<()> {
+    if attachments.len() > MAX_ATTACHMENTS {
+        bail!("at most {MAX_ATTACHMENTS} attachments per message");
+    }
+    let mut total = 0;
+    for a in attachments {
+        if a.name.is_empty()
+            || a.name.len() > MAX_NAME_LEN
+            || a.name.contains(['/', '\\'])
+            || a.name == "."
+            || a.name == ".."
+            || a.name.chars().any(char::is_control)
+        {
+            bail!("invalid attachment name {:?}", a.name);
+        }
+        if a.mime.is_empty() || a.mime.chars().any(|c| c.is_control() || c.is_whitespace()) {
+            bail!("invalid MIME type {:?} for {}", a.mime, a.name);
+        }
+        let Ok(data) = base64::engine::general_purpose::STANDARD.decode(&a.data_b64) else {
+            bail!("attachment {} is not valid base64", a.name);
+        };
+        total += data.len();
+    }
+    if total > MAX_ATTACHMENT_BYTES {
+        bail!("attachments add up to {total} bytes; the limit is {MAX_ATTACHMENT_BYTES}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn attachment(name: &str, data: &[u8]) -> Attachment {
+        Attachment {
+            name: name.to_string(),
+            mime: "application/json".to_string(),
+            data_b64: base64::engine::general_purpose::STANDARD.encode(data),
+        }
+    }
+
+    #[test]
+    fn attachments_are_limited() {
+        assert!(validate(&[attachment("a.patch", b"diff")]).is_ok());
+        assert!(validate(&[attachment("../a.patch", b"diff")]).is_err());
+        assert!(validate(&[attachment("", b"diff")]).is_err());
+        let mut bad_b64 = attachment("a.txt", b"x");
+        bad_b64.data_b64 = "***".to_string();
+        assert!(validate(&[bad_b64]).is_err());
+
+        let too_many: Vec<_> = (0..=MAX_ATTACHMENTS)
+            .map(|i| attachment(&format!("{i}.txt"), b"x"))
+            .collect();
+        assert!(validate(&too_many).is_err());
+        let big = vec![0u8; MAX_ATTACHMENT_BYTES / 2 + 1];
+        assert!(validate(&[attachment("a.bin", &big), attachment("b.bin", &big)]).is_err());
+    }
+}
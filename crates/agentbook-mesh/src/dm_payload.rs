@@ -0,0 +1,99 @@
+//! What a DM plaintext carries besides its body: attachments and the thread
+//! it belongs to.
+//!
+//! A plain DM is sent exactly as typed. One with attachments or a parent
+//! message has a plaintext that starts with [`PAYLOAD_PREFIX`] followed by a
+//! JSON object holding the body and the extras; a node that predates them
+//! shows that text as the message body.
+
+use crate::attachment::{Attachment, validate};
+use serde::{Deserialize, Serialize};
+
+/// Marks a DM plaintext that carries more than a body. Starts with a control
+/// character so no typed message is mistaken for one.
+pub const PAYLOAD_PREFIX: &str = "\u{1}agentbook-dm/1\n";
+
+/// A DM's body and extras.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DmPayload {
+    pub body: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// The message this one replies to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<String>,
+    /// The first message of the conversation; set whenever `in_reply_to` is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+}
+
+impl DmPayload {
+    /// A payload with just a body.
+    pub fn text(body: impl Into<String>) -> Self {
+        Self {
+            body: body.into(),
+            ..Self::default()
+        }
+    }
+
+    /// The DM plaintext for this payload.
+    pub fn encode(&self) -> String {
+        if self.attachments.is_empty() && self.in_reply_to.is_none() {
+            return self.body.clone();
+        }
+        let json = serde_json::to_string(self).expect("payload serializes");
+        format!("{PAYLOAD_PREFIX}{json}")
+    }
+
+    /// Parse a received DM plaintext. A plaintext that is not a well-formed,
+    /// valid payload is returned whole as the body.
+    pub fn decode(plaintext: String) -> Self {
+        let Some(json) = plaintext.strip_prefix(PAYLOAD_PREFIX) else {
+            return Self::text(plaintext);
+        };
+        match serde_json::from_str::<DmPayload>(json) {
+            Ok(payload)
+                if validate(&payload.attachments).is_ok()
+                    && payload.in_reply_to.is_some() == payload.thread_id.is_some() =>
+            {
+                payload
+            }
+            _ => Self::text(plaintext),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attachment::tests::attachment;
+
+    #[test]
+    fn payload_round_trips() {
+        assert_eq!(DmPayload::text("hi").encode(), "hi");
+        assert_eq!(DmPayload::decode("hi".to_string()), DmPayload::text("hi"));
+
+        let with_files = DmPayload {
+            body: "see attached".to_string(),
+            attachments: vec![attachment("report.json", br#"{"ok":true}"#)],
+            ..DmPayload::default()
+        };
+        let plaintext = with_files.encode();
+        assert!(plaintext.starts_with(PAYLOAD_PREFIX));
+        assert_eq!(DmPayload::decode(plaintext), with_files);
+
+        let reply = DmPayload {
+            body: "agreed".to_string(),
+            in_reply_to: Some("m2".to_string()),
+            thread_id: Some("m1".to_string()),
+            ..DmPayload::default()
+        };
+        assert_eq!(DmPayload::decode(reply.encode()), reply);
+
+        // A malformed payload is kept as text rather than dropped.
+        let broken = format!("{PAYLOAD_PREFIX}{{not json");
+        assert_eq!(DmPayload::decode(broken.clone()).body, broken);
+        let half_threaded = format!(r#"{PAYLOAD_PREFIX}{{"body":"x","in_reply_to":"m1"}}"#);
+        assert_eq!(DmPayload::decode(half_threaded.clone()).body, half_threaded);
+    }
+}
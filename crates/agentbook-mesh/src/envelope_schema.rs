@@ -0,0 +1,125 @@
+//! Envelope schema versions, and the shim that converts envelopes between
+//! them so a fleet can be upgraded one node at a time.
+//!
+//! Inbound envelopes are [`upgrade`]d to [`CURRENT`] before the node reads
+//! them. Outbound envelopes are [`downgrade`]d to the schema the recipient
+//! was last seen sending, tracked by [`PeerSchemas`]. Each schema bump adds
+//! one step to both functions.
+
+use agentbook_proto::mesh::v1 as mesh_pb;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Envelopes written before versioning (`schema_version` 0).
+pub const LEGACY: u32 = 1;
+
+/// The schema this build writes. Schema 2 added `schema_version` itself.
+pub const CURRENT: u32 = 2;
+
+/// The schema an envelope was written for.
+pub fn version_of(envelope: &mesh_pb::Envelope) -> u32 {
+    envelope.schema_version.max(LEGACY)
+}
+
+/// Bring an envelope from an older schema up to [`CURRENT`]. Envelopes from
+/// a newer schema are left alone: prost already dropped the fields we don't
+/// know, and the compatibility rules make the rest readable as is.
+pub fn upgrade(mut envelope: mesh_pb::Envelope) -> mesh_pb::Envelope {
+    if version_of(&envelope) < 2 {
+        // 1 -> 2: nothing moved; the version just becomes explicit.
+        envelope.schema_version = 2;
+    }
+    envelope
+}
+
+/// Rewrite an envelope for a peer on schema `peer`, if that is older than
+/// the envelope's.
+pub fn downgrade(mut envelope: mesh_pb::Envelope, peer: u32) -> mesh_pb::Envelope {
+    if peer < 2 && version_of(&envelope) >= 2 {
+        // 2 -> 1: schema 1 has no version field.
+        envelope.schema_version = 0;
+    }
+    envelope
+}
+
+/// The schema each peer last sent us.
+#[derive(Default)]
+pub struct PeerSchemas {
+    seen: Mutex<HashMap<String, u32>>,
+}
+
+impl PeerSchemas {
+    /// Note the sender's schema. Relay-generated room events don't come from
+    /// the node they name, so they are ignored.
+    pub fn observe(&self, envelope: &mesh_pb::Envelope) {
+        if matches!(
+            mesh_pb::MessageType::try_from(envelope.message_type),
+            Ok(mesh_pb::MessageType::RoomJoin | mesh_pb::MessageType::RoomLeave)
+        ) {
+            return;
+        }
+        self.seen
+            .lock()
+            .unwrap()
+            .insert(envelope.from_node_id.clone(), version_of(envelope));
+    }
+
+    /// The schema to write for `node_id`. Peers we haven't heard from get
+    /// [`CURRENT`]; an older node ignores what it doesn't know.
+    pub fn version_for(&self, node_id: &str) -> u32 {
+        self.seen
+            .lock()
+            .unwrap()
+            .get(node_id)
+            .copied()
+            .unwrap_or(CURRENT)
+            .min(CURRENT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope(from: &str, schema_version: u32) -> mesh_pb::Envelope {
+        mesh_pb::Envelope {
+            message_id: "m1".to_string(),
+            from_node_id: from.to_string(),
+            to_node_id: "0xme".to_string(),
+            message_type: mesh_pb::MessageType::DmText as i32,
+            schema_version,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unversioned_envelopes_upgrade_and_round_trip() {
+        let legacy = envelope("0xold", 0);
+        assert_eq!(version_of(&legacy), LEGACY);
+
+        let upgraded = upgrade(legacy.clone());
+        assert_eq!(upgraded.schema_version, CURRENT);
+        assert_eq!(downgrade(upgraded, LEGACY), legacy);
+
+        // Envelopes from a newer schema pass through untouched.
+        let newer = envelope("0xnew", CURRENT + 1);
+        assert_eq!(upgrade(newer.clone()), newer);
+        assert_eq!(downgrade(newer.clone(), CURRENT + 1), newer);
+    }
+
+    #[test]
+    fn peers_are_written_for_in_the_schema_they_send() {
+        let peers = PeerSchemas::default();
+        assert_eq!(peers.version_for("0xold"), CURRENT);
+
+        peers.observe(&envelope("0xold", 0));
+        peers.observe(&envelope("0xnew", CURRENT + 1));
+        assert_eq!(peers.version_for("0xold"), LEGACY);
+        assert_eq!(peers.version_for("0xnew"), CURRENT);
+
+        let mut join = envelope("0xnew", 0);
+        join.message_type = mesh_pb::MessageType::RoomJoin as i32;
+        peers.observe(&join);
+        assert_eq!(peers.version_for("0xnew"), CURRENT);
+    }
+}
@@ -1,9 +1,14 @@
+use crate::at_rest::{self, StateCipher};
+use crate::state_file;
 use anyhow::{Context, Result, bail};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 const FOLLOWING_FILE: &str = "following.json";
 const BLOCKED_FILE: &str = "blocked.json";
+const KEY_HISTORY_FILE: &str = "key_history.json";
 
 /// A node you follow.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -13,6 +18,54 @@ pub struct FollowRecord {
     pub username: Option<String>,
     pub relay_hints: Vec<String>,
     pub followed_at_ms: u64,
+    /// Invite scopes granted to this node when it was followed back after
+    /// redeeming one of our invites. Empty means unrestricted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+    /// Local name for this node, usable wherever a target is accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Local groups this node belongs to, e.g. `team-infra`, so routes and
+    /// broadcasts can address several nodes at once.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+}
+
+impl FollowRecord {
+    /// Whether messages under `scope` are accepted from this node.
+    pub fn allows(&self, scope: &str) -> bool {
+        self.scopes.is_empty() || self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+/// Longest alias accepted.
+const MAX_ALIAS_LEN: usize = 32;
+
+/// Check that `alias` is a short lowercase name that can't be mistaken for
+/// a node ID or an `@username`.
+pub fn validate_alias(alias: &str) -> Result<()> {
+    validate_name("alias", alias)
+}
+
+/// Group names follow the same rules as aliases.
+pub fn validate_group(group: &str) -> Result<()> {
+    validate_name("group", group)
+}
+
+fn validate_name(kind: &str, name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > MAX_ALIAS_LEN {
+        bail!("{kind} must be 1-{MAX_ALIAS_LEN} characters");
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    {
+        bail!("{kind} may only contain a-z, 0-9, '-' and '_'");
+    }
+    if name.starts_with("0x") {
+        bail!("{kind} must not start with 0x");
+    }
+    Ok(())
 }
 
 /// A blocked node.
@@ -22,54 +75,104 @@ pub struct BlockRecord {
     pub blocked_at_ms: u64,
 }
 
-/// Persistent follow graph backed by JSON files.
+/// A public key a followed node used to have, kept after rotation or
+/// revocation so messages it signed earlier can still be attributed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RetiredKey {
+    pub node_id: String,
+    pub public_key_b64: String,
+    /// Node the key was rotated to, if it was rotated rather than revoked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub successor_node_id: Option<String>,
+    /// Revoked keys are distrusted: new messages signed with them are rejected.
+    pub revoked: bool,
+    pub retired_at_ms: u64,
+}
+
+/// Persistent follow graph backed by JSON files, optionally encrypted at
+/// rest (see [`crate::at_rest`]).
 pub struct FollowStore {
     following_path: PathBuf,
     blocked_path: PathBuf,
+    key_history_path: PathBuf,
     following: Vec<FollowRecord>,
     blocked: Vec<BlockRecord>,
+    key_history: Vec<RetiredKey>,
+    cipher: Option<StateCipher>,
 }
 
 impl FollowStore {
     /// Load from disk, or create empty.
     pub fn load(state_dir: &Path) -> Result<Self> {
+        Self::load_with_cipher(state_dir, None)
+    }
+
+    /// Load from disk with files encrypted under `cipher`. Plaintext files
+    /// from before encryption was enabled are rewritten encrypted.
+    pub fn load_encrypted(state_dir: &Path, cipher: StateCipher) -> Result<Self> {
+        Self::load_with_cipher(state_dir, Some(cipher))
+    }
+
+    fn load_with_cipher(state_dir: &Path, cipher: Option<StateCipher>) -> Result<Self> {
         let following_path = state_dir.join(FOLLOWING_FILE);
         let blocked_path = state_dir.join(BLOCKED_FILE);
+        let key_history_path = state_dir.join(KEY_HISTORY_FILE);
 
-        let following = if following_path.exists() {
-            let data = std::fs::read_to_string(&following_path)
-                .context("failed to read following.json")?;
-            serde_json::from_str(&data).context("invalid following.json")?
-        } else {
-            Vec::new()
-        };
-
-        let blocked = if blocked_path.exists() {
-            let data =
-                std::fs::read_to_string(&blocked_path).context("failed to read blocked.json")?;
-            serde_json::from_str(&data).context("invalid blocked.json")?
-        } else {
-            Vec::new()
-        };
+        let mut legacy = false;
+        let following = read_json(&following_path, cipher.as_ref(), &mut legacy)?;
+        let blocked = read_json(&blocked_path, cipher.as_ref(), &mut legacy)?;
+        let key_history = read_json(&key_history_path, cipher.as_ref(), &mut legacy)?;
 
-        Ok(Self {
+        let store = Self {
             following_path,
             blocked_path,
+            key_history_path,
             following,
             blocked,
-        })
+            key_history,
+            cipher,
+        };
+        if legacy && store.is_encrypted() {
+            store.reencrypt()?;
+        }
+        Ok(store)
+    }
+
+    /// Whether files are written encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Rewrite every file, e.g. to finish migrating a plaintext state dir.
+    /// Backups of the old contents are removed.
+    pub fn reencrypt(&self) -> Result<()> {
+        self.save_following()?;
+        self.save_blocked()?;
+        self.save_key_history()?;
+        for path in [
+            &self.following_path,
+            &self.blocked_path,
+            &self.key_history_path,
+        ] {
+            state_file::remove_backup(path)?;
+        }
+        Ok(())
     }
 
     fn save_following(&self) -> Result<()> {
-        let data = serde_json::to_string_pretty(&self.following)?;
-        std::fs::write(&self.following_path, data)
-            .with_context(|| format!("failed to write {}", self.following_path.display()))
+        write_json(&self.following_path, &self.following, self.cipher.as_ref())
     }
 
     fn save_blocked(&self) -> Result<()> {
-        let data = serde_json::to_string_pretty(&self.blocked)?;
-        std::fs::write(&self.blocked_path, data)
-            .with_context(|| format!("failed to write {}", self.blocked_path.display()))
+        write_json(&self.blocked_path, &self.blocked, self.cipher.as_ref())
+    }
+
+    fn save_key_history(&self) -> Result<()> {
+        write_json(
+            &self.key_history_path,
+            &self.key_history,
+            self.cipher.as_ref(),
+        )
     }
 
     /// Follow a node. Deduplicates by node_id.
@@ -92,6 +195,80 @@ impl FollowStore {
         self.save_blocked()
     }
 
+    /// Name a followed node, or clear its alias with `None`. Fails if the
+    /// node isn't followed or another node has the alias.
+    pub fn set_alias(&mut self, node_id: &str, alias: Option<String>) -> Result<()> {
+        if let Some(alias) = &alias {
+            validate_alias(alias)?;
+            if let Some(other) = self.by_alias(alias)
+                && other.node_id != node_id
+            {
+                bail!("alias {alias} is already used for {}", other.node_id);
+            }
+        }
+        let Some(record) = self.following.iter_mut().find(|f| f.node_id == node_id) else {
+            bail!("not following: {node_id}");
+        };
+        record.alias = alias;
+        self.save_following()
+    }
+
+    /// The followed node with `alias`.
+    pub fn by_alias(&self, alias: &str) -> Option<&FollowRecord> {
+        self.following
+            .iter()
+            .find(|f| f.alias.as_deref() == Some(alias))
+    }
+
+    /// Add a followed node to `group`, creating the group if it is new.
+    pub fn add_to_group(&mut self, node_id: &str, group: &str) -> Result<()> {
+        validate_group(group)?;
+        let Some(record) = self.following.iter_mut().find(|f| f.node_id == node_id) else {
+            bail!("not following: {node_id}");
+        };
+        if !record.groups.iter().any(|g| g == group) {
+            record.groups.push(group.to_string());
+            record.groups.sort();
+        }
+        self.save_following()
+    }
+
+    /// Take a followed node out of `group`. A group with no members left
+    /// no longer exists.
+    pub fn remove_from_group(&mut self, node_id: &str, group: &str) -> Result<()> {
+        let Some(record) = self.following.iter_mut().find(|f| f.node_id == node_id) else {
+            bail!("not following: {node_id}");
+        };
+        let before = record.groups.len();
+        record.groups.retain(|g| g != group);
+        if record.groups.len() == before {
+            bail!("{node_id} is not in group {group}");
+        }
+        self.save_following()
+    }
+
+    /// Followed nodes in `group`.
+    pub fn group_members(&self, group: &str) -> Vec<&FollowRecord> {
+        self.following
+            .iter()
+            .filter(|f| f.groups.iter().any(|g| g == group))
+            .collect()
+    }
+
+    /// Every group with its members' node IDs, by group name.
+    pub fn groups(&self) -> BTreeMap<String, Vec<String>> {
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for record in &self.following {
+            for group in &record.groups {
+                groups
+                    .entry(group.clone())
+                    .or_default()
+                    .push(record.node_id.clone());
+            }
+        }
+        groups
+    }
+
     /// Unfollow a node.
     pub fn unfollow(&mut self, node_id: &str) -> Result<()> {
         let before = self.following.len();
@@ -139,6 +316,160 @@ impl FollowStore {
     pub fn blocked(&self) -> &[BlockRecord] {
         &self.blocked
     }
+
+    /// Move a followed node to a new key. The old key is kept in the key
+    /// history and the follow record is carried over to `new_node_id`.
+    ///
+    /// The caller is responsible for verifying the rotation was authorized
+    /// by the old key.
+    pub fn rotate_key(
+        &mut self,
+        old_node_id: &str,
+        new_node_id: &str,
+        new_public_key_b64: &str,
+    ) -> Result<()> {
+        let Some(idx) = self.following.iter().position(|f| f.node_id == old_node_id) else {
+            bail!("not following: {old_node_id}");
+        };
+        if self.is_revoked(old_node_id) {
+            bail!("key already revoked: {old_node_id}");
+        }
+
+        let old = self.following.remove(idx);
+        self.key_history.push(RetiredKey {
+            node_id: old.node_id.clone(),
+            public_key_b64: old.public_key_b64.clone(),
+            successor_node_id: Some(new_node_id.to_string()),
+            revoked: false,
+            retired_at_ms: agentbook_crypto::time::now_ms(),
+        });
+
+        if let Some(existing) = self.following.iter_mut().find(|f| f.node_id == new_node_id) {
+            existing.public_key_b64 = new_public_key_b64.to_string();
+            existing.username = existing.username.take().or(old.username);
+            existing.alias = existing.alias.take().or(old.alias);
+            for group in old.groups {
+                if !existing.groups.contains(&group) {
+                    existing.groups.push(group);
+                }
+            }
+            existing.groups.sort();
+        } else {
+            self.following.push(FollowRecord {
+                node_id: new_node_id.to_string(),
+                public_key_b64: new_public_key_b64.to_string(),
+                ..old
+            });
+        }
+        self.save_key_history()?;
+        self.save_following()
+    }
+
+    /// Revoke a node's key. The node is unfollowed and any further messages
+    /// signed with the key are rejected at ingress.
+    pub fn revoke_key(&mut self, node_id: &str) -> Result<()> {
+        if self.is_revoked(node_id) {
+            return Ok(());
+        }
+        let public_key_b64 = self
+            .get(node_id)
+            .map(|f| f.public_key_b64.clone())
+            .or_else(|| {
+                self.key_history
+                    .iter()
+                    .find(|k| k.node_id == node_id)
+                    .map(|k| k.public_key_b64.clone())
+            });
+        let Some(public_key_b64) = public_key_b64 else {
+            bail!("unknown key: {node_id}");
+        };
+
+        self.following.retain(|f| f.node_id != node_id);
+        if let Some(retired) = self.key_history.iter_mut().find(|k| k.node_id == node_id) {
+            retired.revoked = true;
+        } else {
+            self.key_history.push(RetiredKey {
+                node_id: node_id.to_string(),
+                public_key_b64,
+                successor_node_id: None,
+                revoked: true,
+                retired_at_ms: agentbook_crypto::time::now_ms(),
+            });
+        }
+        self.save_key_history()?;
+        self.save_following()
+    }
+
+    /// Check if a node's key has been revoked.
+    pub fn is_revoked(&self, node_id: &str) -> bool {
+        self.key_history
+            .iter()
+            .any(|k| k.node_id == node_id && k.revoked)
+    }
+
+    /// Whether `public_key_b64` is, or used to be, the key of `node_id`.
+    /// Used to attribute messages received before a rotation.
+    pub fn is_known_key(&self, node_id: &str, public_key_b64: &str) -> bool {
+        self.get(node_id)
+            .is_some_and(|f| f.public_key_b64 == public_key_b64)
+            || self
+                .key_history
+                .iter()
+                .any(|k| k.node_id == node_id && k.public_key_b64 == public_key_b64)
+    }
+
+    /// Follow the rotation chain from `node_id` to the node's current id.
+    pub fn current_node_id<'a>(&'a self, node_id: &'a str) -> &'a str {
+        let mut current = node_id;
+        // Bounded by the history length so a malformed cycle cannot loop.
+        for _ in 0..=self.key_history.len() {
+            match self
+                .key_history
+                .iter()
+                .find(|k| k.node_id == current)
+                .and_then(|k| k.successor_node_id.as_deref())
+            {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+        current
+    }
+
+    /// List all retired keys, oldest first.
+    pub fn retired_keys(&self) -> &[RetiredKey] {
+        &self.key_history
+    }
+}
+
+/// Read a JSON list, or an empty one if the file does not exist. Sets
+/// `legacy` if the file was plaintext.
+fn read_json<T: DeserializeOwned>(
+    path: &Path,
+    cipher: Option<&StateCipher>,
+    legacy: &mut bool,
+) -> Result<Vec<T>> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let parsed = state_file::read(path, |data| {
+        let data = data.trim();
+        let sealed = at_rest::is_sealed(data);
+        let json =
+            at_rest::decode(cipher, data).with_context(|| format!("failed to read {name}"))?;
+        let list = serde_json::from_str(&json).with_context(|| format!("invalid {name}"))?;
+        Ok((list, sealed))
+    })?;
+    Ok(match parsed {
+        Some((list, sealed)) => {
+            *legacy |= !sealed;
+            list
+        }
+        None => Vec::new(),
+    })
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T, cipher: Option<&StateCipher>) -> Result<()> {
+    let data = at_rest::encode(cipher, &serde_json::to_string_pretty(value)?)?;
+    state_file::write(path, data)
 }
 
 #[cfg(test)]
@@ -153,9 +484,59 @@ mod tests {
             username: None,
             relay_hints: vec![],
             followed_at_ms: now_ms(),
+            scopes: vec![],
+            alias: None,
+            groups: vec![],
         }
     }
 
+    #[test]
+    fn aliases_are_unique_and_survive_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FollowStore::load(dir.path()).unwrap();
+        store.follow(make_follow("a")).unwrap();
+        store.follow(make_follow("b")).unwrap();
+
+        store.set_alias("a", Some("build-bot".into())).unwrap();
+        assert!(store.set_alias("b", Some("build-bot".into())).is_err());
+        assert!(store.set_alias("c", Some("other".into())).is_err());
+        assert!(store.set_alias("b", Some("Bad Name".into())).is_err());
+        assert!(store.set_alias("b", Some("0xabc".into())).is_err());
+
+        let reloaded = FollowStore::load(dir.path()).unwrap();
+        assert_eq!(reloaded.by_alias("build-bot").unwrap().node_id, "a");
+
+        store.set_alias("a", None).unwrap();
+        assert!(store.by_alias("build-bot").is_none());
+        store.set_alias("b", Some("build-bot".into())).unwrap();
+    }
+
+    #[test]
+    fn groups_are_built_from_memberships() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FollowStore::load(dir.path()).unwrap();
+        store.follow(make_follow("a")).unwrap();
+        store.follow(make_follow("b")).unwrap();
+
+        store.add_to_group("a", "team-infra").unwrap();
+        store.add_to_group("b", "team-infra").unwrap();
+        store.add_to_group("b", "oncall").unwrap();
+        store.add_to_group("b", "oncall").unwrap();
+        assert!(store.add_to_group("c", "team-infra").is_err());
+        assert!(store.add_to_group("a", "Team Infra").is_err());
+
+        let reloaded = FollowStore::load(dir.path()).unwrap();
+        let groups = reloaded.groups();
+        assert_eq!(groups["team-infra"], ["a", "b"]);
+        assert_eq!(groups["oncall"], ["b"]);
+        assert_eq!(reloaded.get("b").unwrap().groups, ["oncall", "team-infra"]);
+
+        store.remove_from_group("b", "oncall").unwrap();
+        assert!(store.remove_from_group("b", "oncall").is_err());
+        assert!(!store.groups().contains_key("oncall"));
+        assert_eq!(store.group_members("team-infra").len(), 2);
+    }
+
     #[test]
     fn follow_and_unfollow() {
         let dir = tempfile::tempdir().unwrap();
@@ -214,6 +595,80 @@ mod tests {
         assert_eq!(store.blocked()[0].node_id, "y");
     }
 
+    #[test]
+    fn encrypted_store_migrates_plaintext() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let mut store = FollowStore::load(dir.path()).unwrap();
+            store.follow(make_follow("x")).unwrap();
+        }
+        let cipher = StateCipher::new(&crate::crypto::random_key_material());
+        let store = FollowStore::load_encrypted(dir.path(), cipher.clone()).unwrap();
+        assert!(store.is_following("x"));
+
+        let on_disk = std::fs::read_to_string(dir.path().join(FOLLOWING_FILE)).unwrap();
+        assert!(at_rest::is_sealed(&on_disk));
+        assert!(!on_disk.contains("\"x\""));
+        assert!(FollowStore::load(dir.path()).is_err());
+        let reloaded = FollowStore::load_encrypted(dir.path(), cipher).unwrap();
+        assert!(reloaded.is_following("x"));
+    }
+
+    #[test]
+    fn rotate_key_moves_follow_and_keeps_old_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FollowStore::load(dir.path()).unwrap();
+        let mut record = make_follow("a");
+        record.username = Some("alice".to_string());
+        store.follow(record).unwrap();
+
+        store.rotate_key("a", "a2", "pub_a2").unwrap();
+        assert!(!store.is_following("a"));
+        let current = store.get("a2").unwrap();
+        assert_eq!(current.public_key_b64, "pub_a2");
+        assert_eq!(current.username.as_deref(), Some("alice"));
+
+        assert!(store.is_known_key("a", "pub_a"));
+        assert!(store.is_known_key("a2", "pub_a2"));
+        assert!(!store.is_known_key("a", "pub_a2"));
+        assert_eq!(store.current_node_id("a"), "a2");
+
+        store.rotate_key("a2", "a3", "pub_a3").unwrap();
+        assert_eq!(store.current_node_id("a"), "a3");
+
+        let reloaded = FollowStore::load(dir.path()).unwrap();
+        assert_eq!(reloaded.retired_keys().len(), 2);
+        assert!(reloaded.is_known_key("a", "pub_a"));
+        assert!(reloaded.is_following("a3"));
+    }
+
+    #[test]
+    fn rotate_key_requires_follow() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FollowStore::load(dir.path()).unwrap();
+        assert!(store.rotate_key("a", "a2", "pub_a2").is_err());
+    }
+
+    #[test]
+    fn revoke_key_unfollows_and_blocks_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FollowStore::load(dir.path()).unwrap();
+        store.follow(make_follow("a")).unwrap();
+
+        store.revoke_key("a").unwrap();
+        assert!(store.is_revoked("a"));
+        assert!(!store.is_following("a"));
+        assert!(store.is_known_key("a", "pub_a"));
+
+        // Idempotent, and revocation survives a restart.
+        store.revoke_key("a").unwrap();
+        let reloaded = FollowStore::load(dir.path()).unwrap();
+        assert!(reloaded.is_revoked("a"));
+        assert_eq!(reloaded.retired_keys().len(), 1);
+
+        assert!(store.revoke_key("unknown").is_err());
+    }
+
     #[test]
     fn unfollow_nonexistent_fails() {
         let dir = tempfile::tempdir().unwrap();
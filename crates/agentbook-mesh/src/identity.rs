@@ -15,7 +15,9 @@ use zeroize::Zeroizing;
 const NODE_KEY_FILE: &str = "node.key";
 const NODE_PUB_FILE: &str = "node.pub";
 const NODE_JSON_FILE: &str = "node.json";
+const RETIRED_DIR: &str = "retired";
 const KEYSTORE_LABEL: &[u8] = b"agentbook-node-keystore-v1";
+const IDENTITY_KEY_LABEL: &[u8] = b"agentbook-node-identity-v1";
 
 /// Persistent node identity backed by a secp256k1 key pair.
 #[derive(Clone)]
@@ -40,6 +42,47 @@ struct EncryptedKeystore {
     nonce_b64: String,
 }
 
+impl EncryptedKeystore {
+    fn seal(secret_key: &SecretKey, kek: &[u8; ENVELOPE_KEY_BYTES]) -> Result<Self> {
+        let encryption_key = derive_symmetric_key(KEYSTORE_LABEL, kek);
+        let (ciphertext_b64, nonce_b64) =
+            encrypt_with_key(&encryption_key, &secret_key.to_bytes())?;
+        Ok(Self {
+            ciphertext_b64,
+            nonce_b64,
+        })
+    }
+
+    fn open(&self, kek: &[u8; ENVELOPE_KEY_BYTES]) -> Result<SecretKey> {
+        let decryption_key = derive_symmetric_key(KEYSTORE_LABEL, kek);
+        let secret_bytes = decrypt_with_key(&decryption_key, &self.ciphertext_b64, &self.nonce_b64)
+            .context("failed to decrypt node key (wrong recovery key?)")?;
+        SecretKey::from_slice(&secret_bytes).context("decrypted key is not valid secp256k1")
+    }
+}
+
+/// A key the node rotated away from.
+///
+/// Retired keys stay on disk (still encrypted under the recovery key) so
+/// messages addressed to the old node id can be read during the grace period,
+/// and so nothing held by the old address is ever lost.
+#[derive(Clone)]
+pub struct RetiredIdentity {
+    pub identity: NodeIdentity,
+    pub retired_at_ms: u64,
+    /// Until when the node keeps accepting messages for this key.
+    pub grace_until_ms: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RetiredKeystore {
+    node_id: String,
+    public_key_b64: String,
+    retired_at_ms: u64,
+    grace_until_ms: u64,
+    keystore: EncryptedKeystore,
+}
+
 impl NodeIdentity {
     /// Load an existing identity from `state_dir`, or create a new one.
     ///
@@ -56,7 +99,55 @@ impl NodeIdentity {
             return Self::load(state_dir, &key_path, &pub_path, &meta_path, kek);
         }
 
-        Self::create(state_dir, &key_path, &pub_path, &meta_path, kek)
+        let secret_key = SecretKey::random(&mut OsRng);
+        Self::create(state_dir, &key_path, &pub_path, &meta_path, secret_key, kek)
+    }
+
+    /// Like [`NodeIdentity::load_or_create`], but a new identity uses the key
+    /// derived from `kek`, so the recovery phrase alone can restore it.
+    pub fn load_or_derive(state_dir: &Path, kek: &[u8; ENVELOPE_KEY_BYTES]) -> Result<Self> {
+        if state_dir.join(NODE_KEY_FILE).exists() {
+            return Self::load_or_create(state_dir, kek);
+        }
+        Self::restore(state_dir, kek, None)
+    }
+
+    /// Recreate an identity on a new machine from the recovery key.
+    ///
+    /// `secret_key` is the node key exported by a backup; without one, the key
+    /// derived from `kek` is used. Refuses to overwrite an existing identity.
+    pub fn restore(
+        state_dir: &Path,
+        kek: &[u8; ENVELOPE_KEY_BYTES],
+        secret_key: Option<SecretKey>,
+    ) -> Result<Self> {
+        ensure_state_dir(state_dir)?;
+
+        let key_path = state_dir.join(NODE_KEY_FILE);
+        if key_path.exists() {
+            bail!(
+                "{} already holds a node identity; refusing to overwrite it",
+                state_dir.display()
+            );
+        }
+        let secret_key = match secret_key {
+            Some(key) => key,
+            None => derive_node_key(kek)?,
+        };
+        Self::create(
+            state_dir,
+            &key_path,
+            &state_dir.join(NODE_PUB_FILE),
+            &state_dir.join(NODE_JSON_FILE),
+            secret_key,
+            kek,
+        )
+    }
+
+    /// Whether this identity's key is the one derived from `kek`, i.e. the
+    /// recovery phrase alone is enough to restore it.
+    pub fn is_derived_from(&self, kek: &[u8; ENVELOPE_KEY_BYTES]) -> bool {
+        derive_node_key(kek).is_ok_and(|key| key == self.secret_key)
     }
 
     fn load(
@@ -71,16 +162,7 @@ impl NodeIdentity {
         let keystore: EncryptedKeystore =
             serde_json::from_str(&keystore_json).context("invalid keystore format")?;
 
-        let decryption_key = derive_symmetric_key(KEYSTORE_LABEL, kek);
-        let secret_bytes = decrypt_with_key(
-            &decryption_key,
-            &keystore.ciphertext_b64,
-            &keystore.nonce_b64,
-        )
-        .context("failed to decrypt node key (wrong recovery key?)")?;
-
-        let secret_key =
-            SecretKey::from_slice(&secret_bytes).context("decrypted key is not valid secp256k1")?;
+        let secret_key = keystore.open(kek)?;
         let public_key = secret_key.public_key();
         let public_key_b64 =
             base64::engine::general_purpose::STANDARD.encode(public_key.to_sec1_bytes());
@@ -120,31 +202,17 @@ impl NodeIdentity {
         key_path: &Path,
         pub_path: &Path,
         meta_path: &Path,
+        secret_key: SecretKey,
         kek: &[u8; ENVELOPE_KEY_BYTES],
     ) -> Result<Self> {
-        let secret_key = SecretKey::random(&mut OsRng);
         let public_key = secret_key.public_key();
         let public_key_b64 =
             base64::engine::general_purpose::STANDARD.encode(public_key.to_sec1_bytes());
         let node_id = evm_address_from_public_key(&public_key);
 
         // Encrypt and persist the private key
-        let encryption_key = derive_symmetric_key(KEYSTORE_LABEL, kek);
-        let (ciphertext_b64, nonce_b64) =
-            encrypt_with_key(&encryption_key, &secret_key.to_bytes())?;
-        let keystore = EncryptedKeystore {
-            ciphertext_b64,
-            nonce_b64,
-        };
-        let keystore_json = serde_json::to_string_pretty(&keystore)?;
-        std::fs::write(key_path, &keystore_json)
-            .with_context(|| format!("failed to write {}", key_path.display()))?;
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            std::fs::set_permissions(key_path, std::fs::Permissions::from_mode(0o600))
-                .with_context(|| format!("failed to set permissions on {}", key_path.display()))?;
-        }
+        let keystore = EncryptedKeystore::seal(&secret_key, kek)?;
+        write_private(key_path, &serde_json::to_string_pretty(&keystore)?)?;
 
         // Write public key
         std::fs::write(pub_path, &public_key_b64)
@@ -173,6 +241,99 @@ impl NodeIdentity {
         })
     }
 
+    /// Replace this identity with a freshly generated key pair.
+    ///
+    /// The current key is moved to `retired/<node_id>.json` (still encrypted
+    /// under `kek`) and accepted for inbound messages until `grace_until_ms`.
+    /// Fails if the key on disk is no longer this identity, i.e. a rotation
+    /// is already waiting for the node to restart.
+    pub fn rotate(
+        &self,
+        kek: &[u8; ENVELOPE_KEY_BYTES],
+        now_ms: u64,
+        grace_until_ms: u64,
+    ) -> Result<Self> {
+        let pub_path = self.state_dir.join(NODE_PUB_FILE);
+        let stored_pub = std::fs::read_to_string(&pub_path)
+            .context("failed to read node.pub")?
+            .trim()
+            .to_string();
+        if stored_pub != self.public_key_b64 {
+            bail!("node key on disk has already been rotated; restart the node first");
+        }
+
+        let retired_dir = self.state_dir.join(RETIRED_DIR);
+        ensure_state_dir(&retired_dir)?;
+        let retired = RetiredKeystore {
+            node_id: self.node_id.clone(),
+            public_key_b64: self.public_key_b64.clone(),
+            retired_at_ms: now_ms,
+            grace_until_ms,
+            keystore: EncryptedKeystore::seal(&self.secret_key, kek)?,
+        };
+        write_private(
+            &retired_dir.join(format!("{}.json", self.node_id)),
+            &serde_json::to_string_pretty(&retired)?,
+        )?;
+
+        Self::create(
+            &self.state_dir,
+            &self.state_dir.join(NODE_KEY_FILE),
+            &pub_path,
+            &self.state_dir.join(NODE_JSON_FILE),
+            SecretKey::random(&mut OsRng),
+            kek,
+        )
+    }
+
+    /// Load every key this node has rotated away from, oldest first.
+    pub fn load_retired(
+        state_dir: &Path,
+        kek: &[u8; ENVELOPE_KEY_BYTES],
+    ) -> Result<Vec<RetiredIdentity>> {
+        let retired_dir = state_dir.join(RETIRED_DIR);
+        if !retired_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut retired = Vec::new();
+        for entry in std::fs::read_dir(&retired_dir)
+            .with_context(|| format!("failed to read {}", retired_dir.display()))?
+        {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let json = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let record: RetiredKeystore = serde_json::from_str(&json)
+                .with_context(|| format!("invalid retired key {}", path.display()))?;
+            let secret_key = record.keystore.open(kek)?;
+            let public_key = secret_key.public_key();
+            let public_key_b64 =
+                base64::engine::general_purpose::STANDARD.encode(public_key.to_sec1_bytes());
+            if public_key_b64 != record.public_key_b64 {
+                bail!(
+                    "retired key {} does not match its public key",
+                    path.display()
+                );
+            }
+            retired.push(RetiredIdentity {
+                identity: Self {
+                    secret_key,
+                    public_key,
+                    node_id: record.node_id,
+                    public_key_b64,
+                    state_dir: state_dir.to_path_buf(),
+                },
+                retired_at_ms: record.retired_at_ms,
+                grace_until_ms: record.grace_until_ms,
+            });
+        }
+        retired.sort_by_key(|r| r.retired_at_ms);
+        Ok(retired)
+    }
+
     /// Sign arbitrary payload bytes with this node's key.
     pub fn sign(&self, payload: &[u8]) -> Result<String> {
         crate::crypto::sign_payload(&self.secret_key, payload)
@@ -197,6 +358,25 @@ impl NodeIdentity {
     }
 }
 
+/// The node key a recovery key deterministically maps to.
+fn derive_node_key(kek: &[u8; ENVELOPE_KEY_BYTES]) -> Result<SecretKey> {
+    let seed = Zeroizing::new(derive_symmetric_key(IDENTITY_KEY_LABEL, kek));
+    SecretKey::from_slice(seed.as_slice()).context("recovery key does not map to a valid node key")
+}
+
+/// Write a file only the owner can read.
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    std::fs::write(path, contents)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("failed to set permissions on {}", path.display()))?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -251,6 +431,59 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn rotate_retires_old_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("node");
+        let kek = random_key_material();
+        let old = NodeIdentity::load_or_create(&state, &kek).unwrap();
+
+        let new = old.rotate(&kek, 1_000, 5_000).unwrap();
+        assert_ne!(new.node_id, old.node_id);
+        let loaded = NodeIdentity::load_or_create(&state, &kek).unwrap();
+        assert_eq!(loaded.node_id, new.node_id);
+
+        let retired = NodeIdentity::load_retired(&state, &kek).unwrap();
+        assert_eq!(retired.len(), 1);
+        assert_eq!(retired[0].identity.node_id, old.node_id);
+        assert_eq!(retired[0].grace_until_ms, 5_000);
+        // The retired key still decrypts what was encrypted to the old key.
+        let peer_dir = tempfile::tempdir().unwrap();
+        let peer = NodeIdentity::load_or_create(peer_dir.path(), &kek).unwrap();
+        assert_eq!(
+            retired[0].identity.derive_shared_key(&peer.public_key),
+            old.derive_shared_key(&peer.public_key)
+        );
+
+        // A second rotation from the stale identity is refused.
+        assert!(old.rotate(&kek, 2_000, 6_000).is_err());
+    }
+
+    #[test]
+    fn derived_identity_restores_on_new_machine() {
+        let kek = random_key_material();
+        let original_dir = tempfile::tempdir().unwrap();
+        let original = NodeIdentity::load_or_derive(original_dir.path(), &kek).unwrap();
+        assert!(original.is_derived_from(&kek));
+        assert!(NodeIdentity::restore(original_dir.path(), &kek, None).is_err());
+
+        let restored_dir = tempfile::tempdir().unwrap();
+        let restored = NodeIdentity::restore(restored_dir.path(), &kek, None).unwrap();
+        assert_eq!(restored.node_id, original.node_id);
+        assert_eq!(restored.secret_key_bytes(), original.secret_key_bytes());
+
+        // Random (legacy or rotated) keys need the exported node key as well.
+        let random_dir = tempfile::tempdir().unwrap();
+        let random = NodeIdentity::load_or_create(random_dir.path(), &kek).unwrap();
+        assert!(!random.is_derived_from(&kek));
+        let copy_dir = tempfile::tempdir().unwrap();
+        let copy = NodeIdentity::restore(copy_dir.path(), &kek, Some(random.secret_key().clone()))
+            .unwrap();
+        assert_eq!(copy.node_id, random.node_id);
+        let reloaded = NodeIdentity::load_or_create(copy_dir.path(), &kek).unwrap();
+        assert_eq!(reloaded.node_id, random.node_id);
+    }
+
     #[test]
     fn ecdh_shared_key_is_symmetric() {
         let dir1 = tempfile::tempdir().unwrap();
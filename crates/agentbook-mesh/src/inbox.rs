@@ -1,3 +1,6 @@
+use crate::at_rest::{self, StateCipher};
+use crate::attachment::Attachment;
+use crate::state_file;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -39,21 +42,42 @@ pub struct InboxMessage {
     pub acked: bool,
     #[serde(default)]
     pub message_type: MessageType,
+    /// Files carried by a DM.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// The DM this one replies to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<String>,
+    /// The first message of the conversation this DM belongs to; `None` for
+    /// a message that starts one (or is not a reply).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
 }
 
-/// Append-only node-level inbox persisted as JSONL.
-///
-/// Persistence strategy:
-/// - New messages are appended to `inbox.jsonl`.
-/// - Acks are appended to `inbox_acked.jsonl` (just the message_id).
-/// - On load, acked IDs are merged into the message list.
-/// - A full rewrite (compaction) only happens when evicting old messages.
-///
-/// This avoids the O(N) rewrite on every ack while keeping the on-disk
-/// format simple.
+/// Where a [`NodeInbox`] keeps its messages. The inbox holds the working set
+/// in memory and calls the storage to persist each change.
+pub trait InboxStorage: Send {
+    /// Every stored message with acks applied, oldest first. Returns `true`
+    /// as well if the stored form should be rewritten (e.g. plaintext found
+    /// under a cipher).
+    fn load(&mut self) -> Result<(Vec<InboxMessage>, bool)>;
+    /// Persist a newly received message.
+    fn append(&mut self, msg: &InboxMessage) -> Result<()>;
+    /// Persist an ack.
+    fn ack(&mut self, message_id: &str) -> Result<()>;
+    /// Replace everything stored with `messages`, e.g. after eviction.
+    fn rewrite(&mut self, messages: &[InboxMessage]) -> Result<()>;
+    /// Rewrite under the current cipher, leaving no older copy behind.
+    fn reencrypt(&mut self, messages: &[InboxMessage]) -> Result<()> {
+        self.rewrite(messages)
+    }
+    fn is_encrypted(&self) -> bool;
+}
+
+/// Node-level inbox: messages in memory, persisted through an
+/// [`InboxStorage`] (JSON lines by default, see [`JsonlStorage`]).
 pub struct NodeInbox {
-    path: PathBuf,
-    acked_path: PathBuf,
+    storage: Box<dyn InboxStorage>,
     messages: Vec<InboxMessage>,
     /// Running count of unread (un-acked) messages for O(1) access.
     unread_count: usize,
@@ -69,59 +93,40 @@ impl NodeInbox {
 
     /// Load with a custom max inbox size.
     pub fn load_with_capacity(state_dir: &Path, max_size: usize) -> Result<Self> {
-        let path = state_dir.join(INBOX_FILE);
-        let acked_path = state_dir.join(ACKED_FILE);
+        Self::with_storage(Box::new(JsonlStorage::new(state_dir, None)), max_size)
+    }
 
-        // Load acked IDs from the ack journal.
-        let acked_ids: HashSet<String> = if acked_path.exists() {
-            let data =
-                std::fs::read_to_string(&acked_path).context("failed to read inbox_acked.jsonl")?;
-            data.lines()
-                .filter(|l| !l.trim().is_empty())
-                .map(|l| l.trim().to_string())
-                .collect()
-        } else {
-            HashSet::new()
-        };
+    /// Load with lines encrypted under `cipher`. Plaintext lines from before
+    /// encryption was enabled are rewritten encrypted.
+    pub fn load_encrypted(state_dir: &Path, cipher: StateCipher) -> Result<Self> {
+        Self::with_storage(
+            Box::new(JsonlStorage::new(state_dir, Some(cipher))),
+            DEFAULT_MAX_INBOX_SIZE,
+        )
+    }
 
-        // Load messages and merge ack state.
-        let mut messages: Vec<InboxMessage> = if path.exists() {
-            let data = std::fs::read_to_string(&path).context("failed to read inbox.jsonl")?;
-            data.lines()
-                .filter(|l| !l.trim().is_empty())
-                .map(|l| {
-                    let mut msg: InboxMessage =
-                        serde_json::from_str(l).context("invalid inbox entry")?;
-                    if acked_ids.contains(&msg.message_id) {
-                        msg.acked = true;
-                    }
-                    Ok(msg)
-                })
-                .collect::<Result<Vec<_>>>()?
-        } else {
-            Vec::new()
-        };
+    /// Load from any storage backend.
+    pub fn with_storage(mut storage: Box<dyn InboxStorage>, max_size: usize) -> Result<Self> {
+        let (mut messages, needs_rewrite) = storage.load()?;
 
         // If we loaded more than max_size, compact immediately.
-        if messages.len() > max_size {
+        let evicted = messages.len() > max_size;
+        if evicted {
             evict_to_capacity(&mut messages, max_size);
         }
 
         let unread_count = messages.iter().filter(|m| !m.acked).count();
-
-        let inbox = Self {
-            path,
-            acked_path,
+        let mut inbox = Self {
+            storage,
             messages,
             unread_count,
             max_size,
         };
-
-        // If we had acked IDs to merge, compact the files so next load is clean.
-        if !acked_ids.is_empty() {
-            inbox.compact()?;
+        if needs_rewrite && inbox.is_encrypted() {
+            inbox.reencrypt()?;
+        } else if needs_rewrite || evicted {
+            inbox.storage.rewrite(&inbox.messages)?;
         }
-
         Ok(inbox)
     }
 
@@ -133,18 +138,10 @@ impl NodeInbox {
         if self.messages.len() >= self.max_size {
             let evicted = evict_to_capacity(&mut self.messages, self.max_size.saturating_sub(1));
             self.unread_count = self.unread_count.saturating_sub(evicted);
-            self.compact()?;
+            self.storage.rewrite(&self.messages)?;
         }
 
-        // Append to disk.
-        let line = serde_json::to_string(&msg)?;
-        let mut file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.path)
-            .with_context(|| format!("failed to open {}", self.path.display()))?;
-        writeln!(file, "{line}")?;
-
+        self.storage.append(&msg)?;
         self.messages.push(msg);
         if is_unread {
             self.unread_count += 1;
@@ -182,11 +179,24 @@ impl NodeInbox {
         items
     }
 
+    /// Look up a message by ID.
+    pub fn get(&self, message_id: &str) -> Option<&InboxMessage> {
+        self.messages.iter().find(|m| m.message_id == message_id)
+    }
+
+    /// The message that starts thread `thread_id` and every reply in it,
+    /// oldest first.
+    pub fn list_thread(&self, thread_id: &str) -> Vec<&InboxMessage> {
+        let mut items: Vec<_> = self
+            .messages
+            .iter()
+            .filter(|m| m.message_id == thread_id || m.thread_id.as_deref() == Some(thread_id))
+            .collect();
+        items.sort_by_key(|m| m.timestamp_ms);
+        items
+    }
+
     /// Mark a message as acknowledged.
-    ///
-    /// Instead of rewriting the entire inbox file, we append the acked
-    /// message ID to a separate journal file. The journal is merged on
-    /// load and cleared during compaction.
     pub fn ack(&mut self, message_id: &str) -> Result<bool> {
         if let Some(msg) = self
             .messages
@@ -197,8 +207,7 @@ impl NodeInbox {
                 msg.acked = true;
                 self.unread_count = self.unread_count.saturating_sub(1);
             }
-            // Append to ack journal instead of rewriting the whole file.
-            self.append_ack(message_id)?;
+            self.storage.ack(message_id)?;
             Ok(true)
         } else {
             Ok(false)
@@ -220,25 +229,185 @@ impl NodeInbox {
         self.messages.is_empty()
     }
 
-    /// Append a single acked message ID to the journal file.
-    fn append_ack(&self, message_id: &str) -> Result<()> {
+    /// Whether messages are stored encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.storage.is_encrypted()
+    }
+
+    /// Rewrite the stored inbox with fresh encryption. No copy of the old
+    /// contents is kept.
+    pub fn reencrypt(&mut self) -> Result<()> {
+        self.storage.reencrypt(&self.messages)
+    }
+
+    /// Approximate size of the stored messages: their JSON, before any
+    /// encryption.
+    pub fn stored_bytes(&self) -> u64 {
+        self.messages.iter().map(stored_size).sum()
+    }
+
+    /// Drop messages outside `retention`, then persist the result. Age is
+    /// applied first and drops unread messages too; the count and size limits
+    /// drop the oldest acked messages before any unread ones.
+    pub fn prune(&mut self, retention: &Retention, now_ms: u64) -> Result<Pruned> {
+        let mut pruned = Pruned::default();
+        if let Some(max_age_ms) = retention.max_age_ms {
+            let cutoff = now_ms.saturating_sub(max_age_ms);
+            let before = self.messages.len();
+            self.messages.retain(|m| m.timestamp_ms >= cutoff);
+            pruned.by_age = before - self.messages.len();
+        }
+        if let Some(max_count) = retention.max_count {
+            let before = self.messages.len();
+            evict_to_capacity(&mut self.messages, max_count);
+            pruned.by_count = before - self.messages.len();
+        }
+        if let Some(max_bytes) = retention.max_bytes {
+            pruned.by_bytes = evict_to_bytes(&mut self.messages, max_bytes);
+        }
+        if pruned.total() > 0 {
+            self.unread_count = self.messages.iter().filter(|m| !m.acked).count();
+            self.storage.rewrite(&self.messages)?;
+        }
+        Ok(pruned)
+    }
+}
+
+/// Limits on what the inbox keeps. `None` means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Retention {
+    /// Drop messages received more than this long ago.
+    pub max_age_ms: Option<u64>,
+    /// Keep at most this many messages.
+    pub max_count: Option<usize>,
+    /// Keep [`NodeInbox::stored_bytes`] at or under this.
+    pub max_bytes: Option<u64>,
+}
+
+/// How many messages [`NodeInbox::prune`] dropped, by the limit that
+/// dropped them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pruned {
+    pub by_age: usize,
+    pub by_count: usize,
+    pub by_bytes: usize,
+}
+
+impl Pruned {
+    pub fn total(&self) -> usize {
+        self.by_age + self.by_count + self.by_bytes
+    }
+}
+
+/// The default storage: append-only JSON lines.
+///
+/// Persistence strategy:
+/// - New messages are appended to `inbox.jsonl`.
+/// - Acks are appended to `inbox_acked.jsonl` (just the message_id).
+/// - On load, acked IDs are merged into the message list.
+/// - A full rewrite (compaction) only happens when evicting old messages.
+///
+/// This avoids the O(N) rewrite on every ack while keeping the on-disk
+/// format simple. With a [`StateCipher`] each line is encrypted on its own.
+pub struct JsonlStorage {
+    path: PathBuf,
+    acked_path: PathBuf,
+    cipher: Option<StateCipher>,
+}
+
+impl JsonlStorage {
+    pub fn new(state_dir: &Path, cipher: Option<StateCipher>) -> Self {
+        Self {
+            path: state_dir.join(INBOX_FILE),
+            acked_path: state_dir.join(ACKED_FILE),
+            cipher,
+        }
+    }
+
+    /// Where the JSONL inbox of `state_dir` lives, for migrating off it.
+    pub fn files(state_dir: &Path) -> [PathBuf; 2] {
+        [state_dir.join(INBOX_FILE), state_dir.join(ACKED_FILE)]
+    }
+}
+
+impl InboxStorage for JsonlStorage {
+    fn load(&mut self) -> Result<(Vec<InboxMessage>, bool)> {
+        let cipher = self.cipher.as_ref();
+        let mut legacy = false;
+
+        // Load acked IDs from the ack journal.
+        let acked_ids: HashSet<String> = if self.acked_path.exists() {
+            let data = std::fs::read_to_string(&self.acked_path)
+                .context("failed to read inbox_acked.jsonl")?;
+            parse_lines(&data, ACKED_FILE, |l| {
+                legacy |= !at_rest::is_sealed(l);
+                at_rest::decode(cipher, l).context("failed to read inbox_acked.jsonl")
+            })?
+            .into_iter()
+            .collect()
+        } else {
+            HashSet::new()
+        };
+
+        // Load messages and merge ack state.
+        let messages: Vec<InboxMessage> = if self.path.exists() {
+            let data = std::fs::read_to_string(&self.path).context("failed to read inbox.jsonl")?;
+            parse_lines(&data, INBOX_FILE, |l| {
+                legacy |= !at_rest::is_sealed(l);
+                let line = at_rest::decode(cipher, l).context("failed to read inbox.jsonl")?;
+                let mut msg: InboxMessage =
+                    serde_json::from_str(&line).context("invalid inbox entry")?;
+                if acked_ids.contains(&msg.message_id) {
+                    msg.acked = true;
+                }
+                Ok(msg)
+            })?
+        } else {
+            Vec::new()
+        };
+
+        // If we had acked IDs to merge, or plaintext to encrypt, compact the
+        // files so next load is clean.
+        let needs_rewrite = !acked_ids.is_empty() || (legacy && self.cipher.is_some());
+        Ok((messages, needs_rewrite))
+    }
+
+    fn append(&mut self, msg: &InboxMessage) -> Result<()> {
+        let line = at_rest::encode(self.cipher.as_ref(), &serde_json::to_string(msg)?)?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open {}", self.path.display()))?;
+        writeln!(file, "{line}")?;
+        Ok(())
+    }
+
+    /// Instead of rewriting the entire inbox file, append the acked message
+    /// ID to a separate journal file. The journal is merged on load and
+    /// cleared during compaction.
+    fn ack(&mut self, message_id: &str) -> Result<()> {
         let mut file = std::fs::OpenOptions::new()
             .create(true)
             .append(true)
             .open(&self.acked_path)
             .with_context(|| format!("failed to open {}", self.acked_path.display()))?;
-        writeln!(file, "{message_id}")?;
+        writeln!(
+            file,
+            "{}",
+            at_rest::encode(self.cipher.as_ref(), message_id)?
+        )?;
         Ok(())
     }
 
     /// Compact: rewrite inbox.jsonl with current state and clear the ack journal.
-    fn compact(&self) -> Result<()> {
-        let mut file = std::fs::File::create(&self.path)
-            .with_context(|| format!("failed to rewrite {}", self.path.display()))?;
-        for msg in &self.messages {
-            let line = serde_json::to_string(msg)?;
-            writeln!(file, "{line}")?;
+    fn rewrite(&mut self, messages: &[InboxMessage]) -> Result<()> {
+        let mut data = String::new();
+        for msg in messages {
+            data += &at_rest::encode(self.cipher.as_ref(), &serde_json::to_string(msg)?)?;
+            data.push('\n');
         }
+        state_file::write(&self.path, data)?;
         // Clear ack journal since all ack state is now in the main file.
         if self.acked_path.exists() {
             std::fs::File::create(&self.acked_path)
@@ -246,6 +415,68 @@ impl NodeInbox {
         }
         Ok(())
     }
+
+    fn reencrypt(&mut self, messages: &[InboxMessage]) -> Result<()> {
+        self.rewrite(messages)?;
+        state_file::remove_backup(&self.path)
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+}
+
+/// Parse a JSON-lines file. Appends are not atomic, so a crash can leave a
+/// torn last line; that one is dropped instead of failing the whole load.
+fn parse_lines<T>(
+    data: &str,
+    name: &str,
+    mut parse: impl FnMut(&str) -> Result<T>,
+) -> Result<Vec<T>> {
+    let lines: Vec<&str> = data
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    let mut parsed = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        match parse(line) {
+            Ok(value) => parsed.push(value),
+            Err(e) if i + 1 == lines.len() && !data.ends_with('\n') => {
+                tracing::warn!(file = name, err = %format!("{e:#}"), "dropping torn last line");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(parsed)
+}
+
+fn stored_size(msg: &InboxMessage) -> u64 {
+    serde_json::to_string(msg).map_or(0, |json| json.len() as u64 + 1)
+}
+
+/// Evict oldest acked messages, then oldest unread ones, until the stored
+/// size is at most `max_bytes`. Returns how many were evicted.
+fn evict_to_bytes(messages: &mut Vec<InboxMessage>, max_bytes: u64) -> usize {
+    let sizes: Vec<u64> = messages.iter().map(stored_size).collect();
+    let mut total: u64 = sizes.iter().sum();
+    let mut evict = vec![false; messages.len()];
+    let mut evicted = 0;
+    for acked in [true, false] {
+        for (i, msg) in messages.iter().enumerate() {
+            if total <= max_bytes {
+                break;
+            }
+            if msg.acked == acked {
+                evict[i] = true;
+                total -= sizes[i];
+                evicted += 1;
+            }
+        }
+    }
+    let mut evict = evict.into_iter();
+    messages.retain(|_| !evict.next().unwrap_or(false));
+    evicted
 }
 
 /// Evict oldest acked messages until `messages.len() <= target_size`.
@@ -299,6 +530,9 @@ mod tests {
             timestamp_ms: 1000,
             acked: false,
             message_type: MessageType::default(),
+            attachments: Vec::new(),
+            in_reply_to: None,
+            thread_id: None,
         }
     }
 
@@ -337,6 +571,50 @@ mod tests {
         assert_eq!(inbox.unread_count(), 1);
     }
 
+    #[test]
+    fn torn_last_line_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let mut inbox = NodeInbox::load(dir.path()).unwrap();
+            inbox.push(make_msg("1")).unwrap();
+        }
+        let path = dir.path().join(INBOX_FILE);
+        let line = std::fs::read_to_string(&path).unwrap();
+        let torn = r#"{"message_id":"2","from_"#;
+
+        std::fs::write(&path, format!("{line}{torn}")).unwrap();
+        let inbox = NodeInbox::load(dir.path()).unwrap();
+        assert_eq!(inbox.list(false, None).len(), 1);
+
+        // A bad line in the middle is still an error.
+        std::fs::write(&path, format!("{line}{torn}\n{line}")).unwrap();
+        assert!(NodeInbox::load(dir.path()).is_err());
+    }
+
+    #[test]
+    fn encrypted_inbox_migrates_and_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let mut inbox = NodeInbox::load(dir.path()).unwrap();
+            inbox.push(make_msg("1")).unwrap();
+        }
+        let cipher = StateCipher::new(&crate::crypto::random_key_material());
+        {
+            let mut inbox = NodeInbox::load_encrypted(dir.path(), cipher.clone()).unwrap();
+            inbox.push(make_msg("2")).unwrap();
+            inbox.ack("1").unwrap();
+        }
+
+        for file in [INBOX_FILE, ACKED_FILE] {
+            let data = std::fs::read_to_string(dir.path().join(file)).unwrap();
+            assert!(data.lines().all(at_rest::is_sealed), "{file}: {data}");
+            assert!(!data.contains("hello"));
+        }
+        let inbox = NodeInbox::load_encrypted(dir.path(), cipher).unwrap();
+        assert_eq!(inbox.len(), 2);
+        assert_eq!(inbox.unread_count(), 1);
+    }
+
     #[test]
     fn list_limit_returns_newest_messages() {
         let dir = tempfile::tempdir().unwrap();
@@ -365,6 +643,33 @@ mod tests {
         assert_eq!(ids, vec!["2", "3"]);
     }
 
+    #[test]
+    fn list_thread_orders_the_conversation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut inbox = NodeInbox::load(dir.path()).unwrap();
+        let reply = |id: &str, parent: &str, ts| {
+            let mut msg = make_msg(id);
+            msg.in_reply_to = Some(parent.to_string());
+            msg.thread_id = Some("1".to_string());
+            msg.timestamp_ms = ts;
+            msg
+        };
+        inbox.push(make_msg("1")).unwrap();
+        inbox.push(make_msg("other")).unwrap();
+        // Delivery order need not match send order.
+        inbox.push(reply("3", "2", 3000)).unwrap();
+        inbox.push(reply("2", "1", 2000)).unwrap();
+
+        let ids: Vec<_> = inbox
+            .list_thread("1")
+            .iter()
+            .map(|m| m.message_id.as_str())
+            .collect();
+        assert_eq!(ids, ["1", "2", "3"]);
+        assert_eq!(inbox.get("3").unwrap().in_reply_to.as_deref(), Some("2"));
+        assert!(inbox.list_thread("missing").is_empty());
+    }
+
     #[test]
     fn ack_does_not_rewrite_main_file() {
         let dir = tempfile::tempdir().unwrap();
@@ -501,6 +806,58 @@ mod tests {
         assert!(ids.contains(&"4"));
     }
 
+    #[test]
+    fn prune_applies_age_count_and_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut inbox = NodeInbox::load(dir.path()).unwrap();
+        for (id, ts) in [("old", 1_000), ("a", 5_000), ("b", 6_000), ("c", 7_000)] {
+            let mut msg = make_msg(id);
+            msg.timestamp_ms = ts;
+            inbox.push(msg).unwrap();
+        }
+        inbox.ack("b").unwrap();
+
+        let pruned = inbox
+            .prune(
+                &Retention {
+                    max_age_ms: Some(5_000),
+                    max_count: Some(2),
+                    ..Retention::default()
+                },
+                10_000,
+            )
+            .unwrap();
+        assert_eq!((pruned.by_age, pruned.by_count), (1, 1));
+        // The acked message went first.
+        let ids: Vec<_> = inbox
+            .list(false, None)
+            .iter()
+            .map(|m| m.message_id.as_str())
+            .collect();
+        assert_eq!(ids, ["a", "c"]);
+        assert_eq!(inbox.unread_count(), 2);
+
+        let one = inbox.stored_bytes() / 2;
+        let pruned = inbox
+            .prune(
+                &Retention {
+                    max_bytes: Some(one),
+                    ..Retention::default()
+                },
+                10_000,
+            )
+            .unwrap();
+        assert_eq!(pruned.total(), 1);
+        assert_eq!(inbox.list(false, None)[0].message_id, "c");
+
+        // Nothing left to prune means no rewrite and no change after reload.
+        assert_eq!(
+            inbox.prune(&Retention::default(), 10_000).unwrap().total(),
+            0
+        );
+        assert_eq!(NodeInbox::load(dir.path()).unwrap().len(), 1);
+    }
+
     #[test]
     fn list_by_topic_filters_correctly() {
         let dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,232 @@
+//! SQLite storage for the node inbox (`sqlite` feature).
+//!
+//! Messages live in `inbox.db`, one row per message keyed by `message_id`, so
+//! an ack is an indexed `UPDATE` and eviction a `DELETE` instead of a journal
+//! append and a full-file rewrite. The message itself is kept as the same
+//! JSON (sealed under the [`StateCipher`] when there is one) that
+//! `inbox.jsonl` holds, so only the ack flag is visible without the key.
+//!
+//! Opening an empty database next to an existing `inbox.jsonl` imports it and
+//! renames the file to `inbox.jsonl.migrated`; delete that once the node has
+//! run fine on the database.
+
+use crate::at_rest::{self, StateCipher};
+use crate::inbox::{InboxMessage, InboxStorage, JsonlStorage};
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+use std::path::Path;
+use std::time::Duration;
+
+const DB_FILE: &str = "inbox.db";
+
+/// [`InboxStorage`] backed by a SQLite database in the state directory.
+pub struct SqliteStorage {
+    conn: Connection,
+    cipher: Option<StateCipher>,
+}
+
+impl SqliteStorage {
+    /// Open (or create) `inbox.db` in `state_dir`, importing a JSONL inbox
+    /// the first time.
+    pub fn open(state_dir: &Path, cipher: Option<StateCipher>) -> Result<Self> {
+        let path = state_dir.join(DB_FILE);
+        let conn = Connection::open(&path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        let mut storage = Self::with_connection(conn, cipher)?;
+        storage.migrate_jsonl(state_dir)?;
+        Ok(storage)
+    }
+
+    fn with_connection(conn: Connection, cipher: Option<StateCipher>) -> Result<Self> {
+        conn.pragma_update(None, "journal_mode", "WAL").ok();
+        conn.pragma_update(None, "synchronous", "NORMAL").ok();
+        conn.busy_timeout(Duration::from_secs(5)).ok();
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS inbox (
+                seq         INTEGER PRIMARY KEY AUTOINCREMENT,
+                message_id  TEXT NOT NULL UNIQUE,
+                acked       INTEGER NOT NULL DEFAULT 0,
+                data        TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_inbox_unread ON inbox(acked, seq);",
+        )
+        .context("failed to create inbox table")?;
+        Ok(Self { conn, cipher })
+    }
+
+    fn migrate_jsonl(&mut self, state_dir: &Path) -> Result<()> {
+        let [inbox_file, acked_file] = JsonlStorage::files(state_dir);
+        if !inbox_file.exists() {
+            return Ok(());
+        }
+        let count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM inbox", [], |row| row.get(0))?;
+        if count > 0 {
+            tracing::warn!(
+                path = %inbox_file.display(),
+                "inbox.db already has messages, not importing the JSONL inbox"
+            );
+            return Ok(());
+        }
+
+        let (messages, _) = JsonlStorage::new(state_dir, self.cipher.clone()).load()?;
+        self.rewrite(&messages)?;
+        std::fs::rename(&inbox_file, inbox_file.with_extension("jsonl.migrated"))
+            .with_context(|| format!("failed to rename {}", inbox_file.display()))?;
+        for stale in [acked_file, crate::state_file::backup_path(&inbox_file)] {
+            if stale.exists() {
+                std::fs::remove_file(&stale)
+                    .with_context(|| format!("failed to remove {}", stale.display()))?;
+            }
+        }
+        tracing::info!(count = messages.len(), "imported JSONL inbox into inbox.db");
+        Ok(())
+    }
+
+    fn insert(conn: &Connection, cipher: Option<&StateCipher>, msg: &InboxMessage) -> Result<()> {
+        let data = at_rest::encode(cipher, &serde_json::to_string(msg)?)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO inbox (message_id, acked, data) VALUES (?1, ?2, ?3)",
+            params![msg.message_id, msg.acked, data],
+        )?;
+        Ok(())
+    }
+}
+
+impl InboxStorage for SqliteStorage {
+    fn load(&mut self) -> Result<(Vec<InboxMessage>, bool)> {
+        let cipher = self.cipher.as_ref();
+        let mut legacy = false;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT acked, data FROM inbox ORDER BY seq")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, bool>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut messages = Vec::new();
+        for row in rows {
+            let (acked, data) = row?;
+            legacy |= !at_rest::is_sealed(&data);
+            let line = at_rest::decode(cipher, &data).context("failed to read inbox.db")?;
+            let mut msg: InboxMessage =
+                serde_json::from_str(&line).context("invalid inbox entry")?;
+            // The column is authoritative: acks do not rewrite `data`.
+            msg.acked = acked;
+            messages.push(msg);
+        }
+        Ok((messages, legacy && cipher.is_some()))
+    }
+
+    fn append(&mut self, msg: &InboxMessage) -> Result<()> {
+        Self::insert(&self.conn, self.cipher.as_ref(), msg)
+    }
+
+    fn ack(&mut self, message_id: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE inbox SET acked = 1 WHERE message_id = ?1",
+            params![message_id],
+        )?;
+        Ok(())
+    }
+
+    fn rewrite(&mut self, messages: &[InboxMessage]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM inbox", [])?;
+        for msg in messages {
+            Self::insert(&tx, self.cipher.as_ref(), msg)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Rewrite every row, then vacuum so freed pages holding the old
+    /// ciphertext (or plaintext) do not linger in the file or the WAL.
+    fn reencrypt(&mut self, messages: &[InboxMessage]) -> Result<()> {
+        self.rewrite(messages)?;
+        self.conn
+            .execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+            .context("failed to vacuum inbox.db")
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inbox::{MessageType, NodeInbox};
+
+    fn make_msg(id: &str) -> InboxMessage {
+        InboxMessage {
+            message_id: id.to_string(),
+            from_node_id: "node-a".to_string(),
+            from_public_key_b64: "pub".to_string(),
+            to_node_id: None,
+            topic: None,
+            body: "hello".to_string(),
+            timestamp_ms: 1000,
+            acked: false,
+            message_type: MessageType::default(),
+            attachments: Vec::new(),
+            in_reply_to: None,
+            thread_id: None,
+        }
+    }
+
+    #[test]
+    fn acks_and_evictions_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let cipher = StateCipher::new(&crate::crypto::random_key_material());
+        let open = |cap| {
+            let storage = SqliteStorage::open(dir.path(), Some(cipher.clone())).unwrap();
+            NodeInbox::with_storage(Box::new(storage), cap).unwrap()
+        };
+        {
+            let mut inbox = open(3);
+            for id in ["1", "2", "3"] {
+                inbox.push(make_msg(id)).unwrap();
+            }
+            inbox.ack("1").unwrap();
+            inbox.push(make_msg("4")).unwrap();
+        }
+
+        let inbox = open(3);
+        let ids: Vec<_> = inbox
+            .list(false, None)
+            .iter()
+            .map(|m| m.message_id.as_str())
+            .collect();
+        assert_eq!(ids, ["2", "3", "4"]);
+        assert_eq!(inbox.unread_count(), 3);
+        assert!(inbox.is_encrypted());
+    }
+
+    #[test]
+    fn jsonl_inbox_is_imported_once() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let mut inbox = NodeInbox::load(dir.path()).unwrap();
+            inbox.push(make_msg("1")).unwrap();
+            inbox.push(make_msg("2")).unwrap();
+            inbox.ack("2").unwrap();
+        }
+
+        let cipher = StateCipher::new(&crate::crypto::random_key_material());
+        let storage = SqliteStorage::open(dir.path(), Some(cipher.clone())).unwrap();
+        let inbox = NodeInbox::with_storage(Box::new(storage), 10).unwrap();
+        assert_eq!(inbox.len(), 2);
+        assert_eq!(inbox.unread_count(), 1);
+        assert!(!dir.path().join("inbox.jsonl").exists());
+        assert!(!dir.path().join("inbox_acked.jsonl").exists());
+        assert!(dir.path().join("inbox.jsonl.migrated").exists());
+
+        // The plaintext import was sealed on load.
+        let mut storage = SqliteStorage::open(dir.path(), Some(cipher)).unwrap();
+        let (messages, needs_rewrite) = storage.load().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(!needs_rewrite);
+    }
+}
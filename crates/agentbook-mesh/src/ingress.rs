@@ -1,7 +1,8 @@
 use crate::crypto::verify_signature;
 use crate::follow::FollowStore;
 use crate::inbox::MessageType;
-use agentbook_crypto::rate_limit::{CheckResult, RateLimiter};
+use crate::ingress_limits::{IngressLimits, RateClass};
+use agentbook_crypto::rate_limit::CheckResult;
 
 /// Result of ingress validation.
 pub enum IngressResult {
@@ -24,14 +25,14 @@ pub struct IngressRequest<'a> {
 /// Validates inbound messages against signature, follow graph, and rate limits.
 pub struct IngressPolicy<'a> {
     follow_store: &'a FollowStore,
-    rate_limiter: &'a mut RateLimiter,
+    limits: &'a mut IngressLimits,
 }
 
 impl<'a> IngressPolicy<'a> {
-    pub fn new(follow_store: &'a FollowStore, rate_limiter: &'a mut RateLimiter) -> Self {
+    pub fn new(follow_store: &'a FollowStore, limits: &'a mut IngressLimits) -> Self {
         Self {
             follow_store,
-            rate_limiter,
+            limits,
         }
     }
 
@@ -39,11 +40,13 @@ impl<'a> IngressPolicy<'a> {
     ///
     /// Steps:
     /// 1. Verify signature
-    /// 2. Check blocked list
+    /// 2. Check blocked list and revoked keys
     /// 3. For DMs: require that we follow the sender (mutual follow gating
     ///    is enforced at the sender side — we accept if we follow them)
     /// 4. For feed posts: accept from anyone we follow
-    /// 5. Rate limit
+    /// 5. If we followed the sender back through a scoped invite, require
+    ///    the scope for this message type (`dm` or `feed`)
+    /// 6. Rate limit against the sender's budget for this message type
     pub fn check(&mut self, req: &IngressRequest<'_>) -> IngressResult {
         // Relay-generated room system events have no signature to verify.
         if matches!(
@@ -65,22 +68,33 @@ impl<'a> IngressPolicy<'a> {
         if self.follow_store.is_blocked(req.from_node_id) {
             return IngressResult::Reject("sender is blocked".to_string());
         }
+        if self.follow_store.is_revoked(req.from_node_id) {
+            return IngressResult::Reject("sender key revoked".to_string());
+        }
 
-        // 3. Check follow relationship
-        let is_following = self.follow_store.is_following(req.from_node_id);
+        // 3. Check follow relationship and invite scopes
+        let follow = self.follow_store.get(req.from_node_id);
         match req.message_type {
-            MessageType::DmText => {
-                if !is_following {
+            MessageType::DmText => match follow {
+                None => {
                     return IngressResult::Reject(
                         "DMs require mutual follow (you don't follow sender)".to_string(),
                     );
                 }
-            }
-            MessageType::FeedPost => {
-                if !is_following {
-                    return IngressResult::Reject("not following sender".to_string());
+                Some(f) if !f.allows("dm") => {
+                    return IngressResult::Reject("sender's invite does not grant dm".to_string());
                 }
-            }
+                Some(_) => {}
+            },
+            MessageType::FeedPost => match follow {
+                None => return IngressResult::Reject("not following sender".to_string()),
+                Some(f) if !f.allows("feed") => {
+                    return IngressResult::Reject(
+                        "sender's invite does not grant feed".to_string(),
+                    );
+                }
+                Some(_) => {}
+            },
             MessageType::RoomMessage | MessageType::RoomJoin | MessageType::RoomLeave => {
                 // Room messages and room system events skip follow-graph check.
             }
@@ -88,7 +102,10 @@ impl<'a> IngressPolicy<'a> {
         }
 
         // 4. Rate limit
-        match self.rate_limiter.check(req.from_node_id) {
+        match self
+            .limits
+            .check(req.from_node_id, RateClass::from(req.message_type))
+        {
             CheckResult::Allowed => {}
             CheckResult::RateLimited | CheckResult::Banned { .. } => {
                 return IngressResult::Reject("rate limited".to_string());
@@ -116,6 +133,9 @@ mod tests {
             username: None,
             relay_hints: vec![],
             followed_at_ms: now_ms(),
+            scopes: vec![],
+            alias: None,
+            groups: vec![],
         }
     }
 
@@ -132,7 +152,7 @@ mod tests {
             .follow(make_follow_record(&node_id, &pub_b64))
             .unwrap();
 
-        let mut rl = RateLimiter::new(10, 1.0);
+        let mut rl = IngressLimits::uniform(10, 1.0);
         let mut policy = IngressPolicy::new(&store, &mut rl);
 
         let payload = b"test";
@@ -158,7 +178,7 @@ mod tests {
         let pub_b64 = base64::engine::general_purpose::STANDARD.encode(public.to_sec1_bytes());
         let node_id = evm_address_from_public_key(&public);
 
-        let mut rl = RateLimiter::new(10, 1.0);
+        let mut rl = IngressLimits::uniform(10, 1.0);
         let mut policy = IngressPolicy::new(&store, &mut rl);
 
         let payload = b"test";
@@ -178,11 +198,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn invite_scopes_limit_message_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FollowStore::load(dir.path()).unwrap();
+        let secret = SecretKey::random(&mut OsRng);
+        let public = secret.public_key();
+        let pub_b64 = base64::engine::general_purpose::STANDARD.encode(public.to_sec1_bytes());
+        let node_id = evm_address_from_public_key(&public);
+
+        store
+            .follow(FollowRecord {
+                scopes: vec!["feed".to_string()],
+                ..make_follow_record(&node_id, &pub_b64)
+            })
+            .unwrap();
+
+        let mut rl = IngressLimits::uniform(10, 1.0);
+        let mut policy = IngressPolicy::new(&store, &mut rl);
+
+        let payload = b"test";
+        let sig = sign_payload(&secret, payload).unwrap();
+        let mut req = IngressRequest {
+            from_node_id: &node_id,
+            from_public_key_b64: &pub_b64,
+            payload,
+            signature_b64: &sig,
+            my_node_id: "my_node",
+            message_type: MessageType::DmText,
+        };
+        match policy.check(&req) {
+            IngressResult::Reject(msg) => assert!(msg.contains("does not grant dm")),
+            IngressResult::Accept => panic!("expected Reject"),
+        }
+        req.message_type = MessageType::FeedPost;
+        assert!(matches!(policy.check(&req), IngressResult::Accept));
+    }
+
     #[test]
     fn reject_bad_signature() {
         let dir = tempfile::tempdir().unwrap();
         let store = FollowStore::load(dir.path()).unwrap();
-        let mut rl = RateLimiter::new(10, 1.0);
+        let mut rl = IngressLimits::uniform(10, 1.0);
         let mut policy = IngressPolicy::new(&store, &mut rl);
 
         let req = IngressRequest {
@@ -210,7 +267,7 @@ mod tests {
 
         store.block(&node_id).unwrap();
 
-        let mut rl = RateLimiter::new(10, 1.0);
+        let mut rl = IngressLimits::uniform(10, 1.0);
         let mut policy = IngressPolicy::new(&store, &mut rl);
 
         let payload = b"test";
@@ -229,4 +286,38 @@ mod tests {
             IngressResult::Accept => panic!("expected Reject"),
         }
     }
+
+    #[test]
+    fn reject_from_revoked() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FollowStore::load(dir.path()).unwrap();
+        let secret = SecretKey::random(&mut OsRng);
+        let public = secret.public_key();
+        let pub_b64 = base64::engine::general_purpose::STANDARD.encode(public.to_sec1_bytes());
+        let node_id = evm_address_from_public_key(&public);
+
+        store
+            .follow(make_follow_record(&node_id, &pub_b64))
+            .unwrap();
+        store.revoke_key(&node_id).unwrap();
+
+        let mut rl = IngressLimits::uniform(10, 1.0);
+        let mut policy = IngressPolicy::new(&store, &mut rl);
+
+        let payload = b"test";
+        let sig = sign_payload(&secret, payload).unwrap();
+
+        let req = IngressRequest {
+            from_node_id: &node_id,
+            from_public_key_b64: &pub_b64,
+            payload,
+            signature_b64: &sig,
+            my_node_id: "my_node",
+            message_type: MessageType::DmText,
+        };
+        match policy.check(&req) {
+            IngressResult::Reject(msg) => assert!(msg.contains("revoked")),
+            IngressResult::Accept => panic!("expected Reject"),
+        }
+    }
 }
@@ -0,0 +1,330 @@
+//! Per-peer, per-message-type ingress rate budgets that survive restarts.
+
+use crate::inbox::MessageType;
+use crate::state_file;
+use agentbook_crypto::rate_limit::{CheckResult, KeyUsage, RateLimiter, RateLimiterSnapshot};
+use agentbook_crypto::time::{Clock, SystemClock};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const RATE_LIMITS_FILE: &str = "rate_limits.json";
+
+/// Peers idle this long are dropped when saving; their buckets are full again.
+const MAX_IDLE_SECS: f64 = 24.0 * 3_600.0;
+
+/// Which budget an inbound delivery is charged against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateClass {
+    Dm,
+    Feed,
+    /// Key rotation and revocation notices.
+    KeyNotice,
+    /// Pings, each of which costs us a pong.
+    Ping,
+    Other,
+}
+
+impl From<MessageType> for RateClass {
+    fn from(message_type: MessageType) -> Self {
+        match message_type {
+            MessageType::DmText => Self::Dm,
+            MessageType::FeedPost => Self::Feed,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Token bucket parameters for one [`RateClass`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateBudget {
+    /// Max burst size.
+    pub capacity: u32,
+    /// Sustained rate.
+    pub per_second: f64,
+}
+
+impl RateClass {
+    pub const ALL: [RateClass; 5] = [
+        Self::Dm,
+        Self::Feed,
+        Self::KeyNotice,
+        Self::Ping,
+        Self::Other,
+    ];
+
+    /// Wire name, matching the serde representation.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dm => "dm",
+            Self::Feed => "feed",
+            Self::KeyNotice => "key_notice",
+            Self::Ping => "ping",
+            Self::Other => "other",
+        }
+    }
+
+    /// Parse a wire name produced by [`RateClass::as_str`].
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|class| class.as_str() == name)
+    }
+
+    /// Default budget per sender.
+    pub fn default_budget(self) -> RateBudget {
+        let (capacity, per_second) = match self {
+            Self::Dm => (20, 2.0),
+            Self::Feed => (10, 0.5),
+            Self::KeyNotice => (5, 0.1),
+            Self::Ping => (5, 0.2),
+            Self::Other => (20, 2.0),
+        };
+        RateBudget {
+            capacity,
+            per_second,
+        }
+    }
+}
+
+/// Current budget of one peer for one class.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerUsage {
+    pub class: RateClass,
+    pub usage: KeyUsage,
+}
+
+/// One rate limiter per [`RateClass`], keyed by sender node ID.
+///
+/// Counters and bans are written to `rate_limits.json` by [`IngressLimits::save`]
+/// so a restart neither resets a sender's budget nor lifts a ban. [`check`]
+/// only marks the limits dirty; callers flush them periodically.
+///
+/// [`check`]: IngressLimits::check
+pub struct IngressLimits {
+    path: Option<PathBuf>,
+    limiters: BTreeMap<RateClass, RateLimiter>,
+    dirty: bool,
+}
+
+impl IngressLimits {
+    /// In-memory limits using `budget_for` for each class.
+    pub fn new(budget_for: impl Fn(RateClass) -> RateBudget, clock: Arc<dyn Clock>) -> Self {
+        let limiters = RateClass::ALL
+            .into_iter()
+            .map(|class| {
+                let budget = budget_for(class);
+                let limiter =
+                    RateLimiter::new(budget.capacity, budget.per_second).with_clock(clock.clone());
+                (class, limiter)
+            })
+            .collect();
+        Self {
+            path: None,
+            limiters,
+            dirty: false,
+        }
+    }
+
+    /// In-memory limits with the same budget for every class.
+    pub fn uniform(capacity: u32, per_second: f64) -> Self {
+        let budget = RateBudget {
+            capacity,
+            per_second,
+        };
+        Self::new(|_| budget, Arc::new(SystemClock))
+    }
+
+    /// Default budgets, persisted to `state_dir`, starting with no history.
+    pub fn empty(state_dir: &Path, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            path: Some(state_dir.join(RATE_LIMITS_FILE)),
+            ..Self::new(RateClass::default_budget, clock)
+        }
+    }
+
+    /// Default budgets, restoring counters saved in `state_dir`.
+    pub fn load(state_dir: &Path, clock: Arc<dyn Clock>) -> Result<Self> {
+        let mut limits = Self::empty(state_dir, clock);
+        let path = state_dir.join(RATE_LIMITS_FILE);
+        let snapshots = state_file::read(&path, |data| {
+            serde_json::from_str::<BTreeMap<RateClass, RateLimiterSnapshot>>(data)
+                .context("invalid rate_limits.json")
+        })?;
+        if let Some(snapshots) = snapshots {
+            for (class, snapshot) in snapshots {
+                if let Some(limiter) = limits.limiters.get_mut(&class) {
+                    limiter.restore(snapshot);
+                }
+            }
+        }
+        Ok(limits)
+    }
+
+    /// Charge one delivery from `node_id` against its `class` budget.
+    pub fn check(&mut self, node_id: &str, class: RateClass) -> CheckResult {
+        self.dirty = true;
+        self.limiters
+            .get_mut(&class)
+            .map_or(CheckResult::Allowed, |limiter| limiter.check(node_id))
+    }
+
+    /// Persist counters, dropping long-idle peers. No-op for in-memory limits.
+    pub fn save(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let snapshots: BTreeMap<RateClass, RateLimiterSnapshot> = self
+            .limiters
+            .iter_mut()
+            .map(|(class, limiter)| {
+                limiter.cleanup(MAX_IDLE_SECS);
+                (*class, limiter.snapshot())
+            })
+            .collect();
+        let data = serde_json::to_string(&snapshots)?;
+        state_file::write(path, data)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// [`IngressLimits::save`] if anything was charged since the last save.
+    pub fn flush(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.save()
+    }
+
+    /// Replace the budget for `class`, keeping peers' counters and bans.
+    pub fn set_budget(&mut self, class: RateClass, budget: RateBudget) {
+        if let Some(limiter) = self.limiters.get_mut(&class) {
+            limiter.set_budget(budget.capacity, budget.per_second);
+        }
+    }
+
+    /// Budget configured for each class.
+    pub fn budgets(&self) -> Vec<(RateClass, RateBudget)> {
+        self.limiters
+            .iter()
+            .map(|(class, limiter)| {
+                let budget = RateBudget {
+                    capacity: limiter.capacity(),
+                    per_second: limiter.refill_rate(),
+                };
+                (*class, budget)
+            })
+            .collect()
+    }
+
+    /// Current usage of every tracked peer, or only `node_id` when given.
+    pub fn usage(&self, node_id: Option<&str>) -> Vec<PeerUsage> {
+        self.limiters
+            .iter()
+            .flat_map(|(class, limiter)| {
+                limiter
+                    .usage()
+                    .into_iter()
+                    .filter(|usage| node_id.is_none_or(|id| usage.key == id))
+                    .map(|usage| PeerUsage {
+                        class: *class,
+                        usage,
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentbook_crypto::time::ManualClock;
+
+    #[test]
+    fn classes_have_separate_budgets() {
+        let mut limits = IngressLimits::new(
+            |_| RateBudget {
+                capacity: 1,
+                per_second: 0.001,
+            },
+            Arc::new(SystemClock),
+        );
+        assert_eq!(limits.check("peer", RateClass::Dm), CheckResult::Allowed);
+        assert_eq!(
+            limits.check("peer", RateClass::Dm),
+            CheckResult::RateLimited
+        );
+        assert_eq!(limits.check("peer", RateClass::Feed), CheckResult::Allowed);
+        assert_eq!(limits.check("other", RateClass::Dm), CheckResult::Allowed);
+    }
+
+    #[test]
+    fn set_budget_clamps_existing_balances() {
+        let clock = Arc::new(ManualClock::new());
+        let mut limits = IngressLimits::new(RateClass::default_budget, clock);
+        for _ in 0..3 {
+            assert_eq!(limits.check("peer", RateClass::Dm), CheckResult::Allowed);
+        }
+        let tight = RateBudget {
+            capacity: 2,
+            per_second: 0.001,
+        };
+        limits.set_budget(RateClass::Dm, tight);
+        for _ in 0..2 {
+            assert_eq!(limits.check("peer", RateClass::Dm), CheckResult::Allowed);
+        }
+        assert_eq!(
+            limits.check("peer", RateClass::Dm),
+            CheckResult::RateLimited
+        );
+        assert!(limits.budgets().contains(&(RateClass::Dm, tight)));
+        assert_eq!(RateClass::parse("key_notice"), Some(RateClass::KeyNotice));
+        assert_eq!(RateClass::parse("ping"), Some(RateClass::Ping));
+        assert_eq!(RateClass::parse("bogus"), None);
+    }
+
+    #[test]
+    fn counters_persist_across_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let clock = Arc::new(ManualClock::new());
+        let mut limits = IngressLimits::load(dir.path(), clock.clone()).unwrap();
+        for _ in 0..10 {
+            assert_eq!(limits.check("peer", RateClass::Feed), CheckResult::Allowed);
+        }
+        assert_eq!(
+            limits.check("peer", RateClass::Feed),
+            CheckResult::RateLimited
+        );
+        limits.save().unwrap();
+
+        let mut reloaded = IngressLimits::load(dir.path(), clock.clone()).unwrap();
+        assert_eq!(
+            reloaded.check("peer", RateClass::Feed),
+            CheckResult::RateLimited
+        );
+        let usage = reloaded.usage(Some("peer"));
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].class, RateClass::Feed);
+        assert_eq!(usage[0].usage.violations, 2);
+        assert!(reloaded.usage(Some("nobody")).is_empty());
+    }
+
+    #[test]
+    fn check_defers_writes_until_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(RATE_LIMITS_FILE);
+        let mut limits = IngressLimits::empty(dir.path(), Arc::new(ManualClock::new()));
+        limits.flush().unwrap();
+        assert!(!path.exists());
+
+        assert_eq!(limits.check("peer", RateClass::Dm), CheckResult::Allowed);
+        assert!(!path.exists());
+        limits.flush().unwrap();
+        assert!(path.exists());
+
+        std::fs::remove_file(&path).unwrap();
+        limits.flush().unwrap();
+        assert!(!path.exists(), "clean limits are not rewritten");
+    }
+}
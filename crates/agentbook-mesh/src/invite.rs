@@ -1,9 +1,39 @@
 use crate::crypto::{sign_payload, verify_signature};
+use crate::state_file;
+use agentbook_crypto::time::now_ms;
 use anyhow::{Context, Result, bail};
 use base64::Engine;
 use k256::SecretKey;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+
+const INVITES_FILE: &str = "invites.json";
+
+/// Scopes an invite can grant: `dm` to send us direct messages, `feed` to
+/// have their feed posts accepted. An invite with no scopes grants both.
+pub const SCOPES: &[&str] = &["dm", "feed"];
+
+/// Longest lifetime an invite may be issued with (one year).
+pub const MAX_INVITE_TTL_MS: u64 = 365 * 24 * 60 * 60 * 1000;
+
+/// Reject lifetimes above [`MAX_INVITE_TTL_MS`].
+pub fn validate_ttl(ttl_ms: u64) -> Result<()> {
+    if ttl_ms > MAX_INVITE_TTL_MS {
+        bail!("invite ttl {ttl_ms}ms exceeds the maximum of {MAX_INVITE_TTL_MS}ms");
+    }
+    Ok(())
+}
+
+/// Reject scopes outside [`SCOPES`].
+pub fn validate_scopes(scopes: &[String]) -> Result<()> {
+    if let Some(unknown) = scopes.iter().find(|s| !SCOPES.contains(&s.as_str())) {
+        bail!(
+            "unknown invite scope {unknown:?} (expected one of: {})",
+            SCOPES.join(", ")
+        );
+    }
+    Ok(())
+}
 
 /// Payload carried inside an invite link.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +61,14 @@ impl InvitePayload {
     }
 }
 
+impl SignedInvite {
+    /// Encode as a base64url invite token.
+    pub fn encode(&self) -> Result<String> {
+        let json = serde_json::to_vec(self).context("failed to serialize invite")?;
+        Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json))
+    }
+}
+
 /// Create a signed invite token.
 pub fn create_invite(
     inviter_node_id: &str,
@@ -40,34 +78,79 @@ pub fn create_invite(
     scopes: Vec<String>,
     ttl_ms: u64,
 ) -> Result<String> {
-    let now_ms = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64;
+    create_signed_invite(
+        inviter_node_id,
+        inviter_public_key_b64,
+        inviter_secret,
+        relay_hosts,
+        scopes,
+        ttl_ms,
+    )?
+    .encode()
+}
+
+/// Create a signed invite, keeping the payload available so the inviter can
+/// record it before handing out the encoded token.
+pub fn create_signed_invite(
+    inviter_node_id: &str,
+    inviter_public_key_b64: &str,
+    inviter_secret: &SecretKey,
+    relay_hosts: Vec<String>,
+    scopes: Vec<String>,
+    ttl_ms: u64,
+) -> Result<SignedInvite> {
+    create_signed_invite_at(
+        inviter_node_id,
+        inviter_public_key_b64,
+        inviter_secret,
+        relay_hosts,
+        scopes,
+        ttl_ms,
+        now_ms(),
+    )
+}
 
+/// Like [`create_signed_invite`], with the expiry measured from `now_ms`
+/// rather than the system clock.
+pub fn create_signed_invite_at(
+    inviter_node_id: &str,
+    inviter_public_key_b64: &str,
+    inviter_secret: &SecretKey,
+    relay_hosts: Vec<String>,
+    scopes: Vec<String>,
+    ttl_ms: u64,
+    now_ms: u64,
+) -> Result<SignedInvite> {
+    validate_ttl(ttl_ms)?;
+    let expires_at_ms = now_ms
+        .checked_add(ttl_ms)
+        .context("invite expiry overflows")?;
     let payload = InvitePayload {
         token_id: uuid::Uuid::new_v4().to_string(),
         inviter_node_id: inviter_node_id.to_string(),
         inviter_public_key_b64: inviter_public_key_b64.to_string(),
         relay_hosts,
         scopes,
-        expires_at_ms: now_ms + ttl_ms,
+        expires_at_ms,
     };
 
     let canonical = payload.canonical_bytes();
     let signature_b64 = sign_payload(inviter_secret, &canonical)?;
 
-    let signed = SignedInvite {
+    Ok(SignedInvite {
         payload,
         signature_b64,
-    };
-
-    let json = serde_json::to_vec(&signed).context("failed to serialize invite")?;
-    Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json))
+    })
 }
 
 /// Decode and verify a signed invite token. Returns the payload if valid.
 pub fn accept_invite(token: &str) -> Result<InvitePayload> {
+    accept_invite_at(token, now_ms())
+}
+
+/// Like [`accept_invite`], checking expiry against `now_ms` rather than the
+/// system clock.
+pub fn accept_invite_at(token: &str, now_ms: u64) -> Result<InvitePayload> {
     let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
         .decode(token)
         .context("invite token is not valid base64url")?;
@@ -75,10 +158,6 @@ pub fn accept_invite(token: &str) -> Result<InvitePayload> {
         serde_json::from_slice(&json).context("invite token is not valid JSON")?;
 
     // Check expiry
-    let now_ms = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64;
     if now_ms > signed.payload.expires_at_ms {
         bail!("invite token has expired");
     }
@@ -96,6 +175,140 @@ pub fn accept_invite(token: &str) -> Result<InvitePayload> {
     Ok(signed.payload)
 }
 
+/// A node that redeemed an issued invite.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InviteUse {
+    pub node_id: String,
+    pub redeemed_at_ms: u64,
+}
+
+/// An invite this node has handed out.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IssuedInvite {
+    pub token_id: String,
+    pub created_at_ms: u64,
+    pub expires_at_ms: u64,
+    /// Maximum number of distinct nodes that may redeem it (`None` = unlimited).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+    #[serde(default)]
+    pub uses: Vec<InviteUse>,
+    #[serde(default)]
+    pub revoked: bool,
+    /// Scopes granted to nodes that redeem it. Empty means unrestricted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+}
+
+impl IssuedInvite {
+    /// Whether the invite has been redeemed as many times as allowed.
+    pub fn is_exhausted(&self) -> bool {
+        self.max_uses
+            .is_some_and(|max| self.uses.len() >= max as usize)
+    }
+}
+
+/// Persistent record of issued invites, consulted before auto-following a
+/// node that redeems one.
+pub struct InviteStore {
+    path: PathBuf,
+    invites: Vec<IssuedInvite>,
+}
+
+impl InviteStore {
+    /// Load from disk, or create empty.
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        let path = state_dir.join(INVITES_FILE);
+        let invites = state_file::read(&path, |data| {
+            serde_json::from_str(data).context("invalid invites.json")
+        })?
+        .unwrap_or_default();
+        Ok(Self { path, invites })
+    }
+
+    /// Create an empty store that persists to `state_dir` on first write.
+    pub fn empty(state_dir: &Path) -> Self {
+        Self {
+            path: state_dir.join(INVITES_FILE),
+            invites: Vec::new(),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let data = serde_json::to_string_pretty(&self.invites)?;
+        state_file::write(&self.path, data)
+    }
+
+    /// Record a newly issued invite.
+    pub fn record(
+        &mut self,
+        payload: &InvitePayload,
+        created_at_ms: u64,
+        max_uses: Option<u32>,
+    ) -> Result<()> {
+        if max_uses == Some(0) {
+            bail!("max_uses must be at least 1");
+        }
+        self.invites.push(IssuedInvite {
+            token_id: payload.token_id.clone(),
+            created_at_ms,
+            expires_at_ms: payload.expires_at_ms,
+            max_uses,
+            uses: Vec::new(),
+            revoked: false,
+            scopes: payload.scopes.clone(),
+        });
+        self.save()
+    }
+
+    /// Revoke an invite so it can no longer be redeemed. Returns false if
+    /// the invite is unknown.
+    pub fn revoke(&mut self, token_id: &str) -> Result<bool> {
+        let Some(invite) = self.invites.iter_mut().find(|i| i.token_id == token_id) else {
+            return Ok(false);
+        };
+        invite.revoked = true;
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Count a redemption by `node_id`, failing if the invite is unknown,
+    /// revoked, expired or used up. Redeeming twice from the same node is
+    /// not counted again.
+    pub fn redeem(&mut self, token_id: &str, node_id: &str, now_ms: u64) -> Result<()> {
+        let Some(invite) = self.invites.iter_mut().find(|i| i.token_id == token_id) else {
+            bail!("unknown invite");
+        };
+        if invite.revoked {
+            bail!("invite has been revoked");
+        }
+        if now_ms > invite.expires_at_ms {
+            bail!("invite token has expired");
+        }
+        if invite.uses.iter().any(|u| u.node_id == node_id) {
+            return Ok(());
+        }
+        if invite.is_exhausted() {
+            bail!("invite has no uses left");
+        }
+        invite.uses.push(InviteUse {
+            node_id: node_id.to_string(),
+            redeemed_at_ms: now_ms,
+        });
+        self.save()
+    }
+
+    /// Get an issued invite by token id.
+    pub fn get(&self, token_id: &str) -> Option<&IssuedInvite> {
+        self.invites.iter().find(|i| i.token_id == token_id)
+    }
+
+    /// All issued invites, oldest first.
+    pub fn list(&self) -> &[IssuedInvite] {
+        &self.invites
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,6 +343,50 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("expired"));
     }
 
+    #[test]
+    fn invite_expiry_uses_supplied_clock() {
+        let secret = SecretKey::random(&mut OsRng);
+        let public = secret.public_key();
+        let pub_b64 = base64::engine::general_purpose::STANDARD.encode(public.to_sec1_bytes());
+        let node_id = evm_address_from_public_key(&public);
+
+        let signed =
+            create_signed_invite_at(&node_id, &pub_b64, &secret, vec![], vec![], 60_000, 1_000)
+                .unwrap();
+        assert_eq!(signed.payload.expires_at_ms, 61_000);
+        let token = signed.encode().unwrap();
+
+        assert!(accept_invite_at(&token, 61_000).is_ok());
+        let err = accept_invite_at(&token, 61_001).unwrap_err();
+        assert!(err.to_string().contains("expired"));
+    }
+
+    #[test]
+    fn oversized_ttl_rejected() {
+        let secret = SecretKey::random(&mut OsRng);
+        let public = secret.public_key();
+        let pub_b64 = base64::engine::general_purpose::STANDARD.encode(public.to_sec1_bytes());
+        let node_id = evm_address_from_public_key(&public);
+
+        for ttl_ms in [MAX_INVITE_TTL_MS + 1, u64::MAX] {
+            let err =
+                create_signed_invite_at(&node_id, &pub_b64, &secret, vec![], vec![], ttl_ms, 1_000)
+                    .unwrap_err();
+            assert!(err.to_string().contains("exceeds the maximum"));
+        }
+        let err = create_signed_invite_at(
+            &node_id,
+            &pub_b64,
+            &secret,
+            vec![],
+            vec![],
+            MAX_INVITE_TTL_MS,
+            u64::MAX,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("overflows"));
+    }
+
     #[test]
     fn tampered_invite_rejected() {
         let secret = SecretKey::random(&mut OsRng);
@@ -153,8 +410,104 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("signature"));
     }
 
+    #[test]
+    fn scopes_are_signed_and_validated() {
+        let secret = SecretKey::random(&mut OsRng);
+        let public = secret.public_key();
+        let pub_b64 = base64::engine::general_purpose::STANDARD.encode(public.to_sec1_bytes());
+        let node_id = evm_address_from_public_key(&public);
+
+        assert!(validate_scopes(&["dm".to_string(), "feed".to_string()]).is_ok());
+        let err = validate_scopes(&["dm".to_string(), "admin".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("admin"));
+
+        let signed = create_signed_invite(
+            &node_id,
+            &pub_b64,
+            &secret,
+            vec![],
+            vec!["dm".to_string()],
+            60_000,
+        )
+        .unwrap();
+        let mut tampered = signed.clone();
+        tampered.payload.scopes.push("feed".to_string());
+        let token = tampered.encode().unwrap();
+        assert!(
+            accept_invite(&token)
+                .unwrap_err()
+                .to_string()
+                .contains("signature")
+        );
+        assert_eq!(
+            accept_invite(&signed.encode().unwrap()).unwrap().scopes,
+            ["dm"]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = InviteStore::load(dir.path()).unwrap();
+        store.record(&signed.payload, 0, None).unwrap();
+        let reloaded = InviteStore::load(dir.path()).unwrap();
+        assert_eq!(
+            reloaded.get(&signed.payload.token_id).unwrap().scopes,
+            ["dm"]
+        );
+    }
+
     #[test]
     fn malformed_token_rejected() {
         assert!(accept_invite("not-a-valid-token!!!").is_err());
     }
+
+    fn issue(store: &mut InviteStore, max_uses: Option<u32>) -> String {
+        let secret = SecretKey::random(&mut OsRng);
+        let public = secret.public_key();
+        let pub_b64 = base64::engine::general_purpose::STANDARD.encode(public.to_sec1_bytes());
+        let node_id = evm_address_from_public_key(&public);
+        let signed =
+            create_signed_invite(&node_id, &pub_b64, &secret, vec![], vec![], 60_000).unwrap();
+        store.record(&signed.payload, 0, max_uses).unwrap();
+        signed.payload.token_id
+    }
+
+    #[test]
+    fn single_use_invite_redeems_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = InviteStore::load(dir.path()).unwrap();
+        let token_id = issue(&mut store, Some(1));
+
+        store.redeem(&token_id, "0xa", 1).unwrap();
+        // The same node redeeming again is not a new use.
+        store.redeem(&token_id, "0xa", 2).unwrap();
+        let err = store.redeem(&token_id, "0xb", 3).unwrap_err();
+        assert!(err.to_string().contains("no uses left"));
+        assert!(store.get(&token_id).unwrap().is_exhausted());
+    }
+
+    #[test]
+    fn revoked_invite_cannot_be_redeemed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = InviteStore::load(dir.path()).unwrap();
+        let token_id = issue(&mut store, None);
+
+        assert!(store.revoke(&token_id).unwrap());
+        assert!(!store.revoke("unknown").unwrap());
+        let err = store.redeem(&token_id, "0xa", 1).unwrap_err();
+        assert!(err.to_string().contains("revoked"));
+
+        let reloaded = InviteStore::load(dir.path()).unwrap();
+        assert!(reloaded.get(&token_id).unwrap().revoked);
+    }
+
+    #[test]
+    fn redeem_rejects_unknown_and_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = InviteStore::load(dir.path()).unwrap();
+        let token_id = issue(&mut store, None);
+
+        assert!(store.redeem("unknown", "0xa", 1).is_err());
+        let expires = store.get(&token_id).unwrap().expires_at_ms;
+        let err = store.redeem(&token_id, "0xa", expires + 1).unwrap_err();
+        assert!(err.to_string().contains("expired"));
+    }
 }
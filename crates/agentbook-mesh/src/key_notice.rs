@@ -0,0 +1,151 @@
+//! Key rotation and revocation notices exchanged between followers.
+//!
+//! Notices travel in the `ciphertext_b64` field of an envelope as base64 JSON.
+//! They are not secret; authenticity comes from the envelope signature, which
+//! must be made with the key being rotated away from (or revoked).
+
+use crate::crypto::{evm_address_from_public_key, verify_signature};
+use crate::identity::NodeIdentity;
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use k256::PublicKey;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Announces that `old_node_id` has moved to a new keypair.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyRotationNotice {
+    pub old_node_id: String,
+    pub new_node_id: String,
+    pub new_public_key_b64: String,
+    pub issued_at_ms: u64,
+    /// Signature by the new key over the rotation binding, proving possession.
+    pub new_key_signature_b64: String,
+}
+
+/// Announces that a node's key is compromised and must no longer be trusted.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyRevocationNotice {
+    pub node_id: String,
+    pub issued_at_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl KeyRotationNotice {
+    /// Create a rotation notice from `old_node_id` to `new_identity`.
+    ///
+    /// The caller still has to sign the encoded notice with the old key when
+    /// placing it in an envelope.
+    pub fn create(
+        old_node_id: &str,
+        new_identity: &NodeIdentity,
+        issued_at_ms: u64,
+    ) -> Result<Self> {
+        let binding = rotation_binding(old_node_id, &new_identity.node_id, issued_at_ms);
+        Ok(Self {
+            old_node_id: old_node_id.to_string(),
+            new_node_id: new_identity.node_id.clone(),
+            new_public_key_b64: new_identity.public_key_b64.clone(),
+            issued_at_ms,
+            new_key_signature_b64: new_identity.sign(&binding)?,
+        })
+    }
+
+    /// Check that the new key matches `new_node_id` and signed the binding.
+    pub fn verify(&self) -> Result<()> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(&self.new_public_key_b64)
+            .context("invalid new public key encoding")?;
+        let public_key = PublicKey::from_sec1_bytes(&bytes).context("invalid new public key")?;
+        if evm_address_from_public_key(&public_key) != self.new_node_id {
+            bail!("new node id does not match new public key");
+        }
+        if self.new_node_id == self.old_node_id {
+            bail!("rotation must move to a different key");
+        }
+        let binding = rotation_binding(&self.old_node_id, &self.new_node_id, self.issued_at_ms);
+        if !verify_signature(
+            &self.new_public_key_b64,
+            &binding,
+            &self.new_key_signature_b64,
+        ) {
+            bail!("new key signature is invalid");
+        }
+        Ok(())
+    }
+}
+
+fn rotation_binding(old_node_id: &str, new_node_id: &str, issued_at_ms: u64) -> Vec<u8> {
+    format!("agentbook-key-rotation-v1:{old_node_id}:{new_node_id}:{issued_at_ms}").into_bytes()
+}
+
+/// Encode a notice for the envelope `ciphertext_b64` field.
+pub fn encode_notice<T: Serialize>(notice: &T) -> Result<String> {
+    let json = serde_json::to_vec(notice)?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(json))
+}
+
+/// Decode a notice from the envelope `ciphertext_b64` field.
+pub fn decode_notice<T: DeserializeOwned>(payload_b64: &str) -> Result<T> {
+    let json = base64::engine::general_purpose::STANDARD
+        .decode(payload_b64)
+        .context("invalid notice encoding")?;
+    serde_json::from_slice(&json).context("invalid notice payload")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::random_key_material;
+
+    fn make_identity() -> (NodeIdentity, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let id = NodeIdentity::load_or_create(dir.path(), &random_key_material()).unwrap();
+        (id, dir)
+    }
+
+    #[test]
+    fn rotation_notice_round_trip_verifies() {
+        let (old, _d1) = make_identity();
+        let (new, _d2) = make_identity();
+
+        let notice = KeyRotationNotice::create(&old.node_id, &new, 1000).unwrap();
+        let decoded: KeyRotationNotice = decode_notice(&encode_notice(&notice).unwrap()).unwrap();
+        assert_eq!(decoded, notice);
+        decoded.verify().unwrap();
+    }
+
+    #[test]
+    fn rotation_notice_rejects_tampered_target() {
+        let (old, _d1) = make_identity();
+        let (new, _d2) = make_identity();
+        let (attacker, _d3) = make_identity();
+
+        let mut notice = KeyRotationNotice::create(&old.node_id, &new, 1000).unwrap();
+        notice.new_node_id = attacker.node_id.clone();
+        notice.new_public_key_b64 = attacker.public_key_b64.clone();
+        assert!(notice.verify().is_err());
+    }
+
+    #[test]
+    fn rotation_notice_rejects_mismatched_node_id() {
+        let (old, _d1) = make_identity();
+        let (new, _d2) = make_identity();
+
+        let mut notice = KeyRotationNotice::create(&old.node_id, &new, 1000).unwrap();
+        notice.new_public_key_b64 = old.public_key_b64.clone();
+        assert!(notice.verify().is_err());
+    }
+
+    #[test]
+    fn revocation_notice_round_trip() {
+        let notice = KeyRevocationNotice {
+            node_id: "0xabc".to_string(),
+            issued_at_ms: 5,
+            reason: Some("laptop stolen".to_string()),
+        };
+        let decoded: KeyRevocationNotice = decode_notice(&encode_notice(&notice).unwrap()).unwrap();
+        assert_eq!(decoded, notice);
+    }
+}
@@ -1,11 +1,24 @@
+pub mod at_rest;
+pub mod attachment;
 pub mod crypto;
+pub mod dm_payload;
+pub mod envelope_schema;
 pub mod follow;
 pub mod identity;
 pub mod inbox;
+#[cfg(feature = "sqlite")]
+pub mod inbox_sqlite;
 pub mod ingress;
+pub mod ingress_limits;
 pub mod invite;
+pub mod key_notice;
+pub mod outbox;
+pub mod padding;
+pub mod proxy;
 pub mod recovery;
+pub mod relay_directory;
 pub mod state_dir;
+pub mod state_file;
 pub mod transport;
 
 /// Re-export the shared rate limiter from `agentbook-crypto`.
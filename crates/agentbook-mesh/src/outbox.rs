@@ -0,0 +1,299 @@
+use crate::state_file;
+use agentbook_proto::mesh::v1 as mesh_pb;
+use anyhow::{Context, Result};
+use base64::Engine;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const OUTBOX_FILE: &str = "outbox.json";
+
+/// Default maximum number of undelivered envelopes kept in the outbox.
+pub const DEFAULT_MAX_OUTBOX_SIZE: usize = 1_000;
+
+/// Delivery attempts before an entry is given up on and dropped.
+pub const MAX_DELIVERY_ATTEMPTS: u32 = 10;
+
+/// First retry delay; doubles on every failed attempt.
+const BASE_BACKOFF_MS: u64 = 2_000;
+
+/// Upper bound on the retry delay.
+const MAX_BACKOFF_MS: u64 = 5 * 60 * 1_000;
+
+/// An envelope waiting to be (re)sent via the relay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub message_id: String,
+    pub to_node_id: String,
+    /// Protobuf-encoded `Envelope`, base64. The envelope is already encrypted
+    /// and signed, so retries resend it byte-for-byte.
+    envelope_b64: String,
+    pub attempts: u32,
+    pub created_at_ms: u64,
+    pub next_attempt_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl OutboxEntry {
+    /// Decode the stored envelope.
+    pub fn envelope(&self) -> Result<mesh_pb::Envelope> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(&self.envelope_b64)
+            .context("invalid outbox envelope encoding")?;
+        mesh_pb::Envelope::decode(bytes.as_slice()).context("invalid outbox envelope")
+    }
+}
+
+/// Retry delay after `attempts` failed deliveries (exponential, capped).
+pub fn backoff_ms(attempts: u32) -> u64 {
+    let shift = attempts.saturating_sub(1).min(16);
+    BASE_BACKOFF_MS
+        .saturating_mul(1 << shift)
+        .min(MAX_BACKOFF_MS)
+}
+
+/// Persistent queue of outbound envelopes that could not be delivered yet.
+///
+/// Entries for the same peer are delivered strictly in the order they were
+/// queued: only the oldest entry per peer is ever handed out for retry.
+pub struct NodeOutbox {
+    path: PathBuf,
+    entries: Vec<OutboxEntry>,
+    max_size: usize,
+}
+
+impl NodeOutbox {
+    /// Load from disk, or create empty.
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        let path = state_dir.join(OUTBOX_FILE);
+        let entries = state_file::read(&path, |data| {
+            serde_json::from_str(data).context("invalid outbox.json")
+        })?
+        .unwrap_or_default();
+        Ok(Self {
+            path,
+            entries,
+            max_size: DEFAULT_MAX_OUTBOX_SIZE,
+        })
+    }
+
+    /// Create an empty outbox that persists to `state_dir` on first write.
+    pub fn empty(state_dir: &Path) -> Self {
+        Self {
+            path: state_dir.join(OUTBOX_FILE),
+            entries: Vec::new(),
+            max_size: DEFAULT_MAX_OUTBOX_SIZE,
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let data = serde_json::to_string_pretty(&self.entries)?;
+        state_file::write(&self.path, data)
+    }
+
+    /// Queue an envelope for retry. The first attempt is scheduled after the
+    /// base backoff, since the caller has usually just failed to send it.
+    pub fn enqueue(
+        &mut self,
+        envelope: &mesh_pb::Envelope,
+        now_ms: u64,
+        last_error: Option<String>,
+    ) -> Result<()> {
+        if self.entries.len() >= self.max_size {
+            anyhow::bail!("outbox is full ({} undelivered messages)", self.max_size);
+        }
+        self.entries.push(OutboxEntry {
+            message_id: envelope.message_id.clone(),
+            to_node_id: envelope.to_node_id.clone(),
+            envelope_b64: base64::engine::general_purpose::STANDARD
+                .encode(envelope.encode_to_vec()),
+            attempts: 0,
+            created_at_ms: now_ms,
+            next_attempt_ms: now_ms + backoff_ms(1),
+            last_error,
+        });
+        self.save()
+    }
+
+    /// Whether any envelope to `to_node_id` is still waiting. New messages
+    /// to that peer must queue behind it to preserve ordering.
+    pub fn has_pending_for(&self, to_node_id: &str) -> bool {
+        self.entries.iter().any(|e| e.to_node_id == to_node_id)
+    }
+
+    /// Entries ready for a retry: the oldest entry per peer, if it is due.
+    pub fn due(&self, now_ms: u64) -> Vec<OutboxEntry> {
+        let mut seen = std::collections::HashSet::new();
+        self.entries
+            .iter()
+            .filter(|e| seen.insert(e.to_node_id.as_str()))
+            .filter(|e| e.next_attempt_ms <= now_ms)
+            .cloned()
+            .collect()
+    }
+
+    /// Remove an entry after successful delivery.
+    pub fn mark_sent(&mut self, message_id: &str) -> Result<bool> {
+        self.remove(message_id)
+    }
+
+    /// Record a failed attempt and schedule the next one. Returns the entry
+    /// if it has now exhausted its attempts and was dropped.
+    pub fn mark_failed(
+        &mut self,
+        message_id: &str,
+        now_ms: u64,
+        error: &str,
+    ) -> Result<Option<OutboxEntry>> {
+        let Some(idx) = self.entries.iter().position(|e| e.message_id == message_id) else {
+            return Ok(None);
+        };
+        let entry = &mut self.entries[idx];
+        entry.attempts += 1;
+        entry.last_error = Some(error.to_string());
+        let dropped = if entry.attempts >= MAX_DELIVERY_ATTEMPTS {
+            Some(self.entries.remove(idx))
+        } else {
+            entry.next_attempt_ms = now_ms + backoff_ms(entry.attempts + 1);
+            None
+        };
+        self.save()?;
+        Ok(dropped)
+    }
+
+    /// Cancel a queued message. Returns false if it was not queued.
+    pub fn cancel(&mut self, message_id: &str) -> Result<bool> {
+        self.remove(message_id)
+    }
+
+    /// All queued entries, oldest first.
+    pub fn list(&self) -> &[OutboxEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn remove(&mut self, message_id: &str) -> Result<bool> {
+        let before = self.entries.len();
+        self.entries.retain(|e| e.message_id != message_id);
+        if self.entries.len() == before {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope(message_id: &str, to: &str) -> mesh_pb::Envelope {
+        mesh_pb::Envelope {
+            message_id: message_id.to_string(),
+            from_node_id: "0xme".to_string(),
+            to_node_id: to.to_string(),
+            timestamp_ms: 1000,
+            nonce_b64: "nonce".to_string(),
+            ciphertext_b64: "ct".to_string(),
+            signature_b64: "sig".to_string(),
+            from_public_key_b64: "pk".to_string(),
+            topic: None,
+            message_type: mesh_pb::MessageType::DmText as i32,
+            sealed: false,
+            schema_version: crate::envelope_schema::CURRENT,
+        }
+    }
+
+    #[test]
+    fn enqueue_persists_and_round_trips_envelope() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut outbox = NodeOutbox::load(dir.path()).unwrap();
+        outbox.enqueue(&envelope("m1", "0xbob"), 0, None).unwrap();
+
+        let reloaded = NodeOutbox::load(dir.path()).unwrap();
+        assert_eq!(reloaded.len(), 1);
+        let env = reloaded.list()[0].envelope().unwrap();
+        assert_eq!(env, envelope("m1", "0xbob"));
+    }
+
+    #[test]
+    fn due_returns_only_head_of_line_per_peer() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut outbox = NodeOutbox::load(dir.path()).unwrap();
+        outbox.enqueue(&envelope("m1", "0xbob"), 0, None).unwrap();
+        outbox.enqueue(&envelope("m2", "0xbob"), 0, None).unwrap();
+        outbox.enqueue(&envelope("m3", "0xcarol"), 0, None).unwrap();
+
+        assert!(outbox.due(0).is_empty(), "first retry waits for backoff");
+
+        let due: Vec<String> = outbox
+            .due(backoff_ms(1))
+            .into_iter()
+            .map(|e| e.message_id)
+            .collect();
+        assert_eq!(due, vec!["m1", "m3"]);
+
+        outbox.mark_sent("m1").unwrap();
+        let due: Vec<String> = outbox
+            .due(backoff_ms(1))
+            .into_iter()
+            .map(|e| e.message_id)
+            .collect();
+        assert_eq!(due, vec!["m2", "m3"]);
+    }
+
+    #[test]
+    fn failed_attempts_back_off_and_eventually_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut outbox = NodeOutbox::load(dir.path()).unwrap();
+        outbox.enqueue(&envelope("m1", "0xbob"), 0, None).unwrap();
+
+        assert!(outbox.mark_failed("m1", 0, "offline").unwrap().is_none());
+        assert_eq!(outbox.list()[0].attempts, 1);
+        assert_eq!(outbox.list()[0].next_attempt_ms, backoff_ms(2));
+        assert_eq!(outbox.list()[0].last_error.as_deref(), Some("offline"));
+
+        for _ in 1..MAX_DELIVERY_ATTEMPTS - 1 {
+            assert!(outbox.mark_failed("m1", 0, "offline").unwrap().is_none());
+        }
+        let dropped = outbox.mark_failed("m1", 0, "offline").unwrap();
+        assert_eq!(dropped.unwrap().attempts, MAX_DELIVERY_ATTEMPTS);
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn backoff_is_exponential_and_capped() {
+        assert_eq!(backoff_ms(1), 2_000);
+        assert_eq!(backoff_ms(2), 4_000);
+        assert_eq!(backoff_ms(3), 8_000);
+        assert_eq!(backoff_ms(30), MAX_BACKOFF_MS);
+    }
+
+    #[test]
+    fn cancel_removes_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut outbox = NodeOutbox::load(dir.path()).unwrap();
+        outbox.enqueue(&envelope("m1", "0xbob"), 0, None).unwrap();
+        assert!(outbox.has_pending_for("0xbob"));
+        assert!(outbox.cancel("m1").unwrap());
+        assert!(!outbox.cancel("m1").unwrap());
+        assert!(!outbox.has_pending_for("0xbob"));
+    }
+
+    #[test]
+    fn enqueue_rejects_when_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut outbox = NodeOutbox::load(dir.path()).unwrap();
+        outbox.max_size = 1;
+        outbox.enqueue(&envelope("m1", "0xbob"), 0, None).unwrap();
+        assert!(outbox.enqueue(&envelope("m2", "0xbob"), 0, None).is_err());
+    }
+}
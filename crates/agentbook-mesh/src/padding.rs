@@ -0,0 +1,84 @@
+//! Fixed-size bucket padding for relay privacy mode.
+//!
+//! Plaintext is length-prefixed and zero-padded up to the next bucket size
+//! before encryption, so the relay cannot infer message length beyond the
+//! bucket it falls into.
+
+use anyhow::Result;
+
+/// Padded payload sizes in bytes (including the 4-byte length prefix).
+/// Payloads larger than the last bucket are rounded up to a multiple of it.
+pub const PADDING_BUCKETS: [usize; 5] = [256, 1024, 4096, 16384, 65536];
+
+/// Size of the big-endian length prefix.
+const LEN_PREFIX: usize = 4;
+
+/// Pick the padded size for a payload of `len` bytes.
+pub fn bucket_for(len: usize) -> usize {
+    let needed = len + LEN_PREFIX;
+    if let Some(bucket) = PADDING_BUCKETS.iter().find(|b| **b >= needed) {
+        return *bucket;
+    }
+    let largest = PADDING_BUCKETS[PADDING_BUCKETS.len() - 1];
+    needed.div_ceil(largest) * largest
+}
+
+/// Length-prefix `data` and pad it with zeros to its bucket size.
+pub fn pad(data: &[u8]) -> Vec<u8> {
+    let len = u32::try_from(data.len()).expect("payload exceeds u32::MAX bytes");
+    let mut out = Vec::with_capacity(bucket_for(data.len()));
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(data);
+    out.resize(bucket_for(data.len()), 0);
+    out
+}
+
+/// Strip padding added by [`pad`].
+pub fn unpad(padded: &[u8]) -> Result<Vec<u8>> {
+    if padded.len() < LEN_PREFIX {
+        anyhow::bail!("padded payload too short");
+    }
+    let mut prefix = [0u8; LEN_PREFIX];
+    prefix.copy_from_slice(&padded[..LEN_PREFIX]);
+    let len = u32::from_be_bytes(prefix) as usize;
+    let end = LEN_PREFIX
+        .checked_add(len)
+        .filter(|end| *end <= padded.len())
+        .ok_or_else(|| anyhow::anyhow!("padded payload length prefix out of range"))?;
+    Ok(padded[LEN_PREFIX..end].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pad_unpad_round_trip() {
+        for data in [&b""[..], b"hi", &[7u8; 300], &[1u8; 70_000]] {
+            let padded = pad(data);
+            assert_eq!(unpad(&padded).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn pad_hides_length_within_bucket() {
+        assert_eq!(pad(b"a").len(), 256);
+        assert_eq!(pad(&[0u8; 200]).len(), 256);
+        assert_eq!(pad(&[0u8; 252]).len(), 256);
+        assert_eq!(pad(&[0u8; 253]).len(), 1024);
+    }
+
+    #[test]
+    fn oversized_payload_rounds_to_largest_bucket() {
+        assert_eq!(bucket_for(65_532), 65_536);
+        assert_eq!(bucket_for(65_533), 131_072);
+    }
+
+    #[test]
+    fn unpad_rejects_bad_prefix() {
+        assert!(unpad(&[0, 0]).is_err());
+        let mut padded = pad(b"hello");
+        padded[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(unpad(&padded).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Relay selection from a published directory: fetch the signed relay list,
+//! probe each relay, and keep the fastest healthy ones alongside any relays
+//! the user pinned.
+
+use crate::crypto::{public_key_matches_node_id, relay_directory_payload, verify_signature};
+use crate::transport::{RelaySecurity, connect_relay, relay_endpoint};
+use agentbook_proto::host::v1 as host_pb;
+use anyhow::{Context, Result, bail};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+/// Oldest directory copy accepted, and how far ahead of our clock one may be.
+pub const MAX_DIRECTORY_AGE_MS: u64 = 10 * 60 * 1000;
+
+/// A relay that doesn't answer a probe within this long is skipped.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Check a directory was signed by `signer` (an address like a node ID)
+/// recently, returning its relays.
+pub fn verify(
+    directory: &host_pb::RelayDirectory,
+    signer: &str,
+    now_ms: u64,
+) -> Result<Vec<String>> {
+    if !public_key_matches_node_id(&directory.public_key_b64, signer) {
+        bail!("relay directory is not signed by {signer}");
+    }
+    let payload = relay_directory_payload(&directory.hosts, directory.issued_at_ms);
+    if !verify_signature(
+        &directory.public_key_b64,
+        &payload,
+        &directory.signature_b64,
+    ) {
+        bail!("relay directory has an invalid signature");
+    }
+    if now_ms.abs_diff(directory.issued_at_ms) > MAX_DIRECTORY_AGE_MS {
+        bail!(
+            "relay directory was issued at {}, too far from now",
+            directory.issued_at_ms
+        );
+    }
+    Ok(directory.hosts.clone())
+}
+
+/// Pinned relays first, in the order given, then up to `count` of the other
+/// relays that answered, fastest first.
+pub fn choose(
+    pinned: &[String],
+    probed: Vec<(String, Option<Duration>)>,
+    count: usize,
+) -> Vec<String> {
+    let mut healthy: Vec<(String, Duration)> = probed
+        .into_iter()
+        .filter(|(host, _)| !pinned.contains(host))
+        .filter_map(|(host, rtt)| rtt.map(|rtt| (host, rtt)))
+        .collect();
+    healthy.sort_by_key(|(_, rtt)| *rtt);
+    pinned
+        .iter()
+        .cloned()
+        .chain(healthy.into_iter().take(count).map(|(host, _)| host))
+        .collect()
+}
+
+/// Fetch and verify the directory published by `directory_host`.
+pub async fn fetch(
+    directory_host: &str,
+    signer: &str,
+    security: &RelaySecurity,
+    now_ms: u64,
+) -> Result<Vec<String>> {
+    let mut client = connect_relay(&relay_endpoint(directory_host), security)
+        .await
+        .with_context(|| format!("failed to reach {directory_host}"))?;
+    let directory = client
+        .get_relay_directory(host_pb::GetRelayDirectoryRequest {})
+        .await
+        .with_context(|| format!("{directory_host} did not return a relay directory"))?
+        .into_inner();
+    verify(&directory, signer, now_ms)
+}
+
+/// Time a connection and a lookup of our own node ID on `host`, or `None`
+/// if it fails or takes longer than [`PROBE_TIMEOUT`].
+pub async fn probe(host: &str, node_id: &str, security: &RelaySecurity) -> Option<Duration> {
+    let started = Instant::now();
+    let lookup = async {
+        let mut client = connect_relay(&relay_endpoint(host), security).await?;
+        client
+            .lookup(host_pb::LookupRequest {
+                node_id: node_id.to_string(),
+            })
+            .await?;
+        anyhow::Ok(())
+    };
+    match tokio::time::timeout(PROBE_TIMEOUT, lookup).await {
+        Ok(Ok(())) => Some(started.elapsed()),
+        Ok(Err(e)) => {
+            tracing::debug!(host, err = %e, "relay probe failed");
+            None
+        }
+        Err(_) => {
+            tracing::debug!(host, "relay probe timed out");
+            None
+        }
+    }
+}
+
+/// Fetch the directory, probe every relay in it concurrently, and
+/// [`choose`] the relays to connect to.
+pub async fn select(
+    directory_host: &str,
+    signer: &str,
+    pinned: &[String],
+    count: usize,
+    node_id: &str,
+    security: &RelaySecurity,
+    now_ms: u64,
+) -> Result<Vec<String>> {
+    let hosts = fetch(directory_host, signer, security, now_ms).await?;
+    let mut probes = JoinSet::new();
+    for host in hosts.into_iter().filter(|h| !pinned.contains(h)) {
+        let node_id = node_id.to_string();
+        let security = security.clone();
+        probes.spawn(async move {
+            let rtt = probe(&host, &node_id, &security).await;
+            (host, rtt)
+        });
+    }
+    let mut probed = Vec::new();
+    while let Some(result) = probes.join_next().await {
+        if let Ok(probe) = result {
+            probed.push(probe);
+        }
+    }
+    Ok(choose(pinned, probed, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{evm_address_from_public_key, sign_payload};
+    use base64::Engine;
+    use k256::SecretKey;
+
+    fn signed(secret: &SecretKey, hosts: &[&str], issued_at_ms: u64) -> host_pb::RelayDirectory {
+        let hosts: Vec<String> = hosts.iter().map(|h| h.to_string()).collect();
+        let payload = relay_directory_payload(&hosts, issued_at_ms);
+        host_pb::RelayDirectory {
+            signature_b64: sign_payload(secret, &payload).unwrap(),
+            hosts,
+            issued_at_ms,
+            public_key_b64: base64::engine::general_purpose::STANDARD
+                .encode(secret.public_key().to_sec1_bytes()),
+        }
+    }
+
+    #[test]
+    fn only_fresh_directories_from_the_pinned_signer_are_accepted() {
+        let secret = SecretKey::random(&mut rand::rngs::OsRng);
+        let signer = evm_address_from_public_key(&secret.public_key());
+        let now = 100 * MAX_DIRECTORY_AGE_MS;
+
+        let directory = signed(&secret, &["a:1", "b:1"], now);
+        assert_eq!(verify(&directory, &signer, now).unwrap(), ["a:1", "b:1"]);
+
+        let other = SecretKey::random(&mut rand::rngs::OsRng);
+        let other_signer = evm_address_from_public_key(&other.public_key());
+        assert!(verify(&directory, &other_signer, now).is_err());
+
+        let mut tampered = directory.clone();
+        tampered.hosts.push("evil:1".into());
+        assert!(verify(&tampered, &signer, now).is_err());
+
+        let stale = signed(&secret, &["a:1"], now - MAX_DIRECTORY_AGE_MS - 1);
+        assert!(verify(&stale, &signer, now).is_err());
+    }
+
+    #[test]
+    fn pinned_relays_come_first_then_the_fastest_healthy_ones() {
+        let ms = Duration::from_millis;
+        let probed = vec![
+            ("slow:1".to_string(), Some(ms(300))),
+            ("down:1".to_string(), None),
+            ("fast:1".to_string(), Some(ms(20))),
+            ("mid:1".to_string(), Some(ms(80))),
+            ("pinned:1".to_string(), Some(ms(1))),
+        ];
+        let chosen = choose(&["pinned:1".to_string()], probed, 2);
+        assert_eq!(chosen, ["pinned:1", "fast:1", "mid:1"]);
+
+        assert!(choose(&[], vec![("down:1".to_string(), None)], 3).is_empty());
+    }
+}
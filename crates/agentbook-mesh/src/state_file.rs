@@ -0,0 +1,205 @@
+//! Crash-safe persistence for state files.
+//!
+//! [`write`] never modifies a state file in place: the new contents go to a
+//! temporary sibling, are fsynced, and are renamed over the old file, so a
+//! crash leaves either the old or the new version. The version being replaced
+//! is kept as `<name>.bak` (a hard link, so it costs no copy), and [`read`]
+//! falls back to it when the current file cannot be parsed.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Where the previous good copy of `path` is kept.
+pub fn backup_path(path: &Path) -> PathBuf {
+    sibling(path, "", ".bak")
+}
+
+fn sibling(path: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{prefix}{name}{suffix}"))
+}
+
+/// Atomically replace `path` with `data`, keeping the old version as the
+/// backup.
+pub fn write(path: &Path, data: impl AsRef<[u8]>) -> Result<()> {
+    let tmp = sibling(path, ".", ".tmp");
+    let result = write_and_swap(path, &tmp, data.as_ref());
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result.with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn write_and_swap(path: &Path, tmp: &Path, data: &[u8]) -> std::io::Result<()> {
+    // A temporary file left by a crash may have a looser mode; start afresh.
+    let _ = fs::remove_file(tmp);
+    let mut file = create_private(tmp)?;
+    if let Ok(meta) = fs::metadata(path) {
+        // Keep the old file's mode.
+        file.set_permissions(meta.permissions())?;
+    }
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+
+    if path.exists() {
+        let backup = backup_path(path);
+        let backup_tmp = sibling(path, ".", ".bak.tmp");
+        let _ = fs::remove_file(&backup_tmp);
+        if fs::hard_link(path, &backup_tmp).is_err() {
+            fs::copy(path, &backup_tmp)?;
+        }
+        fs::rename(&backup_tmp, &backup)?;
+    }
+    fs::rename(tmp, path)?;
+    sync_dir(path);
+    Ok(())
+}
+
+/// Create `path` readable by the owner only, so secrets never sit in a
+/// world-readable file, even briefly.
+fn create_private(path: &Path) -> std::io::Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// Make the rename itself durable. Best-effort: not every platform or
+/// filesystem lets a directory be opened and synced.
+fn sync_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent()
+        && let Ok(dir) = File::open(if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        })
+    {
+        let _ = dir.sync_all();
+    }
+}
+
+/// Delete the backup of `path`, e.g. after re-encrypting it so no copy under
+/// the old key (or in plaintext) is left behind.
+pub fn remove_backup(path: &Path) -> Result<()> {
+    let backup = backup_path(path);
+    match fs::remove_file(&backup) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("failed to remove {}", backup.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Read and parse `path`; `Ok(None)` if it does not exist. If the file is
+/// there but unreadable or fails `parse` (e.g. it was truncated by a crash
+/// under an older version), the backup is tried before giving up.
+pub fn read<T>(path: &Path, parse: impl Fn(&str) -> Result<T>) -> Result<Option<T>> {
+    let err = match fs::read_to_string(path) {
+        Ok(data) => match parse(&data) {
+            Ok(value) => return Ok(Some(value)),
+            Err(e) => e,
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => anyhow::Error::new(e).context(format!("failed to read {}", path.display())),
+    };
+
+    let backup = backup_path(path);
+    match fs::read_to_string(&backup)
+        .map_err(anyhow::Error::new)
+        .and_then(|d| parse(&d))
+    {
+        Ok(value) => {
+            tracing::warn!(
+                path = %path.display(),
+                err = %format!("{err:#}"),
+                "state file unreadable, restored the previous copy"
+            );
+            Ok(Some(value))
+        }
+        Err(_) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_list(data: &str) -> Result<Vec<u32>> {
+        Ok(serde_json::from_str(data)?)
+    }
+
+    #[test]
+    fn write_replaces_and_keeps_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        assert_eq!(read(&path, parse_list).unwrap(), None);
+
+        write(&path, "[1]").unwrap();
+        assert!(!backup_path(&path).exists());
+        write(&path, "[1,2]").unwrap();
+
+        assert_eq!(read(&path, parse_list).unwrap(), Some(vec![1, 2]));
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "[1]");
+        // No temporary files are left behind.
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(names.len(), 2, "{names:?}");
+    }
+
+    #[test]
+    fn corrupt_file_falls_back_to_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        write(&path, "[1]").unwrap();
+        write(&path, "[1,2]").unwrap();
+
+        // A torn in-place write from an older version.
+        fs::write(&path, "[1,").unwrap();
+        assert_eq!(read(&path, parse_list).unwrap(), Some(vec![1]));
+
+        fs::remove_file(backup_path(&path)).unwrap();
+        assert!(read(&path, parse_list).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_keeps_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secret.json");
+        fs::write(&path, "[]").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        write(&path, "[1]").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn new_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fresh.json");
+        // A stale, world-readable temporary file from a crashed write.
+        let tmp = sibling(&path, ".", ".tmp");
+        fs::write(&tmp, "[9]").unwrap();
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write(&path, "[1]").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(read(&path, parse_list).unwrap(), Some(vec![1]));
+    }
+}
@@ -1,9 +1,39 @@
+use crate::envelope_schema::{self, PeerSchemas};
+use crate::proxy::{Proxy, ProxyConnector};
+use agentbook_crypto::crypto::{relay_registration_payload, sign_payload};
 use agentbook_proto::host::v1 as host_pb;
 use agentbook_proto::host::v1::host_service_client::HostServiceClient;
 use agentbook_proto::mesh::v1 as mesh_pb;
 use anyhow::{Context, Result};
+use k256::SecretKey;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_stream::StreamExt;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
+
+/// How long [`MeshTransport::send_via_relay`] waits for a relay to accept an
+/// envelope before trying the next relay.
+const SEND_ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether a relay accepted an envelope, or the relay's reason for refusing it.
+type SendResult = std::result::Result<(), String>;
+
+/// An envelope handed to a relay session. `sent` is answered once the relay
+/// acks or refuses the envelope (or, for relays that don't ack, once it is
+/// written to the relay stream). Dropping it unanswered tells the caller the
+/// envelope did not go out.
+struct Outbound {
+    envelope: mesh_pb::Envelope,
+    sent: oneshot::Sender<SendResult>,
+}
+
+/// A frame for the relay stream, with the notification to answer once it is
+/// written, if any.
+type StreamFrame = (host_pb::NodeFrame, Option<oneshot::Sender<SendResult>>);
 
 /// Configuration for a relay connection.
 pub struct RelayConfig {
@@ -13,17 +43,53 @@ pub struct RelayConfig {
     pub signature_b64: String,
     pub reconnect_interval: Duration,
     pub ping_interval: Duration,
+    pub security: RelaySecurity,
+    /// Shared across relays; updated from every delivery.
+    pub peer_schemas: Arc<PeerSchemas>,
+}
+
+/// TLS, proxy and registration options applied to every relay connection.
+#[derive(Clone, Default)]
+pub struct RelaySecurity {
+    /// Extra CA certificate (PEM) to trust, e.g. for a relay with a private CA.
+    pub ca_cert_pem: Option<Vec<u8>>,
+    /// Client certificate and key (PEM) presented to relays requiring mutual TLS.
+    pub client_identity: Option<(Vec<u8>, Vec<u8>)>,
+    /// When set, each registration is signed over a fresh timestamp so the
+    /// relay can reject replays. Otherwise the static node_id signature is sent.
+    pub signing_key: Option<SecretKey>,
+    /// Egress proxy to reach relays through (see [`crate::proxy`]).
+    pub proxy: Option<Arc<Proxy>>,
+}
+
+/// A relay session that failed; its loop reconnects after
+/// [`RelayConfig::reconnect_interval`].
+#[derive(Debug, Clone)]
+pub struct RelayReconnect {
+    pub host: String,
+    pub error: String,
 }
 
 /// MeshTransport manages relay connections and message routing.
 /// Incoming deliveries from all relays are forwarded to a shared channel.
 pub struct MeshTransport {
     /// Senders for outbound envelopes, one per relay.
-    senders: Vec<mpsc::Sender<mesh_pb::Envelope>>,
+    senders: Vec<mpsc::Sender<Outbound>>,
     /// Senders for control frames (room subscribe/unsubscribe), one per relay.
     control_senders: Vec<mpsc::Sender<host_pb::NodeFrame>>,
     /// Receiver for incoming envelopes from all relays.
     pub incoming: tokio::sync::Mutex<mpsc::Receiver<mesh_pb::Envelope>>,
+    /// Number of relay sessions currently registered.
+    connected: Arc<AtomicUsize>,
+    /// Announces each failed relay session.
+    reconnects: broadcast::Sender<RelayReconnect>,
+    /// Options the relay sessions were started with, for unary relay RPCs.
+    security: RelaySecurity,
+    /// The envelope schema each peer last sent, for downgrading replies.
+    peer_schemas: Arc<PeerSchemas>,
+    /// When set, outbound DMs and feed posts are sealed (padded, type hidden)
+    /// so the relay only learns routing node IDs.
+    privacy_mode: bool,
 }
 
 impl MeshTransport {
@@ -35,14 +101,35 @@ impl MeshTransport {
         node_id: String,
         public_key_b64: String,
         signature_b64: String,
+    ) -> Self {
+        Self::with_security(
+            relay_hosts,
+            node_id,
+            public_key_b64,
+            signature_b64,
+            RelaySecurity::default(),
+        )
+    }
+
+    /// Like [`MeshTransport::new`], with custom TLS trust, a client
+    /// certificate, and/or timestamped registration signing.
+    pub fn with_security(
+        relay_hosts: Vec<String>,
+        node_id: String,
+        public_key_b64: String,
+        signature_b64: String,
+        security: RelaySecurity,
     ) -> Self {
         let (delivery_tx, delivery_rx) = mpsc::channel::<mesh_pb::Envelope>(256);
 
         let mut senders = Vec::new();
         let mut control_senders = Vec::new();
+        let connected = Arc::new(AtomicUsize::new(0));
+        let (reconnects, _) = broadcast::channel(64);
+        let peer_schemas = Arc::new(PeerSchemas::default());
 
         for host_addr in relay_hosts {
-            let (send_tx, send_rx) = mpsc::channel::<mesh_pb::Envelope>(256);
+            let (send_tx, send_rx) = mpsc::channel::<Outbound>(256);
             let (ctrl_tx, ctrl_rx) = mpsc::channel::<host_pb::NodeFrame>(64);
             let dtx = delivery_tx.clone();
             tokio::spawn(relay_loop(
@@ -53,10 +140,14 @@ impl MeshTransport {
                     signature_b64: signature_b64.clone(),
                     reconnect_interval: Duration::from_secs(5),
                     ping_interval: Duration::from_secs(30),
+                    security: security.clone(),
+                    peer_schemas: peer_schemas.clone(),
                 },
                 send_rx,
                 ctrl_rx,
                 dtx,
+                connected.clone(),
+                reconnects.clone(),
             ));
             senders.push(send_tx);
             control_senders.push(ctrl_tx);
@@ -66,17 +157,59 @@ impl MeshTransport {
             senders,
             control_senders,
             incoming: tokio::sync::Mutex::new(delivery_rx),
+            connected,
+            reconnects,
+            security,
+            peer_schemas,
+            privacy_mode: false,
         }
     }
 
-    /// Send an envelope via the first available relay.
+    /// Enable or disable relay privacy mode (see [`crate::padding`]).
+    pub fn with_privacy_mode(mut self, enabled: bool) -> Self {
+        self.privacy_mode = enabled;
+        self
+    }
+
+    /// Whether outbound envelopes should be sealed before hitting the relay.
+    pub fn privacy_mode(&self) -> bool {
+        self.privacy_mode
+    }
+
+    /// Send an envelope via the first available relay, in the schema the
+    /// recipient was last seen using. Returns `Ok` once a relay has accepted
+    /// it, and the relay's error (e.g. `NOT_FOUND` for a peer that is not
+    /// connected) if every relay refused it.
+    #[tracing::instrument(
+        name = "mesh_send",
+        skip_all,
+        fields(message_id = %envelope.message_id, to = %envelope.to_node_id)
+    )]
     pub async fn send_via_relay(&self, envelope: mesh_pb::Envelope) -> Result<()> {
+        let peer = self.peer_schemas.version_for(&envelope.to_node_id);
+        let envelope = envelope_schema::downgrade(envelope, peer);
+        let mut refused = None;
         for sender in &self.senders {
-            if sender.send(envelope.clone()).await.is_ok() {
-                return Ok(());
+            let (sent, ack) = oneshot::channel();
+            let outbound = Outbound {
+                envelope: envelope.clone(),
+                sent,
+            };
+            if sender.send(outbound).await.is_err() {
+                continue;
+            }
+            // On timeout the receiver is dropped and a session that has not
+            // written the envelope yet skips it, so it is not sent twice.
+            match tokio::time::timeout(SEND_ACK_TIMEOUT, ack).await {
+                Ok(Ok(Ok(()))) => return Ok(()),
+                Ok(Ok(Err(reason))) => refused = Some(reason),
+                _ => {}
             }
         }
-        anyhow::bail!("no relay available")
+        match refused {
+            Some(reason) => anyhow::bail!("relay refused message: {reason}"),
+            None => anyhow::bail!("no relay available"),
+        }
     }
 
     /// Send a control frame (e.g., room subscribe/unsubscribe) via the first available relay.
@@ -89,6 +222,21 @@ impl MeshTransport {
         anyhow::bail!("no relay available for control frame")
     }
 
+    /// Whether at least one relay session is currently registered.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed) > 0
+    }
+
+    /// TLS and proxy options for other connections to the same relays.
+    pub fn security(&self) -> &RelaySecurity {
+        &self.security
+    }
+
+    /// Subscribe to relay session failures.
+    pub fn subscribe_reconnects(&self) -> broadcast::Receiver<RelayReconnect> {
+        self.reconnects.subscribe()
+    }
+
     /// Get the number of relay connections.
     pub fn relay_count(&self) -> usize {
         self.senders.len()
@@ -97,93 +245,167 @@ impl MeshTransport {
 
 async fn relay_loop(
     config: RelayConfig,
-    mut send_rx: mpsc::Receiver<mesh_pb::Envelope>,
+    mut send_rx: mpsc::Receiver<Outbound>,
     mut control_rx: mpsc::Receiver<host_pb::NodeFrame>,
     delivery_tx: mpsc::Sender<mesh_pb::Envelope>,
+    connected: Arc<AtomicUsize>,
+    reconnects: broadcast::Sender<RelayReconnect>,
 ) {
     loop {
-        match run_relay_session(&config, &mut send_rx, &mut control_rx, &delivery_tx).await {
+        match run_relay_session(
+            &config,
+            &mut send_rx,
+            &mut control_rx,
+            &delivery_tx,
+            &connected,
+        )
+        .await
+        {
             Ok(()) => {
                 tracing::info!(host = %config.host_addr, "relay session ended cleanly");
                 break; // send_rx closed → node shutting down
             }
             Err(e) => {
                 tracing::warn!(host = %config.host_addr, err = %e, "relay session failed, reconnecting");
+                let _ = reconnects.send(RelayReconnect {
+                    host: config.host_addr.clone(),
+                    error: e.to_string(),
+                });
                 tokio::time::sleep(config.reconnect_interval).await;
             }
         }
     }
 }
 
-/// Determine whether a relay address refers to localhost.
-pub fn is_localhost(addr: &str) -> bool {
-    // Strip scheme if present
+/// The host of a relay address, without scheme, port or IPv6 brackets. A
+/// bare IPv6 address is taken as a host with no port.
+fn host_of(addr: &str) -> &str {
     let host_part = addr
         .strip_prefix("https://")
         .or_else(|| addr.strip_prefix("http://"))
         .unwrap_or(addr);
-    // Handle bracketed IPv6 like [::1]:50100
-    let host = if host_part.starts_with('[') {
-        host_part
-            .split(']')
-            .next()
-            .unwrap_or(host_part)
-            .trim_start_matches('[')
-    } else {
-        // Strip port for non-IPv6 addresses (host:port)
-        host_part.split(':').next().unwrap_or(host_part)
-    };
-    host == "localhost" || host == "127.0.0.1" || host == "::1"
+    if let Some(rest) = host_part.strip_prefix('[') {
+        // Bracketed IPv6 like [::1]:50100
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    if host_part.parse::<Ipv6Addr>().is_ok() {
+        return host_part;
+    }
+    // Strip port for non-IPv6 addresses (host:port)
+    host_part.split(':').next().unwrap_or(host_part)
+}
+
+/// Determine whether a relay address refers to localhost.
+pub fn is_localhost(addr: &str) -> bool {
+    let host = host_of(addr);
+    host == "localhost" || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
 }
 
 /// Build the relay endpoint URL from a host address.
 /// Uses https:// for non-localhost addresses, http:// for localhost.
-/// Respects explicit scheme if already provided.
+/// Respects explicit scheme if already provided. A bare IPv6 address is
+/// bracketed so it forms a valid URL.
 pub fn relay_endpoint(host_addr: &str) -> String {
     if host_addr.starts_with("http://") || host_addr.starts_with("https://") {
-        host_addr.to_string()
-    } else if is_localhost(host_addr) {
-        format!("http://{host_addr}")
+        return host_addr.to_string();
+    }
+    let scheme = if is_localhost(host_addr) {
+        "http"
+    } else {
+        "https"
+    };
+    if host_addr.parse::<Ipv6Addr>().is_ok() {
+        format!("{scheme}://[{host_addr}]")
     } else {
-        format!("https://{host_addr}")
+        format!("{scheme}://{host_addr}")
+    }
+}
+
+/// Connect to a relay, applying any custom TLS configuration and proxy.
+pub async fn connect_relay(
+    endpoint: &str,
+    security: &RelaySecurity,
+) -> Result<HostServiceClient<Channel>> {
+    let mut relay =
+        Endpoint::from_shared(endpoint.to_string()).context("invalid relay endpoint")?;
+    if security.ca_cert_pem.is_some() || security.client_identity.is_some() {
+        let mut tls = ClientTlsConfig::new().with_enabled_roots();
+        if let Some(ca_pem) = &security.ca_cert_pem {
+            tls = tls.ca_certificate(Certificate::from_pem(ca_pem));
+        }
+        if let Some((cert_pem, key_pem)) = &security.client_identity {
+            tls = tls.identity(Identity::from_pem(cert_pem, key_pem));
+        }
+        relay = relay.tls_config(tls).context("invalid relay TLS config")?;
     }
+    let proxy = security
+        .proxy
+        .as_ref()
+        .filter(|proxy| proxy.applies_to(relay.uri().host().unwrap_or_default()));
+    let channel = match proxy {
+        Some(proxy) => {
+            relay
+                .connect_with_connector(ProxyConnector::new(proxy.clone()))
+                .await?
+        }
+        None => relay.connect().await?,
+    };
+    Ok(HostServiceClient::new(channel))
 }
 
+#[tracing::instrument(name = "relay_session", skip_all, fields(host = %config.host_addr))]
 async fn run_relay_session(
     config: &RelayConfig,
-    send_rx: &mut mpsc::Receiver<mesh_pb::Envelope>,
+    send_rx: &mut mpsc::Receiver<Outbound>,
     control_rx: &mut mpsc::Receiver<host_pb::NodeFrame>,
     delivery_tx: &mpsc::Sender<mesh_pb::Envelope>,
+    connected: &Arc<AtomicUsize>,
 ) -> Result<()> {
     let endpoint = relay_endpoint(&config.host_addr);
 
-    let mut client = HostServiceClient::connect(endpoint.clone())
+    let mut client = connect_relay(&endpoint, &config.security)
         .await
         .with_context(|| format!("connect to relay host at {endpoint}"))?;
 
     // Channel for outbound NodeFrames
-    let (frame_tx, frame_rx) = mpsc::channel::<host_pb::NodeFrame>(256);
+    let (frame_tx, frame_rx) = mpsc::channel::<StreamFrame>(256);
 
     // Send Register as first frame
     let now_ms = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
         .as_millis() as u64;
+    let signature_b64 = match &config.security.signing_key {
+        Some(key) => sign_payload(key, &relay_registration_payload(&config.node_id, now_ms))
+            .context("sign relay registration")?,
+        None => config.signature_b64.clone(),
+    };
     frame_tx
-        .send(host_pb::NodeFrame {
-            frame: Some(host_pb::node_frame::Frame::Register(
-                host_pb::RegisterFrame {
-                    node_id: config.node_id.clone(),
-                    public_key_b64: config.public_key_b64.clone(),
-                    signature_b64: config.signature_b64.clone(),
-                    timestamp_ms: now_ms,
-                },
-            )),
-        })
+        .send((
+            host_pb::NodeFrame {
+                frame: Some(host_pb::node_frame::Frame::Register(
+                    host_pb::RegisterFrame {
+                        node_id: config.node_id.clone(),
+                        public_key_b64: config.public_key_b64.clone(),
+                        signature_b64,
+                        timestamp_ms: now_ms,
+                    },
+                )),
+            },
+            None,
+        ))
         .await
         .context("send register frame")?;
 
-    let outbound = tokio_stream::wrappers::ReceiverStream::new(frame_rx);
+    // Frames carrying a notification count as sent when the RPC pulls them
+    // off the channel; any still buffered when the session drops take their
+    // notification with them.
+    let outbound = tokio_stream::wrappers::ReceiverStream::new(frame_rx).map(|(frame, sent)| {
+        if let Some(sent) = sent {
+            let _ = sent.send(Ok(()));
+        }
+        frame
+    });
     let response = client.relay(outbound).await.context("relay RPC")?;
     let mut inbound = response.into_inner();
 
@@ -193,7 +415,7 @@ async fn run_relay_session(
         .await
         .context("receive register ack")?
         .context("relay closed before ack")?;
-    match first.frame {
+    let relay_acks = match first.frame {
         Some(host_pb::host_frame::Frame::RegisterAck(ack)) => {
             if !ack.success {
                 anyhow::bail!(
@@ -202,11 +424,16 @@ async fn run_relay_session(
                 );
             }
             tracing::info!(host = %config.host_addr, node_id = %config.node_id, "registered with relay");
+            ack.relay_acks
         }
         _ => {
             anyhow::bail!("expected RegisterAck, got {:?}", first.frame);
         }
-    }
+    };
+    // Envelopes written but not yet acked, by message ID. Dropped with the
+    // session, which fails their sends.
+    let mut awaiting_ack: HashMap<String, oneshot::Sender<SendResult>> = HashMap::new();
+    let _connected = ConnectedGuard::new(connected.clone());
 
     // Spawn ping task
     let ping_tx = frame_tx.clone();
@@ -220,11 +447,14 @@ async fn run_relay_session(
                 .unwrap_or_default()
                 .as_millis() as u64;
             if ping_tx
-                .send(host_pb::NodeFrame {
-                    frame: Some(host_pb::node_frame::Frame::Ping(host_pb::PingFrame {
-                        timestamp_ms: now,
-                    })),
-                })
+                .send((
+                    host_pb::NodeFrame {
+                        frame: Some(host_pb::node_frame::Frame::Ping(host_pb::PingFrame {
+                            timestamp_ms: now,
+                        })),
+                    },
+                    None,
+                ))
                 .await
                 .is_err()
             {
@@ -241,15 +471,29 @@ async fn run_relay_session(
                     Ok(Some(frame)) => {
                         match frame.frame {
                             Some(host_pb::host_frame::Frame::Delivery(delivery)) => {
-                                if let Some(envelope) = delivery.envelope
-                                    && delivery_tx.send(envelope).await.is_err()
-                                {
-                                    break; // receiver dropped
+                                if let Some(envelope) = delivery.envelope {
+                                    config.peer_schemas.observe(&envelope);
+                                    let envelope = envelope_schema::upgrade(envelope);
+                                    if delivery_tx.send(envelope).await.is_err() {
+                                        break; // receiver dropped
+                                    }
                                 }
                             }
                             Some(host_pb::host_frame::Frame::Pong(_)) => {}
+                            Some(host_pb::host_frame::Frame::RelayAck(ack)) => {
+                                if let Some(sent) = awaiting_ack.remove(&ack.message_id) {
+                                    let _ = sent.send(Ok(()));
+                                }
+                            }
                             Some(host_pb::host_frame::Frame::Error(err)) => {
-                                tracing::warn!(code = %err.code, msg = %err.message, "relay error");
+                                match awaiting_ack.remove(&err.message_id) {
+                                    Some(sent) => {
+                                        let _ = sent.send(Err(format!("{}: {}", err.code, err.message)));
+                                    }
+                                    None => {
+                                        tracing::warn!(code = %err.code, msg = %err.message, "relay error");
+                                    }
+                                }
                             }
                             _ => {}
                         }
@@ -260,9 +504,20 @@ async fn run_relay_session(
                     }
                 }
             }
-            envelope = send_rx.recv() => {
-                match envelope {
-                    Some(env) => {
+            outbound = send_rx.recv() => {
+                match outbound {
+                    // The sender gave up waiting and will retry on its own.
+                    Some(Outbound { sent, .. }) if sent.is_closed() => {}
+                    Some(Outbound { envelope: env, sent }) => {
+                        // Relays that ack answer `sent` from the read loop;
+                        // for the rest, writing the frame is as far as we see.
+                        let sent = if relay_acks {
+                            awaiting_ack.retain(|_, sent| !sent.is_closed());
+                            awaiting_ack.insert(env.message_id.clone(), sent);
+                            None
+                        } else {
+                            Some(sent)
+                        };
                         let relay_frame = host_pb::NodeFrame {
                             frame: Some(host_pb::node_frame::Frame::RelaySend(
                                 host_pb::RelaySendFrame {
@@ -271,7 +526,7 @@ async fn run_relay_session(
                                 },
                             )),
                         };
-                        if frame_tx.send(relay_frame).await.is_err() {
+                        if frame_tx.send((relay_frame, sent)).await.is_err() {
                             break;
                         }
                     }
@@ -282,15 +537,11 @@ async fn run_relay_session(
                 }
             }
             ctrl_frame = control_rx.recv() => {
-                match ctrl_frame {
-                    Some(frame) => {
-                        if frame_tx.send(frame).await.is_err() {
-                            break;
-                        }
-                    }
-                    None => {
-                        // Control channel closed — not fatal, just stop listening.
-                    }
+                // A closed control channel is not fatal — just stop listening.
+                if let Some(frame) = ctrl_frame
+                    && frame_tx.send((frame, None)).await.is_err()
+                {
+                    break;
                 }
             }
         }
@@ -300,6 +551,22 @@ async fn run_relay_session(
     anyhow::bail!("relay stream closed")
 }
 
+/// Counts a registered relay session for as long as it is alive.
+struct ConnectedGuard(Arc<AtomicUsize>);
+
+impl ConnectedGuard {
+    fn new(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for ConnectedGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -314,6 +581,8 @@ mod tests {
         assert!(is_localhost("[::1]:50100"));
         assert!(is_localhost("http://localhost:50100"));
         assert!(is_localhost("https://127.0.0.1:50100"));
+        assert!(is_localhost("::1"));
+        assert!(is_localhost("127.0.0.2:50100"));
     }
 
     #[test]
@@ -322,6 +591,18 @@ mod tests {
         assert!(!is_localhost("agentbook.ardabot.ai:50100"));
         assert!(!is_localhost("192.168.1.1:50100"));
         assert!(!is_localhost("example.com"));
+        assert!(!is_localhost("[2001:db8::1]:50100"));
+        assert!(!is_localhost("2001:db8::1"));
+    }
+
+    #[test]
+    fn relay_endpoint_handles_ipv6() {
+        assert_eq!(
+            relay_endpoint("[2001:db8::1]:50100"),
+            "https://[2001:db8::1]:50100"
+        );
+        assert_eq!(relay_endpoint("2001:db8::1"), "https://[2001:db8::1]");
+        assert_eq!(relay_endpoint("[::1]:50100"), "http://[::1]:50100");
     }
 
     #[test]
@@ -353,4 +634,25 @@ mod tests {
             "https://localhost:50100"
         );
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn send_via_relay_fails_when_no_session_writes_the_envelope() {
+        // Nothing listens here, so the session never gets to write the
+        // envelope and the caller must not treat it as sent.
+        let transport = MeshTransport::new(
+            vec!["127.0.0.1:1".to_string()],
+            "0xme".to_string(),
+            "pk".to_string(),
+            "sig".to_string(),
+        );
+        let envelope = mesh_pb::Envelope {
+            message_id: "m1".to_string(),
+            from_node_id: "0xme".to_string(),
+            to_node_id: "0xbob".to_string(),
+            message_type: mesh_pb::MessageType::DmText as i32,
+            schema_version: envelope_schema::CURRENT,
+            ..Default::default()
+        };
+        assert!(transport.send_via_relay(envelope).await.is_err());
+    }
 }
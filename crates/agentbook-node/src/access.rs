@@ -0,0 +1,316 @@
+//! Multi-user access: which local users and API tokens may connect, and
+//! what each may do.
+//!
+//! By default only the daemon's own uid can use the Unix socket and only the
+//! `api_token` unlocks the TCP listener, both with full access. Additional
+//! users and tokens are granted a [`Role`] in `access.json` in the state
+//! directory:
+//!
+//! ```json
+//! {
+//!   "users": [{ "uid": 1001, "role": "view_only" }],
+//!   "tokens": [{ "name": "ci", "token": "…", "role": "task_runner" }]
+//! }
+//! ```
+//!
+//! Listing any user opens the socket file to other accounts; each connection
+//! is then admitted by its peer uid.
+
+use agentbook::protocol::Request;
+pub(crate) use agentbook_crypto::crypto::token_matches;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+const ACCESS_FILE: &str = "access.json";
+
+/// What a connection may do, checked per request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Read identity, inbox, follows, rooms and balances.
+    ViewOnly,
+    /// Also message, post, follow and join rooms, but not move funds,
+    /// change keys or settings, or stop the node.
+    TaskRunner,
+    /// Everything, like the node's owner.
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::ViewOnly => write!(f, "view_only"),
+            Role::TaskRunner => write!(f, "task_runner"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl Role {
+    /// Whether this role may send `request`.
+    pub fn permits(self, request: &Request) -> bool {
+        self >= Self::required_for(request)
+    }
+
+    /// The least privileged role allowed to send `request`.
+    pub fn required_for(request: &Request) -> Role {
+        match request {
+            Request::Identity
+            | Request::Health
+            | Request::RelayUsage { .. }
+            | Request::DaemonLog { .. }
+            | Request::Negotiate { .. }
+            | Request::IngressStats { .. }
+            | Request::RetentionStats
+            | Request::Config
+            | Request::Following
+            | Request::Groups
+            | Request::Followers
+            | Request::InviteList
+            | Request::LookupUsername { .. }
+            | Request::LookupNodeId { .. }
+            | Request::Inbox { .. }
+            | Request::MessageThread { .. }
+            | Request::PendingReplies
+            | Request::OutboxList
+            | Request::WalletBalance { .. }
+            | Request::ReadContract { .. }
+            | Request::RoomInbox { .. }
+            | Request::ListRooms => Role::ViewOnly,
+
+            Request::Follow { .. }
+            | Request::Unfollow { .. }
+            | Request::SetAlias { .. }
+            | Request::GroupAdd { .. }
+            | Request::GroupRemove { .. }
+            | Request::Block { .. }
+            | Request::InviteCreate { .. }
+            | Request::InviteAccept { .. }
+            | Request::SendDm { .. }
+            | Request::BroadcastDm { .. }
+            | Request::Ping { .. }
+            | Request::PostFeed { .. }
+            | Request::SendTemplate { .. }
+            | Request::InboxAck { .. }
+            | Request::ClaimInbox
+            | Request::OutboxCancel { .. }
+            | Request::JoinRoom { .. }
+            | Request::LeaveRoom { .. }
+            | Request::SendRoom { .. } => Role::TaskRunner,
+
+            Request::RotateKey { .. }
+            | Request::ConfigSet { .. }
+            | Request::InviteRevoke { .. }
+            | Request::RegisterUsername { .. }
+            | Request::SendEth { .. }
+            | Request::SendUsdc { .. }
+            | Request::YoloSendEth { .. }
+            | Request::YoloSendUsdc { .. }
+            | Request::SetupTotp
+            | Request::VerifyTotp { .. }
+            | Request::WriteContract { .. }
+            | Request::YoloWriteContract { .. }
+            | Request::SignMessage { .. }
+            | Request::YoloSignMessage { .. }
+            | Request::SyncPush { .. }
+            | Request::SyncPull { .. }
+            | Request::ReencryptState
+            | Request::Clients
+            | Request::Disconnect { .. }
+            | Request::Freeze
+            | Request::Unfreeze
+            | Request::Share { .. }
+            | Request::Unshare
+            | Request::Drain { .. }
+            | Request::Shutdown => Role::Admin,
+        }
+    }
+}
+
+/// Whether `request` may run while the node is frozen: reads, plus the
+/// controls a supervisor needs to inspect, unfreeze or stop the node.
+/// Anything that sends, changes state or moves funds waits for `Unfreeze`,
+/// whatever the caller's role.
+pub fn allowed_while_frozen(request: &Request) -> bool {
+    match request {
+        Request::Freeze
+        | Request::Unfreeze
+        | Request::Clients
+        | Request::Drain { .. }
+        | Request::Shutdown => true,
+
+        Request::Identity
+        | Request::Health
+        | Request::RelayUsage { .. }
+        | Request::DaemonLog { .. }
+        | Request::Negotiate { .. }
+        | Request::IngressStats { .. }
+        | Request::RetentionStats
+        | Request::Config
+        | Request::Following
+        | Request::Groups
+        | Request::Followers
+        | Request::InviteList
+        | Request::LookupUsername { .. }
+        | Request::LookupNodeId { .. }
+        | Request::Inbox { .. }
+        | Request::MessageThread { .. }
+        | Request::PendingReplies
+        | Request::OutboxList
+        | Request::WalletBalance { .. }
+        | Request::ReadContract { .. }
+        | Request::RoomInbox { .. }
+        | Request::ListRooms => true,
+
+        Request::Follow { .. }
+        | Request::Unfollow { .. }
+        | Request::SetAlias { .. }
+        | Request::GroupAdd { .. }
+        | Request::GroupRemove { .. }
+        | Request::Block { .. }
+        | Request::InviteCreate { .. }
+        | Request::InviteAccept { .. }
+        | Request::SendDm { .. }
+        | Request::BroadcastDm { .. }
+        | Request::Ping { .. }
+        | Request::PostFeed { .. }
+        | Request::SendTemplate { .. }
+        | Request::InboxAck { .. }
+        | Request::ClaimInbox
+        | Request::OutboxCancel { .. }
+        | Request::JoinRoom { .. }
+        | Request::LeaveRoom { .. }
+        | Request::SendRoom { .. }
+        | Request::RotateKey { .. }
+        | Request::ConfigSet { .. }
+        | Request::InviteRevoke { .. }
+        | Request::RegisterUsername { .. }
+        | Request::SendEth { .. }
+        | Request::SendUsdc { .. }
+        | Request::YoloSendEth { .. }
+        | Request::YoloSendUsdc { .. }
+        | Request::SetupTotp
+        | Request::VerifyTotp { .. }
+        | Request::WriteContract { .. }
+        | Request::YoloWriteContract { .. }
+        | Request::SignMessage { .. }
+        | Request::YoloSignMessage { .. }
+        | Request::SyncPush { .. }
+        | Request::SyncPull { .. }
+        | Request::ReencryptState
+        | Request::Disconnect { .. }
+        | Request::Share { .. }
+        | Request::Unshare => false,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UserGrant {
+    uid: u32,
+    role: Role,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TokenGrant {
+    /// Shown in logs instead of the token.
+    name: String,
+    token: String,
+    role: Role,
+}
+
+/// Extra users and tokens allowed to use the client API.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccessPolicy {
+    #[serde(default)]
+    users: Vec<UserGrant>,
+    #[serde(default)]
+    tokens: Vec<TokenGrant>,
+}
+
+impl AccessPolicy {
+    /// Read `access.json`; a missing file grants nothing extra.
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        let path = state_dir.join(ACCESS_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = std::fs::read_to_string(&path).context("failed to read access.json")?;
+        serde_json::from_str(&data).context("invalid access.json")
+    }
+
+    /// Whether users other than the owner may connect to the Unix socket.
+    pub fn is_multi_user(&self) -> bool {
+        !self.users.is_empty()
+    }
+
+    /// Role of a Unix socket peer. The daemon's own uid is always admin.
+    pub fn role_for_uid(&self, uid: u32, owner_uid: u32) -> Option<Role> {
+        if uid == owner_uid {
+            return Some(Role::Admin);
+        }
+        self.users
+            .iter()
+            .find(|grant| grant.uid == uid)
+            .map(|grant| grant.role)
+    }
+
+    /// Role and display name for a TCP client's token. `admin_token` is the
+    /// node's own API token.
+    pub fn role_for_token(&self, token: &str, admin_token: &str) -> Option<(Role, &str)> {
+        if token_matches(token, admin_token) {
+            return Some((Role::Admin, "api_token"));
+        }
+        self.tokens
+            .iter()
+            .find(|grant| token_matches(token, &grant.token))
+            .map(|grant| (grant.role, grant.name.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_are_nested() {
+        let view = Request::Inbox {
+            unread_only: false,
+            limit: None,
+        };
+        let post = Request::PostFeed { body: "hi".into() };
+        assert!(Role::ViewOnly.permits(&view));
+        assert!(!Role::ViewOnly.permits(&post));
+        assert!(Role::TaskRunner.permits(&post));
+        assert!(!Role::TaskRunner.permits(&Request::Shutdown));
+        assert!(Role::Admin.permits(&Request::Shutdown));
+    }
+
+    #[test]
+    fn policy_grants_listed_users_and_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!AccessPolicy::load(dir.path()).unwrap().is_multi_user());
+
+        std::fs::write(
+            dir.path().join(ACCESS_FILE),
+            r#"{"users":[{"uid":1001,"role":"view_only"}],
+                "tokens":[{"name":"ci","token":"t-ci","role":"task_runner"}]}"#,
+        )
+        .unwrap();
+        let policy = AccessPolicy::load(dir.path()).unwrap();
+        assert!(policy.is_multi_user());
+        assert_eq!(policy.role_for_uid(1000, 1000), Some(Role::Admin));
+        assert_eq!(policy.role_for_uid(1001, 1000), Some(Role::ViewOnly));
+        assert_eq!(policy.role_for_uid(1002, 1000), None);
+        assert_eq!(
+            policy.role_for_token("admin", "admin"),
+            Some((Role::Admin, "api_token"))
+        );
+        assert_eq!(
+            policy.role_for_token("t-ci", "admin"),
+            Some((Role::TaskRunner, "ci"))
+        );
+        assert_eq!(policy.role_for_token("nope", "admin"), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Connected socket and TCP clients, so an admin can see who is attached to
+//! the node (`Clients`) and drop a stuck connection (`Disconnect`).
+//!
+//! gRPC calls are one-shot and are not tracked here.
+//!
+//! One session at a time may also hold the inbox (`ClaimInbox`): messages
+//! accepted into the inbox are pushed to it through [`Registry::push_inbox`].
+
+use crate::access::Role;
+use crate::handler::NodeState;
+use agentbook::protocol::{ClientInfo, Event};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Notify, mpsc};
+
+/// Pushed messages a slow inbox gateway may fall behind by before new ones
+/// are left in the inbox only.
+const INBOX_PUSH_BUFFER: usize = 256;
+
+struct Entry {
+    info: ClientInfo,
+    kick: Arc<Notify>,
+}
+
+/// Every live client session, by ID.
+#[derive(Default)]
+pub struct Registry {
+    next_id: AtomicU64,
+    clients: Mutex<BTreeMap<u64, Entry>>,
+    /// The session holding the inbox, and where to push to it.
+    inbox_gateway: Mutex<Option<(u64, mpsc::Sender<Event>)>>,
+}
+
+impl Registry {
+    /// Snapshot of the connected clients, oldest first.
+    pub fn list(&self) -> Vec<ClientInfo> {
+        let gateway = self.inbox_gateway_id();
+        let clients = self.clients.lock().unwrap();
+        clients
+            .values()
+            .map(|e| ClientInfo {
+                inbox_gateway: gateway == Some(e.info.client_id),
+                ..e.info.clone()
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn inbox_gateway_id(&self) -> Option<u64> {
+        self.inbox_gateway
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(id, _)| *id)
+    }
+
+    /// Whether a session holds the inbox.
+    pub fn has_inbox_gateway(&self) -> bool {
+        self.inbox_gateway_id().is_some()
+    }
+
+    /// Push `event` to the session holding the inbox, if any. Never waits: a
+    /// gateway that has fallen behind misses the push, and finds the message
+    /// in the inbox instead.
+    pub fn push_inbox(&self, event: Event) {
+        let gateway = self.inbox_gateway.lock().unwrap();
+        if let Some((id, tx)) = gateway.as_ref()
+            && tx.try_send(event).is_err()
+        {
+            tracing::warn!(client_id = id, "inbox gateway fell behind, push dropped");
+        }
+    }
+
+    /// Ask client `id`'s session to close. Returns whether it exists.
+    pub fn disconnect(&self, id: u64) -> bool {
+        match self.clients.lock().unwrap().get(&id) {
+            Some(entry) => {
+                entry.kick.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A client session's place in the [`Registry`]; removed when dropped.
+pub struct Connection {
+    state: Arc<NodeState>,
+    id: u64,
+    kick: Arc<Notify>,
+}
+
+impl Connection {
+    /// Add a session to `state.clients`.
+    pub fn register(
+        state: &Arc<NodeState>,
+        transport: &'static str,
+        peer: String,
+        role: Role,
+    ) -> Self {
+        let registry = &state.clients;
+        let id = registry.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let kick = Arc::new(Notify::new());
+        let info = ClientInfo {
+            client_id: id,
+            transport: transport.to_string(),
+            peer,
+            role: role.to_string(),
+            connected_at_ms: state.clock.now_ms(),
+            requests: 0,
+            inbox_gateway: false,
+        };
+        registry.clients.lock().unwrap().insert(
+            id,
+            Entry {
+                info,
+                kick: kick.clone(),
+            },
+        );
+        Self {
+            state: state.clone(),
+            id,
+            kick,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Count a request answered on this session.
+    pub fn record_request(&self) {
+        if let Some(entry) = self.state.clients.clients.lock().unwrap().get_mut(&self.id) {
+            entry.info.requests += 1;
+        }
+    }
+
+    /// Make this session the inbox gateway, taking over from any other.
+    /// Pushed events arrive on the returned receiver, which closes if
+    /// another session claims the inbox.
+    pub fn claim_inbox(&self) -> mpsc::Receiver<Event> {
+        let (tx, rx) = mpsc::channel(INBOX_PUSH_BUFFER);
+        *self.state.clients.inbox_gateway.lock().unwrap() = Some((self.id, tx));
+        tracing::info!(client_id = self.id, "client claimed the inbox");
+        rx
+    }
+
+    /// Resolves once an admin disconnects this session.
+    pub async fn disconnected(&self) {
+        self.kick.notified().await
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let registry = &self.state.clients;
+        registry.clients.lock().unwrap().remove(&self.id);
+        let mut gateway = registry.inbox_gateway.lock().unwrap();
+        if gateway.as_ref().is_some_and(|(id, _)| *id == self.id) {
+            *gateway = None;
+        }
+    }
+}
@@ -0,0 +1,534 @@
+//! Optional gRPC variant of the client API (`--listen-grpc`), generated from
+//! `agentbook_node.proto`, so agents in other languages get typed clients
+//! instead of implementing JSON-lines framing.
+//!
+//! Every RPC maps onto a socket [`Request`] and goes through the same
+//! handler, role checks and drain accounting. Callers authenticate with
+//! `authorization: Bearer <token>` metadata, using the node's API token or a
+//! token granted in `access.json`.
+
+use crate::access::Role;
+use crate::handler::NodeState;
+use crate::middleware::{self, RequestContext};
+use agentbook::protocol::{self, Event, MessageType, Request, Response};
+use agentbook_proto::mesh::v1 as mesh_pb;
+use agentbook_proto::node::v1 as node_pb;
+use agentbook_proto::node::v1::node_service_server::NodeService;
+use serde::de::DeserializeOwned;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Code, Status};
+use zeroize::Zeroizing;
+
+/// Metadata key carrying the daemon's error code on failed RPCs.
+pub const ERROR_CODE_HEADER: &str = "x-agentbook-error";
+
+pub type EventStream = Pin<Box<dyn Stream<Item = Result<node_pb::Event, Status>> + Send>>;
+
+/// gRPC front end over the node's request handler.
+pub struct NodeServiceImpl {
+    state: Arc<NodeState>,
+    token: Zeroizing<String>,
+}
+
+impl NodeServiceImpl {
+    /// `token` grants admin access; `access.json` may grant more.
+    pub fn new(state: Arc<NodeState>, token: String) -> Self {
+        Self {
+            state,
+            token: Zeroizing::new(token),
+        }
+    }
+
+    fn authorize<T>(&self, req: &tonic::Request<T>) -> Result<Role, Status> {
+        let token = req
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("missing API token"))?;
+        self.state
+            .access
+            .role_for_token(token, &self.token)
+            .map(|(role, _)| role)
+            .ok_or_else(|| Status::unauthenticated("invalid API token"))
+    }
+
+    /// Authorize `req`, run `request` and return the response data.
+    async fn call<T: Sync>(
+        &self,
+        req: &tonic::Request<T>,
+        request: Request,
+    ) -> Result<Option<serde_json::Value>, Status> {
+        let role = self.authorize(req)?;
+        let ctx = RequestContext::new(role, None, "grpc");
+        match middleware::dispatch(&self.state, ctx, request).await {
+            Response::Ok { data } => Ok(data),
+            Response::Error { code, message } => Err(error_status(&code, &message)),
+            other => Err(Status::internal(format!("unexpected response: {other:?}"))),
+        }
+    }
+
+    /// Like [`NodeServiceImpl::call`], decoding the data as `D`.
+    async fn call_data<T: Sync, D: DeserializeOwned>(
+        &self,
+        req: &tonic::Request<T>,
+        request: Request,
+    ) -> Result<D, Status> {
+        let data = self.call(req, request).await?.unwrap_or_default();
+        serde_json::from_value(data).map_err(|e| Status::internal(format!("bad response: {e}")))
+    }
+}
+
+/// Map a daemon error code onto the closest gRPC status, keeping the original
+/// code in [`ERROR_CODE_HEADER`].
+fn error_status(code: &str, message: &str) -> Status {
+    let grpc_code = match code {
+        "not_found" | "not_joined" => Code::NotFound,
+        "draining" | "no_relay" | "relay_unavailable" | "transport_error" => Code::Unavailable,
+        "cooldown" | "spending_limit" => Code::ResourceExhausted,
+        "already_configured" => Code::AlreadyExists,
+        "forbidden" => Code::PermissionDenied,
+        c if c.starts_with("invalid_") || c == "empty_message" => Code::InvalidArgument,
+        _ => Code::FailedPrecondition,
+    };
+    let mut status = Status::new(grpc_code, message);
+    if let Ok(value) = code.parse() {
+        status.metadata_mut().insert(ERROR_CODE_HEADER, value);
+    }
+    status
+}
+
+fn message_type(mt: MessageType) -> i32 {
+    let mt = match mt {
+        MessageType::Unspecified => mesh_pb::MessageType::Unspecified,
+        MessageType::DmText => mesh_pb::MessageType::DmText,
+        MessageType::FeedPost => mesh_pb::MessageType::FeedPost,
+        MessageType::RoomMessage => mesh_pb::MessageType::RoomMessage,
+        MessageType::RoomJoin => mesh_pb::MessageType::RoomJoin,
+        MessageType::RoomLeave => mesh_pb::MessageType::RoomLeave,
+    };
+    mt as i32
+}
+
+fn follow_list(follows: Vec<protocol::FollowInfo>) -> node_pb::FollowList {
+    node_pb::FollowList {
+        follows: follows
+            .into_iter()
+            .map(|f| node_pb::FollowInfo {
+                node_id: f.node_id,
+                username: f.username,
+                followed_at_ms: f.followed_at_ms,
+                alias: f.alias,
+            })
+            .collect(),
+    }
+}
+
+fn inbox_response(messages: Vec<protocol::InboxEntry>) -> node_pb::InboxResponse {
+    node_pb::InboxResponse {
+        messages: messages.into_iter().map(inbox_entry).collect(),
+    }
+}
+
+fn inbox_entry(m: protocol::InboxEntry) -> node_pb::InboxEntry {
+    node_pb::InboxEntry {
+        message_id: m.message_id,
+        from_node_id: m.from_node_id,
+        from_username: m.from_username,
+        to_node_id: m.to_node_id,
+        message_type: message_type(m.message_type),
+        body: m.body,
+        timestamp_ms: m.timestamp_ms,
+        acked: m.acked,
+        room: m.room,
+    }
+}
+
+fn event(event: Event) -> node_pb::Event {
+    use node_pb::event::Event as E;
+    let event = match event {
+        Event::NewMessage {
+            message_id,
+            from,
+            message_type: mt,
+            preview,
+        } => E::NewMessage(node_pb::NewMessage {
+            message_id,
+            from,
+            message_type: message_type(mt),
+            preview,
+        }),
+        Event::NewRoomMessage {
+            message_id,
+            from,
+            room,
+            message_type: mt,
+            preview,
+        } => E::NewRoomMessage(node_pb::NewRoomMessage {
+            message_id,
+            from,
+            room,
+            message_type: message_type(mt),
+            preview,
+        }),
+        Event::NewFollower { node_id } => E::NewFollower(node_pb::NewFollower { node_id }),
+        Event::KeyRotated {
+            old_node_id,
+            new_node_id,
+        } => E::KeyRotated(node_pb::KeyRotated {
+            old_node_id,
+            new_node_id,
+        }),
+        Event::KeyRevoked { node_id } => E::KeyRevoked(node_pb::KeyRevoked { node_id }),
+        Event::PeerIdle { node_id, idle_ms } => E::PeerIdle(node_pb::PeerIdle { node_id, idle_ms }),
+        Event::PeerActive { node_id } => E::PeerActive(node_pb::PeerActive { node_id }),
+        Event::ReplyOverdue {
+            message_id,
+            to,
+            deadline_ms,
+        } => E::ReplyOverdue(node_pb::ReplyOverdue {
+            message_id,
+            to,
+            deadline_ms,
+        }),
+        Event::MessageReceived { message } => E::MessageReceived(node_pb::MessageReceived {
+            message: Some(inbox_entry(*message)),
+        }),
+    };
+    node_pb::Event { event: Some(event) }
+}
+
+#[derive(serde::Deserialize)]
+struct Sent {
+    message_id: String,
+    #[serde(default)]
+    queued: bool,
+}
+
+impl From<Sent> for node_pb::SendResponse {
+    fn from(sent: Sent) -> Self {
+        Self {
+            message_id: sent.message_id,
+            queued: sent.queued,
+        }
+    }
+}
+
+type RpcResult<T> = Result<tonic::Response<T>, Status>;
+
+#[tonic::async_trait]
+impl NodeService for NodeServiceImpl {
+    async fn identity(
+        &self,
+        req: tonic::Request<node_pb::IdentityRequest>,
+    ) -> RpcResult<node_pb::IdentityResponse> {
+        let info: protocol::IdentityInfo = self.call_data(&req, Request::Identity).await?;
+        Ok(tonic::Response::new(node_pb::IdentityResponse {
+            node_id: info.node_id,
+            public_key_b64: info.public_key_b64,
+            username: info.username,
+        }))
+    }
+
+    async fn health(
+        &self,
+        req: tonic::Request<node_pb::HealthRequest>,
+    ) -> RpcResult<node_pb::HealthResponse> {
+        let health: protocol::HealthStatus = self.call_data(&req, Request::Health).await?;
+        Ok(tonic::Response::new(node_pb::HealthResponse {
+            healthy: health.healthy,
+            relay_connected: health.relay_connected,
+            following_count: health.following_count as u64,
+            unread_count: health.unread_count as u64,
+            uptime_secs: health.uptime_secs,
+            rss_bytes: health.rss_bytes,
+            open_fds: health.open_fds.map(|n| n as u64),
+            outbox_depth: health.outbox_depth as u64,
+            event_backlog: health.event_backlog as u64,
+            events_lagged: health.events_lagged,
+        }))
+    }
+
+    async fn follow(
+        &self,
+        req: tonic::Request<node_pb::TargetRequest>,
+    ) -> RpcResult<node_pb::Empty> {
+        let target = req.get_ref().target.clone();
+        self.call(&req, Request::Follow { target }).await?;
+        Ok(tonic::Response::new(node_pb::Empty {}))
+    }
+
+    async fn unfollow(
+        &self,
+        req: tonic::Request<node_pb::TargetRequest>,
+    ) -> RpcResult<node_pb::Empty> {
+        let target = req.get_ref().target.clone();
+        self.call(&req, Request::Unfollow { target }).await?;
+        Ok(tonic::Response::new(node_pb::Empty {}))
+    }
+
+    async fn block(
+        &self,
+        req: tonic::Request<node_pb::TargetRequest>,
+    ) -> RpcResult<node_pb::Empty> {
+        let target = req.get_ref().target.clone();
+        self.call(&req, Request::Block { target }).await?;
+        Ok(tonic::Response::new(node_pb::Empty {}))
+    }
+
+    async fn following(
+        &self,
+        req: tonic::Request<node_pb::Empty>,
+    ) -> RpcResult<node_pb::FollowList> {
+        let follows = self.call_data(&req, Request::Following).await?;
+        Ok(tonic::Response::new(follow_list(follows)))
+    }
+
+    async fn followers(
+        &self,
+        req: tonic::Request<node_pb::Empty>,
+    ) -> RpcResult<node_pb::FollowList> {
+        let follows = self.call_data(&req, Request::Followers).await?;
+        Ok(tonic::Response::new(follow_list(follows)))
+    }
+
+    async fn send_dm(
+        &self,
+        req: tonic::Request<node_pb::SendDmRequest>,
+    ) -> RpcResult<node_pb::SendResponse> {
+        let node_pb::SendDmRequest { to, body } = req.get_ref().clone();
+        let request = Request::SendDm {
+            to,
+            body,
+            attachments: Vec::new(),
+            in_reply_to: None,
+            reply_within_secs: None,
+        };
+        let sent: Sent = self.call_data(&req, request).await?;
+        Ok(tonic::Response::new(sent.into()))
+    }
+
+    async fn post_feed(
+        &self,
+        req: tonic::Request<node_pb::PostFeedRequest>,
+    ) -> RpcResult<node_pb::SendResponse> {
+        let body = req.get_ref().body.clone();
+        let sent: Sent = self.call_data(&req, Request::PostFeed { body }).await?;
+        Ok(tonic::Response::new(sent.into()))
+    }
+
+    async fn inbox(
+        &self,
+        req: tonic::Request<node_pb::InboxRequest>,
+    ) -> RpcResult<node_pb::InboxResponse> {
+        let request = Request::Inbox {
+            unread_only: req.get_ref().unread_only,
+            limit: req.get_ref().limit.map(|l| l as usize),
+        };
+        let messages = self.call_data(&req, request).await?;
+        Ok(tonic::Response::new(inbox_response(messages)))
+    }
+
+    async fn inbox_ack(
+        &self,
+        req: tonic::Request<node_pb::InboxAckRequest>,
+    ) -> RpcResult<node_pb::Empty> {
+        let message_id = req.get_ref().message_id.clone();
+        self.call(&req, Request::InboxAck { message_id }).await?;
+        Ok(tonic::Response::new(node_pb::Empty {}))
+    }
+
+    async fn join_room(
+        &self,
+        req: tonic::Request<node_pb::JoinRoomRequest>,
+    ) -> RpcResult<node_pb::Empty> {
+        let node_pb::JoinRoomRequest { room, passphrase } = req.get_ref().clone();
+        self.call(&req, Request::JoinRoom { room, passphrase })
+            .await?;
+        Ok(tonic::Response::new(node_pb::Empty {}))
+    }
+
+    async fn leave_room(
+        &self,
+        req: tonic::Request<node_pb::RoomRequest>,
+    ) -> RpcResult<node_pb::Empty> {
+        let room = req.get_ref().room.clone();
+        self.call(&req, Request::LeaveRoom { room }).await?;
+        Ok(tonic::Response::new(node_pb::Empty {}))
+    }
+
+    async fn send_room(
+        &self,
+        req: tonic::Request<node_pb::SendRoomRequest>,
+    ) -> RpcResult<node_pb::Empty> {
+        let node_pb::SendRoomRequest { room, body } = req.get_ref().clone();
+        self.call(&req, Request::SendRoom { room, body }).await?;
+        Ok(tonic::Response::new(node_pb::Empty {}))
+    }
+
+    async fn room_inbox(
+        &self,
+        req: tonic::Request<node_pb::RoomInboxRequest>,
+    ) -> RpcResult<node_pb::InboxResponse> {
+        let request = Request::RoomInbox {
+            room: req.get_ref().room.clone(),
+            limit: req.get_ref().limit.map(|l| l as usize),
+        };
+        let messages = self.call_data(&req, request).await?;
+        Ok(tonic::Response::new(inbox_response(messages)))
+    }
+
+    async fn list_rooms(
+        &self,
+        req: tonic::Request<node_pb::Empty>,
+    ) -> RpcResult<node_pb::RoomList> {
+        let rooms: Vec<protocol::RoomInfo> = self.call_data(&req, Request::ListRooms).await?;
+        Ok(tonic::Response::new(node_pb::RoomList {
+            rooms: rooms
+                .into_iter()
+                .map(|r| node_pb::RoomInfo {
+                    room: r.room,
+                    secure: r.secure,
+                })
+                .collect(),
+        }))
+    }
+
+    type SubscribeStream = EventStream;
+
+    async fn subscribe(&self, req: tonic::Request<node_pb::Empty>) -> RpcResult<EventStream> {
+        // Events carry inbox previews, so they need read access.
+        let role = self.authorize(&req)?;
+        if !role.permits(&Request::Inbox {
+            unread_only: false,
+            limit: None,
+        }) {
+            return Err(Status::permission_denied(
+                "events require the view_only role",
+            ));
+        }
+
+        let state = self.state.clone();
+        let mut event_rx = state.event_tx.subscribe();
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            loop {
+                let item = match event_rx.recv().await {
+                    Ok(e) => Ok(event(e)),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::debug!(skipped = n, "gRPC subscriber lagged");
+                        state.events_lagged.fetch_add(n, Ordering::Relaxed);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if tx.send(item).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(tonic::Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::WalletConfig;
+    use agentbook_mesh::follow::FollowStore;
+    use agentbook_mesh::identity::NodeIdentity;
+    use agentbook_mesh::inbox::NodeInbox;
+    use agentbook_wallet::spending_limit::SpendingLimitConfig;
+
+    fn make_service(state_dir: &std::path::Path) -> NodeServiceImpl {
+        let kek = agentbook_mesh::crypto::random_key_material();
+        let state = NodeState::new(
+            NodeIdentity::load_or_create(state_dir, &kek).unwrap(),
+            FollowStore::load(state_dir).unwrap(),
+            NodeInbox::load(state_dir).unwrap(),
+            None,
+            vec![],
+            WalletConfig {
+                rpc_url: "https://mainnet.base.org".to_string(),
+                yolo_enabled: false,
+                state_dir: state_dir.to_path_buf(),
+                kek: Zeroizing::new(kek),
+                spending_limit_config: SpendingLimitConfig::default(),
+            },
+        );
+        NodeServiceImpl::new(state, "secret-token".to_string())
+    }
+
+    fn with_token<T>(msg: T, token: &str) -> tonic::Request<T> {
+        let mut req = tonic::Request::new(msg);
+        req.metadata_mut()
+            .insert("authorization", format!("Bearer {token}").parse().unwrap());
+        req
+    }
+
+    #[tokio::test]
+    async fn rpcs_need_a_token_and_get_its_role() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("access.json"),
+            r#"{"tokens":[{"name":"dashboard","token":"view-token","role":"view_only"}]}"#,
+        )
+        .unwrap();
+        let svc = make_service(dir.path());
+
+        let err = svc
+            .health(tonic::Request::new(node_pb::HealthRequest {}))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+
+        let health = svc
+            .health(with_token(node_pb::HealthRequest {}, "view-token"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(health.healthy);
+
+        let post = node_pb::PostFeedRequest { body: "hi".into() };
+        let err = svc
+            .post_feed(with_token(post, "view-token"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::PermissionDenied);
+
+        let identity = svc
+            .identity(with_token(node_pb::IdentityRequest {}, "secret-token"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(identity.node_id, svc.state.identity.node_id);
+    }
+
+    #[tokio::test]
+    async fn daemon_errors_keep_their_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let svc = make_service(dir.path());
+
+        let ack = node_pb::InboxAckRequest {
+            message_id: "missing".into(),
+        };
+        let err = svc
+            .inbox_ack(with_token(ack, "secret-token"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+        assert_eq!(
+            err.metadata()
+                .get(ERROR_CODE_HEADER)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "not_found"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Peer inactivity: with `idle_after_secs` set in `node_config.json`, a
+//! peer that has messaged this node and then goes quiet for that long gets
+//! an [`Event::PeerIdle`], and its next message an [`Event::PeerActive`].
+//! Peers are tracked from their first message after startup.
+
+use super::NodeState;
+use agentbook::protocol::Event;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the watcher looks for peers that went quiet.
+const WATCH_TICK: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+struct Peer {
+    last_seen_ms: u64,
+    idle: bool,
+}
+
+/// When each peer was last heard from.
+#[derive(Debug, Default)]
+pub struct Activity {
+    /// From `node_config.json`; `None` turns idle detection off.
+    pub idle_after_ms: Option<u64>,
+    peers: HashMap<String, Peer>,
+}
+
+impl Activity {
+    /// Note a message from `node_id`. Returns whether the peer had been
+    /// idle.
+    fn seen(&mut self, node_id: &str, now_ms: u64) -> bool {
+        let peer = self.peers.entry(node_id.to_string()).or_insert(Peer {
+            last_seen_ms: now_ms,
+            idle: false,
+        });
+        peer.last_seen_ms = peer.last_seen_ms.max(now_ms);
+        std::mem::replace(&mut peer.idle, false)
+    }
+
+    /// Mark peers quiet for longer than the threshold as idle, returning
+    /// each newly idle peer and how long it has been quiet.
+    fn newly_idle(&mut self, now_ms: u64) -> Vec<(String, u64)> {
+        let Some(idle_after_ms) = self.idle_after_ms else {
+            return Vec::new();
+        };
+        let mut idle = Vec::new();
+        for (node_id, peer) in &mut self.peers {
+            let quiet_ms = now_ms.saturating_sub(peer.last_seen_ms);
+            if !peer.idle && quiet_ms >= idle_after_ms {
+                peer.idle = true;
+                idle.push((node_id.clone(), quiet_ms));
+            }
+        }
+        idle.sort();
+        idle
+    }
+}
+
+/// Record a message from `node_id`, emitting `PeerActive` if it had gone
+/// idle.
+pub async fn record(state: &Arc<NodeState>, node_id: &str) {
+    let now_ms = state.clock.now_ms();
+    let was_idle = state.activity.lock().await.seen(node_id, now_ms);
+    if was_idle {
+        let _ = state.event_tx.send(Event::PeerActive {
+            node_id: node_id.to_string(),
+        });
+    }
+}
+
+/// Emit `PeerIdle` for every peer that went quiet since the last check.
+pub async fn check_once(state: &Arc<NodeState>) {
+    let now_ms = state.clock.now_ms();
+    let idle = state.activity.lock().await.newly_idle(now_ms);
+    for (node_id, idle_ms) in idle {
+        tracing::info!(node_id, idle_ms, "peer went idle");
+        let _ = state.event_tx.send(Event::PeerIdle { node_id, idle_ms });
+    }
+}
+
+/// Check for idle peers every [`WATCH_TICK`] until the daemon shuts down.
+pub async fn watch_loop(state: Arc<NodeState>) {
+    let mut interval = tokio::time::interval(WATCH_TICK);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = state.lifecycle.shutdown_requested() => return,
+        }
+        check_once(&state).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peers_go_idle_once_and_wake_on_the_next_message() {
+        let mut activity = Activity::default();
+        activity.seen("0xa", 0);
+        activity.seen("0xb", 50_000);
+        assert!(activity.newly_idle(100_000).is_empty(), "detection is off");
+
+        activity.idle_after_ms = Some(60_000);
+        assert_eq!(activity.newly_idle(100_000), [("0xa".to_string(), 100_000)]);
+        assert!(activity.newly_idle(105_000).is_empty());
+        assert_eq!(activity.newly_idle(110_000), [("0xb".to_string(), 60_000)]);
+
+        assert!(activity.seen("0xa", 120_000));
+        assert!(!activity.seen("0xa", 121_000));
+        assert!(activity.newly_idle(150_000).is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+//! Runtime-adjustable settings: the log filter, ingress rate budgets,
+//! webhooks (and their secrets), inbox retention, whether plaintext is
+//! allowed, message routing, outgoing message templates and peer idle
+//! detection.
+//!
+//! Settings live in `node_config.json` in the state directory. The node
+//! applies the file at startup and again on SIGHUP, and `ConfigSet` edits it
+//! over the socket, so none of them need a restart.
+
+use super::{NodeState, error_response, ok_response, retention, routing, templates};
+use crate::{telemetry, webhooks};
+use agentbook::protocol::{IngressBudget, NodeConfig, Response};
+use agentbook_mesh::ingress_limits::{RateBudget, RateClass};
+use agentbook_mesh::state_file;
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+const CONFIG_FILE: &str = "node_config.json";
+
+/// Read `node_config.json`; a missing file means all defaults.
+pub fn load_config(state_dir: &Path) -> Result<NodeConfig> {
+    let config = state_file::read(&state_dir.join(CONFIG_FILE), |data| {
+        serde_json::from_str(data).context("invalid node_config.json")
+    })?;
+    Ok(config.unwrap_or_default())
+}
+
+fn save_config(state_dir: &Path, config: &NodeConfig) -> Result<()> {
+    let data = serde_json::to_string_pretty(config)?;
+    state_file::write(&state_dir.join(CONFIG_FILE), data)
+}
+
+fn parse_budget(budget: &IngressBudget) -> Result<(RateClass, RateBudget)> {
+    let Some(class) = RateClass::parse(&budget.message_class) else {
+        bail!("unknown message class {:?}", budget.message_class);
+    };
+    if budget.capacity == 0 {
+        bail!("{} capacity must be at least 1", budget.message_class);
+    }
+    if !budget.per_second.is_finite() || budget.per_second <= 0.0 {
+        bail!("{} per_second must be positive", budget.message_class);
+    }
+    Ok((
+        class,
+        RateBudget {
+            capacity: budget.capacity,
+            per_second: budget.per_second,
+        },
+    ))
+}
+
+/// Validate `config` in full, then make it the running configuration.
+/// Classes without an override go back to their default budget.
+async fn apply(state: &Arc<NodeState>, config: &NodeConfig) -> Result<()> {
+    let overrides = config
+        .ingress_budgets
+        .iter()
+        .map(parse_budget)
+        .collect::<Result<Vec<_>>>()?;
+    for hook in &config.webhooks {
+        webhooks::validate(hook)?;
+    }
+    retention::validate(&config.retention)?;
+    for rule in &config.routes {
+        routing::validate(rule)?;
+    }
+    templates::validate_all(&config.templates)?;
+    if config.idle_after_secs == Some(0) {
+        bail!("idle_after_secs must be at least 1");
+    }
+    let hooks = webhooks::resolve_secrets(&config.webhooks).await?;
+    telemetry::set_log_filter(config.log_level.as_deref())?;
+    *state.webhooks.lock().await = hooks;
+    state.retention.lock().await.policy = config.retention;
+    state
+        .require_encryption
+        .store(config.require_encryption, Ordering::Relaxed);
+    *state.routes.lock().await = config.routes.clone();
+    *state.templates.lock().await = config.templates.clone();
+    state.activity.lock().await.idle_after_ms =
+        config.idle_after_secs.map(|s| s.saturating_mul(1000));
+
+    let mut ingress_limits = state.ingress_limits.lock().await;
+    for class in RateClass::ALL {
+        ingress_limits.set_budget(class, class.default_budget());
+    }
+    for (class, budget) in overrides {
+        ingress_limits.set_budget(class, budget);
+    }
+    Ok(())
+}
+
+/// Re-read `node_config.json` and apply it. On error the running
+/// configuration is left unchanged.
+pub async fn reload(state: &Arc<NodeState>) -> Result<()> {
+    let config = load_config(&state.wallet.state_dir)?;
+    apply(state, &config).await?;
+    tracing::info!(
+        log_level = config.log_level.as_deref().unwrap_or("default"),
+        ingress_overrides = config.ingress_budgets.len(),
+        webhooks = config.webhooks.len(),
+        retention = !config.retention.is_unlimited(),
+        require_encryption = config.require_encryption,
+        routes = config.routes.len(),
+        templates = config.templates.len(),
+        "configuration applied"
+    );
+    Ok(())
+}
+
+pub async fn handle_config(state: &Arc<NodeState>) -> Response {
+    let log_level = match telemetry::log_filter() {
+        Some(filter) => Some(filter),
+        None => load_config(&state.wallet.state_dir)
+            .ok()
+            .and_then(|config| config.log_level),
+    };
+    let ingress_budgets = state
+        .ingress_limits
+        .lock()
+        .await
+        .budgets()
+        .into_iter()
+        .map(|(class, budget)| IngressBudget {
+            message_class: class.as_str().to_string(),
+            capacity: budget.capacity,
+            per_second: budget.per_second,
+        })
+        .collect();
+    let webhooks = state
+        .webhooks
+        .lock()
+        .await
+        .iter()
+        .cloned()
+        .map(|mut hook| {
+            if hook.secret_ref.is_some() {
+                hook.secret = None;
+            } else if hook.secret.is_some() {
+                hook.secret = Some("redacted".to_string());
+            }
+            hook
+        })
+        .collect();
+    let config = NodeConfig {
+        log_level,
+        ingress_budgets,
+        webhooks,
+        retention: state.retention.lock().await.policy,
+        require_encryption: state.require_encryption.load(Ordering::Relaxed),
+        routes: state.routes.lock().await.clone(),
+        templates: state.templates.lock().await.clone(),
+        idle_after_secs: state
+            .activity
+            .lock()
+            .await
+            .idle_after_ms
+            .map(|ms| ms / 1000),
+    };
+    ok_response(Some(serde_json::to_value(config).unwrap()))
+}
+
+pub async fn handle_config_set(
+    state: &Arc<NodeState>,
+    log_level: Option<String>,
+    ingress_budgets: Vec<IngressBudget>,
+) -> Response {
+    let state_dir = &state.wallet.state_dir;
+    let mut config = match load_config(state_dir) {
+        Ok(config) => config,
+        Err(e) => return error_response("config_error", &format!("{e:#}")),
+    };
+    if log_level.is_some() {
+        config.log_level = log_level;
+    }
+    for budget in ingress_budgets {
+        config
+            .ingress_budgets
+            .retain(|existing| existing.message_class != budget.message_class);
+        config.ingress_budgets.push(budget);
+    }
+    config
+        .ingress_budgets
+        .sort_by(|a, b| a.message_class.cmp(&b.message_class));
+
+    if let Err(e) = apply(state, &config).await {
+        return error_response("invalid_config", &format!("{e:#}"));
+    }
+    if let Err(e) = save_config(state_dir, &config) {
+        return error_response("config_error", &format!("{e:#}"));
+    }
+    tracing::info!("configuration updated over the socket");
+    handle_config(state).await
+}
@@ -0,0 +1,155 @@
+//! The daemon log: a bounded, in-memory record of decisions that are
+//! otherwise only visible at debug log level — which inbound messages
+//! ingress accepted or rejected and why, relay reconnects, and what happened
+//! to each outbound delivery. `agentbook events --follow` tails it.
+
+use super::{NodeState, ok_response};
+use agentbook::protocol::{DaemonLogEntry, DaemonLogKind, Response};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Entries kept; older ones are dropped first.
+const CAPACITY: usize = 1000;
+
+/// Entries returned by one `DaemonLog` request when it doesn't say.
+const DEFAULT_LIMIT: usize = 100;
+
+/// The most recent [`CAPACITY`] entries.
+#[derive(Default)]
+pub struct DaemonLog {
+    inner: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    next_seq: u64,
+    entries: VecDeque<DaemonLogEntry>,
+}
+
+/// What to record; [`DaemonLog::record`] stamps the sequence number.
+#[derive(Default)]
+pub struct Record<'a> {
+    pub node_id: Option<&'a str>,
+    pub host: Option<&'a str>,
+    pub message_id: Option<&'a str>,
+    pub reason: Option<String>,
+}
+
+impl DaemonLog {
+    pub fn record(&self, at_ms: u64, kind: DaemonLogKind, record: Record<'_>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_seq += 1;
+        let seq = inner.next_seq;
+        if inner.entries.len() == CAPACITY {
+            inner.entries.pop_front();
+        }
+        inner.entries.push_back(DaemonLogEntry {
+            seq,
+            at_ms,
+            kind,
+            node_id: record.node_id.map(str::to_string),
+            host: record.host.map(str::to_string),
+            message_id: record.message_id.map(str::to_string),
+            reason: record.reason,
+        });
+    }
+
+    /// Up to `limit` entries after `after_seq`, oldest first. Without
+    /// `after_seq`, the latest `limit` entries.
+    pub fn since(&self, after_seq: Option<u64>, limit: usize) -> Vec<DaemonLogEntry> {
+        let inner = self.inner.lock().unwrap();
+        match after_seq {
+            Some(after) => inner
+                .entries
+                .iter()
+                .filter(|e| e.seq > after)
+                .take(limit)
+                .cloned()
+                .collect(),
+            None => {
+                let skip = inner.entries.len().saturating_sub(limit);
+                inner.entries.iter().skip(skip).cloned().collect()
+            }
+        }
+    }
+}
+
+/// Shorthand for recording against the node's clock.
+pub fn record(state: &NodeState, kind: DaemonLogKind, record: Record<'_>) {
+    state.daemon_log.record(state.clock.now_ms(), kind, record);
+}
+
+pub async fn handle_daemon_log(
+    state: &Arc<NodeState>,
+    after_seq: Option<u64>,
+    limit: Option<usize>,
+) -> Response {
+    let entries = state
+        .daemon_log
+        .since(after_seq, limit.unwrap_or(DEFAULT_LIMIT));
+    ok_response(Some(serde_json::to_value(entries).unwrap()))
+}
+
+/// Record each relay reconnect until the transport shuts down.
+pub async fn watch_relay(state: Arc<NodeState>) {
+    let Some(transport) = &state.transport else {
+        return;
+    };
+    let mut reconnects = transport.subscribe_reconnects();
+    loop {
+        let reconnect = tokio::select! {
+            received = reconnects.recv() => match received {
+                Ok(reconnect) => reconnect,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+            },
+            _ = state.lifecycle.shutdown_requested() => return,
+        };
+        record(
+            &state,
+            DaemonLogKind::RelayReconnect,
+            Record {
+                host: Some(&reconnect.host),
+                reason: Some(reconnect.error),
+                ..Record::default()
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected(log: &DaemonLog, at_ms: u64) {
+        log.record(
+            at_ms,
+            DaemonLogKind::IngressRejected,
+            Record {
+                node_id: Some("0xa"),
+                reason: Some("not following".into()),
+                ..Record::default()
+            },
+        );
+    }
+
+    #[test]
+    fn keeps_the_latest_entries_and_resumes_after_a_seq() {
+        let log = DaemonLog::default();
+        for at_ms in 0..CAPACITY as u64 + 5 {
+            rejected(&log, at_ms);
+        }
+
+        let latest = log.since(None, 3);
+        let seqs: Vec<u64> = latest.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, [1003, 1004, 1005]);
+        assert_eq!(latest[0].node_id.as_deref(), Some("0xa"));
+
+        // The oldest entries were dropped, so following from seq 0 starts
+        // at the first one still kept.
+        assert_eq!(log.since(Some(0), 1)[0].seq, 6);
+        assert!(log.since(Some(1005), 10).is_empty());
+        rejected(&log, 2_000);
+        assert_eq!(log.since(Some(1005), 10)[0].seq, 1006);
+    }
+}
@@ -0,0 +1,128 @@
+//! Draining shutdown: stop taking new requests, let in-flight work finish,
+//! persist state, then let the socket server exit.
+
+use super::{NodeState, error_response, ok_response};
+use agentbook::protocol::{DrainResult, Request, Response};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// How long a drain waits for in-flight work by default.
+pub const DEFAULT_DRAIN_TIMEOUT_MS: u64 = 5 * 60 * 1000;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often changed ingress rate limits are written to disk.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Tracks in-flight requests and whether the node is shutting down.
+#[derive(Default)]
+pub struct Lifecycle {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    shutdown: CancellationToken,
+}
+
+/// Marks one request as in flight until dropped.
+pub struct RequestGuard<'a>(&'a Lifecycle);
+
+impl Drop for RequestGuard<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Lifecycle {
+    /// Admit a request, or `None` once draining. Health checks are always
+    /// answered so orchestrators can watch the drain, and `Shutdown` still
+    /// cuts a drain short.
+    pub fn admit(&self, request: &Request) -> Option<RequestGuard<'_>> {
+        if self.is_draining() && !matches!(request, Request::Health | Request::Shutdown) {
+            return None;
+        }
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        Some(RequestGuard(self))
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Ask the socket server to stop.
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Resolves once [`Lifecycle::shutdown`] has been called.
+    pub async fn shutdown_requested(&self) {
+        self.shutdown.cancelled().await;
+    }
+}
+
+/// Stop admitting requests and wait until in-flight requests and queued
+/// outbound messages are done, or `timeout_ms` passes.
+pub async fn handle_drain(state: &Arc<NodeState>, timeout_ms: Option<u64>) -> Response {
+    if state.lifecycle.draining.swap(true, Ordering::SeqCst) {
+        return error_response("draining", "node is already draining");
+    }
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_DRAIN_TIMEOUT_MS));
+    let deadline = tokio::time::Instant::now() + timeout;
+    tracing::info!(timeout_ms = timeout.as_millis() as u64, "draining");
+
+    let (drained, outbox_pending) = loop {
+        // Queued messages can only go out while a relay is attached.
+        let outbox_pending = if state.transport.is_some() {
+            state.outbox.lock().await.len()
+        } else {
+            0
+        };
+        if state.lifecycle.in_flight() == 0 && outbox_pending == 0 {
+            break (true, 0);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            break (false, outbox_pending);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    };
+
+    persist_ingress_limits(state).await;
+
+    let result = DrainResult {
+        drained,
+        in_flight: state.lifecycle.in_flight(),
+        outbox_pending,
+    };
+    tracing::info!(
+        drained,
+        in_flight = result.in_flight,
+        outbox_pending,
+        "drain finished"
+    );
+    ok_response(Some(serde_json::to_value(result).unwrap()))
+}
+
+/// Write ingress rate limits if they changed since the last write.
+pub async fn persist_ingress_limits(state: &NodeState) {
+    if let Err(e) = state.ingress_limits.lock().await.flush() {
+        tracing::warn!(err = %e, "failed to persist ingress rate limits");
+    }
+}
+
+/// Persist ingress rate limits every [`PERSIST_INTERVAL`], and once more
+/// when shutdown is requested.
+pub async fn persist_loop(state: Arc<NodeState>) {
+    let mut interval = tokio::time::interval(PERSIST_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = state.lifecycle.shutdown_requested() => break,
+        }
+        persist_ingress_limits(&state).await;
+    }
+    persist_ingress_limits(&state).await;
+}
@@ -0,0 +1,231 @@
+use super::{NodeState, error_response, now_ms, ok_response};
+use agentbook::protocol::{Event, InviteInfo, Response};
+use agentbook_mesh::crypto::{public_key_matches_node_id, verify_signature};
+use agentbook_mesh::envelope_schema;
+use agentbook_mesh::follow::FollowRecord;
+use agentbook_mesh::invite::{
+    accept_invite_at, create_signed_invite_at, validate_scopes, validate_ttl,
+};
+use agentbook_proto::mesh::v1 as mesh_pb;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Invite lifetime when the client does not ask for one (7 days).
+const DEFAULT_INVITE_TTL_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+
+pub async fn handle_invite_create(
+    state: &Arc<NodeState>,
+    ttl_ms: Option<u64>,
+    max_uses: Option<u32>,
+    mut scopes: Vec<String>,
+) -> Response {
+    if let Err(e) = validate_scopes(&scopes) {
+        return error_response("invalid_scope", &e.to_string());
+    }
+    let ttl_ms = ttl_ms.unwrap_or(DEFAULT_INVITE_TTL_MS);
+    if let Err(e) = validate_ttl(ttl_ms) {
+        return error_response("invalid_ttl", &e.to_string());
+    }
+    scopes.sort();
+    scopes.dedup();
+    let now = state.clock.now_ms();
+    let signed = match create_signed_invite_at(
+        &state.identity.node_id,
+        &state.identity.public_key_b64,
+        state.identity.secret_key(),
+        state.relay_hosts.clone(),
+        scopes,
+        ttl_ms,
+        now,
+    ) {
+        Ok(s) => s,
+        Err(e) => return error_response("invite_failed", &e.to_string()),
+    };
+    let token = match signed.encode() {
+        Ok(t) => t,
+        Err(e) => return error_response("invite_failed", &e.to_string()),
+    };
+
+    if let Err(e) = state
+        .invites
+        .lock()
+        .await
+        .record(&signed.payload, now, max_uses)
+    {
+        return error_response("invite_failed", &e.to_string());
+    }
+
+    ok_response(Some(serde_json::json!({
+        "token": token,
+        "token_id": signed.payload.token_id,
+        "expires_at_ms": signed.payload.expires_at_ms,
+    })))
+}
+
+pub async fn handle_invite_list(state: &Arc<NodeState>) -> Response {
+    let invites = state.invites.lock().await;
+    let list: Vec<InviteInfo> = invites
+        .list()
+        .iter()
+        .map(|i| InviteInfo {
+            token_id: i.token_id.clone(),
+            created_at_ms: i.created_at_ms,
+            expires_at_ms: i.expires_at_ms,
+            max_uses: i.max_uses,
+            redeemed_by: i.uses.iter().map(|u| u.node_id.clone()).collect(),
+            revoked: i.revoked,
+            scopes: i.scopes.clone(),
+        })
+        .collect();
+    ok_response(Some(serde_json::to_value(list).unwrap()))
+}
+
+pub async fn handle_invite_revoke(state: &Arc<NodeState>, token_id: &str) -> Response {
+    let mut invites = state.invites.lock().await;
+    match invites.revoke(token_id) {
+        Ok(true) => ok_response(None),
+        Ok(false) => error_response("not_found", &format!("no invite with id {token_id}")),
+        Err(e) => error_response("revoke_failed", &e.to_string()),
+    }
+}
+
+/// Accept an invite: follow the inviter, then send them the token so they
+/// can follow us back.
+pub async fn handle_invite_accept(state: &Arc<NodeState>, token: &str) -> Response {
+    let payload = match accept_invite_at(token, state.clock.now_ms()) {
+        Ok(p) => p,
+        Err(e) => return error_response("invalid_invite", &e.to_string()),
+    };
+    if payload.inviter_node_id == state.identity.node_id {
+        return error_response("invalid_invite", "cannot accept your own invite");
+    }
+    if !public_key_matches_node_id(&payload.inviter_public_key_b64, &payload.inviter_node_id) {
+        return error_response("invalid_invite", "inviter key does not match node id");
+    }
+
+    let record = FollowRecord {
+        node_id: payload.inviter_node_id.clone(),
+        public_key_b64: payload.inviter_public_key_b64.clone(),
+        username: None,
+        relay_hints: payload.relay_hosts.clone(),
+        followed_at_ms: now_ms(),
+        scopes: vec![],
+        alias: None,
+        groups: vec![],
+    };
+    if let Err(e) = state.follow_store.lock().await.follow(record) {
+        return error_response("follow_failed", &e.to_string());
+    }
+    let _ = super::social::notify_relay_follow(state, &payload.inviter_node_id).await;
+
+    let notified = match &state.transport {
+        Some(transport) => {
+            let ciphertext_b64 = token.to_string();
+            let signature_b64 = state
+                .identity
+                .sign(ciphertext_b64.as_bytes())
+                .unwrap_or_default();
+            let envelope = mesh_pb::Envelope {
+                message_id: Uuid::new_v4().to_string(),
+                from_node_id: state.identity.node_id.clone(),
+                to_node_id: payload.inviter_node_id.clone(),
+                from_public_key_b64: state.identity.public_key_b64.clone(),
+                message_type: mesh_pb::MessageType::InviteRedeem as i32,
+                ciphertext_b64,
+                nonce_b64: String::new(),
+                signature_b64,
+                timestamp_ms: now_ms(),
+                topic: None,
+                sealed: false,
+                schema_version: envelope_schema::CURRENT,
+            };
+            match transport.send_via_relay(envelope).await {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::warn!(err = %e, "failed to notify inviter");
+                    false
+                }
+            }
+        }
+        None => false,
+    };
+
+    ok_response(Some(serde_json::json!({
+        "inviter_node_id": payload.inviter_node_id,
+        "notified": notified,
+    })))
+}
+
+/// Handle an invite redemption from the node that accepted one of our
+/// invites. The issued-invite record is consulted before following back,
+/// so revoked, expired and used-up invites are refused.
+pub async fn process_inbound_invite_redeem(state: &Arc<NodeState>, envelope: mesh_pb::Envelope) {
+    if let Err(reason) = redeem(state, &envelope).await {
+        tracing::warn!(
+            from = %envelope.from_node_id,
+            msg_id = %envelope.message_id,
+            reason = %reason,
+            "invite redemption rejected"
+        );
+    }
+}
+
+async fn redeem(state: &Arc<NodeState>, envelope: &mesh_pb::Envelope) -> Result<(), String> {
+    if !verify_signature(
+        &envelope.from_public_key_b64,
+        envelope.ciphertext_b64.as_bytes(),
+        &envelope.signature_b64,
+    ) {
+        return Err("invalid signature".to_string());
+    }
+    // We are about to trust this key for the sender, so it must be theirs.
+    if !public_key_matches_node_id(&envelope.from_public_key_b64, &envelope.from_node_id) {
+        return Err("sender key does not match node id".to_string());
+    }
+
+    let now = state.clock.now_ms();
+    let payload = accept_invite_at(&envelope.ciphertext_b64, now).map_err(|e| e.to_string())?;
+    if payload.inviter_node_id != state.identity.node_id {
+        return Err("invite was issued by another node".to_string());
+    }
+
+    let mut follow_store = state.follow_store.lock().await;
+    if follow_store.is_blocked(&envelope.from_node_id) {
+        return Err("sender is blocked".to_string());
+    }
+    if follow_store.is_revoked(&envelope.from_node_id) {
+        return Err("sender key revoked".to_string());
+    }
+
+    state
+        .invites
+        .lock()
+        .await
+        .redeem(&payload.token_id, &envelope.from_node_id, now)
+        .map_err(|e| e.to_string())?;
+
+    // Scopes only restrict nodes we follow because of the invite; a node we
+    // already followed keeps its existing access.
+    if !follow_store.is_following(&envelope.from_node_id) {
+        follow_store
+            .follow(FollowRecord {
+                node_id: envelope.from_node_id.clone(),
+                public_key_b64: envelope.from_public_key_b64.clone(),
+                username: None,
+                relay_hints: vec![],
+                followed_at_ms: now_ms(),
+                scopes: payload.scopes.clone(),
+                alias: None,
+                groups: vec![],
+            })
+            .map_err(|e| e.to_string())?;
+    }
+    drop(follow_store);
+
+    tracing::info!(from = %envelope.from_node_id, token_id = %payload.token_id, "invite redeemed");
+    let _ = super::social::notify_relay_follow(state, &envelope.from_node_id).await;
+    let _ = state.event_tx.send(Event::NewFollower {
+        node_id: envelope.from_node_id.clone(),
+    });
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use super::messaging::send_or_queue;
+use super::social::fetch_followers_from_relay;
+use super::{NodeState, error_response, ok_response};
+use agentbook::protocol::{Event, KeyRotationInfo, Response};
+use agentbook_crypto::rate_limit::CheckResult;
+use agentbook_mesh::crypto::verify_signature;
+use agentbook_mesh::envelope_schema;
+use agentbook_mesh::ingress_limits::RateClass;
+use agentbook_mesh::key_notice::{
+    KeyRevocationNotice, KeyRotationNotice, decode_notice, encode_notice,
+};
+use agentbook_proto::mesh::v1 as mesh_pb;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// How long messages to a rotated-away key are still accepted by default.
+pub const DEFAULT_ROTATION_GRACE_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+
+/// Rotate this node's key and announce it to every follow and follower.
+///
+/// The notice is signed with the current (old) key, which is what peers
+/// check before moving their follow record. Notices to unreachable peers go
+/// through the outbox like DMs. The running node keeps using the old key;
+/// the new one is picked up on the next start.
+pub async fn handle_rotate_key(state: &Arc<NodeState>, grace_ms: Option<u64>) -> Response {
+    let Some(transport) = &state.transport else {
+        return error_response("no_relay", "not connected to any relay");
+    };
+
+    // Collect peers before touching the key, so a relay failure leaves
+    // nothing half-done.
+    let mut peers: Vec<String> = state
+        .follow_store
+        .lock()
+        .await
+        .following()
+        .iter()
+        .map(|f| f.node_id.clone())
+        .collect();
+    match fetch_followers_from_relay(state, &state.identity.node_id).await {
+        Ok(followers) => peers.extend(followers.into_iter().map(|f| f.node_id)),
+        Err(e) => return error_response("relay_unavailable", &e),
+    }
+    peers.sort();
+    peers.dedup();
+
+    let now = state.clock.now_ms();
+    let grace_until_ms = now.saturating_add(grace_ms.unwrap_or(DEFAULT_ROTATION_GRACE_MS));
+    let new_identity = match state
+        .identity
+        .rotate(&state.wallet.kek, now, grace_until_ms)
+    {
+        Ok(identity) => identity,
+        Err(e) => return error_response("rotate_failed", &e.to_string()),
+    };
+    tracing::info!(old = %state.identity.node_id, new = %new_identity.node_id, "node key rotated");
+
+    let payload_b64 = match KeyRotationNotice::create(&state.identity.node_id, &new_identity, now)
+        .and_then(|notice| encode_notice(&notice))
+    {
+        Ok(payload) => payload,
+        Err(e) => return error_response("rotate_failed", &e.to_string()),
+    };
+    let signature_b64 = match state.identity.sign(payload_b64.as_bytes()) {
+        Ok(sig) => sig,
+        Err(e) => return error_response("rotate_failed", &e.to_string()),
+    };
+
+    let mut notified = 0;
+    for peer in peers {
+        let envelope = mesh_pb::Envelope {
+            message_id: Uuid::new_v4().to_string(),
+            from_node_id: state.identity.node_id.clone(),
+            to_node_id: peer.clone(),
+            from_public_key_b64: state.identity.public_key_b64.clone(),
+            message_type: mesh_pb::MessageType::KeyRotation as i32,
+            ciphertext_b64: payload_b64.clone(),
+            nonce_b64: String::new(),
+            signature_b64: signature_b64.clone(),
+            timestamp_ms: now,
+            topic: None,
+            sealed: false,
+            schema_version: envelope_schema::CURRENT,
+        };
+        match send_or_queue(state, transport, envelope).await {
+            Ok(_) => notified += 1,
+            Err(e) => tracing::warn!(peer = %peer, err = %e, "failed to send key rotation notice"),
+        }
+    }
+
+    let info = KeyRotationInfo {
+        old_node_id: state.identity.node_id.clone(),
+        new_node_id: new_identity.node_id,
+        new_public_key_b64: new_identity.public_key_b64,
+        grace_until_ms,
+        notified,
+    };
+    ok_response(Some(serde_json::to_value(info).unwrap()))
+}
+
+/// Apply a key rotation or revocation notice from a followed node.
+///
+/// Notices are only honoured when the envelope is signed by the key we
+/// currently have on record for the sender, so only the holder of the old
+/// key can move (or kill) its follow record.
+pub async fn process_inbound_key_notice(state: &Arc<NodeState>, envelope: mesh_pb::Envelope) {
+    if let Err(reason) = apply_key_notice(state, &envelope).await {
+        tracing::warn!(
+            from = %envelope.from_node_id,
+            msg_id = %envelope.message_id,
+            reason = %reason,
+            "key notice rejected"
+        );
+    }
+}
+
+async fn apply_key_notice(
+    state: &Arc<NodeState>,
+    envelope: &mesh_pb::Envelope,
+) -> Result<(), String> {
+    let message_type = mesh_pb::MessageType::try_from(envelope.message_type)
+        .map_err(|_| "unknown message type".to_string())?;
+
+    let mut follow_store = state.follow_store.lock().await;
+    if follow_store.is_blocked(&envelope.from_node_id) {
+        return Err("sender is blocked".to_string());
+    }
+    let Some(record) = follow_store.get(&envelope.from_node_id) else {
+        return Err("not following sender".to_string());
+    };
+    if record.public_key_b64 != envelope.from_public_key_b64 {
+        return Err("sender key does not match follow record".to_string());
+    }
+    if !verify_signature(
+        &record.public_key_b64,
+        envelope.ciphertext_b64.as_bytes(),
+        &envelope.signature_b64,
+    ) {
+        return Err("invalid signature".to_string());
+    }
+    {
+        let mut ingress_limits = state.ingress_limits.lock().await;
+        let result = ingress_limits.check(&envelope.from_node_id, RateClass::KeyNotice);
+        if let CheckResult::RateLimited | CheckResult::Banned { .. } = result {
+            return Err("rate limited".to_string());
+        }
+    }
+
+    let event = match message_type {
+        mesh_pb::MessageType::KeyRotation => {
+            let notice: KeyRotationNotice =
+                decode_notice(&envelope.ciphertext_b64).map_err(|e| e.to_string())?;
+            if notice.old_node_id != envelope.from_node_id {
+                return Err("rotation notice is for a different node".to_string());
+            }
+            notice.verify().map_err(|e| e.to_string())?;
+            follow_store
+                .rotate_key(
+                    &notice.old_node_id,
+                    &notice.new_node_id,
+                    &notice.new_public_key_b64,
+                )
+                .map_err(|e| e.to_string())?;
+            tracing::info!(old = %notice.old_node_id, new = %notice.new_node_id, "followed node rotated its key");
+            Event::KeyRotated {
+                old_node_id: notice.old_node_id,
+                new_node_id: notice.new_node_id,
+            }
+        }
+        mesh_pb::MessageType::KeyRevocation => {
+            let notice: KeyRevocationNotice =
+                decode_notice(&envelope.ciphertext_b64).map_err(|e| e.to_string())?;
+            if notice.node_id != envelope.from_node_id {
+                return Err("revocation notice is for a different node".to_string());
+            }
+            follow_store
+                .revoke_key(&notice.node_id)
+                .map_err(|e| e.to_string())?;
+            tracing::warn!(node = %notice.node_id, reason = ?notice.reason, "followed node revoked its key");
+            Event::KeyRevoked {
+                node_id: notice.node_id,
+            }
+        }
+        _ => return Err("not a key notice".to_string()),
+    };
+    drop(follow_store);
+
+    let _ = state.event_tx.send(event);
+    Ok(())
+}
@@ -1,17 +1,43 @@
 use super::social::fetch_followers_from_relay;
-use super::{NodeState, error_response, now_ms, ok_response, to_protocol_message_type};
-use agentbook::protocol::{InboxEntry, Response};
+use super::{NodeState, daemon_log, error_response, now_ms, ok_response, to_protocol_message_type};
+use agentbook::protocol::{
+    Attachment, BroadcastDelivery, DaemonLogKind, Event, InboxEntry, Response,
+};
+use agentbook_mesh::attachment;
 use agentbook_mesh::crypto::{decrypt_with_key, encrypt_with_key, random_key_material};
+use agentbook_mesh::dm_payload::DmPayload;
+use agentbook_mesh::envelope_schema;
 use agentbook_mesh::follow::FollowStore;
 use agentbook_mesh::identity::NodeIdentity;
-use agentbook_mesh::inbox::MessageType as MeshMessageType;
+use agentbook_mesh::inbox::{InboxMessage, MessageType as MeshMessageType};
+use agentbook_mesh::padding;
+use agentbook_mesh::transport::MeshTransport;
 use agentbook_proto::mesh::v1 as mesh_pb;
 use base64::Engine;
+use futures_util::StreamExt;
 use k256::PublicKey;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
 
-pub async fn handle_send_dm(state: &Arc<NodeState>, to: &str, body: &str) -> Response {
+/// How many DMs a broadcast sends at once.
+const BROADCAST_CONCURRENCY: usize = 8;
+
+pub async fn handle_send_dm(
+    state: &Arc<NodeState>,
+    to: &str,
+    body: &str,
+    attachments: &[Attachment],
+    in_reply_to: Option<&str>,
+    reply_within_secs: Option<u64>,
+) -> Response {
+    if reply_within_secs == Some(0) {
+        return error_response("invalid_request", "reply_within_secs must be at least 1");
+    }
+    let attachments = to_mesh_attachments(attachments);
+    if let Err(e) = attachment::validate(&attachments) {
+        return error_response("invalid_attachment", &format!("{e:#}"));
+    }
     let transport = match &state.transport {
         Some(t) => t,
         None => return error_response("no_relay", "not connected to any relay"),
@@ -24,6 +50,26 @@ pub async fn handle_send_dm(state: &Arc<NodeState>, to: &str, body: &str) -> Res
     };
     let resolved_to = resolved.node_id.clone();
 
+    // A reply joins its parent's thread, which is named after the message
+    // that started it.
+    let thread_id = match in_reply_to {
+        Some(parent_id) => {
+            let inbox = state.inbox.lock().await;
+            match inbox.get(parent_id) {
+                Some(parent) => Some(
+                    parent
+                        .thread_id
+                        .clone()
+                        .unwrap_or_else(|| parent.message_id.clone()),
+                ),
+                None => {
+                    return error_response("not_found", &format!("message {parent_id} not found"));
+                }
+            }
+        }
+        None => None,
+    };
+
     // Look up recipient's public key from follow store
     let peer_public_key = {
         let follow_store = state.follow_store.lock().await;
@@ -33,9 +79,28 @@ pub async fn handle_send_dm(state: &Arc<NodeState>, to: &str, body: &str) -> Res
         }
     };
 
-    // Derive ECDH shared key and encrypt message body
-    let shared_key = state.identity.derive_shared_key(&peer_public_key);
-    let (ciphertext_b64, nonce_b64) = match encrypt_with_key(&shared_key, body.as_bytes()) {
+    // Derive ECDH shared key and encrypt message body. In privacy mode the
+    // body is sealed instead, hiding its length and message type from the relay.
+    let sealed = transport.privacy_mode();
+    let payload = DmPayload {
+        body: body.to_string(),
+        attachments,
+        in_reply_to: in_reply_to.map(str::to_string),
+        thread_id,
+    };
+    let plaintext = payload.encode();
+    let encrypted = if sealed {
+        seal_payload(
+            &state.identity,
+            &peer_public_key,
+            mesh_pb::MessageType::DmText,
+            &plaintext,
+        )
+    } else {
+        let shared_key = state.identity.derive_shared_key(&peer_public_key);
+        encrypt_with_key(&shared_key, plaintext.as_bytes()).map_err(|e| e.to_string())
+    };
+    let (ciphertext_b64, nonce_b64) = match encrypted {
         Ok(pair) => pair,
         Err(e) => return error_response("encryption_error", &format!("encryption failed: {e}")),
     };
@@ -52,35 +117,170 @@ pub async fn handle_send_dm(state: &Arc<NodeState>, to: &str, body: &str) -> Res
         from_node_id: state.identity.node_id.clone(),
         to_node_id: resolved_to.clone(),
         from_public_key_b64: state.identity.public_key_b64.clone(),
-        message_type: mesh_pb::MessageType::DmText as i32,
+        message_type: wire_message_type(mesh_pb::MessageType::DmText, sealed),
         ciphertext_b64,
         nonce_b64,
         signature_b64,
         timestamp_ms: now_ms(),
         topic: None,
+        sealed,
+        schema_version: envelope_schema::CURRENT,
     };
 
-    match transport.send_via_relay(envelope).await {
-        Ok(()) => {
-            let own_msg = agentbook_mesh::inbox::InboxMessage {
-                message_id: msg_id.clone(),
-                from_node_id: state.identity.node_id.clone(),
-                from_public_key_b64: state.identity.public_key_b64.clone(),
-                to_node_id: Some(resolved_to),
-                topic: None,
-                body: body.to_string(),
-                timestamp_ms: now_ms(),
-                acked: true,
-                message_type: MeshMessageType::DmText,
-            };
-            let mut inbox = state.inbox.lock().await;
-            if let Err(e) = inbox.push(own_msg) {
-                tracing::error!(err = %e, "failed to store own DM in inbox");
-            }
-            ok_response(Some(serde_json::json!({ "message_id": msg_id })))
+    let queued = match send_or_queue(state, transport, envelope).await {
+        Ok(queued) => queued,
+        Err(e) => return error_response("send_failed", &e),
+    };
+
+    if let Some(secs) = reply_within_secs {
+        let deadline_ms = state.clock.now_ms() + secs.saturating_mul(1000);
+        state
+            .pending_replies
+            .lock()
+            .await
+            .expect(&msg_id, &resolved_to, deadline_ms);
+    }
+
+    let own_msg = InboxMessage {
+        message_id: msg_id.clone(),
+        from_node_id: state.identity.node_id.clone(),
+        from_public_key_b64: state.identity.public_key_b64.clone(),
+        to_node_id: Some(resolved_to),
+        topic: None,
+        body: payload.body,
+        timestamp_ms: now_ms(),
+        acked: true,
+        message_type: MeshMessageType::DmText,
+        attachments: payload.attachments,
+        in_reply_to: payload.in_reply_to,
+        thread_id: payload.thread_id,
+    };
+    let mut inbox = state.inbox.lock().await;
+    if let Err(e) = inbox.push(own_msg) {
+        tracing::error!(err = %e, "failed to store own DM in inbox");
+    }
+    ok_response(Some(
+        serde_json::json!({ "message_id": msg_id, "queued": queued }),
+    ))
+}
+
+pub async fn handle_broadcast_dm(
+    state: &Arc<NodeState>,
+    body: &str,
+    mutual_only: bool,
+    group: Option<&str>,
+) -> Response {
+    if state.transport.is_none() {
+        return error_response("no_relay", "not connected to any relay");
+    }
+    let mut recipients: Vec<String> = {
+        let follow_store = state.follow_store.lock().await;
+        match group {
+            Some(group) => follow_store
+                .group_members(group)
+                .into_iter()
+                .map(|f| f.node_id.clone())
+                .collect(),
+            None => follow_store
+                .following()
+                .iter()
+                .map(|f| f.node_id.clone())
+                .collect(),
         }
-        Err(e) => error_response("send_failed", &e.to_string()),
+    };
+    if mutual_only {
+        let followers = match fetch_followers_from_relay(state, &state.identity.node_id).await {
+            Ok(entries) => entries,
+            Err(e) => return error_response("relay_unavailable", &e),
+        };
+        recipients.retain(|node_id| followers.iter().any(|f| &f.node_id == node_id));
+    }
+    if recipients.is_empty() {
+        return error_response("no_recipients", "no followed nodes to broadcast to");
     }
+
+    // Each copy goes through `handle_send_dm`, so it is encrypted for its
+    // recipient and queued in the outbox if it can't be sent now.
+    let deliveries: Vec<BroadcastDelivery> = futures_util::stream::iter(recipients)
+        .map(|node_id| async move {
+            match handle_send_dm(state, &node_id, body, &[], None, None).await {
+                Response::Ok { data } => {
+                    let data = data.unwrap_or_default();
+                    BroadcastDelivery {
+                        node_id,
+                        message_id: data["message_id"].as_str().map(str::to_string),
+                        queued: data["queued"].as_bool().unwrap_or(false),
+                        error: None,
+                    }
+                }
+                Response::Error { code, message } => {
+                    tracing::warn!(to = %node_id, code, message, "broadcast DM failed");
+                    BroadcastDelivery {
+                        node_id,
+                        message_id: None,
+                        queued: false,
+                        error: Some(message),
+                    }
+                }
+                other => BroadcastDelivery {
+                    node_id,
+                    message_id: None,
+                    queued: false,
+                    error: Some(format!("unexpected response: {other:?}")),
+                },
+            }
+        })
+        .buffered(BROADCAST_CONCURRENCY)
+        .collect()
+        .await;
+    ok_response(Some(serde_json::to_value(deliveries).unwrap()))
+}
+
+/// Send an envelope, or put it in the outbox if the relay is down or earlier
+/// messages to the same peer are still waiting, so per-peer ordering is
+/// preserved. Returns whether the envelope was queued.
+pub(crate) async fn send_or_queue(
+    state: &Arc<NodeState>,
+    transport: &MeshTransport,
+    envelope: mesh_pb::Envelope,
+) -> Result<bool, String> {
+    let queue_reason = if state
+        .outbox
+        .lock()
+        .await
+        .has_pending_for(&envelope.to_node_id)
+    {
+        Some("queued behind earlier undelivered messages".to_string())
+    } else if !transport.is_connected() {
+        Some("no relay connected".to_string())
+    } else {
+        transport
+            .send_via_relay(envelope.clone())
+            .await
+            .err()
+            .map(|e| e.to_string())
+    };
+    let delivery = |reason| daemon_log::Record {
+        node_id: Some(&envelope.to_node_id),
+        message_id: Some(&envelope.message_id),
+        reason,
+        ..Default::default()
+    };
+    let Some(reason) = queue_reason else {
+        daemon_log::record(state, DaemonLogKind::DeliverySent, delivery(None));
+        return Ok(false);
+    };
+    daemon_log::record(
+        state,
+        DaemonLogKind::DeliveryQueued,
+        delivery(Some(reason.clone())),
+    );
+    let mut outbox = state.outbox.lock().await;
+    outbox
+        .enqueue(&envelope, state.clock.now_ms(), Some(reason))
+        .map_err(|e| e.to_string())?;
+    tracing::info!(msg_id = %envelope.message_id, to = %envelope.to_node_id, "message queued for retry");
+    Ok(true)
 }
 
 pub async fn handle_post_feed(state: &Arc<NodeState>, body: &str) -> Response {
@@ -123,6 +323,7 @@ pub async fn handle_post_feed(state: &Arc<NodeState>, body: &str) -> Response {
         };
 
     let timestamp = now_ms();
+    let sealed = transport.privacy_mode();
 
     // Build and send envelopes to all followers concurrently.
     // Each follower gets the content key wrapped with their ECDH shared key.
@@ -140,11 +341,32 @@ pub async fn handle_post_feed(state: &Arc<NodeState>, body: &str) -> Response {
                 }
             };
 
-            // Wrap the content key with the per-follower ECDH shared key
-            let shared_key = state.identity.derive_shared_key(&peer_public_key);
-            let (wrapped_key_b64, wrapped_key_nonce_b64) =
-                match encrypt_with_key(&shared_key, &content_key) {
+            // Privacy mode: seal the body per follower so feed envelopes are
+            // indistinguishable from DMs on the wire.
+            let (combined_ciphertext, nonce_b64) = if sealed {
+                match seal_payload(
+                    &state.identity,
+                    &peer_public_key,
+                    mesh_pb::MessageType::FeedPost,
+                    body,
+                ) {
                     Ok(pair) => pair,
+                    Err(e) => {
+                        tracing::warn!(
+                            to = %follower_node_id, err = %e,
+                            "skipping feed post: failed to seal payload"
+                        );
+                        return None;
+                    }
+                }
+            } else {
+                match wrap_feed_content(
+                    &state.identity,
+                    &peer_public_key,
+                    &content_key,
+                    &content_ciphertext_b64,
+                ) {
+                    Ok(combined) => (combined, content_nonce_b64.clone()),
                     Err(e) => {
                         tracing::warn!(
                             to = %follower_node_id, err = %e,
@@ -152,12 +374,8 @@ pub async fn handle_post_feed(state: &Arc<NodeState>, body: &str) -> Response {
                         );
                         return None;
                     }
-                };
-
-            // Pack wrapped key + encrypted body into the envelope ciphertext field.
-            // Format: "<wrapped_key_b64>:<wrapped_key_nonce_b64>:<content_ciphertext_b64>"
-            let combined_ciphertext =
-                format!("{wrapped_key_b64}:{wrapped_key_nonce_b64}:{content_ciphertext_b64}");
+                }
+            };
 
             // Sign the per-follower combined ciphertext (each follower gets a
             // unique wrapped key, so the ciphertext_b64 differs per envelope)
@@ -171,12 +389,14 @@ pub async fn handle_post_feed(state: &Arc<NodeState>, body: &str) -> Response {
                 from_node_id: state.identity.node_id.clone(),
                 to_node_id: follower_node_id.clone(),
                 from_public_key_b64: state.identity.public_key_b64.clone(),
-                message_type: mesh_pb::MessageType::FeedPost as i32,
+                message_type: wire_message_type(mesh_pb::MessageType::FeedPost, sealed),
                 ciphertext_b64: combined_ciphertext,
-                nonce_b64: content_nonce_b64.clone(),
+                nonce_b64,
                 signature_b64,
                 timestamp_ms: timestamp,
                 topic: None,
+                sealed,
+                schema_version: envelope_schema::CURRENT,
             };
 
             let node_id = follower_node_id.clone();
@@ -202,7 +422,7 @@ pub async fn handle_post_feed(state: &Arc<NodeState>, body: &str) -> Response {
     }
 
     // Store the post in our own inbox so it appears in our feed
-    let own_msg = agentbook_mesh::inbox::InboxMessage {
+    let own_msg = InboxMessage {
         message_id: msg_id.clone(),
         from_node_id: state.identity.node_id.clone(),
         from_public_key_b64: state.identity.public_key_b64.clone(),
@@ -212,6 +432,9 @@ pub async fn handle_post_feed(state: &Arc<NodeState>, body: &str) -> Response {
         timestamp_ms: timestamp,
         acked: false,
         message_type: MeshMessageType::FeedPost,
+        attachments: Vec::new(),
+        in_reply_to: None,
+        thread_id: None,
     };
     let preview = own_msg.body.chars().take(50).collect::<String>();
     {
@@ -244,24 +467,62 @@ pub async fn handle_inbox(
             .cloned()
             .collect::<Vec<_>>()
     };
+    inbox_entries(state, raw_messages).await
+}
+
+pub async fn handle_message_thread(state: &Arc<NodeState>, thread_id: &str) -> Response {
+    let raw_messages = {
+        let inbox = state.inbox.lock().await;
+        inbox
+            .list_thread(thread_id)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    if raw_messages.is_empty() {
+        return error_response("not_found", &format!("thread {thread_id} not found"));
+    }
+    inbox_entries(state, raw_messages).await
+}
+
+async fn inbox_entries(state: &Arc<NodeState>, raw_messages: Vec<InboxMessage>) -> Response {
     let mut messages = Vec::with_capacity(raw_messages.len());
     for m in raw_messages {
-        let from_username = super::social::lookup_display_username(state, &m.from_node_id).await;
-        messages.push(InboxEntry {
-            message_id: m.message_id.clone(),
-            from_node_id: m.from_node_id.clone(),
-            from_username,
-            to_node_id: m.to_node_id.clone(),
-            message_type: to_protocol_message_type(m.message_type),
-            body: m.body.clone(),
-            timestamp_ms: m.timestamp_ms,
-            acked: m.acked,
-            room: m.topic.clone(),
-        });
+        messages.push(inbox_entry(state, m).await);
     }
     ok_response(Some(serde_json::to_value(messages).unwrap()))
 }
 
+async fn inbox_entry(state: &Arc<NodeState>, m: InboxMessage) -> InboxEntry {
+    let from_username = super::social::lookup_display_username(state, &m.from_node_id).await;
+    InboxEntry {
+        message_id: m.message_id,
+        from_node_id: m.from_node_id,
+        from_username,
+        to_node_id: m.to_node_id,
+        message_type: to_protocol_message_type(m.message_type),
+        body: m.body,
+        timestamp_ms: m.timestamp_ms,
+        acked: m.acked,
+        room: m.topic,
+        attachments: to_protocol_attachments(&m.attachments),
+        in_reply_to: m.in_reply_to,
+        thread_id: m.thread_id,
+    }
+}
+
+/// Push a message just accepted into the inbox to the session holding the
+/// inbox (`ClaimInbox`), if there is one.
+pub(crate) async fn push_to_inbox_gateway(state: &Arc<NodeState>, message: InboxMessage) {
+    if !state.clients.has_inbox_gateway() {
+        return;
+    }
+    let message = inbox_entry(state, message).await;
+    state.clients.push_inbox(Event::MessageReceived {
+        message: Box::new(message),
+    });
+}
+
 pub async fn handle_inbox_ack(state: &Arc<NodeState>, message_id: &str) -> Response {
     let mut inbox = state.inbox.lock().await;
     match inbox.ack(message_id) {
@@ -271,6 +532,28 @@ pub async fn handle_inbox_ack(state: &Arc<NodeState>, message_id: &str) -> Respo
     }
 }
 
+fn to_mesh_attachments(attachments: &[Attachment]) -> Vec<attachment::Attachment> {
+    attachments
+        .iter()
+        .map(|a| attachment::Attachment {
+            name: a.name.clone(),
+            mime: a.mime.clone(),
+            data_b64: a.data_b64.clone(),
+        })
+        .collect()
+}
+
+fn to_protocol_attachments(attachments: &[attachment::Attachment]) -> Vec<Attachment> {
+    attachments
+        .iter()
+        .map(|a| Attachment {
+            name: a.name.clone(),
+            mime: a.mime.clone(),
+            data_b64: a.data_b64.clone(),
+        })
+        .collect()
+}
+
 // ---------------------------------------------------------------------------
 // Encryption helpers
 // ---------------------------------------------------------------------------
@@ -286,6 +569,88 @@ pub(crate) fn parse_public_key_b64(public_key_b64: &str) -> Result<PublicKey, St
     PublicKey::from_sec1_bytes(&bytes).map_err(|e| format!("invalid secp256k1 public key: {e}"))
 }
 
+/// Wrap a feed content key for one follower and pack it with the shared
+/// content ciphertext.
+///
+/// Format: "<wrapped_key_b64>:<wrapped_key_nonce_b64>:<content_ciphertext_b64>"
+fn wrap_feed_content(
+    identity: &NodeIdentity,
+    peer_public_key: &PublicKey,
+    content_key: &[u8; 32],
+    content_ciphertext_b64: &str,
+) -> Result<String, String> {
+    let shared_key = identity.derive_shared_key(peer_public_key);
+    let (wrapped_key_b64, wrapped_key_nonce_b64) =
+        encrypt_with_key(&shared_key, content_key).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "{wrapped_key_b64}:{wrapped_key_nonce_b64}:{content_ciphertext_b64}"
+    ))
+}
+
+/// Inner payload of a sealed envelope (relay privacy mode).
+///
+/// Everything except routing node IDs lives here, so the relay sees neither
+/// the message type nor the topic.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SealedPayload {
+    pub message_type: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    pub body: String,
+}
+
+/// The message type written on the wire: sealed envelopes never reveal it.
+fn wire_message_type(message_type: mesh_pb::MessageType, sealed: bool) -> i32 {
+    if sealed {
+        mesh_pb::MessageType::Unspecified as i32
+    } else {
+        message_type as i32
+    }
+}
+
+/// Seal a message for a single peer: serialize the real type and body, pad to
+/// a fixed bucket size, and encrypt with the ECDH shared key.
+pub(crate) fn seal_payload(
+    identity: &NodeIdentity,
+    peer_public_key: &PublicKey,
+    message_type: mesh_pb::MessageType,
+    body: &str,
+) -> Result<(String, String), String> {
+    let payload = SealedPayload {
+        message_type: message_type as i32,
+        topic: None,
+        body: body.to_string(),
+    };
+    let json = serde_json::to_vec(&payload).map_err(|e| format!("seal failed: {e}"))?;
+    let shared_key = identity.derive_shared_key(peer_public_key);
+    encrypt_with_key(&shared_key, &padding::pad(&json)).map_err(|e| format!("seal failed: {e}"))
+}
+
+/// Open a sealed envelope, returning the inner message type and payload.
+pub(crate) fn unseal_envelope(
+    identity: &NodeIdentity,
+    envelope: &mesh_pb::Envelope,
+) -> Result<(MeshMessageType, SealedPayload), String> {
+    let sender_public_key = parse_public_key_b64(&envelope.from_public_key_b64)?;
+    let shared_key = identity.derive_shared_key(&sender_public_key);
+    let padded = decrypt_with_key(&shared_key, &envelope.ciphertext_b64, &envelope.nonce_b64)
+        .map_err(|e| format!("sealed envelope decryption failed: {e}"))?;
+    let json = padding::unpad(&padded).map_err(|e| format!("invalid sealed padding: {e}"))?;
+    let payload: SealedPayload =
+        serde_json::from_slice(&json).map_err(|e| format!("invalid sealed payload: {e}"))?;
+    let message_type = match mesh_pb::MessageType::try_from(payload.message_type) {
+        Ok(mesh_pb::MessageType::DmText) => MeshMessageType::DmText,
+        Ok(mesh_pb::MessageType::FeedPost) => MeshMessageType::FeedPost,
+        // A newer sender may seal a type we don't know; keep it rather than
+        // drop it, as with unsealed envelopes.
+        _ if envelope_schema::version_of(envelope) > envelope_schema::CURRENT => {
+            MeshMessageType::Unspecified
+        }
+        _ => return Err("sealed envelopes may only carry DMs and feed posts".to_string()),
+    };
+    Ok((message_type, payload))
+}
+
 /// Resolve a peer's public key from the follow store.
 fn resolve_peer_public_key(follow_store: &FollowStore, node_id: &str) -> Result<PublicKey, String> {
     match follow_store.get(node_id) {
@@ -395,6 +760,8 @@ mod tests {
             signature_b64,
             timestamp_ms: 1000,
             topic: None,
+            sealed: false,
+            schema_version: envelope_schema::CURRENT,
         };
 
         // Receiver decrypts
@@ -425,6 +792,8 @@ mod tests {
             signature_b64: String::new(),
             timestamp_ms: 1000,
             topic: None,
+            sealed: false,
+            schema_version: envelope_schema::CURRENT,
         };
 
         // Wrong recipient cannot decrypt
@@ -463,6 +832,8 @@ mod tests {
             signature_b64: String::new(),
             timestamp_ms: 1000,
             topic: None,
+            sealed: false,
+            schema_version: envelope_schema::CURRENT,
         };
 
         // Follower decrypts
@@ -499,6 +870,8 @@ mod tests {
             signature_b64: String::new(),
             timestamp_ms: 1000,
             topic: None,
+            sealed: false,
+            schema_version: envelope_schema::CURRENT,
         };
 
         // Outsider cannot unwrap the content key
@@ -542,6 +915,8 @@ mod tests {
             signature_b64: String::new(),
             timestamp_ms: 1000,
             topic: None,
+            sealed: false,
+            schema_version: envelope_schema::CURRENT,
         };
         let env_b = mesh_pb::Envelope {
             message_id: "f2".to_string(),
@@ -554,6 +929,8 @@ mod tests {
             signature_b64: String::new(),
             timestamp_ms: 1000,
             topic: None,
+            sealed: false,
+            schema_version: envelope_schema::CURRENT,
         };
 
         assert_eq!(
@@ -585,6 +962,8 @@ mod tests {
             signature_b64: String::new(),
             timestamp_ms: 1000,
             topic: None,
+            sealed: false,
+            schema_version: envelope_schema::CURRENT,
         };
 
         let result = decrypt_envelope(&receiver, &envelope, MeshMessageType::Unspecified);
@@ -609,6 +988,118 @@ mod tests {
         assert_eq!(pk, id.public_key);
     }
 
+    #[test]
+    fn sealed_payload_round_trip_hides_type_and_length() {
+        let (sender, _d1) = make_identity();
+        let (receiver, _d2) = make_identity();
+
+        let (short_ct, nonce_b64) = seal_payload(
+            &sender,
+            &receiver.public_key,
+            mesh_pb::MessageType::FeedPost,
+            "hi",
+        )
+        .unwrap();
+        let (longer_ct, _) = seal_payload(
+            &sender,
+            &receiver.public_key,
+            mesh_pb::MessageType::DmText,
+            "a somewhat longer message that still fits in the first bucket",
+        )
+        .unwrap();
+        // Both land in the same padding bucket, so ciphertext sizes match.
+        assert_eq!(short_ct.len(), longer_ct.len());
+
+        let envelope = mesh_pb::Envelope {
+            message_id: "sealed-1".to_string(),
+            from_node_id: sender.node_id.clone(),
+            to_node_id: receiver.node_id.clone(),
+            from_public_key_b64: sender.public_key_b64.clone(),
+            message_type: wire_message_type(mesh_pb::MessageType::FeedPost, true),
+            ciphertext_b64: short_ct,
+            nonce_b64,
+            signature_b64: String::new(),
+            timestamp_ms: 1000,
+            topic: None,
+            sealed: true,
+            schema_version: envelope_schema::CURRENT,
+        };
+        assert_eq!(
+            envelope.message_type,
+            mesh_pb::MessageType::Unspecified as i32
+        );
+
+        let (message_type, payload) = unseal_envelope(&receiver, &envelope).unwrap();
+        assert_eq!(message_type, MeshMessageType::FeedPost);
+        assert_eq!(payload.body, "hi");
+        assert!(payload.topic.is_none());
+    }
+
+    #[test]
+    fn sealed_unknown_type_is_kept_only_from_a_newer_schema() {
+        let (sender, _d1) = make_identity();
+        let (receiver, _d2) = make_identity();
+
+        let (ciphertext_b64, nonce_b64) = seal_payload(
+            &sender,
+            &receiver.public_key,
+            mesh_pb::MessageType::Ping,
+            "from the future",
+        )
+        .unwrap();
+        let mut envelope = mesh_pb::Envelope {
+            message_id: "sealed-new".to_string(),
+            from_node_id: sender.node_id.clone(),
+            to_node_id: receiver.node_id.clone(),
+            from_public_key_b64: sender.public_key_b64.clone(),
+            message_type: mesh_pb::MessageType::Unspecified as i32,
+            ciphertext_b64,
+            nonce_b64,
+            signature_b64: String::new(),
+            timestamp_ms: 1000,
+            topic: None,
+            sealed: true,
+            schema_version: envelope_schema::CURRENT,
+        };
+        assert!(unseal_envelope(&receiver, &envelope).is_err());
+
+        envelope.schema_version = envelope_schema::CURRENT + 1;
+        let (message_type, payload) = unseal_envelope(&receiver, &envelope).unwrap();
+        assert_eq!(message_type, MeshMessageType::Unspecified);
+        assert_eq!(payload.body, "from the future");
+    }
+
+    #[test]
+    fn sealed_payload_wrong_recipient_cannot_unseal() {
+        let (sender, _d1) = make_identity();
+        let (receiver, _d2) = make_identity();
+        let (outsider, _d3) = make_identity();
+
+        let (ciphertext_b64, nonce_b64) = seal_payload(
+            &sender,
+            &receiver.public_key,
+            mesh_pb::MessageType::DmText,
+            "secret",
+        )
+        .unwrap();
+        let envelope = mesh_pb::Envelope {
+            message_id: "sealed-2".to_string(),
+            from_node_id: sender.node_id.clone(),
+            to_node_id: receiver.node_id.clone(),
+            from_public_key_b64: sender.public_key_b64.clone(),
+            message_type: mesh_pb::MessageType::Unspecified as i32,
+            ciphertext_b64,
+            nonce_b64,
+            signature_b64: String::new(),
+            timestamp_ms: 1000,
+            topic: None,
+            sealed: true,
+            schema_version: envelope_schema::CURRENT,
+        };
+
+        assert!(unseal_envelope(&outsider, &envelope).is_err());
+    }
+
     #[test]
     fn dm_nonces_are_unique_per_message() {
         let (sender, _d1) = make_identity();
@@ -1,15 +1,36 @@
+pub mod activity;
+pub mod config;
+pub mod daemon_log;
+pub mod drain;
+pub mod invites;
+pub mod keys;
 pub mod messaging;
+pub mod outbox;
+pub mod ping;
+pub mod replies;
+pub mod retention;
 pub mod rooms;
+pub mod routing;
 pub mod social;
+pub mod storage;
+pub mod templates;
 pub mod username_cache;
 pub mod wallet;
 
-use agentbook::protocol::{Event, MessageType, Request, Response};
-use agentbook_crypto::rate_limit::RateLimiter;
+use crate::access::AccessPolicy;
+use agentbook::protocol::{
+    DaemonLogKind, Event, MessageTemplate, MessageType, Request, Response, RouteRule, ShareInfo,
+    WebhookConfig,
+};
+use agentbook_crypto::time::{Clock, SystemClock};
+use agentbook_mesh::dm_payload::DmPayload;
 use agentbook_mesh::follow::FollowStore;
-use agentbook_mesh::identity::NodeIdentity;
+use agentbook_mesh::identity::{NodeIdentity, RetiredIdentity};
 use agentbook_mesh::inbox::{InboxMessage, MessageType as MeshMessageType, NodeInbox};
 use agentbook_mesh::ingress::{IngressPolicy, IngressRequest, IngressResult};
+use agentbook_mesh::ingress_limits::IngressLimits;
+use agentbook_mesh::invite::InviteStore;
+use agentbook_mesh::outbox::NodeOutbox;
 use agentbook_mesh::transport::MeshTransport;
 use agentbook_proto::host::v1::host_service_client::HostServiceClient;
 use agentbook_proto::mesh::v1 as mesh_pb;
@@ -18,6 +39,7 @@ use agentbook_wallet::wallet::BaseWallet;
 use alloy::providers::RootProvider;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, OnceLock};
 use std::time::Instant;
 use tokio::sync::{Mutex, broadcast};
@@ -42,8 +64,14 @@ pub struct WalletConfig {
 /// Shared node state accessible by all client connections.
 pub struct NodeState {
     pub identity: NodeIdentity,
+    /// Keys this node rotated away from (see [`NodeState::recipient_identity`]).
+    pub retired_identities: Vec<RetiredIdentity>,
     pub follow_store: Mutex<FollowStore>,
     pub inbox: Mutex<NodeInbox>,
+    /// DMs that failed to send and are waiting for retry (persisted).
+    pub outbox: Mutex<NodeOutbox>,
+    /// Invites this node has issued (persisted).
+    pub invites: Mutex<InviteStore>,
     pub transport: Option<MeshTransport>,
     pub username: Mutex<Option<String>>,
     /// Relay host addresses (for unary RPCs like username registration).
@@ -57,8 +85,45 @@ pub struct NodeState {
     pub wallet: WalletConfig,
     /// Spending limiter for yolo wallet transactions.
     pub spending_limiter: Mutex<SpendingLimiter>,
-    /// Rate limiter for inbound message ingress validation.
-    pub rate_limiter: Mutex<RateLimiter>,
+    /// Per-peer, per-message-type budgets for inbound message ingress validation.
+    pub ingress_limits: Mutex<IngressLimits>,
+    /// Webhooks from `node_config.json`, replaced on each config apply.
+    pub webhooks: Mutex<Vec<WebhookConfig>>,
+    /// Inbox retention policy from `node_config.json` and pruning counters.
+    pub retention: Mutex<retention::Janitor>,
+    /// `require_encryption` from `node_config.json`: no open rooms.
+    pub require_encryption: AtomicBool,
+    /// Set by `Freeze`: only reads and node controls are served (see
+    /// [`crate::access::allowed_while_frozen`]).
+    pub frozen: AtomicBool,
+    /// Events that lagging subscribers missed, for the health report.
+    pub events_lagged: AtomicU64,
+    /// When the node started, for the health report.
+    pub started_at: Instant,
+    /// Inbound room message routing rules from `node_config.json`.
+    pub routes: Mutex<Vec<RouteRule>>,
+    /// Outgoing message templates from `node_config.json`.
+    pub templates: Mutex<Vec<MessageTemplate>>,
+    /// When each peer last messaged this node, for idle detection.
+    pub activity: Mutex<activity::Activity>,
+    /// Sent DMs waiting for a reply by a deadline.
+    pub pending_replies: Mutex<replies::PendingReplies>,
+    /// Pings waiting for their pong.
+    pub pings: Mutex<ping::PendingPings>,
+    /// Recent ingress, relay and delivery decisions.
+    pub daemon_log: daemon_log::DaemonLog,
+    /// Connected socket and TCP clients.
+    pub clients: crate::clients::Registry,
+    /// Read-only share tokens (see [`crate::view`]).
+    pub shares: crate::view::Shares,
+    /// Hooks run around every client request (see [`crate::middleware`]).
+    pub middleware: crate::middleware::Chain,
+    /// Time source for invite expiry, outbox backoff and ingress rate limits.
+    pub clock: Arc<dyn Clock>,
+    /// In-flight request tracking and the shutdown signal for the socket server.
+    pub lifecycle: drain::Lifecycle,
+    /// Users and API tokens besides the owner's, and their roles.
+    pub access: AccessPolicy,
     /// Joined rooms: room name → config (includes optional encryption key).
     pub rooms: Mutex<HashMap<String, rooms::RoomConfig>>,
     /// Per-room send cooldown tracking.
@@ -79,11 +144,31 @@ impl NodeState {
         transport: Option<MeshTransport>,
         relay_hosts: Vec<String>,
         wallet: WalletConfig,
+    ) -> Arc<Self> {
+        Self::with_clock(
+            identity,
+            follow_store,
+            inbox,
+            transport,
+            relay_hosts,
+            wallet,
+            Arc::new(SystemClock),
+        )
+    }
+
+    /// Like [`NodeState::new`], with a custom time source (e.g. a
+    /// `ManualClock` in tests).
+    pub fn with_clock(
+        identity: NodeIdentity,
+        follow_store: FollowStore,
+        inbox: NodeInbox,
+        transport: Option<MeshTransport>,
+        relay_hosts: Vec<String>,
+        wallet: WalletConfig,
+        clock: Arc<dyn Clock>,
     ) -> Arc<Self> {
         let (event_tx, _) = broadcast::channel(256);
         let spending_limiter = SpendingLimiter::new(wallet.spending_limit_config.clone());
-        // Ingress rate limiter: burst of 20 messages, sustained 2/sec per sender.
-        let rate_limiter = RateLimiter::new(20, 2.0);
         // Load username cache and seed from follow records with known usernames
         let mut cache = username_cache::UsernameCache::load(&wallet.state_dir);
         cache.seed_from_follows(
@@ -93,10 +178,36 @@ impl NodeState {
                 .filter_map(|f| f.username.as_deref().map(|u| (f.node_id.as_str(), u))),
         );
 
+        let outbox = NodeOutbox::load(&wallet.state_dir).unwrap_or_else(|e| {
+            tracing::warn!(err = %e, "failed to load outbox.json, starting fresh");
+            NodeOutbox::empty(&wallet.state_dir)
+        });
+        let invites = InviteStore::load(&wallet.state_dir).unwrap_or_else(|e| {
+            tracing::warn!(err = %e, "failed to load invites.json, starting fresh");
+            InviteStore::empty(&wallet.state_dir)
+        });
+        let ingress_limits =
+            IngressLimits::load(&wallet.state_dir, clock.clone()).unwrap_or_else(|e| {
+                tracing::warn!(err = %e, "failed to load rate_limits.json, starting fresh");
+                IngressLimits::empty(&wallet.state_dir, clock.clone())
+            });
+        let access = AccessPolicy::load(&wallet.state_dir).unwrap_or_else(|e| {
+            tracing::warn!(err = %e, "failed to load access.json, allowing only the owner");
+            AccessPolicy::default()
+        });
+        let retired_identities = NodeIdentity::load_retired(&wallet.state_dir, &wallet.kek)
+            .unwrap_or_else(|e| {
+                tracing::warn!(err = %e, "failed to load retired node keys");
+                Vec::new()
+            });
+
         Arc::new(Self {
             identity,
+            retired_identities,
             follow_store: Mutex::new(follow_store),
             inbox: Mutex::new(inbox),
+            outbox: Mutex::new(outbox),
+            invites: Mutex::new(invites),
             transport,
             username: Mutex::new(None),
             relay_hosts,
@@ -105,7 +216,25 @@ impl NodeState {
             yolo_wallet: OnceLock::new(),
             wallet,
             spending_limiter: Mutex::new(spending_limiter),
-            rate_limiter: Mutex::new(rate_limiter),
+            ingress_limits: Mutex::new(ingress_limits),
+            webhooks: Mutex::new(Vec::new()),
+            retention: Mutex::new(retention::Janitor::default()),
+            require_encryption: AtomicBool::new(false),
+            frozen: AtomicBool::new(false),
+            events_lagged: AtomicU64::new(0),
+            started_at: Instant::now(),
+            routes: Mutex::new(Vec::new()),
+            templates: Mutex::new(Vec::new()),
+            activity: Mutex::new(activity::Activity::default()),
+            pending_replies: Mutex::new(replies::PendingReplies::default()),
+            pings: Mutex::new(ping::PendingPings::default()),
+            daemon_log: daemon_log::DaemonLog::default(),
+            clients: crate::clients::Registry::default(),
+            shares: crate::view::Shares::default(),
+            middleware: crate::middleware::Chain::default(),
+            clock,
+            lifecycle: drain::Lifecycle::default(),
+            access,
             rooms: Mutex::new(HashMap::new()),
             room_cooldowns: Mutex::new(HashMap::new()),
             grpc_clients: Mutex::new(HashMap::new()),
@@ -114,21 +243,34 @@ impl NodeState {
         })
     }
 
+    /// The identity an inbound envelope addressed to `to_node_id` was
+    /// encrypted for: a retired key still within its grace period, or the
+    /// current one.
+    pub fn recipient_identity(&self, to_node_id: &str) -> &NodeIdentity {
+        let now = self.clock.now_ms();
+        self.retired_identities
+            .iter()
+            .find(|r| r.identity.node_id == to_node_id && now < r.grace_until_ms)
+            .map_or(&self.identity, |r| &r.identity)
+    }
+
     /// Get or create a cached gRPC `HostServiceClient` for the given relay host.
     /// Returns a cloned client (gRPC clients are cheap to clone -- they share the
-    /// underlying HTTP/2 connection).
-    pub async fn get_grpc_client(
-        &self,
-        host: &str,
-    ) -> Result<HostServiceClient<Channel>, tonic::transport::Error> {
+    /// underlying HTTP/2 connection). Uses the relay transport's TLS and
+    /// proxy options.
+    pub async fn get_grpc_client(&self, host: &str) -> anyhow::Result<HostServiceClient<Channel>> {
         let mut clients = self.grpc_clients.lock().await;
         if let Some(client) = clients.get(host) {
             return Ok(client.clone());
         }
 
         let endpoint = agentbook_mesh::transport::relay_endpoint(host);
-
-        let client = HostServiceClient::connect(endpoint).await?;
+        let security = self
+            .transport
+            .as_ref()
+            .map(|t| t.security().clone())
+            .unwrap_or_default();
+        let client = agentbook_mesh::transport::connect_relay(&endpoint, &security).await?;
         clients.insert(host.to_string(), client.clone());
         Ok(client)
     }
@@ -150,12 +292,59 @@ impl NodeState {
 
 /// Handle a single request from a client.
 pub async fn handle_request(state: &Arc<NodeState>, req: Request) -> Response {
+    // A drain waits for every other request, so it is not counted itself.
+    let _in_flight = match &req {
+        Request::Drain { .. } => None,
+        _ => match state.lifecycle.admit(&req) {
+            Some(guard) => Some(guard),
+            None => {
+                return error_response(
+                    "draining",
+                    "node is draining and not accepting new requests",
+                );
+            }
+        },
+    };
+
     match req {
         // Social / identity
         Request::Identity => social::handle_identity(state).await,
         Request::Health => social::handle_health(state).await,
+        Request::RelayUsage { since_ms } => social::handle_relay_usage(state, since_ms).await,
+        Request::Ping { to, timeout_ms } => ping::handle_ping(state, &to, timeout_ms).await,
+        Request::DaemonLog { after_seq, limit } => {
+            daemon_log::handle_daemon_log(state, after_seq, limit).await
+        }
+        // Answered by the socket session, which owns the connection's version.
+        Request::Negotiate { .. } => error_response(
+            "invalid_request",
+            "negotiate only applies to a socket connection",
+        ),
+        // Likewise: once this passes the role and freeze checks, the socket
+        // session takes the claim and receives the pushes.
+        Request::ClaimInbox => ok_response(None),
+        Request::RotateKey { grace_ms } => keys::handle_rotate_key(state, grace_ms).await,
+        Request::IngressStats { node_id } => {
+            social::handle_ingress_stats(state, node_id.as_deref()).await
+        }
+        Request::RetentionStats => retention::handle_retention_stats(state).await,
+        Request::Config => config::handle_config(state).await,
+        Request::ConfigSet {
+            log_level,
+            ingress_budgets,
+        } => config::handle_config_set(state, log_level, ingress_budgets).await,
         Request::Follow { target } => social::handle_follow(state, &target).await,
         Request::Unfollow { target } => social::handle_unfollow(state, &target).await,
+        Request::SetAlias { target, alias } => {
+            social::handle_set_alias(state, &target, alias).await
+        }
+        Request::GroupAdd { group, target } => {
+            social::handle_group_membership(state, &group, &target, true).await
+        }
+        Request::GroupRemove { group, target } => {
+            social::handle_group_membership(state, &group, &target, false).await
+        }
+        Request::Groups => social::handle_groups(state).await,
         Request::Block { target } => social::handle_block(state, &target).await,
         Request::Following => social::handle_following(state).await,
         Request::Followers => social::handle_followers(state).await,
@@ -169,6 +358,16 @@ pub async fn handle_request(state: &Arc<NodeState>, req: Request) -> Response {
         Request::SyncPush { confirm } => social::handle_sync_push(state, confirm).await,
         Request::SyncPull { confirm } => social::handle_sync_pull(state, confirm).await,
 
+        // Invites
+        Request::InviteCreate {
+            ttl_ms,
+            max_uses,
+            scopes,
+        } => invites::handle_invite_create(state, ttl_ms, max_uses, scopes).await,
+        Request::InviteList => invites::handle_invite_list(state).await,
+        Request::InviteRevoke { token_id } => invites::handle_invite_revoke(state, &token_id).await,
+        Request::InviteAccept { token } => invites::handle_invite_accept(state, &token).await,
+
         // Rooms
         Request::JoinRoom { room, passphrase } => {
             rooms::handle_join_room(state, &room, passphrase.as_deref()).await
@@ -179,12 +378,44 @@ pub async fn handle_request(state: &Arc<NodeState>, req: Request) -> Response {
         Request::ListRooms => rooms::handle_list_rooms(state).await,
 
         // Messaging
-        Request::SendDm { to, body } => messaging::handle_send_dm(state, &to, &body).await,
+        Request::SendDm {
+            to,
+            body,
+            attachments,
+            in_reply_to,
+            reply_within_secs,
+        } => {
+            messaging::handle_send_dm(
+                state,
+                &to,
+                &body,
+                &attachments,
+                in_reply_to.as_deref(),
+                reply_within_secs,
+            )
+            .await
+        }
+        Request::BroadcastDm {
+            body,
+            mutual_only,
+            group,
+        } => messaging::handle_broadcast_dm(state, &body, mutual_only, group.as_deref()).await,
         Request::PostFeed { body } => messaging::handle_post_feed(state, &body).await,
+        Request::SendTemplate { template, to, vars } => {
+            templates::handle_send_template(state, &template, to.as_deref(), &vars).await
+        }
         Request::Inbox { unread_only, limit } => {
             messaging::handle_inbox(state, unread_only, limit).await
         }
         Request::InboxAck { message_id } => messaging::handle_inbox_ack(state, &message_id).await,
+        Request::MessageThread { thread_id } => {
+            messaging::handle_message_thread(state, &thread_id).await
+        }
+        Request::PendingReplies => replies::handle_pending_replies(state).await,
+        Request::OutboxList => outbox::handle_outbox_list(state).await,
+        Request::OutboxCancel { message_id } => {
+            outbox::handle_outbox_cancel(state, &message_id).await
+        }
 
         // Wallet
         Request::WalletBalance { wallet: w } => wallet::handle_wallet_balance(state, w).await,
@@ -250,6 +481,14 @@ pub async fn handle_request(state: &Arc<NodeState>, req: Request) -> Response {
         Request::YoloSignMessage { message } => {
             wallet::handle_yolo_sign_message(state, &message).await
         }
+        Request::ReencryptState => storage::handle_reencrypt_state(state).await,
+        Request::Clients => ok_response(Some(serde_json::to_value(state.clients.list()).unwrap())),
+        Request::Disconnect { client_id } => handle_disconnect(state, client_id),
+        Request::Freeze => handle_freeze(state, true),
+        Request::Share { ttl_secs } => handle_share(state, ttl_secs),
+        Request::Unshare => handle_unshare(state),
+        Request::Unfreeze => handle_freeze(state, false),
+        Request::Drain { timeout_ms } => drain::handle_drain(state, timeout_ms).await,
         Request::Shutdown => handle_shutdown().await,
     }
 }
@@ -258,15 +497,126 @@ async fn handle_shutdown() -> Response {
     ok_response(None)
 }
 
+fn handle_freeze(state: &Arc<NodeState>, frozen: bool) -> Response {
+    let was = state.frozen.swap(frozen, Ordering::SeqCst);
+    if was != frozen {
+        tracing::warn!(frozen, "node freeze changed on request");
+    }
+    ok_response(None)
+}
+
+fn handle_share(state: &Arc<NodeState>, ttl_secs: Option<u64>) -> Response {
+    let ttl_secs = ttl_secs.unwrap_or(crate::view::DEFAULT_TTL_SECS);
+    if ttl_secs == 0 {
+        return error_response("invalid_ttl", "ttl_secs must be at least 1");
+    }
+    if let Err(e) = crate::view::listen(state) {
+        return error_response("share_failed", &format!("{e:#}"));
+    }
+    let (token, expires_at_ms) = state
+        .shares
+        .issue(state.clock.now_ms(), ttl_secs.saturating_mul(1000));
+    tracing::info!(expires_at_ms, "issued a read-only share token");
+    let info = ShareInfo {
+        node_id: state.identity.node_id.clone(),
+        token,
+        expires_at_ms,
+    };
+    ok_response(Some(serde_json::to_value(info).unwrap()))
+}
+
+fn handle_unshare(state: &Arc<NodeState>) -> Response {
+    let revoked = state.shares.revoke_all(state.clock.now_ms());
+    let viewers: Vec<u64> = state
+        .clients
+        .list()
+        .into_iter()
+        .filter(|c| c.transport == "view")
+        .map(|c| c.client_id)
+        .collect();
+    for client_id in &viewers {
+        state.clients.disconnect(*client_id);
+    }
+    tracing::info!(revoked, viewers = viewers.len(), "revoked share tokens");
+    ok_response(Some(serde_json::json!({
+        "revoked": revoked,
+        "disconnected": viewers.len(),
+    })))
+}
+
+fn handle_disconnect(state: &Arc<NodeState>, client_id: u64) -> Response {
+    if state.clients.disconnect(client_id) {
+        tracing::info!(client_id, "disconnecting client on request");
+        ok_response(None)
+    } else {
+        error_response("not_found", &format!("client {client_id} not connected"))
+    }
+}
+
 /// Process an inbound envelope from the relay into the inbox.
+#[tracing::instrument(
+    name = "mesh_delivery",
+    skip_all,
+    fields(
+        message_id = %envelope.message_id,
+        from = %envelope.from_node_id,
+        message_type = envelope.message_type,
+    )
+)]
 pub async fn process_inbound(state: &Arc<NodeState>, envelope: mesh_pb::Envelope) {
-    let mesh_msg_type = match mesh_pb::MessageType::try_from(envelope.message_type) {
-        Ok(mesh_pb::MessageType::DmText) => MeshMessageType::DmText,
-        Ok(mesh_pb::MessageType::FeedPost) => MeshMessageType::FeedPost,
-        Ok(mesh_pb::MessageType::RoomMessage) => MeshMessageType::RoomMessage,
-        Ok(mesh_pb::MessageType::RoomJoin) => MeshMessageType::RoomJoin,
-        Ok(mesh_pb::MessageType::RoomLeave) => MeshMessageType::RoomLeave,
-        _ => MeshMessageType::Unspecified,
+    // Key rotation/revocation notices update the follow graph, not the inbox.
+    if !envelope.sealed
+        && matches!(
+            mesh_pb::MessageType::try_from(envelope.message_type),
+            Ok(mesh_pb::MessageType::KeyRotation | mesh_pb::MessageType::KeyRevocation)
+        )
+    {
+        keys::process_inbound_key_notice(state, envelope).await;
+        return;
+    }
+    if !envelope.sealed
+        && matches!(
+            mesh_pb::MessageType::try_from(envelope.message_type),
+            Ok(mesh_pb::MessageType::Ping | mesh_pb::MessageType::Pong)
+        )
+    {
+        ping::process_inbound(state, envelope).await;
+        return;
+    }
+    if !envelope.sealed && envelope.message_type == mesh_pb::MessageType::InviteRedeem as i32 {
+        invites::process_inbound_invite_redeem(state, envelope).await;
+        return;
+    }
+
+    // Sealed envelopes (relay privacy mode) carry the real message type inside
+    // the ciphertext, so they must be opened before routing.
+    let mut sealed_payload = None;
+    let recipient = state.recipient_identity(&envelope.to_node_id);
+    let mesh_msg_type = if envelope.sealed {
+        match messaging::unseal_envelope(recipient, &envelope) {
+            Ok((message_type, payload)) => {
+                sealed_payload = Some(payload);
+                message_type
+            }
+            Err(e) => {
+                tracing::warn!(
+                    from = %envelope.from_node_id,
+                    msg_id = %envelope.message_id,
+                    err = %e,
+                    "failed to unseal inbound envelope"
+                );
+                return;
+            }
+        }
+    } else {
+        match mesh_pb::MessageType::try_from(envelope.message_type) {
+            Ok(mesh_pb::MessageType::DmText) => MeshMessageType::DmText,
+            Ok(mesh_pb::MessageType::FeedPost) => MeshMessageType::FeedPost,
+            Ok(mesh_pb::MessageType::RoomMessage) => MeshMessageType::RoomMessage,
+            Ok(mesh_pb::MessageType::RoomJoin) => MeshMessageType::RoomJoin,
+            Ok(mesh_pb::MessageType::RoomLeave) => MeshMessageType::RoomLeave,
+            _ => MeshMessageType::Unspecified,
+        }
     };
 
     // Route room messages and system events to the rooms handler.
@@ -281,32 +631,59 @@ pub async fn process_inbound(state: &Arc<NodeState>, envelope: mesh_pb::Envelope
     // Ingress validation: signature, blocked, follow graph, rate limit.
     {
         let follow_store = state.follow_store.lock().await;
-        let mut rate_limiter = state.rate_limiter.lock().await;
-        let mut policy = IngressPolicy::new(&follow_store, &mut rate_limiter);
+        let mut ingress_limits = state.ingress_limits.lock().await;
 
         let req = IngressRequest {
             from_node_id: &envelope.from_node_id,
             from_public_key_b64: &envelope.from_public_key_b64,
             payload: envelope.ciphertext_b64.as_bytes(),
             signature_b64: &envelope.signature_b64,
-            my_node_id: &state.identity.node_id,
+            my_node_id: &recipient.node_id,
             message_type: mesh_msg_type,
         };
 
-        if let IngressResult::Reject(reason) = policy.check(&req) {
+        let result = IngressPolicy::new(&follow_store, &mut ingress_limits).check(&req);
+        if let IngressResult::Reject(reason) = result {
             tracing::warn!(
                 from = %envelope.from_node_id,
                 msg_id = %envelope.message_id,
                 reason = %reason,
                 "ingress rejected"
             );
+            daemon_log::record(
+                state,
+                DaemonLogKind::IngressRejected,
+                daemon_log::Record {
+                    node_id: Some(&envelope.from_node_id),
+                    message_id: Some(&envelope.message_id),
+                    reason: Some(reason.to_string()),
+                    ..Default::default()
+                },
+            );
             return;
         }
     }
+    daemon_log::record(
+        state,
+        DaemonLogKind::IngressAccepted,
+        daemon_log::Record {
+            node_id: Some(&envelope.from_node_id),
+            message_id: Some(&envelope.message_id),
+            ..Default::default()
+        },
+    );
 
     // Decrypt the message body using ECDH shared key
-    let body = match messaging::decrypt_envelope(&state.identity, &envelope, mesh_msg_type) {
-        Ok(plaintext) => plaintext,
+    let (topic, decrypted) = match sealed_payload {
+        Some(payload) => (payload.topic, Ok(payload.body)),
+        None => (
+            None,
+            messaging::decrypt_envelope(recipient, &envelope, mesh_msg_type),
+        ),
+    };
+    let payload = match decrypted {
+        Ok(plaintext) if mesh_msg_type == MeshMessageType::DmText => DmPayload::decode(plaintext),
+        Ok(plaintext) => DmPayload::text(plaintext),
         Err(e) => {
             tracing::warn!(
                 from = %envelope.from_node_id,
@@ -315,9 +692,14 @@ pub async fn process_inbound(state: &Arc<NodeState>, envelope: mesh_pb::Envelope
                 "failed to decrypt inbound message, storing raw"
             );
             // Fallback: store the ciphertext_b64 as-is so the message is not lost
-            envelope.ciphertext_b64.clone()
+            DmPayload::text(envelope.ciphertext_b64.clone())
         }
     };
+    if mesh_msg_type == MeshMessageType::DmText
+        && !routing::apply(state, None, &envelope.from_node_id, &payload.body).await
+    {
+        return;
+    }
 
     let msg = InboxMessage {
         message_id: envelope.message_id.clone(),
@@ -325,23 +707,33 @@ pub async fn process_inbound(state: &Arc<NodeState>, envelope: mesh_pb::Envelope
         from_public_key_b64: envelope.from_public_key_b64.clone(),
         to_node_id: (mesh_msg_type == MeshMessageType::DmText)
             .then(|| state.identity.node_id.clone()),
-        topic: None,
-        body,
+        topic,
+        body: payload.body,
         timestamp_ms: envelope.timestamp_ms,
         acked: false,
         message_type: mesh_msg_type,
+        attachments: payload.attachments,
+        in_reply_to: payload.in_reply_to,
+        thread_id: payload.thread_id,
     };
 
     let preview = msg.body.chars().take(50).collect::<String>();
     let from = msg.from_node_id.clone();
     let msg_id = msg.message_id.clone();
     let protocol_msg_type = to_protocol_message_type(msg.message_type);
+    let in_reply_to = msg.in_reply_to.clone();
+    let pushed = state.clients.has_inbox_gateway().then(|| msg.clone());
 
     let mut inbox = state.inbox.lock().await;
     if let Err(e) = inbox.push(msg) {
         tracing::error!(err = %e, "failed to store inbound message");
         return;
     }
+    drop(inbox);
+    activity::record(state, &from).await;
+    if let Some(parent) = in_reply_to {
+        state.pending_replies.lock().await.answered(&parent, &from);
+    }
 
     // Broadcast event to connected clients
     let _ = state.event_tx.send(Event::NewMessage {
@@ -350,6 +742,9 @@ pub async fn process_inbound(state: &Arc<NodeState>, envelope: mesh_pb::Envelope
         message_type: protocol_msg_type,
         preview,
     });
+    if let Some(msg) = pushed {
+        messaging::push_to_inbox_gateway(state, msg).await;
+    }
 }
 
 // ---- Shared helpers ----
@@ -380,4 +775,4 @@ pub fn error_response(code: &str, message: &str) -> Response {
 pub use agentbook_crypto::time::now_ms;
 
 #[cfg(test)]
-mod tests;
+pub(crate) mod tests;
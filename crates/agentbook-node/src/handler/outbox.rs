@@ -0,0 +1,99 @@
+use super::{NodeState, daemon_log, error_response, ok_response};
+use agentbook::protocol::{DaemonLogKind, OutboxInfo, Response};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the retry loop checks the outbox for due entries.
+const RETRY_TICK: Duration = Duration::from_secs(1);
+
+pub async fn handle_outbox_list(state: &Arc<NodeState>) -> Response {
+    let outbox = state.outbox.lock().await;
+    let list: Vec<OutboxInfo> = outbox
+        .list()
+        .iter()
+        .map(|e| OutboxInfo {
+            message_id: e.message_id.clone(),
+            to_node_id: e.to_node_id.clone(),
+            attempts: e.attempts,
+            created_at_ms: e.created_at_ms,
+            next_attempt_ms: e.next_attempt_ms,
+            last_error: e.last_error.clone(),
+        })
+        .collect();
+    ok_response(Some(serde_json::to_value(list).unwrap()))
+}
+
+pub async fn handle_outbox_cancel(state: &Arc<NodeState>, message_id: &str) -> Response {
+    let mut outbox = state.outbox.lock().await;
+    match outbox.cancel(message_id) {
+        Ok(true) => ok_response(None),
+        Ok(false) => error_response("not_found", &format!("message {message_id} is not queued")),
+        Err(e) => error_response("cancel_failed", &e.to_string()),
+    }
+}
+
+/// Retry every due outbox entry once. Entries are resent head-of-line per
+/// peer, so a peer's messages always arrive in the order they were sent.
+pub async fn flush_outbox(state: &Arc<NodeState>) {
+    let Some(transport) = &state.transport else {
+        return;
+    };
+    let due = state.outbox.lock().await.due(state.clock.now_ms());
+    if due.is_empty() || !transport.is_connected() {
+        return;
+    }
+
+    for entry in due {
+        let result = match entry.envelope() {
+            Ok(envelope) => transport.send_via_relay(envelope).await,
+            Err(e) => Err(e),
+        };
+        daemon_log::record(
+            state,
+            if result.is_ok() {
+                DaemonLogKind::DeliverySent
+            } else {
+                DaemonLogKind::DeliveryFailed
+            },
+            daemon_log::Record {
+                node_id: Some(&entry.to_node_id),
+                message_id: Some(&entry.message_id),
+                reason: result.as_ref().err().map(|e| e.to_string()),
+                ..Default::default()
+            },
+        );
+        let mut outbox = state.outbox.lock().await;
+        match result {
+            Ok(()) => {
+                tracing::info!(msg_id = %entry.message_id, to = %entry.to_node_id, "delivered queued message");
+                if let Err(e) = outbox.mark_sent(&entry.message_id) {
+                    tracing::error!(err = %e, "failed to update outbox");
+                }
+            }
+            Err(err) => {
+                match outbox.mark_failed(&entry.message_id, state.clock.now_ms(), &err.to_string())
+                {
+                    Ok(Some(dropped)) => {
+                        tracing::warn!(
+                            msg_id = %dropped.message_id,
+                            to = %dropped.to_node_id,
+                            attempts = dropped.attempts,
+                            "giving up on queued message"
+                        );
+                    }
+                    Ok(None) => {}
+                    Err(e) => tracing::error!(err = %e, "failed to update outbox"),
+                }
+            }
+        }
+    }
+}
+
+/// Periodically retry queued outbound messages until the node shuts down.
+pub async fn outbox_retry_loop(state: Arc<NodeState>) {
+    let mut interval = tokio::time::interval(RETRY_TICK);
+    loop {
+        interval.tick().await;
+        flush_outbox(&state).await;
+    }
+}
@@ -0,0 +1,220 @@
+//! Reachability checks: `Ping` sends a signed ping envelope to a node on
+//! each path and waits for its pong, reporting each path's round trip.
+//! Nodes only answer pings from nodes they follow, the same rule DMs use,
+//! so a timeout to a friend means the DM path to them is broken too.
+//! Inbound pings are charged against [`RateClass::Ping`].
+
+use super::{NodeState, error_response, now_ms, ok_response};
+use agentbook::protocol::{PathProbe, PingResult, Response};
+use agentbook_crypto::rate_limit::CheckResult;
+use agentbook_mesh::crypto::{public_key_matches_node_id, verify_signature};
+use agentbook_mesh::envelope_schema;
+use agentbook_mesh::ingress_limits::RateClass;
+use agentbook_mesh::transport::MeshTransport;
+use agentbook_proto::mesh::v1 as mesh_pb;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// How long to wait for a pong when `Ping` doesn't say.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why the direct path is never probed.
+const NO_DIRECT_PATH: &str = "nodes only connect to each other through relays";
+
+/// Pings waiting for their pong.
+#[derive(Default)]
+pub struct PendingPings {
+    /// Ping message ID → the node expected to answer, and its waiter.
+    pending: HashMap<String, (String, oneshot::Sender<()>)>,
+}
+
+impl PendingPings {
+    fn expect(&mut self, ping_id: &str, node_id: &str) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .insert(ping_id.to_string(), (node_id.to_string(), tx));
+        rx
+    }
+
+    /// Wake the waiter for `ping_id` if `from` is the node it was sent to.
+    fn answered(&mut self, ping_id: &str, from: &str) -> bool {
+        match self.pending.get(ping_id) {
+            Some((node_id, _)) if node_id == from => {
+                let (_, tx) = self.pending.remove(ping_id).unwrap();
+                tx.send(()).is_ok()
+            }
+            _ => false,
+        }
+    }
+
+    fn forget(&mut self, ping_id: &str) {
+        self.pending.remove(ping_id);
+    }
+}
+
+/// A ping or pong: unencrypted, with the ping's ID as the signed payload.
+fn control_envelope(
+    state: &NodeState,
+    to: &str,
+    message_type: mesh_pb::MessageType,
+    ping_id: &str,
+) -> mesh_pb::Envelope {
+    mesh_pb::Envelope {
+        message_id: Uuid::new_v4().to_string(),
+        from_node_id: state.identity.node_id.clone(),
+        to_node_id: to.to_string(),
+        from_public_key_b64: state.identity.public_key_b64.clone(),
+        message_type: message_type as i32,
+        ciphertext_b64: ping_id.to_string(),
+        nonce_b64: String::new(),
+        signature_b64: state.identity.sign(ping_id.as_bytes()).unwrap_or_default(),
+        timestamp_ms: now_ms(),
+        topic: None,
+        sealed: false,
+        schema_version: envelope_schema::CURRENT,
+    }
+}
+
+pub async fn handle_ping(state: &Arc<NodeState>, to: &str, timeout_ms: Option<u64>) -> Response {
+    if timeout_ms == Some(0) {
+        return error_response("invalid_request", "timeout_ms must be at least 1");
+    }
+    let transport = match &state.transport {
+        Some(t) => t,
+        None => return error_response("no_relay", "not connected to any relay"),
+    };
+    let resolved = match super::social::resolve_target(state, to).await {
+        Ok(r) => r,
+        Err(resp) => return resp,
+    };
+    let node_id = resolved.node_id;
+    let timeout = timeout_ms.map_or(DEFAULT_TIMEOUT, Duration::from_millis);
+
+    let relay = match ping_via_relay(state, transport, &node_id, timeout).await {
+        Ok(rtt_ms) => PathProbe::Reachable { rtt_ms },
+        // There is no direct path to fall back on, so the node is unreachable.
+        Err((code, reason)) => return error_response(code, &reason),
+    };
+    let direct = PathProbe::Unavailable {
+        reason: NO_DIRECT_PATH.to_string(),
+    };
+    ok_response(Some(
+        serde_json::to_value(PingResult {
+            node_id,
+            relay,
+            direct,
+        })
+        .unwrap(),
+    ))
+}
+
+/// Ping `node_id` through the relay, returning the round trip in ms or an
+/// error code and message.
+async fn ping_via_relay(
+    state: &NodeState,
+    transport: &MeshTransport,
+    node_id: &str,
+    timeout: Duration,
+) -> Result<u64, (&'static str, String)> {
+    let ping_id = Uuid::new_v4().to_string();
+    let envelope = control_envelope(state, node_id, mesh_pb::MessageType::Ping, &ping_id);
+    let pong = state.pings.lock().await.expect(&ping_id, node_id);
+    let sent_at = Instant::now();
+    if let Err(e) = transport.send_via_relay(envelope).await {
+        state.pings.lock().await.forget(&ping_id);
+        return Err(("send_failed", e.to_string()));
+    }
+
+    match tokio::time::timeout(timeout, pong).await {
+        Ok(Ok(())) => Ok(sent_at.elapsed().as_millis() as u64),
+        _ => {
+            state.pings.lock().await.forget(&ping_id);
+            Err((
+                "timeout",
+                format!("no pong from {node_id} within {}ms", timeout.as_millis()),
+            ))
+        }
+    }
+}
+
+/// Answer a ping from a node we follow, or complete a ping we sent.
+pub async fn process_inbound(state: &Arc<NodeState>, envelope: mesh_pb::Envelope) {
+    if !verify_signature(
+        &envelope.from_public_key_b64,
+        envelope.ciphertext_b64.as_bytes(),
+        &envelope.signature_b64,
+    ) || !public_key_matches_node_id(&envelope.from_public_key_b64, &envelope.from_node_id)
+    {
+        tracing::warn!(from = %envelope.from_node_id, "dropping ping with a bad signature");
+        return;
+    }
+    let ping_id = &envelope.ciphertext_b64;
+
+    if envelope.message_type == mesh_pb::MessageType::Pong as i32 {
+        if !state
+            .pings
+            .lock()
+            .await
+            .answered(ping_id, &envelope.from_node_id)
+        {
+            tracing::debug!(from = %envelope.from_node_id, "ignoring unexpected pong");
+        }
+        return;
+    }
+
+    let limited = matches!(
+        state
+            .ingress_limits
+            .lock()
+            .await
+            .check(&envelope.from_node_id, RateClass::Ping),
+        CheckResult::RateLimited | CheckResult::Banned { .. }
+    );
+    if limited {
+        tracing::debug!(from = %envelope.from_node_id, "ignoring ping over its rate limit");
+        return;
+    }
+
+    let allowed = {
+        let follow_store = state.follow_store.lock().await;
+        follow_store.is_following(&envelope.from_node_id)
+            && !follow_store.is_blocked(&envelope.from_node_id)
+            && !follow_store.is_revoked(&envelope.from_node_id)
+    };
+    if !allowed {
+        tracing::debug!(from = %envelope.from_node_id, "ignoring ping from a node we don't follow");
+        return;
+    }
+    let Some(transport) = &state.transport else {
+        return;
+    };
+    let pong = control_envelope(
+        state,
+        &envelope.from_node_id,
+        mesh_pb::MessageType::Pong,
+        ping_id,
+    );
+    if let Err(e) = transport.send_via_relay(pong).await {
+        tracing::warn!(to = %envelope.from_node_id, err = %e, "failed to send pong");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn only_the_pinged_node_can_answer() {
+        let mut pings = PendingPings::default();
+        let pong = pings.expect("ping-1", "0xb");
+        assert!(!pings.answered("ping-1", "0xc"));
+        assert!(!pings.answered("ping-2", "0xb"));
+        assert!(pings.answered("ping-1", "0xb"));
+        pong.await.unwrap();
+        // A replayed pong finds nobody waiting.
+        assert!(!pings.answered("ping-1", "0xb"));
+    }
+}
@@ -0,0 +1,137 @@
+//! Reply deadlines for DMs sent with `reply_within_secs`.
+//!
+//! The node remembers each such DM until a reply to it (a DM from the
+//! recipient with `in_reply_to` naming it) arrives. A background watcher
+//! emits [`Event::ReplyOverdue`] once when the deadline passes; the DM stays
+//! listed as overdue until it is answered. Deadlines are kept in memory and
+//! forgotten on restart.
+
+use super::{NodeState, ok_response};
+use agentbook::protocol::{Event, PendingReply, Response};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the watcher looks for passed deadlines.
+const WATCH_TICK: Duration = Duration::from_secs(5);
+
+/// Most DMs tracked at once; past this the earliest deadline is forgotten.
+const MAX_PENDING: usize = 1024;
+
+/// Sent DMs waiting for a reply, by message ID.
+#[derive(Debug, Default)]
+pub struct PendingReplies {
+    waiting: BTreeMap<String, PendingReply>,
+}
+
+impl PendingReplies {
+    /// Start waiting for `to` to reply to `message_id` by `deadline_ms`.
+    pub fn expect(&mut self, message_id: &str, to: &str, deadline_ms: u64) {
+        if self.waiting.len() >= MAX_PENDING
+            && let Some(earliest) = self
+                .waiting
+                .values()
+                .min_by_key(|p| p.deadline_ms)
+                .map(|p| p.message_id.clone())
+        {
+            tracing::warn!(message_id = %earliest, "too many pending replies; forgetting one");
+            self.waiting.remove(&earliest);
+        }
+        self.waiting.insert(
+            message_id.to_string(),
+            PendingReply {
+                message_id: message_id.to_string(),
+                to: to.to_string(),
+                deadline_ms,
+                overdue: false,
+            },
+        );
+    }
+
+    /// Note a DM from `from` replying to `in_reply_to`. Returns whether it
+    /// answered a pending DM.
+    pub fn answered(&mut self, in_reply_to: &str, from: &str) -> bool {
+        match self.waiting.get(in_reply_to) {
+            Some(pending) if pending.to == from => {
+                self.waiting.remove(in_reply_to);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Mark DMs whose deadline has passed as overdue, returning the ones
+    /// that were not already.
+    fn newly_overdue(&mut self, now_ms: u64) -> Vec<PendingReply> {
+        self.waiting
+            .values_mut()
+            .filter(|p| !p.overdue && p.deadline_ms <= now_ms)
+            .map(|p| {
+                p.overdue = true;
+                p.clone()
+            })
+            .collect()
+    }
+
+    fn list(&self) -> Vec<PendingReply> {
+        let mut list: Vec<_> = self.waiting.values().cloned().collect();
+        list.sort_by_key(|p| p.deadline_ms);
+        list
+    }
+}
+
+/// Emit `ReplyOverdue` for every deadline that has passed since the last
+/// check.
+pub async fn check_once(state: &Arc<NodeState>) {
+    let now_ms = state.clock.now_ms();
+    let overdue = state.pending_replies.lock().await.newly_overdue(now_ms);
+    for pending in overdue {
+        tracing::info!(message_id = %pending.message_id, to = %pending.to, "reply overdue");
+        let _ = state.event_tx.send(Event::ReplyOverdue {
+            message_id: pending.message_id,
+            to: pending.to,
+            deadline_ms: pending.deadline_ms,
+        });
+    }
+}
+
+/// Check deadlines every [`WATCH_TICK`] until the daemon shuts down.
+pub async fn watch_loop(state: Arc<NodeState>) {
+    let mut interval = tokio::time::interval(WATCH_TICK);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = state.lifecycle.shutdown_requested() => return,
+        }
+        check_once(&state).await;
+    }
+}
+
+pub async fn handle_pending_replies(state: &Arc<NodeState>) -> Response {
+    let list = state.pending_replies.lock().await.list();
+    ok_response(Some(serde_json::to_value(list).unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_recipient_answers_and_overdue_fires_once() {
+        let mut pending = PendingReplies::default();
+        pending.expect("m1", "0xbob", 1_000);
+        pending.expect("m2", "0xbob", 5_000);
+
+        assert!(pending.newly_overdue(999).is_empty());
+        let overdue = pending.newly_overdue(1_000);
+        assert_eq!(overdue.len(), 1);
+        assert_eq!(overdue[0].message_id, "m1");
+        assert!(pending.newly_overdue(2_000).is_empty());
+
+        assert!(!pending.answered("m1", "0xmallory"));
+        assert!(pending.answered("m1", "0xbob"));
+        assert!(!pending.answered("m1", "0xbob"));
+        let left: Vec<_> = pending.list().into_iter().map(|p| p.message_id).collect();
+        assert_eq!(left, ["m2"]);
+    }
+}
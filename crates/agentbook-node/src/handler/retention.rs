@@ -0,0 +1,103 @@
+//! Inbox retention: a background janitor that prunes messages outside the
+//! policy in `node_config.json`, and the counters `RetentionStats` reports.
+
+use super::{NodeState, ok_response};
+use agentbook::protocol::{Response, RetentionPolicy, RetentionStats};
+use agentbook_mesh::inbox::{Pruned, Retention};
+use anyhow::{Result, bail};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the janitor checks the inbox against the policy.
+const JANITOR_TICK: Duration = Duration::from_secs(5 * 60);
+
+/// The policy in effect and what it has pruned since startup.
+#[derive(Debug, Default)]
+pub struct Janitor {
+    pub policy: RetentionPolicy,
+    pruned_by_age: u64,
+    pruned_by_count: u64,
+    pruned_by_bytes: u64,
+    last_run_ms: Option<u64>,
+}
+
+impl Janitor {
+    fn record(&mut self, pruned: Pruned, now_ms: u64) {
+        self.pruned_by_age += pruned.by_age as u64;
+        self.pruned_by_count += pruned.by_count as u64;
+        self.pruned_by_bytes += pruned.by_bytes as u64;
+        self.last_run_ms = Some(now_ms);
+    }
+}
+
+/// Reject limits that would empty the inbox outright.
+pub fn validate(policy: &RetentionPolicy) -> Result<()> {
+    if policy.max_age_secs == Some(0) {
+        bail!("retention max_age_secs must be at least 1");
+    }
+    if policy.max_count == Some(0) {
+        bail!("retention max_count must be at least 1");
+    }
+    if policy.max_bytes == Some(0) {
+        bail!("retention max_bytes must be at least 1");
+    }
+    Ok(())
+}
+
+fn limits(policy: &RetentionPolicy) -> Retention {
+    Retention {
+        max_age_ms: policy.max_age_secs.map(|s| s.saturating_mul(1000)),
+        max_count: policy.max_count,
+        max_bytes: policy.max_bytes,
+    }
+}
+
+/// Apply the current policy to the inbox once.
+pub async fn prune_once(state: &Arc<NodeState>) -> Result<Pruned> {
+    let policy = state.retention.lock().await.policy;
+    let now_ms = state.clock.now_ms();
+    let pruned = state.inbox.lock().await.prune(&limits(&policy), now_ms)?;
+    state.retention.lock().await.record(pruned, now_ms);
+    if pruned.total() > 0 {
+        tracing::info!(
+            by_age = pruned.by_age,
+            by_count = pruned.by_count,
+            by_bytes = pruned.by_bytes,
+            "pruned inbox"
+        );
+    }
+    Ok(pruned)
+}
+
+/// Prune the inbox every [`JANITOR_TICK`] until the daemon shuts down.
+/// Policy changes take effect on the next tick.
+pub async fn janitor_loop(state: Arc<NodeState>) {
+    let mut interval = tokio::time::interval(JANITOR_TICK);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = state.lifecycle.shutdown_requested() => return,
+        }
+        if let Err(e) = prune_once(&state).await {
+            tracing::warn!(err = %format!("{e:#}"), "inbox pruning failed");
+        }
+    }
+}
+
+pub async fn handle_retention_stats(state: &Arc<NodeState>) -> Response {
+    let (inbox_messages, inbox_bytes) = {
+        let inbox = state.inbox.lock().await;
+        (inbox.len(), inbox.stored_bytes())
+    };
+    let janitor = state.retention.lock().await;
+    let stats = RetentionStats {
+        policy: janitor.policy,
+        inbox_messages,
+        inbox_bytes,
+        pruned_by_age: janitor.pruned_by_age,
+        pruned_by_count: janitor.pruned_by_count,
+        pruned_by_bytes: janitor.pruned_by_bytes,
+        last_run_ms: janitor.last_run_ms,
+    };
+    ok_response(Some(serde_json::to_value(stats).unwrap()))
+}
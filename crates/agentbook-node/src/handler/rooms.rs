@@ -1,14 +1,17 @@
-use super::{NodeState, error_response, now_ms, ok_response};
+use super::{NodeState, error_response, now_ms, ok_response, routing};
 use agentbook::protocol::{Event, InboxEntry, MessageType, Response, RoomInfo};
 use agentbook_crypto::crypto::{decrypt_with_key, encrypt_with_key, verify_signature};
 use agentbook_crypto::recovery::derive_key_from_passphrase;
+use agentbook_mesh::envelope_schema;
 use agentbook_mesh::inbox::{InboxMessage, MessageType as MeshMessageType};
+use agentbook_mesh::state_file;
 use agentbook_proto::host::v1 as host_pb;
 use agentbook_proto::mesh::v1 as mesh_pb;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
 
 /// Maximum room message body length.
@@ -60,6 +63,12 @@ pub async fn handle_join_room(
     if let Err(e) = validate_room_name(room) {
         return error_response("invalid_room", &e);
     }
+    if passphrase.is_none() && state.require_encryption.load(Ordering::Relaxed) {
+        return error_response(
+            "encryption_required",
+            "this node only allows encrypted rooms; join with a passphrase",
+        );
+    }
 
     // Derive encryption key if passphrase provided
     let encrypted_key_hex = if let Some(pass) = passphrase {
@@ -151,6 +160,12 @@ pub async fn handle_send_room(state: &Arc<NodeState>, room: &str, body: &str) ->
         None => return error_response("not_joined", &format!("not in room #{room}")),
     };
     drop(rooms);
+    if config.key().is_none() && state.require_encryption.load(Ordering::Relaxed) {
+        return error_response(
+            "encryption_required",
+            &format!("#{room} is an open room and this node only sends encrypted messages"),
+        );
+    }
 
     // Check cooldown
     {
@@ -200,6 +215,8 @@ pub async fn handle_send_room(state: &Arc<NodeState>, room: &str, body: &str) ->
         signature_b64,
         timestamp_ms: timestamp,
         topic: Some(room.to_string()),
+        sealed: false,
+        schema_version: envelope_schema::CURRENT,
     };
 
     if let Err(e) = transport.send_via_relay(envelope).await {
@@ -217,6 +234,9 @@ pub async fn handle_send_room(state: &Arc<NodeState>, room: &str, body: &str) ->
         timestamp_ms: timestamp,
         acked: true, // own messages are auto-acked
         message_type: MeshMessageType::RoomMessage,
+        attachments: Vec::new(),
+        in_reply_to: None,
+        thread_id: None,
     };
 
     let mut inbox = state.inbox.lock().await;
@@ -279,6 +299,9 @@ pub async fn handle_room_inbox(
             timestamp_ms: m.timestamp_ms,
             acked: m.acked,
             room: m.topic.clone(),
+            attachments: Vec::new(),
+            in_reply_to: None,
+            thread_id: None,
         });
     }
 
@@ -348,6 +371,13 @@ pub async fn process_inbound_room(state: &Arc<NodeState>, envelope: mesh_pb::Env
         }
     };
     drop(rooms);
+    if room_system_type.is_none()
+        && config.key().is_none()
+        && state.require_encryption.load(Ordering::Relaxed)
+    {
+        tracing::debug!(room, msg_id = %envelope.message_id, "dropping plaintext room message");
+        return;
+    }
 
     // Room system events: body is the display label in ciphertext_b64, no decryption needed.
     if let Some(system_type) = room_system_type {
@@ -367,6 +397,9 @@ pub async fn process_inbound_room(state: &Arc<NodeState>, envelope: mesh_pb::Env
             timestamp_ms: envelope.timestamp_ms,
             acked: false,
             message_type: system_type,
+            attachments: Vec::new(),
+            in_reply_to: None,
+            thread_id: None,
         };
         let msg_id = envelope.message_id.clone();
         let from = envelope.from_node_id.clone();
@@ -406,6 +439,10 @@ pub async fn process_inbound_room(state: &Arc<NodeState>, envelope: mesh_pb::Env
         envelope.ciphertext_b64.clone()
     };
 
+    if !routing::apply(state, Some(&room), &envelope.from_node_id, &body).await {
+        return;
+    }
+
     let msg = InboxMessage {
         message_id: envelope.message_id.clone(),
         from_node_id: envelope.from_node_id.clone(),
@@ -416,17 +453,23 @@ pub async fn process_inbound_room(state: &Arc<NodeState>, envelope: mesh_pb::Env
         timestamp_ms: envelope.timestamp_ms,
         acked: false,
         message_type: MeshMessageType::RoomMessage,
+        attachments: Vec::new(),
+        in_reply_to: None,
+        thread_id: None,
     };
 
     let preview = body.chars().take(50).collect::<String>();
     let from = envelope.from_node_id.clone();
     let msg_id = envelope.message_id.clone();
+    let pushed = state.clients.has_inbox_gateway().then(|| msg.clone());
 
     let mut inbox = state.inbox.lock().await;
     if let Err(e) = inbox.push(msg) {
         tracing::error!(err = %e, "failed to store room message");
         return;
     }
+    drop(inbox);
+    super::activity::record(state, &from).await;
 
     // Emit event
     let _ = state.event_tx.send(Event::NewRoomMessage {
@@ -436,24 +479,25 @@ pub async fn process_inbound_room(state: &Arc<NodeState>, envelope: mesh_pb::Env
         message_type: MessageType::RoomMessage,
         preview,
     });
+    if let Some(msg) = pushed {
+        super::messaging::push_to_inbox_gateway(state, msg).await;
+    }
 }
 
 /// Load persisted room configs from rooms.json.
 pub fn load_rooms(state_dir: &Path) -> HashMap<String, RoomConfig> {
     let path = state_dir.join("rooms.json");
-    if !path.exists() {
-        return HashMap::new();
-    }
-    match std::fs::read_to_string(&path) {
-        Ok(data) => match serde_json::from_str::<Vec<RoomConfig>>(&data) {
-            Ok(configs) => configs.into_iter().map(|c| (c.room.clone(), c)).collect(),
-            Err(e) => {
-                tracing::warn!(err = %e, "failed to parse rooms.json, starting fresh");
-                HashMap::new()
-            }
-        },
+    let parsed = state_file::read(&path, |data| {
+        Ok(serde_json::from_str::<Vec<RoomConfig>>(data)?)
+    });
+    match parsed {
+        Ok(configs) => configs
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| (c.room.clone(), c))
+            .collect(),
         Err(e) => {
-            tracing::warn!(err = %e, "failed to read rooms.json");
+            tracing::warn!(err = %format!("{e:#}"), "failed to load rooms.json, starting fresh");
             HashMap::new()
         }
     }
@@ -464,8 +508,7 @@ fn save_rooms(state_dir: &Path, rooms: &HashMap<String, RoomConfig>) -> anyhow::
     let path = state_dir.join("rooms.json");
     let configs: Vec<&RoomConfig> = rooms.values().collect();
     let data = serde_json::to_string_pretty(&configs)?;
-    std::fs::write(path, data)?;
-    Ok(())
+    state_file::write(&path, data)
 }
 
 #[cfg(test)]
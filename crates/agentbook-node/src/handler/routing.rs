@@ -0,0 +1,231 @@
+//! Routing rules for inbound DMs and room messages from `node_config.json`:
+//! drop noise, copy matching messages to another node as DMs, or freeze the
+//! node when something alarming arrives.
+
+use super::{NodeState, messaging};
+use agentbook::protocol::{Response, RouteAction, RouteRule};
+use agentbook_mesh::follow::validate_group;
+use anyhow::{Result, bail};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+/// Reject rules that match everything, could never match, or have nowhere
+/// to forward to.
+pub fn validate(rule: &RouteRule) -> Result<()> {
+    match (&rule.topic, &rule.contains, &rule.group) {
+        (None, None, None) => bail!("route needs a topic, contains or group"),
+        (Some(topic), _, _) if topic.is_empty() => bail!("route topic must not be empty"),
+        (_, Some(text), _) if text.is_empty() => bail!("route contains must not be empty"),
+        (_, _, Some(group)) => validate_group(group)?,
+        _ => {}
+    }
+    if let RouteAction::Forward { to } = &rule.action
+        && to.trim_start_matches('@').is_empty()
+    {
+        bail!("route forwards to an empty target");
+    }
+    Ok(())
+}
+
+/// Whether `topic` matches `pattern`, where `*` matches any run of
+/// characters.
+fn matches(pattern: &str, topic: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = topic.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    if parts.peek().is_none() {
+        return rest.is_empty();
+    }
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+fn rule_matches(rule: &RouteRule, room: Option<&str>, groups: &[String], body: &str) -> bool {
+    let topic_ok = match (&rule.topic, room) {
+        (None, _) => true,
+        (Some(pattern), Some(room)) => matches(pattern, room),
+        (Some(_), None) => false,
+    };
+    topic_ok
+        && rule
+            .group
+            .as_ref()
+            .is_none_or(|group| groups.contains(group))
+        && rule
+            .contains
+            .as_ref()
+            .is_none_or(|text| body.contains(text))
+}
+
+/// The action of the first rule matching a message in `room` (`None` for
+/// a DM) with `body`, from a sender in `groups`.
+pub fn route<'a>(
+    rules: &'a [RouteRule],
+    room: Option<&str>,
+    groups: &[String],
+    body: &str,
+) -> Option<&'a RouteAction> {
+    rules
+        .iter()
+        .find(|rule| rule_matches(rule, room, groups, body))
+        .map(|rule| &rule.action)
+}
+
+/// Apply the routing rules to an inbound message. Returns whether it should
+/// be stored.
+pub async fn apply(state: &Arc<NodeState>, room: Option<&str>, from: &str, body: &str) -> bool {
+    let groups = state
+        .follow_store
+        .lock()
+        .await
+        .get(from)
+        .map(|record| record.groups.clone())
+        .unwrap_or_default();
+    let action = route(&state.routes.lock().await, room, &groups, body).cloned();
+    match action {
+        None => true,
+        Some(RouteAction::Drop) => {
+            tracing::debug!(room, from, "dropping routed message");
+            false
+        }
+        Some(RouteAction::Forward { to }) => {
+            // Two nodes forwarding to each other would bounce a DM forever.
+            if room.is_some() || to != from {
+                forward(state, &to, room, from, body);
+            }
+            true
+        }
+        Some(RouteAction::Freeze) => {
+            if !state.frozen.swap(true, Ordering::SeqCst) {
+                tracing::warn!(room, from, "node frozen by a routing rule");
+            }
+            true
+        }
+    }
+}
+
+/// Send a copy of a message to `to` in the background. Failures are
+/// logged; the original is stored either way.
+fn forward(state: &Arc<NodeState>, to: &str, room: Option<&str>, from: &str, body: &str) {
+    let state = state.clone();
+    let to = to.to_string();
+    let body = match room {
+        Some(room) => format!("[#{room}] {from}: {body}"),
+        None => format!("[dm] {from}: {body}"),
+    };
+    tokio::spawn(async move {
+        if let Response::Error { code, message } =
+            messaging::handle_send_dm(&state, &to, &body, &[], None, None).await
+        {
+            tracing::warn!(to, code, message, "failed to forward message");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(topic: Option<&str>, contains: Option<&str>, action: RouteAction) -> RouteRule {
+        RouteRule {
+            topic: topic.map(str::to_string),
+            contains: contains.map(str::to_string),
+            group: None,
+            action,
+        }
+    }
+
+    #[test]
+    fn patterns_match_room_names() {
+        assert!(matches("ops", "ops"));
+        assert!(!matches("ops", "ops-2"));
+        assert!(matches("escalation*", "escalation"));
+        assert!(matches("escalation*", "escalation-db"));
+        assert!(!matches("escalation*", "db-escalation"));
+        assert!(matches("*-alerts", "db-alerts"));
+        assert!(matches("team-*-alerts", "team-db-alerts"));
+        assert!(!matches("team-*-alerts", "team-db-alerts-old"));
+        assert!(matches("*", "anything"));
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let forward = RouteAction::Forward {
+            to: "@supervisor".into(),
+        };
+        let rules = vec![
+            rule(None, Some("panicked at"), RouteAction::Freeze),
+            rule(Some("escalation*"), None, forward.clone()),
+            rule(Some("*"), None, RouteAction::Drop),
+        ];
+        assert_eq!(
+            route(&rules, Some("escalation-db"), &[], "disk full"),
+            Some(&forward)
+        );
+        assert_eq!(
+            route(
+                &rules,
+                Some("escalation-db"),
+                &[],
+                "thread 'main' panicked at src/x.rs"
+            ),
+            Some(&RouteAction::Freeze)
+        );
+        assert_eq!(
+            route(&rules, Some("chatter"), &[], "hi"),
+            Some(&RouteAction::Drop)
+        );
+        // Topic rules never match DMs.
+        assert_eq!(route(&rules, None, &[], "hi"), None);
+        assert_eq!(
+            route(&rules, None, &[], "worker panicked at start"),
+            Some(&RouteAction::Freeze)
+        );
+    }
+
+    #[test]
+    fn group_rules_match_members_only() {
+        let infra = vec!["team-infra".to_string()];
+        let rules = vec![RouteRule {
+            group: Some("team-infra".into()),
+            ..rule(None, Some("deploy"), RouteAction::Freeze)
+        }];
+        assert_eq!(
+            route(&rules, None, &infra, "deploy failed"),
+            Some(&RouteAction::Freeze)
+        );
+        assert_eq!(
+            route(&rules, Some("ops"), &infra, "deploy failed"),
+            Some(&RouteAction::Freeze)
+        );
+        assert_eq!(route(&rules, None, &[], "deploy failed"), None);
+        assert_eq!(route(&rules, None, &infra, "hello"), None);
+
+        let bad = RouteRule {
+            group: Some("Team Infra".into()),
+            ..rule(None, None, RouteAction::Drop)
+        };
+        assert!(validate(&bad).is_err());
+    }
+
+    #[test]
+    fn rules_must_say_what_they_match() {
+        assert!(validate(&rule(None, None, RouteAction::Drop)).is_err());
+        assert!(validate(&rule(Some(""), None, RouteAction::Drop)).is_err());
+        assert!(validate(&rule(None, Some(""), RouteAction::Drop)).is_err());
+        let nowhere = RouteAction::Forward { to: "@".into() };
+        assert!(validate(&rule(Some("ops"), None, nowhere)).is_err());
+        assert!(validate(&rule(None, Some("panicked at"), RouteAction::Freeze)).is_ok());
+    }
+}
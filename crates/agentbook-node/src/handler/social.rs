@@ -1,9 +1,14 @@
 use super::{NodeState, error_response, now_ms, ok_response};
-use agentbook::protocol::{FollowInfo, HealthStatus, IdentityInfo, Response, SyncResult};
+use agentbook::protocol::{
+    FollowInfo, GroupInfo, HealthStatus, IdentityInfo, IngressBudget, IngressStats,
+    PeerIngressUsage, RelayUsage, Response, SyncResult, UsageWindow,
+};
+use agentbook_crypto::crypto::relay_usage_payload;
 use agentbook_mesh::follow::FollowRecord;
 use agentbook_proto::host::v1 as host_pb;
 use alloy::primitives::Address;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
 
 /// Resolved target info from a `@username` or raw node_id.
 pub(crate) struct ResolvedTarget {
@@ -29,13 +34,23 @@ fn username_target(target: &str, allow_bare_username: bool) -> Option<&str> {
     None
 }
 
-/// Resolve a target that may be `@username` or a raw node_id.
-/// Username lookups accept both `@username` and bare `username`.
+/// Resolve a target that may be an alias, `@username` or a raw node_id.
+/// A bare name is looked up as an alias of a followed node first, then as
+/// a username; `@username` always goes to the relay.
 /// Address-like targets are treated as raw node IDs.
 pub(crate) async fn resolve_target(
     state: &Arc<NodeState>,
     target: &str,
 ) -> Result<ResolvedTarget, Response> {
+    if !target.starts_with('@')
+        && let Some(record) = state.follow_store.lock().await.by_alias(target)
+    {
+        return Ok(ResolvedTarget {
+            node_id: record.node_id.clone(),
+            public_key_b64: record.public_key_b64.clone(),
+            username: record.username.clone(),
+        });
+    }
     if let Some(username) = username_target(target, !state.relay_hosts.is_empty()) {
         if state.relay_hosts.is_empty() {
             return Err(error_response(
@@ -95,7 +110,7 @@ pub(crate) async fn resolve_target(
 }
 
 /// Notify the relay about a follow relationship (best-effort, non-blocking).
-async fn notify_relay_follow(state: &Arc<NodeState>, followed_node_id: &str) -> bool {
+pub(crate) async fn notify_relay_follow(state: &Arc<NodeState>, followed_node_id: &str) -> bool {
     let sig = match state.identity.sign(state.identity.node_id.as_bytes()) {
         Ok(s) => s,
         Err(e) => {
@@ -212,6 +227,69 @@ pub(crate) async fn fetch_followers_from_relay(
     Err("could not reach any relay for GetFollowers".to_string())
 }
 
+/// Ask each relay how much this node has sent through it. Unreachable
+/// relays are skipped.
+pub async fn handle_relay_usage(state: &Arc<NodeState>, since_ms: Option<u64>) -> Response {
+    if state.relay_hosts.is_empty() {
+        return error_response("no_relay", "not connected to any relay");
+    }
+    let mut reports = Vec::new();
+    for host in &state.relay_hosts {
+        let mut client = match state.get_grpc_client(host).await {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!(host = %host, err = %e, "failed to connect for GetUsage");
+                continue;
+            }
+        };
+        let timestamp_ms = now_ms();
+        let payload = relay_usage_payload(&state.identity.node_id, timestamp_ms);
+        let signature_b64 = match state.identity.sign(&payload) {
+            Ok(sig) => sig,
+            Err(e) => return error_response("sign_failed", &e.to_string()),
+        };
+        let report = match client
+            .get_usage(host_pb::GetUsageRequest {
+                node_id: state.identity.node_id.clone(),
+                public_key_b64: state.identity.public_key_b64.clone(),
+                timestamp_ms,
+                signature_b64,
+                since_ms: since_ms.unwrap_or(0),
+            })
+            .await
+        {
+            Ok(resp) => resp.into_inner(),
+            Err(e) => {
+                tracing::warn!(host = %host, err = %e, "GetUsage RPC failed");
+                continue;
+            }
+        };
+        let windows: Vec<UsageWindow> = report
+            .windows
+            .into_iter()
+            .map(|w| UsageWindow {
+                start_ms: w.start_ms,
+                frames: w.frames,
+                bytes: w.bytes,
+            })
+            .collect();
+        reports.push(RelayUsage {
+            host: host.clone(),
+            window_ms: report.window_ms,
+            total_frames: windows.iter().map(|w| w.frames).sum(),
+            total_bytes: windows.iter().map(|w| w.bytes).sum(),
+            windows,
+        });
+    }
+    if reports.is_empty() {
+        return error_response(
+            "relay_unavailable",
+            "could not reach any relay for GetUsage",
+        );
+    }
+    ok_response(Some(serde_json::to_value(reports).unwrap()))
+}
+
 pub(crate) async fn ensure_own_username(state: &Arc<NodeState>) -> Option<String> {
     if let Some(username) = state.username.lock().await.clone() {
         return Some(username);
@@ -246,10 +324,46 @@ pub async fn handle_health(state: &Arc<NodeState>) -> Response {
         relay_connected: state.transport.is_some(),
         following_count,
         unread_count,
+        clients: state.clients.len(),
+        frozen: state.frozen.load(Ordering::SeqCst),
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        rss_bytes: crate::self_stats::rss_bytes(),
+        open_fds: crate::self_stats::open_fds(),
+        outbox_depth: state.outbox.lock().await.len(),
+        event_backlog: state.event_tx.len(),
+        events_lagged: state.events_lagged.load(Ordering::Relaxed),
     };
     ok_response(Some(serde_json::to_value(status).unwrap()))
 }
 
+pub async fn handle_ingress_stats(state: &Arc<NodeState>, node_id: Option<&str>) -> Response {
+    let ingress_limits = state.ingress_limits.lock().await;
+    let stats = IngressStats {
+        budgets: ingress_limits
+            .budgets()
+            .into_iter()
+            .map(|(class, budget)| IngressBudget {
+                message_class: class.as_str().to_string(),
+                capacity: budget.capacity,
+                per_second: budget.per_second,
+            })
+            .collect(),
+        peers: ingress_limits
+            .usage(node_id)
+            .into_iter()
+            .map(|peer| PeerIngressUsage {
+                node_id: peer.usage.key,
+                message_class: peer.class.as_str().to_string(),
+                tokens: peer.usage.tokens,
+                violations: peer.usage.violations,
+                times_banned: peer.usage.times_banned,
+                banned_for_ms: peer.usage.banned_for.map(|d| d.as_millis() as u64),
+            })
+            .collect(),
+    };
+    ok_response(Some(serde_json::to_value(stats).unwrap()))
+}
+
 pub async fn handle_follow(state: &Arc<NodeState>, target: &str) -> Response {
     // Resolve @username → node_id + pubkey
     let resolved = match resolve_target(state, target).await {
@@ -272,6 +386,9 @@ pub async fn handle_follow(state: &Arc<NodeState>, target: &str) -> Response {
         username: resolved.username,
         relay_hints: vec![],
         followed_at_ms: now_ms(),
+        scopes: vec![],
+        alias: None,
+        groups: vec![],
     };
 
     {
@@ -335,6 +452,65 @@ pub async fn handle_block(state: &Arc<NodeState>, target: &str) -> Response {
     ok_response(None)
 }
 
+/// Name a followed node, or clear its alias when `alias` is `None`.
+pub async fn handle_set_alias(
+    state: &Arc<NodeState>,
+    target: &str,
+    alias: Option<String>,
+) -> Response {
+    let resolved = match resolve_target(state, target).await {
+        Ok(r) => r,
+        Err(resp) => return resp,
+    };
+    let mut follow_store = state.follow_store.lock().await;
+    if !follow_store.is_following(&resolved.node_id) {
+        return error_response("not_found", &format!("not following {target}"));
+    }
+    match follow_store.set_alias(&resolved.node_id, alias) {
+        Ok(()) => ok_response(None),
+        Err(e) => error_response("invalid_alias", &e.to_string()),
+    }
+}
+
+/// Add a followed node to `group`, or take it out when `add` is false.
+pub async fn handle_group_membership(
+    state: &Arc<NodeState>,
+    group: &str,
+    target: &str,
+    add: bool,
+) -> Response {
+    let resolved = match resolve_target(state, target).await {
+        Ok(r) => r,
+        Err(resp) => return resp,
+    };
+    let mut follow_store = state.follow_store.lock().await;
+    if !follow_store.is_following(&resolved.node_id) {
+        return error_response("not_found", &format!("not following {target}"));
+    }
+    let result = if add {
+        follow_store.add_to_group(&resolved.node_id, group)
+    } else {
+        follow_store.remove_from_group(&resolved.node_id, group)
+    };
+    match result {
+        Ok(()) => ok_response(None),
+        Err(e) if add => error_response("invalid_group", &e.to_string()),
+        Err(e) => error_response("not_found", &e.to_string()),
+    }
+}
+
+pub async fn handle_groups(state: &Arc<NodeState>) -> Response {
+    let groups: Vec<GroupInfo> = state
+        .follow_store
+        .lock()
+        .await
+        .groups()
+        .into_iter()
+        .map(|(name, members)| GroupInfo { name, members })
+        .collect();
+    ok_response(Some(serde_json::to_value(groups).unwrap()))
+}
+
 pub async fn handle_following(state: &Arc<NodeState>) -> Response {
     let follow_store = state.follow_store.lock().await;
     let list: Vec<FollowInfo> = follow_store
@@ -344,6 +520,8 @@ pub async fn handle_following(state: &Arc<NodeState>) -> Response {
             node_id: f.node_id.clone(),
             username: f.username.clone(),
             followed_at_ms: f.followed_at_ms,
+            alias: f.alias.clone(),
+            groups: f.groups.clone(),
         })
         .collect();
     ok_response(Some(serde_json::to_value(list).unwrap()))
@@ -366,6 +544,8 @@ pub async fn handle_followers(state: &Arc<NodeState>) -> Response {
                         Some(e.username)
                     },
                     followed_at_ms: 0, // relay doesn't expose this currently
+                    alias: None,
+                    groups: vec![],
                 })
                 .collect();
             ok_response(Some(serde_json::to_value(list).unwrap()))
@@ -701,6 +881,9 @@ pub async fn sync_pull_from_relay(state: &Arc<NodeState>) -> Result<SyncResult,
             },
             relay_hints: vec![],
             followed_at_ms: now_ms(),
+            scopes: vec![],
+            alias: None,
+            groups: vec![],
         };
 
         if let Err(e) = follow_store.follow(record) {
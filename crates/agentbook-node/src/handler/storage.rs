@@ -0,0 +1,25 @@
+use super::{NodeState, error_response, ok_response};
+use agentbook::protocol::Response;
+use std::sync::Arc;
+
+/// Rewrite the follow store and inbox under the state cipher. Both stores
+/// already migrate plaintext on load; this is for forcing fresh nonces or
+/// checking that everything on disk is encrypted.
+pub async fn handle_reencrypt_state(state: &Arc<NodeState>) -> Response {
+    let follow_store = state.follow_store.lock().await;
+    let mut inbox = state.inbox.lock().await;
+    if !follow_store.is_encrypted() || !inbox.is_encrypted() {
+        return error_response("not_encrypted", "node state is not encrypted at rest");
+    }
+    if let Err(e) = follow_store.reencrypt() {
+        return error_response("reencrypt_failed", &format!("follow store: {e}"));
+    }
+    if let Err(e) = inbox.reencrypt() {
+        return error_response("reencrypt_failed", &format!("inbox: {e}"));
+    }
+    ok_response(Some(serde_json::json!({
+        "following": follow_store.following().len(),
+        "blocked": follow_store.blocked().len(),
+        "inbox_messages": inbox.len(),
+    })))
+}
@@ -0,0 +1,230 @@
+//! Message templates from `node_config.json`: canned DMs, feed posts and
+//! room messages sent by name with `SendTemplate`, with `{name}`
+//! placeholders filled from the request's variables.
+
+use super::{NodeState, error_response, messaging, rooms};
+use agentbook::protocol::{MessageTemplate, MessageType, Response};
+use anyhow::{Result, bail};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+fn valid_var_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Expand `body`, replacing each `{name}` with `lookup(name)`. `{{` and `}}`
+/// are literal braces; any other brace is an error.
+fn expand(body: &str, mut lookup: impl FnMut(&str) -> Result<String>) -> Result<String> {
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if let Some(after) = tail.strip_prefix("{{") {
+            out.push('{');
+            rest = after;
+        } else if let Some(after) = tail.strip_prefix("}}") {
+            out.push('}');
+            rest = after;
+        } else if tail.starts_with('}') {
+            bail!("unmatched '}}' in template body");
+        } else {
+            let Some(end) = tail.find('}') else {
+                bail!("unclosed placeholder in template body");
+            };
+            let name = &tail[1..end];
+            if !valid_var_name(name) {
+                bail!("invalid placeholder {{{name}}}");
+            }
+            out.push_str(&lookup(name)?);
+            rest = &tail[end + 1..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Reject templates that could never be sent as written.
+pub fn validate(template: &MessageTemplate) -> Result<()> {
+    let name = &template.name;
+    if name.is_empty() {
+        bail!("template name must not be empty");
+    }
+    match (template.message_type, &template.topic) {
+        (MessageType::RoomMessage, None) => bail!("template {name} needs a topic (the room)"),
+        (MessageType::RoomMessage, Some(room)) if room.is_empty() => {
+            bail!("template {name} has an empty topic")
+        }
+        (MessageType::RoomMessage, Some(_)) => {}
+        (MessageType::DmText | MessageType::FeedPost, None) => {}
+        (MessageType::DmText | MessageType::FeedPost, Some(_)) => {
+            bail!("template {name}: only room_message templates take a topic")
+        }
+        (other, _) => bail!("template {name} has unsupported message_type {other:?}"),
+    }
+    let mut seen = HashSet::new();
+    for var in &template.required_vars {
+        if !valid_var_name(var) {
+            bail!("template {name} has invalid variable name {var:?}");
+        }
+        if !seen.insert(var.as_str()) {
+            bail!("template {name} lists {var} twice");
+        }
+    }
+    expand(&template.body, |var| {
+        if !seen.contains(var) {
+            bail!("template {name} uses {{{var}}} but does not list it in required_vars");
+        }
+        Ok(String::new())
+    })?;
+    Ok(())
+}
+
+/// [`validate`] each template and check their names are unique.
+pub fn validate_all(templates: &[MessageTemplate]) -> Result<()> {
+    let mut names = HashSet::new();
+    for template in templates {
+        validate(template)?;
+        if !names.insert(template.name.as_str()) {
+            bail!("more than one template is named {}", template.name);
+        }
+    }
+    Ok(())
+}
+
+/// The body `template` produces with `vars`, which must supply exactly its
+/// required variables.
+pub fn render(template: &MessageTemplate, vars: &BTreeMap<String, String>) -> Result<String> {
+    let missing: Vec<&str> = template
+        .required_vars
+        .iter()
+        .filter(|var| !vars.contains_key(*var))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        bail!("missing variables: {}", missing.join(", "));
+    }
+    let unknown: Vec<&str> = vars
+        .keys()
+        .filter(|var| !template.required_vars.contains(var))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        bail!("unknown variables: {}", unknown.join(", "));
+    }
+    expand(&template.body, |var| match vars.get(var) {
+        Some(value) => Ok(value.clone()),
+        None => bail!("missing variables: {var}"),
+    })
+}
+
+pub async fn handle_send_template(
+    state: &Arc<NodeState>,
+    name: &str,
+    to: Option<&str>,
+    vars: &BTreeMap<String, String>,
+) -> Response {
+    let template = state
+        .templates
+        .lock()
+        .await
+        .iter()
+        .find(|t| t.name == name)
+        .cloned();
+    let Some(template) = template else {
+        return error_response("not_found", &format!("no template named {name:?}"));
+    };
+    let body = match render(&template, vars) {
+        Ok(body) => body,
+        Err(e) => return error_response("invalid_request", &format!("template {name}: {e:#}")),
+    };
+    match (template.message_type, to, template.topic.as_deref()) {
+        (MessageType::DmText, Some(to), _) => {
+            messaging::handle_send_dm(state, to, &body, &[], None, None).await
+        }
+        (MessageType::DmText, None, _) => error_response(
+            "invalid_request",
+            &format!("template {name} sends a DM; give a recipient"),
+        ),
+        (_, Some(_), _) => error_response(
+            "invalid_request",
+            &format!("template {name} does not send a DM; it takes no recipient"),
+        ),
+        (MessageType::FeedPost, None, _) => messaging::handle_post_feed(state, &body).await,
+        (MessageType::RoomMessage, None, Some(room)) => {
+            rooms::handle_send_room(state, room, &body).await
+        }
+        _ => error_response("invalid_request", &format!("template {name} is invalid")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(message_type: MessageType, body: &str, vars: &[&str]) -> MessageTemplate {
+        MessageTemplate {
+            name: "deploy-request".into(),
+            message_type,
+            body: body.into(),
+            topic: (message_type == MessageType::RoomMessage).then(|| "deploys".into()),
+            required_vars: vars.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn placeholders_are_filled_and_braces_escaped() {
+        let t = template(
+            MessageType::DmText,
+            "deploy {sha} to {env} {{now}}",
+            &["env", "sha"],
+        );
+        assert!(validate(&t).is_ok());
+        assert_eq!(
+            render(&t, &vars(&[("env", "staging"), ("sha", "abc123")])).unwrap(),
+            "deploy abc123 to staging {now}"
+        );
+    }
+
+    #[test]
+    fn vars_must_match_the_template_exactly() {
+        let t = template(MessageType::FeedPost, "deploying {env}", &["env"]);
+        let missing = render(&t, &vars(&[])).unwrap_err();
+        assert!(missing.to_string().contains("missing variables: env"));
+        let unknown = render(&t, &vars(&[("env", "prod"), ("typo", "x")])).unwrap_err();
+        assert!(unknown.to_string().contains("unknown variables: typo"));
+    }
+
+    #[test]
+    fn templates_are_checked_when_loaded() {
+        // Every placeholder must be declared.
+        assert!(validate(&template(MessageType::DmText, "to {env}", &[])).is_err());
+        assert!(validate(&template(MessageType::DmText, "to {env", &["env"])).is_err());
+        assert!(validate(&template(MessageType::DmText, "to env}", &[])).is_err());
+        assert!(validate(&template(MessageType::DmText, "{}", &[])).is_err());
+        assert!(validate(&template(MessageType::DmText, "hi", &["a", "a"])).is_err());
+
+        // Only room messages have a topic, and they need one.
+        let mut room = template(MessageType::RoomMessage, "hi", &[]);
+        assert!(validate(&room).is_ok());
+        room.topic = None;
+        assert!(validate(&room).is_err());
+        let mut dm = template(MessageType::DmText, "hi", &[]);
+        dm.topic = Some("deploys".into());
+        assert!(validate(&dm).is_err());
+        assert!(validate(&template(MessageType::RoomJoin, "hi", &[])).is_err());
+
+        let t = template(MessageType::FeedPost, "hi", &[]);
+        assert!(validate_all(&[t.clone(), t]).is_err());
+    }
+}
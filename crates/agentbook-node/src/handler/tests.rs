@@ -1,12 +1,14 @@
 use super::*;
 use agentbook::protocol::{
-    FollowInfo, HealthStatus, IdentityInfo, InboxEntry, MessageType, Request, Response,
+    FollowInfo, GroupInfo, HealthStatus, IdentityInfo, InboxEntry, MessageType, Request, Response,
     TotpSetupInfo, WalletType as ProtoWalletType,
 };
 use agentbook_mesh::crypto::{encrypt_with_key, random_key_material};
+use agentbook_mesh::envelope_schema;
 use agentbook_mesh::follow::{FollowRecord, FollowStore};
 use agentbook_mesh::identity::NodeIdentity;
 use agentbook_mesh::inbox::{InboxMessage, MessageType as MeshMessageType, NodeInbox};
+use agentbook_mesh::key_notice::{KeyRevocationNotice, KeyRotationNotice, encode_notice};
 use agentbook_proto::mesh::v1 as mesh_pb;
 use agentbook_wallet::spending_limit::SpendingLimitConfig;
 use base64::Engine;
@@ -14,24 +16,51 @@ use std::sync::Arc;
 use zeroize::Zeroizing;
 
 /// Create a test NodeState with no relay transport and yolo disabled.
-fn make_test_state() -> (Arc<NodeState>, tempfile::TempDir) {
+pub(crate) fn make_test_state() -> (Arc<NodeState>, tempfile::TempDir) {
+    make_test_state_with_clock(Arc::new(agentbook_crypto::time::SystemClock))
+}
+
+/// Like `make_test_state`, driven by the given clock.
+pub(crate) fn make_test_state_with_clock(
+    clock: Arc<dyn agentbook_crypto::time::Clock>,
+) -> (Arc<NodeState>, tempfile::TempDir) {
     let dir = tempfile::tempdir().unwrap();
-    let state_dir = dir.path().to_path_buf();
+    let state = state_in(dir.path(), clock);
+    (state, dir)
+}
+
+/// Like `make_test_state`, in an existing state directory (e.g. one with an
+/// `access.json` already written).
+pub(crate) fn make_test_state_in(state_dir: &std::path::Path) -> Arc<NodeState> {
+    state_in(state_dir, Arc::new(agentbook_crypto::time::SystemClock))
+}
+
+fn state_in(
+    state_dir: &std::path::Path,
+    clock: Arc<dyn agentbook_crypto::time::Clock>,
+) -> Arc<NodeState> {
     let kek = random_key_material();
-    let identity = NodeIdentity::load_or_create(&state_dir, &kek).unwrap();
-    let follow_store = FollowStore::load(&state_dir).unwrap();
-    let inbox = NodeInbox::load(&state_dir).unwrap();
+    let identity = NodeIdentity::load_or_create(state_dir, &kek).unwrap();
+    let follow_store = FollowStore::load(state_dir).unwrap();
+    let inbox = NodeInbox::load(state_dir).unwrap();
 
     let wallet_config = WalletConfig {
         rpc_url: "https://mainnet.base.org".to_string(),
         yolo_enabled: false,
-        state_dir,
+        state_dir: state_dir.to_path_buf(),
         kek: Zeroizing::new(kek),
         spending_limit_config: SpendingLimitConfig::default(),
     };
 
-    let state = NodeState::new(identity, follow_store, inbox, None, vec![], wallet_config);
-    (state, dir)
+    NodeState::with_clock(
+        identity,
+        follow_store,
+        inbox,
+        None,
+        vec![],
+        wallet_config,
+        clock,
+    )
 }
 
 /// Create a test NodeState with yolo enabled (but no key file on disk).
@@ -105,6 +134,8 @@ fn make_encrypted_dm_envelope(
         signature_b64,
         timestamp_ms: 12345,
         topic: None,
+        sealed: false,
+        schema_version: envelope_schema::CURRENT,
     }
 }
 
@@ -116,6 +147,9 @@ async fn follow_sender(state: &Arc<NodeState>, sender: &NodeIdentity) {
         username: None,
         relay_hints: vec![],
         followed_at_ms: now_ms(),
+        scopes: vec![],
+        alias: None,
+        groups: vec![],
     };
     state.follow_store.lock().await.follow(record).unwrap();
 }
@@ -183,6 +217,64 @@ async fn health_no_relay() {
     assert!(!status.relay_connected);
     assert_eq!(status.following_count, 0);
     assert_eq!(status.unread_count, 0);
+    assert_eq!(status.outbox_depth, 0);
+    assert_eq!(status.events_lagged, 0);
+}
+
+// ---------------------------------------------------------------------------
+// Drain
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn drain_waits_for_in_flight_and_refuses_new_requests() {
+    let (state, _dir) = make_test_state();
+    let guard = state.lifecycle.admit(&Request::Identity).unwrap();
+
+    let drain_state = state.clone();
+    let drain = tokio::spawn(async move {
+        handle_request(
+            &drain_state,
+            Request::Drain {
+                timeout_ms: Some(10_000),
+            },
+        )
+        .await
+    });
+    while !state.lifecycle.is_draining() {
+        tokio::task::yield_now().await;
+    }
+
+    assert_error(&handle_request(&state, Request::Identity).await, "draining");
+    assert_error(
+        &handle_request(&state, Request::Drain { timeout_ms: None }).await,
+        "draining",
+    );
+    assert_ok(&handle_request(&state, Request::Health).await);
+    assert!(!drain.is_finished());
+
+    drop(guard);
+    let data = assert_ok(&drain.await.unwrap()).unwrap();
+    let result: agentbook::protocol::DrainResult = serde_json::from_value(data).unwrap();
+    assert!(result.drained);
+    assert_eq!(result.in_flight, 0);
+}
+
+#[tokio::test]
+async fn drain_reports_timeout() {
+    let (state, _dir) = make_test_state();
+    let _guard = state.lifecycle.admit(&Request::Identity).unwrap();
+
+    let resp = handle_request(
+        &state,
+        Request::Drain {
+            timeout_ms: Some(50),
+        },
+    )
+    .await;
+    let result: agentbook::protocol::DrainResult =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert!(!result.drained);
+    assert_eq!(result.in_flight, 1);
 }
 
 // ---------------------------------------------------------------------------
@@ -297,6 +389,92 @@ async fn unfollow_succeeds() {
     assert!(list.is_empty());
 }
 
+#[tokio::test]
+async fn alias_names_a_followed_node() {
+    let (state, _dir) = make_test_state();
+    let set_alias = |target: &str, alias: &str| Request::SetAlias {
+        target: target.into(),
+        alias: Some(alias.into()),
+    };
+    assert_error(
+        &handle_request(&state, set_alias("node-a", "builder")).await,
+        "not_found",
+    );
+
+    handle_request(
+        &state,
+        Request::Follow {
+            target: "node-a".into(),
+        },
+    )
+    .await;
+    assert_error(
+        &handle_request(&state, set_alias("node-a", "Builder!")).await,
+        "invalid_alias",
+    );
+    assert_ok(&handle_request(&state, set_alias("node-a", "builder")).await);
+
+    let resp = handle_request(&state, Request::Following).await;
+    let list: Vec<FollowInfo> = serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert_eq!(list[0].alias.as_deref(), Some("builder"));
+
+    // The alias works wherever a target does.
+    let resp = handle_request(
+        &state,
+        Request::Unfollow {
+            target: "builder".into(),
+        },
+    )
+    .await;
+    assert_ok(&resp);
+    assert!(!state.follow_store.lock().await.is_following("node-a"));
+}
+
+#[tokio::test]
+async fn groups_collect_followed_nodes() {
+    let (state, _dir) = make_test_state();
+    let membership = |group: &str, target: &str| Request::GroupAdd {
+        group: group.into(),
+        target: target.into(),
+    };
+    assert_error(
+        &handle_request(&state, membership("team-infra", "node-a")).await,
+        "not_found",
+    );
+
+    for target in ["node-a", "node-b"] {
+        handle_request(
+            &state,
+            Request::Follow {
+                target: target.into(),
+            },
+        )
+        .await;
+    }
+    assert_error(
+        &handle_request(&state, membership("Team Infra", "node-a")).await,
+        "invalid_group",
+    );
+    assert_ok(&handle_request(&state, membership("team-infra", "node-a")).await);
+    assert_ok(&handle_request(&state, membership("team-infra", "node-b")).await);
+    assert_ok(
+        &handle_request(
+            &state,
+            Request::GroupRemove {
+                group: "team-infra".into(),
+                target: "node-b".into(),
+            },
+        )
+        .await,
+    );
+
+    let resp = handle_request(&state, Request::Groups).await;
+    let groups: Vec<GroupInfo> = serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].name, "team-infra");
+    assert_eq!(groups[0].members, ["node-a"]);
+}
+
 #[tokio::test]
 async fn unfollow_nonexistent_fails() {
     let (state, _dir) = make_test_state();
@@ -424,6 +602,9 @@ async fn inbox_uses_own_username_for_self_authored_messages() {
             timestamp_ms: 12345,
             acked: false,
             message_type: MeshMessageType::FeedPost,
+            attachments: Vec::new(),
+            in_reply_to: None,
+            thread_id: None,
         })
         .unwrap();
 
@@ -459,6 +640,9 @@ async fn room_inbox_uses_own_username_for_self_authored_messages() {
             timestamp_ms: 12345,
             acked: true,
             message_type: MeshMessageType::RoomMessage,
+            attachments: Vec::new(),
+            in_reply_to: None,
+            thread_id: None,
         })
         .unwrap();
 
@@ -531,6 +715,174 @@ async fn process_inbound_encrypted_dm() {
     }
 }
 
+#[tokio::test]
+async fn inbound_replies_form_a_thread() {
+    let (state, _dir) = make_test_state();
+    let (sender, _sender_dir) = make_sender_identity();
+    follow_sender(&state, &sender).await;
+
+    let reply = |parent: &str, body: &str| {
+        DmPayload {
+            body: body.to_string(),
+            in_reply_to: Some(parent.to_string()),
+            thread_id: Some("msg-1".to_string()),
+            ..DmPayload::default()
+        }
+        .encode()
+    };
+    for (id, body) in [
+        ("msg-1", "can you review #42?".to_string()),
+        ("msg-2", reply("msg-1", "on it")),
+        ("unrelated", "lunch?".to_string()),
+        ("msg-3", reply("msg-2", "approved")),
+    ] {
+        let envelope = make_encrypted_dm_envelope(&sender, &state.identity, id, &body);
+        process_inbound(&state, envelope).await;
+    }
+
+    let resp = handle_request(
+        &state,
+        Request::MessageThread {
+            thread_id: "msg-1".into(),
+        },
+    )
+    .await;
+    let data = assert_ok(&resp).unwrap();
+    let list: Vec<InboxEntry> = serde_json::from_value(data).unwrap();
+    let ids: Vec<_> = list.iter().map(|e| e.message_id.as_str()).collect();
+    assert_eq!(ids, ["msg-1", "msg-2", "msg-3"]);
+    assert_eq!(list[2].body, "approved");
+    assert_eq!(list[2].in_reply_to.as_deref(), Some("msg-2"));
+    assert_eq!(list[2].thread_id.as_deref(), Some("msg-1"));
+    assert_eq!(list[0].thread_id, None);
+
+    let resp = handle_request(
+        &state,
+        Request::MessageThread {
+            thread_id: "missing".into(),
+        },
+    )
+    .await;
+    assert_error(&resp, "not_found");
+}
+
+#[tokio::test]
+async fn unanswered_dms_go_overdue_until_the_reply_arrives() {
+    use agentbook_crypto::time::{Clock, ManualClock};
+
+    let clock = Arc::new(ManualClock::new());
+    let (state, _dir) = make_test_state_with_clock(clock.clone());
+    let (sender, _sender_dir) = make_sender_identity();
+    follow_sender(&state, &sender).await;
+    let mut event_rx = state.event_tx.subscribe();
+    let deadline_ms = clock.now_ms() + 60_000;
+    for id in ["ask-1", "ask-2"] {
+        state
+            .pending_replies
+            .lock()
+            .await
+            .expect(id, &sender.node_id, deadline_ms);
+    }
+
+    clock.advance(std::time::Duration::from_secs(61));
+    replies::check_once(&state).await;
+    let mut overdue = Vec::new();
+    while let Ok(Event::ReplyOverdue { message_id, to, .. }) = event_rx.try_recv() {
+        assert_eq!(to, sender.node_id);
+        overdue.push(message_id);
+    }
+    assert_eq!(overdue, ["ask-1", "ask-2"]);
+
+    let reply = DmPayload {
+        body: "done".to_string(),
+        in_reply_to: Some("ask-1".to_string()),
+        thread_id: Some("ask-1".to_string()),
+        ..DmPayload::default()
+    };
+    let envelope = make_encrypted_dm_envelope(&sender, &state.identity, "re-1", &reply.encode());
+    process_inbound(&state, envelope).await;
+
+    let resp = handle_request(&state, Request::PendingReplies).await;
+    let list: Vec<agentbook::protocol::PendingReply> =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].message_id, "ask-2");
+    assert!(list[0].overdue);
+}
+
+#[tokio::test]
+async fn process_inbound_sealed_dm() {
+    let (state, _dir) = make_test_state();
+    let (sender, _sender_dir) = make_sender_identity();
+    follow_sender(&state, &sender).await;
+
+    let (ciphertext_b64, nonce_b64) = messaging::seal_payload(
+        &sender,
+        &state.identity.public_key,
+        mesh_pb::MessageType::DmText,
+        "sealed hello",
+    )
+    .unwrap();
+    let signature_b64 = sender.sign(ciphertext_b64.as_bytes()).unwrap();
+    let envelope = mesh_pb::Envelope {
+        message_id: "sealed-msg".into(),
+        from_node_id: sender.node_id.clone(),
+        to_node_id: state.identity.node_id.clone(),
+        from_public_key_b64: sender.public_key_b64.clone(),
+        message_type: mesh_pb::MessageType::Unspecified as i32,
+        ciphertext_b64,
+        nonce_b64,
+        signature_b64,
+        timestamp_ms: 12345,
+        topic: None,
+        sealed: true,
+        schema_version: envelope_schema::CURRENT,
+    };
+    process_inbound(&state, envelope).await;
+
+    let inbox = state.inbox.lock().await;
+    let list = inbox.list(false, None);
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].body, "sealed hello");
+    assert_eq!(list[0].message_type, MeshMessageType::DmText);
+    assert_eq!(
+        list[0].to_node_id.as_deref(),
+        Some(state.identity.node_id.as_str())
+    );
+}
+
+#[tokio::test]
+async fn process_inbound_sealed_dm_requires_follow() {
+    let (state, _dir) = make_test_state();
+    let (sender, _sender_dir) = make_sender_identity();
+
+    let (ciphertext_b64, nonce_b64) = messaging::seal_payload(
+        &sender,
+        &state.identity.public_key,
+        mesh_pb::MessageType::DmText,
+        "not following you",
+    )
+    .unwrap();
+    let signature_b64 = sender.sign(ciphertext_b64.as_bytes()).unwrap();
+    let envelope = mesh_pb::Envelope {
+        message_id: "sealed-unfollowed".into(),
+        from_node_id: sender.node_id.clone(),
+        to_node_id: state.identity.node_id.clone(),
+        from_public_key_b64: sender.public_key_b64.clone(),
+        message_type: mesh_pb::MessageType::Unspecified as i32,
+        ciphertext_b64,
+        nonce_b64,
+        signature_b64,
+        timestamp_ms: 12345,
+        topic: None,
+        sealed: true,
+        schema_version: envelope_schema::CURRENT,
+    };
+    process_inbound(&state, envelope).await;
+
+    assert!(state.inbox.lock().await.list(false, None).is_empty());
+}
+
 #[tokio::test]
 async fn process_inbound_fallback_stores_raw_on_decryption_failure() {
     let (state, _dir) = make_test_state();
@@ -552,6 +904,8 @@ async fn process_inbound_fallback_stores_raw_on_decryption_failure() {
         signature_b64,
         timestamp_ms: 99999,
         topic: None,
+        sealed: false,
+        schema_version: envelope_schema::CURRENT,
     };
 
     process_inbound(&state, envelope).await;
@@ -573,101 +927,639 @@ async fn process_inbound_fallback_stores_raw_on_decryption_failure() {
     assert_eq!(list[0].body, raw_ciphertext);
 }
 
+/// Build an envelope carrying a key notice, signed by `signer`.
+fn make_key_notice_envelope(
+    sender: &NodeIdentity,
+    signer: &NodeIdentity,
+    recipient: &NodeIdentity,
+    message_type: mesh_pb::MessageType,
+    payload_b64: String,
+) -> mesh_pb::Envelope {
+    let signature_b64 = signer.sign(payload_b64.as_bytes()).unwrap();
+    mesh_pb::Envelope {
+        message_id: uuid::Uuid::new_v4().to_string(),
+        from_node_id: sender.node_id.clone(),
+        to_node_id: recipient.node_id.clone(),
+        from_public_key_b64: signer.public_key_b64.clone(),
+        message_type: message_type as i32,
+        ciphertext_b64: payload_b64,
+        nonce_b64: String::new(),
+        signature_b64,
+        timestamp_ms: 12345,
+        topic: None,
+        sealed: false,
+        schema_version: envelope_schema::CURRENT,
+    }
+}
+
 #[tokio::test]
-async fn inbox_ack_after_inbound() {
+async fn process_inbound_key_rotation_moves_follow() {
     let (state, _dir) = make_test_state();
     let (sender, _sender_dir) = make_sender_identity();
+    let (rotated, _rotated_dir) = make_sender_identity();
     follow_sender(&state, &sender).await;
 
-    let envelope = make_encrypted_dm_envelope(&sender, &state.identity, "ack-test", "hi");
+    // A message received before the rotation.
+    let envelope = make_encrypted_dm_envelope(&sender, &state.identity, "pre-rotation", "hi");
     process_inbound(&state, envelope).await;
 
-    // Ack it
-    let resp = handle_request(
-        &state,
-        Request::InboxAck {
-            message_id: "ack-test".into(),
-        },
-    )
-    .await;
-    assert_ok(&resp);
+    let mut events = state.event_tx.subscribe();
+    let notice = KeyRotationNotice::create(&sender.node_id, &rotated, now_ms()).unwrap();
+    let envelope = make_key_notice_envelope(
+        &sender,
+        &sender,
+        &state.identity,
+        mesh_pb::MessageType::KeyRotation,
+        encode_notice(&notice).unwrap(),
+    );
+    process_inbound(&state, envelope).await;
 
-    // Unread count should be 0
-    let resp = handle_request(&state, Request::Health).await;
-    let data = assert_ok(&resp).unwrap();
-    let status: HealthStatus = serde_json::from_value(data).unwrap();
-    assert_eq!(status.unread_count, 0);
+    match events.try_recv().unwrap() {
+        Event::KeyRotated {
+            old_node_id,
+            new_node_id,
+        } => {
+            assert_eq!(old_node_id, sender.node_id);
+            assert_eq!(new_node_id, rotated.node_id);
+        }
+        other => panic!("expected KeyRotated, got {other:?}"),
+    }
 
-    // Unread-only filter returns empty
-    let resp = handle_request(
-        &state,
-        Request::Inbox {
-            unread_only: true,
-            limit: None,
-        },
-    )
-    .await;
-    let data = assert_ok(&resp).unwrap();
-    let list: Vec<InboxEntry> = serde_json::from_value(data).unwrap();
-    assert!(list.is_empty());
+    {
+        let follow_store = state.follow_store.lock().await;
+        assert!(!follow_store.is_following(&sender.node_id));
+        assert!(follow_store.is_following(&rotated.node_id));
+        // The pre-rotation message is still attributable to the old key.
+        let inbox = state.inbox.lock().await;
+        let old = &inbox.list(false, None)[0];
+        assert!(follow_store.is_known_key(&old.from_node_id, &old.from_public_key_b64));
+    }
+
+    // DMs from the new key are now accepted.
+    let envelope = make_encrypted_dm_envelope(&rotated, &state.identity, "post-rotation", "hi");
+    process_inbound(&state, envelope).await;
+    assert_eq!(state.inbox.lock().await.list(false, None).len(), 2);
 }
 
 #[tokio::test]
-async fn inbox_limit() {
+async fn process_inbound_key_rotation_requires_old_key_signature() {
     let (state, _dir) = make_test_state();
     let (sender, _sender_dir) = make_sender_identity();
+    let (attacker, _attacker_dir) = make_sender_identity();
     follow_sender(&state, &sender).await;
 
-    for i in 0..5u64 {
-        let envelope = make_encrypted_dm_envelope(
-            &sender,
-            &state.identity,
-            &format!("msg-{i}"),
-            &format!("msg {i}"),
-        );
-        process_inbound(&state, envelope).await;
-    }
+    // The attacker claims to be the sender but signs with its own key.
+    let notice = KeyRotationNotice::create(&sender.node_id, &attacker, now_ms()).unwrap();
+    let envelope = make_key_notice_envelope(
+        &sender,
+        &attacker,
+        &state.identity,
+        mesh_pb::MessageType::KeyRotation,
+        encode_notice(&notice).unwrap(),
+    );
+    process_inbound(&state, envelope).await;
 
-    let resp = handle_request(
-        &state,
-        Request::Inbox {
-            unread_only: false,
-            limit: Some(3),
-        },
-    )
-    .await;
-    let data = assert_ok(&resp).unwrap();
-    let list: Vec<InboxEntry> = serde_json::from_value(data).unwrap();
-    assert_eq!(list.len(), 3);
+    let follow_store = state.follow_store.lock().await;
+    assert!(follow_store.is_following(&sender.node_id));
+    assert!(!follow_store.is_following(&attacker.node_id));
 }
 
 #[tokio::test]
-async fn multiple_inbound_and_unread_filter() {
+async fn process_inbound_key_revocation_rejects_later_messages() {
     let (state, _dir) = make_test_state();
     let (sender, _sender_dir) = make_sender_identity();
     follow_sender(&state, &sender).await;
 
-    for i in 0..3u64 {
-        let envelope = make_encrypted_dm_envelope(
-            &sender,
-            &state.identity,
-            &format!("m-{i}"),
-            &format!("msg {i}"),
-        );
-        process_inbound(&state, envelope).await;
-    }
+    let notice = KeyRevocationNotice {
+        node_id: sender.node_id.clone(),
+        issued_at_ms: now_ms(),
+        reason: None,
+    };
+    let envelope = make_key_notice_envelope(
+        &sender,
+        &sender,
+        &state.identity,
+        mesh_pb::MessageType::KeyRevocation,
+        encode_notice(&notice).unwrap(),
+    );
+    process_inbound(&state, envelope).await;
+    assert!(state.follow_store.lock().await.is_revoked(&sender.node_id));
 
-    // Ack first two
-    handle_request(
-        &state,
-        Request::InboxAck {
-            message_id: "m-0".into(),
-        },
-    )
-    .await;
-    handle_request(
-        &state,
-        Request::InboxAck {
+    // Re-following does not un-revoke the key; ingress still rejects it.
+    follow_sender(&state, &sender).await;
+    let envelope = make_encrypted_dm_envelope(&sender, &state.identity, "after-revoke", "hi");
+    process_inbound(&state, envelope).await;
+    assert!(state.inbox.lock().await.list(false, None).is_empty());
+}
+
+#[tokio::test]
+async fn retired_key_receives_until_grace_ends() {
+    use agentbook_crypto::time::{Clock, ManualClock};
+
+    let dir = tempfile::tempdir().unwrap();
+    let kek = random_key_material();
+    let clock = Arc::new(ManualClock::new());
+    let old = NodeIdentity::load_or_create(dir.path(), &kek).unwrap();
+    let now = clock.now_ms();
+    let new = old.rotate(&kek, now, now + 60_000).unwrap();
+
+    let wallet_config = WalletConfig {
+        rpc_url: "https://mainnet.base.org".to_string(),
+        yolo_enabled: false,
+        state_dir: dir.path().to_path_buf(),
+        kek: Zeroizing::new(kek),
+        spending_limit_config: SpendingLimitConfig::default(),
+    };
+    let state = NodeState::with_clock(
+        new,
+        FollowStore::load(dir.path()).unwrap(),
+        NodeInbox::load(dir.path()).unwrap(),
+        None,
+        vec![],
+        wallet_config,
+        clock.clone(),
+    );
+    assert_eq!(state.retired_identities.len(), 1);
+
+    let (sender, _sender_dir) = make_sender_identity();
+    follow_sender(&state, &sender).await;
+
+    // A peer that has not seen the rotation yet still writes to the old key.
+    let envelope = make_encrypted_dm_envelope(&sender, &old, "to-old-1", "still there?");
+    process_inbound(&state, envelope).await;
+    {
+        let inbox = state.inbox.lock().await;
+        let list = inbox.list(false, None);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].body, "still there?");
+    }
+
+    // After the grace period the old key no longer decrypts anything.
+    clock.advance(std::time::Duration::from_secs(61));
+    let envelope = make_encrypted_dm_envelope(&sender, &old, "to-old-2", "hello?");
+    process_inbound(&state, envelope).await;
+    let inbox = state.inbox.lock().await;
+    let list = inbox.list(false, None);
+    assert!(list.iter().all(|m| m.body != "hello?"));
+}
+
+#[tokio::test]
+async fn rotate_key_requires_relay() {
+    let (state, _dir) = make_test_state();
+    let resp = handle_request(&state, Request::RotateKey { grace_ms: None }).await;
+    assert_error(&resp, "no_relay");
+    // Nothing was rotated on disk.
+    let loaded = NodeIdentity::load_or_create(&state.wallet.state_dir, &state.wallet.kek).unwrap();
+    assert_eq!(loaded.node_id, state.identity.node_id);
+}
+
+#[tokio::test]
+async fn inbound_pings_have_their_own_budget() {
+    let clock = Arc::new(agentbook_crypto::time::ManualClock::new());
+    let (state, _dir) = make_test_state_with_clock(clock);
+    let (sender, _sender_dir) = make_sender_identity();
+    follow_sender(&state, &sender).await;
+
+    // The ping budget allows a burst of 5; the clock is frozen, so no refill.
+    for i in 0..6 {
+        let envelope = make_key_notice_envelope(
+            &sender,
+            &sender,
+            &state.identity,
+            mesh_pb::MessageType::Ping,
+            format!("ping-{i}"),
+        );
+        process_inbound(&state, envelope).await;
+    }
+
+    let resp = handle_request(
+        &state,
+        Request::IngressStats {
+            node_id: Some(sender.node_id.clone()),
+        },
+    )
+    .await;
+    let stats: agentbook::protocol::IngressStats =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert_eq!(stats.peers.len(), 1);
+    assert_eq!(stats.peers[0].message_class, "ping");
+    assert_eq!(stats.peers[0].violations, 1);
+}
+
+#[tokio::test]
+async fn ingress_budget_survives_restart_and_shows_in_stats() {
+    let clock = Arc::new(agentbook_crypto::time::ManualClock::new());
+    let (state, _dir) = make_test_state_with_clock(clock.clone());
+    let (sender, _sender_dir) = make_sender_identity();
+    follow_sender(&state, &sender).await;
+
+    // The DM budget allows a burst of 20; the clock is frozen, so no refill.
+    for i in 0..21 {
+        let envelope = make_encrypted_dm_envelope(&sender, &state.identity, &format!("m{i}"), "hi");
+        process_inbound(&state, envelope).await;
+    }
+    assert_eq!(state.inbox.lock().await.list(false, None).len(), 20);
+
+    let resp = handle_request(
+        &state,
+        Request::IngressStats {
+            node_id: Some(sender.node_id.clone()),
+        },
+    )
+    .await;
+    let stats: agentbook::protocol::IngressStats =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert_eq!(
+        stats.budgets.len(),
+        agentbook_mesh::ingress_limits::RateClass::ALL.len()
+    );
+    assert_eq!(stats.peers.len(), 1);
+    assert_eq!(stats.peers[0].message_class, "dm");
+    assert_eq!(stats.peers[0].violations, 1);
+    assert!(stats.peers[0].tokens < 1.0);
+
+    // A restarted node picks up the spent budget that shutdown wrote to
+    // rate_limits.json.
+    super::drain::persist_ingress_limits(&state).await;
+    let restarted = NodeState::with_clock(
+        NodeIdentity::load_or_create(&state.wallet.state_dir, &state.wallet.kek).unwrap(),
+        FollowStore::load(&state.wallet.state_dir).unwrap(),
+        NodeInbox::load(&state.wallet.state_dir).unwrap(),
+        None,
+        vec![],
+        WalletConfig {
+            rpc_url: "https://mainnet.base.org".to_string(),
+            yolo_enabled: false,
+            state_dir: state.wallet.state_dir.clone(),
+            kek: state.wallet.kek.clone(),
+            spending_limit_config: SpendingLimitConfig::default(),
+        },
+        clock,
+    );
+    let envelope = make_encrypted_dm_envelope(&sender, &restarted.identity, "m21", "hi");
+    process_inbound(&restarted, envelope).await;
+    assert_eq!(restarted.inbox.lock().await.list(false, None).len(), 20);
+}
+
+#[tokio::test]
+async fn config_set_applies_and_persists_ingress_budgets() {
+    let (state, _dir) = make_test_state();
+    let budget = |class: &str, capacity, per_second| agentbook::protocol::IngressBudget {
+        message_class: class.to_string(),
+        capacity,
+        per_second,
+    };
+    let dm_budget = |config: &agentbook::protocol::NodeConfig| {
+        let dm = config
+            .ingress_budgets
+            .iter()
+            .find(|b| b.message_class == "dm")
+            .unwrap();
+        (dm.capacity, dm.per_second)
+    };
+
+    let resp = handle_request(
+        &state,
+        Request::ConfigSet {
+            log_level: Some("debug".to_string()),
+            ingress_budgets: vec![budget("dm", 1, 0.5)],
+        },
+    )
+    .await;
+    let config: agentbook::protocol::NodeConfig =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert_eq!(dm_budget(&config), (1, 0.5));
+    assert_eq!(
+        config.ingress_budgets.len(),
+        agentbook_mesh::ingress_limits::RateClass::ALL.len()
+    );
+
+    // Only the override is written; the log level is kept alongside it.
+    let saved = config::load_config(&state.wallet.state_dir).unwrap();
+    assert_eq!(saved.log_level.as_deref(), Some("debug"));
+    assert_eq!(saved.ingress_budgets.len(), 1);
+
+    // An invalid update is rejected as a whole and changes nothing.
+    let resp = handle_request(
+        &state,
+        Request::ConfigSet {
+            log_level: None,
+            ingress_budgets: vec![budget("dm", 5, 1.0), budget("bogus", 1, 1.0)],
+        },
+    )
+    .await;
+    assert_error(&resp, "invalid_config");
+    let config: agentbook::protocol::NodeConfig =
+        serde_json::from_value(assert_ok(&handle_request(&state, Request::Config).await).unwrap())
+            .unwrap();
+    assert_eq!(dm_budget(&config), (1, 0.5));
+
+    // Removing the override from the file and reloading restores the default.
+    std::fs::write(state.wallet.state_dir.join("node_config.json"), "{}").unwrap();
+    config::reload(&state).await.unwrap();
+    let config: agentbook::protocol::NodeConfig =
+        serde_json::from_value(assert_ok(&handle_request(&state, Request::Config).await).unwrap())
+            .unwrap();
+    assert_eq!(dm_budget(&config), (20, 2.0));
+}
+
+#[tokio::test]
+async fn janitor_prunes_by_configured_retention() {
+    use agentbook_crypto::time::Clock;
+
+    let clock = Arc::new(agentbook_crypto::time::ManualClock::new());
+    let (state, _dir) = make_test_state_with_clock(clock.clone());
+    let now = clock.now_ms();
+    for (id, age_ms) in [("stale", 3 * 3_600_000), ("fresh", 0)] {
+        state
+            .inbox
+            .lock()
+            .await
+            .push(InboxMessage {
+                message_id: id.into(),
+                from_node_id: "0xpeer".into(),
+                from_public_key_b64: "pub".into(),
+                to_node_id: None,
+                topic: None,
+                body: "hi".into(),
+                timestamp_ms: now - age_ms,
+                acked: false,
+                message_type: MeshMessageType::DmText,
+                attachments: Vec::new(),
+                in_reply_to: None,
+                thread_id: None,
+            })
+            .unwrap();
+    }
+
+    // A zero limit is rejected and leaves retention off.
+    let path = state.wallet.state_dir.join("node_config.json");
+    std::fs::write(&path, r#"{"retention":{"max_count":0}}"#).unwrap();
+    assert!(config::reload(&state).await.is_err());
+    std::fs::write(&path, r#"{"retention":{"max_age_secs":3600}}"#).unwrap();
+    config::reload(&state).await.unwrap();
+
+    let pruned = retention::prune_once(&state).await.unwrap();
+    assert_eq!(pruned.by_age, 1);
+    let resp = handle_request(&state, Request::RetentionStats).await;
+    let stats: agentbook::protocol::RetentionStats =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert_eq!(stats.policy.max_age_secs, Some(3600));
+    assert_eq!(stats.inbox_messages, 1);
+    assert_eq!(stats.pruned_by_age, 1);
+    assert_eq!(stats.last_run_ms, Some(now));
+}
+
+#[tokio::test]
+async fn require_encryption_refuses_open_rooms() {
+    let (state, _dir) = make_test_state();
+    state.rooms.lock().await.insert(
+        "lobby".into(),
+        rooms::RoomConfig {
+            room: "lobby".into(),
+            encrypted_key_hex: None,
+        },
+    );
+    let path = state.wallet.state_dir.join("node_config.json");
+    std::fs::write(&path, r#"{"require_encryption":true}"#).unwrap();
+    config::reload(&state).await.unwrap();
+
+    let join = |passphrase: Option<&str>| Request::JoinRoom {
+        room: "ops-alerts".into(),
+        passphrase: passphrase.map(str::to_string),
+    };
+    assert_error(
+        &handle_request(&state, join(None)).await,
+        "encryption_required",
+    );
+    // With a passphrase the join gets as far as the (missing) relay.
+    assert_error(&handle_request(&state, join(Some("pw"))).await, "no_relay");
+
+    let send = Request::SendRoom {
+        room: "lobby".into(),
+        body: "hi".into(),
+    };
+    assert_error(&handle_request(&state, send).await, "encryption_required");
+
+    let resp = handle_request(&state, Request::Config).await;
+    let config: agentbook::protocol::NodeConfig =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert!(config.require_encryption);
+}
+
+#[tokio::test]
+async fn routes_drop_and_forward_room_messages() {
+    let (state, _dir) = make_test_state();
+    let (sender, _sender_dir) = make_sender_identity();
+    for room in ["noise-1", "escalation"] {
+        state.rooms.lock().await.insert(
+            room.into(),
+            rooms::RoomConfig {
+                room: room.into(),
+                encrypted_key_hex: None,
+            },
+        );
+    }
+    let path = state.wallet.state_dir.join("node_config.json");
+    std::fs::write(
+        &path,
+        r#"{"routes":[
+            {"topic":"noise*","action":"drop"},
+            {"topic":"escalation","action":"forward","to":"@supervisor"}
+        ]}"#,
+    )
+    .unwrap();
+    config::reload(&state).await.unwrap();
+
+    for (id, room) in [("m1", "noise-1"), ("m2", "escalation")] {
+        let body = "db is down".to_string();
+        let envelope = mesh_pb::Envelope {
+            message_id: id.into(),
+            from_node_id: sender.node_id.clone(),
+            to_node_id: String::new(),
+            from_public_key_b64: sender.public_key_b64.clone(),
+            message_type: mesh_pb::MessageType::RoomMessage as i32,
+            signature_b64: sender.sign(body.as_bytes()).unwrap(),
+            ciphertext_b64: body,
+            nonce_b64: String::new(),
+            timestamp_ms: 12345,
+            topic: Some(room.into()),
+            sealed: false,
+            schema_version: envelope_schema::CURRENT,
+        };
+        rooms::process_inbound_room(&state, envelope).await;
+    }
+
+    // Forwarded messages are still stored; the copy fails here for want
+    // of a relay.
+    let inbox = state.inbox.lock().await;
+    let ids: Vec<_> = inbox
+        .list(false, None)
+        .iter()
+        .map(|m| m.message_id.as_str())
+        .collect();
+    assert_eq!(ids, ["m2"]);
+    drop(inbox);
+
+    std::fs::write(&path, r#"{"routes":[{"topic":"","action":"drop"}]}"#).unwrap();
+    assert!(config::reload(&state).await.is_err());
+    assert_eq!(state.routes.lock().await.len(), 2);
+}
+
+#[tokio::test]
+async fn watchdog_route_freezes_on_matching_dm() {
+    let (state, _dir) = make_test_state();
+    let (sender, _sender_dir) = make_sender_identity();
+    follow_sender(&state, &sender).await;
+    let path = state.wallet.state_dir.join("node_config.json");
+    std::fs::write(
+        &path,
+        r#"{"routes":[{"contains":"panicked at","action":"freeze"}]}"#,
+    )
+    .unwrap();
+    config::reload(&state).await.unwrap();
+
+    let calm = make_encrypted_dm_envelope(&sender, &state.identity, "m1", "all good");
+    process_inbound(&state, calm).await;
+    assert!(!state.frozen.load(Ordering::SeqCst));
+
+    let body = "thread 'worker' panicked at src/main.rs:3";
+    let alarm = make_encrypted_dm_envelope(&sender, &state.identity, "m2", body);
+    process_inbound(&state, alarm).await;
+    assert!(state.frozen.load(Ordering::SeqCst));
+    assert_eq!(state.inbox.lock().await.len(), 2);
+}
+
+#[tokio::test]
+async fn reencrypt_state_rewrites_encrypted_stores() {
+    use agentbook_mesh::at_rest::{StateCipher, is_sealed};
+
+    let (plain, _plain_dir) = make_test_state();
+    let resp = handle_request(&plain, Request::ReencryptState).await;
+    assert_error(&resp, "not_encrypted");
+
+    let dir = tempfile::tempdir().unwrap();
+    let kek = random_key_material();
+    let cipher = StateCipher::new(&kek);
+    let state = NodeState::new(
+        NodeIdentity::load_or_create(dir.path(), &kek).unwrap(),
+        FollowStore::load_encrypted(dir.path(), cipher.clone()).unwrap(),
+        NodeInbox::load_encrypted(dir.path(), cipher).unwrap(),
+        None,
+        vec![],
+        WalletConfig {
+            rpc_url: "https://mainnet.base.org".to_string(),
+            yolo_enabled: false,
+            state_dir: dir.path().to_path_buf(),
+            kek: Zeroizing::new(kek),
+            spending_limit_config: SpendingLimitConfig::default(),
+        },
+    );
+    let (sender, _sender_dir) = make_sender_identity();
+    follow_sender(&state, &sender).await;
+
+    let resp = handle_request(&state, Request::ReencryptState).await;
+    let data = assert_ok(&resp).unwrap();
+    assert_eq!(data["following"], 1);
+    let on_disk = std::fs::read_to_string(dir.path().join("following.json")).unwrap();
+    assert!(is_sealed(&on_disk));
+}
+
+#[tokio::test]
+async fn inbox_ack_after_inbound() {
+    let (state, _dir) = make_test_state();
+    let (sender, _sender_dir) = make_sender_identity();
+    follow_sender(&state, &sender).await;
+
+    let envelope = make_encrypted_dm_envelope(&sender, &state.identity, "ack-test", "hi");
+    process_inbound(&state, envelope).await;
+
+    // Ack it
+    let resp = handle_request(
+        &state,
+        Request::InboxAck {
+            message_id: "ack-test".into(),
+        },
+    )
+    .await;
+    assert_ok(&resp);
+
+    // Unread count should be 0
+    let resp = handle_request(&state, Request::Health).await;
+    let data = assert_ok(&resp).unwrap();
+    let status: HealthStatus = serde_json::from_value(data).unwrap();
+    assert_eq!(status.unread_count, 0);
+
+    // Unread-only filter returns empty
+    let resp = handle_request(
+        &state,
+        Request::Inbox {
+            unread_only: true,
+            limit: None,
+        },
+    )
+    .await;
+    let data = assert_ok(&resp).unwrap();
+    let list: Vec<InboxEntry> = serde_json::from_value(data).unwrap();
+    assert!(list.is_empty());
+}
+
+#[tokio::test]
+async fn inbox_limit() {
+    let (state, _dir) = make_test_state();
+    let (sender, _sender_dir) = make_sender_identity();
+    follow_sender(&state, &sender).await;
+
+    for i in 0..5u64 {
+        let envelope = make_encrypted_dm_envelope(
+            &sender,
+            &state.identity,
+            &format!("msg-{i}"),
+            &format!("msg {i}"),
+        );
+        process_inbound(&state, envelope).await;
+    }
+
+    let resp = handle_request(
+        &state,
+        Request::Inbox {
+            unread_only: false,
+            limit: Some(3),
+        },
+    )
+    .await;
+    let data = assert_ok(&resp).unwrap();
+    let list: Vec<InboxEntry> = serde_json::from_value(data).unwrap();
+    assert_eq!(list.len(), 3);
+}
+
+#[tokio::test]
+async fn multiple_inbound_and_unread_filter() {
+    let (state, _dir) = make_test_state();
+    let (sender, _sender_dir) = make_sender_identity();
+    follow_sender(&state, &sender).await;
+
+    for i in 0..3u64 {
+        let envelope = make_encrypted_dm_envelope(
+            &sender,
+            &state.identity,
+            &format!("m-{i}"),
+            &format!("msg {i}"),
+        );
+        process_inbound(&state, envelope).await;
+    }
+
+    // Ack first two
+    handle_request(
+        &state,
+        Request::InboxAck {
+            message_id: "m-0".into(),
+        },
+    )
+    .await;
+    handle_request(
+        &state,
+        Request::InboxAck {
             message_id: "m-1".into(),
         },
     )
@@ -713,6 +1605,9 @@ async fn send_dm_no_relay() {
         Request::SendDm {
             to: "node-b".into(),
             body: "hello".into(),
+            attachments: Vec::new(),
+            in_reply_to: None,
+            reply_within_secs: None,
         },
     )
     .await;
@@ -732,6 +1627,363 @@ async fn post_feed_no_relay() {
     assert_error(&resp, "no_relay");
 }
 
+#[tokio::test]
+async fn broadcast_dm_no_relay() {
+    let (state, _dir) = make_test_state();
+    let resp = handle_request(
+        &state,
+        Request::BroadcastDm {
+            body: "all hands".into(),
+            mutual_only: false,
+            group: None,
+        },
+    )
+    .await;
+    assert_error(&resp, "no_relay");
+}
+
+#[tokio::test]
+async fn send_template_checks_vars_before_sending() {
+    let (state, _dir) = make_test_state();
+    *state.templates.lock().await = vec![MessageTemplate {
+        name: "deploy-request".into(),
+        message_type: MessageType::DmText,
+        body: "please deploy to {env}".into(),
+        topic: None,
+        required_vars: vec!["env".into()],
+    }];
+    let send = |template: &str, to: Option<&str>, vars: &[(&str, &str)]| Request::SendTemplate {
+        template: template.into(),
+        to: to.map(str::to_string),
+        vars: vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    };
+
+    let resp = handle_request(
+        &state,
+        send("missing", Some("node-b"), &[("env", "staging")]),
+    )
+    .await;
+    assert_error(&resp, "not_found");
+    let resp = handle_request(&state, send("deploy-request", Some("node-b"), &[])).await;
+    assert_error(&resp, "invalid_request");
+    let resp = handle_request(&state, send("deploy-request", None, &[("env", "staging")])).await;
+    assert_error(&resp, "invalid_request");
+    // Valid, so it gets as far as the relay.
+    let resp = handle_request(
+        &state,
+        send("deploy-request", Some("node-b"), &[("env", "staging")]),
+    )
+    .await;
+    assert_error(&resp, "no_relay");
+}
+
+// ---------------------------------------------------------------------------
+// Invites
+// ---------------------------------------------------------------------------
+
+/// Issue an invite on `state` and return its token.
+async fn create_invite_token(state: &Arc<NodeState>, max_uses: Option<u32>) -> String {
+    let resp = handle_request(
+        state,
+        Request::InviteCreate {
+            ttl_ms: None,
+            max_uses,
+            scopes: vec![],
+        },
+    )
+    .await;
+    let data = assert_ok(&resp).unwrap();
+    data["token"].as_str().unwrap().to_string()
+}
+
+/// Build the redemption envelope an invitee sends back to the inviter.
+fn make_invite_redeem_envelope(
+    invitee: &NodeIdentity,
+    inviter: &NodeIdentity,
+    token: &str,
+) -> mesh_pb::Envelope {
+    mesh_pb::Envelope {
+        message_id: uuid::Uuid::new_v4().to_string(),
+        from_node_id: invitee.node_id.clone(),
+        to_node_id: inviter.node_id.clone(),
+        from_public_key_b64: invitee.public_key_b64.clone(),
+        message_type: mesh_pb::MessageType::InviteRedeem as i32,
+        ciphertext_b64: token.to_string(),
+        nonce_b64: String::new(),
+        signature_b64: invitee.sign(token.as_bytes()).unwrap(),
+        timestamp_ms: 12345,
+        topic: None,
+        sealed: false,
+        schema_version: envelope_schema::CURRENT,
+    }
+}
+
+#[tokio::test]
+async fn invite_create_list_and_revoke() {
+    let (state, _dir) = make_test_state();
+    create_invite_token(&state, Some(1)).await;
+
+    let resp = handle_request(&state, Request::InviteList).await;
+    let list: Vec<agentbook::protocol::InviteInfo> =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].max_uses, Some(1));
+    assert!(!list[0].revoked);
+
+    let token_id = list[0].token_id.clone();
+    let resp = handle_request(&state, Request::InviteRevoke { token_id }).await;
+    assert_ok(&resp);
+
+    let resp = handle_request(&state, Request::InviteList).await;
+    let list: Vec<agentbook::protocol::InviteInfo> =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert!(list[0].revoked);
+
+    let resp = handle_request(
+        &state,
+        Request::InviteRevoke {
+            token_id: "nope".into(),
+        },
+    )
+    .await;
+    assert_error(&resp, "not_found");
+}
+
+#[tokio::test]
+async fn invite_accept_follows_inviter() {
+    let (inviter, _inviter_dir) = make_test_state();
+    let (invitee, _invitee_dir) = make_test_state();
+    let token = create_invite_token(&inviter, None).await;
+
+    let resp = handle_request(
+        &invitee,
+        Request::InviteAccept {
+            token: token.clone(),
+        },
+    )
+    .await;
+    let data = assert_ok(&resp).unwrap();
+    assert_eq!(data["notified"], false);
+    assert!(
+        invitee
+            .follow_store
+            .lock()
+            .await
+            .is_following(&inviter.identity.node_id)
+    );
+
+    let resp = handle_request(&inviter, Request::InviteAccept { token }).await;
+    assert_error(&resp, "invalid_invite");
+}
+
+#[tokio::test]
+async fn invite_accept_rejected_after_ttl() {
+    let (inviter, _inviter_dir) = make_test_state();
+    let clock = Arc::new(agentbook_crypto::time::ManualClock::new());
+    let (invitee, _invitee_dir) = make_test_state_with_clock(clock.clone());
+    let token = create_invite_token(&inviter, None).await;
+
+    // Default invite TTL is 7 days.
+    clock.advance(std::time::Duration::from_secs(8 * 24 * 60 * 60));
+    let resp = handle_request(&invitee, Request::InviteAccept { token }).await;
+    let message = assert_error(&resp, "invalid_invite");
+    assert!(message.contains("expired"), "{message}");
+}
+
+#[tokio::test]
+async fn invite_redeem_follows_back_once_for_single_use() {
+    let (state, _dir) = make_test_state();
+    let (first, _first_dir) = make_sender_identity();
+    let (second, _second_dir) = make_sender_identity();
+    let token = create_invite_token(&state, Some(1)).await;
+
+    let mut events = state.event_tx.subscribe();
+    process_inbound(
+        &state,
+        make_invite_redeem_envelope(&first, &state.identity, &token),
+    )
+    .await;
+    assert!(matches!(
+        events.try_recv().unwrap(),
+        Event::NewFollower { node_id } if node_id == first.node_id
+    ));
+
+    process_inbound(
+        &state,
+        make_invite_redeem_envelope(&second, &state.identity, &token),
+    )
+    .await;
+
+    let follow_store = state.follow_store.lock().await;
+    assert!(follow_store.is_following(&first.node_id));
+    assert!(!follow_store.is_following(&second.node_id));
+}
+
+#[tokio::test]
+async fn invite_redeem_rejected_after_revoke() {
+    let (state, _dir) = make_test_state();
+    let (invitee, _invitee_dir) = make_sender_identity();
+    let token = create_invite_token(&state, None).await;
+
+    let token_id = state.invites.lock().await.list()[0].token_id.clone();
+    assert_ok(&handle_request(&state, Request::InviteRevoke { token_id }).await);
+
+    process_inbound(
+        &state,
+        make_invite_redeem_envelope(&invitee, &state.identity, &token),
+    )
+    .await;
+    assert!(
+        !state
+            .follow_store
+            .lock()
+            .await
+            .is_following(&invitee.node_id)
+    );
+}
+
+#[tokio::test]
+async fn invite_ttl_is_capped() {
+    let (state, _dir) = make_test_state();
+    for ttl_ms in [u64::MAX, agentbook_mesh::invite::MAX_INVITE_TTL_MS + 1] {
+        let resp = handle_request(
+            &state,
+            Request::InviteCreate {
+                ttl_ms: Some(ttl_ms),
+                max_uses: None,
+                scopes: vec![],
+            },
+        )
+        .await;
+        assert_error(&resp, "invalid_ttl");
+    }
+    assert!(state.invites.lock().await.list().is_empty());
+}
+
+#[tokio::test]
+async fn invite_scopes_restrict_the_redeemer() {
+    let (state, _dir) = make_test_state();
+    let (invitee, _invitee_dir) = make_sender_identity();
+
+    let resp = handle_request(
+        &state,
+        Request::InviteCreate {
+            ttl_ms: None,
+            max_uses: None,
+            scopes: vec!["dm".into(), "admin".into()],
+        },
+    )
+    .await;
+    assert_error(&resp, "invalid_scope");
+
+    let resp = handle_request(
+        &state,
+        Request::InviteCreate {
+            ttl_ms: None,
+            max_uses: None,
+            scopes: vec!["feed".into()],
+        },
+    )
+    .await;
+    let token = assert_ok(&resp).unwrap()["token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    process_inbound(
+        &state,
+        make_invite_redeem_envelope(&invitee, &state.identity, &token),
+    )
+    .await;
+    let scopes = state
+        .follow_store
+        .lock()
+        .await
+        .get(&invitee.node_id)
+        .unwrap()
+        .scopes
+        .clone();
+    assert_eq!(scopes, ["feed"]);
+
+    // Followed back, but not allowed to DM.
+    let envelope = make_encrypted_dm_envelope(&invitee, &state.identity, "scoped-dm", "hi");
+    process_inbound(&state, envelope).await;
+    assert!(state.inbox.lock().await.list(false, None).is_empty());
+
+    let resp = handle_request(&state, Request::InviteList).await;
+    let list: Vec<agentbook::protocol::InviteInfo> =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert_eq!(list[0].scopes, ["feed"]);
+}
+
+// ---------------------------------------------------------------------------
+// Outbox
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn outbox_list_and_cancel() {
+    let (state, _dir) = make_test_state();
+    let (sender, _sender_dir) = make_sender_identity();
+
+    let resp = handle_request(&state, Request::OutboxList).await;
+    let list: Vec<agentbook::protocol::OutboxInfo> =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert!(list.is_empty());
+
+    let envelope = make_encrypted_dm_envelope(&state.identity, &sender, "queued-1", "later");
+    state
+        .outbox
+        .lock()
+        .await
+        .enqueue(&envelope, now_ms(), Some("no relay connected".into()))
+        .unwrap();
+
+    let resp = handle_request(&state, Request::OutboxList).await;
+    let list: Vec<agentbook::protocol::OutboxInfo> =
+        serde_json::from_value(assert_ok(&resp).unwrap()).unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].message_id, "queued-1");
+    assert_eq!(list[0].to_node_id, sender.node_id);
+    assert_eq!(list[0].last_error.as_deref(), Some("no relay connected"));
+
+    let resp = handle_request(
+        &state,
+        Request::OutboxCancel {
+            message_id: "queued-1".into(),
+        },
+    )
+    .await;
+    assert_ok(&resp);
+    assert!(state.outbox.lock().await.is_empty());
+
+    let resp = handle_request(
+        &state,
+        Request::OutboxCancel {
+            message_id: "queued-1".into(),
+        },
+    )
+    .await;
+    assert_error(&resp, "not_found");
+}
+
+#[tokio::test]
+async fn outbox_persists_across_restart() {
+    let (state, dir) = make_test_state();
+    let envelope = make_encrypted_dm_envelope(&state.identity, &state.identity, "q", "hi");
+    state
+        .outbox
+        .lock()
+        .await
+        .enqueue(&envelope, now_ms(), None)
+        .unwrap();
+
+    let reloaded = agentbook_mesh::outbox::NodeOutbox::load(dir.path()).unwrap();
+    assert_eq!(reloaded.len(), 1);
+    assert_eq!(reloaded.list()[0].envelope().unwrap(), envelope);
+}
+
 // ---------------------------------------------------------------------------
 // RegisterUsername / LookupUsername without relay hosts
 // ---------------------------------------------------------------------------
@@ -1135,6 +2387,8 @@ async fn process_inbound_unspecified_message_type_stores_fallback() {
         signature_b64,
         timestamp_ms: 5000,
         topic: None,
+        sealed: false,
+        schema_version: envelope_schema::CURRENT,
     };
 
     process_inbound(&state, envelope).await;
@@ -1,3 +1,4 @@
+use agentbook_mesh::state_file;
 use std::collections::HashMap;
 use std::path::Path;
 
@@ -57,8 +58,10 @@ impl UsernameCache {
 
     fn save(&self) {
         let path = self.state_dir.join(Self::FILE);
-        if let Ok(data) = serde_json::to_string(&self.map) {
-            std::fs::write(path, data).ok();
+        if let Ok(data) = serde_json::to_string(&self.map)
+            && let Err(e) = state_file::write(&path, data)
+        {
+            tracing::debug!(err = %format!("{e:#}"), "failed to save username cache");
         }
     }
 }
@@ -0,0 +1,295 @@
+//! journald and syslog log backends.
+//!
+//! A `tracing` layer that sends each event as one datagram to the local
+//! journal (native protocol, one field per event field) or to the syslog
+//! socket (RFC 3164 line with `key=value` pairs). Both carry the node id once
+//! it is known, so logs from several nodes on one host can be told apart.
+//! Sending never blocks and errors are dropped: logging must not take the
+//! daemon down.
+
+use std::fmt::Write as _;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+const SYSLOG_SOCKET: &str = "/dev/log";
+const IDENTIFIER: &str = "agentbook-node";
+/// `daemon` facility.
+const SYSLOG_FACILITY: u8 = 3;
+
+/// Set once the identity is loaded; records before that carry no node id.
+static NODE_ID: OnceLock<String> = OnceLock::new();
+
+/// Tag every later record with `node_id`.
+pub fn set_node_id(node_id: &str) {
+    NODE_ID.set(node_id.to_string()).ok();
+}
+
+/// Where the daemon's logs go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogBackend {
+    /// Formatted text on stderr.
+    #[default]
+    Stderr,
+    Journald,
+    Syslog,
+}
+
+impl FromStr for LogBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stderr" => Ok(Self::Stderr),
+            "journald" => Ok(Self::Journald),
+            "syslog" => Ok(Self::Syslog),
+            other => Err(format!(
+                "unknown log backend '{other}' (expected stderr, journald or syslog)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Journald,
+    Syslog,
+}
+
+/// Sends events to journald or syslog over a Unix datagram socket.
+pub struct JournalLayer {
+    socket: UnixDatagram,
+    path: PathBuf,
+    format: Format,
+}
+
+impl JournalLayer {
+    /// Log to the system journal.
+    pub fn journald() -> std::io::Result<Self> {
+        Self::new(JOURNALD_SOCKET.into(), Format::Journald)
+    }
+
+    /// Log to the local syslog daemon.
+    pub fn syslog() -> std::io::Result<Self> {
+        Self::new(SYSLOG_SOCKET.into(), Format::Syslog)
+    }
+
+    fn new(path: PathBuf, format: Format) -> std::io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.set_nonblocking(true)?;
+        // Fail at startup rather than silently logging nowhere.
+        socket.connect(&path)?;
+        Ok(Self {
+            socket,
+            path,
+            format,
+        })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for JournalLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let meta = event.metadata();
+        let record = Record {
+            level: *meta.level(),
+            target: meta.target(),
+            file: meta.file(),
+            line: meta.line(),
+            node_id: NODE_ID.get().map(String::as_str),
+            message: &fields.message,
+            fields: &fields.fields,
+        };
+        let payload = match self.format {
+            Format::Journald => journald_payload(&record),
+            Format::Syslog => syslog_line(&record).into_bytes(),
+        };
+        // The journal may have restarted; reconnect once and retry.
+        if self.socket.send(&payload).is_err() && self.socket.connect(&self.path).is_ok() {
+            let _ = self.socket.send(&payload);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push((field.name(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.fields.push((field.name(), format!("{value:?}")));
+        }
+    }
+}
+
+struct Record<'a> {
+    level: Level,
+    target: &'a str,
+    file: Option<&'a str>,
+    line: Option<u32>,
+    node_id: Option<&'a str>,
+    message: &'a str,
+    fields: &'a [(&'static str, String)],
+}
+
+/// syslog severity.
+fn priority(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+/// Journal field names are upper-case ASCII letters, digits and underscores,
+/// and may not start with an underscore (those are trusted fields).
+fn journald_field_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    name.trim_start_matches('_').to_string()
+}
+
+/// Encode one record in journald's native protocol.
+fn journald_payload(record: &Record<'_>) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut put = |name: &str, value: &str| {
+        if value.contains('\n') {
+            // Binary-safe form: name, newline, little-endian length, value.
+            out.extend_from_slice(name.as_bytes());
+            out.push(b'\n');
+            out.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            out.extend_from_slice(value.as_bytes());
+        } else {
+            out.extend_from_slice(name.as_bytes());
+            out.push(b'=');
+            out.extend_from_slice(value.as_bytes());
+        }
+        out.push(b'\n');
+    };
+    put("MESSAGE", record.message);
+    put("PRIORITY", &priority(record.level).to_string());
+    put("SYSLOG_IDENTIFIER", IDENTIFIER);
+    put("TARGET", record.target);
+    if let Some(file) = record.file {
+        put("CODE_FILE", file);
+    }
+    if let Some(line) = record.line {
+        put("CODE_LINE", &line.to_string());
+    }
+    if let Some(node_id) = record.node_id {
+        put("NODE_ID", node_id);
+    }
+    for (name, value) in record.fields {
+        let name = journald_field_name(name);
+        if !name.is_empty() {
+            put(&name, value);
+        }
+    }
+    out
+}
+
+/// Format one record as an RFC 3164 syslog line.
+fn syslog_line(record: &Record<'_>) -> String {
+    let mut line = format!(
+        "<{}>{IDENTIFIER}[{}]: {}",
+        SYSLOG_FACILITY * 8 + priority(record.level),
+        std::process::id(),
+        record.message
+    );
+    if let Some(node_id) = record.node_id {
+        let _ = write!(line, " node_id={node_id}");
+    }
+    for (name, value) in record.fields {
+        if value.contains(char::is_whitespace) {
+            let _ = write!(line, " {name}={value:?}");
+        } else {
+            let _ = write!(line, " {name}={value}");
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn capture(format: Format, emit: impl FnOnce()) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.sock");
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        let layer = JournalLayer::new(path, format).unwrap();
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), emit);
+        let mut buf = vec![0; 4096];
+        let n = receiver.recv(&mut buf).unwrap();
+        buf.truncate(n);
+        buf
+    }
+
+    #[test]
+    fn journald_records_carry_event_fields() {
+        let payload = capture(Format::Journald, || {
+            tracing::warn!(peer = "0xabc", attempts = 3, "delivery failed\nretrying");
+        });
+        let text = String::from_utf8_lossy(&payload);
+        assert!(text.contains("PRIORITY=4\n"), "{text}");
+        assert!(text.contains("SYSLOG_IDENTIFIER=agentbook-node\n"));
+        assert!(text.contains("PEER=0xabc\n"));
+        assert!(text.contains("ATTEMPTS=3\n"));
+        // Multi-line messages use the length-prefixed form.
+        let mut binary = b"MESSAGE\n".to_vec();
+        binary.extend_from_slice(&24u64.to_le_bytes());
+        binary.extend_from_slice(b"delivery failed\nretrying\n");
+        assert!(payload.starts_with(&binary));
+    }
+
+    #[test]
+    fn syslog_lines_have_priority_and_pairs() {
+        let payload = capture(Format::Syslog, || {
+            tracing::info!(room = "ops", reason = "rate limited", "message dropped");
+        });
+        let line = String::from_utf8(payload).unwrap();
+        assert!(line.starts_with("<30>agentbook-node["), "{line}");
+        assert!(
+            line.ends_with(r#"]: message dropped room=ops reason="rate limited""#),
+            "{line}"
+        );
+    }
+
+    #[test]
+    fn field_names_are_journald_safe() {
+        assert_eq!(journald_field_name("node_id"), "NODE_ID");
+        assert_eq!(journald_field_name("_private.key"), "PRIVATE_KEY");
+        assert!("journald".parse::<LogBackend>().is_ok());
+        assert!("file".parse::<LogBackend>().is_err());
+    }
+}
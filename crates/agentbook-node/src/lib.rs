@@ -1,2 +1,13 @@
+pub mod access;
+pub mod clients;
+pub mod grpc;
 pub mod handler;
+pub mod journal;
+pub mod middleware;
+pub mod secrets;
+pub mod self_stats;
 pub mod socket;
+pub mod tcp;
+pub mod telemetry;
+pub mod view;
+pub mod webhooks;
@@ -1,21 +1,31 @@
 use agentbook::client::default_socket_path;
+use agentbook_mesh::at_rest::StateCipher;
 use agentbook_mesh::follow::FollowStore;
 use agentbook_mesh::identity::NodeIdentity;
 use agentbook_mesh::inbox::NodeInbox;
+use agentbook_mesh::proxy::Proxy;
 use agentbook_mesh::recovery;
+use agentbook_mesh::relay_directory;
 use agentbook_mesh::state_dir::default_state_dir;
-use agentbook_mesh::transport::MeshTransport;
+use agentbook_mesh::transport::{MeshTransport, RelaySecurity};
 use agentbook_node::handler::{self, NodeState, WalletConfig};
-use agentbook_node::socket;
+use agentbook_node::journal::{self, LogBackend};
+use agentbook_node::{grpc, socket, tcp, telemetry, webhooks};
+use agentbook_proto::node::v1::node_service_server::NodeServiceServer;
 use agentbook_wallet::wallet::DEFAULT_RPC_URL;
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{ArgGroup, Parser};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Identity, Server, ServerTlsConfig};
 use zeroize::Zeroizing;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "agentbook node daemon")]
+#[command(group(ArgGroup::new("api_listener").multiple(true).args(["listen_tcp", "listen_grpc"])))]
 struct Args {
     /// Path to the Unix socket.
     #[arg(long)]
@@ -25,6 +35,26 @@ struct Args {
     #[arg(long)]
     state_dir: Option<PathBuf>,
 
+    /// Also serve the client API over TLS on this TCP address, for callers
+    /// that cannot reach the Unix socket (e.g. from outside a container).
+    /// Clients authenticate with the token in `<state-dir>/api_token`.
+    #[arg(long, requires_all = ["tls_cert", "tls_key"])]
+    listen_tcp: Option<SocketAddr>,
+
+    /// Also serve the client API as the gRPC `NodeService` over TLS on this
+    /// TCP address. Clients send `authorization: Bearer <token>` metadata with
+    /// the same tokens as --listen-tcp.
+    #[arg(long, requires_all = ["tls_cert", "tls_key"])]
+    listen_grpc: Option<SocketAddr>,
+
+    /// Certificate chain (PEM) for --listen-tcp and --listen-grpc.
+    #[arg(long, requires = "api_listener")]
+    tls_cert: Option<PathBuf>,
+
+    /// Private key (PEM) for --tls-cert.
+    #[arg(long, requires = "api_listener")]
+    tls_key: Option<PathBuf>,
+
     /// Relay host address(es) to connect to (can be repeated).
     /// Defaults to agentbook.ardabot.ai if none specified.
     #[arg(long)]
@@ -34,6 +64,49 @@ struct Args {
     #[arg(long)]
     no_relay: bool,
 
+    /// Connect to the N fastest healthy relays from the signed relay
+    /// directory, besides any --relay-host, which stay pinned.
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with = "no_relay",
+        requires = "relay_directory_signer"
+    )]
+    auto_relays: Option<usize>,
+
+    /// Address that must have signed the relay directory (like a node ID).
+    #[arg(long)]
+    relay_directory_signer: Option<String>,
+
+    /// Relay to fetch the relay directory from.
+    #[arg(long, default_value = agentbook::DEFAULT_RELAY_HOST)]
+    relay_directory_host: String,
+
+    /// Relay privacy mode: pad DMs and feed posts to fixed size buckets and
+    /// hide their message type, so the relay only sees routing node IDs.
+    #[arg(long)]
+    privacy_mode: bool,
+
+    /// Extra CA certificate (PEM) to trust for relay TLS, e.g. a self-hosted
+    /// relay with a private CA.
+    #[arg(long)]
+    relay_ca_cert: Option<PathBuf>,
+
+    /// Client certificate (PEM) for relays that require mutual TLS.
+    #[arg(long, requires = "relay_client_key")]
+    relay_client_cert: Option<PathBuf>,
+
+    /// Private key (PEM) for --relay-client-cert.
+    #[arg(long, requires = "relay_client_cert")]
+    relay_client_key: Option<PathBuf>,
+
+    /// Reach relays through this proxy: socks5://[user:pass@]host:port or
+    /// http://[user:pass@]host:port (HTTP CONNECT). Defaults to
+    /// AGENTBOOK_PROXY, HTTPS_PROXY or ALL_PROXY; NO_PROXY lists relays to
+    /// reach directly.
+    #[arg(long)]
+    relay_proxy: Option<String>,
+
     /// Base chain RPC URL (default: https://mainnet.base.org).
     #[arg(long, default_value = DEFAULT_RPC_URL)]
     rpc_url: String,
@@ -61,6 +134,27 @@ struct Args {
     /// Max USDC the yolo wallet can spend per rolling 24h window (default: 100).
     #[arg(long, default_value = "100")]
     max_yolo_daily_usdc: String,
+
+    /// Where to send logs: stderr, journald or syslog. journald and syslog
+    /// records carry the node id and each event's fields.
+    #[arg(long, default_value = "stderr")]
+    log_backend: LogBackend,
+
+    /// OTLP/HTTP endpoint to export traces to, e.g.
+    /// http://localhost:4318/v1/traces.
+    #[cfg(feature = "otel")]
+    #[arg(long)]
+    otlp_endpoint: Option<String>,
+
+    /// Keep the inbox in SQLite (`inbox.db`) instead of JSON lines. An
+    /// existing `inbox.jsonl` is imported on the first start.
+    #[cfg(feature = "sqlite")]
+    #[arg(long)]
+    sqlite_inbox: bool,
+}
+
+fn read_pem(path: &std::path::Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))
 }
 
 fn startup_room_plan(
@@ -73,17 +167,14 @@ fn startup_room_plan(
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        // Keep stdout dedicated to READY handshake when --notify-ready is used.
-        .with_writer(std::io::stderr)
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "agentbook_node=info".into()),
-        )
-        .init();
-
     let args = Args::parse();
 
+    #[cfg(feature = "otel")]
+    let otlp_endpoint = args.otlp_endpoint.as_deref();
+    #[cfg(not(feature = "otel"))]
+    let otlp_endpoint = None;
+    let _telemetry = telemetry::init(otlp_endpoint, args.log_backend)?;
+
     let state_dir = args
         .state_dir
         .unwrap_or_else(|| default_state_dir().expect("failed to determine state directory"));
@@ -104,6 +195,7 @@ async fn main() -> Result<()> {
     let identity =
         NodeIdentity::load_or_create(&state_dir, &kek).context("failed to load identity")?;
 
+    journal::set_node_id(&identity.node_id);
     tracing::info!(node_id = %identity.node_id, "node identity loaded");
 
     // Require TOTP to be set up
@@ -148,30 +240,95 @@ async fn main() -> Result<()> {
         drop(std::io::Write::flush(&mut std::io::stdout()));
     }
 
-    // Load follow store and inbox
-    let follow_store = FollowStore::load(&state_dir).context("failed to load follow store")?;
-    let inbox = NodeInbox::load(&state_dir).context("failed to load inbox")?;
+    // Load follow store and inbox, encrypted at rest under the recovery key
+    let cipher = StateCipher::new(&kek);
+    let follow_store = FollowStore::load_encrypted(&state_dir, cipher.clone())
+        .context("failed to load follow store")?;
+    #[cfg(feature = "sqlite")]
+    let inbox = if args.sqlite_inbox {
+        let storage = agentbook_mesh::inbox_sqlite::SqliteStorage::open(&state_dir, Some(cipher))
+            .context("failed to open inbox.db")?;
+        NodeInbox::with_storage(
+            Box::new(storage),
+            agentbook_mesh::inbox::DEFAULT_MAX_INBOX_SIZE,
+        )
+    } else {
+        NodeInbox::load_encrypted(&state_dir, cipher)
+    }
+    .context("failed to load inbox")?;
+    #[cfg(not(feature = "sqlite"))]
+    let inbox = NodeInbox::load_encrypted(&state_dir, cipher).context("failed to load inbox")?;
+
+    // Set up relay transport if configured
+    let security = RelaySecurity {
+        ca_cert_pem: args.relay_ca_cert.as_deref().map(read_pem).transpose()?,
+        client_identity: match (&args.relay_client_cert, &args.relay_client_key) {
+            (Some(cert), Some(key)) => Some((read_pem(cert)?, read_pem(key)?)),
+            _ => None,
+        },
+        signing_key: None,
+        proxy: Proxy::configured(args.relay_proxy.as_deref())
+            .context("invalid relay proxy")?
+            .map(Arc::new),
+    };
+    if let Some(proxy) = &security.proxy {
+        tracing::info!(?proxy, "connecting to relays through a proxy");
+    }
 
-    // Resolve relay hosts: use default if none specified (unless --no-relay)
+    // Resolve relay hosts: pinned --relay-host entries, plus the fastest from
+    // the relay directory with --auto-relays, else the default relay.
     let relay_hosts = if args.no_relay {
         vec![]
+    } else if let (Some(count), Some(signer)) = (args.auto_relays, &args.relay_directory_signer) {
+        match relay_directory::select(
+            &args.relay_directory_host,
+            signer,
+            &args.relay_host,
+            count,
+            &identity.node_id,
+            &security,
+            agentbook_crypto::time::now_ms(),
+        )
+        .await
+        {
+            Ok(hosts) if !hosts.is_empty() => {
+                tracing::info!(?hosts, "selected relays from the relay directory");
+                hosts
+            }
+            Ok(_) => {
+                tracing::warn!("no relay in the relay directory answered, using the default");
+                vec![agentbook::DEFAULT_RELAY_HOST.to_string()]
+            }
+            Err(e) => {
+                tracing::warn!(err = %e, "relay directory unavailable");
+                if args.relay_host.is_empty() {
+                    vec![agentbook::DEFAULT_RELAY_HOST.to_string()]
+                } else {
+                    args.relay_host.clone()
+                }
+            }
+        }
     } else if args.relay_host.is_empty() {
         vec![agentbook::DEFAULT_RELAY_HOST.to_string()]
     } else {
         args.relay_host.clone()
     };
+    let (tcp_api, grpc_tls) = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => {
+            let token = tcp::load_or_create_api_token(&state_dir)?;
+            let (cert, key) = (read_pem(cert)?, read_pem(key)?);
+            let grpc_tls = ServerTlsConfig::new().identity(Identity::from_pem(&cert, &key));
+            let tcp_api = tcp::TcpApi::new(&cert, &key, token.clone())?;
+            (Some((tcp_api, token)), Some(grpc_tls))
+        }
+        _ => (None, None),
+    };
 
-    // Set up relay transport if configured
     let transport = if !relay_hosts.is_empty() {
-        let sig = identity
-            .sign(identity.node_id.as_bytes())
-            .context("failed to sign for relay registration")?;
-        Some(MeshTransport::new(
-            relay_hosts.clone(),
-            identity.node_id.clone(),
-            identity.public_key_b64.clone(),
-            sig,
-        ))
+        Some(
+            relay_transport(&identity, &relay_hosts, &security)?
+                .with_privacy_mode(args.privacy_mode),
+        )
     } else {
         None
     };
@@ -217,6 +374,16 @@ async fn main() -> Result<()> {
         wallet_config,
     );
 
+    if let Err(e) = handler::config::reload(&state).await {
+        tracing::warn!(err = %e, "ignoring node_config.json");
+    }
+    tokio::spawn(reload_on_sighup(state.clone()));
+    tokio::spawn(webhooks::delivery_loop(state.clone()));
+    tokio::spawn(handler::retention::janitor_loop(state.clone()));
+    tokio::spawn(handler::replies::watch_loop(state.clone()));
+    tokio::spawn(handler::activity::watch_loop(state.clone()));
+    tokio::spawn(handler::drain::persist_loop(state.clone()));
+
     // Populate rooms from persisted config
     if !persisted_rooms.is_empty() {
         let mut rooms = state.rooms.lock().await;
@@ -243,7 +410,10 @@ async fn main() -> Result<()> {
     }
 
     // Auto-join #shire (the default open room) if not already joined
-    if state.transport.is_some() && should_auto_join_shire {
+    if state.transport.is_some()
+        && should_auto_join_shire
+        && !state.require_encryption.load(Ordering::Relaxed)
+    {
         match handler::rooms::handle_join_room(&state, "shire", None).await {
             agentbook::protocol::Response::Ok { .. } => {
                 tracing::info!("auto-joined #shire");
@@ -279,6 +449,60 @@ async fn main() -> Result<()> {
         tokio::spawn(async move {
             relay_inbound_loop(state_clone).await;
         });
+        tokio::spawn(handler::outbox::outbox_retry_loop(state.clone()));
+        tokio::spawn(handler::daemon_log::watch_relay(state.clone()));
+
+        // Keep receiving for rotated-away keys until their grace period ends.
+        for retired in &state.retired_identities {
+            if retired.grace_until_ms <= state.clock.now_ms() {
+                continue;
+            }
+            tracing::info!(
+                node_id = %retired.identity.node_id,
+                grace_until_ms = retired.grace_until_ms,
+                "listening for messages to retired key"
+            );
+            let transport = relay_transport(&retired.identity, &state.relay_hosts, &security)?;
+            tokio::spawn(retired_inbound_loop(
+                state.clone(),
+                transport,
+                retired.grace_until_ms,
+            ));
+        }
+    }
+
+    let api_token = tcp_api.as_ref().map(|(_, token)| token.clone());
+    if let (Some(addr), Some((api, _))) = (args.listen_tcp, tcp_api) {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("failed to bind {addr}"))?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = tcp::serve(state, listener, api).await {
+                tracing::error!(err = %e, "TCP listener failed");
+            }
+        });
+    }
+
+    if let (Some(addr), Some(tls), Some(token)) = (args.listen_grpc, grpc_tls, api_token) {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("failed to bind {addr}"))?;
+        tracing::info!(addr = %listener.local_addr()?, "gRPC client API listening");
+        let svc = grpc::NodeServiceImpl::new(state.clone(), token);
+        let state = state.clone();
+        let server = Server::builder()
+            .tls_config(tls)
+            .context("failed to configure gRPC TLS")?
+            .add_service(NodeServiceServer::new(svc))
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async move {
+                state.lifecycle.shutdown_requested().await
+            });
+        tokio::spawn(async move {
+            if let Err(e) = server.await {
+                tracing::error!(err = %e, "gRPC listener failed");
+            }
+        });
     }
 
     // Run Unix socket server (blocks until shutdown signal)
@@ -291,12 +515,33 @@ async fn main() -> Result<()> {
         }
     }
 
+    handler::drain::persist_ingress_limits(&state).await;
+
     // Cleanup socket file
     std::fs::remove_file(&socket_path).ok();
     tracing::info!("agentbook-node shut down");
     Ok(())
 }
 
+/// Re-apply `node_config.json` whenever the daemon receives SIGHUP.
+async fn reload_on_sighup(state: Arc<NodeState>) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            tracing::warn!(err = %e, "failed to install SIGHUP handler");
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        tracing::info!("received SIGHUP, reloading configuration");
+        if let Err(e) = handler::config::reload(&state).await {
+            tracing::warn!(err = %e, "configuration reload failed, keeping current settings");
+        }
+    }
+}
+
 /// Load and decrypt recovery key. Tries 1Password auto-fill, then falls back to manual prompt.
 async fn load_encrypted_recovery_key(path: &std::path::Path) -> Result<Zeroizing<[u8; 32]>> {
     use agentbook::agent_protocol::default_agent_socket_path;
@@ -428,6 +673,49 @@ fn verify_startup_totp(state_dir: &std::path::Path, kek: &[u8; 32]) -> Result<()
     }
 }
 
+/// Relay transport registered as `identity`.
+fn relay_transport(
+    identity: &NodeIdentity,
+    relay_hosts: &[String],
+    security: &RelaySecurity,
+) -> Result<MeshTransport> {
+    let sig = identity
+        .sign(identity.node_id.as_bytes())
+        .context("failed to sign for relay registration")?;
+    Ok(MeshTransport::with_security(
+        relay_hosts.to_vec(),
+        identity.node_id.clone(),
+        identity.public_key_b64.clone(),
+        sig,
+        RelaySecurity {
+            signing_key: Some(identity.secret_key().clone()),
+            ..security.clone()
+        },
+    ))
+}
+
+/// Deliver messages addressed to a retired key until `grace_until_ms`, then
+/// drop its relay sessions.
+async fn retired_inbound_loop(
+    state: Arc<NodeState>,
+    transport: MeshTransport,
+    grace_until_ms: u64,
+) {
+    let remaining = grace_until_ms.saturating_sub(state.clock.now_ms());
+    let deadline = tokio::time::sleep(std::time::Duration::from_millis(remaining));
+    tokio::pin!(deadline);
+    let mut incoming = transport.incoming.lock().await;
+    loop {
+        tokio::select! {
+            envelope = incoming.recv() => match envelope {
+                Some(envelope) => handler::process_inbound(&state, envelope).await,
+                None => break,
+            },
+            _ = &mut deadline => break,
+        }
+    }
+}
+
 async fn relay_inbound_loop(state: Arc<NodeState>) {
     let transport = state.transport.as_ref().unwrap();
     let mut incoming = transport.incoming.lock().await;
@@ -0,0 +1,291 @@
+//! Request middleware: hooks that run around every client request, so policy
+//! (quotas, custom auth, audit) can be added to a daemon without touching the
+//! socket or gRPC front ends.
+//!
+//! Middleware is registered on [`NodeState::middleware`] before the servers
+//! start and runs in registration order. Each layer's [`Middleware::before`]
+//! may rewrite the request, annotate it for later layers, or reject it with a
+//! response of its own; [`Middleware::after`] runs in reverse order on every
+//! layer whose `before` let the request through, and may rewrite the
+//! response. Role checks happen after the `before` hooks, so a rewritten
+//! request is still checked against the caller's role (and against a
+//! freeze, see [`NodeState::frozen`]).
+
+use crate::access::{Role, allowed_while_frozen};
+use crate::handler::{NodeState, handle_request};
+use agentbook::protocol::{Request, Response};
+use std::collections::BTreeMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+
+/// What the daemon knows about a request besides its body.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    /// The caller's role on this connection.
+    pub role: Role,
+    /// The client's `request_id`, if it sent one.
+    pub request_id: Option<u64>,
+    /// Which front end the request came in on: `socket` or `grpc`.
+    pub transport: &'static str,
+    /// Free-form notes from earlier layers; logged with the request.
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl RequestContext {
+    pub fn new(role: Role, request_id: Option<u64>, transport: &'static str) -> Self {
+        Self {
+            role,
+            request_id,
+            transport,
+            annotations: BTreeMap::new(),
+        }
+    }
+
+    /// Attach a note for later layers and the request log.
+    pub fn annotate(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.annotations.insert(key.into(), value.into());
+    }
+}
+
+/// Whether a request goes on to the next layer.
+#[derive(Debug)]
+pub enum Flow {
+    Continue,
+    /// Answer with this response instead; later layers and the handler do
+    /// not run.
+    Reject(Response),
+}
+
+/// A pre/post hook around request handling.
+///
+/// Hooks run on the connection's task, so they must not block; anything slow
+/// (a remote quota service, say) belongs in a background task the hook reads
+/// from.
+pub trait Middleware: Send + Sync {
+    /// Name used in logs.
+    fn name(&self) -> &str;
+
+    /// Inspect, rewrite or reject a request before it is handled.
+    fn before(&self, _ctx: &mut RequestContext, _request: &mut Request) -> Flow {
+        Flow::Continue
+    }
+
+    /// Inspect or rewrite the response to `request` (as `before` left it).
+    fn after(&self, _ctx: &RequestContext, _request: &Request, _response: &mut Response) {}
+}
+
+/// The registered middleware, in order.
+#[derive(Default)]
+pub struct Chain {
+    layers: RwLock<Vec<Arc<dyn Middleware>>>,
+}
+
+impl Chain {
+    /// Add `layer` after the ones already registered.
+    pub fn register(&self, layer: Arc<dyn Middleware>) {
+        tracing::debug!(middleware = layer.name(), "registered middleware");
+        self.layers.write().unwrap().push(layer);
+    }
+
+    /// Names of the registered layers, in order.
+    pub fn names(&self) -> Vec<String> {
+        let layers = self.layers.read().unwrap();
+        layers.iter().map(|l| l.name().to_string()).collect()
+    }
+
+    fn snapshot(&self) -> Vec<Arc<dyn Middleware>> {
+        self.layers.read().unwrap().clone()
+    }
+}
+
+/// Run `request` through the middleware chain, the role check and the
+/// handler.
+pub async fn dispatch(
+    state: &Arc<NodeState>,
+    mut ctx: RequestContext,
+    mut request: Request,
+) -> Response {
+    let layers = state.middleware.snapshot();
+    let mut passed = 0;
+    let mut rejected = None;
+    for layer in &layers {
+        match layer.before(&mut ctx, &mut request) {
+            Flow::Continue => passed += 1,
+            Flow::Reject(response) => {
+                tracing::debug!(middleware = layer.name(), "request rejected by middleware");
+                rejected = Some(response);
+                break;
+            }
+        }
+    }
+
+    let mut response = match rejected {
+        Some(response) => response,
+        None if !ctx.role.permits(&request) => Response::Error {
+            code: "forbidden".to_string(),
+            message: format!(
+                "{} requires more than the {} role",
+                crate::socket::request_kind(&request),
+                ctx.role
+            ),
+        },
+        None if state.frozen.load(Ordering::SeqCst) && !allowed_while_frozen(&request) => {
+            Response::Error {
+                code: "frozen".to_string(),
+                message: format!(
+                    "{} is refused while the node is frozen",
+                    crate::socket::request_kind(&request)
+                ),
+            }
+        }
+        // Without middleware nothing needs the request afterwards.
+        None if layers.is_empty() => return handle_request(state, request).await,
+        None => handle_request(state, request.clone()).await,
+    };
+
+    for layer in layers[..passed].iter().rev() {
+        layer.after(&ctx, &request, &mut response);
+    }
+    if !ctx.annotations.is_empty() {
+        tracing::debug!(annotations = ?ctx.annotations, "request annotations");
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::make_test_state;
+    use std::sync::Mutex;
+
+    /// Rejects inbox reads, turns `ack` into `health`, and records what it saw.
+    #[derive(Default)]
+    struct Policy {
+        seen: Mutex<Vec<String>>,
+    }
+
+    impl Middleware for Policy {
+        fn name(&self) -> &str {
+            "policy"
+        }
+
+        fn before(&self, ctx: &mut RequestContext, request: &mut Request) -> Flow {
+            match request {
+                Request::Inbox { .. } => {
+                    return Flow::Reject(Response::Error {
+                        code: "quota_exceeded".to_string(),
+                        message: "inbox reads are limited".to_string(),
+                    });
+                }
+                Request::InboxAck { .. } => *request = Request::Health,
+                _ => {}
+            }
+            ctx.annotate("policy", "checked");
+            Flow::Continue
+        }
+
+        fn after(&self, ctx: &RequestContext, request: &Request, response: &mut Response) {
+            let ok = matches!(response, Response::Ok { .. });
+            self.seen.lock().unwrap().push(format!(
+                "{} {} {ok}",
+                crate::socket::request_kind(request),
+                ctx.annotations["policy"]
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware_rewrites_rejects_and_sees_responses() {
+        let (state, _dir) = make_test_state();
+        let policy = Arc::new(Policy::default());
+        state.middleware.register(policy.clone());
+        assert_eq!(state.middleware.names(), ["policy"]);
+
+        let ctx = || RequestContext::new(Role::Admin, Some(1), "socket");
+        let resp = dispatch(
+            &state,
+            ctx(),
+            Request::Inbox {
+                unread_only: false,
+                limit: None,
+            },
+        )
+        .await;
+        assert!(
+            matches!(&resp, Response::Error { code, .. } if code == "quota_exceeded"),
+            "{resp:?}"
+        );
+
+        // Acking an unknown message would fail; the rewritten request succeeds.
+        let resp = dispatch(
+            &state,
+            ctx(),
+            Request::InboxAck {
+                message_id: "nope".to_string(),
+            },
+        )
+        .await;
+        assert!(matches!(resp, Response::Ok { .. }), "{resp:?}");
+
+        // Rejected requests never reach `after`.
+        assert_eq!(*policy.seen.lock().unwrap(), ["health checked true"]);
+    }
+
+    #[tokio::test]
+    async fn rewritten_requests_are_still_role_checked() {
+        struct Escalate;
+        impl Middleware for Escalate {
+            fn name(&self) -> &str {
+                "escalate"
+            }
+            fn before(&self, _ctx: &mut RequestContext, request: &mut Request) -> Flow {
+                *request = Request::Shutdown;
+                Flow::Continue
+            }
+        }
+
+        let (state, _dir) = make_test_state();
+        state.middleware.register(Arc::new(Escalate));
+        let resp = dispatch(
+            &state,
+            RequestContext::new(Role::ViewOnly, None, "socket"),
+            Request::Health,
+        )
+        .await;
+        assert!(
+            matches!(&resp, Response::Error { code, .. } if code == "forbidden"),
+            "{resp:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn frozen_node_refuses_everything_but_reads_and_controls() {
+        let (state, _dir) = make_test_state();
+        let admin = || RequestContext::new(Role::Admin, None, "socket");
+        let post = || Request::PostFeed {
+            body: "still here".to_string(),
+        };
+        let code = |resp: &Response| match resp {
+            Response::Error { code, .. } => code.clone(),
+            _ => "ok".to_string(),
+        };
+
+        let resp = dispatch(&state, admin(), Request::Freeze).await;
+        assert!(matches!(resp, Response::Ok { .. }), "{resp:?}");
+        assert_eq!(code(&dispatch(&state, admin(), post()).await), "frozen");
+        let inbox = Request::Inbox {
+            unread_only: false,
+            limit: None,
+        };
+        assert_eq!(code(&dispatch(&state, admin(), inbox).await), "ok");
+        // Admin requests that move funds are refused too.
+        let yolo = Request::YoloSendEth {
+            to: "0x0000000000000000000000000000000000000001".to_string(),
+            amount: "0.01".to_string(),
+        };
+        assert_eq!(code(&dispatch(&state, admin(), yolo).await), "frozen");
+
+        dispatch(&state, admin(), Request::Unfreeze).await;
+        assert_ne!(code(&dispatch(&state, admin(), post()).await), "frozen");
+    }
+}
@@ -0,0 +1,155 @@
+//! Resolving [`SecretRef`]s from `node_config.json`: files, environment
+//! variables, commands and HashiCorp Vault.
+//!
+//! Errors name the reference (path, variable, command) but never include
+//! what was read, so a misconfigured secret cannot end up in the log.
+
+use agentbook::protocol::SecretRef;
+use anyhow::{Context, Result, bail};
+use std::process::Stdio;
+use std::time::Duration;
+
+/// How long a command or Vault request may take.
+const TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_VAULT_TOKEN_ENV: &str = "VAULT_TOKEN";
+
+/// Short description of `secret` for error messages.
+fn describe(secret: &SecretRef) -> String {
+    match secret {
+        SecretRef::File { path } => format!("file {path}"),
+        SecretRef::Env { var } => format!("environment variable {var}"),
+        SecretRef::Exec { command } => format!(
+            "command {:?}",
+            command.first().map(String::as_str).unwrap_or("")
+        ),
+        SecretRef::Vault { url, field, .. } => format!("vault field {field:?} at {url}"),
+    }
+}
+
+/// Fetch the value `secret` points at.
+pub async fn resolve(secret: &SecretRef) -> Result<String> {
+    let value = match secret {
+        SecretRef::File { path } => tokio::fs::read_to_string(path)
+            .await
+            .context("failed to read it")
+            .map(strip_newline),
+        SecretRef::Env { var } => std::env::var(var).context("not set"),
+        SecretRef::Exec { command } => run(command).await,
+        SecretRef::Vault {
+            url,
+            field,
+            token_env,
+        } => {
+            vault(
+                url,
+                field,
+                token_env.as_deref().unwrap_or(DEFAULT_VAULT_TOKEN_ENV),
+            )
+            .await
+        }
+    };
+    let value = value.with_context(|| format!("secret from {}", describe(secret)))?;
+    if value.is_empty() {
+        bail!("secret from {} is empty", describe(secret));
+    }
+    Ok(value)
+}
+
+fn strip_newline(mut value: String) -> String {
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+    value
+}
+
+async fn run(command: &[String]) -> Result<String> {
+    let Some((program, args)) = command.split_first() else {
+        bail!("command is empty");
+    };
+    let child = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        // The command's own diagnostics go to the daemon's stderr.
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()
+        .context("failed to run it")?;
+    let output = tokio::time::timeout(TIMEOUT, child.wait_with_output())
+        .await
+        .context("timed out")??;
+    if !output.status.success() {
+        bail!("it exited with {}", output.status);
+    }
+    let value = String::from_utf8(output.stdout).context("output is not UTF-8")?;
+    Ok(strip_newline(value))
+}
+
+async fn vault(url: &str, field: &str, token_env: &str) -> Result<String> {
+    let token = std::env::var(token_env).with_context(|| format!("{token_env} is not set"))?;
+    let client = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+    let resp = client
+        .get(url)
+        .header("X-Vault-Token", token)
+        .send()
+        .await
+        .context("vault request failed")?;
+    if !resp.status().is_success() {
+        bail!("vault returned {}", resp.status());
+    }
+    let body: serde_json::Value = resp.json().await.context("invalid vault response")?;
+    // KV v2 nests the secret under `data.data`; v1 under `data`.
+    let data = &body["data"];
+    let data = if data["data"].is_object() {
+        &data["data"]
+    } else {
+        data
+    };
+    match data[field].as_str() {
+        Some(value) => Ok(value.to_string()),
+        None => bail!("no string field {field:?} in the secret"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn file_and_exec_secrets_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hook.secret");
+        std::fs::write(&path, "s3cret\n").unwrap();
+        let file = SecretRef::File {
+            path: path.to_string_lossy().into_owned(),
+        };
+        assert_eq!(resolve(&file).await.unwrap(), "s3cret");
+
+        let exec = SecretRef::Exec {
+            command: vec!["printf".into(), "from-cmd\\n".into()],
+        };
+        assert_eq!(resolve(&exec).await.unwrap(), "from-cmd");
+    }
+
+    #[tokio::test]
+    async fn errors_do_not_leak_values() {
+        let exec = SecretRef::Exec {
+            command: vec!["sh".into(), "-c".into(), "echo leaked; exit 3".into()],
+        };
+        let err = format!("{:#}", resolve(&exec).await.unwrap_err());
+        assert!(err.contains("exit status: 3"), "{err}");
+        assert!(!err.contains("leaked"), "{err}");
+
+        let env = SecretRef::Env {
+            var: "AGENTBOOK_TEST_SECRET_THAT_IS_NOT_SET".into(),
+        };
+        let err = format!("{:#}", resolve(&env).await.unwrap_err());
+        assert!(
+            err.contains("AGENTBOOK_TEST_SECRET_THAT_IS_NOT_SET"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,33 @@
+//! The daemon's own resource usage for the health report, read from
+//! `/proc`. Elsewhere the values are unknown.
+
+/// Resident set size in bytes.
+pub fn rss_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let resident_pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    u64::try_from(page_size)
+        .ok()
+        .map(|size| resident_pages * size)
+}
+
+/// Number of open file descriptors.
+pub fn open_fds() -> Option<usize> {
+    let entries = std::fs::read_dir("/proc/self/fd").ok()?;
+    // The handle reading the directory is one of the entries.
+    Some(entries.count().saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_own_usage_on_linux() {
+        assert!(rss_bytes().is_some_and(|rss| rss > 0));
+        // Other tests open files concurrently, so only check it is plausible.
+        assert!(open_fds().is_some_and(|fds| fds >= 3));
+    }
+}
@@ -1,14 +1,34 @@
-use crate::handler::{NodeState, handle_request};
-use agentbook::protocol::{MAX_LINE_BYTES, Request, RequestEnvelope, Response, ResponseEnvelope};
+use crate::access::Role;
+use crate::clients::Connection;
+use crate::handler::NodeState;
+use crate::middleware::{self, RequestContext};
+use agentbook::protocol::{
+    Event, MAX_LINE_BYTES, MIN_PROTOCOL_VERSION, Negotiated, PROTOCOL_VERSION, Request,
+    RequestEnvelope, Response, ResponseEnvelope, negotiate_protocol, protocol_warnings,
+};
 use anyhow::{Context, Result};
 use futures_util::{SinkExt, StreamExt};
 use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::UnixListener;
-use tokio_util::codec::{FramedRead, FramedWrite, LinesCodec};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio_util::bytes::BytesMut;
+use tokio_util::codec::{Decoder, FramedRead, FramedWrite, LinesCodec, LinesCodecError};
+use tracing::Instrument;
 
 /// Start the Unix socket server. Accepts client connections and processes requests.
 pub async fn serve(state: Arc<NodeState>, socket_path: &Path) -> Result<()> {
+    // Other users need to reach the socket in multi-user mode; peer uids are
+    // checked per connection instead.
+    let (dir_mode, socket_mode) = if state.access.is_multi_user() {
+        (0o711, 0o666)
+    } else {
+        (0o700, 0o600)
+    };
+
     // Ensure parent directory exists
     if let Some(parent) = socket_path.parent() {
         std::fs::create_dir_all(parent)
@@ -16,7 +36,7 @@ pub async fn serve(state: Arc<NodeState>, socket_path: &Path) -> Result<()> {
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700)).ok();
+            std::fs::set_permissions(parent, std::fs::Permissions::from_mode(dir_mode)).ok();
         }
     }
 
@@ -31,33 +51,81 @@ pub async fn serve(state: Arc<NodeState>, socket_path: &Path) -> Result<()> {
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
-        std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(0o600)).ok();
+        std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(socket_mode)).ok();
     }
 
     tracing::info!(path = %socket_path.display(), "Unix socket listening");
+    let owner_uid = unsafe { libc::getuid() };
 
     loop {
-        let (stream, _) = listener.accept().await?;
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = state.lifecycle.shutdown_requested() => {
+                tracing::info!("socket server stopping");
+                return Ok(());
+            }
+        };
+        let uid = stream.peer_cred().ok().map(|cred| cred.uid());
         let state = state.clone();
-        tokio::spawn(async move {
-            if let Err(e) = handle_client(state, stream).await {
-                tracing::debug!(err = %e, "client disconnected");
+        tokio::spawn(
+            async move {
+                if let Err(e) = handle_client(state, stream, uid, owner_uid).await {
+                    tracing::debug!(err = %e, "client disconnected");
+                }
             }
-        });
+            .instrument(tracing::info_span!("client_session", uid)),
+        );
     }
 }
 
-async fn handle_client(state: Arc<NodeState>, stream: tokio::net::UnixStream) -> Result<()> {
+async fn handle_client(
+    state: Arc<NodeState>,
+    stream: tokio::net::UnixStream,
+    uid: Option<u32>,
+    owner_uid: u32,
+) -> Result<()> {
     let (r, w) = stream.into_split();
-    let mut reader = FramedRead::new(r, LinesCodec::new_with_max_length(MAX_LINE_BYTES));
+    let reader = FramedRead::new(r, RequestLines::new());
     let mut writer = FramedWrite::new(w, LinesCodec::new_with_max_length(MAX_LINE_BYTES));
 
-    // Send Hello
+    let Some(role) = uid.and_then(|uid| state.access.role_for_uid(uid, owner_uid)) else {
+        tracing::warn!(uid, "rejected socket client from unlisted user");
+        let resp = error_envelope(None, "unauthorized", "this user may not use the node");
+        writer.send(serde_json::to_string(&resp)?).await.ok();
+        return Ok(());
+    };
+    let peer = match uid {
+        Some(uid) => format!("uid {uid}"),
+        None => "unknown".to_string(),
+    };
+    run_session(state, reader, writer, role, "socket", peer).await
+}
+
+/// Greet an admitted client, then answer the requests its `role` permits and
+/// forward events until it disconnects, is disconnected by an admin, or asks
+/// the node to shut down.
+pub(crate) async fn run_session<R, W>(
+    state: Arc<NodeState>,
+    mut reader: FramedRead<R, RequestLines>,
+    mut writer: FramedWrite<W, LinesCodec>,
+    role: Role,
+    transport: &'static str,
+    peer: String,
+) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    // Send Hello. The session speaks version 1 until the client negotiates.
+    let mut protocol = MIN_PROTOCOL_VERSION;
     let hello = ResponseEnvelope {
         request_id: None,
         response: Response::Hello {
             node_id: state.identity.node_id.clone(),
             version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_min: MIN_PROTOCOL_VERSION,
+            protocol_max: PROTOCOL_VERSION,
+            warnings: protocol_warnings(protocol),
         },
     };
     let hello_line = serde_json::to_string(&hello)?;
@@ -65,51 +133,332 @@ async fn handle_client(state: Arc<NodeState>, stream: tokio::net::UnixStream) ->
 
     // Subscribe to events
     let mut event_rx = state.event_tx.subscribe();
+    let connection = Connection::register(&state, transport, peer, role);
+    tracing::debug!(client_id = connection.id(), "client session started");
+    let mut inbox_rx = None;
 
     loop {
         tokio::select! {
             line = reader.next() => {
                 let Some(line) = line else { break };
-                let line = line?;
-                let req = parse_request_envelope(&line)
-                    .with_context(|| format!("invalid request: {line}"))?;
+                let line = match line? {
+                    Some(line) => line,
+                    None => {
+                        let resp = error_envelope(
+                            None,
+                            "line_too_long",
+                            &format!("request exceeds {MAX_LINE_BYTES} bytes"),
+                        );
+                        writer.send(serde_json::to_string(&resp)?).await?;
+                        continue;
+                    }
+                };
+                let req = match parse_request_envelope(&line, protocol) {
+                    Ok(req) => req,
+                    Err(resp) => {
+                        tracing::debug!(line = %truncate(&line), "invalid request");
+                        writer.send(serde_json::to_string(&resp)?).await?;
+                        continue;
+                    }
+                };
 
-                let is_shutdown = matches!(req.request, agentbook::protocol::Request::Shutdown);
-                let resp = handle_request(&state, req.request).await;
+                if let Request::Negotiate { min, max } = req.request {
+                    let response = match negotiate_protocol(min, max) {
+                        Some(version) => {
+                            protocol = version;
+                            let negotiated = Negotiated {
+                                protocol,
+                                warnings: protocol_warnings(protocol),
+                            };
+                            Response::Ok {
+                                data: Some(serde_json::to_value(negotiated)?),
+                            }
+                        }
+                        None => Response::Error {
+                            code: "unsupported_protocol".to_string(),
+                            message: format!(
+                                "no common protocol version: client speaks {min}..={max}, \
+                                 daemon speaks {MIN_PROTOCOL_VERSION}..={PROTOCOL_VERSION}"
+                            ),
+                        },
+                    };
+                    let resp = ResponseEnvelope {
+                        request_id: req.request_id,
+                        response,
+                    };
+                    writer.send(serde_json::to_string(&resp)?).await?;
+                    continue;
+                }
+
+                let is_claim = matches!(req.request, Request::ClaimInbox);
+                let is_shutdown = matches!(
+                    req.request,
+                    Request::Shutdown | Request::Drain { .. }
+                );
+                let span = tracing::info_span!(
+                    "request",
+                    kind = request_kind(&req.request),
+                    request_id = req.request_id,
+                );
+                crate::telemetry::set_parent(&span, req.trace_context.as_ref());
+                let ctx = RequestContext::new(role, req.request_id, transport);
+                let resp = middleware::dispatch(&state, ctx, req.request)
+                    .instrument(span)
+                    .await;
+                let is_shutdown = is_shutdown && matches!(resp, Response::Ok { .. });
+                // The claim is checked like any request, but the session
+                // holds it and receives the pushes.
+                if is_claim && matches!(resp, Response::Ok { .. }) {
+                    inbox_rx = Some(connection.claim_inbox());
+                }
                 let resp = ResponseEnvelope {
                     request_id: req.request_id,
                     response: resp,
                 };
+                connection.record_request();
                 let resp_line = serde_json::to_string(&resp)?;
                 writer.send(resp_line).await?;
 
                 if is_shutdown {
+                    state.lifecycle.shutdown();
                     break;
                 }
             }
             event = event_rx.recv() => {
-                if let Ok(event) = event {
-                    let resp = ResponseEnvelope {
-                        request_id: None,
-                        response: Response::Event { event },
-                    };
-                    let resp_line = serde_json::to_string(&resp)?;
-                    writer.send(resp_line).await?;
+                match event {
+                    Ok(event) => {
+                        let resp = ResponseEnvelope {
+                            request_id: None,
+                            response: Response::Event { event },
+                        };
+                        let resp_line = serde_json::to_string(&resp)?;
+                        writer.send(resp_line).await?;
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::debug!(client_id = connection.id(), skipped, "client lagged");
+                        state.events_lagged.fetch_add(skipped, Ordering::Relaxed);
+                    }
+                    Err(RecvError::Closed) => {}
                 }
             }
+            pushed = next_push(&mut inbox_rx) => {
+                match pushed {
+                    Some(event) => {
+                        let resp = ResponseEnvelope {
+                            request_id: None,
+                            response: Response::Event { event },
+                        };
+                        writer.send(serde_json::to_string(&resp)?).await?;
+                    }
+                    // Another session claimed the inbox.
+                    None => inbox_rx = None,
+                }
+            }
+            _ = connection.disconnected() => {
+                let resp = error_envelope(None, "disconnected", "disconnected by an admin");
+                writer.send(serde_json::to_string(&resp)?).await.ok();
+                break;
+            }
         }
     }
 
     Ok(())
 }
 
-fn parse_request_envelope(line: &str) -> Result<RequestEnvelope> {
+/// Line codec that reports oversized lines as `None` instead of failing.
+///
+/// `FramedRead` ends the stream after any decoder error, so surfacing
+/// `MaxLineLengthExceeded` as an error would drop the client. The inner codec
+/// discards up to the next newline, after which decoding resumes normally.
+pub(crate) struct RequestLines(LinesCodec);
+
+impl RequestLines {
+    pub(crate) fn new() -> Self {
+        Self(LinesCodec::new_with_max_length(MAX_LINE_BYTES))
+    }
+}
+
+impl Decoder for RequestLines {
+    type Item = Option<String>;
+    type Error = LinesCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.0.decode(buf) {
+            Ok(line) => Ok(line.map(Some)),
+            Err(LinesCodecError::MaxLineLengthExceeded) => Ok(Some(None)),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.0.decode_eof(buf) {
+            Ok(line) => Ok(line.map(Some)),
+            Err(LinesCodecError::MaxLineLengthExceeded) => Ok(Some(None)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Decode one request line. Lines that are not a valid request yield an
+/// `invalid_request` error response (`unknown_request` for an unknown `type`
+/// from protocol 2 on), echoing the `request_id` when one can be recovered so
+/// clients can match it to the request they sent.
+fn parse_request_envelope(line: &str, protocol: u32) -> Result<RequestEnvelope, ResponseEnvelope> {
     serde_json::from_str::<RequestEnvelope>(line)
         .or_else(|_| {
             serde_json::from_str::<Request>(line).map(|request| RequestEnvelope {
                 request_id: None,
+                trace_context: None,
                 request,
             })
         })
-        .map_err(Into::into)
+        .map_err(|e| {
+            let request_id = serde_json::from_str::<serde_json::Value>(line)
+                .ok()
+                .and_then(|v| v.get("request_id").and_then(|id| id.as_u64()));
+            let message = e.to_string();
+            let code = if protocol >= 2 && message.starts_with("unknown variant") {
+                "unknown_request"
+            } else {
+                "invalid_request"
+            };
+            error_envelope(request_id, code, &message)
+        })
+}
+
+/// The next inbox push, once this session has claimed the inbox.
+async fn next_push(inbox_rx: &mut Option<mpsc::Receiver<Event>>) -> Option<Event> {
+    match inbox_rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// The request's wire `type` tag, for span names and logs.
+pub(crate) fn request_kind(request: &Request) -> String {
+    serde_json::to_value(request)
+        .ok()
+        .and_then(|v| v.get("type")?.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+pub(crate) fn error_envelope(
+    request_id: Option<u64>,
+    code: &str,
+    message: &str,
+) -> ResponseEnvelope {
+    ResponseEnvelope {
+        request_id,
+        response: Response::Error {
+            code: code.to_string(),
+            message: message.to_string(),
+        },
+    }
+}
+
+/// Shorten a request line for logging.
+fn truncate(line: &str) -> &str {
+    match line.char_indices().nth(200) {
+        Some((i, _)) => &line[..i],
+        None => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn error_code(resp: &ResponseEnvelope) -> &str {
+        match &resp.response {
+            Response::Error { code, .. } => code,
+            other => panic!("expected error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn request_kind_is_wire_tag() {
+        assert_eq!(request_kind(&Request::Health), "health");
+        assert_eq!(
+            request_kind(&Request::InviteAccept { token: "t".into() }),
+            "invite_accept"
+        );
+    }
+
+    #[test]
+    fn unknown_variant_is_invalid_request_with_id() {
+        let resp =
+            parse_request_envelope(r#"{"request_id":9,"type":"launch_missiles"}"#, 1).unwrap_err();
+        assert_eq!(error_code(&resp), "invalid_request");
+        assert_eq!(resp.request_id, Some(9));
+
+        // Protocol 2 tells an unknown request apart from a malformed one.
+        let resp =
+            parse_request_envelope(r#"{"request_id":9,"type":"launch_missiles"}"#, 2).unwrap_err();
+        assert_eq!(error_code(&resp), "unknown_request");
+        let resp = parse_request_envelope(r#"{"type":"follow"}"#, 2).unwrap_err();
+        assert_eq!(error_code(&resp), "invalid_request");
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_lines_never_panic(line in any::<String>()) {
+            if let Err(resp) = parse_request_envelope(&line, 1) {
+                prop_assert_eq!(error_code(&resp), "invalid_request");
+            }
+        }
+
+        #[test]
+        fn truncated_requests_are_rejected(
+            to in "[a-z0-9@]{1,20}",
+            body in any::<String>(),
+            id in any::<u64>(),
+            cut in any::<prop::sample::Index>(),
+        ) {
+            let line = serde_json::to_string(&RequestEnvelope {
+                request_id: Some(id),
+                trace_context: None,
+                request: Request::SendDm { to: to.clone(), body: body.clone(), attachments: Vec::new(), in_reply_to: None, reply_within_secs: None },
+            })
+            .unwrap();
+            match parse_request_envelope(&line, 1) {
+                Ok(RequestEnvelope { request_id, request: Request::SendDm { to: t, body: b, .. }, .. }) => {
+                    prop_assert_eq!(request_id, Some(id));
+                    prop_assert_eq!(t, to);
+                    prop_assert_eq!(b, body);
+                }
+                other => prop_assert!(false, "round trip failed: {:?}", other.err()),
+            }
+
+            // Any strict prefix of a JSON object is incomplete.
+            let end = cut.index(line.len());
+            if let Some(prefix) = line.get(..end) {
+                let resp = parse_request_envelope(prefix, 1).unwrap_err();
+                prop_assert_eq!(error_code(&resp), "invalid_request");
+            }
+        }
+
+        #[test]
+        fn wrong_field_types_are_rejected(
+            ty in "(send_dm|follow|inbox|join_room|invite_create)",
+            value in prop_oneof![
+                Just(serde_json::json!(null)),
+                any::<bool>().prop_map(serde_json::Value::from),
+                any::<i64>().prop_map(serde_json::Value::from),
+                Just(serde_json::json!([1, 2, 3])),
+            ],
+        ) {
+            let line = serde_json::json!({
+                "type": ty,
+                "to": value,
+                "target": value,
+                "room": value,
+                "ttl_ms": "soon",
+                "unread_only": "maybe",
+            })
+            .to_string();
+            let resp = parse_request_envelope(&line, 1).unwrap_err();
+            prop_assert_eq!(error_code(&resp), "invalid_request");
+        }
+    }
 }
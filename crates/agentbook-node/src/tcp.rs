@@ -0,0 +1,237 @@
+//! Optional TCP + TLS listener for the client API, for daemons running where
+//! the Unix socket is out of reach (containers, VMs).
+//!
+//! It speaks the same JSON-lines protocol as the Unix socket. TCP has no file
+//! permissions to lean on, so each connection must open with an
+//! [`AuthRequest`] carrying the node's API token, or a token granted in
+//! `access.json`, before it is greeted.
+
+use crate::handler::NodeState;
+use crate::socket::{RequestLines, error_envelope, run_session};
+use agentbook::protocol::{AuthRequest, MAX_LINE_BYTES};
+use anyhow::{Context, Result, bail};
+use futures_util::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::{self, ServerConfig};
+use tokio_util::codec::{FramedRead, FramedWrite, LinesCodec};
+use tracing::Instrument;
+use zeroize::Zeroizing;
+
+const API_TOKEN_FILE: &str = "api_token";
+
+/// How long a client has to authenticate after the TLS handshake.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// TLS settings and the node's admin API token.
+pub struct TcpApi {
+    acceptor: TlsAcceptor,
+    token: Zeroizing<String>,
+}
+
+impl TcpApi {
+    /// Serve TLS with a PEM certificate chain and private key. `token`
+    /// grants admin access; `access.json` may grant more.
+    pub fn new(cert_pem: &[u8], key_pem: &[u8], token: String) -> Result<Self> {
+        let certs = CertificateDer::pem_slice_iter(cert_pem)
+            .collect::<Result<Vec<_>, _>>()
+            .context("invalid TLS certificate")?;
+        let key = PrivateKeyDer::from_pem_slice(key_pem).context("invalid TLS private key")?;
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()?
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .context("TLS certificate does not match private key")?;
+        Ok(Self {
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+            token: Zeroizing::new(token),
+        })
+    }
+}
+
+/// Read the API token from `state_dir`, creating a random one on first use.
+pub fn load_or_create_api_token(state_dir: &Path) -> Result<String> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let path = state_dir.join(API_TOKEN_FILE);
+    let token = hex::encode(agentbook_mesh::crypto::random_key_material());
+    // `create_new` so the file is never readable by others, not even briefly,
+    // and two nodes starting at once agree on the token.
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+    {
+        Ok(mut file) => {
+            file.write_all(token.as_bytes())
+                .and_then(|()| file.sync_all())
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            Ok(token)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            let token = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let token = token.trim();
+            if token.is_empty() {
+                bail!("{} is empty", path.display());
+            }
+            Ok(token.to_string())
+        }
+        Err(e) => Err(e).with_context(|| format!("failed to create {}", path.display())),
+    }
+}
+
+/// Accept TLS clients on `listener` until the node shuts down.
+pub async fn serve(state: Arc<NodeState>, listener: TcpListener, api: TcpApi) -> Result<()> {
+    let api = Arc::new(api);
+    tracing::info!(addr = %listener.local_addr()?, "TCP listener accepting TLS clients");
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = state.lifecycle.shutdown_requested() => {
+                tracing::info!("TCP listener stopping");
+                return Ok(());
+            }
+        };
+        let state = state.clone();
+        let api = api.clone();
+        tokio::spawn(
+            async move {
+                if let Err(e) = handle_connection(state, stream, peer, &api).await {
+                    tracing::debug!(err = %e, "client disconnected");
+                }
+            }
+            .instrument(tracing::info_span!("client_session", peer = %peer)),
+        );
+    }
+}
+
+async fn handle_connection(
+    state: Arc<NodeState>,
+    stream: TcpStream,
+    peer: SocketAddr,
+    api: &TcpApi,
+) -> Result<()> {
+    let stream = api
+        .acceptor
+        .accept(stream)
+        .await
+        .context("TLS handshake failed")?;
+    let (r, w) = tokio::io::split(stream);
+    let mut reader = FramedRead::new(r, RequestLines::new());
+    let mut writer = FramedWrite::new(w, LinesCodec::new_with_max_length(MAX_LINE_BYTES));
+
+    let grant = match tokio::time::timeout(AUTH_TIMEOUT, reader.next()).await {
+        Ok(Some(Ok(Some(line)))) => {
+            serde_json::from_str::<AuthRequest>(&line)
+                .ok()
+                .and_then(|auth| {
+                    let (role, name) = state.access.role_for_token(&auth.token, &api.token)?;
+                    Some((role, name.to_string()))
+                })
+        }
+        _ => None,
+    };
+    let Some((role, name)) = grant else {
+        tracing::warn!(%peer, "rejected TCP client without a valid API token");
+        let resp = error_envelope(None, "unauthorized", "missing or invalid API token");
+        writer.send(serde_json::to_string(&resp)?).await.ok();
+        return Ok(());
+    };
+    tracing::info!(%peer, token = %name, %role, "TCP client authenticated");
+
+    run_session(state, reader, writer, role, "tcp", peer.to_string()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::make_test_state_in;
+    use agentbook::client::NodeClient;
+    use agentbook::protocol::Request;
+
+    const CERT: &[u8] = include_bytes!("../testdata/localhost.crt");
+    const KEY: &[u8] = include_bytes!("../testdata/localhost.key");
+
+    #[test]
+    fn api_token_is_created_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let token = load_or_create_api_token(dir.path()).unwrap();
+        assert_eq!(token.len(), 64);
+        assert_eq!(load_or_create_api_token(dir.path()).unwrap(), token);
+
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.path().join(API_TOKEN_FILE))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn existing_api_token_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(API_TOKEN_FILE), "preset\n").unwrap();
+        assert_eq!(load_or_create_api_token(dir.path()).unwrap(), "preset");
+
+        std::fs::write(dir.path().join(API_TOKEN_FILE), "").unwrap();
+        assert!(load_or_create_api_token(dir.path()).is_err());
+    }
+
+    #[tokio::test]
+    async fn tls_clients_need_a_token_and_get_its_role() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("access.json"),
+            r#"{"tokens":[{"name":"dashboard","token":"view-token","role":"view_only"}]}"#,
+        )
+        .unwrap();
+        let state = make_test_state_in(dir.path());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let api = TcpApi::new(CERT, KEY, "secret-token".to_string()).unwrap();
+        let server = tokio::spawn(serve(state.clone(), listener, api));
+
+        let err = NodeClient::connect_tcp(&addr, "localhost", CERT, "wrong")
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("invalid API token"), "{err}");
+
+        // A view-only token can read but not act.
+        let mut viewer = NodeClient::connect_tcp(&addr, "localhost", CERT, "view-token")
+            .await
+            .unwrap();
+        viewer.request(Request::Health).await.unwrap();
+        let err = viewer
+            .request(Request::PostFeed { body: "hi".into() })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("view_only"), "{err}");
+        viewer.request(Request::Shutdown).await.unwrap_err();
+
+        let mut client = NodeClient::connect_tcp(&addr, "localhost", CERT, "secret-token")
+            .await
+            .unwrap();
+        assert_eq!(client.node_id(), state.identity.node_id);
+        client.request(Request::Health).await.unwrap();
+
+        // Shutdown over TCP stops the listener like it does the Unix socket.
+        client.request(Request::Shutdown).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+//! Logging and (with the `otel` feature) OpenTelemetry span export.
+//!
+//! Logs go to stderr by default, or to journald or syslog (see
+//! [`crate::journal`]) for production deployments.
+//!
+//! Request handling, client and relay sessions, and mesh deliveries are
+//! always `tracing` spans. With `otel` enabled and an OTLP endpoint given,
+//! those spans are also exported, and socket requests carrying a
+//! `trace_context` continue the caller's trace.
+
+use crate::journal::{JournalLayer, LogBackend};
+use agentbook::protocol::TraceContext;
+use anyhow::{Context, Result};
+use std::sync::OnceLock;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry, reload};
+
+const DEFAULT_LOG_FILTER: &str = "agentbook_node=info";
+
+/// Swaps the installed filter at runtime; unset until [`init`] runs.
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Keeps the span exporter alive; flushes pending spans on drop.
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("failed to flush trace exporter: {e}");
+        }
+    }
+}
+
+/// Install the global subscriber. Stderr logs keep stdout free for the READY
+/// handshake. `otlp_endpoint` is ignored without the `otel` feature.
+pub fn init(otlp_endpoint: Option<&str>, backend: LogBackend) -> Result<TelemetryGuard> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| DEFAULT_LOG_FILTER.into());
+    let (filter, handle) = reload::Layer::new(filter);
+    LOG_FILTER.set(handle).ok();
+    let fmt = (backend == LogBackend::Stderr)
+        .then(|| tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
+    let journal = match backend {
+        LogBackend::Stderr => None,
+        LogBackend::Journald => {
+            Some(JournalLayer::journald().context("failed to connect to journald")?)
+        }
+        LogBackend::Syslog => Some(JournalLayer::syslog().context("failed to connect to syslog")?),
+    };
+
+    #[cfg(feature = "otel")]
+    {
+        use opentelemetry::trace::TracerProvider;
+        use opentelemetry_otlp::WithExportConfig;
+
+        let provider = match otlp_endpoint {
+            Some(endpoint) => {
+                let exporter = opentelemetry_otlp::SpanExporter::builder()
+                    .with_http()
+                    .with_endpoint(endpoint)
+                    .build()?;
+                Some(
+                    opentelemetry_sdk::trace::SdkTracerProvider::builder()
+                        .with_batch_exporter(exporter)
+                        .with_resource(
+                            opentelemetry_sdk::Resource::builder()
+                                .with_service_name("agentbook-node")
+                                .build(),
+                        )
+                        .build(),
+                )
+            }
+            None => None,
+        };
+        let otel = provider
+            .as_ref()
+            .map(|p| tracing_opentelemetry::layer().with_tracer(p.tracer("agentbook-node")));
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt)
+            .with(journal)
+            .with(otel)
+            .init();
+        Ok(TelemetryGuard { provider })
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        let _ = otlp_endpoint;
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt)
+            .with(journal)
+            .init();
+        Ok(TelemetryGuard {})
+    }
+}
+
+/// Replace the log filter, e.g. `debug` or `agentbook_node=trace,warn`.
+/// `None` restores the startup filter (`RUST_LOG` or the built-in default).
+/// The directives are validated even before [`init`] has run.
+pub fn set_log_filter(directives: Option<&str>) -> Result<()> {
+    let filter = match directives {
+        Some(directives) => EnvFilter::try_new(directives)
+            .with_context(|| format!("invalid log filter {directives:?}"))?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| DEFAULT_LOG_FILTER.into()),
+    };
+    if let Some(handle) = LOG_FILTER.get() {
+        handle.reload(filter).context("failed to swap log filter")?;
+    }
+    Ok(())
+}
+
+/// The active log filter, if logging was initialized by [`init`].
+pub fn log_filter() -> Option<String> {
+    LOG_FILTER
+        .get()
+        .and_then(|handle| handle.with_current(|filter| filter.to_string()).ok())
+}
+
+/// Make `span` a child of the caller's trace, if the request carried one.
+#[cfg(feature = "otel")]
+pub fn set_parent(span: &tracing::Span, trace_context: Option<&TraceContext>) {
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    if let Some(carrier) = trace_context {
+        let parent = TraceContextPropagator::new().extract(carrier);
+        let _ = span.set_parent(parent);
+    }
+}
+
+/// Make `span` a child of the caller's trace, if the request carried one.
+#[cfg(not(feature = "otel"))]
+pub fn set_parent(_span: &tracing::Span, _trace_context: Option<&TraceContext>) {}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{TraceContextExt, TracerProvider};
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    #[test]
+    fn request_span_continues_caller_trace() {
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+            let carrier = TraceContext::from([(
+                "traceparent".to_string(),
+                format!("00-{trace_id}-00f067aa0ba902b7-01"),
+            )]);
+            let span = tracing::info_span!("request");
+            set_parent(&span, Some(&carrier));
+            let cx = span.context();
+            assert_eq!(cx.span().span_context().trace_id().to_string(), trace_id);
+        });
+    }
+}
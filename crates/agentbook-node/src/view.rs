@@ -0,0 +1,186 @@
+//! Read-only access for other local users without listing them in
+//! `access.json`.
+//!
+//! `Share` issues a token that expires, and starts a listener on the Linux
+//! abstract socket named by [`view_socket_name`]. Abstract sockets have no
+//! file permissions, so any local user can connect there. Each connection
+//! must open with an [`AuthRequest`] carrying a live share token, and is
+//! then served as [`Role::ViewOnly`]. The main socket's uid check is
+//! unchanged.
+//!
+//! Any local user could also bind that name first, so tokens start with the
+//! node owner's uid and viewers check the listener's peer credentials
+//! against it before sending the token (see `NodeClient::connect_view`).
+
+use crate::access::{Role, token_matches};
+use crate::handler::NodeState;
+use crate::socket::{RequestLines, error_envelope, run_session};
+use agentbook::client::view_socket_name;
+use agentbook::protocol::{AuthRequest, MAX_LINE_BYTES};
+use anyhow::Result;
+use futures_util::{SinkExt, StreamExt};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::codec::{FramedRead, FramedWrite, LinesCodec};
+use tracing::Instrument;
+use zeroize::Zeroizing;
+
+/// How long a share token lasts when `Share` doesn't say.
+pub const DEFAULT_TTL_SECS: u64 = 3600;
+
+/// How long a viewer has to present its token after connecting.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Share {
+    token: Zeroizing<String>,
+    expires_at_ms: u64,
+}
+
+/// Live share tokens, and whether the view listener is running.
+#[derive(Default)]
+pub struct Shares {
+    tokens: Mutex<Vec<Share>>,
+    listening: Mutex<bool>,
+}
+
+impl Shares {
+    /// Issue a token valid for `ttl_ms`, returning it and its expiry. The
+    /// token is `<uid>.<secret>`, naming the user the node runs as.
+    pub fn issue(&self, now_ms: u64, ttl_ms: u64) -> (String, u64) {
+        let uid = unsafe { libc::getuid() };
+        let secret = hex::encode(agentbook_mesh::crypto::random_key_material());
+        let token = format!("{uid}.{secret}");
+        let expires_at_ms = now_ms.saturating_add(ttl_ms);
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.retain(|share| share.expires_at_ms > now_ms);
+        tokens.push(Share {
+            token: Zeroizing::new(token.clone()),
+            expires_at_ms,
+        });
+        (token, expires_at_ms)
+    }
+
+    /// Whether `token` is an unexpired share token.
+    pub fn admits(&self, token: &str, now_ms: u64) -> bool {
+        self.tokens
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|share| share.expires_at_ms > now_ms && token_matches(token, &share.token))
+    }
+
+    /// Forget every token, returning how many were live.
+    pub fn revoke_all(&self, now_ms: u64) -> usize {
+        let mut tokens = self.tokens.lock().unwrap();
+        let live = tokens.iter().filter(|s| s.expires_at_ms > now_ms).count();
+        tokens.clear();
+        live
+    }
+}
+
+/// Start the view listener unless it is already running.
+pub fn listen(state: &Arc<NodeState>) -> Result<()> {
+    let mut listening = state.shares.listening.lock().unwrap();
+    if *listening {
+        return Ok(());
+    }
+    let listener = bind(&view_socket_name(&state.identity.node_id))?;
+    *listening = true;
+    tracing::info!("view socket listening for shared read-only access");
+    tokio::spawn(serve(state.clone(), listener));
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn bind(name: &str) -> Result<tokio::net::UnixListener> {
+    use anyhow::Context;
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::{SocketAddr, UnixListener};
+
+    let addr = SocketAddr::from_abstract_name(name)?;
+    let listener = UnixListener::bind_addr(&addr).context("failed to bind the view socket")?;
+    listener.set_nonblocking(true)?;
+    Ok(tokio::net::UnixListener::from_std(listener)?)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bind(_name: &str) -> Result<tokio::net::UnixListener> {
+    anyhow::bail!("sharing needs Linux abstract sockets")
+}
+
+async fn serve(state: Arc<NodeState>, listener: tokio::net::UnixListener) {
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!(err = %e, "view socket accept failed");
+                    continue;
+                }
+            },
+            _ = state.lifecycle.shutdown_requested() => return,
+        };
+        let uid = stream.peer_cred().ok().map(|cred| cred.uid());
+        let state = state.clone();
+        tokio::spawn(
+            async move {
+                if let Err(e) = handle_viewer(state, stream, uid).await {
+                    tracing::debug!(err = %e, "viewer disconnected");
+                }
+            }
+            .instrument(tracing::info_span!("view_session", uid)),
+        );
+    }
+}
+
+async fn handle_viewer(
+    state: Arc<NodeState>,
+    stream: tokio::net::UnixStream,
+    uid: Option<u32>,
+) -> Result<()> {
+    let (r, w) = stream.into_split();
+    let mut reader = FramedRead::new(r, RequestLines::new());
+    let mut writer = FramedWrite::new(w, LinesCodec::new_with_max_length(MAX_LINE_BYTES));
+
+    let admitted = match tokio::time::timeout(AUTH_TIMEOUT, reader.next()).await {
+        Ok(Some(Ok(Some(line)))) => serde_json::from_str::<AuthRequest>(&line)
+            .is_ok_and(|auth| state.shares.admits(&auth.token, state.clock.now_ms())),
+        _ => false,
+    };
+    if !admitted {
+        tracing::warn!(uid, "rejected viewer without a valid share token");
+        let resp = error_envelope(
+            None,
+            "unauthorized",
+            "missing, expired or revoked share token",
+        );
+        writer.send(serde_json::to_string(&resp)?).await.ok();
+        return Ok(());
+    }
+    let peer = match uid {
+        Some(uid) => format!("uid {uid}"),
+        None => "unknown".to_string(),
+    };
+    tracing::info!(peer, "viewer connected");
+    run_session(state, reader, writer, Role::ViewOnly, "view", peer).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_expire_and_can_be_revoked() {
+        let shares = Shares::default();
+        let (first, expires_at_ms) = shares.issue(1_000, 60_000);
+        assert_eq!(expires_at_ms, 61_000);
+        let (second, _) = shares.issue(2_000, 600_000);
+        assert!(shares.admits(&first, 60_999));
+        assert!(!shares.admits(&first, 61_000));
+        assert!(shares.admits(&second, 61_000));
+        assert!(!shares.admits("not-a-token", 2_000));
+
+        assert_eq!(shares.revoke_all(61_000), 1);
+        assert!(!shares.admits(&second, 61_000));
+    }
+}
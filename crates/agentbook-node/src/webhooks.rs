@@ -0,0 +1,188 @@
+//! Webhook delivery: POST daemon events to configured URLs.
+//!
+//! Each matching event is sent once as
+//! `{"node_id", "timestamp_ms", "event": {"kind", ...}}`, with the event kind
+//! in `X-Agentbook-Event` and, when the hook has a secret, an HMAC-SHA256 of
+//! the body in `X-Agentbook-Signature: sha256=<hex>`. Delivery is best-effort:
+//! failures are logged and not retried, so receivers that must not miss
+//! anything should also reconcile against the inbox.
+
+use crate::handler::NodeState;
+use agentbook::protocol::{Event, WebhookConfig};
+use anyhow::{Context, Result, bail};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Event kinds a webhook can filter on.
+pub const EVENT_KINDS: &[&str] = &[
+    "new_message",
+    "new_room_message",
+    "new_follower",
+    "key_rotated",
+    "key_revoked",
+    "peer_idle",
+    "peer_active",
+    "reply_overdue",
+];
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reject hooks that could never deliver: bad URLs and unknown event kinds.
+pub fn validate(hook: &WebhookConfig) -> Result<()> {
+    if !(hook.url.starts_with("http://") || hook.url.starts_with("https://")) {
+        bail!(
+            "webhook url {:?} must start with http:// or https://",
+            hook.url
+        );
+    }
+    if let Some(kind) = hook
+        .events
+        .iter()
+        .find(|k| !EVENT_KINDS.contains(&k.as_str()))
+    {
+        bail!(
+            "unknown webhook event {kind:?} (expected one of {})",
+            EVENT_KINDS.join(", ")
+        );
+    }
+    if hook.secret.is_some() && hook.secret_ref.is_some() {
+        bail!("webhook {:?} sets both secret and secret_ref", hook.url);
+    }
+    Ok(())
+}
+
+/// Copies of `hooks` with each `secret_ref` resolved into `secret`.
+pub async fn resolve_secrets(hooks: &[WebhookConfig]) -> Result<Vec<WebhookConfig>> {
+    let mut resolved = hooks.to_vec();
+    for hook in &mut resolved {
+        if let Some(secret_ref) = &hook.secret_ref {
+            let secret = crate::secrets::resolve(secret_ref)
+                .await
+                .with_context(|| format!("webhook {:?}", hook.url))?;
+            hook.secret = Some(secret);
+        }
+    }
+    Ok(resolved)
+}
+
+fn wants(hook: &WebhookConfig, kind: &str) -> bool {
+    hook.events.is_empty() || hook.events.iter().any(|k| k == kind)
+}
+
+/// `sha256=<hex>` HMAC of `body` under `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Forward events to the configured webhooks until the daemon shuts down.
+pub async fn delivery_loop(state: Arc<NodeState>) {
+    let client = match reqwest::Client::builder().timeout(TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::warn!(err = %e, "failed to build webhook client, webhooks disabled");
+            return;
+        }
+    };
+    let mut events = state.event_tx.subscribe();
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = state.lifecycle.shutdown_requested() => return,
+        };
+        match event {
+            Ok(event) => deliver(&state, &client, &event).await,
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "webhook delivery fell behind, events dropped");
+                state.events_lagged.fetch_add(skipped, Ordering::Relaxed);
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+async fn deliver(state: &NodeState, client: &reqwest::Client, event: &Event) {
+    let hooks = state.webhooks.lock().await.clone();
+    if hooks.is_empty() {
+        return;
+    }
+    let event = serde_json::to_value(event).unwrap_or_default();
+    let kind = event["kind"].as_str().unwrap_or_default().to_string();
+    let body = serde_json::json!({
+        "node_id": state.identity.node_id,
+        "timestamp_ms": state.clock.now_ms(),
+        "event": event,
+    })
+    .to_string();
+
+    for hook in hooks.into_iter().filter(|h| wants(h, &kind)) {
+        let mut request = client
+            .post(&hook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Agentbook-Event", &kind);
+        if let Some(secret) = &hook.secret {
+            request = request.header("X-Agentbook-Signature", sign(secret, body.as_bytes()));
+        }
+        let request = request.body(body.clone());
+        let kind = kind.clone();
+        // One slow endpoint must not hold up the others or the next event.
+        tokio::spawn(async move {
+            match request.send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    tracing::debug!(url = %hook.url, kind, "webhook delivered");
+                }
+                Ok(resp) => {
+                    tracing::warn!(url = %hook.url, kind, status = %resp.status(), "webhook rejected");
+                }
+                Err(e) => tracing::warn!(url = %hook.url, kind, err = %e, "webhook failed"),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentbook::protocol::SecretRef;
+
+    fn hook(url: &str, events: &[&str]) -> WebhookConfig {
+        WebhookConfig {
+            url: url.to_string(),
+            events: events.iter().map(|e| e.to_string()).collect(),
+            secret: None,
+            secret_ref: None,
+        }
+    }
+
+    #[test]
+    fn signature_is_hmac_sha256() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn hooks_are_validated_and_filtered() {
+        assert!(validate(&hook("https://ci.example/hook", &["new_message"])).is_ok());
+        assert!(validate(&hook("ftp://ci.example/hook", &[])).is_err());
+        assert!(validate(&hook("https://ci.example/hook", &["session_exited"])).is_err());
+        let mut both = hook("https://ci.example/hook", &[]);
+        both.secret = Some("inline".to_string());
+        both.secret_ref = Some(SecretRef::Env {
+            var: "HOOK_SECRET".to_string(),
+        });
+        assert!(validate(&both).is_err());
+
+        assert!(wants(&hook("https://a", &[]), "key_revoked"));
+        assert!(wants(&hook("https://a", &["new_message"]), "new_message"));
+        assert!(!wants(&hook("https://a", &["new_message"]), "new_follower"));
+    }
+}
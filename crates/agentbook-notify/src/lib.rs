@@ -0,0 +1,304 @@
+//! Desktop notifications for node events.
+//!
+//! Subscribes to the daemon's event stream and raises a desktop notification
+//! for the events the user picked, so whoever supervises their agents sees a
+//! DM or a mention without keeping the TUI in front. Notifications go through
+//! the platform's own tool (`notify-send` on Linux, `osascript` on macOS,
+//! PowerShell on Windows); nothing is linked in.
+
+use agentbook::protocol::{Event, MessageType};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// Which events raise a notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// A direct message, i.e. one that usually wants a reply.
+    Dm,
+    /// A feed post from someone we follow.
+    Feed,
+    /// A room message that mentions our `@username`.
+    Mention,
+    /// Any room message.
+    Room,
+    /// Someone followed us.
+    Follower,
+    /// A followed node rotated or revoked its key.
+    Key,
+}
+
+impl Trigger {
+    pub const DEFAULT: &[Trigger] = &[
+        Trigger::Dm,
+        Trigger::Mention,
+        Trigger::Follower,
+        Trigger::Key,
+    ];
+}
+
+impl FromStr for Trigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dm" => Ok(Self::Dm),
+            "feed" => Ok(Self::Feed),
+            "mention" => Ok(Self::Mention),
+            "room" => Ok(Self::Room),
+            "follower" => Ok(Self::Follower),
+            "key" => Ok(Self::Key),
+            other => Err(format!(
+                "unknown trigger '{other}' (expected dm, feed, mention, room, follower or key)"
+            )),
+        }
+    }
+}
+
+/// A notification ready to show.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    /// Shown with the platform's urgent/critical styling where it has one.
+    pub urgent: bool,
+}
+
+/// Decide whether `event` deserves a notification. `username` is our own,
+/// without the `@`, used to spot mentions.
+pub fn notification_for(
+    event: &Event,
+    triggers: &[Trigger],
+    username: Option<&str>,
+) -> Option<Notification> {
+    let on = |t| triggers.contains(&t);
+    match event {
+        Event::NewMessage {
+            from,
+            message_type,
+            preview,
+            ..
+        } => {
+            let (trigger, title) = match message_type {
+                MessageType::DmText => (Trigger::Dm, format!("DM from {from}")),
+                MessageType::FeedPost => (Trigger::Feed, format!("Post from {from}")),
+                _ => return None,
+            };
+            on(trigger).then(|| Notification {
+                title,
+                body: preview.clone(),
+                urgent: trigger == Trigger::Dm,
+            })
+        }
+        Event::NewRoomMessage {
+            from,
+            room,
+            message_type: MessageType::RoomMessage,
+            preview,
+            ..
+        } => {
+            let mentioned = username.is_some_and(|u| mentions(preview, u));
+            if !(on(Trigger::Room) || (mentioned && on(Trigger::Mention))) {
+                return None;
+            }
+            Some(Notification {
+                title: format!("#{room}: {from}"),
+                body: preview.clone(),
+                urgent: mentioned,
+            })
+        }
+        Event::NewRoomMessage { .. } => None,
+        Event::NewFollower { node_id } => on(Trigger::Follower).then(|| Notification {
+            title: "New follower".to_string(),
+            body: node_id.clone(),
+            urgent: false,
+        }),
+        Event::KeyRotated {
+            old_node_id,
+            new_node_id,
+        } => on(Trigger::Key).then(|| Notification {
+            title: "Key rotated".to_string(),
+            body: format!("{old_node_id} is now {new_node_id}"),
+            urgent: false,
+        }),
+        Event::KeyRevoked { node_id } => on(Trigger::Key).then(|| Notification {
+            title: "Key revoked".to_string(),
+            body: format!("{node_id} revoked its key and was unfollowed"),
+            urgent: true,
+        }),
+        Event::PeerIdle { node_id, idle_ms } => on(Trigger::Dm).then(|| Notification {
+            title: format!("{node_id} went quiet"),
+            body: format!("No messages for {}s", idle_ms / 1000),
+            urgent: false,
+        }),
+        Event::PeerActive { .. } => None,
+        Event::ReplyOverdue { message_id, to, .. } => on(Trigger::Dm).then(|| Notification {
+            title: format!("No reply from {to}"),
+            body: format!("Message {message_id} is past its reply deadline"),
+            urgent: false,
+        }),
+        // Already notified for as `NewMessage`/`NewRoomMessage`.
+        Event::MessageReceived { .. } => None,
+    }
+}
+
+/// Whether `text` contains `@username` as a whole word.
+fn mentions(text: &str, username: &str) -> bool {
+    let needle = format!("@{}", username.to_ascii_lowercase());
+    let text = text.to_ascii_lowercase();
+    text.match_indices(&needle).any(|(i, m)| {
+        !text[i + m.len()..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Show `notification` on the desktop. Returns false if no notifier could be
+/// started.
+pub fn show(notification: &Notification) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        let script = format!(
+            "display notification {} with title \"agentbook\" subtitle {}",
+            applescript_string(&notification.body),
+            applescript_string(&notification.title),
+        );
+        return spawn("osascript", &["-e", &script]);
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let urgency = if notification.urgent {
+            "critical"
+        } else {
+            "normal"
+        };
+        return spawn(
+            "notify-send",
+            &[
+                "--app-name=agentbook",
+                "--urgency",
+                urgency,
+                "--",
+                &notification.title,
+                &notification.body,
+            ],
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let script = format!(
+            "[reflection.assembly]::LoadWithPartialName('System.Windows.Forms') | Out-Null; \
+             $n = New-Object System.Windows.Forms.NotifyIcon; \
+             $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+             $n.ShowBalloonTip(5000, {}, {}, 'Info'); Start-Sleep -Seconds 6; $n.Dispose()",
+            powershell_string(&notification.title),
+            powershell_string(&notification.body),
+        );
+        return spawn(
+            "powershell",
+            &["-NoProfile", "-NonInteractive", "-Command", &script],
+        );
+    }
+
+    #[allow(unreachable_code)]
+    {
+        let _ = notification;
+        false
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(target_os = "windows")]
+fn powershell_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux", target_os = "windows"))]
+fn spawn(program: &str, args: &[&str]) -> bool {
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            std::thread::spawn(move || {
+                let _ = child.wait();
+            });
+            true
+        }
+        Err(e) => {
+            tracing::warn!(program, err = %e, "failed to start notifier");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room_message(preview: &str) -> Event {
+        Event::NewRoomMessage {
+            message_id: "m1".to_string(),
+            from: "@bob".to_string(),
+            room: "ops".to_string(),
+            message_type: MessageType::RoomMessage,
+            preview: preview.to_string(),
+        }
+    }
+
+    #[test]
+    fn triggers_parse() {
+        assert_eq!("mention".parse::<Trigger>(), Ok(Trigger::Mention));
+        assert!("task_done".parse::<Trigger>().is_err());
+    }
+
+    #[test]
+    fn dms_notify_and_feed_posts_are_opt_in() {
+        let dm = Event::NewMessage {
+            message_id: "m1".to_string(),
+            from: "@alice".to_string(),
+            message_type: MessageType::DmText,
+            preview: "can you review?".to_string(),
+        };
+        let n = notification_for(&dm, Trigger::DEFAULT, None).unwrap();
+        assert_eq!(n.title, "DM from @alice");
+        assert!(n.urgent);
+
+        let post = Event::NewMessage {
+            message_id: "m2".to_string(),
+            from: "@alice".to_string(),
+            message_type: MessageType::FeedPost,
+            preview: "shipped".to_string(),
+        };
+        assert!(notification_for(&post, Trigger::DEFAULT, None).is_none());
+        assert!(notification_for(&post, &[Trigger::Feed], None).is_some());
+    }
+
+    #[test]
+    fn room_messages_notify_on_mentions() {
+        let triggers = Trigger::DEFAULT;
+        assert!(notification_for(&room_message("hi all"), triggers, Some("carol")).is_none());
+        assert!(
+            notification_for(&room_message("@carolyn ping"), triggers, Some("carol")).is_none()
+        );
+        let n = notification_for(
+            &room_message("@Carol, blocked on you"),
+            triggers,
+            Some("carol"),
+        )
+        .unwrap();
+        assert_eq!(n.title, "#ops: @bob");
+        assert!(n.urgent);
+
+        assert!(notification_for(&room_message("hi all"), &[Trigger::Room], None).is_some());
+    }
+}
@@ -0,0 +1,70 @@
+use agentbook::client::{NodeClient, default_socket_path};
+use agentbook::protocol::{IdentityInfo, Request, Response};
+use agentbook_notify::{Trigger, notification_for, show};
+use anyhow::Result;
+use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long to wait before reconnecting after the daemon goes away.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about = "Desktop notifications for agentbook node events"
+)]
+struct Args {
+    /// Path to the node daemon's Unix socket.
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Events to notify on: dm, feed, mention, room, follower, key.
+    #[arg(long, value_delimiter = ',', default_value = "dm,mention,follower,key")]
+    on: Vec<Trigger>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "agentbook_notify=info".into()),
+        )
+        .init();
+
+    let args = Args::parse();
+    let socket_path = args.socket.unwrap_or_else(default_socket_path);
+    loop {
+        tokio::select! {
+            result = watch(&socket_path, &args.on) => {
+                if let Err(e) = result {
+                    tracing::warn!(err = %format!("{e:#}"), "lost the node daemon; reconnecting");
+                }
+            }
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Notify on events from one daemon connection until it closes.
+async fn watch(socket_path: &Path, triggers: &[Trigger]) -> Result<()> {
+    let mut client = NodeClient::connect(socket_path).await?;
+    let identity: IdentityInfo =
+        serde_json::from_value(client.request(Request::Identity).await?.unwrap_or_default())?;
+    tracing::info!(node_id = %identity.node_id, "watching for events");
+
+    loop {
+        let envelope = client.next_response_envelope().await?;
+        let Response::Event { event } = envelope.response else {
+            continue;
+        };
+        if let Some(notification) = notification_for(&event, triggers, identity.username.as_deref())
+        {
+            tracing::debug!(title = %notification.title, "notifying");
+            show(&notification);
+        }
+    }
+}
@@ -12,6 +12,7 @@ fn main() -> Result<()> {
     let protos = vec![
         PathBuf::from("proto/agentbook_mesh.proto"),
         PathBuf::from("proto/agentbook_host.proto"),
+        PathBuf::from("proto/agentbook_node.proto"),
     ];
     let includes = vec![PathBuf::from("proto"), include];
     tonic_prost_build::configure()
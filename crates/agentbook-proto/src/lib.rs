@@ -10,3 +10,9 @@ pub mod host {
         tonic::include_proto!("agentbook.host.v1");
     }
 }
+
+pub mod node {
+    pub mod v1 {
+        tonic::include_proto!("agentbook.node.v1");
+    }
+}
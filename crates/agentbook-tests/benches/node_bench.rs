@@ -0,0 +1,118 @@
+//! Node daemon benchmark: socket request latency and event fan-out.
+//!
+//! Run with `cargo bench -p agentbook-tests`. Starts an offline node in
+//! process, so the numbers cover the socket server, middleware and handler
+//! but not the relay. `agentbook bench` measures a running node instead.
+
+use agentbook::client::NodeClient;
+use agentbook::protocol::{Event, MessageType, Request, Response};
+use agentbook_tests::harness::node::TestNode;
+use std::time::{Duration, Instant};
+
+const DONE: &str = "bench-done";
+
+fn percentile(sorted: &[Duration], pct: usize) -> Duration {
+    let idx = (sorted.len() * pct).div_ceil(100).saturating_sub(1);
+    sorted[idx.min(sorted.len() - 1)]
+}
+
+/// `clients` connections each send `requests` health requests back to back.
+async fn request_latency(node: &TestNode, clients: usize, requests: usize) {
+    let start = Instant::now();
+    let mut tasks = tokio::task::JoinSet::new();
+    for _ in 0..clients {
+        let socket_path = node.socket_path.clone();
+        tasks.spawn(async move {
+            let mut client = NodeClient::connect(&socket_path).await.unwrap();
+            let mut latencies = Vec::with_capacity(requests);
+            for _ in 0..requests {
+                let sent = Instant::now();
+                client.request(Request::Health).await.unwrap();
+                latencies.push(sent.elapsed());
+            }
+            latencies
+        });
+    }
+    let mut latencies = Vec::new();
+    while let Some(run) = tasks.join_next().await {
+        latencies.extend(run.unwrap());
+    }
+    let elapsed = start.elapsed();
+    latencies.sort_unstable();
+    println!(
+        "Health requests, {clients} clients x {requests}:\n  Total: {elapsed:?}\n  Throughput: {:.0} req/sec\n  p50: {:?}  p99: {:?}  max: {:?}\n",
+        latencies.len() as f64 / elapsed.as_secs_f64(),
+        percentile(&latencies, 50),
+        percentile(&latencies, 99),
+        latencies.last().unwrap(),
+    );
+}
+
+/// Publish `events` events to `subscribers` connections as fast as possible
+/// and count what each one received. Events a slow connection falls behind
+/// on are dropped by the node, not queued.
+async fn event_fanout(node: &TestNode, subscribers: usize, events: usize) {
+    let mut tasks = tokio::task::JoinSet::new();
+    for _ in 0..subscribers {
+        let client = NodeClient::connect(&node.socket_path).await.unwrap();
+        tasks.spawn(async move {
+            let (_writer, mut reader) = client.into_split();
+            let mut received = 0usize;
+            let wait = tokio::time::timeout(Duration::from_secs(30), async {
+                while let Some(Ok(resp)) = reader.next().await {
+                    match resp.response {
+                        Response::Event {
+                            event: Event::NewFollower { node_id },
+                        } if node_id == DONE => break,
+                        Response::Event { .. } => received += 1,
+                        _ => {}
+                    }
+                }
+            });
+            let _ = wait.await;
+            received
+        });
+    }
+    // Let every connection subscribe before publishing.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let start = Instant::now();
+    for i in 0..events {
+        let _ = node.state.event_tx.send(Event::NewMessage {
+            message_id: i.to_string(),
+            from: "0xbench".to_string(),
+            message_type: MessageType::DmText,
+            preview: "x".repeat(64),
+        });
+        if i % 256 == 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+    let _ = node.state.event_tx.send(Event::NewFollower {
+        node_id: DONE.to_string(),
+    });
+
+    let mut delivered = 0;
+    while let Some(received) = tasks.join_next().await {
+        delivered += received.unwrap();
+    }
+    let elapsed = start.elapsed();
+    let expected = events * subscribers;
+    println!(
+        "Event fan-out, {events} events to {subscribers} subscribers:\n  Total: {elapsed:?}\n  Delivered: {delivered}/{expected} ({:.0} events/sec)\n  Dropped: {}\n",
+        delivered as f64 / elapsed.as_secs_f64(),
+        expected - delivered,
+    );
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let node = TestNode::spawn_offline().await.unwrap();
+        println!("=== agentbook-node Socket Benchmark ===\n");
+        request_latency(&node, 1, 5_000).await;
+        request_latency(&node, 16, 1_000).await;
+        event_fanout(&node, 1, 50_000).await;
+        event_fanout(&node, 16, 10_000).await;
+    });
+}
@@ -0,0 +1,162 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{oneshot, watch};
+
+/// Faults currently applied by a [`ChaosProxy`].
+#[derive(Default)]
+struct Faults {
+    latency_ms: AtomicU64,
+    blackhole: AtomicBool,
+    refuse: AtomicBool,
+    connections: AtomicUsize,
+}
+
+/// A TCP proxy placed between a node and a relay (or any two endpoints) that
+/// injects faults on command.
+///
+/// Point a node at [`ChaosProxy::relay_addr`] instead of the relay itself,
+/// then toggle faults from the test body. Faults apply per forwarded chunk,
+/// so they take effect on live connections immediately.
+pub struct ChaosProxy {
+    pub addr: SocketAddr,
+    faults: Arc<Faults>,
+    disconnect_tx: watch::Sender<u64>,
+    shutdown_tx: Option<oneshot::Sender<()>>,
+}
+
+impl ChaosProxy {
+    /// Start a proxy on a random local port forwarding to `upstream`.
+    pub async fn spawn(upstream: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let faults = Arc::new(Faults::default());
+        let (disconnect_tx, _) = watch::channel(0u64);
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
+
+        let accept_faults = faults.clone();
+        let accept_disconnect = disconnect_tx.clone();
+        tokio::spawn(async move {
+            loop {
+                let accepted = tokio::select! {
+                    accepted = listener.accept() => accepted,
+                    _ = &mut shutdown_rx => break,
+                };
+                let Ok((client, _)) = accepted else {
+                    continue;
+                };
+                if accept_faults.refuse.load(Ordering::Relaxed) {
+                    drop(client);
+                    continue;
+                }
+                let faults = accept_faults.clone();
+                let disconnect_rx = accept_disconnect.subscribe();
+                tokio::spawn(async move {
+                    if let Err(e) = proxy_connection(client, upstream, faults, disconnect_rx).await
+                    {
+                        tracing::debug!(err = %e, "chaos proxy connection ended");
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            addr,
+            faults,
+            disconnect_tx,
+            shutdown_tx: Some(shutdown_tx),
+        })
+    }
+
+    /// Proxy address as a string suitable for node connections.
+    pub fn relay_addr(&self) -> String {
+        format!("127.0.0.1:{}", self.addr.port())
+    }
+
+    /// Delay every forwarded chunk by `latency` (zero disables).
+    pub fn set_latency(&self, latency: Duration) {
+        self.faults
+            .latency_ms
+            .store(latency.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Silently drop all traffic in both directions while keeping
+    /// connections open, so peers only notice via timeouts.
+    pub fn set_blackhole(&self, enabled: bool) {
+        self.faults.blackhole.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Close new connections as soon as they are accepted.
+    pub fn set_refuse_connections(&self, enabled: bool) {
+        self.faults.refuse.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Drop every open connection. New connections are still accepted
+    /// unless [`ChaosProxy::set_refuse_connections`] is set.
+    pub fn disconnect_all(&self) {
+        self.disconnect_tx
+            .send_modify(|generation| *generation += 1);
+    }
+
+    /// Number of connections currently being proxied.
+    pub fn connection_count(&self) -> usize {
+        self.faults.connections.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for ChaosProxy {
+    fn drop(&mut self) {
+        self.disconnect_all();
+        if let Some(tx) = self.shutdown_tx.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+async fn proxy_connection(
+    client: TcpStream,
+    upstream: SocketAddr,
+    faults: Arc<Faults>,
+    mut disconnect_rx: watch::Receiver<u64>,
+) -> Result<()> {
+    let server = TcpStream::connect(upstream).await?;
+    let (client_read, client_write) = client.into_split();
+    let (server_read, server_write) = server.into_split();
+
+    faults.connections.fetch_add(1, Ordering::Relaxed);
+    let result = tokio::select! {
+        r = pump(client_read, server_write, &faults) => r,
+        r = pump(server_read, client_write, &faults) => r,
+        _ = disconnect_rx.changed() => Ok(()),
+    };
+    faults.connections.fetch_sub(1, Ordering::Relaxed);
+    result
+}
+
+/// Copy one direction of a connection, applying the current faults to each
+/// chunk read.
+async fn pump(
+    mut from: impl AsyncReadExt + Unpin,
+    mut to: impl AsyncWriteExt + Unpin,
+    faults: &Faults,
+) -> Result<()> {
+    let mut buf = vec![0u8; 16 * 1024];
+    loop {
+        let n = from.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        let latency = faults.latency_ms.load(Ordering::Relaxed);
+        if latency > 0 {
+            tokio::time::sleep(Duration::from_millis(latency)).await;
+        }
+        if faults.blackhole.load(Ordering::Relaxed) {
+            continue;
+        }
+        to.write_all(&buf[..n]).await?;
+    }
+}
@@ -1,5 +1,9 @@
+use super::response::ResponseExt;
 use agentbook::client::NodeClient;
-use agentbook::protocol::{InboxEntry, Request, Response, RoomInfo};
+use agentbook::protocol::{
+    Attachment, BroadcastDelivery, DaemonLogEntry, Event, InboxEntry, KeyRotationInfo, PingResult,
+    RelayUsage, Request, Response, RoomInfo,
+};
 use anyhow::{Result, bail};
 use std::path::Path;
 
@@ -53,23 +57,128 @@ impl TestClient {
         Ok(())
     }
 
+    /// Rotate the node key.
+    pub async fn rotate_key(&mut self, grace_ms: Option<u64>) -> Result<KeyRotationInfo> {
+        let data = self.inner.request(Request::RotateKey { grace_ms }).await?;
+        data.path("")
+    }
+
+    /// Ping a node, waiting up to `timeout_ms` for the pong.
+    pub async fn ping(&mut self, to: &str, timeout_ms: u64) -> Result<PingResult> {
+        let data = self
+            .inner
+            .request(Request::Ping {
+                to: to.to_string(),
+                timeout_ms: Some(timeout_ms),
+            })
+            .await?;
+        data.path("")
+    }
+
+    /// Each relay's usage report for this node.
+    pub async fn relay_usage(&mut self) -> Result<Vec<RelayUsage>> {
+        let data = self
+            .inner
+            .request(Request::RelayUsage { since_ms: None })
+            .await?;
+        data.path("")
+    }
+
+    /// Daemon log entries after `after_seq`.
+    pub async fn daemon_log(&mut self, after_seq: Option<u64>) -> Result<Vec<DaemonLogEntry>> {
+        let data = self
+            .inner
+            .request(Request::DaemonLog {
+                after_seq,
+                limit: None,
+            })
+            .await?;
+        data.path("")
+    }
+
+    /// Create an invite link, returning the encoded token.
+    pub async fn invite_create(&mut self, max_uses: Option<u32>) -> Result<String> {
+        let data = self
+            .inner
+            .request(Request::InviteCreate {
+                ttl_ms: None,
+                max_uses,
+                scopes: vec![],
+            })
+            .await?;
+        data.path("token")
+    }
+
+    /// Accept an invite token, following the inviter.
+    pub async fn invite_accept(&mut self, token: &str) -> Result<()> {
+        self.inner
+            .request(Request::InviteAccept {
+                token: token.to_string(),
+            })
+            .await?;
+        Ok(())
+    }
+
     /// Send a DM.
     pub async fn send_dm(&mut self, to: &str, body: &str) -> Result<()> {
         self.inner
             .request(Request::SendDm {
                 to: to.to_string(),
                 body: body.to_string(),
+                attachments: Vec::new(),
+                in_reply_to: None,
+                reply_within_secs: None,
             })
             .await?;
         Ok(())
     }
 
+    /// Send a DM with attachments.
+    pub async fn send_dm_with_attachments(
+        &mut self,
+        to: &str,
+        body: &str,
+        attachments: Vec<Attachment>,
+    ) -> Result<()> {
+        self.inner
+            .request(Request::SendDm {
+                to: to.to_string(),
+                body: body.to_string(),
+                attachments,
+                in_reply_to: None,
+                reply_within_secs: None,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// DM every followed node (or only mutual follows), returning each
+    /// recipient's delivery.
+    pub async fn broadcast_dm(
+        &mut self,
+        body: &str,
+        mutual_only: bool,
+    ) -> Result<Vec<BroadcastDelivery>> {
+        let data = self
+            .inner
+            .request(Request::BroadcastDm {
+                body: body.to_string(),
+                mutual_only,
+                group: None,
+            })
+            .await?;
+        data.path("")
+    }
+
     /// Send a DM, returning the raw response (including errors).
     pub async fn try_send_dm(&mut self, to: &str, body: &str) -> Result<Response> {
         self.inner
             .send(Request::SendDm {
                 to: to.to_string(),
                 body: body.to_string(),
+                attachments: Vec::new(),
+                in_reply_to: None,
+                reply_within_secs: None,
             })
             .await?;
         loop {
@@ -119,6 +228,25 @@ impl TestClient {
         }
     }
 
+    /// Make this connection the inbox gateway.
+    pub async fn claim_inbox(&mut self) -> Result<()> {
+        self.inner.request(Request::ClaimInbox).await?;
+        Ok(())
+    }
+
+    /// Wait for the next message pushed to a claimed inbox, skipping other
+    /// events.
+    pub async fn next_pushed_message(&mut self) -> Result<InboxEntry> {
+        loop {
+            if let Response::Event {
+                event: Event::MessageReceived { message },
+            } = self.inner.next_response().await?
+            {
+                return Ok(*message);
+            }
+        }
+    }
+
     /// Register a username on the relay.
     pub async fn register_username(&mut self, name: &str) -> Result<()> {
         self.inner
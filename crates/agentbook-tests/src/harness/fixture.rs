@@ -0,0 +1,114 @@
+use super::client::TestClient;
+use super::node::TestNode;
+use super::poll_inbox_until;
+use super::relay::TestRelay;
+use agentbook::protocol::InboxEntry;
+use anyhow::{Result, bail};
+use std::time::Duration;
+
+/// How long fixture helpers wait for relay round-trips before giving up.
+const WAIT: Duration = Duration::from_secs(5);
+
+/// A relay plus `N` nodes connected to it, each with an open client.
+///
+/// Nodes are addressed by index in the order they were spawned. The fields
+/// are public so tests can drop down to the raw harness for anything the
+/// helpers do not cover.
+pub struct MeshFixture {
+    pub relay: TestRelay,
+    pub nodes: Vec<TestNode>,
+    pub clients: Vec<TestClient>,
+}
+
+impl MeshFixture {
+    /// Spawn a relay and `n` nodes connected to it.
+    pub async fn spawn(n: usize) -> Result<Self> {
+        let relay = TestRelay::spawn().await?;
+        let mut fixture = Self {
+            relay,
+            nodes: Vec::with_capacity(n),
+            clients: Vec::with_capacity(n),
+        };
+        for _ in 0..n {
+            let addr = fixture.relay.relay_addr();
+            fixture.add_node(&addr).await?;
+        }
+        Ok(fixture)
+    }
+
+    /// Spawn another node connected to `relay_addr` (e.g. a
+    /// [`super::chaos::ChaosProxy`] in front of the fixture relay) and
+    /// return its index.
+    pub async fn add_node(&mut self, relay_addr: &str) -> Result<usize> {
+        let node = TestNode::spawn(relay_addr).await?;
+        self.clients
+            .push(TestClient::connect(&node.socket_path).await?);
+        self.nodes.push(node);
+        Ok(self.nodes.len() - 1)
+    }
+
+    /// Node at index `i`.
+    pub fn node(&self, i: usize) -> &TestNode {
+        &self.nodes[i]
+    }
+
+    /// Client for the node at index `i`.
+    pub fn client(&mut self, i: usize) -> &mut TestClient {
+        &mut self.clients[i]
+    }
+
+    /// Node id of the node at index `i`.
+    pub fn node_id(&self, i: usize) -> &str {
+        &self.nodes[i].node_id
+    }
+
+    /// Make `a` and `b` mutual follows via an invite issued by `a`, waiting
+    /// until both follow stores reflect it.
+    pub async fn befriend(&mut self, a: usize, b: usize) -> Result<()> {
+        let token = self.clients[a].invite_create(Some(1)).await?;
+        self.clients[b].invite_accept(&token).await?;
+
+        let a_id = self.nodes[a].node_id.clone();
+        let b_id = self.nodes[b].node_id.clone();
+        let deadline = tokio::time::Instant::now() + WAIT;
+        loop {
+            let a_follows = self.nodes[a]
+                .state
+                .follow_store
+                .lock()
+                .await
+                .is_following(&b_id);
+            let b_follows = self.nodes[b]
+                .state
+                .follow_store
+                .lock()
+                .await
+                .is_following(&a_id);
+            if a_follows && b_follows {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                bail!("nodes {a} and {b} did not become mutual follows");
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Send a DM from `a` to `b` and wait for it to land in `b`'s inbox.
+    /// Returns the delivered entry.
+    pub async fn send_and_wait(&mut self, a: usize, b: usize, body: &str) -> Result<InboxEntry> {
+        let to = self.nodes[b].node_id.clone();
+        let from = self.nodes[a].node_id.clone();
+        let seen = self.clients[b].inbox().await?.len();
+        self.clients[a].send_dm(&to, body).await?;
+
+        let entries = poll_inbox_until(&mut self.clients[b], seen + 1, WAIT).await;
+        match entries
+            .into_iter()
+            .find(|m| m.body == body && m.from_node_id == from)
+        {
+            Some(entry) => Ok(entry),
+            None => bail!("node {b} never received {body:?} from node {a}"),
+        }
+    }
+}
@@ -1,6 +1,9 @@
+pub mod chaos;
 pub mod client;
+pub mod fixture;
 pub mod node;
 pub mod relay;
+pub mod response;
 
 use agentbook::protocol::{InboxEntry, MessageType, Response};
 use std::time::Duration;
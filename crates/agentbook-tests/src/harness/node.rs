@@ -1,8 +1,9 @@
 use agentbook_crypto::crypto::random_key_material;
+use agentbook_mesh::at_rest::StateCipher;
 use agentbook_mesh::follow::FollowStore;
 use agentbook_mesh::identity::NodeIdentity;
 use agentbook_mesh::inbox::NodeInbox;
-use agentbook_mesh::transport::MeshTransport;
+use agentbook_mesh::transport::{MeshTransport, RelaySecurity};
 use agentbook_node::handler::{NodeState, WalletConfig};
 use agentbook_node::socket;
 use agentbook_wallet::spending_limit::SpendingLimitConfig;
@@ -39,9 +40,11 @@ impl TestNode {
         let node_id = identity.node_id.clone();
         let public_key_b64 = identity.public_key_b64.clone();
 
-        let follow_store =
-            FollowStore::load(state_dir.path()).context("failed to load follow store")?;
-        let inbox = NodeInbox::load(state_dir.path()).context("failed to load inbox")?;
+        let cipher = StateCipher::new(&kek);
+        let follow_store = FollowStore::load_encrypted(state_dir.path(), cipher.clone())
+            .context("failed to load follow store")?;
+        let inbox =
+            NodeInbox::load_encrypted(state_dir.path(), cipher).context("failed to load inbox")?;
 
         let relay_hosts = vec![relay_addr.to_string()];
 
@@ -49,11 +52,15 @@ impl TestNode {
         let sig = identity
             .sign(identity.node_id.as_bytes())
             .context("failed to sign for relay registration")?;
-        let transport = MeshTransport::new(
+        let transport = MeshTransport::with_security(
             relay_hosts.clone(),
             identity.node_id.clone(),
             identity.public_key_b64.clone(),
             sig,
+            RelaySecurity {
+                signing_key: Some(identity.secret_key().clone()),
+                ..RelaySecurity::default()
+            },
         );
 
         let wallet_config = WalletConfig {
@@ -82,6 +89,9 @@ impl TestNode {
                 agentbook_node::handler::process_inbound(&state_for_relay, envelope).await;
             }
         });
+        tokio::spawn(agentbook_node::handler::outbox::outbox_retry_loop(
+            state.clone(),
+        ));
 
         // Spawn socket server with shutdown
         let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
@@ -136,9 +146,11 @@ impl TestNode {
         let node_id = identity.node_id.clone();
         let public_key_b64 = identity.public_key_b64.clone();
 
-        let follow_store =
-            FollowStore::load(state_dir.path()).context("failed to load follow store")?;
-        let inbox = NodeInbox::load(state_dir.path()).context("failed to load inbox")?;
+        let cipher = StateCipher::new(&kek);
+        let follow_store = FollowStore::load_encrypted(state_dir.path(), cipher.clone())
+            .context("failed to load follow store")?;
+        let inbox =
+            NodeInbox::load_encrypted(state_dir.path(), cipher).context("failed to load inbox")?;
 
         let wallet_config = WalletConfig {
             rpc_url: "https://mainnet.base.org".to_string(),
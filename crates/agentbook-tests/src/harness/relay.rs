@@ -1,9 +1,14 @@
-use agentbook_host::service::spawn_relay;
+use agentbook_host::federation::Federation;
+use agentbook_host::service::{spawn_federated_relay, spawn_relay};
 use anyhow::Result;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tempfile::TempDir;
 use tokio::sync::oneshot;
 
+/// Federation token shared by every federated test relay.
+const FEDERATION_TOKEN: &str = "test-federation-token";
+
 /// A test relay host running on a random port with a temp data directory.
 pub struct TestRelay {
     pub addr: SocketAddr,
@@ -23,6 +28,24 @@ impl TestRelay {
         })
     }
 
+    /// Spawn a relay federated with the given peer relays. An empty peer list
+    /// still accepts forwards from other hosts.
+    pub async fn spawn_federated(peers: &[&TestRelay]) -> Result<Self> {
+        let data_dir = TempDir::new()?;
+        let peers = peers
+            .iter()
+            .map(|p| format!("http://{}", p.relay_addr()))
+            .collect();
+        let federation = Arc::new(Federation::new(peers, FEDERATION_TOKEN.to_string()));
+        let (addr, shutdown_tx) =
+            spawn_federated_relay(Some(data_dir.path()), Some(federation)).await?;
+        Ok(Self {
+            addr,
+            shutdown_tx: Some(shutdown_tx),
+            _data_dir: data_dir,
+        })
+    }
+
     /// Get the relay address as a string suitable for node connections.
     pub fn relay_addr(&self) -> String {
         format!("127.0.0.1:{}", self.addr.port())
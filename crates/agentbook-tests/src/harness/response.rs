@@ -0,0 +1,128 @@
+use agentbook::protocol::{FollowInfo, IdentityInfo, InboxEntry, Response};
+use anyhow::{Context, Result, bail};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Typed extraction from node responses, with errors that say what was
+/// expected and what the payload actually looked like.
+pub trait ResponseExt {
+    /// The JSON payload to extract from.
+    fn payload(&self) -> Result<&Value>;
+
+    /// Deserialize the value at a dotted `path` (e.g. `"wallet.address"`,
+    /// `"entries.0.body"`). An empty path selects the whole payload.
+    fn path<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let mut value = self.payload()?;
+        for segment in path.split('.').filter(|s| !s.is_empty()) {
+            let next = match value {
+                Value::Object(map) => map.get(segment),
+                Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+                _ => None,
+            };
+            value = match next {
+                Some(v) => v,
+                None => bail!(
+                    "no `{segment}` at `{path}` in response: {}",
+                    truncate(value)
+                ),
+            };
+        }
+        serde_json::from_value(value.clone()).with_context(|| {
+            format!(
+                "`{path}` has unexpected shape for {}: {}",
+                std::any::type_name::<T>(),
+                truncate(value)
+            )
+        })
+    }
+
+    /// Payload of an `Identity` response.
+    fn identity_info(&self) -> Result<IdentityInfo> {
+        self.path("")
+    }
+
+    /// Payload of a `Following`/`Followers` response.
+    fn follow_list(&self) -> Result<Vec<FollowInfo>> {
+        self.path("")
+    }
+
+    /// Payload of an `Inbox`/`RoomInbox` response.
+    fn inbox_entries(&self) -> Result<Vec<InboxEntry>> {
+        self.path("")
+    }
+}
+
+impl ResponseExt for Value {
+    fn payload(&self) -> Result<&Value> {
+        Ok(self)
+    }
+}
+
+impl ResponseExt for Option<Value> {
+    fn payload(&self) -> Result<&Value> {
+        self.as_ref().context("response carried no data")
+    }
+}
+
+impl ResponseExt for Response {
+    fn payload(&self) -> Result<&Value> {
+        match self {
+            Response::Ok { data: Some(data) } => Ok(data),
+            Response::Ok { data: None } => bail!("response carried no data"),
+            Response::Error { code, message } => bail!("expected Ok, got Error({code}): {message}"),
+            other => bail!("expected Ok, got {other:?}"),
+        }
+    }
+}
+
+/// Render a value for an error message without dumping huge payloads.
+fn truncate(value: &Value) -> String {
+    const MAX: usize = 200;
+    let s = value.to_string();
+    match s.char_indices().nth(MAX) {
+        Some((i, _)) => format!("{}…", &s[..i]),
+        None => s,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn path_walks_objects_and_arrays() {
+        let v = json!({"a": {"b": [{"c": "deep"}]}, "n": 3});
+        assert_eq!(v.path::<String>("a.b.0.c").unwrap(), "deep");
+        assert_eq!(v.path::<u64>("n").unwrap(), 3);
+    }
+
+    #[test]
+    fn path_errors_name_the_missing_segment() {
+        let v = json!({"a": {"b": 1}});
+        let err = v.path::<u64>("a.x").unwrap_err().to_string();
+        assert!(err.contains("no `x` at `a.x`"), "{err}");
+
+        let err = v.path::<String>("a.b").unwrap_err().to_string();
+        assert!(err.contains("unexpected shape"), "{err}");
+    }
+
+    #[test]
+    fn error_responses_surface_code() {
+        let resp = Response::Error {
+            code: "not_found".into(),
+            message: "nope".into(),
+        };
+        let err = resp.path::<String>("x").unwrap_err().to_string();
+        assert!(err.contains("not_found"), "{err}");
+    }
+
+    #[test]
+    fn typed_identity_extraction() {
+        let resp = Response::Ok {
+            data: Some(json!({"node_id": "0xabc", "public_key_b64": "pk", "username": null})),
+        };
+        assert_eq!(resp.identity_info().unwrap().node_id, "0xabc");
+        assert!(resp.follow_list().is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use agentbook_tests::harness::chaos::ChaosProxy;
+use agentbook_tests::harness::fixture::MeshFixture;
+use std::time::{Duration, Instant};
+
+/// Spawn a fixture with node 0 on the relay directly and node 1 behind a
+/// chaos proxy, already befriended.
+async fn proxied_pair() -> (MeshFixture, ChaosProxy) {
+    let mut mesh = MeshFixture::spawn(1).await.unwrap();
+    let proxy = ChaosProxy::spawn(mesh.relay.addr).await.unwrap();
+    let proxied = mesh.add_node(&proxy.relay_addr()).await.unwrap();
+    mesh.befriend(0, proxied).await.unwrap();
+    (mesh, proxy)
+}
+
+async fn wait_connected(mesh: &MeshFixture, i: usize, connected: bool) {
+    let transport = mesh.node(i).state.transport.as_ref().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while transport.is_connected() != connected {
+        assert!(
+            Instant::now() < deadline,
+            "node {i} never became connected={connected}"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+async fn dm_survives_injected_latency() {
+    let (mut mesh, proxy) = proxied_pair().await;
+    proxy.set_latency(Duration::from_millis(150));
+
+    let started = Instant::now();
+    mesh.send_and_wait(0, 1, "slow hello").await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(150));
+
+    mesh.send_and_wait(1, 0, "slow reply").await.unwrap();
+}
+
+#[tokio::test]
+async fn queued_dm_delivered_after_reconnect() {
+    let (mut mesh, proxy) = proxied_pair().await;
+    assert!(proxy.connection_count() >= 1);
+
+    proxy.set_refuse_connections(true);
+    proxy.disconnect_all();
+    wait_connected(&mesh, 1, false).await;
+
+    // Relay is unreachable, so the DM lands in node 1's outbox.
+    let to = mesh.node_id(0).to_string();
+    mesh.client(1).send_dm(&to, "queued hello").await.unwrap();
+    assert_eq!(mesh.node(1).state.outbox.lock().await.list().len(), 1);
+
+    proxy.set_refuse_connections(false);
+    wait_connected(&mesh, 1, true).await;
+
+    let inbox =
+        agentbook_tests::harness::poll_inbox_until(mesh.client(0), 1, Duration::from_secs(10))
+            .await;
+    assert!(inbox.iter().any(|m| m.body == "queued hello"));
+}
+
+#[tokio::test]
+async fn dm_to_disconnected_peer_stays_queued() {
+    let (mut mesh, proxy) = proxied_pair().await;
+
+    proxy.set_refuse_connections(true);
+    proxy.disconnect_all();
+    wait_connected(&mesh, 1, false).await;
+    // Let the relay notice node 1's session is gone.
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    // Node 0 is still connected, but the relay answers NOT_FOUND, so the DM
+    // must stay in node 0's outbox rather than count as sent.
+    let to = mesh.node_id(1).to_string();
+    mesh.client(0).send_dm(&to, "are you there?").await.unwrap();
+    let queued = mesh.node(0).state.outbox.lock().await.list().to_vec();
+    assert_eq!(queued.len(), 1);
+    let last_error = queued[0].last_error.as_deref().unwrap_or_default();
+    assert!(last_error.contains("NOT_FOUND"), "got: {last_error}");
+
+    proxy.set_refuse_connections(false);
+    wait_connected(&mesh, 1, true).await;
+
+    let inbox =
+        agentbook_tests::harness::poll_inbox_until(mesh.client(1), 1, Duration::from_secs(15))
+            .await;
+    assert!(inbox.iter().any(|m| m.body == "are you there?"));
+    assert!(mesh.node(0).state.outbox.lock().await.is_empty());
+}
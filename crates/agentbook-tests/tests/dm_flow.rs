@@ -1,3 +1,4 @@
+use agentbook::protocol::{Attachment, DaemonLogKind};
 use agentbook_tests::harness::{
     client::TestClient, node::TestNode, poll_inbox_until, relay::TestRelay,
 };
@@ -47,6 +48,50 @@ async fn dm_round_trip_through_relay() {
     );
 }
 
+#[tokio::test]
+async fn dm_attachments_arrive_with_the_body() {
+    let relay = TestRelay::spawn().await.unwrap();
+    let alice = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+    let bob = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+
+    let mut alice_client = TestClient::connect(&alice.socket_path).await.unwrap();
+    let mut bob_client = TestClient::connect(&bob.socket_path).await.unwrap();
+
+    alice_client.register_username("alice").await.unwrap();
+    bob_client.register_username("bob").await.unwrap();
+    alice_client.follow("@bob").await.unwrap();
+    bob_client.follow("@alice").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let report = Attachment {
+        name: "report.json".to_string(),
+        mime: "application/json".to_string(),
+        data_b64: "eyJvayI6dHJ1ZX0=".to_string(),
+    };
+    alice_client
+        .send_dm_with_attachments("@bob", "results attached", vec![report.clone()])
+        .await
+        .unwrap();
+
+    let bob_inbox = poll_inbox_until(&mut bob_client, 1, Duration::from_secs(3)).await;
+    assert_eq!(bob_inbox.len(), 1);
+    assert_eq!(bob_inbox[0].body, "results attached");
+    assert_eq!(bob_inbox[0].attachments, [report]);
+
+    // Path-like names are refused before anything is sent.
+    let escape = Attachment {
+        name: "../.bashrc".to_string(),
+        mime: "text/plain".to_string(),
+        data_b64: String::new(),
+    };
+    assert!(
+        alice_client
+            .send_dm_with_attachments("@bob", "nope", vec![escape])
+            .await
+            .is_err()
+    );
+}
+
 #[tokio::test]
 async fn dm_bidirectional() {
     let relay = TestRelay::spawn().await.unwrap();
@@ -74,6 +119,187 @@ async fn dm_bidirectional() {
     assert!(alice_inbox.iter().any(|m| m.body == "hi alice"));
 }
 
+#[tokio::test]
+async fn broadcast_dm_reaches_each_followed_node() {
+    let relay = TestRelay::spawn().await.unwrap();
+    let alice = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+    let bob = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+    let carol = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+    let dave = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+
+    let mut alice_client = TestClient::connect(&alice.socket_path).await.unwrap();
+    let mut bob_client = TestClient::connect(&bob.socket_path).await.unwrap();
+    let mut carol_client = TestClient::connect(&carol.socket_path).await.unwrap();
+    let mut dave_client = TestClient::connect(&dave.socket_path).await.unwrap();
+
+    alice_client.register_username("alice").await.unwrap();
+    bob_client.register_username("bob").await.unwrap();
+    carol_client.register_username("carol").await.unwrap();
+    dave_client.register_username("dave").await.unwrap();
+    for peer in ["@bob", "@carol", "@dave"] {
+        alice_client.follow(peer).await.unwrap();
+    }
+    bob_client.follow("@alice").await.unwrap();
+    carol_client.follow("@alice").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Dave doesn't follow Alice back, so he is left out.
+    let deliveries = alice_client
+        .broadcast_dm("deploy at noon", true)
+        .await
+        .unwrap();
+    let mut recipients: Vec<_> = deliveries.iter().map(|d| d.node_id.clone()).collect();
+    recipients.sort();
+    let mut expected = vec![bob.node_id.clone(), carol.node_id.clone()];
+    expected.sort();
+    assert_eq!(recipients, expected);
+    assert!(
+        deliveries
+            .iter()
+            .all(|d| d.message_id.is_some() && d.error.is_none())
+    );
+
+    for client in [&mut bob_client, &mut carol_client] {
+        let inbox = poll_inbox_until(client, 1, Duration::from_secs(3)).await;
+        assert_eq!(inbox[0].body, "deploy at noon");
+        assert_eq!(inbox[0].from_node_id, alice.node_id);
+    }
+
+    let deliveries = alice_client.broadcast_dm("all hands", false).await.unwrap();
+    assert_eq!(deliveries.len(), 3);
+}
+
+#[tokio::test]
+async fn relay_reports_bytes_each_node_sent() {
+    let relay = TestRelay::spawn().await.unwrap();
+    let alice = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+    let bob = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+
+    let mut alice_client = TestClient::connect(&alice.socket_path).await.unwrap();
+    let mut bob_client = TestClient::connect(&bob.socket_path).await.unwrap();
+
+    alice_client.register_username("alice").await.unwrap();
+    bob_client.register_username("bob").await.unwrap();
+    alice_client.follow("@bob").await.unwrap();
+    bob_client.follow("@alice").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    alice_client.send_dm("@bob", "one").await.unwrap();
+    alice_client.send_dm("@bob", "two").await.unwrap();
+    poll_inbox_until(&mut bob_client, 2, Duration::from_secs(3)).await;
+
+    let reports = alice_client.relay_usage().await.unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].total_frames, 2);
+    assert!(reports[0].total_bytes > 0);
+
+    let reports = bob_client.relay_usage().await.unwrap();
+    assert_eq!(reports[0].total_frames, 0);
+}
+
+#[tokio::test]
+async fn daemon_log_records_ingress_and_delivery_decisions() {
+    let relay = TestRelay::spawn().await.unwrap();
+    let alice = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+    let bob = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+    let carol = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+
+    let mut alice_client = TestClient::connect(&alice.socket_path).await.unwrap();
+    let mut bob_client = TestClient::connect(&bob.socket_path).await.unwrap();
+    let mut carol_client = TestClient::connect(&carol.socket_path).await.unwrap();
+
+    alice_client.register_username("alice").await.unwrap();
+    bob_client.register_username("bob").await.unwrap();
+    carol_client.register_username("carol").await.unwrap();
+    alice_client.follow("@bob").await.unwrap();
+    bob_client.follow("@alice").await.unwrap();
+    // Bob doesn't follow Carol back, so her DM fails ingress.
+    carol_client.follow("@bob").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let before = bob_client.daemon_log(None).await.unwrap();
+    let after_seq = before.last().map(|e| e.seq);
+
+    alice_client.send_dm("@bob", "hi").await.unwrap();
+    poll_inbox_until(&mut bob_client, 1, Duration::from_secs(3)).await;
+    carol_client.send_dm("@bob", "hi too").await.unwrap();
+
+    let sent = alice_client.daemon_log(None).await.unwrap();
+    assert!(
+        sent.iter()
+            .any(|e| e.kind == DaemonLogKind::DeliverySent && e.message_id.is_some())
+    );
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(3);
+    let entries = loop {
+        let entries = bob_client.daemon_log(after_seq).await.unwrap();
+        if entries.len() >= 2 || tokio::time::Instant::now() >= deadline {
+            break entries;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    let kinds: Vec<_> = entries.iter().map(|e| e.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            DaemonLogKind::IngressAccepted,
+            DaemonLogKind::IngressRejected
+        ]
+    );
+    assert!(entries[1].reason.is_some());
+    assert!(entries[0].seq < entries[1].seq);
+}
+
+#[tokio::test]
+async fn claimed_inbox_gets_accepted_dms_pushed() {
+    let relay = TestRelay::spawn().await.unwrap();
+    let alice = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+    let bob = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+
+    let mut alice_client = TestClient::connect(&alice.socket_path).await.unwrap();
+    let mut bob_client = TestClient::connect(&bob.socket_path).await.unwrap();
+    let mut first_gateway = TestClient::connect(&bob.socket_path).await.unwrap();
+    let mut gateway = TestClient::connect(&bob.socket_path).await.unwrap();
+
+    alice_client.register_username("alice").await.unwrap();
+    bob_client.register_username("bob").await.unwrap();
+    alice_client.follow("@bob").await.unwrap();
+    bob_client.follow("@alice").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // The later claim takes the inbox over.
+    first_gateway.claim_inbox().await.unwrap();
+    gateway.claim_inbox().await.unwrap();
+
+    alice_client
+        .send_dm_with_attachments(
+            "@bob",
+            "deploy is done",
+            vec![Attachment {
+                name: "report.json".to_string(),
+                mime: "application/json".to_string(),
+                data_b64: "e30=".to_string(),
+            }],
+        )
+        .await
+        .unwrap();
+
+    let pushed = tokio::time::timeout(Duration::from_secs(3), gateway.next_pushed_message())
+        .await
+        .expect("no message pushed to the inbox gateway")
+        .unwrap();
+    assert_eq!(pushed.body, "deploy is done");
+    assert_eq!(pushed.from_username.as_deref(), Some("alice"));
+    assert_eq!(pushed.attachments.len(), 1);
+
+    let missed = tokio::time::timeout(
+        Duration::from_millis(300),
+        first_gateway.next_pushed_message(),
+    )
+    .await;
+    assert!(missed.is_err(), "replaced gateway still got a push");
+}
+
 #[tokio::test]
 async fn dm_round_trip_through_relay_with_bare_username() {
     let relay = TestRelay::spawn().await.unwrap();
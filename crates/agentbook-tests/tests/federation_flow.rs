@@ -0,0 +1,52 @@
+use agentbook_tests::harness::{
+    client::TestClient, node::TestNode, poll_inbox_until, relay::TestRelay, response::ResponseExt,
+};
+use std::time::Duration;
+
+#[tokio::test]
+async fn dm_across_federated_relays() {
+    // Bob's home relay accepts forwards; Alice's relay peers with it.
+    let bob_relay = TestRelay::spawn_federated(&[]).await.unwrap();
+    let alice_relay = TestRelay::spawn_federated(&[&bob_relay]).await.unwrap();
+
+    let alice = TestNode::spawn(&alice_relay.relay_addr()).await.unwrap();
+    let bob = TestNode::spawn(&bob_relay.relay_addr()).await.unwrap();
+
+    let mut alice_client = TestClient::connect(&alice.socket_path).await.unwrap();
+    let mut bob_client = TestClient::connect(&bob.socket_path).await.unwrap();
+
+    bob_client.register_username("bob").await.unwrap();
+
+    // @bob is only registered on Bob's relay; Alice resolves it via federation.
+    let result = alice_client.lookup_username("bob").await.unwrap();
+    assert_eq!(result.path::<String>("node_id").unwrap(), bob.node_id);
+
+    alice_client.follow("@bob").await.unwrap();
+    bob_client.follow(&alice.node_id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    alice_client
+        .send_dm("@bob", "hello from another relay")
+        .await
+        .unwrap();
+
+    let bob_inbox = poll_inbox_until(&mut bob_client, 1, Duration::from_secs(3)).await;
+    assert_eq!(bob_inbox.len(), 1);
+    assert_eq!(bob_inbox[0].body, "hello from another relay");
+    assert_eq!(bob_inbox[0].from_node_id, alice.node_id);
+}
+
+#[tokio::test]
+async fn unfederated_relay_does_not_resolve_remote_usernames() {
+    let bob_relay = TestRelay::spawn().await.unwrap();
+    let alice_relay = TestRelay::spawn().await.unwrap();
+
+    let alice = TestNode::spawn(&alice_relay.relay_addr()).await.unwrap();
+    let bob = TestNode::spawn(&bob_relay.relay_addr()).await.unwrap();
+
+    let mut alice_client = TestClient::connect(&alice.socket_path).await.unwrap();
+    let mut bob_client = TestClient::connect(&bob.socket_path).await.unwrap();
+
+    bob_client.register_username("bob").await.unwrap();
+    assert!(alice_client.lookup_username("bob").await.is_err());
+}
@@ -0,0 +1,24 @@
+use agentbook_tests::harness::fixture::MeshFixture;
+
+#[tokio::test]
+async fn befriend_then_dm_both_ways() {
+    let mut mesh = MeshFixture::spawn(2).await.unwrap();
+    mesh.befriend(0, 1).await.unwrap();
+
+    let entry = mesh.send_and_wait(0, 1, "hello from 0").await.unwrap();
+    assert_eq!(entry.from_node_id, mesh.node_id(0));
+
+    let entry = mesh.send_and_wait(1, 0, "hello from 1").await.unwrap();
+    assert_eq!(entry.from_node_id, mesh.node_id(1));
+}
+
+#[tokio::test]
+async fn three_node_mesh() {
+    let mut mesh = MeshFixture::spawn(3).await.unwrap();
+    mesh.befriend(0, 1).await.unwrap();
+    mesh.befriend(0, 2).await.unwrap();
+
+    mesh.send_and_wait(1, 0, "from 1").await.unwrap();
+    mesh.send_and_wait(2, 0, "from 2").await.unwrap();
+    mesh.send_and_wait(0, 2, "to 2").await.unwrap();
+}
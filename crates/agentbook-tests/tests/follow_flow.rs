@@ -1,3 +1,4 @@
+use agentbook::protocol::PathProbe;
 use agentbook_tests::harness::{client::TestClient, node::TestNode, relay::TestRelay};
 use std::time::Duration;
 
@@ -68,3 +69,27 @@ async fn block_filters_room_messages() {
         "Bob should not see messages from blocked user in room"
     );
 }
+
+#[tokio::test]
+async fn ping_is_answered_only_by_nodes_that_follow_us() {
+    let relay = TestRelay::spawn().await.unwrap();
+    let alice = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+    let bob = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+    let carol = TestNode::spawn(&relay.relay_addr()).await.unwrap();
+
+    let mut alice_client = TestClient::connect(&alice.socket_path).await.unwrap();
+    let mut bob_client = TestClient::connect(&bob.socket_path).await.unwrap();
+    let mut carol_client = TestClient::connect(&carol.socket_path).await.unwrap();
+
+    bob_client.follow(&alice.node_id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let result = alice_client.ping(&bob.node_id, 3_000).await.unwrap();
+    assert_eq!(result.node_id, bob.node_id);
+    assert!(matches!(result.relay, PathProbe::Reachable { .. }));
+    assert!(matches!(result.direct, PathProbe::Unavailable { .. }));
+
+    // Bob doesn't follow Carol, so her ping goes unanswered.
+    let err = carol_client.ping(&bob.node_id, 500).await.unwrap_err();
+    assert!(err.to_string().contains("no pong"), "{err}");
+}
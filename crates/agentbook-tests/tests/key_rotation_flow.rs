@@ -0,0 +1,38 @@
+use agentbook_tests::harness::fixture::MeshFixture;
+use std::time::Duration;
+
+#[tokio::test]
+async fn rotation_notice_moves_friend_to_new_key() {
+    let mut mesh = MeshFixture::spawn(2).await.unwrap();
+    mesh.befriend(0, 1).await.unwrap();
+    let old_id = mesh.node_id(0).to_string();
+
+    let info = mesh.client(0).rotate_key(None).await.unwrap();
+    assert_eq!(info.old_node_id, old_id);
+    assert_ne!(info.new_node_id, old_id);
+    assert_eq!(info.notified, 1);
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    loop {
+        {
+            let follow_store = mesh.node(1).state.follow_store.lock().await;
+            if follow_store.is_following(&info.new_node_id) {
+                assert!(!follow_store.is_following(&old_id));
+                assert_eq!(
+                    follow_store.get(&info.new_node_id).unwrap().public_key_b64,
+                    info.new_public_key_b64
+                );
+                break;
+            }
+        }
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "node 1 never applied the rotation"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    // The running node keeps its key until restart; a second rotation
+    // before then is refused.
+    assert!(mesh.client(0).rotate_key(None).await.is_err());
+}
@@ -0,0 +1,253 @@
+use agentbook::protocol::{
+    ClientInfo, MAX_LINE_BYTES, Negotiated, PROTOCOL_VERSION, Response, ResponseEnvelope,
+};
+use agentbook_tests::harness::node::TestNode;
+use proptest::prelude::*;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+/// Raw line-level connection to a node socket.
+struct RawConn {
+    reader: BufReader<tokio::net::unix::OwnedReadHalf>,
+    writer: tokio::net::unix::OwnedWriteHalf,
+}
+
+impl RawConn {
+    async fn connect(node: &TestNode) -> Self {
+        let (r, writer) = UnixStream::connect(&node.socket_path)
+            .await
+            .unwrap()
+            .into_split();
+        let mut conn = Self {
+            reader: BufReader::new(r),
+            writer,
+        };
+        assert!(matches!(conn.recv().await.response, Response::Hello { .. }));
+        conn
+    }
+
+    async fn send_raw(&mut self, bytes: &[u8]) {
+        self.writer.write_all(bytes).await.unwrap();
+        self.writer.write_all(b"\n").await.unwrap();
+    }
+
+    /// Next non-event response.
+    async fn recv(&mut self) -> ResponseEnvelope {
+        loop {
+            let mut line = String::new();
+            let n = self.reader.read_line(&mut line).await.unwrap();
+            assert!(n > 0, "node closed the connection");
+            let resp: ResponseEnvelope = serde_json::from_str(&line).unwrap();
+            if !matches!(resp.response, Response::Event { .. }) {
+                return resp;
+            }
+        }
+    }
+
+    async fn assert_alive(&mut self) {
+        self.send_raw(br#"{"request_id":4242,"type":"health"}"#)
+            .await;
+        let resp = self.recv().await;
+        assert_eq!(resp.request_id, Some(4242));
+        assert!(matches!(resp.response, Response::Ok { .. }));
+    }
+}
+
+fn error_code(resp: &ResponseEnvelope) -> &str {
+    match &resp.response {
+        Response::Error { code, .. } => code,
+        other => panic!("expected error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn malformed_lines_get_structured_errors() {
+    let node = TestNode::spawn_offline().await.unwrap();
+    let mut conn = RawConn::connect(&node).await;
+
+    conn.send_raw(b"not json").await;
+    assert_eq!(error_code(&conn.recv().await), "invalid_request");
+
+    conn.send_raw(br#"{"request_id":5,"type":"no_such_request"}"#)
+        .await;
+    let resp = conn.recv().await;
+    assert_eq!(error_code(&resp), "invalid_request");
+    assert_eq!(resp.request_id, Some(5));
+
+    conn.send_raw(br#"{"request_id":6,"type":"send_dm","to":"x""#)
+        .await;
+    assert_eq!(error_code(&conn.recv().await), "invalid_request");
+
+    conn.assert_alive().await;
+}
+
+#[tokio::test]
+async fn negotiation_upgrades_only_the_connection_that_asks() {
+    let node = TestNode::spawn_offline().await.unwrap();
+    let (r, writer) = UnixStream::connect(&node.socket_path)
+        .await
+        .unwrap()
+        .into_split();
+    let mut conn = RawConn {
+        reader: BufReader::new(r),
+        writer,
+    };
+    match conn.recv().await.response {
+        Response::Hello {
+            protocol_min,
+            protocol_max,
+            warnings,
+            ..
+        } => {
+            assert_eq!((protocol_min, protocol_max), (1, PROTOCOL_VERSION));
+            assert_eq!(warnings.len(), 1, "version 1 is flagged as deprecated");
+        }
+        other => panic!("expected Hello, got {other:?}"),
+    }
+
+    conn.send_raw(br#"{"request_id":1,"type":"negotiate","min":99,"max":100}"#)
+        .await;
+    assert_eq!(error_code(&conn.recv().await), "unsupported_protocol");
+
+    conn.send_raw(br#"{"request_id":2,"type":"negotiate","min":1,"max":100}"#)
+        .await;
+    let Response::Ok { data: Some(data) } = conn.recv().await.response else {
+        panic!("negotiate failed");
+    };
+    let negotiated: Negotiated = serde_json::from_value(data).unwrap();
+    assert_eq!(negotiated.protocol, PROTOCOL_VERSION);
+    assert!(negotiated.warnings.is_empty());
+
+    conn.send_raw(br#"{"request_id":3,"type":"no_such_request"}"#)
+        .await;
+    assert_eq!(error_code(&conn.recv().await), "unknown_request");
+
+    // Other connections keep version 1 semantics.
+    let mut old = RawConn::connect(&node).await;
+    old.send_raw(br#"{"request_id":3,"type":"no_such_request"}"#)
+        .await;
+    assert_eq!(error_code(&old.recv().await), "invalid_request");
+
+    // The bundled client negotiates on its own.
+    let client = agentbook::client::NodeClient::connect(&node.socket_path)
+        .await
+        .unwrap();
+    assert_eq!(client.protocol(), PROTOCOL_VERSION);
+}
+
+#[tokio::test]
+async fn admin_can_list_and_disconnect_clients() {
+    let node = TestNode::spawn_offline().await.unwrap();
+    let mut admin = RawConn::connect(&node).await;
+    let mut stuck = RawConn::connect(&node).await;
+    stuck.assert_alive().await;
+
+    admin
+        .send_raw(br#"{"request_id":1,"type":"clients"}"#)
+        .await;
+    let Response::Ok { data: Some(data) } = admin.recv().await.response else {
+        panic!("clients failed");
+    };
+    let clients: Vec<ClientInfo> = serde_json::from_value(data).unwrap();
+    assert_eq!(clients.len(), 2);
+    assert!(clients.iter().all(|c| c.transport == "socket"));
+    // Sessions are listed oldest first; `stuck` has answered one request.
+    let stuck_id = clients[1].client_id;
+    assert_eq!(clients[1].requests, 1);
+
+    let disconnect = format!(r#"{{"request_id":2,"type":"disconnect","client_id":{stuck_id}}}"#);
+    admin.send_raw(disconnect.as_bytes()).await;
+    assert!(matches!(admin.recv().await.response, Response::Ok { .. }));
+    assert_eq!(error_code(&stuck.recv().await), "disconnected");
+    let mut line = String::new();
+    assert_eq!(stuck.reader.read_line(&mut line).await.unwrap(), 0);
+
+    admin.send_raw(disconnect.as_bytes()).await;
+    assert_eq!(error_code(&admin.recv().await), "not_found");
+    admin.assert_alive().await;
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn share_token_grants_read_only_view_access() {
+    use agentbook::client::NodeClient;
+    use agentbook::protocol::{Request, ShareInfo};
+
+    let node = TestNode::spawn_offline().await.unwrap();
+    let mut owner = NodeClient::connect(&node.socket_path).await.unwrap();
+    let data = owner
+        .request(Request::Share { ttl_secs: Some(60) })
+        .await
+        .unwrap();
+    let share: ShareInfo = serde_json::from_value(data.unwrap()).unwrap();
+    assert_eq!(share.node_id, node.node_id);
+
+    let err = NodeClient::connect_view(&node.node_id, "wrong")
+        .await
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("malformed share token"), "{err}");
+    // Same owner uid, wrong secret: the node itself refuses it.
+    let (uid, _) = share.token.split_once('.').unwrap();
+    let err = NodeClient::connect_view(&node.node_id, &format!("{uid}.wrong"))
+        .await
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("share token"), "{err}");
+
+    let mut viewer = NodeClient::connect_view(&node.node_id, &share.token)
+        .await
+        .unwrap();
+    viewer.request(Request::Health).await.unwrap();
+    let err = viewer
+        .request(Request::PostFeed { body: "hi".into() })
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("view_only"), "{err}");
+
+    owner.request(Request::Unshare).await.unwrap();
+    // The viewer may get one more answer before its session sees the kick.
+    let kicked = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while viewer.request(Request::Health).await.is_ok() {}
+    });
+    assert!(kicked.await.is_ok(), "viewer still connected");
+    assert!(
+        NodeClient::connect_view(&node.node_id, &share.token)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn oversized_line_is_rejected_without_disconnect() {
+    let node = TestNode::spawn_offline().await.unwrap();
+    let mut conn = RawConn::connect(&node).await;
+
+    let body = "a".repeat(MAX_LINE_BYTES + 1);
+    let line = format!(r#"{{"type":"send_dm","to":"x","body":"{body}"}}"#);
+    conn.send_raw(line.as_bytes()).await;
+    assert_eq!(error_code(&conn.recv().await), "line_too_long");
+
+    conn.assert_alive().await;
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn arbitrary_lines_keep_the_connection_open(
+        lines in prop::collection::vec("[^\n\r]{0,256}", 1..8),
+    ) {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let node = TestNode::spawn_offline().await.unwrap();
+            let mut conn = RawConn::connect(&node).await;
+            for line in &lines {
+                conn.send_raw(line.as_bytes()).await;
+                // Every line gets exactly one response, whatever it contained.
+                let _ = conn.recv().await;
+            }
+            conn.assert_alive().await;
+        });
+    }
+}
@@ -1,4 +1,6 @@
-use agentbook_tests::harness::{client::TestClient, node::TestNode, relay::TestRelay};
+use agentbook_tests::harness::{
+    client::TestClient, node::TestNode, relay::TestRelay, response::ResponseExt,
+};
 
 #[tokio::test]
 async fn register_and_lookup() {
@@ -12,8 +14,8 @@ async fn register_and_lookup() {
 
     // Look up the username
     let result = client.lookup_username("alice").await.unwrap();
-    assert_eq!(result["node_id"].as_str().unwrap(), alice.node_id);
-    assert!(!result["public_key_b64"].as_str().unwrap().is_empty());
+    assert_eq!(result.path::<String>("node_id").unwrap(), alice.node_id);
+    assert!(!result.path::<String>("public_key_b64").unwrap().is_empty());
 }
 
 #[tokio::test]
@@ -239,6 +239,14 @@ pub struct App {
     pub terminal_waiting_input_windows: HashSet<usize>,
     /// Background prompt scan job so tmux inspection does not block the UI loop.
     pub terminal_waiting_input_scan_rx: Option<mpsc::Receiver<Result<HashSet<usize>, String>>>,
+    /// Directory that new terminal panes are recorded into (`--record`).
+    pub record_dir: Option<PathBuf>,
+    /// Whether recordings include what is typed into panes (`--record-input`).
+    pub record_input: bool,
+    /// Panes recorded so far, used to keep recording file names unique.
+    recorded_panes: usize,
+    /// Terminal type and locale new panes start with (`--term`, `--locale`).
+    pub pane_env: crate::terminal::PaneEnv,
 
     /// Joined rooms, ordered (determines tab order).
     pub rooms: Vec<String>,
@@ -333,6 +341,10 @@ impl App {
             active_terminal_window: 0,
             terminal_waiting_input_windows: HashSet::new(),
             terminal_waiting_input_scan_rx: None,
+            record_dir: None,
+            record_input: false,
+            recorded_panes: 0,
+            pane_env: crate::terminal::PaneEnv::default(),
             rooms: Vec::new(),
             room_messages: HashMap::new(),
             activity_rooms: HashMap::new(),
@@ -452,6 +464,29 @@ impl App {
                 }
             }
             Event::NewFollower { .. } => {}
+            Event::KeyRotated {
+                old_node_id,
+                new_node_id,
+            } => {
+                self.status_msg = format!(
+                    "{} rotated key to {}",
+                    truncate(&old_node_id, 16),
+                    truncate(&new_node_id, 16)
+                );
+            }
+            Event::KeyRevoked { node_id } => {
+                self.status_msg = format!("{} revoked its key", truncate(&node_id, 16));
+            }
+            Event::PeerIdle { node_id, idle_ms } => {
+                self.status_msg =
+                    format!("{} idle for {}s", truncate(&node_id, 16), idle_ms / 1000);
+            }
+            Event::PeerActive { .. } => {}
+            Event::ReplyOverdue { to, .. } => {
+                self.status_msg = format!("No reply yet from {}", truncate(&to, 16));
+            }
+            // The same message also arrives as `NewMessage`/`NewRoomMessage`.
+            Event::MessageReceived { .. } => {}
         }
         notify
     }
@@ -520,6 +555,23 @@ impl App {
         }
     }
 
+    /// Spawn a terminal pane, recording it to `record_dir` when set.
+    /// Recording failures are reported in the status line but do not stop
+    /// the pane from opening.
+    pub fn spawn_terminal(&mut self) -> Result<crate::terminal::TerminalEmulator> {
+        // Default size — will be resized on next draw.
+        let mut term = crate::terminal::TerminalEmulator::spawn(80, 24, &self.pane_env)?;
+        if let Some(dir) = &self.record_dir {
+            let path = crate::recording::recording_path(dir, self.recorded_panes);
+            self.recorded_panes += 1;
+            match term.start_recording(&path, self.record_input) {
+                Ok(()) => self.status_msg = format!("Recording to {}", path.display()),
+                Err(e) => self.status_msg = format!("Recording failed: {e}"),
+            }
+        }
+        Ok(term)
+    }
+
     /// Mutable reference to the active terminal pane, if any.
     pub fn active_terminal_mut(&mut self) -> Option<&mut crate::terminal::TerminalEmulator> {
         self.terminals.get_mut(self.active_terminal)
@@ -561,7 +613,17 @@ impl App {
                     .retain(|idx| self.terminal_window_indices.contains(idx));
             }
             Ok(_) => {
-                self.terminal_window_tabs = vec!["1 shell".to_string()];
+                // The local shell names itself through OSC 0/2 and 7.
+                let label = match self.active_terminal().map(|t| (t.title(), t.cwd())) {
+                    Some((Some(title), cwd)) => terminal_tab_label(title, cwd),
+                    Some((None, Some(cwd))) => std::path::Path::new(cwd)
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .unwrap_or(cwd)
+                        .to_string(),
+                    _ => "shell".to_string(),
+                };
+                self.terminal_window_tabs = vec![format!("1 {label}")];
                 self.terminal_window_indices = vec![0];
                 self.active_terminal_window = 0;
                 self.terminal_waiting_input_windows.retain(|idx| *idx == 0);
@@ -683,6 +745,9 @@ mod tests {
             acked: false,
             message_type: msg_type,
             room: None,
+            attachments: Vec::new(),
+            in_reply_to: None,
+            thread_id: None,
         }
     }
 
@@ -43,6 +43,9 @@ struct TabSnapshot {
     name: String,
     active: bool,
     text: String,
+    /// Latest structured progress report (OSC 9;9) from the tab's task.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -295,6 +298,7 @@ fn collect_tab_snapshots(source: AutomationSnapshotSource) -> Result<Vec<TabSnap
             name: tab.name,
             active: tab.active,
             text: tab.text,
+            progress: tab.progress,
         })
         .collect::<Vec<_>>();
     tabs.sort_by_key(|t| (!t.active, t.index));
@@ -1162,6 +1166,7 @@ mod tests {
             name: "work".to_string(),
             active: true,
             text: "Build done.\nPress Enter to continue".to_string(),
+            progress: None,
         }];
         let decision = decide_rules_heartbeat(&tabs);
         assert_eq!(decision.target_window, Some(1));
@@ -1182,18 +1187,21 @@ mod tests {
                 name: "main".to_string(),
                 active: true,
                 text: "Press Enter to continue".to_string(),
+                progress: None,
             },
             TabSnapshot {
                 index: 2,
                 name: "agent".to_string(),
                 active: false,
                 text: "Continue? (y/n)".to_string(),
+                progress: None,
             },
             TabSnapshot {
                 index: 3,
                 name: "logs".to_string(),
                 active: false,
                 text: "all good".to_string(),
+                progress: None,
             },
         ];
         let waiting = waiting_input_window_indices(&tabs);
@@ -1294,6 +1302,7 @@ mod tests {
             name: "agent".to_string(),
             active: true,
             text: "Delete all files and continue? (y/n)".to_string(),
+            progress: None,
         }];
         let decision = decide_rules_heartbeat(&tabs);
         // Should NOT auto-send y\n because "delete" is destructive.
@@ -1324,6 +1333,7 @@ mod tests {
             name: "agent".to_string(),
             active: true,
             text: "Continue with build? (y/n)".to_string(),
+            progress: None,
         }];
         let decision = decide_rules_heartbeat(&tabs);
         // Should auto-send y\n because no destructive keyword.
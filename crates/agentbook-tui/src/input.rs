@@ -191,6 +191,34 @@ pub async fn handle_key(
     }
 }
 
+/// Handle text pasted into the TUI (bracketed paste from the outer
+/// terminal). In the Terminal tab it goes to the shell in one piece; the
+/// one-line text inputs get it with line breaks turned into spaces.
+pub fn handle_paste(app: &mut App, text: &str) {
+    if app.quit_confirm || app.prefix_mode || app.rename_input.is_some() {
+        return;
+    }
+    let one_line = || text.replace("\r\n", " ").replace(['\r', '\n'], " ");
+    if app.tab != Tab::Terminal {
+        app.input.push_str(&one_line());
+        return;
+    }
+    if app.auto_agent.enabled && app.auto_agent.chat_focus {
+        app.auto_agent.chat_input.push_str(&one_line());
+        return;
+    }
+    if app.scroll_mode {
+        app.scroll_mode = false;
+        app.status_msg.clear();
+        request_full_redraw(app);
+    }
+    if let Some(term) = app.active_terminal_mut()
+        && let Err(e) = term.paste(text)
+    {
+        app.status_msg = format!("Paste failed: {e}");
+    }
+}
+
 /// Handle slash commands. Returns `Some(PendingResponse)` if a request was sent.
 async fn handle_slash_command(
     app: &mut App,
@@ -583,6 +611,9 @@ async fn send_message(
             Request::SendDm {
                 to,
                 body: input.to_string(),
+                attachments: Vec::new(),
+                in_reply_to: None,
+                reply_within_secs: None,
             }
         }
         Tab::Terminal => return None,
@@ -607,8 +638,7 @@ fn ensure_terminal(app: &mut App) {
     if !app.terminals.is_empty() {
         return;
     }
-    // Default size — will be resized on next draw.
-    match crate::terminal::TerminalEmulator::spawn(80, 24) {
+    match app.spawn_terminal() {
         Ok(term) => {
             app.terminals.push(term);
             app.active_terminal = 0;
@@ -672,7 +702,7 @@ fn split_terminal(app: &mut App, direction: TerminalSplit) {
         app.status_msg = format!("Pane limit reached ({MAX_TERMINAL_PANES})");
         return;
     }
-    match crate::terminal::TerminalEmulator::spawn(80, 24) {
+    match app.spawn_terminal() {
         Ok(term) => {
             app.terminals.push(term);
             app.active_terminal = app.terminals.len().saturating_sub(1);
@@ -1895,6 +1925,19 @@ mod tests {
         panic!("missing modal target: {target:?}");
     }
 
+    #[test]
+    fn pastes_into_chat_input_are_one_line() {
+        let mut app = App::new("me".to_string());
+        app.tab = Tab::Feed;
+        app.input = "note: ".to_string();
+        handle_paste(&mut app, "first\r\nsecond\nthird");
+        assert_eq!(app.input, "note: first second third");
+
+        app.quit_confirm = true;
+        handle_paste(&mut app, "ignored");
+        assert_eq!(app.input, "note: first second third");
+    }
+
     #[test]
     fn click_confirm_quit_modal_sets_should_quit() {
         let viewport = Rect::new(0, 0, 120, 40);
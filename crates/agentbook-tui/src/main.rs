@@ -1,6 +1,8 @@
 mod app;
 mod automation;
 mod input;
+mod reconnect;
+mod recording;
 mod sound;
 mod terminal;
 mod ui;
@@ -12,7 +14,7 @@ use agentbook::protocol::{
 };
 use anyhow::{Context, Result};
 use app::{App, NotificationCue, PendingRequest, Tab, TerminalSplit};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use crossterm::event::{self, Event, MouseButton, MouseEventKind};
 use crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
@@ -20,11 +22,15 @@ use crossterm::terminal::{
 use ratatui::Terminal;
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::Rect;
+use reconnect::Backoff;
 use std::collections::{HashMap, HashSet};
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+/// How long one reconnect attempt may take, handshake included.
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Map crossterm mouse button to our terminal mouse button type.
 fn crossterm_to_terminal_button(button: MouseButton) -> terminal::MouseButton {
     match button {
@@ -40,11 +46,65 @@ struct Args {
     /// Path to the node daemon's Unix socket.
     #[arg(long)]
     socket: Option<PathBuf>,
+    /// Record every terminal pane to an asciicast v2 file in this directory.
+    #[arg(long, value_name = "DIR")]
+    record: Option<PathBuf>,
+    /// Also record what is typed or pasted into each pane, for auditing.
+    #[arg(long, requires = "record")]
+    record_input: bool,
+    /// TERM for shells in terminal panes [default: xterm-256color].
+    #[arg(long)]
+    term: Option<String>,
+    /// Locale (LANG and LC_CTYPE) for shells in terminal panes [default:
+    /// yours if it is UTF-8, else C.UTF-8].
+    #[arg(long)]
+    locale: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Replay a recorded terminal session (asciicast v2) in this terminal.
+    Play {
+        file: PathBuf,
+        /// Playback speed multiplier.
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+        /// Cap pauses between output at this many seconds.
+        #[arg(long)]
+        idle_limit: Option<f64>,
+    },
+    /// List the input in a recording made with `--record-input`, with the
+    /// time each was sent.
+    Inputs { file: PathBuf },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
+    match args.command {
+        Some(Command::Play {
+            file,
+            speed,
+            idle_limit,
+        }) => {
+            let cast = recording::Cast::load(&file)?;
+            eprintln!(
+                "Playing {} (recorded at {}x{})",
+                file.display(),
+                cast.header.width,
+                cast.header.height
+            );
+            let idle_limit = idle_limit.map(Duration::from_secs_f64);
+            return recording::play(&cast, &mut io::stdout(), speed, idle_limit);
+        }
+        Some(Command::Inputs { file }) => {
+            let cast = recording::Cast::load(&file)?;
+            return recording::print_inputs(&cast, &mut io::stdout());
+        }
+        None => {}
+    }
     let socket_path = args.socket.unwrap_or_else(default_socket_path);
 
     // Pre-flight: check if setup has been run
@@ -71,9 +131,17 @@ async fn main() -> Result<()> {
     if let Err(e) = app.load_preferences() {
         eprintln!("Warning: failed to load TUI preferences: {e}");
     }
+    app.record_dir = args.record;
+    app.record_input = args.record_input;
+    if let Some(term) = args.term {
+        app.pane_env.term = term;
+    }
+    if let Some(locale) = args.locale {
+        app.pane_env.locale = locale;
+    }
 
     // Spawn the terminal immediately since it's the first tab.
-    match terminal::TerminalEmulator::spawn(80, 24) {
+    match app.spawn_terminal() {
         Ok(term) => app.terminals.push(term),
         Err(e) => eprintln!("Warning: failed to spawn shell: {e}"),
     }
@@ -85,12 +153,20 @@ async fn main() -> Result<()> {
     crossterm::execute!(
         stdout,
         EnterAlternateScreen,
-        crossterm::event::EnableMouseCapture
+        crossterm::event::EnableMouseCapture,
+        crossterm::event::EnableBracketedPaste
     )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let result = run_loop(&mut terminal, &mut app, &mut writer, &mut reader).await;
+    let result = run_loop(
+        &mut terminal,
+        &mut app,
+        &socket_path,
+        &mut writer,
+        &mut reader,
+    )
+    .await;
     let save_warning = app
         .persist_preferences()
         .err()
@@ -101,7 +177,8 @@ async fn main() -> Result<()> {
     crossterm::execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        crossterm::event::DisableMouseCapture
+        crossterm::event::DisableMouseCapture,
+        crossterm::event::DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
     if let Some(msg) = save_warning {
@@ -114,43 +191,16 @@ async fn main() -> Result<()> {
 async fn run_loop(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     app: &mut App,
+    socket_path: &Path,
     writer: &mut agentbook::client::NodeWriter,
     reader: &mut agentbook::client::NodeReader,
 ) -> Result<()> {
     let mut refresh_interval = tokio::time::interval(Duration::from_secs(30));
     let mut prompt_scan_interval = tokio::time::interval(Duration::from_millis(1200));
     let mut pending: HashMap<u64, PendingRequest> = HashMap::new();
-    enqueue_request(
-        writer,
-        &mut pending,
-        Request::Identity,
-        PendingRequest::Identity,
-    )
-    .await;
-    enqueue_request(
-        writer,
-        &mut pending,
-        Request::Inbox {
-            unread_only: false,
-            limit: Some(100),
-        },
-        PendingRequest::Inbox,
-    )
-    .await;
-    enqueue_request(
-        writer,
-        &mut pending,
-        Request::Following,
-        PendingRequest::Following,
-    )
-    .await;
-    enqueue_request(
-        writer,
-        &mut pending,
-        Request::ListRooms,
-        PendingRequest::ListRooms,
-    )
-    .await;
+    // Set while the daemon connection is down.
+    let mut reconnect: Option<Backoff> = None;
+    request_initial_state(writer, &mut pending).await;
 
     // FPS cap for terminal rendering — 16ms ≈ 60fps.
     let mut last_draw = std::time::Instant::now();
@@ -194,6 +244,9 @@ async fn run_loop(
             }
         }
 
+        let reconnect_at = reconnect
+            .as_ref()
+            .map_or_else(tokio::time::Instant::now, |r| r.next_at().into());
         tokio::select! {
             // Keyboard events
             poll_result = tokio::task::spawn_blocking(|| event::poll(Duration::from_millis(16))) => {
@@ -287,6 +340,7 @@ async fn run_loop(
                                 _ => {}
                             }
                         }
+                        Event::Paste(text) => input::handle_paste(app, &text),
                         Event::Resize(_, _) => {
                             // Terminal widget will pick up new size on next draw.
                             resize_terminal_panes(terminal, app)?;
@@ -298,7 +352,7 @@ async fn run_loop(
             }
 
             // Socket responses and events from the node daemon.
-            response = reader.next() => {
+            response = reader.next(), if reconnect.is_none() => {
                 match response {
                     Some(Ok(envelope)) => match envelope.response {
                     Response::Event { event } => {
@@ -366,12 +420,39 @@ async fn run_loop(
                         app.status_msg = format!("Socket error: {e}");
                     }
                     None => {
-                        app.status_msg = "Daemon disconnected".to_string();
-                        app.should_quit = true;
+                        app.status_msg = "Daemon disconnected, reconnecting...".to_string();
+                        pending.clear();
+                        reconnect = Some(Backoff::start(std::time::Instant::now()));
                     }
                 }
             }
 
+            _ = tokio::time::sleep_until(reconnect_at), if reconnect.is_some() => {
+                let connected = tokio::time::timeout(
+                    RECONNECT_TIMEOUT,
+                    NodeClient::connect(socket_path),
+                )
+                .await;
+                match connected {
+                    Ok(Ok(client)) => {
+                        (*writer, *reader) = client.into_split();
+                        reconnect = None;
+                        request_initial_state(writer, &mut pending).await;
+                        app.status_msg = "Reconnected to daemon".to_string();
+                    }
+                    _ => {
+                        let backoff = reconnect.as_mut().expect("reconnecting");
+                        let delay = backoff.failed(std::time::Instant::now());
+                        app.status_msg = format!(
+                            "Daemon unreachable, reconnecting in {}s (attempt {})...",
+                            delay.as_secs_f32().ceil(),
+                            backoff.attempts()
+                        );
+                    }
+                }
+                app.request_full_redraw = true;
+            }
+
             // Periodic refresh (longer interval since events push now).
             _ = refresh_interval.tick() => {
                 enqueue_request(
@@ -424,6 +505,7 @@ async fn run_loop(
         // Skip the active terminal while in scroll mode — new PTY data would
         // reset the scrollback offset to 0, making scroll unusable.
         let mut terminal_changed = false;
+        let mut location_changed = false;
         for (idx, term) in app.terminals.iter_mut().enumerate() {
             if app.scroll_mode && idx == app.active_terminal {
                 continue;
@@ -431,6 +513,12 @@ async fn run_loop(
             if term.process_output() {
                 terminal_changed = true;
             }
+            if term.take_location_changed() && !term.is_persistent_mux() {
+                location_changed = true;
+            }
+        }
+        if location_changed {
+            app.refresh_terminal_tabs();
         }
         if terminal_changed && app.tab != Tab::Terminal {
             app.activity_terminal = true;
@@ -448,9 +536,10 @@ async fn run_loop(
             };
             if dead {
                 panes_changed = true;
-                let code = {
+                let (code, manifest) = {
                     let term = &mut app.terminals[pane_idx];
-                    term.exit_status()
+                    let code = term.exit_status();
+                    (code, term.finish_recording(code))
                 };
                 app.terminals.remove(pane_idx);
                 if app.terminals.is_empty() {
@@ -469,6 +558,9 @@ async fn run_loop(
                         "Shell exited (status {code}). Ctrl+Space then 1 to restart terminal."
                     );
                 }
+                if let Some(Err(e)) = manifest {
+                    app.status_msg = format!("Failed to write run manifest: {e:#}");
+                }
             } else {
                 pane_idx += 1;
             }
@@ -497,7 +589,7 @@ fn resize_terminal_panes(
     let (term_area, _) =
         ui::terminal_main_and_sidekick_areas(full_terminal_area, app.auto_agent.enabled);
     let pane_areas = ui::terminal_pane_areas(term_area, app.terminals.len(), app.terminal_split);
-    for (term, pane) in app.terminals.iter_mut().zip(pane_areas.into_iter()) {
+    for (term, pane) in app.terminals.iter_mut().zip(pane_areas) {
         term.resize(pane.width.saturating_sub(2), pane.height.saturating_sub(2));
     }
     Ok(())
@@ -671,6 +763,39 @@ async fn handle_ok_response(
     }
 }
 
+/// Load everything the UI shows; sent on connect and again on reconnect,
+/// since events sent while disconnected were missed.
+async fn request_initial_state(
+    writer: &mut agentbook::client::NodeWriter,
+    pending: &mut HashMap<u64, PendingRequest>,
+) {
+    enqueue_request(writer, pending, Request::Identity, PendingRequest::Identity).await;
+    enqueue_request(
+        writer,
+        pending,
+        Request::Inbox {
+            unread_only: false,
+            limit: Some(100),
+        },
+        PendingRequest::Inbox,
+    )
+    .await;
+    enqueue_request(
+        writer,
+        pending,
+        Request::Following,
+        PendingRequest::Following,
+    )
+    .await;
+    enqueue_request(
+        writer,
+        pending,
+        Request::ListRooms,
+        PendingRequest::ListRooms,
+    )
+    .await;
+}
+
 async fn enqueue_request(
     writer: &mut agentbook::client::NodeWriter,
     pending: &mut HashMap<u64, PendingRequest>,
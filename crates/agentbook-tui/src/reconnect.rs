@@ -0,0 +1,64 @@
+//! Backoff for reconnecting to the node daemon after the connection drops,
+//! so a daemon restart doesn't close the TUI.
+
+use std::time::{Duration, Instant};
+
+/// Wait before the first reconnect attempt; doubles after each failure.
+const FIRST_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Reconnect schedule while the daemon is unreachable.
+pub struct Backoff {
+    failures: u32,
+    next_at: Instant,
+}
+
+impl Backoff {
+    /// Start reconnecting after the connection dropped at `now`.
+    pub fn start(now: Instant) -> Self {
+        Self {
+            failures: 0,
+            next_at: now + FIRST_DELAY,
+        }
+    }
+
+    /// When to make the next attempt.
+    pub fn next_at(&self) -> Instant {
+        self.next_at
+    }
+
+    /// Attempts made so far.
+    pub fn attempts(&self) -> u32 {
+        self.failures
+    }
+
+    /// Schedule another attempt after one failed at `now`, returning the
+    /// wait.
+    pub fn failed(&mut self, now: Instant) -> Duration {
+        self.failures += 1;
+        let delay = FIRST_DELAY
+            .saturating_mul(1 << self.failures.min(16))
+            .min(MAX_DELAY);
+        self.next_at = now + delay;
+        delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        let now = Instant::now();
+        let mut backoff = Backoff::start(now);
+        assert_eq!(backoff.next_at(), now + FIRST_DELAY);
+        let delays: Vec<_> = (0..8).map(|_| backoff.failed(now).as_millis()).collect();
+        assert_eq!(
+            delays,
+            [1_000, 2_000, 4_000, 8_000, 16_000, 30_000, 30_000, 30_000]
+        );
+        assert_eq!(backoff.attempts(), 8);
+        assert_eq!(backoff.next_at(), now + MAX_DELAY);
+    }
+}
@@ -0,0 +1,394 @@
+//! Terminal session recording in asciicast v2 format.
+//!
+//! Each recorded pane becomes one `.cast` file: a JSON header line followed
+//! by `[elapsed_secs, "o" | "i" | "r", data]` event lines. Input (`"i"`) is
+//! only recorded when asked for, for auditing what was typed into a pane.
+//! Files play back with `agentbook-tui play` or any asciinema-compatible
+//! player; `agentbook-tui inputs` lists the input.
+//!
+//! When a recorded pane ends, a `.manifest.json` next to its recording
+//! notes what ran, where, and how it finished (see [`RunManifest`]).
+
+use crate::terminal::PaneEnv;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// asciicast v2 file header.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CastHeader {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+}
+
+/// Writes PTY output for one terminal pane to an asciicast v2 file.
+pub struct Recorder {
+    out: BufWriter<File>,
+    started: Instant,
+    /// Trailing bytes of an incomplete UTF-8 sequence from the last chunk.
+    pending: Vec<u8>,
+}
+
+impl Recorder {
+    /// Create `path` and write the header for a `cols`x`rows` terminal
+    /// started with `pane`.
+    pub fn create(path: &Path, cols: u16, rows: u16, pane: &PaneEnv) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        let mut env = HashMap::new();
+        env.insert("TERM".to_string(), pane.term.clone());
+        env.insert("LANG".to_string(), pane.locale.clone());
+        if let Ok(shell) = std::env::var("SHELL") {
+            env.insert("SHELL".to_string(), shell);
+        }
+        let header = CastHeader {
+            version: 2,
+            width: cols,
+            height: rows,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs()),
+            env,
+        };
+        let mut out = BufWriter::new(file);
+        serde_json::to_writer(&mut out, &header)?;
+        out.write_all(b"\n")?;
+        Ok(Self {
+            out,
+            started: Instant::now(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Record a chunk of PTY output. Multi-byte characters split across
+    /// chunks are held back until complete.
+    pub fn output(&mut self, bytes: &[u8]) -> Result<()> {
+        self.pending.extend_from_slice(bytes);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            // `error_len() == None` means the input ended mid-character.
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        if valid == 0 {
+            return Ok(());
+        }
+        let rest = self.pending.split_off(valid);
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending = rest;
+        self.event("o", &text)
+    }
+
+    /// Record bytes written to the pane: keystrokes, pastes, mouse reports.
+    pub fn input(&mut self, bytes: &[u8]) -> Result<()> {
+        self.event("i", &String::from_utf8_lossy(bytes))
+    }
+
+    /// Record a terminal resize.
+    pub fn resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        self.event("r", &format!("{cols}x{rows}"))
+    }
+
+    fn event(&mut self, kind: &str, data: &str) -> Result<()> {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        serde_json::to_writer(&mut self.out, &(elapsed, kind, data))?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// A parsed recording.
+#[derive(Debug)]
+pub struct Cast {
+    pub header: CastHeader,
+    /// `(elapsed_secs, kind, data)` in file order.
+    pub events: Vec<(f64, String, String)>,
+}
+
+impl Cast {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let mut lines = BufReader::new(file).lines();
+        let header_line = lines.next().context("recording is empty")??;
+        let header: CastHeader =
+            serde_json::from_str(&header_line).context("invalid asciicast header")?;
+        if header.version != 2 {
+            bail!("unsupported asciicast version {}", header.version);
+        }
+        let mut events = Vec::new();
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = serde_json::from_str(&line)
+                .with_context(|| format!("invalid event on line {}", i + 2))?;
+            events.push(event);
+        }
+        Ok(Self { header, events })
+    }
+}
+
+/// Replay a recording's output to `out`, honouring the original timing
+/// divided by `speed`. Pauses longer than `idle_limit` are shortened to it.
+pub fn play(
+    cast: &Cast,
+    out: &mut impl Write,
+    speed: f64,
+    idle_limit: Option<Duration>,
+) -> Result<()> {
+    if speed <= 0.0 {
+        bail!("speed must be positive");
+    }
+    let mut last = 0.0;
+    for (at, kind, data) in &cast.events {
+        let mut gap = Duration::from_secs_f64(((at - last) / speed).max(0.0));
+        if let Some(limit) = idle_limit {
+            gap = gap.min(limit);
+        }
+        last = *at;
+        if !gap.is_zero() {
+            std::thread::sleep(gap);
+        }
+        if kind == "o" {
+            out.write_all(data.as_bytes())?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Write one line per input event in `cast` to `out`: the time it was sent
+/// and the input with control characters escaped.
+pub fn print_inputs(cast: &Cast, out: &mut impl Write) -> Result<()> {
+    for (at, kind, data) in &cast.events {
+        if kind == "i" {
+            writeln!(out, "{at:>10.3}  {}", data.escape_debug())?;
+        }
+    }
+    Ok(())
+}
+
+/// What ran in a recorded pane, so a run can be compared with or repeated
+/// from its recording.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunManifest {
+    /// Program and arguments the pane ran.
+    pub command: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    /// `HEAD` of the git checkout the pane started in, if any.
+    pub git_commit: Option<String>,
+    pub term: String,
+    pub locale: String,
+    /// SHA-256 of the pane's environment, to tell whether two runs had the
+    /// same one without storing its values.
+    pub env_sha256: String,
+    /// Unix time the pane started.
+    pub started_at: u64,
+    pub duration_secs: f64,
+    /// `None` when the pane was closed rather than exiting on its own.
+    pub exit_status: Option<u32>,
+    /// File name of the recording, in the same directory.
+    pub recording: String,
+    pub recording_bytes: u64,
+    pub recording_sha256: String,
+}
+
+/// A recorded run whose [`RunManifest`] is written when it ends.
+pub struct PendingManifest {
+    manifest: RunManifest,
+    started: Instant,
+    cast: PathBuf,
+}
+
+impl PendingManifest {
+    /// Start describing a run of `command` with `env`, recorded to `cast`.
+    pub fn start(cast: &Path, command: Vec<String>, env: &PaneEnv) -> Self {
+        let cwd = std::env::current_dir().ok();
+        let git_commit = cwd.as_deref().and_then(git_head);
+        let manifest = RunManifest {
+            command,
+            cwd,
+            git_commit,
+            term: env.term.clone(),
+            locale: env.locale.clone(),
+            env_sha256: env.env_sha256(),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            duration_secs: 0.0,
+            exit_status: None,
+            recording: cast
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            recording_bytes: 0,
+            recording_sha256: String::new(),
+        };
+        Self {
+            manifest,
+            started: Instant::now(),
+            cast: cast.to_path_buf(),
+        }
+    }
+
+    /// Record how the run ended and write its manifest, returning the path.
+    /// The recording must be complete, as its hash goes in the manifest.
+    pub fn finish(self, exit_status: Option<u32>) -> Result<PathBuf> {
+        let Self {
+            mut manifest,
+            started,
+            cast,
+        } = self;
+        manifest.duration_secs = started.elapsed().as_secs_f64();
+        manifest.exit_status = exit_status;
+        let mut file =
+            File::open(&cast).with_context(|| format!("failed to open {}", cast.display()))?;
+        let mut hasher = Sha256::new();
+        manifest.recording_bytes = std::io::copy(&mut file, &mut hasher)?;
+        manifest.recording_sha256 = format!("{:x}", hasher.finalize());
+        let path = manifest_path(&cast);
+        let json = serde_json::to_vec_pretty(&manifest)?;
+        std::fs::write(&path, json)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Where the manifest for the recording at `cast` goes.
+pub fn manifest_path(cast: &Path) -> PathBuf {
+    cast.with_extension("manifest.json")
+}
+
+fn git_head(dir: &Path) -> Option<String> {
+    let out = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "HEAD"])
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// File name for a new recording of pane `index`.
+pub fn recording_path(dir: &Path, index: usize) -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    dir.join(format!(
+        "terminal-{secs}-{}-{index}.cast",
+        std::process::id()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_and_replays_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.cast");
+        let pane = PaneEnv {
+            term: "screen-256color".to_string(),
+            locale: "C.UTF-8".to_string(),
+        };
+        let mut rec = Recorder::create(&path, 80, 24, &pane).unwrap();
+        rec.output(b"hello ").unwrap();
+        // "é" split across two chunks must not be mangled.
+        rec.output(&[0xc3]).unwrap();
+        rec.output(&[0xa9, b'\n']).unwrap();
+        rec.resize(100, 30).unwrap();
+        drop(rec);
+
+        let cast = Cast::load(&path).unwrap();
+        assert_eq!(cast.header.width, 80);
+        assert_eq!(cast.header.height, 24);
+        assert_eq!(cast.header.env["TERM"], "screen-256color");
+        assert_eq!(cast.header.env["LANG"], "C.UTF-8");
+        let kinds: Vec<_> = cast.events.iter().map(|e| e.1.as_str()).collect();
+        assert_eq!(kinds, ["o", "o", "r"]);
+        assert_eq!(cast.events[2].2, "100x30");
+
+        let mut out = Vec::new();
+        play(&cast, &mut out, 1000.0, Some(Duration::ZERO)).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "hello é\n");
+    }
+
+    #[test]
+    fn input_is_recorded_for_audit_but_not_replayed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.cast");
+        let mut rec = Recorder::create(&path, 80, 24, &PaneEnv::default()).unwrap();
+        rec.input(b"sudo systemctl restart db\r").unwrap();
+        rec.output(b"done\n").unwrap();
+        drop(rec);
+
+        let cast = Cast::load(&path).unwrap();
+        let mut listed = Vec::new();
+        print_inputs(&cast, &mut listed).unwrap();
+        let listed = String::from_utf8(listed).unwrap();
+        assert!(listed.ends_with("  sudo systemctl restart db\\r\n"));
+        assert_eq!(listed.lines().count(), 1);
+
+        let mut out = Vec::new();
+        play(&cast, &mut out, 1000.0, Some(Duration::ZERO)).unwrap();
+        assert_eq!(out, b"done\n");
+    }
+
+    #[test]
+    fn manifest_describes_the_finished_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("build.cast");
+        let env = PaneEnv::default();
+        let pending = PendingManifest::start(&path, vec!["/bin/sh".into()], &env);
+        let mut rec = Recorder::create(&path, 80, 24, &env).unwrap();
+        rec.output(b"make\n").unwrap();
+        drop(rec);
+
+        let written = pending.finish(Some(2)).unwrap();
+        assert_eq!(written, dir.path().join("build.manifest.json"));
+        let manifest: RunManifest =
+            serde_json::from_slice(&std::fs::read(&written).unwrap()).unwrap();
+        assert_eq!(manifest.command, ["/bin/sh"]);
+        assert_eq!(manifest.exit_status, Some(2));
+        assert_eq!(manifest.recording, "build.cast");
+        assert_eq!(manifest.env_sha256, env.env_sha256());
+        let cast = std::fs::read(&path).unwrap();
+        assert_eq!(manifest.recording_bytes, cast.len() as u64);
+        assert_eq!(
+            manifest.recording_sha256,
+            format!("{:x}", Sha256::digest(&cast))
+        );
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("v1.cast");
+        std::fs::write(&path, "{\"version\":1,\"width\":80,\"height\":24}\n").unwrap();
+        assert!(Cast::load(&path).is_err());
+    }
+}
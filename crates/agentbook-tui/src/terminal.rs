@@ -1,10 +1,82 @@
 use anyhow::{Context, Result};
 use portable_pty::{CommandBuilder, MasterPty, NativePtySystem, PtySize, PtySystem};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::io::Write;
 use std::sync::mpsc;
 
 const DEFAULT_TMUX_SOCKET: &str = "agentbook";
 const DEFAULT_TMUX_SESSION: &str = "main";
+const DEFAULT_TERM: &str = "xterm-256color";
+/// Locale used when the TUI's own is not UTF-8.
+const FALLBACK_LOCALE: &str = "C.UTF-8";
+
+/// Terminal type and locale a pane's shell starts with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaneEnv {
+    /// `TERM` for the shell (or the tmux client).
+    pub term: String,
+    /// `LANG` and `LC_CTYPE` for the shell, e.g. `en_US.UTF-8`.
+    pub locale: String,
+}
+
+impl Default for PaneEnv {
+    /// `xterm-256color`, which is what the vt100 parser emulates, and the
+    /// TUI's own locale if it is UTF-8, else `C.UTF-8`.
+    fn default() -> Self {
+        let inherited = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .find_map(|key| std::env::var(key).ok().filter(|v| !v.is_empty()));
+        Self {
+            term: DEFAULT_TERM.to_string(),
+            locale: inherited
+                .filter(|locale| is_utf8_locale(locale))
+                .unwrap_or_else(|| FALLBACK_LOCALE.to_string()),
+        }
+    }
+}
+
+impl PaneEnv {
+    fn apply(&self, cmd: &mut CommandBuilder) {
+        cmd.env("TERM", &self.term);
+        cmd.env("LANG", &self.locale);
+        cmd.env("LC_CTYPE", &self.locale);
+        // LC_ALL overrides both, so an inherited non-UTF-8 one must go.
+        if std::env::var("LC_ALL").is_ok_and(|v| !is_utf8_locale(&v)) {
+            cmd.env_remove("LC_ALL");
+        }
+    }
+
+    /// SHA-256 over the sorted `KEY=value` lines of the environment a pane
+    /// shell gets: ours with [`PaneEnv::apply`]'s changes.
+    pub fn env_sha256(&self) -> String {
+        let mut vars: BTreeMap<String, String> = std::env::vars_os()
+            .map(|(k, v)| {
+                (
+                    k.to_string_lossy().into_owned(),
+                    v.to_string_lossy().into_owned(),
+                )
+            })
+            .collect();
+        vars.insert("TERM".into(), self.term.clone());
+        vars.insert("LANG".into(), self.locale.clone());
+        vars.insert("LC_CTYPE".into(), self.locale.clone());
+        if vars.get("LC_ALL").is_some_and(|v| !is_utf8_locale(v)) {
+            vars.remove("LC_ALL");
+        }
+        let mut hasher = Sha256::new();
+        for (key, value) in &vars {
+            hasher.update(format!("{key}={value}\n"));
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// Whether `locale` (e.g. `en_US.UTF-8` or `C.utf8`) uses UTF-8.
+fn is_utf8_locale(locale: &str) -> bool {
+    let lower = locale.to_ascii_lowercase();
+    lower.contains("utf-8") || lower.contains("utf8")
+}
 
 /// Mouse button identifier for PTY event forwarding.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -52,11 +124,15 @@ pub struct MuxWindow {
 pub enum AutomationSnapshotSource {
     Local {
         text: String,
+        progress: Option<serde_json::Value>,
     },
     Tmux {
         socket: String,
         session: String,
         max_lines: usize,
+        /// Reported through the attached client, so it belongs to the
+        /// active window.
+        progress: Option<serde_json::Value>,
     },
 }
 
@@ -66,21 +142,24 @@ pub struct AutomationWindowSnapshot {
     pub name: String,
     pub active: bool,
     pub text: String,
+    pub progress: Option<serde_json::Value>,
 }
 
 impl AutomationSnapshotSource {
     pub fn collect(&self) -> Result<Vec<AutomationWindowSnapshot>> {
         match self {
-            Self::Local { text } => Ok(vec![AutomationWindowSnapshot {
+            Self::Local { text, progress } => Ok(vec![AutomationWindowSnapshot {
                 index: 0,
                 name: "shell".to_string(),
                 active: true,
                 text: text.clone(),
+                progress: progress.clone(),
             }]),
             Self::Tmux {
                 socket,
                 session,
                 max_lines,
+                progress,
             } => {
                 let out = run_tmux_capture(
                     socket,
@@ -112,11 +191,13 @@ impl AutomationSnapshotSource {
                             &format!("-{}", max_lines),
                         ],
                     )?;
+                    let active = active == "1";
                     snapshots.push(AutomationWindowSnapshot {
                         index,
                         name: name.to_string(),
-                        active: active == "1",
+                        active,
                         text,
+                        progress: progress.clone().filter(|_| active),
                     });
                 }
                 Ok(snapshots)
@@ -125,20 +206,145 @@ impl AutomationSnapshotSource {
     }
 }
 
+/// Structured progress a task reported with `ESC ] 9 ; 9 ; <json object> BEL`.
+///
+/// Inside the tmux backend the sequence must be wrapped for passthrough
+/// (`ESC P tmux; ESC ESC ] 9;9;… BEL ESC \\`).
+#[derive(Clone, Debug)]
+pub struct TaskProgress {
+    pub json: serde_json::Value,
+}
+
+impl TaskProgress {
+    /// Short form for the pane title: `percent` and `message` when the task
+    /// sends them, otherwise the raw JSON.
+    pub fn summary(&self) -> String {
+        let percent = self.json.get("percent").and_then(|p| p.as_f64());
+        let message = self.json.get("message").and_then(|m| m.as_str());
+        match (percent, message) {
+            (Some(p), Some(m)) => format!("{p:.0}% {m}"),
+            (Some(p), None) => format!("{p:.0}%"),
+            (None, Some(m)) => m.to_string(),
+            (None, None) => self.json.to_string(),
+        }
+    }
+}
+
+/// Parse the parameters of an OSC 9;9 progress report. Payload semicolons
+/// were split off by the parser and are rejoined here.
+fn parse_progress_osc(params: &[&[u8]]) -> Option<serde_json::Value> {
+    let [b"9", b"9", payload @ ..] = params else {
+        return None;
+    };
+    let json: serde_json::Value = serde_json::from_slice(&payload.join(&b';')).ok()?;
+    // ConEmu uses 9;9 for working-directory reports; only objects are ours.
+    json.is_object().then_some(json)
+}
+
+/// Parse an OSC 7 working-directory report, `ESC ] 7 ; file://host/path BEL`
+/// with the path percent-encoded, into the path.
+fn parse_cwd_osc(params: &[&[u8]]) -> Option<String> {
+    let [b"7", url @ ..] = params else {
+        return None;
+    };
+    let url = String::from_utf8(url.join(&b';')).ok()?;
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    percent_decode(path)
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Bytes to send a program for pasted `text`. Line breaks become CR, which
+/// is what Enter sends. With `bracketed` (the program enabled mode 2004)
+/// the text is wrapped in paste markers, and any end marker inside it is
+/// dropped so the paste can't end early and run the rest as keystrokes.
+pub fn paste_bytes(text: &str, bracketed: bool) -> Vec<u8> {
+    let text = text.replace("\r\n", "\r").replace('\n', "\r");
+    if !bracketed {
+        return text.into_bytes();
+    }
+    let mut out = Vec::with_capacity(text.len() + PASTE_START.len() + PASTE_END.len());
+    out.extend_from_slice(PASTE_START);
+    out.extend_from_slice(text.replace("\x1b[201~", "").as_bytes());
+    out.extend_from_slice(PASTE_END);
+    out
+}
+
+/// vt100 hooks for sequences the parser does not handle itself.
+#[derive(Default)]
+pub struct PaneCallbacks {
+    progress: Option<TaskProgress>,
+    /// Window title from OSC 0 or 2.
+    title: Option<String>,
+    /// Working directory from OSC 7.
+    cwd: Option<String>,
+    /// Set when the title or working directory changes.
+    location_changed: bool,
+}
+
+impl vt100::Callbacks for PaneCallbacks {
+    fn set_window_title(&mut self, _: &mut vt100::Screen, title: &[u8]) {
+        let title = String::from_utf8_lossy(title).trim().to_string();
+        let title = (!title.is_empty()).then_some(title);
+        if title != self.title {
+            self.title = title;
+            self.location_changed = true;
+        }
+    }
+
+    fn unhandled_osc(&mut self, _: &mut vt100::Screen, params: &[&[u8]]) {
+        if let Some(json) = parse_progress_osc(params) {
+            self.progress = Some(TaskProgress { json });
+        } else if let Some(cwd) = parse_cwd_osc(params)
+            && self.cwd.as_ref() != Some(&cwd)
+        {
+            self.cwd = Some(cwd);
+            self.location_changed = true;
+        }
+    }
+}
+
 /// Embedded terminal emulator backed by a PTY + vt100 parser.
 pub struct TerminalEmulator {
-    parser: vt100::Parser,
+    parser: vt100::Parser<PaneCallbacks>,
     master: Box<dyn MasterPty + Send>,
     pty_writer: Box<dyn Write + Send>,
     pty_reader_rx: mpsc::Receiver<Vec<u8>>,
     child: Box<dyn portable_pty::Child + Send + Sync>,
     size: (u16, u16),
     backend: BackendKind,
+    env: PaneEnv,
+    recorder: Option<crate::recording::Recorder>,
+    /// Whether the recorder also gets what is written to the pane.
+    record_input: bool,
+    /// Program and arguments the pane runs.
+    command: Vec<String>,
+    /// Manifest written when a recorded pane ends.
+    manifest: Option<crate::recording::PendingManifest>,
 }
 
 impl TerminalEmulator {
-    /// Spawn a new PTY running `$SHELL` (fallback `/bin/bash`).
-    pub fn spawn(cols: u16, rows: u16) -> Result<Self> {
+    /// Spawn a new PTY running `$SHELL` (fallback `/bin/bash`) with `env`.
+    pub fn spawn(cols: u16, rows: u16, env: &PaneEnv) -> Result<Self> {
         let pty_system = NativePtySystem::default();
         let pair = pty_system
             .openpty(PtySize {
@@ -158,22 +364,22 @@ impl TerminalEmulator {
             BackendKind::LocalShell
         };
 
-        let mut cmd = match &backend {
+        let command = match &backend {
             BackendKind::LocalShell => {
-                let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
-                CommandBuilder::new(&shell)
-            }
-            BackendKind::Tmux { socket, session } => {
-                let mut cmd = CommandBuilder::new("tmux");
-                cmd.arg("-L");
-                cmd.arg(socket);
-                cmd.arg("attach-session");
-                cmd.arg("-t");
-                cmd.arg(session);
-                cmd
+                vec![std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string())]
             }
+            BackendKind::Tmux { socket, session } => vec![
+                "tmux".to_string(),
+                "-L".to_string(),
+                socket.clone(),
+                "attach-session".to_string(),
+                "-t".to_string(),
+                session.clone(),
+            ],
         };
-        cmd.env("TERM", "xterm-256color");
+        let mut cmd = CommandBuilder::new(&command[0]);
+        cmd.args(&command[1..]);
+        env.apply(&mut cmd);
 
         let child = pair
             .slave
@@ -203,13 +409,18 @@ impl TerminalEmulator {
         });
 
         Ok(Self {
-            parser: vt100::Parser::new(rows, cols, 10_000),
+            parser: vt100::Parser::new_with_callbacks(rows, cols, 10_000, PaneCallbacks::default()),
             master,
             pty_writer: writer,
             pty_reader_rx: rx,
             child,
             size: (cols, rows),
             backend,
+            env: env.clone(),
+            recorder: None,
+            record_input: false,
+            command,
+            manifest: None,
         })
     }
 
@@ -218,9 +429,22 @@ impl TerminalEmulator {
         self.scroll_to_bottom();
         self.pty_writer.write_all(bytes)?;
         self.pty_writer.flush()?;
+        if self.record_input
+            && let Some(rec) = &mut self.recorder
+            && rec.input(bytes).is_err()
+        {
+            self.recorder = None;
+        }
         Ok(())
     }
 
+    /// Paste `text` into the PTY (see [`paste_bytes`]) as one write, so it
+    /// can't interleave with keystrokes.
+    pub fn paste(&mut self, text: &str) -> Result<()> {
+        let bracketed = self.parser.screen().bracketed_paste();
+        self.write_input(&paste_bytes(text, bracketed))
+    }
+
     /// Send a mouse wheel event to the PTY using the currently active xterm
     /// mouse protocol mode/encoding. Returns `Ok(true)` when forwarded, or
     /// `Ok(false)` if mouse reporting is not enabled by the child app.
@@ -398,11 +622,43 @@ impl TerminalEmulator {
         let mut any = false;
         while let Ok(chunk) = self.pty_reader_rx.try_recv() {
             self.parser.process(&chunk);
+            // A failed write (e.g. disk full) stops recording rather than the pane.
+            if let Some(rec) = &mut self.recorder
+                && rec.output(&chunk).is_err()
+            {
+                self.recorder = None;
+            }
             any = true;
         }
         any
     }
 
+    /// Start recording this pane's output to an asciicast file at `path`,
+    /// and its input too with `record_input`.
+    pub fn start_recording(&mut self, path: &std::path::Path, record_input: bool) -> Result<()> {
+        let (cols, rows) = self.size;
+        self.recorder = Some(crate::recording::Recorder::create(
+            path, cols, rows, &self.env,
+        )?);
+        self.record_input = record_input;
+        self.manifest = Some(crate::recording::PendingManifest::start(
+            path,
+            self.command.clone(),
+            &self.env,
+        ));
+        Ok(())
+    }
+
+    /// End this pane's recording and write its run manifest with
+    /// `exit_status`. `None` if the pane wasn't recorded.
+    pub fn finish_recording(
+        &mut self,
+        exit_status: Option<u32>,
+    ) -> Option<Result<std::path::PathBuf>> {
+        self.recorder = None;
+        self.manifest.take().map(|m| m.finish(exit_status))
+    }
+
     /// Resize the PTY and parser.
     pub fn resize(&mut self, cols: u16, rows: u16) {
         if (cols, rows) == self.size || cols == 0 || rows == 0 {
@@ -410,6 +666,9 @@ impl TerminalEmulator {
         }
         self.size = (cols, rows);
         self.parser.screen_mut().set_size(rows, cols);
+        if let Some(rec) = &mut self.recorder {
+            let _ = rec.resize(cols, rows);
+        }
         let _ = self.master.resize(PtySize {
             rows,
             cols,
@@ -428,7 +687,29 @@ impl TerminalEmulator {
     /// Useful when switching tmux panes/windows to avoid stale glyph artifacts
     /// between full redraws.
     pub fn reset_screen(&mut self) {
-        self.parser = vt100::Parser::new(self.size.1, self.size.0, 10_000);
+        let callbacks = std::mem::take(self.parser.callbacks_mut());
+        self.parser =
+            vt100::Parser::new_with_callbacks(self.size.1, self.size.0, 10_000, callbacks);
+    }
+
+    /// The title the pane's program last set (OSC 0 or 2), if any.
+    pub fn title(&self) -> Option<&str> {
+        self.parser.callbacks().title.as_deref()
+    }
+
+    /// The working directory the pane's shell last reported (OSC 7), if any.
+    pub fn cwd(&self) -> Option<&str> {
+        self.parser.callbacks().cwd.as_deref()
+    }
+
+    /// Whether the title or working directory changed since the last call.
+    pub fn take_location_changed(&mut self) -> bool {
+        std::mem::take(&mut self.parser.callbacks_mut().location_changed)
+    }
+
+    /// The latest progress report from a task in this pane, if any.
+    pub fn progress(&self) -> Option<&TaskProgress> {
+        self.parser.callbacks().progress.as_ref()
     }
 
     /// Return a plain-text snapshot of visible rows from the current screen.
@@ -464,11 +745,13 @@ impl TerminalEmulator {
         match &self.backend {
             BackendKind::LocalShell => AutomationSnapshotSource::Local {
                 text: self.snapshot_text(max_lines),
+                progress: self.progress().map(|p| p.json.clone()),
             },
             BackendKind::Tmux { socket, session } => AutomationSnapshotSource::Tmux {
                 socket: socket.clone(),
                 session: session.clone(),
                 max_lines,
+                progress: self.progress().map(|p| p.json.clone()),
             },
         }
     }
@@ -699,6 +982,8 @@ impl TerminalEmulator {
 impl Drop for TerminalEmulator {
     fn drop(&mut self) {
         let _ = self.child.kill();
+        // A pane closed before its shell exited still gets a manifest.
+        let _ = self.finish_recording(None);
     }
 }
 
@@ -743,6 +1028,13 @@ fn setup_tmux_session(socket: &str, session: &str) -> Result<()> {
     run_tmux(socket, &["set-option", "-t", session, "prefix", "C-a"])?;
     run_tmux(socket, &["unbind-key", "-T", "prefix", "C-b"])?;
     run_tmux(socket, &["bind-key", "-T", "prefix", "C-a", "send-prefix"])?;
+    // Let tasks' progress reports through to our parser. Older tmux lacks
+    // the option; progress is then only seen from the local shell backend.
+    run_tmux(
+        socket,
+        &["set-option", "-t", session, "allow-passthrough", "on"],
+    )
+    .ok();
 
     Ok(())
 }
@@ -866,11 +1158,73 @@ mod tests {
         }
     }
 
+    #[test]
+    fn progress_osc_parses_json_objects_only() {
+        let json = parse_progress_osc(&[b"9", b"9", br#"{"percent":40,"message":"a"#, br#"b"}"#])
+            .expect("progress object");
+        let progress = TaskProgress { json };
+        assert_eq!(progress.summary(), "40% a;b");
+
+        assert!(parse_progress_osc(&[b"9", b"9", b"/home/user"]).is_none());
+        assert!(parse_progress_osc(&[b"9", b"4", b"{}"]).is_none());
+    }
+
+    #[test]
+    fn title_and_cwd_are_tracked_from_output() {
+        let mut parser = vt100::Parser::new_with_callbacks(24, 80, 0, PaneCallbacks::default());
+        parser.process(b"\x1b]2;cargo test\x07");
+        parser.process(b"\x1b]7;file://host/home/dev/my%20app\x1b\\");
+        let callbacks = parser.callbacks_mut();
+        assert_eq!(callbacks.title.as_deref(), Some("cargo test"));
+        assert_eq!(callbacks.cwd.as_deref(), Some("/home/dev/my app"));
+        assert!(std::mem::take(&mut callbacks.location_changed));
+
+        // Repeating the same values is not a change.
+        parser.process(b"\x1b]0;cargo test\x07\x1b]7;file://host/home/dev/my%20app\x07");
+        assert!(!parser.callbacks().location_changed);
+
+        assert!(parse_cwd_osc(&[b"7", b"/no/scheme"]).is_none());
+        assert!(parse_cwd_osc(&[b"7", b"file://host/bad%zz"]).is_none());
+    }
+
+    #[test]
+    fn pastes_keep_line_breaks_and_stay_bracketed() {
+        assert_eq!(paste_bytes("a\nb\r\nc", false), b"a\rb\rc");
+        assert_eq!(
+            paste_bytes("ls\nrm -rf /\x1b[201~\n", true),
+            b"\x1b[200~ls\rrm -rf /\r\x1b[201~"
+        );
+    }
+
+    #[test]
+    fn utf8_locales_are_recognised() {
+        assert!(is_utf8_locale("en_US.UTF-8"));
+        assert!(is_utf8_locale("C.utf8"));
+        assert!(!is_utf8_locale("C"));
+        assert!(!is_utf8_locale("en_US.ISO-8859-1"));
+    }
+
+    #[test]
+    fn pane_env_defaults_to_xterm_and_utf8() {
+        let _guard = env_lock().lock().expect("env lock poisoned");
+        let _all = EnvGuard::set("LC_ALL", "");
+        let _ctype = EnvGuard::set("LC_CTYPE", "");
+        {
+            let _lang = EnvGuard::set("LANG", "de_DE.UTF-8");
+            let env = PaneEnv::default();
+            assert_eq!(env.term, "xterm-256color");
+            assert_eq!(env.locale, "de_DE.UTF-8");
+        }
+        let _lang = EnvGuard::set("LANG", "POSIX");
+        assert_eq!(PaneEnv::default().locale, "C.UTF-8");
+    }
+
     #[test]
     fn spawn_uses_local_shell_when_tmux_disabled() {
         let _guard = env_lock().lock().expect("env lock poisoned");
         let _tmux = EnvGuard::set("AGENTBOOK_TMUX", "0");
-        let term = TerminalEmulator::spawn(80, 24).expect("spawn should succeed");
+        let term =
+            TerminalEmulator::spawn(80, 24, &PaneEnv::default()).expect("spawn should succeed");
         assert!(!term.is_persistent_mux());
     }
 
@@ -887,7 +1241,8 @@ mod tests {
         let _session = EnvGuard::set("AGENTBOOK_TMUX_SESSION", &session);
         kill_server(&socket);
 
-        let term1 = TerminalEmulator::spawn(80, 24).expect("tmux-backed spawn should succeed");
+        let term1 = TerminalEmulator::spawn(80, 24, &PaneEnv::default())
+            .expect("tmux-backed spawn should succeed");
         assert!(term1.is_persistent_mux());
         assert!(has_session(&socket, &session));
         drop(term1);
@@ -895,7 +1250,8 @@ mod tests {
         // Session should outlive the first attached client.
         assert!(has_session(&socket, &session));
 
-        let term2 = TerminalEmulator::spawn(80, 24).expect("reattach should succeed");
+        let term2 =
+            TerminalEmulator::spawn(80, 24, &PaneEnv::default()).expect("reattach should succeed");
         assert!(term2.is_persistent_mux());
         assert!(has_session(&socket, &session));
         drop(term2);
@@ -916,7 +1272,8 @@ mod tests {
         let _session = EnvGuard::set("AGENTBOOK_TMUX_SESSION", &session);
         kill_server(&socket);
 
-        let term = TerminalEmulator::spawn(80, 24).expect("tmux-backed spawn should succeed");
+        let term = TerminalEmulator::spawn(80, 24, &PaneEnv::default())
+            .expect("tmux-backed spawn should succeed");
         assert!(term.is_persistent_mux());
         let before = pane_count(&socket, &session);
         assert!(before >= 1);
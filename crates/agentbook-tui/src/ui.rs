@@ -801,12 +801,21 @@ fn draw_terminal_pane(
     scroll_mode: bool,
 ) {
     let scrolled = term.is_scrolled_back();
+    let location = match (term.title(), term.cwd()) {
+        (Some(title), _) => format!("· {} ", truncate(title, 40)),
+        (None, Some(cwd)) => format!("· {} ", truncate(cwd, 40)),
+        (None, None) => String::new(),
+    };
+    let progress = term
+        .progress()
+        .map(|p| format!("· {} ", truncate(&p.summary(), 40)))
+        .unwrap_or_default();
     let title = if scroll_mode {
-        format!(" Terminal {pane_number} [scroll mode] ")
+        format!(" Terminal {pane_number} [scroll mode] {location}{progress}")
     } else if scrolled {
-        format!(" Terminal {pane_number} (scrollback) ")
+        format!(" Terminal {pane_number} (scrollback) {location}{progress}")
     } else {
-        format!(" Terminal {pane_number} ")
+        format!(" Terminal {pane_number} {location}{progress}")
     };
     let mut block = Block::default().borders(Borders::ALL).title(title);
     if scroll_mode {
@@ -11,7 +11,21 @@ const YOLO_KEY_FILE: &str = "yolo.key";
 pub fn generate_yolo_key(state_dir: &Path) -> Result<[u8; 32]> {
     let secret = SecretKey::random(&mut OsRng);
     let key_bytes: [u8; 32] = secret.to_bytes().into();
+    save_yolo_key(state_dir, &key_bytes)?;
+    Ok(key_bytes)
+}
+
+/// Restore a yolo wallet key (e.g. from its recovery phrase) into state_dir/yolo.key.
+pub fn restore_yolo_key(state_dir: &Path, key_bytes: &[u8; 32]) -> Result<String> {
+    if has_yolo_key(state_dir) {
+        anyhow::bail!("yolo.key already exists; refusing to overwrite it");
+    }
+    let secret = SecretKey::from_slice(key_bytes).context("invalid yolo key bytes")?;
+    save_yolo_key(state_dir, key_bytes)?;
+    Ok(evm_address_from_public_key(&secret.public_key()))
+}
 
+fn save_yolo_key(state_dir: &Path, key_bytes: &[u8; 32]) -> Result<()> {
     let path = state_dir.join(YOLO_KEY_FILE);
     let hex_str = hex::encode(key_bytes);
     std::fs::write(&path, hex_str)
@@ -25,7 +39,7 @@ pub fn generate_yolo_key(state_dir: &Path) -> Result<[u8; 32]> {
             .context("failed to set yolo.key permissions")?;
     }
 
-    Ok(key_bytes)
+    Ok(())
 }
 
 /// Load the yolo wallet key from state_dir/yolo.key.
@@ -157,6 +171,18 @@ mod tests {
         assert_eq!(addr1, addr2);
     }
 
+    #[test]
+    fn restore_yolo_key_matches_address_and_refuses_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = yolo_address(dir.path()).unwrap();
+        let key = load_yolo_key(dir.path()).unwrap();
+
+        let dir2 = tempfile::tempdir().unwrap();
+        assert_eq!(restore_yolo_key(dir2.path(), &key).unwrap(), addr);
+        assert_eq!(yolo_address(dir2.path()).unwrap(), addr);
+        assert!(restore_yolo_key(dir2.path(), &key).is_err());
+    }
+
     #[cfg(unix)]
     #[test]
     fn yolo_key_file_permissions() {
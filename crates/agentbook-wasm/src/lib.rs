@@ -0,0 +1,249 @@
+//! Browser client for the node daemon, over the gateway's WebSocket bridge
+//! (`agentbook-gateway`, `GET /v1/ws`).
+//!
+//! Requests are validated against the same [`agentbook::protocol`] types the
+//! daemon uses before they leave the page, so a dashboard gets the wire
+//! format's serde rules for free. Build with
+//! `wasm-pack build crates/agentbook-wasm --target web`.
+//!
+//! ```js
+//! const node = new NodeSocket("ws://127.0.0.1:8420/v1/ws?access_token=…",
+//!                             (event) => console.log(event.kind, event));
+//! const inbox = await node.request({ type: "inbox", unread_only: true });
+//! ```
+
+use agentbook::protocol::{Request, RequestEnvelope, Response, ResponseEnvelope};
+use js_sys::{Function, Promise};
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use web_sys::{CloseEvent, Event, MessageEvent, WebSocket};
+
+/// A decoded line from the gateway.
+#[derive(Debug, PartialEq)]
+enum Incoming {
+    Hello {
+        node_id: String,
+    },
+    Reply {
+        request_id: u64,
+        result: Result<Value, String>,
+    },
+    Event(Value),
+    /// Errors not tied to a request, e.g. an unparseable request line.
+    Unmatched,
+}
+
+/// Wrap a JSON request (`{"type": "inbox", ...}`) in an envelope, rejecting
+/// anything the daemon would not accept.
+fn encode_request(request_id: u64, request_json: &str) -> Result<String, String> {
+    let request: Request =
+        serde_json::from_str(request_json).map_err(|e| format!("invalid request: {e}"))?;
+    let envelope = RequestEnvelope {
+        request_id: Some(request_id),
+        trace_context: None,
+        request,
+    };
+    serde_json::to_string(&envelope).map_err(|e| e.to_string())
+}
+
+fn decode(line: &str) -> Result<Incoming, String> {
+    let envelope: ResponseEnvelope =
+        serde_json::from_str(line).map_err(|e| format!("invalid response: {e}"))?;
+    Ok(match (envelope.request_id, envelope.response) {
+        (_, Response::Hello { node_id, .. }) => Incoming::Hello { node_id },
+        (_, Response::Event { event }) => {
+            Incoming::Event(serde_json::to_value(event).map_err(|e| e.to_string())?)
+        }
+        (Some(request_id), Response::Ok { data }) => Incoming::Reply {
+            request_id,
+            result: Ok(data.unwrap_or(Value::Null)),
+        },
+        (Some(request_id), Response::Error { code, message }) => Incoming::Reply {
+            request_id,
+            result: Err(format!("{code}: {message}")),
+        },
+        (None, _) => Incoming::Unmatched,
+    })
+}
+
+fn to_js(value: &Value) -> JsValue {
+    js_sys::JSON::parse(&value.to_string()).unwrap_or(JsValue::NULL)
+}
+
+type Pending = Rc<RefCell<HashMap<u64, (Function, Function)>>>;
+
+/// A connection to the node through the gateway. Requests return promises;
+/// events are passed to the callback given at construction.
+#[wasm_bindgen]
+pub struct NodeSocket {
+    ws: WebSocket,
+    next_request_id: Cell<u64>,
+    pending: Pending,
+    /// Requests made before the socket opened.
+    queued: Rc<RefCell<Vec<String>>>,
+    node_id: Rc<RefCell<Option<String>>>,
+    _on_open: Closure<dyn FnMut(Event)>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+}
+
+#[wasm_bindgen]
+impl NodeSocket {
+    /// Connect to the gateway's `/v1/ws` URL. Pass the gateway token as an
+    /// `access_token` query parameter if it has one. The gateway must be
+    /// started with `--allow-origin` for the page's origin.
+    #[wasm_bindgen(constructor)]
+    pub fn new(url: &str, on_event: Function) -> Result<NodeSocket, JsValue> {
+        let ws = WebSocket::new(url)?;
+        let pending: Pending = Rc::default();
+        let queued: Rc<RefCell<Vec<String>>> = Rc::default();
+        let node_id: Rc<RefCell<Option<String>>> = Rc::default();
+
+        let on_open = {
+            let ws = ws.clone();
+            let queued = queued.clone();
+            Closure::<dyn FnMut(Event)>::new(move |_| {
+                for line in queued.borrow_mut().drain(..) {
+                    let _ = ws.send_with_str(&line);
+                }
+            })
+        };
+
+        let on_message = {
+            let pending = pending.clone();
+            let node_id = node_id.clone();
+            Closure::<dyn FnMut(MessageEvent)>::new(move |e: MessageEvent| {
+                let Some(line) = e.data().as_string() else {
+                    return;
+                };
+                match decode(&line) {
+                    Ok(Incoming::Hello { node_id: id }) => *node_id.borrow_mut() = Some(id),
+                    Ok(Incoming::Event(event)) => {
+                        let _ = on_event.call1(&JsValue::NULL, &to_js(&event));
+                    }
+                    Ok(Incoming::Reply { request_id, result }) => {
+                        let Some((resolve, reject)) = pending.borrow_mut().remove(&request_id)
+                        else {
+                            return;
+                        };
+                        let _ = match result {
+                            Ok(data) => resolve.call1(&JsValue::NULL, &to_js(&data)),
+                            Err(e) => reject.call1(&JsValue::NULL, &js_sys::Error::new(&e)),
+                        };
+                    }
+                    Ok(Incoming::Unmatched) | Err(_) => {}
+                }
+            })
+        };
+
+        let on_close = {
+            let pending = pending.clone();
+            Closure::<dyn FnMut(CloseEvent)>::new(move |_| {
+                for (_, (_, reject)) in pending.borrow_mut().drain() {
+                    let err = js_sys::Error::new("connection closed");
+                    let _ = reject.call1(&JsValue::NULL, &err);
+                }
+            })
+        };
+
+        ws.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        ws.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        ws.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        Ok(Self {
+            ws,
+            next_request_id: Cell::new(1),
+            pending,
+            queued,
+            node_id,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_close: on_close,
+        })
+    }
+
+    /// Send a request object such as `{ type: "send_dm", to, body }`. The
+    /// promise resolves with the response data or rejects with the daemon's
+    /// error.
+    pub fn request(&self, request: JsValue) -> Result<Promise, JsValue> {
+        let json = js_sys::JSON::stringify(&request)?
+            .as_string()
+            .unwrap_or_default();
+        let request_id = self.next_request_id.get();
+        self.next_request_id.set(request_id + 1);
+        let line = encode_request(request_id, &json).map_err(|e| js_sys::Error::new(&e))?;
+
+        let pending = self.pending.clone();
+        let promise = Promise::new(&mut |resolve, reject| {
+            pending.borrow_mut().insert(request_id, (resolve, reject));
+        });
+        if self.ws.ready_state() == WebSocket::OPEN {
+            self.ws.send_with_str(&line)?;
+        } else {
+            self.queued.borrow_mut().push(line);
+        }
+        Ok(promise)
+    }
+
+    /// The node's id, once the gateway has said hello.
+    #[wasm_bindgen(getter, js_name = nodeId)]
+    pub fn node_id(&self) -> Option<String> {
+        self.node_id.borrow().clone()
+    }
+
+    pub fn close(&self) -> Result<(), JsValue> {
+        self.ws.close()
+    }
+}
+
+impl Drop for NodeSocket {
+    fn drop(&mut self) {
+        self.ws.set_onopen(None);
+        self.ws.set_onmessage(None);
+        self.ws.set_onclose(None);
+        let _ = self.ws.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_checked_and_enveloped() {
+        let line = encode_request(7, r#"{"type":"inbox","unread_only":true}"#).unwrap();
+        let envelope: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(envelope["request_id"], 7);
+        assert_eq!(envelope["type"], "inbox");
+
+        let err = encode_request(8, r#"{"type":"send_dm","to":"@a"}"#).unwrap_err();
+        assert!(err.contains("body"), "{err}");
+    }
+
+    #[test]
+    fn responses_are_routed() {
+        assert_eq!(
+            decode(r#"{"type":"hello","node_id":"0xabc","version":"1"}"#).unwrap(),
+            Incoming::Hello {
+                node_id: "0xabc".to_string()
+            }
+        );
+        assert_eq!(
+            decode(r#"{"request_id":3,"type":"error","code":"not_found","message":"gone"}"#)
+                .unwrap(),
+            Incoming::Reply {
+                request_id: 3,
+                result: Err("not_found: gone".to_string())
+            }
+        );
+        let event =
+            decode(r#"{"type":"event","event":{"kind":"new_follower","node_id":"0x1"}}"#).unwrap();
+        let Incoming::Event(event) = event else {
+            panic!("expected event, got {event:?}");
+        };
+        assert_eq!(event["kind"], "new_follower");
+    }
+}
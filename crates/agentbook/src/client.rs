@@ -1,26 +1,111 @@
-use crate::protocol::{MAX_LINE_BYTES, Request, RequestEnvelope, Response, ResponseEnvelope};
+use crate::protocol::{
+    AuthRequest, MAX_LINE_BYTES, MIN_PROTOCOL_VERSION, Negotiated, PROTOCOL_VERSION, Request,
+    RequestEnvelope, Response, ResponseEnvelope,
+};
 use anyhow::{Context, Result, anyhow, bail};
 use futures_util::{SinkExt, StreamExt};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::UnixStream;
 use tokio_util::codec::{FramedRead, FramedWrite, LinesCodec};
 
-/// Client for the agentbook node daemon's Unix socket API.
+/// Read half of a daemon connection (Unix socket or TLS stream).
+type ReadHalf = Box<dyn AsyncRead + Send + Unpin>;
+/// Write half of a daemon connection (Unix socket or TLS stream).
+type WriteHalf = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Client for the agentbook node daemon's socket API.
 pub struct NodeClient {
-    reader: FramedRead<tokio::net::unix::OwnedReadHalf, LinesCodec>,
-    writer: FramedWrite<tokio::net::unix::OwnedWriteHalf, LinesCodec>,
+    reader: FramedRead<ReadHalf, LinesCodec>,
+    writer: FramedWrite<WriteHalf, LinesCodec>,
     node_id: String,
     next_request_id: u64,
+    protocol: u32,
+    warnings: Vec<String>,
 }
 
 impl NodeClient {
     /// Connect to the node daemon at the given socket path.
-    /// Waits for the Hello response before returning.
+    /// Waits for the Hello response and negotiates the protocol version
+    /// before returning.
     pub async fn connect(socket_path: &Path) -> Result<Self> {
         let stream = UnixStream::connect(socket_path)
             .await
             .with_context(|| format!("failed to connect to {}", socket_path.display()))?;
         let (r, w) = stream.into_split();
+        Self::handshake(Box::new(r), Box::new(w), None).await
+    }
+
+    /// Connect to a daemon started with `--listen-tcp`. The daemon's TLS
+    /// certificate must chain to `ca_cert_pem` (or be it) and be valid for
+    /// `server_name`; `token` is the daemon's API token.
+    pub async fn connect_tcp(
+        addr: &str,
+        server_name: &str,
+        ca_cert_pem: &[u8],
+        token: &str,
+    ) -> Result<Self> {
+        use tokio_rustls::rustls::pki_types::pem::PemObject;
+        use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+        use tokio_rustls::rustls::{self, RootCertStore};
+
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(ca_cert_pem) {
+            roots.add(cert.context("invalid CA certificate")?)?;
+        }
+        let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+        let server_name =
+            ServerName::try_from(server_name.to_string()).context("invalid server name")?;
+
+        let stream = tokio::net::TcpStream::connect(addr)
+            .await
+            .with_context(|| format!("failed to connect to {addr}"))?;
+        let stream = tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect(server_name, stream)
+            .await
+            .context("TLS handshake failed")?;
+        let (r, w) = tokio::io::split(stream);
+        Self::handshake(Box::new(r), Box::new(w), Some(token)).await
+    }
+
+    /// Connect read-only to another user's node through its view socket,
+    /// with a token from `agentbook share`. The token names the uid the node
+    /// runs as, and is only sent if that user is the one listening: anyone
+    /// can bind an abstract socket name.
+    #[cfg(target_os = "linux")]
+    pub async fn connect_view(node_id: &str, token: &str) -> Result<Self> {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::SocketAddr;
+
+        let owner_uid: u32 = token
+            .split_once('.')
+            .and_then(|(uid, _)| uid.parse().ok())
+            .context("malformed share token")?;
+        let addr = SocketAddr::from_abstract_name(view_socket_name(node_id))?;
+        let stream = std::os::unix::net::UnixStream::connect_addr(&addr)
+            .with_context(|| format!("no shared view for node {node_id}"))?;
+        stream.set_nonblocking(true)?;
+        let stream = UnixStream::from_std(stream)?;
+        let peer_uid = stream.peer_cred()?.uid();
+        if peer_uid != owner_uid {
+            bail!(
+                "view socket for node {node_id} is held by uid {peer_uid}, not the sharing \
+                 user (uid {owner_uid}); not sending the share token"
+            );
+        }
+        let (r, w) = stream.into_split();
+        Self::handshake(Box::new(r), Box::new(w), Some(token)).await
+    }
+
+    /// Authenticate if `token` is given, wait for the Hello, then agree on
+    /// the highest protocol version both sides speak.
+    async fn handshake(r: ReadHalf, w: WriteHalf, token: Option<&str>) -> Result<Self> {
         let reader = FramedRead::new(r, LinesCodec::new_with_max_length(MAX_LINE_BYTES));
         let writer = FramedWrite::new(w, LinesCodec::new_with_max_length(MAX_LINE_BYTES));
 
@@ -29,15 +114,56 @@ impl NodeClient {
             writer,
             node_id: String::new(),
             next_request_id: 1,
+            protocol: MIN_PROTOCOL_VERSION,
+            warnings: Vec::new(),
         };
 
-        match client.next_response_envelope().await?.response {
-            Response::Hello { node_id, .. } => {
+        if let Some(token) = token {
+            let auth = AuthRequest {
+                token: token.to_string(),
+            };
+            client.writer.send(serde_json::to_string(&auth)?).await?;
+        }
+
+        let protocol_max = match client.next_response_envelope().await?.response {
+            Response::Hello {
+                node_id,
+                protocol_max,
+                warnings,
+                ..
+            } => {
                 client.node_id = node_id;
-                Ok(client)
+                client.warnings = warnings;
+                protocol_max
             }
-            other => Err(anyhow!("expected Hello, got {other:?}")),
+            Response::Error { message, .. } => bail!("{message}"),
+            other => return Err(anyhow!("expected Hello, got {other:?}")),
+        };
+
+        // Daemons that predate negotiation only speak version 1; don't send
+        // them a request they would reject.
+        if protocol_max > MIN_PROTOCOL_VERSION {
+            let request = Request::Negotiate {
+                min: MIN_PROTOCOL_VERSION,
+                max: PROTOCOL_VERSION,
+            };
+            let data = client.request(request).await?;
+            let negotiated: Negotiated = serde_json::from_value(data.unwrap_or_default())
+                .context("invalid negotiate response")?;
+            client.protocol = negotiated.protocol;
+            client.warnings = negotiated.warnings;
         }
+        Ok(client)
+    }
+
+    /// Protocol version this connection runs at.
+    pub fn protocol(&self) -> u32 {
+        self.protocol
+    }
+
+    /// Deprecation notices from the daemon for this connection.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
     }
 
     /// The node ID received from the Hello handshake.
@@ -51,6 +177,7 @@ impl NodeClient {
         self.next_request_id = self.next_request_id.saturating_add(1);
         let line = serde_json::to_string(&RequestEnvelope {
             request_id: Some(request_id),
+            trace_context: crate::trace::current_context(),
             request: req,
         })?;
         self.writer.send(line).await?;
@@ -97,6 +224,7 @@ impl NodeClient {
                 writer: self.writer,
                 node_id: self.node_id,
                 next_request_id: self.next_request_id,
+                protocol: self.protocol,
             },
             NodeReader {
                 reader: self.reader,
@@ -107,9 +235,10 @@ impl NodeClient {
 
 /// Write half of a split [`NodeClient`]. Sends requests to the daemon.
 pub struct NodeWriter {
-    writer: FramedWrite<tokio::net::unix::OwnedWriteHalf, LinesCodec>,
+    writer: FramedWrite<WriteHalf, LinesCodec>,
     node_id: String,
     next_request_id: u64,
+    protocol: u32,
 }
 
 impl NodeWriter {
@@ -117,6 +246,11 @@ impl NodeWriter {
         &self.node_id
     }
 
+    /// Protocol version the connection runs at.
+    pub fn protocol(&self) -> u32 {
+        self.protocol
+    }
+
     pub async fn send(&mut self, req: Request) -> Result<()> {
         let _ = self.send_with_id(req).await?;
         Ok(())
@@ -127,6 +261,7 @@ impl NodeWriter {
         self.next_request_id = self.next_request_id.saturating_add(1);
         let line = serde_json::to_string(&RequestEnvelope {
             request_id: Some(request_id),
+            trace_context: crate::trace::current_context(),
             request: req,
         })?;
         self.writer.send(line).await?;
@@ -136,7 +271,7 @@ impl NodeWriter {
 
 /// Read half of a split [`NodeClient`]. Yields all responses including events.
 pub struct NodeReader {
-    reader: FramedRead<tokio::net::unix::OwnedReadHalf, LinesCodec>,
+    reader: FramedRead<ReadHalf, LinesCodec>,
 }
 
 impl NodeReader {
@@ -228,6 +363,12 @@ impl AgentClient {
     }
 }
 
+/// Abstract socket name of a node's read-only view listener (see
+/// `Request::Share`).
+pub fn view_socket_name(node_id: &str) -> String {
+    format!("agentbook-view-{node_id}")
+}
+
 /// Discover the default socket path.
 ///
 /// Checks `$AGENTBOOK_SOCKET` env, then falls back to
@@ -1,7 +1,9 @@
 pub mod agent_protocol;
+#[cfg(feature = "client")]
 pub mod client;
 pub mod gateway;
 pub mod protocol;
+pub mod trace;
 
 /// Default relay host for NAT traversal and username directory.
 pub const DEFAULT_RELAY_HOST: &str = "agentbook.ardabot.ai:50100";
@@ -1,9 +1,44 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 
 /// Maximum size of a JSON-lines frame on the Unix socket (64 KiB).
 pub const MAX_LINE_BYTES: usize = 64 * 1024;
 
+/// Newest socket protocol version this build speaks.
+///
+/// - 1: the original protocol. Every connection starts here, so clients that
+///   never send [`Request::Negotiate`] keep working unchanged.
+/// - 2: a request whose `type` the daemon does not know is answered with
+///   `unknown_request` instead of `invalid_request`, so a client can tell a
+///   daemon too old for a request from a malformed one.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Oldest socket protocol version this build still serves.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// The highest version both sides support, if the ranges overlap.
+pub fn negotiate_protocol(min: u32, max: u32) -> Option<u32> {
+    let version = max.min(PROTOCOL_VERSION);
+    (version >= min.max(MIN_PROTOCOL_VERSION)).then_some(version)
+}
+
+/// Warnings to show a client running at `version`.
+pub fn protocol_warnings(version: u32) -> Vec<String> {
+    if version < PROTOCOL_VERSION {
+        vec![format!(
+            "socket protocol {version} is deprecated; this daemon speaks up to {PROTOCOL_VERSION}, \
+             update the client so it negotiates a newer version"
+        )]
+    } else {
+        Vec::new()
+    }
+}
+
+fn protocol_v1() -> u32 {
+    1
+}
+
 // ---------------------------------------------------------------------------
 // Typed enums for wire format safety
 // ---------------------------------------------------------------------------
@@ -52,12 +87,89 @@ pub enum Request {
     Identity,
     /// Get health status.
     Health,
+    /// Check that a node is reachable: send it a signed ping on each path
+    /// and wait for the pong. Only nodes that follow us answer. Fails only
+    /// when no path does.
+    Ping {
+        to: String,
+        /// How long to wait for the pong (default 10s).
+        #[serde(default)]
+        timeout_ms: Option<u64>,
+    },
+    /// Traffic this node sent through each relay, as the relays account it.
+    RelayUsage {
+        /// Only windows ending after this time; all the relay keeps if unset.
+        #[serde(default)]
+        since_ms: Option<u64>,
+    },
+    /// Agree on the socket protocol version for this connection: the highest
+    /// version in `min..=max` the daemon supports. Answered with
+    /// [`Negotiated`], or `unsupported_protocol` if there is none.
+    Negotiate { min: u32, max: u32 },
+    /// Make this connection the inbox gateway: from now on, each message
+    /// accepted into the inbox (DMs, feed posts, room messages) is also
+    /// pushed to it in full as a [`Event::MessageReceived`], so a local agent
+    /// can react without polling `Inbox`. A later claim by another
+    /// connection takes over; the claim ends when the connection closes.
+    /// Socket and TCP connections only.
+    ClaimInbox,
+    /// Recent daemon decisions: ingress accepts and rejects, relay
+    /// reconnects and delivery attempts, oldest first. Poll with the last
+    /// `seq` seen as `after_seq` to follow new entries.
+    DaemonLog {
+        #[serde(default)]
+        after_seq: Option<u64>,
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// Move the node to a new key pair. Follows and followers are notified
+    /// with a notice signed by the old key; the new key takes effect on the
+    /// next node start, and messages to the old key are still accepted for
+    /// `grace_ms` after that.
+    RotateKey {
+        #[serde(default)]
+        grace_ms: Option<u64>,
+    },
+    /// Current ingress rate budgets and per-peer usage, for debugging
+    /// rejected deliveries. Limited to one sender when `node_id` is set.
+    IngressStats {
+        #[serde(default)]
+        node_id: Option<String>,
+    },
+    /// The inbox retention policy and what the janitor has pruned so far.
+    RetentionStats,
+    /// Show the runtime-adjustable settings currently in effect.
+    Config,
+    /// Change runtime settings without restarting. Omitted fields are left
+    /// alone; the result is saved to `node_config.json` so it also applies
+    /// after a restart or SIGHUP reload.
+    ConfigSet {
+        #[serde(default)]
+        log_level: Option<String>,
+        #[serde(default)]
+        ingress_budgets: Vec<IngressBudget>,
+    },
 
     // -- Follow graph --
     /// Follow a node by node_id/wallet address or @username.
     Follow { target: String },
     /// Unfollow a node.
     Unfollow { target: String },
+    /// Give a followed node a local alias, usable as a target in place of
+    /// its node ID; `None` clears it. Aliases are lowercase `a-z0-9-_`.
+    SetAlias {
+        target: String,
+        #[serde(default)]
+        alias: Option<String>,
+    },
+    /// Put a followed node in a local group, e.g. `team-infra`. Routes and
+    /// `BroadcastDm` can then address the whole group. Group names follow
+    /// the alias rules.
+    GroupAdd { group: String, target: String },
+    /// Take a followed node out of a group.
+    GroupRemove { group: String, target: String },
+    /// List groups and their members.
+    Groups,
     /// Block a node.
     Block { target: String },
     /// List nodes we follow.
@@ -65,6 +177,27 @@ pub enum Request {
     /// List nodes that follow us (known followers).
     Followers,
 
+    // -- Invites --
+    /// Issue a signed invite token. Redeeming it makes us follow the invitee back.
+    InviteCreate {
+        /// Lifetime, at most a year. Defaults to 7 days.
+        #[serde(default)]
+        ttl_ms: Option<u64>,
+        /// Maximum number of nodes that may redeem it (1 = single-use).
+        #[serde(default)]
+        max_uses: Option<u32>,
+        /// What redeemers may send us once followed back: `dm`, `feed`.
+        /// Empty grants everything.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        scopes: Vec<String>,
+    },
+    /// List invites we have issued.
+    InviteList,
+    /// Revoke an issued invite so it can no longer be redeemed.
+    InviteRevoke { token_id: String },
+    /// Accept an invite token: follow the inviter and notify them.
+    InviteAccept { token: String },
+
     // -- Username directory --
     /// Register a username on the relay host.
     RegisterUsername { username: String },
@@ -75,9 +208,44 @@ pub enum Request {
 
     // -- Messaging --
     /// Send a DM to a mutual follow by node_id/wallet address or @username.
-    SendDm { to: String, body: String },
+    SendDm {
+        to: String,
+        body: String,
+        /// Small files sent along, end-to-end encrypted with the body.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        attachments: Vec<Attachment>,
+        /// ID of a DM in the inbox this one replies to.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        in_reply_to: Option<String>,
+        /// Expect the recipient to reply within this many seconds; if they
+        /// don't, a `ReplyOverdue` event is emitted.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reply_within_secs: Option<u64>,
+    },
+    /// DM the same body to every node we follow, or with `mutual_only` to
+    /// those that also follow us. Each recipient gets its own DM, sent or
+    /// queued like `SendDm`; the result lists one [`BroadcastDelivery`] per
+    /// recipient.
+    BroadcastDm {
+        body: String,
+        #[serde(default)]
+        mutual_only: bool,
+        /// Only DM members of this group.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+    },
     /// Post to feed (encrypted per-follower).
     PostFeed { body: String },
+    /// Send a message built from one of the `templates` in
+    /// `node_config.json`, filling its placeholders from `vars`. `to` is
+    /// the recipient of a `dm_text` template and must be unset otherwise.
+    SendTemplate {
+        template: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        to: Option<String>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        vars: BTreeMap<String, String>,
+    },
     /// List inbox messages.
     Inbox {
         #[serde(default)]
@@ -87,6 +255,15 @@ pub enum Request {
     },
     /// Acknowledge (mark as read) a message.
     InboxAck { message_id: String },
+    /// A DM conversation: the message that started it and every reply,
+    /// oldest first.
+    MessageThread { thread_id: String },
+    /// Sent DMs still waiting for the reply they asked for.
+    PendingReplies,
+    /// List DMs queued for retry after a failed relay send.
+    OutboxList,
+    /// Cancel a queued DM so it is never retried.
+    OutboxCancel { message_id: String },
 
     // -- Wallet --
     /// Get wallet info and balances.
@@ -176,6 +353,35 @@ pub enum Request {
     SyncPull { confirm: bool },
 
     // -- Daemon lifecycle --
+    /// List the socket and TCP clients connected to the daemon.
+    Clients,
+    /// Close a client's connection, e.g. one that is stuck.
+    Disconnect { client_id: u64 },
+    /// Refuse every request that sends, changes state or moves funds until
+    /// `Unfreeze`, so a misbehaving agent can be paused and inspected. Reads,
+    /// `Clients`, `Drain` and `Shutdown` still work, and inbound messages are
+    /// still received.
+    Freeze,
+    /// Undo `Freeze`.
+    Unfreeze,
+    /// Issue a token that lets any local user connect read-only to the
+    /// node's view socket, for `ttl_secs` (default an hour).
+    Share {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ttl_secs: Option<u64>,
+    },
+    /// Revoke every share token and disconnect their viewers.
+    Unshare,
+    /// Rewrite the encrypted state files (follow store, inbox) with fresh
+    /// encryption, finishing migration of any plaintext left over.
+    ReencryptState,
+    /// Stop accepting new requests, wait up to `timeout_ms` (default 5
+    /// minutes) for in-flight requests and queued outbound messages to
+    /// finish, persist state, then shut down.
+    Drain {
+        #[serde(default)]
+        timeout_ms: Option<u64>,
+    },
     /// Shut down the daemon.
     Shutdown,
 }
@@ -188,10 +394,26 @@ pub enum Request {
 pub struct RequestEnvelope {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub request_id: Option<u64>,
+    /// W3C trace context (`traceparent`/`tracestate`) of the caller, so the
+    /// daemon's request span joins the caller's trace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_context: Option<TraceContext>,
     #[serde(flatten)]
     pub request: Request,
 }
 
+/// First line a client sends on the daemon's TCP listener. The daemon
+/// answers with `Hello` if the token matches, or an `unauthorized` error
+/// before closing. Unix socket clients skip this; file permissions guard
+/// the socket instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthRequest {
+    pub token: String,
+}
+
+/// Propagation headers carried in [`RequestEnvelope::trace_context`].
+pub type TraceContext = std::collections::HashMap<String, String>;
+
 // ---------------------------------------------------------------------------
 // Response
 // ---------------------------------------------------------------------------
@@ -200,8 +422,21 @@ pub struct RequestEnvelope {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Response {
-    /// Connection established.
-    Hello { node_id: String, version: String },
+    /// Connection established. The connection runs at protocol version 1
+    /// until the client sends [`Request::Negotiate`].
+    Hello {
+        node_id: String,
+        version: String,
+        /// Protocol versions the daemon supports. Daemons from before
+        /// negotiation omit these and speak only version 1.
+        #[serde(default = "protocol_v1")]
+        protocol_min: u32,
+        #[serde(default = "protocol_v1")]
+        protocol_max: u32,
+        /// Deprecation notices for this connection, e.g. an old protocol.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+    },
     /// Request succeeded with optional data.
     Ok { data: Option<serde_json::Value> },
     /// Request failed.
@@ -244,6 +479,27 @@ pub enum Event {
     },
     /// A new follower detected.
     NewFollower { node_id: String },
+    /// A followed node moved to a new key; the follow now points at `new_node_id`.
+    KeyRotated {
+        old_node_id: String,
+        new_node_id: String,
+    },
+    /// A followed node revoked its key and was unfollowed.
+    KeyRevoked { node_id: String },
+    /// A peer that had been messaging this node went quiet for
+    /// `idle_after_secs` (see `NodeConfig`).
+    PeerIdle { node_id: String, idle_ms: u64 },
+    /// An idle peer sent a message again.
+    PeerActive { node_id: String },
+    /// A DM sent with `reply_within_secs` got no reply in time.
+    ReplyOverdue {
+        message_id: String,
+        to: String,
+        deadline_ms: u64,
+    },
+    /// A message accepted into the inbox, in full. Only sent to the
+    /// connection that sent `ClaimInbox`.
+    MessageReceived { message: Box<InboxEntry> },
 }
 
 // ---------------------------------------------------------------------------
@@ -264,6 +520,20 @@ pub struct FollowInfo {
     pub node_id: String,
     pub username: Option<String>,
     pub followed_at_ms: u64,
+    /// Local name set with `SetAlias`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Groups set with `GroupAdd`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+}
+
+/// A group returned by the `Groups` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupInfo {
+    pub name: String,
+    /// Node IDs of the members.
+    pub members: Vec<String>,
 }
 
 /// A message record returned by the `Inbox` request.
@@ -280,6 +550,279 @@ pub struct InboxEntry {
     pub acked: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub room: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+}
+
+/// A file attached to a DM. At most 8 per message and 40 KiB decoded in
+/// total.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// File name, without any directory part.
+    pub name: String,
+    /// MIME type, e.g. `application/json`.
+    pub mime: String,
+    /// Contents, standard base64.
+    pub data_b64: String,
+}
+
+/// A queued outbound message returned by the `OutboxList` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxInfo {
+    pub message_id: String,
+    pub to_node_id: String,
+    pub attempts: u32,
+    pub created_at_ms: u64,
+    pub next_attempt_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// An issued invite returned by the `InviteList` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteInfo {
+    pub token_id: String,
+    pub created_at_ms: u64,
+    pub expires_at_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+    /// Node IDs that redeemed the invite.
+    pub redeemed_by: Vec<String>,
+    pub revoked: bool,
+    /// Scopes granted to redeemers. Empty means unrestricted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+}
+
+/// Result of a `RotateKey` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRotationInfo {
+    pub old_node_id: String,
+    pub new_node_id: String,
+    pub new_public_key_b64: String,
+    /// Until when messages addressed to the old node id are accepted.
+    pub grace_until_ms: u64,
+    /// Number of peers the rotation notice was sent or queued to.
+    pub notified: usize,
+}
+
+/// Result of a `Drain` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainResult {
+    /// Whether everything finished before the timeout.
+    pub drained: bool,
+    /// Requests still running when the drain ended.
+    pub in_flight: usize,
+    /// Outbound messages still queued when the drain ended.
+    pub outbox_pending: usize,
+}
+
+/// Runtime settings, as stored in `node_config.json` and returned by
+/// `Config` and `ConfigSet`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeConfig {
+    /// `tracing` filter directives, e.g. `debug` or `agentbook_node=trace`.
+    /// Unset means `RUST_LOG` or the built-in default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+    /// Ingress budgets overriding the defaults, one entry per message class.
+    #[serde(default)]
+    pub ingress_budgets: Vec<IngressBudget>,
+    /// Endpoints that get each matching event as a JSON POST.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookConfig>,
+    /// Limits on how much of the inbox is kept. Unset fields are unlimited.
+    #[serde(default, skip_serializing_if = "RetentionPolicy::is_unlimited")]
+    pub retention: RetentionPolicy,
+    /// Refuse to send or accept anything that is not end-to-end encrypted:
+    /// open rooms cannot be joined or sent to, and plaintext room messages
+    /// are dropped. DMs and feed posts are always encrypted.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_encryption: bool,
+    /// Emit `PeerIdle` when a peer that has messaged this node is quiet for
+    /// this long. Unset turns idle detection off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_after_secs: Option<u64>,
+    /// What to do with inbound DMs and room messages, by room name or
+    /// body text. The first rule that matches wins; messages no rule
+    /// matches are just stored.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<RouteRule>,
+    /// Canned outgoing messages, sent by name with `SendTemplate`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<MessageTemplate>,
+}
+
+/// One routing rule for inbound messages. A rule needs at least one of
+/// `topic`, `contains` and `group`, and matches only messages that satisfy
+/// every field it sets.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteRule {
+    /// Room name to match. `*` matches any run of characters, so
+    /// `escalation*` covers `escalation` and `escalation-db`. Unset, the
+    /// rule applies to DMs as well as every room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// Text the message body must contain, e.g. `panicked at`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contains: Option<String>,
+    /// Group the sender must be in (see `GroupAdd`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(flatten)]
+    pub action: RouteAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RouteAction {
+    /// Discard the message without storing it.
+    Drop,
+    /// Store the message and also send a copy as a DM to `to` (a node ID or
+    /// `@username`; mutual follow required). DMs from `to` itself are not
+    /// sent back to it.
+    Forward { to: String },
+    /// Store the message and freeze the node (see `Request::Freeze`).
+    Freeze,
+}
+
+/// A named outgoing message. `body` may hold `{name}` placeholders, each
+/// of which must be listed in `required_vars`; `{{` and `}}` stand for
+/// literal braces.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageTemplate {
+    pub name: String,
+    /// `dm_text`, `feed_post` or `room_message`.
+    pub message_type: MessageType,
+    pub body: String,
+    /// Room a `room_message` template is sent to. Not allowed for the
+    /// other types.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// Variables the sender must supply, and the only ones it may.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_vars: Vec<String>,
+}
+
+/// How long and how much of the inbox to keep. A background janitor prunes
+/// whatever falls outside it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Drop messages older than this, read or not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_secs: Option<u64>,
+    /// Keep at most this many messages; read ones go first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<usize>,
+    /// Keep the messages under this many bytes of JSON; read ones go first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+}
+
+impl RetentionPolicy {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Result of a `Negotiate` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Negotiated {
+    /// Protocol version the connection now runs at.
+    pub protocol: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Result of a `RetentionStats` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionStats {
+    pub policy: RetentionPolicy,
+    pub inbox_messages: usize,
+    pub inbox_bytes: u64,
+    /// Messages pruned since the node started, by the limit that pruned them.
+    pub pruned_by_age: u64,
+    pub pruned_by_count: u64,
+    pub pruned_by_bytes: u64,
+    /// When the janitor last ran.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run_ms: Option<u64>,
+}
+
+/// One webhook: where to POST events, which ones, and how to sign them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// `http://` or `https://` URL.
+    pub url: String,
+    /// Event kinds to send (`new_message`, `new_follower`, ...). Empty means
+    /// all of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<String>,
+    /// HMAC-SHA256 key; when set, each POST carries an
+    /// `X-Agentbook-Signature: sha256=<hex>` header over the body.
+    /// `Config` shows it as `"redacted"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// Where to fetch `secret` from instead of writing it into
+    /// `node_config.json`. Resolved each time the config is applied.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_ref: Option<SecretRef>,
+}
+
+/// A secret kept outside the config file, fetched by the daemon when it
+/// applies the config. The value is never logged or returned by `Config`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum SecretRef {
+    /// Contents of a file, minus a trailing newline.
+    File { path: String },
+    /// An environment variable of the daemon.
+    Env { var: String },
+    /// Standard output of a command (argv, no shell), minus a trailing
+    /// newline, e.g. `["pass", "show", "agentbook/webhook"]`.
+    Exec { command: Vec<String> },
+    /// A field of a HashiCorp Vault KV v2 secret, read with the token in
+    /// the `token_env` environment variable (default `VAULT_TOKEN`).
+    Vault {
+        /// Full secret URL, e.g. `https://vault:8200/v1/secret/data/agentbook`.
+        url: String,
+        field: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token_env: Option<String>,
+    },
+}
+
+/// Result of an `IngressStats` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngressStats {
+    pub budgets: Vec<IngressBudget>,
+    pub peers: Vec<PeerIngressUsage>,
+}
+
+/// Token bucket applied to each sender for one message class.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngressBudget {
+    /// `dm`, `feed`, `key_notice`, `ping` or `other`.
+    pub message_class: String,
+    pub capacity: u32,
+    pub per_second: f64,
+}
+
+/// One sender's remaining budget for one message class.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerIngressUsage {
+    pub node_id: String,
+    pub message_class: String,
+    pub tokens: f64,
+    /// Consecutive rejections since the last accepted delivery.
+    pub violations: u32,
+    pub times_banned: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banned_for_ms: Option<u64>,
 }
 
 /// Username lookup result.
@@ -290,6 +833,82 @@ pub struct UsernameLookup {
     pub public_key_b64: String,
 }
 
+/// Result of a `Ping`, with each path to the node probed separately.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PingResult {
+    pub node_id: String,
+    /// Through the relays we are attached to.
+    pub relay: PathProbe,
+    /// Over a direct connection to the node.
+    pub direct: PathProbe,
+}
+
+/// How one path fared in a `Ping`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum PathProbe {
+    /// The pong came back; `rtt_ms` from sending the ping to receiving it.
+    Reachable { rtt_ms: u64 },
+    /// The ping could not be sent this way, or got no answer in time.
+    Unavailable { reason: String },
+}
+
+/// Traffic this node relayed in one window, as counted by the relay.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageWindow {
+    pub start_ms: u64,
+    pub frames: u64,
+    pub bytes: u64,
+}
+
+/// One relay's usage report for this node, returned by `RelayUsage`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayUsage {
+    pub host: String,
+    /// Length of each window.
+    pub window_ms: u64,
+    pub total_frames: u64,
+    pub total_bytes: u64,
+    pub windows: Vec<UsageWindow>,
+}
+
+/// What a [`DaemonLogEntry`] records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DaemonLogKind {
+    /// An inbound message passed ingress checks.
+    IngressAccepted,
+    /// An inbound message was dropped by ingress checks.
+    IngressRejected,
+    /// A relay session failed and is being re-established.
+    RelayReconnect,
+    /// An outbound message was handed to the relay.
+    DeliverySent,
+    /// An outbound message was put in the outbox instead of sent.
+    DeliveryQueued,
+    /// A retry of a queued message failed.
+    DeliveryFailed,
+}
+
+/// One entry in the daemon log, returned by `DaemonLog`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonLogEntry {
+    /// Increases by one per entry since the daemon started.
+    pub seq: u64,
+    pub at_ms: u64,
+    pub kind: DaemonLogKind,
+    /// The peer the message came from or was addressed to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<String>,
+    /// The relay, for `relay_reconnect`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
 /// Health status.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthStatus {
@@ -297,6 +916,29 @@ pub struct HealthStatus {
     pub relay_connected: bool,
     pub following_count: usize,
     pub unread_count: usize,
+    /// Socket and TCP clients currently connected.
+    #[serde(default)]
+    pub clients: usize,
+    /// Whether the node is frozen (see `Request::Freeze`).
+    #[serde(default)]
+    pub frozen: bool,
+    #[serde(default)]
+    pub uptime_secs: u64,
+    /// Resident memory; unknown off Linux.
+    #[serde(default)]
+    pub rss_bytes: Option<u64>,
+    /// Open file descriptors; unknown off Linux.
+    #[serde(default)]
+    pub open_fds: Option<usize>,
+    /// DMs waiting in the outbox for a retry.
+    #[serde(default)]
+    pub outbox_depth: usize,
+    /// Events queued for the slowest subscriber.
+    #[serde(default)]
+    pub event_backlog: usize,
+    /// Events dropped because a subscriber fell behind, since startup.
+    #[serde(default)]
+    pub events_lagged: u64,
 }
 
 /// Wallet info returned by `WalletBalance`.
@@ -342,6 +984,59 @@ pub struct RoomInfo {
     pub secure: bool,
 }
 
+/// A connected client, returned by `Clients`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientInfo {
+    pub client_id: u64,
+    /// `socket`, `tcp` or `view` (see `Request::Share`).
+    pub transport: String,
+    /// Peer uid for socket clients, address for TCP clients.
+    pub peer: String,
+    pub role: String,
+    pub connected_at_ms: u64,
+    /// Requests answered on this connection so far.
+    pub requests: u64,
+    /// Whether this connection holds the inbox (`ClaimInbox`).
+    #[serde(default)]
+    pub inbox_gateway: bool,
+}
+
+/// One recipient's copy of a `BroadcastDm`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BroadcastDelivery {
+    pub node_id: String,
+    /// Set when the DM was sent or queued.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// Whether it is waiting in the outbox.
+    #[serde(default)]
+    pub queued: bool,
+    /// Why it could not be sent at all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A read-only share token, returned by `Share`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareInfo {
+    pub node_id: String,
+    /// `<uid>.<secret>`: viewers only send it to a view socket held by that
+    /// uid.
+    pub token: String,
+    pub expires_at_ms: u64,
+}
+
+/// A sent DM waiting for a reply, returned by `PendingReplies`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingReply {
+    pub message_id: String,
+    /// Recipient node ID; only a reply from this node counts.
+    pub to: String,
+    pub deadline_ms: u64,
+    /// Whether the deadline has passed (and `ReplyOverdue` was emitted).
+    pub overdue: bool,
+}
+
 /// Result of a sync-push or sync-pull operation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncResult {
@@ -354,6 +1049,7 @@ pub struct SyncResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn room_request_serde_round_trip() {
@@ -388,6 +1084,7 @@ mod tests {
     fn request_envelope_round_trip() {
         let req = RequestEnvelope {
             request_id: Some(42),
+            trace_context: None,
             request: Request::Health,
         };
         let json = serde_json::to_string(&req).unwrap();
@@ -423,6 +1120,27 @@ mod tests {
         assert!(matches!(decoded.response, Response::Ok { data: None }));
     }
 
+    #[test]
+    fn protocol_negotiation_picks_highest_common_version() {
+        assert_eq!(negotiate_protocol(1, 1), Some(1));
+        assert_eq!(negotiate_protocol(1, 99), Some(PROTOCOL_VERSION));
+        assert_eq!(negotiate_protocol(PROTOCOL_VERSION + 1, 99), None);
+        assert!(protocol_warnings(PROTOCOL_VERSION).is_empty());
+        assert_eq!(protocol_warnings(1).len(), 1);
+
+        // A Hello from a daemon that predates negotiation.
+        let hello: Response =
+            serde_json::from_str(r#"{"type":"hello","node_id":"0xa","version":"0.1.0"}"#).unwrap();
+        assert!(matches!(
+            hello,
+            Response::Hello {
+                protocol_min: 1,
+                protocol_max: 1,
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn inbox_entry_room_field_skips_none() {
         let entry = InboxEntry {
@@ -435,6 +1153,9 @@ mod tests {
             acked: false,
             message_type: MessageType::FeedPost,
             room: None,
+            attachments: Vec::new(),
+            in_reply_to: None,
+            thread_id: None,
         };
         let json = serde_json::to_string(&entry).unwrap();
         assert!(!json.contains("\"room\""));
@@ -458,4 +1179,19 @@ mod tests {
         assert_eq!(decoded.room, "secret");
         assert!(decoded.secure);
     }
+
+    proptest! {
+        #[test]
+        fn response_decoding_never_panics(line in any::<String>()) {
+            let _ = serde_json::from_str::<ResponseEnvelope>(&line);
+            let _ = serde_json::from_str::<RequestEnvelope>(&line);
+        }
+
+        #[test]
+        fn unknown_response_type_is_an_error(ty in "[a-z_]{1,24}") {
+            prop_assume!(!["ok", "error", "event", "hello"].contains(&ty.as_str()));
+            let line = format!(r#"{{"request_id":1,"type":"{ty}"}}"#);
+            prop_assert!(serde_json::from_str::<ResponseEnvelope>(&line).is_err());
+        }
+    }
 }
@@ -0,0 +1,26 @@
+//! Trace context propagation for socket requests.
+//!
+//! With the `otel` feature, [`current_context`] captures the current
+//! `tracing` span's OpenTelemetry context so the daemon can continue the
+//! caller's trace. Without it, requests carry no trace context.
+
+use crate::protocol::TraceContext;
+
+/// Trace context of the current span, if there is an active trace.
+#[cfg(feature = "otel")]
+pub fn current_context() -> Option<TraceContext> {
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let cx = tracing::Span::current().context();
+    let mut carrier = TraceContext::new();
+    TraceContextPropagator::new().inject_context(&cx, &mut carrier);
+    (!carrier.is_empty()).then_some(carrier)
+}
+
+/// Trace context of the current span, if there is an active trace.
+#[cfg(not(feature = "otel"))]
+pub fn current_context() -> Option<TraceContext> {
+    None
+}